@@ -1,12 +1,15 @@
+use std::fs;
+use std::io;
 use std::path::PathBuf;
 use clap::{Command, Arg, ArgMatches, crate_version};
 
 use sphinx::frontend;
+use sphinx::frontend::json;
 use sphinx::{BuildErrors, build_module};
 use sphinx::source::ModuleSource;
+use sphinx::codegen::CompiledProgram;
+use sphinx::runtime::module::SOURCE_EXT;
 use sphinx::runtime::strings::StringInterner;
-use sphinx::debug::symbol::DebugSymbolResolver;
-use sphinx::debug::dasm::Disassembler;
 
 fn main() {
     env_logger::init();
@@ -26,11 +29,19 @@ fn main() {
             .short('P')
             .help("Parse and print AST instead of dissassembly")
         )
+        .arg(
+            Arg::new("format")
+            .long("format")
+            .help("output format of the AST printed with -P")
+            .value_name("FORMAT")
+            .possible_values(["debug", "json"])
+            .requires("parse_only")
+        )
         .arg(
             Arg::new("bytecode")
             .short('d')
-            .help("disassemble compiled bytecode (not implemented yet)")
-            .value_name("CMD")
+            .help("disassemble a compiled bytecode file")
+            .value_name("FILE")
         )
         .arg(
             Arg::new("cmd")
@@ -50,15 +61,19 @@ fn main() {
     } else if let Some(s) = args.value_of("source") {
         source = ModuleSource::File(PathBuf::from(s));
         name = s;
-    } else if let Some(_s) = args.value_of("bytecode") {
-        // TODO read compiled bytecode and go directly to disassembly
-        unimplemented!()
+    } else if let Some(s) = args.value_of("bytecode") {
+        println!("\nSphinx Version {}\n", version);
+        disassemble_compiled(s);
+        return;
     } else {
         println!("No input.");
         return;
     }
     
-    println!("\nSphinx Version {}\n", version);
+    // the JSON output is meant to be read by other programs, so nothing else is printed with it
+    if args.value_of("format") != Some("json") {
+        println!("\nSphinx Version {}\n", version);
+    }
 
     if args.is_present("parse_only") {
         parse_and_print_ast(&args, name, &source);
@@ -88,25 +103,34 @@ fn main() {
     }
     
     let build = build_result.unwrap();
-    let symbols = build.symbols.values().flat_map(|table| table.symbols());
-    let symbol_table = source.resolve_symbols(symbols);
     
-    let dasm = {
-        let dasm = Disassembler::new(&build.program)
-            .with_symbols(&build.symbols);
+    println!("== \"{}\" ==", name);
+    frontend::print_disassembly(&build, Some(&source));
+}
         
-        if let Ok(ref symbol_table) = symbol_table {
-            dasm.with_symbol_table(symbol_table)
-        } else {
-            dasm
+fn disassemble_compiled(name: &str) {
+    let path = PathBuf::from(name);
+    let read_result = fs::File::open(&path)
+        .and_then(|file| CompiledProgram::read_from(io::BufReader::new(file)));
+    
+    let build = match read_result {
+        Ok(build) => build,
+        Err(error) => {
+            println!("Error reading bytecode: {}.", error);
+            return;
         }
     };
     
+    // debug symbols can only be resolved if the source file is unchanged
+    let source = ModuleSource::File(path.with_extension(SOURCE_EXT));
+    let source_hash = source.source_hash().ok();
+    let has_source = build.source_hash.is_some() && build.source_hash == source_hash;
+    
     println!("== \"{}\" ==", name);
-    println!("{}", dasm);
+    frontend::print_disassembly(&build, has_source.then_some(&source));
 }
 
-fn parse_and_print_ast(_args: &ArgMatches, name: &str, source: &ModuleSource) {
+fn parse_and_print_ast(args: &ArgMatches, name: &str, source: &ModuleSource) {
     let source_text = match source.read_text() {
         Ok(source_text) => source_text,
         
@@ -124,6 +148,9 @@ fn parse_and_print_ast(_args: &ArgMatches, name: &str, source: &ModuleSource) {
             println!("Errors in file \"{}\":\n", name);
             frontend::print_source_errors(source, &errors);
         },
-        Ok(ast) => println!("{:#?}", ast),
+        Ok(ast) => match args.value_of("format") {
+            Some("json") => println!("{:#}", json::ast_to_json(&interner, &ast)),
+            _ => println!("{:#?}", ast),
+        },
     }
 }
\ No newline at end of file
@@ -1,19 +1,32 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::fs;
 use std::io::{self, Write};
+use std::env;
+use std::process;
 use std::path::PathBuf;
-use clap::{Command, Arg, crate_version};
+use clap::{Command, Arg, ArgMatches, crate_version};
 
 use sphinx::frontend;
+use sphinx::frontend::lineedit::{LineEditor, ReadResult};
+use sphinx::frontend::completion::ReplCompleter;
+use sphinx::frontend::highlight::{self, ReplHighlighter};
+use sphinx::frontend::format::{self, FormatOptions};
+use sphinx::frontend::lineedit::Highlighter;
 use sphinx::source::{ModuleSource, SourceText};
 use sphinx::parser::stmt::{StmtMeta, Stmt, StmtList, ControlFlow};
 use sphinx::parser::expr::Expr;
 use sphinx::parser::primary::Atom;
 use sphinx::parser::pattern::{Pattern, MatchAction, Assignment};
-use sphinx::codegen::{Program, CompiledProgram};
-use sphinx::runtime::{Module, VirtualMachine, Gc};
-use sphinx::runtime::module::NamespaceEnv;
-use sphinx::runtime::strings::StringInterner;
+use sphinx::codegen::{Compiler, Program, CompiledProgram, CompileOptions, Backend};
+use sphinx::language::Access;
+use sphinx::runtime::{Module, VirtualMachine, Variant, Gc};
+use sphinx::runtime::gc::{GcTrace, gc_dump_heap};
+use sphinx::runtime::module::{NamespaceEnv, COMPILED_EXT};
+use sphinx::runtime::strings::{StringInterner, StringSymbol};
+use sphinx::debug::codes;
 use sphinx::debug::symbol::resolver::BufferedResolver;
 use sphinx::builtins;
+use sphinx::BuildErrors;
 
 fn main() {
     env_logger::init();
@@ -22,16 +35,26 @@ fn main() {
         .version(crate_version!())
         .author("M. Werezak <mwerezak@gmail.com>")
         .about("An interpreter for the Sphinx programming language")
+        .trailing_var_arg(true)
         .arg(
             Arg::new("file")
             .index(1)
             .help("Path to input script file")
             .value_name("FILE")
         )
+        .arg(
+            Arg::new("args")
+            .index(2)
+            .help("Arguments for the script, produced by os.args()")
+            .value_name("ARGS")
+            .requires("file")
+            .multiple_values(true)
+            .allow_hyphen_values(true)
+        )
         .arg(
             Arg::new("cmd")
             .short('c')
-            .help("Execute a snippet then exit")
+            .help("Execute a snippet then exit, any trailing arguments are produced by os.args()")
             .value_name("CMD")
         )
         .arg(
@@ -40,19 +63,92 @@ fn main() {
             .help("Drop into an interactive REPL after executing")
         )
         .arg(
-            Arg::new("compile_only")
+            Arg::new("disassemble")
             .short('d')
-            .help("Produce compiled bytecode instead of executing (not implemented)")
+            .help("Print the disassembled bytecode instead of executing")
+        )
+        .arg(
+            Arg::new("compile")
+            .long("compile")
+            .help("Compile FILE and write the bytecode alongside it, then exit")
+            .requires("file")
+            .conflicts_with("cmd")
         )
         .arg(
             Arg::new("debug")
             .long("debug")
             .help("Enable step-through debugging")
+        )
+        .arg(
+            Arg::new("register")
+            .long("register")
+            .help("Compile using the experimental register-based bytecode backend")
+        )
+        .arg(
+            Arg::new("path")
+            .short('I')
+            .long("path")
+            .help("Add a directory to the module search path")
+            .value_name("DIR")
+            .multiple_occurrences(true)
+        )
+        .arg(
+            Arg::new("dump-heap")
+            .long("dump-heap")
+            .help("Write a report of the allocations still reachable after executing to FILE")
+            .value_name("FILE")
+        )
+        .arg(
+            Arg::new("explain")
+            .long("explain")
+            .help("Print a description of the error with the given code (e.g. E0306), then exit")
+            .value_name("CODE")
+            .exclusive(true)
+        )
+        .subcommand(
+            Command::new("fmt")
+            .about("Print FILE rewritten in canonical form, keeping comments")
+            .arg(
+                Arg::new("file")
+                .index(1)
+                .required(true)
+                .help("Path to input script file")
+                .value_name("FILE")
+            )
+            .arg(
+                Arg::new("write")
+                .short('w')
+                .long("write")
+                .help("Overwrite FILE instead of printing the result")
+            )
+            .arg(
+                Arg::new("indent")
+                .long("indent")
+                .help("Number of spaces per level of indentation [default: 4]")
+                .value_name("N")
+                .validator(|value| value.parse::<usize>())
+            )
+            .arg(
+                Arg::new("width")
+                .long("width")
+                .help("Break up lists, calls, tables and dicts that don't fit in N columns [default: 100]")
+                .value_name("N")
+                .validator(|value| value.parse::<usize>())
+            )
         );
     
     let version = app.get_version().unwrap();
     let args = app.get_matches();
     
+    if let Some(code) = args.value_of("explain") {
+        explain_error_code(code);
+        return;
+    }
+    
+    if let Some(args) = args.subcommand_matches("fmt") {
+        process::exit(format_file(args));
+    }
+    
     let source;
     if let Some(s) = args.value_of("cmd") {
         source = ModuleSource::String(s.to_string());
@@ -65,53 +161,225 @@ fn main() {
         return;
     }
     
-    if args.is_present("compile_only") {
-        unimplemented!()
+    let mut options = CompileOptions::default();
+    if args.is_present("register") {
+        options.backend = Backend::Register;
+    }
+    
+    if args.is_present("disassemble") {
+        if let Some(build) = build_program(&source, &options) {
+            frontend::print_disassembly(&build, Some(&source));
+        }
+    }
+    else if args.is_present("compile") {
+        if let Some(build) = build_program(&source, &options) {
+            write_compiled(&build, &source);
+        }
     }
     else if args.is_present("interactive") {
-        if let Some(build) = build_program(&source) {
-            let program = Program::load(build.program);
+        if let Some(build) = build_program(&source, &options) {
+            let program = Program::load(build.program).with_symbols(build.symbols);
             
             let repl_env = builtins::create_prelude();
             let main_module = Module::with_env(Some(source), program.data, repl_env);
             
-            let vm = VirtualMachine::new(main_module, &program.main);
+            let mut vm = VirtualMachine::new(main_module, &program.main);
+            add_search_paths(&mut vm, &args);
+            set_script_args(&mut vm, &args);
+            
+            // interrupting the script drops into the REPL
+            install_interrupt_handler();
+            vm.set_interrupt_flag(Some(&INTERRUPT));
+            
+            let mut exit_code = None;
             if args.is_present("debug") {
                 run_debugger(vm);
-            } else if let Err(error) = vm.run() {
-                println!("{}{}", error.traceback(), error);
+            } else {
+                if let Err(error) = vm.run() {
+                    frontend::print_runtime_error(&error);
+                }
+                exit_code = vm.exit_code();
+            }
+            
+            if let Some(path) = args.value_of("dump-heap") {
+                write_heap_dump(&*main_module, Some(path));
+            }
+            
+            // exiting with os.exit() skips the REPL
+            if let Some(code) = exit_code {
+                process::exit(code);
             }
             
             Repl::new(version.to_string(), repl_env).run()
         }
     }
-    else if let Some(build) = build_program(&source) {
-        let program = Program::load(build.program);
+    else if let Some(build) = build_program(&source, &options) {
+        let program = Program::load(build.program).with_symbols(build.symbols);
         
         let main_env = builtins::create_prelude();
         let main_module = Module::with_env(Some(source), program.data, main_env);
         
-        let vm = VirtualMachine::new(main_module, &program.main);
+        let mut vm = VirtualMachine::new(main_module, &program.main);
+        add_search_paths(&mut vm, &args);
+        set_script_args(&mut vm, &args);
+        
+        let mut exit_code = None;
         if args.is_present("debug") {
             run_debugger(vm);
-        } else if let Err(error) = vm.run() {
-            println!("{}{}", error.traceback(), error);
+        } else {
+            if let Err(error) = vm.run() {
+                frontend::print_runtime_error(&error);
+            }
+            exit_code = vm.exit_code();
+        }
+        
+        if let Some(path) = args.value_of("dump-heap") {
+            write_heap_dump(&*main_module, Some(path));
+        }
+        
+        if let Some(code) = exit_code {
+            process::exit(code);
         }
     }
 }
 
 
-fn build_program(source: &ModuleSource) -> Option<CompiledProgram> {
-    match sphinx::build_module(source) {
+fn add_search_paths(vm: &mut VirtualMachine, args: &ArgMatches) {
+    if let Some(paths) = args.values_of("path") {
+        for path in paths {
+            vm.loader_mut().add_search_path(path);
+        }
+    }
+}
+
+fn set_script_args(vm: &mut VirtualMachine, args: &ArgMatches) {
+    let mut script_args = Vec::new();
+    
+    // a snippet has no script file, so FILE is just the first of its arguments
+    if args.is_present("cmd") {
+        script_args.extend(args.value_of("file").map(String::from));
+    }
+    
+    if let Some(values) = args.values_of("args") {
+        script_args.extend(values.map(String::from));
+    }
+    
+    if !script_args.is_empty() {
+        vm.set_args(script_args);
+    }
+}
+
+fn build_program(source: &ModuleSource, options: &CompileOptions) -> Option<CompiledProgram> {
+    match sphinx::build_module_with_options(source, options.clone()) {
         Err(errors) => {
             sphinx::print_build_errors(&errors, source);
             None
         },
         
-        Ok(program) => Some(program)
+        Ok(program) => {
+            sphinx::print_build_warnings(&program.warnings, source);
+            Some(program)
+        }
     }
 }
 
+fn write_compiled(build: &CompiledProgram, source: &ModuleSource) {
+    let path = match source {
+        ModuleSource::File(path) => path.with_extension(COMPILED_EXT),
+        ModuleSource::String(..) => unreachable!("--compile requires a source file"),
+    };
+    
+    let result = fs::File::create(&path).and_then(|file| {
+        let mut writer = io::BufWriter::new(file);
+        build.write_to(&mut writer)?;
+        writer.flush()
+    });
+    
+    if let Err(error) = result {
+        println!("Could not write \"{}\": {}", path.display(), error);
+    }
+}
+
+// writes to stdout if no path is given
+fn write_heap_dump(root: &impl GcTrace, path: Option<&str>) {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            if let Err(error) = gc_dump_heap(root, &mut io::stdout().lock()) {
+                println!("Could not write heap dump: {}", error);
+            }
+            return;
+        }
+    };
+    
+    let result = fs::File::create(path).and_then(|file| {
+        let mut writer = io::BufWriter::new(file);
+        gc_dump_heap(root, &mut writer)?;
+        writer.flush()
+    });
+    
+    if let Err(error) = result {
+        println!("Could not write \"{}\": {}", path, error);
+    }
+}
+
+fn explain_error_code(code: &str) {
+    match codes::lookup(code) {
+        Some(info) => println!("{}: {}\n\n{}", info.code, info.title, info.explanation),
+        None => {
+            let known = codes::iter_codes().map(|info| info.code.as_str()).collect::<Vec<&str>>();
+            println!("Unknown error code \"{}\". The known codes are:\n{}", code, known.join(", "));
+        }
+    }
+}
+
+// returns the exit code, which is non-zero if the file could not be formatted
+fn format_file(args: &ArgMatches) -> i32 {
+    let path = PathBuf::from(args.value_of("file").unwrap());
+    
+    let mut options = FormatOptions::default();
+    if args.is_present("indent") {
+        options.indent_width = args.value_of_t_or_exit("indent");
+    }
+    if args.is_present("width") {
+        options.line_width = args.value_of_t_or_exit("width");
+    }
+    
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) => {
+            println!("Could not read \"{}\": {}", path.display(), error);
+            return 1;
+        }
+    };
+    
+    let formatted = match format::format_source(&text, options) {
+        Ok(formatted) => formatted,
+        Err(errors) => {
+            let errors = BuildErrors::Syntax(errors.into_boxed_slice());
+            sphinx::print_build_errors(&errors, &ModuleSource::File(path));
+            return 1;
+        }
+    };
+    
+    if !args.is_present("write") {
+        print!("{}", formatted);
+        return 0;
+    }
+    
+    // write to a temporary file first, so that a failed write can't leave FILE partly overwritten
+    let mut temp_path = path.clone().into_os_string();
+    temp_path.push(".fmt-tmp");
+    let temp_path = PathBuf::from(temp_path);
+    
+    if let Err(error) = fs::write(&temp_path, formatted).and_then(|_| fs::rename(&temp_path, &path)) {
+        let _ = fs::remove_file(&temp_path);
+        println!("Could not write \"{}\": {}", path.display(), error);
+        return 1;
+    }
+    0
+}
+
 fn run_debugger(vm: VirtualMachine) {
     for status in vm.run_steps() {
         match status {
@@ -126,46 +394,101 @@ fn run_debugger(vm: VirtualMachine) {
         
         let mut input = String::new();
         let result = io::stdin().read_line(&mut input);
-        if let Err(..) = result { }
+        if result.is_err() { }
     }
     
     println!("Execution stopped.");
 }
 
+// Ctrl-C sets this flag, which the VM turns into an InterruptedError instead of the process being killed
+static INTERRUPT: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn handle_sigint(_: libc::c_int) {
+        // if the last interrupt hasn't been picked up (e.g. the VM is stuck inside a native function),
+        // then a second Ctrl-C kills the process as usual
+        if INTERRUPT.swap(true, Ordering::Relaxed) {
+            unsafe {
+                libc::signal(libc::SIGINT, libc::SIG_DFL);
+                libc::raise(libc::SIGINT);
+            }
+        }
+    }
+    
+    unsafe {
+        libc::signal(libc::SIGINT, handle_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_interrupt_handler() { }
+
 //////// REPL ////////
 const PROMT_START: &str = ">>> ";
 const PROMT_CONTINUE: &str = "... ";
 
+// holds the value to echo while the input is run, it isn't a valid identifier so it can't clash with any variables
+const REPL_RESULT: &str = "<result>";
+
 pub struct Repl {
     version: String,
     repl_env: Gc<NamespaceEnv>,
+    compiler: Compiler,  // each input continues the same program, see Compiler::compile_incremental()
+    vm: Option<VirtualMachine<'static>>,  // reused for each input, see VirtualMachine::reload_program()
+    editor: LineEditor,
+    highlighter: Option<ReplHighlighter>,  // used for echoed values, if colors are enabled
 }
 
 enum ReadLine {
     Ok(String),
     Empty,
     Restart,
+    Cancel,
     Quit,
+    DumpHeap(Option<String>),
 }
 
 impl Repl {
     pub fn new(version: String, repl_env: Gc<NamespaceEnv>) -> Self {
         Self {
             version, repl_env,
+            compiler: Compiler::new(StringInterner::new()),
+            vm: None,
+            editor: Self::create_editor(repl_env),
+            highlighter: highlight::color_enabled().then(ReplHighlighter::new),
         }
     }
     
-    fn read_line(&self, prompt: &'static str) -> ReadLine {
-        io::stdout().write_all(prompt.as_bytes()).unwrap();
-        io::stdout().flush().unwrap();
+    // the history is kept in $SPHINX_HISTORY, or ~/.sphinx_history
+    fn create_editor(repl_env: Gc<NamespaceEnv>) -> LineEditor {
+        let mut editor = LineEditor::new(PROMT_CONTINUE)
+            .with_completer(ReplCompleter::new(repl_env));
         
-        let mut input = String::new();
-        let result = io::stdin().read_line(&mut input);
-        if result.is_err() {
-            println!("Could not read input: {}", result.unwrap_err());
-            return ReadLine::Restart;
+        if highlight::color_enabled() {
+            editor = editor.with_highlighter(ReplHighlighter::new());
         }
         
+        let path = env::var_os("SPHINX_HISTORY").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".sphinx_history")));
+        
+        match path {
+            Some(path) => editor.with_history_file(path),
+            None => editor,
+        }
+    }
+    
+    fn read_line(&mut self, prompt: &'static str) -> ReadLine {
+        let mut input = match self.editor.read_line(prompt) {
+            Ok(ReadResult::Line(line)) => line,
+            Ok(ReadResult::Interrupted) => return ReadLine::Cancel,
+            Ok(ReadResult::Eof) => return ReadLine::Quit,
+            Err(error) => {
+                println!("Could not read input: {}", error);
+                return ReadLine::Restart;
+            }
+        };
+        
         input = input.trim_end().to_string();
         
         if input.is_empty() {
@@ -176,14 +499,21 @@ impl Repl {
             return ReadLine::Quit;
         }
         
+        // ":heap [FILE]" writes a heap dump to FILE, or to stdout
+        let mut words = input.split_whitespace();
+        if words.next() == Some(":heap") {
+            return ReadLine::DumpHeap(words.next().map(String::from));
+        }
+        
         ReadLine::Ok(input)
     }
     
     pub fn run(&mut self) {
         println!("\nSphinx Version {}\n", self.version);
         
+        install_interrupt_handler();
+        
         loop {
-            let mut interner;
             let mut input = String::new();
             let mut parse_result = None;
             
@@ -192,11 +522,18 @@ impl Repl {
                     if input.is_empty() { PROMT_START }
                     else { PROMT_CONTINUE };
                 
-                interner = StringInterner::new();
-                
                 match self.read_line(prompt) {
                     ReadLine::Quit => return,
                     ReadLine::Restart => continue,
+                    ReadLine::Cancel => {
+                        input.clear();
+                        parse_result = None;
+                        continue
+                    },
+                    ReadLine::DumpHeap(path) => {
+                        write_heap_dump(&*self.repl_env, path.as_deref());
+                        continue
+                    },
                     ReadLine::Empty => {
                         if input.is_empty() { continue }
                         else { break }
@@ -210,7 +547,7 @@ impl Repl {
                         
                         // If we can't parse the input without errors, then we assume we need to continue
                         let source_text = SourceText::from(input.clone());
-                        if let Ok(ast) = sphinx::parse_source(&mut interner, source_text) {
+                        if let Ok(ast) = sphinx::parse_source(self.compiler.strings_mut(), source_text) {
                             parse_result.replace(ast);
                             break
                         }
@@ -220,11 +557,14 @@ impl Repl {
                 }
             }
             
+            // continuation lines are kept together as a single entry
+            self.editor.add_history(&input);
+            
             let parse_result =
                 if let Some(ast) = parse_result { Ok(ast) }
                 else { 
                     let source_text = SourceText::from(input.clone());
-                    sphinx::parse_source(&mut interner, source_text) 
+                    sphinx::parse_source(self.compiler.strings_mut(), source_text) 
                 };
             
             let mut ast = match parse_result {
@@ -237,10 +577,16 @@ impl Repl {
                 },
             };
             
-            Self::repl_ast_transform(&mut interner, &mut ast);
+            Self::repl_ast_transform(self.compiler.strings_mut(), &mut ast);
             
-            let build = match sphinx::compile_ast(interner, ast) {
-                Ok(build) => build,
+            let build = match self.compiler.compile_incremental(ast.iter()) {
+                Ok(build) => {
+                    if !build.warnings.is_empty() {
+                        let resolver = BufferedResolver::new(&input);
+                        frontend::print_source_errors(&resolver, &build.warnings);
+                    }
+                    build
+                },
                 
                 Err(errors) => {
                     let resolver = BufferedResolver::new(input);
@@ -249,17 +595,38 @@ impl Repl {
                 }
             };
             
-            let program = Program::load(build.program);
+            let program = Program::load(build.program).with_symbols(build.symbols);
+            
+            // each input is kept as the source of its module, so that tracebacks can be resolved
+            // even for functions that were defined by an earlier input
+            let module = Module::with_env(Some(ModuleSource::String(input)), program.data, self.repl_env);
             
-            let module = Module::with_env(None, program.data, self.repl_env);
+            let vm = self.vm.get_or_insert_with(|| {
+                let mut vm = VirtualMachine::new(module, &[]);
+                vm.set_interrupt_flag(Some(&INTERRUPT));
+                vm
+            });
+            vm.reload_program(module, program.main);
+            
+            // ignore any Ctrl-C from before the input was run
+            INTERRUPT.store(false, Ordering::Relaxed);
+            let result = vm.run();
+            if let Some(code) = vm.exit_code() {
+                process::exit(code);
+            }
+            self.repl_env.borrow_mut().delete(&StringSymbol::from(REPL_RESULT)).ok();
             
-            let vm = VirtualMachine::new(module, &program.main);
-            match vm.run() {
+            match result {
                 Ok(value) => if !value.is_nil() {
-                    println!("{}", value.display_echo())
+                    let echo = value.display_echo().to_string();
+                    match self.highlighter.as_ref() {
+                        Some(highlighter) => println!("{}", highlighter.highlight(&echo)),
+                        None => println!("{}", echo),
+                    }
+                    self.bind_result(value);
                 }
                 
-                Err(error) => println!("{}{}", error.traceback(), error),
+                Err(error) => frontend::print_runtime_error(&error),
             }
             
         }
@@ -267,6 +634,7 @@ impl Repl {
     }
     
     // dirty hack to make the REPL work
+    // if the last statement is an expression, its value is returned so that it can be echoed
     fn repl_ast_transform(interner: &mut StringInterner, ast: &mut Vec<StmtMeta>) {
         let last_stmt = match ast.pop() {
             Some(stmt) => stmt,
@@ -275,16 +643,16 @@ impl Repl {
 
         let (stmt, symbol) = last_stmt.take();
         
-        let result_expr;
-        if let Stmt::Expression(expr) = stmt {
-            result_expr = expr;
-        } else {
-            ast.push(StmtMeta::new(stmt, symbol));
-            result_expr = Expr::Atom(Atom::Nil);
-        }
+        let result_expr = match stmt {
+            Stmt::Expression(expr) => expr,
+            stmt => {
+                ast.push(StmtMeta::new(stmt, symbol));
+                return;
+            }
+        };
         
-        // bind the result expression to a global name
-        let result_name = interner.get_or_intern("_");
+        // the expression is evaluated at the top level, so that any variables it declares are global
+        let result_name = interner.get_or_intern(REPL_RESULT);
         let result_decl = Expr::Assignment(Box::new(Assignment {
             action: MatchAction::DeclImmutable,
             lhs: Pattern::Identifier(result_name),
@@ -307,5 +675,10 @@ impl Repl {
         ast.push(StmtMeta::new(wrapper, symbol));
         
     }
+    
+    // like Python, "_" refers to the last result that was echoed
+    fn bind_result(&self, value: Variant) {
+        self.repl_env.borrow_mut().create(StringSymbol::from("_"), Access::ReadOnly, value);
+    }
 }
 
@@ -1,13 +1,22 @@
-use crate::runtime::Gc;
+use crate::runtime::{Gc, Variant, ExecResult};
+use crate::runtime::gc::GcTrace;
 use crate::runtime::module::NamespaceEnv;
+use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
+use crate::runtime::types::{Type, MetaObject, UserData};
 
 mod iter;
 mod primitive;
 mod misc;
+mod gc;
+mod os;
+mod random;
 
 use iter::create_iter_builtins;
 use primitive::{create_primitive_ctors, create_metamethod_builtins};
 use misc::create_misc_builtins;
+use gc::create_gc_builtins;
+use os::create_os_builtins;
+use random::create_random_builtins;
 
 // thread_local! {
 //     pub static PRELUDE: Gc<NamespaceEnv> = {
@@ -26,6 +35,50 @@ pub fn create_prelude() -> Gc<NamespaceEnv> {
     create_primitive_ctors(env);
     create_iter_builtins(env);
     create_misc_builtins(env);
+    create_gc_builtins(env);
+    create_os_builtins(env);
+    create_random_builtins(env);
     
     env
 }
+
+
+/// A namespace of builtins that is accessed through attributes, e.g. `gc.collect()`
+pub struct BuiltinModule {
+    name: StringSymbol,
+    env: Gc<NamespaceEnv>,
+}
+
+impl BuiltinModule {
+    pub fn new(name: impl Into<StringSymbol>, env: Gc<NamespaceEnv>) -> Self {
+        Self { name: name.into(), env }
+    }
+    
+    pub fn env(&self) -> Gc<NamespaceEnv> { self.env }
+}
+
+unsafe impl GcTrace for BuiltinModule {
+    fn trace(&self) {
+        self.env.mark_trace();
+    }
+}
+
+impl UserData for BuiltinModule { }
+
+impl MetaObject for BuiltinModule {
+    fn type_tag(&self) -> Type { Type::UserData }
+    
+    fn type_name(&self) -> ExecResult<StringValue> {
+        Ok(static_symbol!("module").into())
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        let result = format!("<module \"{}\">", self.name);
+        Ok(StringValue::new_uninterned(result))
+    }
+    
+    fn get_attr(&self, name: &StringSymbol) -> Option<ExecResult<Variant>> {
+        self.env.borrow().lookup(name).ok()
+            .map(|value| Ok(*value))
+    }
+}
@@ -0,0 +1,73 @@
+use core::time::Duration;
+use crate::language::{IntType, FloatType};
+use crate::runtime::{Gc, Variant, VariantKey, ExecResult};
+use crate::runtime::gc::GcStats;
+use crate::runtime::module::NamespaceEnv;
+use crate::runtime::strings::static_symbol;
+use crate::runtime::types::{Dict, UserData};
+use crate::runtime::errors::RuntimeError;
+use crate::builtins::BuiltinModule;
+
+
+pub fn create_gc_builtins(env: Gc<NamespaceEnv>) {
+    let gc_env = NamespaceEnv::new();
+    
+    let collect = native_function!(collect, gc_env, vm(vm) => {
+        vm.collect_garbage();
+        Ok(Variant::Nil)
+    });
+    
+    let stats = native_function!(stats, gc_env, vm(vm) => {
+        let dict = stats_to_dict(&vm.gc_stats(), vm.gc_pause_factor())?;
+        Ok(Variant::Dict(Gc::new(dict)))
+    });
+    
+    let set_pause_factor = native_function!(set_pause_factor, gc_env, vm(vm), params(percent) => {
+        let percent = u16::try_from(percent.as_int()?)
+            .map_err(|_| RuntimeError::invalid_value("pause factor must be between 0 and 65535"))?;
+        
+        vm.set_gc_pause_factor(percent);
+        Ok(Variant::Nil)
+    });
+    
+    namespace_insert!(gc_env.borrow_mut(), {
+        fun _ = collect;
+        fun _ = stats;
+        fun _ = set_pause_factor;
+    });
+    
+    let module: Box<dyn UserData> = Box::new(BuiltinModule::new("gc", gc_env));
+    namespace_insert!(env.borrow_mut(), {
+        let gc = (Variant::UserData(Gc::from_box(module)));
+    });
+}
+
+// pause times are given in seconds
+fn stats_to_dict(stats: &GcStats, pause_factor: u16) -> ExecResult<Dict> {
+    fn to_int(value: usize) -> Variant {
+        Variant::from(IntType::try_from(value).unwrap_or(IntType::MAX))
+    }
+    
+    fn to_secs(duration: Duration) -> Variant {
+        Variant::from(duration.as_secs_f64() as FloatType)
+    }
+    
+    let entries = [
+        (static_symbol!("cycles"), to_int(stats.cycle_count)),
+        (static_symbol!("allocated"), to_int(stats.allocated)),
+        (static_symbol!("allocations"), to_int(stats.box_count)),
+        (static_symbol!("total_allocated"), to_int(stats.total_allocated)),
+        (static_symbol!("total_freed"), to_int(stats.total_freed)),
+        (static_symbol!("threshold"), to_int(stats.threshold)),
+        (static_symbol!("pause_factor"), Variant::from(IntType::from(pause_factor))),
+        (static_symbol!("last_pause"), to_secs(stats.last_pause)),
+        (static_symbol!("max_pause"), to_secs(stats.max_pause)),
+        (static_symbol!("total_pause"), to_secs(stats.total_pause)),
+    ];
+    
+    let dict = Dict::with_capacity(entries.len());
+    for (name, value) in entries.into_iter() {
+        dict.insert(VariantKey::try_from(Variant::from(name))?, value);
+    }
+    Ok(dict)
+}
@@ -61,7 +61,7 @@ unsafe impl GcTrace for Zip {
 }
 
 impl Zip {
-    fn new<'a>(iterables: &[Variant]) -> ExecResult<Self> {
+    fn new(iterables: &[Variant]) -> ExecResult<Self> {
         let iters = iterables.iter()
             .map(Variant::iter_init)
             .collect::<Result<Vec<IterState>,_>>()?
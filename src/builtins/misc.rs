@@ -1,27 +1,70 @@
-use crate::runtime::Gc;
+use std::io;
+use crate::runtime::{Gc, Variant, VirtualMachine};
 use crate::runtime::module::NamespaceEnv;
-use crate::runtime::errors::RuntimeError;
+use crate::runtime::strings::StringValue;
+use crate::runtime::errors::{ExecResult, RuntimeError};
 
 
 pub fn create_misc_builtins(env: Gc<NamespaceEnv>) {
     
-    let repr = native_function!(repr, env, params(value) => {
-        Ok(Variant::from(value.fmt_repr()?))
+    let repr = native_function!(repr, env, vm(vm), params(value) => {
+        Ok(Variant::from(vm.fmt_repr(value)?))
     });
     
-    let print = native_function!(print, env, variadic(values) => {
-        if let Some((first, rest)) = values.split_first() {
-            print!("{}", first.fmt_str()?);
-            for value in rest.iter() {
-                print!(" ");
-                print!("{}", value.fmt_str()?);
-            }
+    // Instances of a user-defined class produce the class, all other values produce a type object
+    let type_of = native_function!(type, env, params(value) => {
+        match value {
+            Variant::Instance(instance) => Ok(Variant::Class(instance.class())),
+            _ => Ok(Variant::Type(value.type_tag())),
         }
-        println!();
+    });
+    
+    // An instance is also an instance of any class its class inherits from
+    let isinstance = native_function!(isinstance, env, params(value, ty) => {
+        let result = match (value, ty) {
+            (Variant::Instance(instance), Variant::Class(class)) => instance.class().is_subclass(class),
+            (_, Variant::Class(..)) => false,
+            (_, Variant::Type(ty)) => value.type_tag() == *ty,
+            _ => return Err(RuntimeError::not_a_type(ty)),
+        };
+        Ok(Variant::from(result))
+    });
+    
+    let print = native_function!(print, env, vm(vm), variadic(values) => {
+        write_values(vm, values, " ", "\n")?;
+        Ok(Variant::Nil)
+    });
+    
+    // Like print(), but with the separator and line ending given explicitly
+    let write = native_function!(write, env, vm(vm), params(sep, end), variadic(values) => {
+        let sep = sep.as_strval().ok_or_else(|| RuntimeError::invalid_value("sep must be a string"))?;
+        let end = end.as_strval().ok_or_else(|| RuntimeError::invalid_value("end must be a string"))?;
         
+        sep.with_str(|sep| end.with_str(|end| write_values(vm, values, sep, end)))?;
         Ok(Variant::Nil)
     });
     
+    // Reads a line of input without the line ending, or produces nil at the end of the input
+    let input = native_function!(input, env, vm(vm), defaults(prompt = Variant::Nil) => {
+        if !prompt.is_nil() {
+            let prompt = vm.fmt_str(prompt)?;
+            let output = vm.output();
+            prompt.with_str(|s| output.write_all(s.as_bytes()))
+                .and_then(|_| output.flush())
+                .map_err(output_error)?;
+        }
+        
+        let mut line = String::new();
+        if vm.read_line(&mut line).map_err(input_error)? == 0 {
+            return Ok(Variant::Nil);
+        }
+        
+        let trimmed = line.strip_suffix('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .unwrap_or(&line);
+        Ok(Variant::from(StringValue::new_uninterned(trimmed)))
+    });
+    
     // Produces a tuple of the global names in the current call frame
     // TODO return an object or a namespace instead?
     let globals = native_function!(globals, env, vm(vm)  => {
@@ -35,21 +78,51 @@ pub fn create_misc_builtins(env: Gc<NamespaceEnv>) {
     });
     
     // Prints the signature of a function. Will print an object's docstring if that is ever added.
-    let help = native_function!(help, env, params(object) => {
+    let help = native_function!(help, env, vm(vm), params(object) => {
         let signature = match object {
             Variant::Function(fun) => fun.signature().fmt_signature(),
             Variant::NativeFunction(fun) => fun.signature().fmt_signature(),
+            Variant::Type(ty) if ty.ctor().is_some() => ty.ctor().unwrap().signature().fmt_signature(),
             _ => return Err(RuntimeError::invalid_value("not a function"))
         };
         
-        println!("{}", signature);
+        writeln!(vm.output(), "{}", signature).map_err(output_error)?;
         Ok(Variant::Nil)
     });
     
     namespace_insert!(env.borrow_mut(), {
         fun _ = globals;
         fun _ = repr;
+        fun _ = type_of;
+        fun _ = isinstance;
         fun _ = print;
+        fun _ = write;
+        fun _ = input;
         fun _ = help;
     });
 }
+
+// the values are written all at once, so that output from a single call is never split up
+fn write_values(vm: &mut VirtualMachine<'_>, values: &[Variant], sep: &str, end: &str) -> ExecResult<()> {
+    let mut buf = String::new();
+    for (idx, value) in values.iter().enumerate() {
+        if idx > 0 {
+            buf.push_str(sep);
+        }
+        vm.fmt_str(value)?.with_str(|s| buf.push_str(s));
+    }
+    buf.push_str(end);
+    
+    let output = vm.output();
+    output.write_all(buf.as_bytes())
+        .and_then(|_| output.flush())
+        .map_err(output_error)
+}
+
+fn output_error(error: io::Error) -> Box<RuntimeError> {
+    RuntimeError::other(format!("could not write output: {}", error))
+}
+
+fn input_error(error: io::Error) -> Box<RuntimeError> {
+    RuntimeError::other(format!("could not read input: {}", error))
+}
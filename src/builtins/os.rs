@@ -0,0 +1,120 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use crate::runtime::{Gc, Variant, ExecResult};
+use crate::runtime::module::NamespaceEnv;
+use crate::runtime::strings::StringValue;
+use crate::runtime::types::UserData;
+use crate::runtime::errors::RuntimeError;
+use crate::builtins::BuiltinModule;
+
+
+pub fn create_os_builtins(env: Gc<NamespaceEnv>) {
+    let os_env = NamespaceEnv::new();
+    
+    // Produces nil if the variable is not set
+    let env_var = native_function!(env, os_env, params(name) => {
+        let name = name.as_strval()
+            .ok_or_else(|| RuntimeError::invalid_value("variable name must be a string"))?;
+        
+        let value = name.with_str(|name| env::var_os(name))
+            .map(|value| value.into_string()
+                .map_err(|_| RuntimeError::invalid_value(format!("the value of \"{}\" is not valid unicode", name)))
+            )
+            .transpose()?;
+        
+        Ok(value.map_or(Variant::Nil, |value| Variant::from(StringValue::new_uninterned(value))))
+    });
+    
+    // Produces a tuple of the arguments given to the program, not including the program itself
+    let args = native_function!(args, os_env, vm(vm) => {
+        let args = vm.args().iter()
+            .map(|arg| Variant::from(StringValue::new_uninterned(arg)))
+            .collect::<Vec<Variant>>()
+            .into_boxed_slice();
+        
+        Ok(Variant::from(args))
+    });
+    
+    let exit = native_function!(exit, os_env, vm(vm), defaults(code = Variant::from(0)) => {
+        let code = i32::try_from(code.as_int()?)
+            .map_err(|_| RuntimeError::invalid_value("exit code is out of range"))?;
+        
+        vm.exit(code);
+        Ok(Variant::Nil)
+    });
+    
+    let cwd = native_function!(cwd, os_env => {
+        let path = env::current_dir()
+            .map_err(|error| RuntimeError::other(format!("could not get the current directory: {}", error)))?;
+        path_to_variant(&path)
+    });
+    
+    namespace_insert!(os_env.borrow_mut(), {
+        fun _ = env_var;
+        fun _ = args;
+        fun _ = exit;
+        fun _ = cwd;
+    });
+    
+    create_path_builtins(os_env);
+    
+    let module: Box<dyn UserData> = Box::new(BuiltinModule::new("os", os_env));
+    namespace_insert!(env.borrow_mut(), {
+        let os = (Variant::UserData(Gc::from_box(module)));
+    });
+}
+
+// the os.path module, which only manipulates paths and never touches the filesystem
+fn create_path_builtins(env: Gc<NamespaceEnv>) {
+    let path_env = NamespaceEnv::new();
+    
+    // Later parts replace the path so far if they are absolute, like Path::join()
+    let join = native_function!(join, path_env, variadic(parts) => {
+        let mut path = PathBuf::new();
+        for part in parts.iter() {
+            let part = part.as_strval()
+                .ok_or_else(|| RuntimeError::invalid_value("path must be a string"))?;
+            part.with_str(|part| path.push(part));
+        }
+        path_to_variant(&path)
+    });
+    
+    // Produces nil if the path has no parent
+    let dirname = native_function!(dirname, path_env, params(path) => {
+        with_path(path, |path| match path.parent() {
+            Some(parent) => path_to_variant(parent),
+            None => Ok(Variant::Nil),
+        })
+    });
+    
+    // Produces nil if the path ends in ".."
+    let basename = native_function!(basename, path_env, params(path) => {
+        with_path(path, |path| match path.file_name() {
+            Some(name) => path_to_variant(Path::new(name)),
+            None => Ok(Variant::Nil),
+        })
+    });
+    
+    namespace_insert!(path_env.borrow_mut(), {
+        fun _ = join;
+        fun _ = dirname;
+        fun _ = basename;
+    });
+    
+    let module: Box<dyn UserData> = Box::new(BuiltinModule::new("path", path_env));
+    namespace_insert!(env.borrow_mut(), {
+        let path = (Variant::UserData(Gc::from_box(module)));
+    });
+}
+
+fn with_path(path: &Variant, f: impl FnOnce(&Path) -> ExecResult<Variant>) -> ExecResult<Variant> {
+    let path = path.as_strval()
+        .ok_or_else(|| RuntimeError::invalid_value("path must be a string"))?;
+    path.with_str(|path| f(Path::new(path)))
+}
+
+fn path_to_variant(path: &Path) -> ExecResult<Variant> {
+    let path = path.to_str()
+        .ok_or_else(|| RuntimeError::invalid_value(format!("path \"{}\" is not valid unicode", path.display())))?;
+    Ok(Variant::from(StringValue::new_uninterned(path)))
+}
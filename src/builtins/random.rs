@@ -0,0 +1,60 @@
+use crate::language::IntType;
+use crate::runtime::{Gc, Variant};
+use crate::runtime::module::NamespaceEnv;
+use crate::runtime::random::Random;
+use crate::runtime::types::UserData;
+use crate::runtime::errors::RuntimeError;
+use crate::builtins::BuiltinModule;
+
+
+pub fn create_random_builtins(env: Gc<NamespaceEnv>) {
+    let random_env = NamespaceEnv::new();
+    
+    // Produces a float in the interval [0, 1)
+    let random = native_function!(random, random_env, vm(vm) => {
+        Ok(Variant::from(vm.random().next_float()))
+    });
+    
+    // Produces an integer that range() with the same arguments could also produce
+    let range = native_function!(range, random_env, vm(vm), params(start), defaults(stop = Variant::Nil) => {
+        let (start, stop) = match stop {
+            Variant::Nil => (0, start.as_int()?),
+            stop => (start.as_int()?, stop.as_int()?),
+        };
+        
+        let value = vm.random().next_range(start, stop)
+            .ok_or_else(|| RuntimeError::invalid_value("empty range"))?;
+        Ok(Variant::from(value))
+    });
+    
+    // Picks an item from a sequence that supports len() and indexing
+    let choice = native_function!(choice, random_env, vm(vm), params(seq) => {
+        let index = vm.random().next_index(seq.len()?)
+            .ok_or_else(|| RuntimeError::invalid_value("can't choose from an empty sequence"))?;
+        
+        // the index fits, since it is less than len()
+        seq.get_index(&Variant::from(IntType::try_from(index).unwrap()))
+    });
+    
+    // Restarts the sequence of random numbers, reseeding unpredictably if no seed is given
+    let seed = native_function!(seed, random_env, vm(vm), defaults(seed = Variant::Nil) => {
+        let random = match seed {
+            Variant::Nil => Random::from_entropy(),
+            seed => Random::from_seed(seed.as_int()? as u64),
+        };
+        *vm.random() = random;
+        Ok(Variant::Nil)
+    });
+    
+    namespace_insert!(random_env.borrow_mut(), {
+        fun _ = random;
+        fun _ = range;
+        fun _ = choice;
+        fun _ = seed;
+    });
+    
+    let module: Box<dyn UserData> = Box::new(BuiltinModule::new("random", random_env));
+    namespace_insert!(env.borrow_mut(), {
+        let random = (Variant::UserData(Gc::from_box(module)));
+    });
+}
@@ -1,14 +1,20 @@
 use core::iter;
+use std::io::{self, Read, Write};
+use std::collections::{HashMap, HashSet};
+use string_interner::Symbol as _;
 
 use crate::language::{IntType, FloatType, InternSymbol, Access};
-use crate::parser::stmt::{StmtMeta, Stmt, Label, StmtList, ControlFlow};
-use crate::parser::expr::{Expr, ExprMeta, ExprBlock, ConditionalBranch};
+use crate::parser::stmt::{StmtMeta, Stmt, Label, StmtList, ControlFlow, ExceptClause};
+use crate::parser::expr::{Expr, ExprMeta, ExprBlock, ConditionalBranch, DictEntry};
 use crate::parser::primary::{Atom, Primary, AccessItem};
-use crate::parser::pattern::{Pattern, MatchAction};
+use crate::parser::pattern::{Pattern, PatternMeta, MatchAction, AttributePattern, IndexPattern};
 use crate::parser::fundefs::{FunctionDef, SignatureDef};
+use crate::parser::classdefs::ClassDef;
+use crate::parser::matchdefs::{MatchCase, CasePattern};
 use crate::parser::operator::{UnaryOp, BinaryOp};
 use crate::runtime::strings::{StringInterner};
 use crate::runtime::errors::ErrorKind;
+use crate::utils;
 use crate::debug::symbol::{DebugSymbol, ChunkSymbols, DebugSymbolTable};
 
 mod scope;
@@ -18,15 +24,23 @@ pub mod consts;
 pub mod funproto;
 pub mod opcodes;
 pub mod errors;
+pub mod verify;
+
+mod serialize;
+mod tests;
+
+use serialize::{WriteBytes as _, ReadBytes as _};
 
 pub use opcodes::{OpCode, LocalIndex};
+use opcodes::{REG_CONST_SRC1, REG_CONST_SRC2};
 pub use chunk::{UnloadedProgram, Program, ProgramData, Chunk};
 pub use consts::{ConstID, Constant};
 pub use funproto::{FunctionID, FunctionProto, UpvalueTarget};
-pub use errors::{CompileResult, CompileError};
+pub use errors::{CompileResult, CompileError, CompileWarning, WarningKind};
+use errors::ErrorKind as CompileErrorKind;
 
-use scope::{ScopeTracker, ScopeTag, Scope, LocalName, InsertLocal, ControlFlowTarget};
-use chunk::{ChunkBuilder, ChunkInfo, ChunkBuf};
+use scope::{ScopeTracker, ScopeTag, Scope, Local, LocalName, InsertLocal, ControlFlowTarget, ErrorHandler, FunctionKind};
+use chunk::{ChunkBuilder, BuilderCheckpoint, ChunkInfo, ChunkBuf};
 use funproto::{UnloadedFunction, UnloadedSignature, UnloadedParam};
 
 
@@ -45,11 +59,52 @@ enum Jump {
     IfTrue,
     PopIfFalse,
     PopIfTrue,
+    IfNil,
+    IfNotNil,
+    PushHandler,
+    
+    // compare the top two values on the stack and jump on the result, only available as short jumps
+    IfEQ,
+    IfNotEQ,
+    IfLT,
+    IfNotLT,
+    IfLE,
+    IfNotLE,
 }
 
 impl Jump {
-    pub const fn dummy_width(&self) -> usize {
-        get_jump_opcode(Jump::Uncond, JumpOffset::Short(0)).instr_len()
+    pub const fn dummy_width(&self, long: bool) -> usize {
+        let offset = if long { JumpOffset::Long(0) } else { JumpOffset::Short(0) };
+        get_jump_opcode(*self, offset).instr_len()
+    }
+    
+    // the compare-and-jump that performs a comparison followed by this jump
+    // only EQ, LT, and LE are needed because the other comparisons are defined as their negation
+    const fn fuse_compare(self, op: BinaryOp) -> Option<Jump> {
+        let fused = match (op, self) {
+            (BinaryOp::EQ, Jump::PopIfTrue) | (BinaryOp::NE, Jump::PopIfFalse) => Jump::IfEQ,
+            (BinaryOp::EQ, Jump::PopIfFalse) | (BinaryOp::NE, Jump::PopIfTrue) => Jump::IfNotEQ,
+            (BinaryOp::LT, Jump::PopIfTrue) | (BinaryOp::GE, Jump::PopIfFalse) => Jump::IfLT,
+            (BinaryOp::LT, Jump::PopIfFalse) | (BinaryOp::GE, Jump::PopIfTrue) => Jump::IfNotLT,
+            (BinaryOp::LE, Jump::PopIfTrue) | (BinaryOp::GT, Jump::PopIfFalse) => Jump::IfLE,
+            (BinaryOp::LE, Jump::PopIfFalse) | (BinaryOp::GT, Jump::PopIfTrue) => Jump::IfNotLE,
+            _ => return None,
+        };
+        Some(fused)
+    }
+    
+    // the separate comparison and jump that are used when a compare-and-jump needs a long offset
+    const fn unfuse(self) -> Option<(OpCode, Jump)> {
+        let unfused = match self {
+            Jump::IfEQ    => (OpCode::EQ, Jump::PopIfTrue),
+            Jump::IfNotEQ => (OpCode::EQ, Jump::PopIfFalse),
+            Jump::IfLT    => (OpCode::LT, Jump::PopIfTrue),
+            Jump::IfNotLT => (OpCode::LT, Jump::PopIfFalse),
+            Jump::IfLE    => (OpCode::LE, Jump::PopIfTrue),
+            Jump::IfNotLE => (OpCode::LE, Jump::PopIfFalse),
+            _ => return None,
+        };
+        Some(unfused)
     }
 }
 
@@ -68,15 +123,44 @@ const fn get_jump_opcode(jump: Jump, offset: JumpOffset) -> OpCode {
         
         (Jump::PopIfFalse, JumpOffset::Long(..))   => OpCode::PopLongJumpIfFalse,
         (Jump::PopIfTrue,  JumpOffset::Long(..))   => OpCode::PopLongJumpIfTrue,
+        
+        (Jump::IfNil,    JumpOffset::Short(..)) => OpCode::JumpIfNil,
+        (Jump::IfNotNil, JumpOffset::Short(..)) => OpCode::JumpIfNotNil,
+        
+        (Jump::IfNil,    JumpOffset::Long(..))  => OpCode::LongJumpIfNil,
+        (Jump::IfNotNil, JumpOffset::Long(..))  => OpCode::LongJumpIfNotNil,
+        
+        (Jump::PushHandler, JumpOffset::Short(..)) => OpCode::PushHandler,
+        (Jump::PushHandler, JumpOffset::Long(..))  => OpCode::LongPushHandler,
+        
+        (Jump::IfEQ,    JumpOffset::Short(..)) => OpCode::JumpIfEQ,
+        (Jump::IfNotEQ, JumpOffset::Short(..)) => OpCode::JumpIfNotEQ,
+        (Jump::IfLT,    JumpOffset::Short(..)) => OpCode::JumpIfLT,
+        (Jump::IfNotLT, JumpOffset::Short(..)) => OpCode::JumpIfNotLT,
+        (Jump::IfLE,    JumpOffset::Short(..)) => OpCode::JumpIfLE,
+        (Jump::IfNotLE, JumpOffset::Short(..)) => OpCode::JumpIfNotLE,
+        
+        (Jump::IfEQ | Jump::IfNotEQ | Jump::IfLT | Jump::IfNotLT | Jump::IfLE | Jump::IfNotLE, JumpOffset::Long(..))
+            => panic!("compare-and-jump has no long form"),
     }
 }
 
+// identifies a dummy jump by the order it was emitted in its chunk, 
+// which is the same every time the same program is compiled
+type JumpID = (Chunk, usize);
+
 // represents the site of a dummy jump instruction that will be patched with a target later
 #[derive(Debug)]
 struct JumpSite {
+    id: JumpID,
     jump: Jump,
     offset: usize,
-    width: usize,
+    long: bool,
+    reachable: bool,  // whether the jump instruction itself can be reached
+}
+
+impl JumpSite {
+    fn width(&self) -> usize { self.jump.dummy_width(self.long) }
 }
 
 
@@ -85,48 +169,156 @@ struct JumpSite {
 pub struct CompiledProgram {
     pub program: UnloadedProgram,
     pub symbols: ChunkSymbols,
+    pub source_hash: Option<u64>,  // hash of the source text, used to detect stale compiled artifacts
+    pub warnings: Vec<CompileWarning>,  // not saved with compiled artifacts
+}
+
+impl CompiledProgram {
+    /// Write a compiled artifact that can be loaded instead of the source text
+    pub fn write_to(&self, mut write: impl Write) -> io::Result<()> {
+        self.program.write_to(&mut write)?;
+        serialize::write_option(&mut write, self.source_hash.as_ref(), |write, hash| write.write_u64(*hash))?;
+        serialize::write_symbols(&mut write, &self.symbols)
+    }
+    
+    pub fn read_from(mut read: impl Read) -> io::Result<Self> {
+        let program = UnloadedProgram::read_from(&mut read)?;
+        let source_hash = serialize::read_option(&mut read, |read| read.read_u64())?;
+        let symbols = serialize::read_symbols(&mut read)?;
+        
+        Ok(Self { program, symbols, source_hash, warnings: Vec::new() })
+    }
+}
+
+
+/// Selects the kind of bytecode that the compiler produces
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Instructions take their operands from the value stack
+    #[default]
+    Stack,
+    
+    /// Experimental. Assignments to local variables are compiled to register instructions where possible, 
+    /// which address their local and constant operands directly. Everything else uses stack instructions.
+    Register,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CompileOptions {
+    pub backend: Backend,
 }
 
 
 // Code Generator
 pub struct Compiler {
+    options: CompileOptions,
     builder: ChunkBuilder,
     scopes: ScopeTracker,
     errors: Vec<CompileError>,
+    warnings: Vec<CompileWarning>,
     symbols: ChunkSymbols,
+    
+    // imports bind their names at runtime, so they are only checked against the globals the program uses
+    imports: Vec<(InternSymbol, Option<DebugSymbol>)>,
+    global_uses: HashSet<InternSymbol>,
+    global_decls: HashSet<InternSymbol>,
+    
+    // Dummy jumps are emitted before their target is known, so they are given the short form unless
+    // a previous attempt to compile the same program found that their target was out of range.
+    jump_count: HashMap<Chunk, usize>,
+    long_jumps: HashSet<JumpID>,
+    overflow_jumps: Vec<JumpID>,
 }
 
 impl Compiler {
     pub fn new(strings: StringInterner) -> Self {
+        Self::with_options(strings, CompileOptions::default())
+    }
+    
+    pub fn with_options(strings: StringInterner, options: CompileOptions) -> Self {
         // insert symbol container for main chunk
         let mut symbols = ChunkSymbols::new();
         symbols.insert(Chunk::Main, DebugSymbolTable::new());
         
         Self {
+            options,
             builder: ChunkBuilder::with_strings(strings),
             scopes: ScopeTracker::new(),
             errors: Vec::new(),
+            warnings: Vec::new(),
             symbols,
+            imports: Vec::new(),
+            global_uses: HashSet::new(),
+            global_decls: HashSet::new(),
+            jump_count: HashMap::new(),
+            long_jumps: HashSet::new(),
+            overflow_jumps: Vec::new(),
         }
     }
     
     fn new_chunk(&mut self, info: ChunkInfo) -> CompileResult<Chunk> {
         let chunk_id = self.builder.new_chunk(info)?;
         self.symbols.entry(chunk_id)
-            .or_insert_with(DebugSymbolTable::new);
+            .or_default();
         
         Ok(chunk_id)
     }
     
-    fn get_chunk(&mut self, chunk_id: Chunk) -> CodeGenerator {
+    fn get_chunk(&mut self, chunk_id: Chunk) -> CodeGenerator<'_> {
         CodeGenerator::new(self, chunk_id)
     }
     
-    pub fn compile_program<'a>(mut self, program: impl Iterator<Item=&'a StmtMeta>) -> Result<CompiledProgram, Vec<CompileError>> {
-        for stmt in program {
-            self.push_stmt(stmt);
+    fn push_warning(&mut self, warning: CompileWarning) {
+        // the same code can be compiled more than once (e.g. finally clauses)
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+    
+    fn next_jump_id(&mut self, chunk_id: Chunk) -> JumpID {
+        let count = self.jump_count.entry(chunk_id).or_default();
+        let jump_id = (chunk_id, *count);
+        *count += 1;
+        jump_id
+    }
+    
+    pub fn compile_program<'a>(self, program: impl Iterator<Item=&'a StmtMeta> + Clone) -> Result<CompiledProgram, Vec<CompileError>> {
+        let strings = self.builder.strings().clone();
+        
+        let mut compiler = self;
+        loop {
+            for stmt in program.clone() {
+                compiler.push_stmt(stmt);
+            }
+            
+            if compiler.overflow_jumps.is_empty() || !compiler.errors.is_empty() {
+                compiler.check_unused_imports();
+                return compiler.finish();
+            }
+            
+            // start over, this time emitting long jumps for the ones that were out of range.
+            // widening jumps can put others out of range, but since the set of long jumps only grows this must terminate
+            let mut long_jumps = compiler.long_jumps;
+            long_jumps.extend(compiler.overflow_jumps);
+            
+            compiler = Compiler::with_options(strings.clone(), compiler.options);
+            compiler.long_jumps = long_jumps;
+        }
+    }
+    
+    // only done for whole programs, since later input could still use the names bound by an import
+    fn check_unused_imports(&mut self) {
+        let uses_imported_name = self.global_uses.iter()
+            .any(|name| !self.global_decls.contains(name));
+        
+        if uses_imported_name {
+            return;
+        }
+        
+        for (path, symbol) in core::mem::take(&mut self.imports).into_iter() {
+            let message = format!("unused import \"{}\"", self.builder.resolve_str(path));
+            self.push_warning(CompileWarning::new(WarningKind::UnusedImport, &message, symbol));
         }
-        self.finish()
     }
     
     pub fn push_stmt(&mut self, stmt: &StmtMeta) {
@@ -136,6 +328,10 @@ impl Compiler {
     }
     
     pub fn finish(mut self) -> Result<CompiledProgram, Vec<CompileError>> {
+        if !self.overflow_jumps.is_empty() {
+            self.errors.push(CompileErrorKind::InternalLimit("jump target out of range, use compile_program() to widen jumps automatically").into());
+        }
+        
         if self.errors.is_empty() {
             self.get_chunk(Chunk::Main)
                 .finish();
@@ -143,6 +339,8 @@ impl Compiler {
             let output = CompiledProgram {
                 program: self.builder.build(),
                 symbols: self.symbols,
+                source_hash: None,
+                warnings: self.warnings,
             };
             
             Ok(output)
@@ -150,12 +348,92 @@ impl Compiler {
             Err(self.errors)
         }
     }
+    
+    /// The interner used for the names in the AST, so that each new input can be parsed into it
+    pub fn strings_mut(&mut self) -> &mut StringInterner { self.builder.strings_mut() }
+    
+    /// Compile statements that continue a program whose earlier statements have already been run, e.g. by the REPL.
+    ///
+    /// The statements are appended to the main chunk, and the program that is returned contains only the new 
+    /// part of the main chunk, which can be run on the same global environment as the earlier statements.
+    /// Everything else (functions, constants, strings) is shared with the earlier statements, so their IDs stay valid.
+    /// If the statements fail to compile, they are discarded and the compiler can be used again.
+    pub fn compile_incremental<'a>(&mut self, stmts: impl Iterator<Item=&'a StmtMeta> + Clone) -> Result<CompiledProgram, Vec<CompileError>> {
+        let checkpoint = self.builder.checkpoint();
+        let main_jumps = self.jump_count.get(&Chunk::Main).copied().unwrap_or(0);
+        
+        loop {
+            for stmt in stmts.clone() {
+                self.push_stmt(stmt);
+            }
+            
+            if !self.errors.is_empty() {
+                self.rewind(&checkpoint, main_jumps);
+                
+                // any scopes that were left open by an error are gone as well
+                self.scopes = ScopeTracker::new();
+                self.warnings.clear();
+                return Err(core::mem::take(&mut self.errors));
+            }
+            
+            if self.overflow_jumps.is_empty() {
+                break;
+            }
+            
+            // same as compile_program(), except that only the new statements are compiled again
+            let overflow_jumps = core::mem::take(&mut self.overflow_jumps);
+            self.long_jumps.extend(overflow_jumps);
+            self.rewind(&checkpoint, main_jumps);
+        }
+        
+        self.get_chunk(Chunk::Main).finish();
+        
+        // offsets in the main chunk are relative to the new part
+        let main_offset = checkpoint.main_len();
+        let mut symbols = self.symbols.clone();
+        let main_symbols = symbols.get_mut(&Chunk::Main).unwrap();
+        let mut new_symbols = main_symbols.iter()
+            .filter(|(offset, _)| *offset >= main_offset)
+            .fold(DebugSymbolTable::new(), |mut table, (offset, symbol)| {
+                table.insert(offset - main_offset, *symbol);
+                table
+            });
+        for (offset, name) in main_symbols.iter_name_hints().filter(|(offset, _)| *offset >= main_offset) {
+            new_symbols.insert_name_hint(offset - main_offset, name);
+        }
+        *main_symbols = new_symbols;
+        
+        let output = CompiledProgram {
+            program: self.builder.build_from(main_offset),
+            symbols,
+            source_hash: None,
+            warnings: core::mem::take(&mut self.warnings),
+        };
+        Ok(output)
+    }
+    
+    // undo everything that was emitted since the checkpoint, so that it can be compiled again with the same jump IDs
+    fn rewind(&mut self, checkpoint: &BuilderCheckpoint, main_jumps: usize) {
+        self.builder.restore(checkpoint);
+        
+        let chunk_count = checkpoint.chunk_count();
+        let is_discarded = |chunk_id: &Chunk| matches!(chunk_id, Chunk::Function(fun_id) if usize::from(*fun_id) >= chunk_count);
+        self.symbols.retain(|chunk_id, _| !is_discarded(chunk_id));
+        self.symbols.get_mut(&Chunk::Main).unwrap()
+            .truncate(checkpoint.main_len());
+        
+        self.jump_count.retain(|chunk_id, _| !is_discarded(chunk_id));
+        self.jump_count.insert(Chunk::Main, main_jumps);
+        self.overflow_jumps.clear();
+    }
 }
 
 struct CodeGenerator<'c> {
     compiler: &'c mut Compiler,
     chunk_id: Chunk,
     symbols: Vec<Option<DebugSymbol>>,
+    reachable: bool,  // false if control flow can never reach the current offset
+    unreachable_reported: bool,  // only warn once for each stretch of unreachable code
 }
 
 impl<'c> CodeGenerator<'c> {
@@ -163,6 +441,8 @@ impl<'c> CodeGenerator<'c> {
         Self {
             compiler, chunk_id,
             symbols: Vec::new(),
+            reachable: true,
+            unreachable_reported: false,
         }
     }
 }
@@ -170,26 +450,14 @@ impl<'c> CodeGenerator<'c> {
 impl CodeGenerator<'_> {
     
     pub fn push_stmt(&mut self, stmt: &StmtMeta) -> CompileResult<()> {
-        let symbol = stmt.debug_symbol();
-        self.push_symbol(Some(*symbol));
-        
-        let result = self.compile_stmt(stmt.variant());
-        if let Err(mut error) = result {
-            let symbol = self.symbols.iter().rev()
-                .find_map(|s| s.as_ref());
-            if let Some(symbol) = symbol {
-                error = error.with_symbol(*symbol);
-            }
-            
-            Err(error)
-        } else {
-            self.pop_symbol();
-            Ok(())
-        }
+        self.compile_stmt_with_symbol(stmt)
     }
     
     pub fn finish(mut self) {
         self.symbols.clear();
+        if !self.reachable {
+            return;
+        }
         match self.chunk_id {
             Chunk::Main => self.emit_instr(OpCode::Exit),
             Chunk::Function(..) => self.emit_instr(OpCode::Return),
@@ -240,10 +508,23 @@ impl CodeGenerator<'_> {
             .insert(offset, symbol)
     }
     
-    fn create_chunk(&mut self, metadata: ChunkInfo) -> CompileResult<CodeGenerator> {
+    fn create_chunk(&mut self, metadata: ChunkInfo) -> CompileResult<CodeGenerator<'_>> {
         let chunk_id = self.compiler.new_chunk(metadata)?;
         Ok(self.compiler.get_chunk(chunk_id))
     }
+    
+    fn emit_warning(&mut self, kind: WarningKind, message: &str, symbol: Option<DebugSymbol>) {
+        self.compiler.push_warning(CompileWarning::new(kind, message, symbol))
+    }
+    
+    // returns false if the code at the current offset is unreachable, warning about it the first time
+    fn check_reachable(&mut self, symbol: Option<&DebugSymbol>) -> bool {
+        if !self.reachable && !self.unreachable_reported {
+            self.unreachable_reported = true;
+            self.emit_warning(WarningKind::UnreachableCode, "unreachable code", symbol.copied());
+        }
+        self.reachable
+    }
 }
 
 
@@ -257,6 +538,10 @@ impl CodeGenerator<'_> {
         }
         
         self.chunk_mut().push_byte(opcode);
+        
+        if matches!(opcode, OpCode::Exit | OpCode::Return | OpCode::Error) {
+            self.reachable = false;
+        }
     }
     
     fn emit_instr_byte(&mut self, opcode: OpCode, byte: u8) {
@@ -280,6 +565,13 @@ impl CodeGenerator<'_> {
         self.chunk_mut().push_byte(opcode);
         self.chunk_mut().extend_bytes(bytes);
     }
+    
+    // each cached instruction gets its own inline cache slot
+    fn emit_cached_instr(&mut self, opcode: OpCode) -> CompileResult<()> {
+        let index = self.builder_mut().new_cache_slot()?;
+        self.emit_instr_data(opcode, &index.to_le_bytes());
+        Ok(())
+    }
 }
 
 ///////// Patching Bytecode /////////
@@ -346,8 +638,16 @@ impl CodeGenerator<'_> {
 ///////// Jumps /////////
 impl CodeGenerator<'_> {
     fn emit_jump_instr(&mut self, jump: Jump, target: usize) -> CompileResult<()> {
+        if let Some((cmp_opcode, unfused)) = jump.unfuse() {
+            let jump_end = self.current_offset() + jump.dummy_width(false);
+            if matches!(Self::calc_jump_offset(jump_end, target)?, JumpOffset::Long(..)) {
+                self.emit_instr(cmp_opcode);
+                return self.emit_jump_instr(unfused, target);
+            }
+        }
+        
         let jump_site = self.current_offset();
-        let guess_width = jump.dummy_width();  // guess the width of the jump instruction
+        let guess_width = jump.dummy_width(false);  // guess the width of the jump instruction
         
         let mut jump_offset = Self::calc_jump_offset(jump_site + guess_width, target)?;
         let mut jump_opcode = get_jump_opcode(jump, jump_offset);
@@ -360,7 +660,7 @@ impl CodeGenerator<'_> {
             
             // if we *still* don't have the right width, just abort
             if new_width != new_opcode.instr_len() {
-                return Err("could not calculate jump offset".into());
+                return Err(CompileErrorKind::InternalLimit("could not calculate jump offset").into());
             }
             
             jump_offset = new_offset;
@@ -371,48 +671,67 @@ impl CodeGenerator<'_> {
             JumpOffset::Short(offset) => self.emit_instr_data(jump_opcode, &offset.to_le_bytes()),
             JumpOffset::Long(offset)  => self.emit_instr_data(jump_opcode, &offset.to_le_bytes()),
         }
+        
+        if jump == Jump::Uncond {
+            self.reachable = false;
+        }
         Ok(())
     }
     
     fn emit_dummy_jump(&mut self, jump: Jump) -> JumpSite {
+        let id = self.compiler.next_jump_id(self.chunk_id);
+        let long = self.compiler.long_jumps.contains(&id);
+        
+        let jump = match jump.unfuse() {
+            Some((cmp_opcode, unfused)) if long => {
+                self.emit_instr(cmp_opcode);
+                unfused
+            },
+            _ => jump,
+        };
+        
         let offset = self.current_offset();
         let jump_site = JumpSite {
-            jump, offset,
-            width: jump.dummy_width(),
+            id, jump, offset, long,
+            reachable: self.reachable,
         };
         
-        self.emit_dummy_instr(jump_site.width);
+        self.emit_dummy_instr(jump_site.width());
+        
+        if jump == Jump::Uncond {
+            self.reachable = false;
+        }
         
         jump_site
     }
     
+    // jump targets are normally patched once code generation reaches them, 
+    // so the current offset is reachable if the jump instruction was
     fn patch_jump_instr(&mut self, jump: &JumpSite, target: usize) -> CompileResult<()> {
-        let jump_type = jump.jump;
-        let jump_site = jump.offset;
-        let dummy_width = jump.width;
+        if jump.reachable && !self.reachable {
+            self.reachable = true;
+            self.unreachable_reported = false;
+        }
         
-        let mut jump_offset = Self::calc_jump_offset(jump_site + dummy_width, target)?;
-        let mut jump_opcode = get_jump_opcode(jump_type, jump_offset);
+        let jump_offset = match Self::calc_jump_offset(jump.offset + jump.width(), target)? {
+            // a long jump can hold any offset, but the width of the dummy can no longer change
+            JumpOffset::Short(offset) if jump.long => JumpOffset::Long(offset.into()),
         
-        if dummy_width != jump_opcode.instr_len() {
-            // need to recalculate offset with the new width
-            let new_width = jump_opcode.instr_len();
-            let new_offset = Self::calc_jump_offset(jump_site + new_width, target)?;
-            let new_opcode = get_jump_opcode(jump_type, new_offset);
-            
-            // if we *still* don't have the right width, just abort
-            if new_width != new_opcode.instr_len() {
-                return Err("could not calculate jump offset".into());
+            JumpOffset::Long(..) if !jump.long => {
+                // the program will need to be compiled again with this jump widened
+                self.compiler.overflow_jumps.push(jump.id);
+                return Ok(());
             }
             
-            jump_offset = new_offset;
-            jump_opcode = new_opcode;
-            self.chunk_mut().resize_patch(jump_site, dummy_width, new_width);
-        }
+            jump_offset => jump_offset,
+        };
+        
+        let jump_opcode = get_jump_opcode(jump.jump, jump_offset);
+        debug_assert!(jump_opcode.instr_len() == jump.width());
         
         match jump_offset {
-            JumpOffset::Short(offset) => self.patch_instr_data(jump_site, jump_opcode, &offset.to_le_bytes()),
-            JumpOffset::Long(offset)  => self.patch_instr_data(jump_site, jump_opcode, &offset.to_le_bytes()),
+            JumpOffset::Short(offset) => self.patch_instr_data(jump.offset, jump_opcode, &offset.to_le_bytes()),
+            JumpOffset::Long(offset)  => self.patch_instr_data(jump.offset, jump_opcode, &offset.to_le_bytes()),
         }
         Ok(())
     }
@@ -431,7 +750,7 @@ impl CodeGenerator<'_> {
             return Ok(JumpOffset::Long(offset));
         }
         
-        Err("could not calculate jump offset".into())
+        Err(CompileErrorKind::InternalLimit("could not calculate jump offset").into())
     }
 }
 
@@ -464,7 +783,11 @@ impl CodeGenerator<'_> {
     
     fn emit_end_scope(&mut self) -> Scope {
         let scope = self.scopes_mut().pop_scope();
-        self.emit_scope_drop(&(&scope).into());
+        self.check_unused_locals(scope.locals().iter());
+        
+        if self.reachable {
+            self.emit_scope_drop(&(&scope).into());
+        }
         scope
     }
     
@@ -502,7 +825,7 @@ impl CodeGenerator<'_> {
     
     // If the local name cannot be found, no instructions are emitted and None is returned
     fn try_emit_load_local(&mut self, name: &LocalName) -> Option<u16> {
-        if let Some(index) = self.scopes().resolve_local(name).map(|local| local.index()) {
+        if let Some(index) = self.scopes_mut().read_local(name).map(|local| local.index()) {
             self.emit_load_local_index(index);
             Some(index)
         } else{
@@ -543,7 +866,7 @@ impl CodeGenerator<'_> {
     fn emit_create_temporary(&mut self, access: Access) -> CompileResult<LocalIndex> {
         debug_assert!(self.scopes().is_temporary_scope());
         
-        match self.scopes_mut().insert_local(access, LocalName::Anonymous)? {
+        match self.scopes_mut().insert_local(access, LocalName::Anonymous, None)? {
             InsertLocal::CreateNew(local_index) => {
                 self.emit_instr(OpCode::InsertLocal);
                 Ok(local_index)
@@ -563,9 +886,11 @@ impl CodeGenerator<'_> {
     fn compile_stmt_with_symbol(&mut self, stmt: &StmtMeta) -> CompileResult<()> {
         let symbol = stmt.debug_symbol();
         self.push_symbol(Some(*symbol));
-        self.compile_stmt(stmt.variant())?;
+        let result = self.compile_stmt(stmt.variant());
         self.pop_symbol();
-        Ok(())
+        
+        // errors take the innermost symbol available
+        result.map_err(|error| error.with_symbol(*symbol))
     }
     
     fn compile_stmt(&mut self, stmt: &Stmt) -> CompileResult<()> {
@@ -576,23 +901,72 @@ impl CodeGenerator<'_> {
             
             Stmt::ForLoop { label, pattern, iter, body } => self.compile_for_loop(label.as_ref(), pattern, iter, body)?,
             
+            Stmt::TryExcept { body, handler, finally } => self.compile_try_except(body, handler.as_ref(), finally.as_ref())?,
+            
             Stmt::Assert(expr) => {
                 self.compile_expr(expr)?;
                 self.emit_instr(OpCode::Assert);
                 self.emit_instr(OpCode::Pop);
             }
             
+            Stmt::Import(path) => {
+                let symbol = self.current_symbol();
+                self.compiler.imports.push((*path, symbol));
+                
+                self.emit_load_const(Constant::from(*path))?;
+                self.emit_instr(OpCode::Import);
+            }
+            
+            Stmt::Export(expr) => self.compile_export(expr)?,
+            
             Stmt::Expression(expr) => {
+                // the value of an assignment statement is discarded, so it doesn't need to go through the stack
+                if self.compiler.options.backend == Backend::Register && self.try_emit_register_assignment(expr)? {
+                    return Ok(());
+                }
+                
                 self.compile_expr(expr)?;
-                self.emit_instr(OpCode::Pop);
+                if self.reachable {
+                    self.emit_instr(OpCode::Pop);
+                }
             },
         }
         Ok(())
     }
     
+    fn compile_export(&mut self, expr: &Expr) -> CompileResult<()> {
+        if !self.scopes().is_global_scope() {
+            return Err(CompileErrorKind::InvalidExport("\"export\" is only allowed at the top level of a module").into());
+        }
+        
+        let mut names = Vec::new();
+        if let Expr::Assignment(assignment) = expr {
+            if assignment.op.is_none() {
+                collect_decl_names(assignment.action, &assignment.lhs, &mut names);
+            }
+        }
+        
+        if names.is_empty() {
+            return Err(CompileErrorKind::InvalidExport("only declarations can be exported").into());
+        }
+        
+        self.compile_expr(expr)?;
+        self.emit_instr(OpCode::Pop);
+        
+        for name in names.into_iter() {
+            self.emit_load_const(Constant::from(name))?;
+            self.emit_instr(OpCode::Export);
+        }
+        Ok(())
+    }
+    
     fn compile_stmt_list(&mut self, stmt_list: &StmtList) -> CompileResult<()> {
         // compile stmt suite
         for stmt in stmt_list.iter() {
+            // anything after a statement that never completes is dead code
+            if !self.check_reachable(Some(stmt.debug_symbol())) {
+                break;
+            }
             self.compile_stmt_with_symbol(stmt)?;
         }
         
@@ -603,21 +977,34 @@ impl CodeGenerator<'_> {
     fn compile_stmt_block(&mut self, stmt_list: &StmtList) -> CompileResult<()> {
         self.compile_stmt_list(stmt_list)?;
         if let Some(control) = stmt_list.end_control() {
-            self.compile_control_flow(control)?;
+            if self.check_reachable(control.debug_symbol()) {
+                self.compile_control_flow(control)?;
+            }
         }
         Ok(())
     }
     
-    fn compile_expr_block(&mut self, suite: &ExprBlock) -> CompileResult<()> {
+    // if the block is in tail position, so is its result expression
+    fn compile_expr_block(&mut self, suite: &ExprBlock, tail: bool) -> CompileResult<()> {
         let stmt_list = suite.stmt_list();
         self.compile_stmt_list(stmt_list)?;
         
+        let reachable = match (stmt_list.end_control(), suite.result()) {
+            (Some(control), _) => self.check_reachable(control.debug_symbol()),
+            (None, Some(expr)) => self.check_reachable(Some(expr.debug_symbol())),
+            (None, None) => self.reachable,
+        };
+        
+        if !reachable {
+            return Ok(());
+        }
+        
         if stmt_list.end_control().is_none() {
             // result expression
-            if let Some(expr) = suite.result() {
-                self.compile_expr_with_symbol(expr)?;
-            } else {
-                self.emit_instr(OpCode::Nil); // implicit nil
+            match suite.result() {
+                Some(expr) if tail => self.compile_tail_expr_with_symbol(expr)?,
+                Some(expr) => self.compile_expr_with_symbol(expr)?,
+                None => self.emit_instr(OpCode::Nil), // implicit nil
             }
         }
         
@@ -644,13 +1031,7 @@ impl CodeGenerator<'_> {
             
             ControlFlow::Return { expr, symbol } => {
                 self.push_symbol(*symbol);
-                
-                match expr {
-                    Some(expr) => self.compile_expr(expr)?,
-                    None => self.emit_instr(OpCode::Nil),
-                }
-                
-                self.emit_instr(OpCode::Return);
+                self.compile_return_control(expr.as_deref())?;
                 self.pop_symbol();
             }
         }
@@ -661,12 +1042,7 @@ impl CodeGenerator<'_> {
         // find the target scope
         let target_depth = match self.scopes().resolve_control_flow(ControlFlowTarget::Break(label.copied())) {
             Some(scope) => scope.depth(),
-            None => {
-                let message =
-                    if label.is_some() { "can't find loop or block with matching label for \"break\"" }
-                    else { "\"break\" outside of loop or block" };
-                return Err(message.into());
-            },
+            None => return Err(CompileErrorKind::CantResolveBreak { labeled: label.is_some() }.into()),
         };
         
         // drop all scopes up to and including the target
@@ -675,20 +1051,9 @@ impl CodeGenerator<'_> {
             .map(ScopeDrop::from)
             .collect();
         
-        let (target, through_scopes) = scope_drop.split_last().unwrap();
-        for scope in through_scopes.iter() {
-            self.emit_scope_drop(scope);
-            
-            // expression blocks leave their value on the stack
-            // (this is helped by the fact that break/contine must come last in a list of statements)
-            // so if we break through an expression block we need to pop its value
-            if scope.tag.is_expr_block() {
-                self.emit_instr(OpCode::Pop);
-            }
-        }
-        self.emit_scope_drop(target); // drop target scope
-        
         // if breaking from an expression block, emit the expression value before jumping
+        // this must be done before dropping any scopes, since the expression may refer to their locals
+        let target = scope_drop.last().unwrap();
         if target.tag.is_expr_block() {
             if let Some(expr) = expr {
                 self.compile_expr(expr)?;
@@ -696,7 +1061,15 @@ impl CodeGenerator<'_> {
                 self.emit_instr(OpCode::Nil);
             }
         } else if expr.is_some() {
-            return Err("\"break\" with value outside of block expression".into())
+            return Err(CompileErrorKind::InvalidBreakWithValue.into())
+        }
+        
+        self.emit_exit_handlers(target_depth)?;
+        
+        // since break/continue must come last in a list of statements, the blocks that we 
+        // are jumping out of have not produced a value yet, so only their locals need to be dropped
+        for scope in scope_drop.iter() {
+            self.emit_scope_drop(scope);
         }
         
         // emit jump site, register with scope
@@ -711,16 +1084,46 @@ impl CodeGenerator<'_> {
         
     }
     
+    fn compile_return_control(&mut self, expr: Option<&Expr>) -> CompileResult<()> {
+        if self.scopes().function_kind() == Some(FunctionKind::Constructor) {
+            if expr.is_some() {
+                return Err(CompileErrorKind::InvalidReturnValue.into());
+            }
+            self.emit_load_receiver();
+        } else {
+            match expr {
+                Some(expr) => {
+                    self.compile_tail_expr(expr)?;
+                    if !self.reachable {
+                        return Ok(());  // every branch ended in a tail call
+                    }
+                },
+                None => self.emit_instr(OpCode::Nil),
+            }
+        }
+        
+        self.emit_exit_handlers(0)?;
+        
+        // the VM discards the call frame's locals when returning,
+        // but any captured locals in the enclosing scopes need to be closed first
+        let close_upvals: Vec<LocalIndex> = self.scopes().iter_scopes()
+            .flat_map(|scope| scope.locals().iter())
+            .filter_map(|local| if local.captured() { Some(local.index()) } else { None })
+            .collect();
+        
+        for local_index in close_upvals.into_iter() {
+            self.emit_close_upvalue(local_index);
+        }
+        
+        self.emit_instr(OpCode::Return);
+        Ok(())
+    }
+    
     fn compile_continue_control(&mut self, label: Option<&Label>) -> CompileResult<()> {
         // find the target scope
         let target_depth = match self.scopes().resolve_control_flow(ControlFlowTarget::Continue(label.copied())) {
             Some(scope) => scope.depth(),
-            None => {
-                let message =
-                    if label.is_some() { "can't find loop with matching label for \"continue\"" }
-                    else { "\"continue\" outside of loop" };
-                return Err(message.into());
-            }
+            None => return Err(CompileErrorKind::CantResolveContinue { labeled: label.is_some() }.into()),
         };
         
         // drop all scopes up to and including the target
@@ -729,15 +1132,10 @@ impl CodeGenerator<'_> {
             .map(ScopeDrop::from)
             .collect();
         
+        self.emit_exit_handlers(target_depth)?;
+        
         for scope in scope_drop.iter() {
             self.emit_scope_drop(scope);
-            
-            // expression blocks leave their value on the stack
-            // (this is helped by the fact that break/contine must come last in a list of statements)
-            // so if we jump out of an expression block we need to pop its value
-            if scope.tag.is_expr_block() {
-                self.emit_instr(OpCode::Pop);
-            }
         }
         
         // emit jump site, register with scope
@@ -751,6 +1149,132 @@ impl CodeGenerator<'_> {
         Ok(())
     }
     
+    // pop the error handlers of any try blocks that control flow is leaving and run their finally clauses
+    fn emit_exit_handlers(&mut self, target_depth: usize) -> CompileResult<()> {
+        let handler_depths: Vec<usize> = self.scopes().iter_scopes()
+            .take_while(|scope| scope.depth() >= target_depth)
+            .filter(|scope| scope.handler().is_some())
+            .map(|scope| scope.depth())
+            .collect();
+        
+        for depth in handler_depths.into_iter() {
+            self.emit_instr(OpCode::PopHandler);
+            
+            // the handler is removed while its finally clause is compiled, so that 
+            // control flow inside the finally clause does not try to exit it again
+            let handler = self.scopes_mut().iter_scopes_mut()
+                .find(|scope| scope.depth() == depth)
+                .and_then(|scope| scope.take_handler())
+                .unwrap();
+            
+            let result = match handler.finally() {
+                Some(finally) => self.compile_finally(finally),
+                None => Ok(()),
+            };
+            
+            self.scopes_mut().iter_scopes_mut()
+                .find(|scope| scope.depth() == depth)
+                .unwrap()
+                .set_handler(handler);
+            
+            result?;
+        }
+        
+        Ok(())
+    }
+    
+    fn compile_finally(&mut self, finally: &StmtList) -> CompileResult<()> {
+        self.emit_begin_scope(None, ScopeTag::Branch);
+        self.compile_stmt_block(finally)?;
+        self.emit_end_scope();
+        Ok(())
+    }
+    
+    fn compile_try_except(&mut self, body: &StmtList, except: Option<&ExceptClause>, finally: Option<&StmtList>) -> CompileResult<()> {
+        let mut end_sites = Vec::new();
+        
+        // try block
+        let handler_site = self.emit_dummy_jump(Jump::PushHandler);
+        
+        self.emit_begin_scope(None, ScopeTag::Branch)
+            .set_handler(ErrorHandler::new(finally.cloned()));
+        
+        self.compile_stmt_block(body)?;
+        self.emit_end_scope();
+        
+        if self.reachable {
+            self.emit_instr(OpCode::PopHandler);
+            if let Some(finally) = finally {
+                self.compile_finally(finally)?;
+            }
+        }
+        if self.reachable {
+            end_sites.push(self.emit_dummy_jump(Jump::Uncond));
+        }
+        
+        // except clause, the caught error is on the stack here
+        self.patch_jump_instr(&handler_site, self.current_offset())?;
+        
+        if let Some(except) = except {
+            self.emit_begin_scope(None, ScopeTag::Branch);
+            
+            if let Some(name) = except.name() {
+                self.compile_decl_local_name(Access::ReadOnly, *name)?;
+            }
+            self.emit_instr(OpCode::Pop);
+            
+            // an error raised inside the except clause must still run the finally clause
+            let reraise_site = match finally {
+                Some(..) => Some(self.emit_dummy_jump(Jump::PushHandler)),
+                None => None,
+            };
+            
+            let handler_scope = self.emit_begin_scope(None, ScopeTag::Branch);
+            if let Some(finally) = finally {
+                handler_scope.set_handler(ErrorHandler::new(Some(finally.clone())));
+            }
+            self.compile_stmt_block(except.body())?;
+            self.emit_end_scope();
+            
+            if reraise_site.is_some() && self.reachable {
+                self.emit_instr(OpCode::PopHandler);
+            }
+            
+            let except_scope = self.emit_end_scope();
+            
+            if let Some(finally) = finally {
+                if self.reachable {
+                    self.compile_finally(finally)?;
+                }
+            }
+            
+            if let Some(reraise_site) = reraise_site {
+                if self.reachable {
+                    end_sites.push(self.emit_dummy_jump(Jump::Uncond));
+                }
+                
+                // discard the except clause binding before re-raising the new error
+                self.patch_jump_instr(&reraise_site, self.current_offset())?;
+                self.emit_scope_drop(&(&except_scope).into());
+            }
+        }
+        
+        // re-raise any error that was not handled
+        if (except.is_none() || finally.is_some()) && self.reachable {
+            if let Some(finally) = finally {
+                self.compile_finally(finally)?;
+            }
+            self.emit_instr(OpCode::Error);
+        }
+        
+        let end_target = self.current_offset();
+        for end_site in end_sites.iter() {
+            self.patch_jump_instr(end_site, end_target)?;
+        }
+        
+        Ok(())
+    }
+    
     fn compile_loop(&mut self, label: Option<&Label>, body: &StmtList) -> CompileResult<()> {
         
         let loop_target = self.current_offset();
@@ -760,7 +1284,9 @@ impl CodeGenerator<'_> {
         self.compile_stmt_block(body)?;
         let loop_scope = self.emit_end_scope();
         
-        self.emit_jump_instr(Jump::Uncond, loop_target)?;
+        if self.reachable {
+            self.emit_jump_instr(Jump::Uncond, loop_target)?;
+        }
         
         // finalize scope
         let break_target = self.current_offset();
@@ -771,12 +1297,16 @@ impl CodeGenerator<'_> {
     }
     
     fn compile_while_loop(&mut self, label: Option<&Label>, condition: &Expr, body: &StmtList) -> CompileResult<()> {
+    
+        // "while true" is the usual way to write a loop that only exits with "break" or "return"
+        if !matches!(condition, Expr::Atom(Atom::BooleanLiteral(true))) {
+            self.check_constant_condition(condition);
+        }
         
         // first iteration conditional jump
         let continue_target = self.current_offset();
-        self.compile_expr(condition)?;
-        
-        let end_jump_site = self.emit_dummy_jump(Jump::PopIfFalse);
+        let end_jump = self.compile_jump_condition(condition, Jump::PopIfFalse)?;
+        let end_jump_site = self.emit_dummy_jump(end_jump);
         
         let loop_target = self.current_offset();
         
@@ -785,8 +1315,10 @@ impl CodeGenerator<'_> {
         let loop_scope = self.emit_end_scope();
         
         // rest iteration conditional jump
-        self.compile_expr(condition)?;
-        self.emit_jump_instr(Jump::PopIfTrue, loop_target)?;
+        if self.reachable {
+            let loop_jump = self.compile_jump_condition(condition, Jump::PopIfTrue)?;
+            self.emit_jump_instr(loop_jump, loop_target)?;
+        }
         
         self.patch_jump_instr(&end_jump_site, self.current_offset())?;
         
@@ -798,6 +1330,22 @@ impl CodeGenerator<'_> {
         Ok(())
     }
     
+    // compiles a condition that will be followed by a conditional jump (that pops the condition)
+    // returns the jump to emit, which will be a compare-and-jump if the condition is a comparison
+    fn compile_jump_condition(&mut self, condition: &Expr, jump: Jump) -> CompileResult<Jump> {
+        if let Expr::BinaryOp(op, operands) = condition {
+            if let Some(fused) = jump.fuse_compare(*op) {
+                let (lhs, rhs) = &**operands;
+                self.compile_expr_with_symbol(lhs)?;
+                self.compile_expr_with_symbol(rhs)?;
+                return Ok(fused);
+            }
+        }
+        
+        self.compile_expr(condition)?;
+        Ok(jump)
+    }
+    
     fn compile_for_loop(&mut self, label: Option<&Label>, pattern: &Pattern, iter: &Expr, body: &StmtList) -> CompileResult<()> {
         
         self.emit_begin_scope(label, ScopeTag::Loop);
@@ -824,16 +1372,18 @@ impl CodeGenerator<'_> {
         
         // rest iteration conditional jump
         // should have just [ ... iter state[N] ] on the stack here
-        self.emit_jump_instr(Jump::IfTrue, loop_target)?;
-        
-        let break_target = self.current_offset();
-        self.emit_instr_byte(OpCode::Drop, 2); // drop [ iter state ]
+        if self.reachable {
+            self.emit_jump_instr(Jump::IfTrue, loop_target)?;
+        }
         
         // finalize scope
+        let break_target = self.current_offset();
         self.patch_jump_instr(&end_jump_site, break_target)?;
         self.patch_break_sites(&loop_scope, break_target)?;
         self.patch_continue_sites(&loop_scope, continue_target)?;
         
+        self.emit_instr_byte(OpCode::Drop, 2); // drop [ iter state ]
+        
         Ok(())
     }
 }
@@ -851,9 +1401,10 @@ impl CodeGenerator<'_> {
     fn compile_expr_with_symbol(&mut self, expr: &ExprMeta) -> CompileResult<()> {
         let symbol = expr.debug_symbol();
         self.push_symbol(Some(*symbol));
-        self.compile_expr(expr.variant())?;
+        let result = self.compile_expr(expr.variant());
         self.pop_symbol();
-        Ok(())
+        
+        result.map_err(|error| error.with_symbol(*symbol))
     }
     
     fn compile_expr(&mut self, expr: &Expr) -> CompileResult<()> {
@@ -879,18 +1430,30 @@ impl CodeGenerator<'_> {
             },
             
             Expr::Tuple(items) => self.compile_tuple(items)?,
+            Expr::List(items) => self.compile_list(items)?,
+            
+            Expr::Dict(entries) => self.compile_dict(entries)?,
+            
+            Expr::Concat(parts) => self.compile_concat(parts)?,
             
             Expr::Table(_fields) => unimplemented!(),
             
             // unpacking is only allowed in invocation, tuple literals, and by itself in parentheses
             // note: assignment uses *packing*, not unpacking, which is the Pattern dual of packing.
-            Expr::Unpack(Some(..)) => return Err("unpack expression must be enclosed in parentheses".into()),
-            Expr::Unpack(None) => return Err("\"...\" is not allowed here".into()),
+            Expr::Unpack(Some(..)) => return Err(CompileErrorKind::InvalidUnpack("unpack expression must be enclosed in parentheses").into()),
+            Expr::Unpack(None) => return Err(CompileErrorKind::InvalidUnpack("\"...\" is not allowed here").into()),
             
-            Expr::Block { label, suite } => self.compile_block_expression(label.as_ref(), suite)?,
-            Expr::IfExpr { branches, else_clause } => self.compile_if_expression(branches, else_clause.as_ref().map(|expr| &**expr))?,
+            Expr::Block { label, suite } => self.compile_block_expression(label.as_ref(), suite, false)?,
+            Expr::IfExpr { branches, else_clause } => self.compile_if_expression(branches, else_clause.as_ref().map(|expr| &**expr), false)?,
+            Expr::Match { subject, cases, else_clause } => self.compile_match_expression(subject, cases, else_clause.as_deref(), false)?,
             
             Expr::FunctionDef(fundef) => self.compile_function_def(fundef)?,
+            Expr::ClassDef(classdef) => self.compile_class_def(classdef)?,
+            
+            Expr::Raise(expr) => {
+                self.compile_expr(expr)?;
+                self.emit_instr(OpCode::Error);
+            },
         }
         Ok(())
     }
@@ -915,6 +1478,54 @@ impl CodeGenerator<'_> {
         Ok(())
     }
     
+    fn compile_list(&mut self, expr_list: &[ExprMeta]) -> CompileResult<()> {
+        match self.compile_unpack_sequence(expr_list)? {
+            Unpack::Empty => self.emit_instr_byte(OpCode::BuildList, 0),
+            
+            Unpack::Static(len) => {
+                if let Ok(len) = u8::try_from(len) {
+                    self.emit_instr_byte(OpCode::BuildList, len);
+                } else {
+                    self.compile_integer(len)?;
+                    self.emit_instr(OpCode::BuildListN);
+                }
+            }
+            
+            Unpack::Dynamic => {
+                self.emit_instr(OpCode::BuildListN);
+            }
+        }
+        Ok(())
+    }
+    
+    fn compile_dict(&mut self, entries: &[DictEntry]) -> CompileResult<()> {
+        for entry in entries.iter() {
+            self.compile_expr_with_symbol(&entry.key)?;
+            self.compile_expr_with_symbol(&entry.value)?;
+        }
+        
+        if let Ok(len) = u8::try_from(entries.len()) {
+            self.emit_instr_byte(OpCode::BuildDict, len);
+        } else {
+            let len = IntType::try_from(entries.len())
+                .map_err(|_| CompileErrorKind::InternalLimit("dict literal is too large"))?;
+            self.compile_integer(len)?;
+            self.emit_instr(OpCode::BuildDictN);
+        }
+        Ok(())
+    }
+    
+    fn compile_concat(&mut self, parts: &[ExprMeta]) -> CompileResult<()> {
+        let count = u8::try_from(parts.len())
+            .map_err(|_| CompileErrorKind::InternalLimit("too many parts in interpolated string"))?;
+        
+        for part in parts.iter() {
+            self.compile_expr_with_symbol(part)?;
+        }
+        self.emit_instr_byte(OpCode::Concat, count);
+        Ok(())
+    }
+    
     // compiles to a sequence of values
     fn compile_unpack_sequence(&mut self, seq: &[ExprMeta]) -> CompileResult<Unpack> {
         if seq.is_empty() {
@@ -928,7 +1539,7 @@ impl CodeGenerator<'_> {
         
         for expr in rest.iter() {
             match expr.variant() {
-                Expr::Unpack(None) => return Err("need a value to unpack".into()),
+                Expr::Unpack(None) => return Err(CompileErrorKind::InvalidUnpack("need a value to unpack").into()),
                 
                 Expr::Unpack(Some(unpack)) => {
                     let symbol = expr.debug_symbol();
@@ -956,7 +1567,7 @@ impl CodeGenerator<'_> {
                 _ => {
                     self.compile_expr_with_symbol(expr)?;
                     static_len = static_len.checked_add(1)
-                        .ok_or("unpack length limit exceeded")?;
+                        .ok_or(CompileErrorKind::InternalLimit("unpack length limit exceeded"))?;
                 }
             }
         }
@@ -964,7 +1575,7 @@ impl CodeGenerator<'_> {
         // if the last item is an unpack expression, it does not need to use the local accumulator
         // TODO should there be a dedicated accumulator register?
         match last.variant() {
-            Expr::Unpack(None) => return Err("need a value to unpack".into()),
+            Expr::Unpack(None) => Err(CompileErrorKind::InvalidUnpack("need a value to unpack").into()),
             
             Expr::Unpack(Some(unpack)) => {
                 let symbol = last.debug_symbol();
@@ -994,7 +1605,7 @@ impl CodeGenerator<'_> {
             _ => {
                 self.compile_expr_with_symbol(last)?;
                 static_len = static_len.checked_add(1)
-                    .ok_or("unpack length limit exceeded")?;
+                    .ok_or(CompileErrorKind::InternalLimit("unpack length limit exceeded"))?;
                     
                 if let Some(local_index) = unpack_len {
                     self.emit_load_local_index(local_index);
@@ -1023,23 +1634,24 @@ impl CodeGenerator<'_> {
             Atom::BooleanLiteral(false) => self.emit_instr(OpCode::False),
             
             Atom::IntegerLiteral(value) => self.compile_integer(*value)?,
+            Atom::BigIntegerLiteral(text) => self.emit_load_const(Constant::BigInt(text.to_usize()))?,
             Atom::FloatLiteral(value) => self.compile_float(*value)?,
             
             Atom::StringLiteral(value) => self.emit_load_const(Constant::from(*value))?,
             Atom::Identifier(name) => self.compile_name_lookup(name)?,
             
-            // Atom::Self_ => unimplemented!(),
-            // Atom::Super => unimplemented!(),
+            Atom::Self_ => self.compile_self()?,
+            Atom::Super => return Err(CompileErrorKind::InvalidSuper("\"super\" can only be used to access a method").into()),
             
             Atom::Group { modifier, inner } => {
                 // modifiers are not allowed outside of assignment
                 if modifier.is_some() {
-                    return Err("assignment modifiers are not allowed outside of an assignment expression".into())
+                    return Err(CompileErrorKind::InvalidAssignment("assignment modifiers are not allowed outside of an assignment expression").into())
                 }
                 
                 match &**inner {
                     // tuple constructor
-                    Expr::Unpack(None) => return Err("need a value to unpack".into()),
+                    Expr::Unpack(None) => return Err(CompileErrorKind::InvalidUnpack("need a value to unpack").into()),
                     Expr::Unpack(Some(iter)) => {
                         self.compile_expr(iter)?;
                         self.emit_instr(OpCode::IterInit);
@@ -1087,27 +1699,121 @@ impl CodeGenerator<'_> {
         }
         
         // Otherwise, it must be a Global variable
+        self.compiler.global_uses.insert(*name);
+        self.emit_load_const(Constant::from(*name))?;
+        self.emit_name_hint(name);
+        self.emit_cached_instr(OpCode::LoadGlobal)
+    }
+    
+    // in case the global doesn't exist, remember any similarly named local so that the VM can suggest it instead
+    fn emit_name_hint(&mut self, name: &InternSymbol) {
+        let builder = self.builder();
+        let names = self.scopes().iter_visible_locals()
+            .filter_map(|local| match local.name() {
+                LocalName::Symbol(symbol) if symbol != *name => Some(builder.resolve_str(symbol)),
+                _ => None,
+            });
+        let hint = utils::closest_match(builder.resolve_str(*name), names)
+            .map(str::to_string);
+        
+        if let Some(hint) = hint {
+            let chunk_id = self.chunk_id;
+            let offset = self.current_offset();
+            self.symbols_mut()
+                .get_mut(&chunk_id).unwrap()
+                .insert_name_hint(offset, &hint)
+        }
+    }
+    
+    fn compile_self(&mut self) -> CompileResult<()> {
+        // the receiver of a method, or of an enclosing method if used inside a closure
+        if self.try_emit_load_local(&LocalName::Receiver).is_some() {
+            return Ok(());
+        }
+        
+        if self.try_emit_load_upval(&LocalName::Receiver)?.is_some() {
+            return Ok(());
+        }
+        
+        Err(CompileErrorKind::InvalidSelf.into())
+    }
+    
+    fn emit_load_receiver(&mut self) {
+        self.try_emit_load_local(&LocalName::Receiver)
+            .expect("no receiver in call frame");
+    }
+    
+    // super method access is resolved statically against the parent of the class containing the method
+    fn compile_super_access(&mut self, name: &InternSymbol) -> CompileResult<()> {
+        // [ receiver class name ] => [ method ]
+        self.compile_self()
+            .map_err(|_| CompileErrorKind::InvalidSuper("\"super\" can only be used inside a method"))?;
+        
+        if self.try_emit_load_local(&LocalName::Super).is_none() 
+            && self.try_emit_load_upval(&LocalName::Super)?.is_none() {
+            return Err(CompileErrorKind::InvalidSuper("\"super\" can't be used in a class with no parent").into());
+        }
+        
         self.emit_load_const(Constant::from(*name))?;
-        self.emit_instr(OpCode::LoadGlobal);
+        self.emit_instr(OpCode::GetSuper);
         Ok(())
     }
     
     fn compile_primary(&mut self, primary: &Primary) -> CompileResult<()> {
-        self.compile_atom(primary.atom())?;
+        self.compile_access_path(primary.atom(), primary.path())
+    }
+        
+    fn compile_access_path(&mut self, atom: &Atom, path: &[AccessItem]) -> CompileResult<()> {
+        let mut path = path.iter();
+        
+        if let Atom::Super = atom {
+            match path.next() {
+                Some(AccessItem::Attribute(name)) => self.compile_super_access(name)?,
+                _ => return Err(CompileErrorKind::InvalidSuper("\"super\" can only be used to access a method").into()),
+            }
+        } else {
+            self.compile_atom(atom)?;
+        }
         
-        for item in primary.path().iter() {
+        // a nil receiver skips the rest of the path, leaving the nil on the stack as the result
+        let mut nil_jumps = Vec::new();
+        for item in path {
             match item {
-                AccessItem::Attribute(_name) => unimplemented!(),
-                AccessItem::Index(_index) => unimplemented!(),
+                AccessItem::OptionalChain => nil_jumps.push(self.emit_dummy_jump(Jump::IfNil)),
+                AccessItem::Attribute(name) => self.compile_get_attr(name)?,
+                AccessItem::Index(index) => self.compile_get_index(index)?,
                 AccessItem::Invoke(args) => self.compile_invocation(args)?,
                 AccessItem::InvokeTable(_table) => unimplemented!(),
             }
         }
         
+        for jump_site in nil_jumps.iter() {
+            self.patch_jump_instr(jump_site, self.current_offset())?;
+        }
+        
+        Ok(())
+    }
+    
+    fn compile_get_attr(&mut self, name: &InternSymbol) -> CompileResult<()> {
+        // [ receiver name ] => [ value ]
+        self.emit_load_const(Constant::from(*name))?;
+        self.emit_cached_instr(OpCode::GetAttr)
+    }
+    
+    fn compile_get_index(&mut self, index: &ExprMeta) -> CompileResult<()> {
+        // [ receiver index ] => [ value ]
+        self.compile_expr_with_symbol(index)?;
+        self.emit_instr(OpCode::GetIndex);
         Ok(())
     }
     
     fn compile_invocation(&mut self, args: &[ExprMeta]) -> CompileResult<()> {
+        self.compile_call_args(args)?;
+        self.emit_instr(OpCode::Call);
+        Ok(())
+    }
+    
+    fn compile_call_args(&mut self, args: &[ExprMeta]) -> CompileResult<()> {
         // prepare argument list:
         // [ callobj arg[0] ... arg[n] nargs ] => [ ret_value ] 
 
@@ -1118,13 +1824,68 @@ impl CodeGenerator<'_> {
             Unpack::Dynamic => { } // nothing to do
         }
 
-        self.emit_instr(OpCode::Call);
-
         Ok(())
     }
     
-    fn compile_unary_op(&mut self, op: UnaryOp, expr: &Expr) -> CompileResult<()> {
-        self.compile_expr(expr)?;
+    // compiles an expression whose value is returned immediately
+    // calls in tail position, including in the result of a block or any branch, are compiled as tail calls
+    fn compile_tail_expr(&mut self, expr: &Expr) -> CompileResult<()> {
+        match expr {
+            Expr::Block { label, suite } => self.compile_block_expression(label.as_ref(), suite, true),
+            Expr::IfExpr { branches, else_clause } => self.compile_if_expression(branches, else_clause.as_deref(), true),
+            Expr::Match { subject, cases, else_clause } => self.compile_match_expression(subject, cases, else_clause.as_deref(), true),
+            
+            expr => if self.try_compile_tail_call(expr)? { Ok(()) } else { self.compile_expr(expr) },
+        }
+    }
+    
+    fn compile_tail_expr_with_symbol(&mut self, expr: &ExprMeta) -> CompileResult<()> {
+        let symbol = expr.debug_symbol();
+        self.push_symbol(Some(*symbol));
+        let result = self.compile_tail_expr(expr.variant());
+        self.pop_symbol();
+        
+        result.map_err(|error| error.with_symbol(*symbol))
+    }
+
+    // A call whose result is returned immediately can reuse the caller's call frame.
+    // Emits nothing and returns false if the expression can't be compiled as a tail call.
+    fn try_compile_tail_call(&mut self, expr: &Expr) -> CompileResult<bool> {
+        match self.scopes().function_kind() {
+            None | Some(FunctionKind::Constructor) => return Ok(false),
+            _ => { },
+        }
+        
+        // the call must not outlive any error handlers, which belong to the current call frame
+        if self.scopes().iter_scopes().any(|scope| scope.handler().is_some()) {
+            return Ok(false);
+        }
+        
+        let primary = match expr {
+            Expr::Primary(primary) if !matches!(primary.atom(), Atom::Super) => primary,
+            _ => return Ok(false),
+        };
+        
+        let (args, path) = match primary.path().split_last() {
+            Some((AccessItem::Invoke(args), path)) => (args, path),
+            _ => return Ok(false),
+        };
+        
+        // an optional chain must be able to skip the call
+        if path.iter().any(|item| matches!(item, AccessItem::OptionalChain)) {
+            return Ok(false);
+        }
+        
+        // the VM closes upvalues when it discards the call frame
+        self.compile_access_path(primary.atom(), path)?;
+        self.compile_call_args(args)?;
+        self.emit_instr(OpCode::TailCall);
+        self.emit_instr(OpCode::Return);
+        Ok(true)
+    }
+    
+    fn compile_unary_op(&mut self, op: UnaryOp, expr: &ExprMeta) -> CompileResult<()> {
+        self.compile_expr_with_symbol(expr)?;
         match op {
             UnaryOp::Neg => self.emit_instr(OpCode::Neg),
             UnaryOp::Pos => self.emit_instr(OpCode::Pos),
@@ -1134,7 +1895,7 @@ impl CodeGenerator<'_> {
         Ok(())
     }
     
-    fn compile_binary_op(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> CompileResult<()> {
+    fn compile_binary_op(&mut self, op: BinaryOp, lhs: &ExprMeta, rhs: &ExprMeta) -> CompileResult<()> {
         
         if matches!(op, BinaryOp::And) {
             return self.compile_shortcircuit_and(lhs, rhs);
@@ -1142,21 +1903,70 @@ impl CodeGenerator<'_> {
         if matches!(op, BinaryOp::Or) {
             return self.compile_shortcircuit_or(lhs, rhs);
         }
+        if matches!(op, BinaryOp::Coalesce) {
+            return self.compile_coalesce(lhs, rhs);
+        }
         
-        self.compile_expr(lhs)?;
-        self.compile_expr(rhs)?;
+        if let Expr::Atom(Atom::Identifier(name)) = lhs.variant() {
+            if self.try_emit_local_const_op(op, name, rhs.variant())? {
+                return Ok(());
+            }
+        }
+        
+        self.compile_expr_with_symbol(lhs)?;
+        self.compile_expr_with_symbol(rhs)?;
+        
+        // errors from the operator itself point to both of its operands
+        let symbol = DebugSymbol::try_from((lhs.debug_symbol().start(), rhs.debug_symbol().end())).ok()
+            .or_else(|| self.current_symbol());
+        self.push_symbol(symbol);
         self.emit_binary_op(op);
+        self.pop_symbol();
         
         Ok(())
     }
     
+    // adding or subtracting a numeric literal from a local variable is done using a single instruction
+    fn try_emit_local_const_op(&mut self, op: BinaryOp, name: &InternSymbol, rhs: &Expr) -> CompileResult<bool> {
+        let opcode = match op {
+            BinaryOp::Add => OpCode::AddLocalConst,
+            BinaryOp::Sub => OpCode::SubLocalConst,
+            _ => return Ok(false),
+        };
+        
+        let value = match rhs {
+            Expr::Atom(Atom::IntegerLiteral(value)) => Constant::from(*value),
+            Expr::Atom(Atom::FloatLiteral(value)) => Constant::from(*value),
+            _ => return Ok(false),
+        };
+        
+        let index = self.scopes_mut().read_local(&LocalName::Symbol(*name))
+            .and_then(|local| u8::try_from(local.index()).ok());
+        
+        let index = match index {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        
+        let cid = match u8::try_from(self.get_or_make_const(value)?) {
+            Ok(cid) => cid,
+            Err(..) => return Ok(false),
+        };
+        
+        self.emit_instr_data(opcode, &[index, cid]);
+        Ok(true)
+    }
+    
     fn emit_binary_op(&mut self, op: BinaryOp) {
         match op {
-            BinaryOp::And | BinaryOp::Or => unreachable!(),
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce => unreachable!(),
+            BinaryOp::Pipe => unreachable!("pipes are parsed as calls"),
             
             BinaryOp::Mul => self.emit_instr(OpCode::Mul),
             BinaryOp::Div => self.emit_instr(OpCode::Div),
             BinaryOp::Mod => self.emit_instr(OpCode::Mod),
+            BinaryOp::FloorDiv => self.emit_instr(OpCode::FloorDiv),
+            BinaryOp::Exp => self.emit_instr(OpCode::Exp),
             BinaryOp::Add => self.emit_instr(OpCode::Add),
             BinaryOp::Sub => self.emit_instr(OpCode::Sub),
             
@@ -1173,10 +1983,117 @@ impl CodeGenerator<'_> {
             BinaryOp::GE => self.emit_instr(OpCode::GE),
             BinaryOp::EQ => self.emit_instr(OpCode::EQ),
             BinaryOp::NE => self.emit_instr(OpCode::NE),
+            BinaryOp::In => self.emit_instr(OpCode::In),
+            BinaryOp::NotIn => self.emit_instr(OpCode::NotIn),
+            BinaryOp::Is => self.emit_instr(OpCode::Is),
+        };
+    }
+}
+
+///////// Register Instructions /////////
+impl CodeGenerator<'_> {
+    // compiles an assignment to a local variable using a single register instruction, if its operands allow it
+    fn try_emit_register_assignment(&mut self, expr: &Expr) -> CompileResult<bool> {
+        let assign = match expr {
+            Expr::Assignment(assign) => assign,
+            _ => return Ok(false),
+        };
+        
+        let name = match (&assign.lhs, assign.action) {
+            (Pattern::Identifier(name), MatchAction::AssignLocal | MatchAction::AssignNonLocal) => name,
+            _ => return Ok(false),
+        };
+        
+        if self.scopes().is_global_scope() {
+            return Ok(false);
+        }
+        
+        // assignments to immutable locals are left to the stack instructions to report
+        let dst = self.scopes().resolve_local(&LocalName::Symbol(*name))
+            .filter(|local| local.mode().can_write())
+            .and_then(|local| u8::try_from(local.index()).ok());
+        
+        let dst = match dst {
+            Some(dst) => dst,
+            None => return Ok(false),
+        };
+        
+        let (op, lhs, rhs) = match (assign.op, &assign.rhs) {
+            // for update-assignments the first source is the destination
+            (Some(op), rhs) => (op, None, rhs),
+            (None, Expr::BinaryOp(op, operands)) => (*op, Some(operands.0.variant()), operands.1.variant()),
+            (None, rhs) => return self.try_emit_register_move(dst, rhs),
+        };
+        
+        let opcode = match get_register_opcode(op) {
+            Some(opcode) => opcode,
+            None => return Ok(false),
         };
+        
+        let lhs = match lhs {
+            Some(lhs) => self.get_register_operand(lhs)?,
+            None => Some((false, dst)),
+        };
+        let operands = match lhs {
+            Some(lhs) => self.get_register_operand(rhs)?.map(|rhs| (lhs, rhs)),
+            None => None,
+        };
+        let ((lhs_const, lhs), (rhs_const, rhs)) = match operands {
+            Some(operands) => operands,
+            None => return Ok(false),
+        };
+        
+        let mut mode = 0;
+        if lhs_const { mode |= REG_CONST_SRC1; }
+        if rhs_const { mode |= REG_CONST_SRC2; }
+        self.emit_instr_data(opcode, &[mode, dst, lhs, rhs]);
+        Ok(true)
+    }
+    
+    fn try_emit_register_move(&mut self, dst: u8, expr: &Expr) -> CompileResult<bool> {
+        let (src_const, src) = match self.get_register_operand(expr)? {
+            Some(src) => src,
+            None => return Ok(false),
+        };
+        
+        let mode = if src_const { REG_CONST_SRC1 } else { 0 };
+        self.emit_instr_data(OpCode::MoveReg, &[mode, dst, src]);
+        Ok(true)
+    }
+    
+    // returns true and a constant index for numeric literals, false and a local index for local variables, 
+    // or None if the expression can't be used as the operand of a register instruction
+    fn get_register_operand(&mut self, expr: &Expr) -> CompileResult<Option<(bool, u8)>> {
+        let value = match expr {
+            Expr::Atom(Atom::IntegerLiteral(value)) => Constant::from(*value),
+            Expr::Atom(Atom::FloatLiteral(value)) => Constant::from(*value),
+            
+            Expr::Atom(Atom::Identifier(name)) => {
+                let index = self.scopes_mut().read_local(&LocalName::Symbol(*name))
+                    .and_then(|local| u8::try_from(local.index()).ok());
+                return Ok(index.map(|index| (false, index)));
+            },
+            
+            _ => return Ok(None),
+        };
+        
+        let cid = u8::try_from(self.get_or_make_const(value)?).ok();
+        Ok(cid.map(|cid| (true, cid)))
     }
 }
 
+const fn get_register_opcode(op: BinaryOp) -> Option<OpCode> {
+    let opcode = match op {
+        BinaryOp::Add => OpCode::AddReg,
+        BinaryOp::Sub => OpCode::SubReg,
+        BinaryOp::Mul => OpCode::MulReg,
+        BinaryOp::Div => OpCode::DivReg,
+        BinaryOp::Mod => OpCode::ModReg,
+        _ => return None,
+    };
+    Some(opcode)
+}
+
 ///////// Declarations and Assignments /////////
 impl CodeGenerator<'_> {
     fn compile_update_assignment(&mut self, op: BinaryOp, action: MatchAction, lhs: &Pattern, rhs: &Expr) -> CompileResult<()> {
@@ -1186,30 +2103,74 @@ impl CodeGenerator<'_> {
             MatchAction::AssignNonLocal => false,
             
             MatchAction::DeclImmutable | MatchAction::DeclMutable
-                => return Err("update-assignment is invalid when declaring a variable".into()),
+                => return Err(CompileErrorKind::InvalidAssignment("update-assignment is invalid when declaring a variable").into()),
         };
         
-        // TODO suport Attribute and Index LValues as well
         match lhs {
             Pattern::Identifier(name) => {
-                self.compile_name_lookup(name)?;
-                self.compile_expr(rhs)?;
-                self.emit_binary_op(op);
+                if !self.try_emit_local_const_op(op, name, rhs)? {
+                    self.compile_name_lookup(name)?;
+                    self.compile_expr(rhs)?;
+                    self.emit_binary_op(op);
+                }
                 
                 self.compile_assign_identifier(name, local_only)
             },
             
-            Pattern::Attribute(_target) => unimplemented!(),
+            Pattern::Attribute(target) => self.compile_update_attribute(op, target, rhs),
             
-            Pattern::Index(_target) => unimplemented!(),
+            Pattern::Index(target) => self.compile_update_index(op, target, rhs),
             
             Pattern::Tuple {..} | Pattern::Pack(..)
-                => Err("can't update-assign to this".into()),
+                => Err(CompileErrorKind::InvalidAssignment("can't update-assign to this").into()),
             
             Pattern::Modifier {..} => unreachable!(),
         }
     }
     
+    // the receiver is stored in a temporary so that it is only evaluated once
+    fn compile_update_attribute(&mut self, op: BinaryOp, target: &AttributePattern, rhs: &Expr) -> CompileResult<()> {
+        self.compile_primary(&target.receiver)?;
+        
+        self.emit_begin_scope(None, ScopeTag::Temporary);
+        let receiver = self.emit_create_temporary(Access::ReadOnly)?;
+        
+        self.compile_get_attr(&target.name)?;
+        self.compile_expr(rhs)?;
+        self.emit_binary_op(op);
+        
+        // [ value receiver name ] => [ value ]
+        self.emit_load_local_index(receiver);
+        self.emit_load_const(Constant::from(target.name))?;
+        self.emit_instr(OpCode::SetAttr);
+        
+        self.emit_end_scope();
+        Ok(())
+    }
+    
+    // both the receiver and the index are stored in temporaries so that they are only evaluated once
+    fn compile_update_index(&mut self, op: BinaryOp, target: &IndexPattern, rhs: &Expr) -> CompileResult<()> {
+        self.compile_primary(&target.receiver)?;
+        
+        self.emit_begin_scope(None, ScopeTag::Temporary);
+        let receiver = self.emit_create_temporary(Access::ReadOnly)?;
+        
+        self.compile_expr_with_symbol(&target.index)?;
+        let index = self.emit_create_temporary(Access::ReadOnly)?;
+        
+        self.emit_instr(OpCode::GetIndex);
+        self.compile_expr(rhs)?;
+        self.emit_binary_op(op);
+        
+        // [ value receiver index ] => [ value ]
+        self.emit_load_local_index(receiver);
+        self.emit_load_local_index(index);
+        self.emit_instr(OpCode::SetIndex);
+        
+        self.emit_end_scope();
+        Ok(())
+    }
+    
     fn compile_assignment(&mut self, mut action: MatchAction, mut lhs: &Pattern) -> CompileResult<()> {
         
         while let Pattern::Modifier { modifier, pattern } = lhs {
@@ -1220,10 +2181,7 @@ impl CodeGenerator<'_> {
         match lhs {
             Pattern::Tuple(items) => self.compile_assign_tuple(action, items),
             
-            Pattern::Pack(..) => {
-                let item = std::slice::from_ref(lhs);
-                self.compile_assign_tuple(action, item)
-            }
+            Pattern::Pack(pack_target) => self.compile_assign_tuple_pack(action, pack_target.as_deref(), &[], &[]),
             
             lhs => {
                 match action {
@@ -1247,12 +2205,13 @@ impl CodeGenerator<'_> {
             Pattern::Tuple {..} => unreachable!(),
             Pattern::Modifier {..} => unreachable!(),
             
-            _ => Err("not a variable name".into()),
+            _ => Err(CompileErrorKind::InvalidAssignment("not a variable name").into()),
         }
     }
     
     fn compile_decl_global_name(&mut self, access: Access, name: InternSymbol) -> CompileResult<()> {
         
+        self.compiler.global_decls.insert(name);
         self.emit_load_const(Constant::from(name))?;
         match access {
             Access::ReadOnly => self.emit_instr(OpCode::InsertGlobal),
@@ -1263,9 +2222,18 @@ impl CodeGenerator<'_> {
     
     fn compile_decl_local_name(&mut self, access: Access, name: InternSymbol) -> CompileResult<()> {
         
-        match self.scopes_mut().insert_local(access, LocalName::Symbol(name))? {
-            InsertLocal::CreateNew(..) => 
-                self.emit_instr(OpCode::InsertLocal),
+        let shadows = self.scopes().resolve_local(&LocalName::Symbol(name)).is_some();
+        
+        let symbol = self.current_symbol();
+        match self.scopes_mut().insert_local(access, LocalName::Symbol(name), symbol.as_ref())? {
+            InsertLocal::CreateNew(..) => {
+                // redeclaring a local in the same scope hides it instead, which is not a shadow
+                if shadows {
+                    let message = format!("local \"{}\" shadows a variable from an enclosing scope", self.builder().resolve_str(name));
+                    self.emit_warning(WarningKind::ShadowedName, &message, self.current_symbol());
+                }
+                self.emit_instr(OpCode::InsertLocal)
+            },
             
             InsertLocal::HideExisting(local_index) =>
                 self.emit_assign_local(local_index),
@@ -1279,9 +2247,9 @@ impl CodeGenerator<'_> {
         match lhs {
             Pattern::Identifier(name) => self.compile_assign_identifier(name, allow_nonlocal),
             
-            Pattern::Attribute(_target) => unimplemented!(),
+            Pattern::Attribute(target) => self.compile_assign_attribute(target),
             
-            Pattern::Index(_target) => unimplemented!(),
+            Pattern::Index(target) => self.compile_assign_index(target),
             
             _ => panic!("invalid assignment target"),
         }
@@ -1300,7 +2268,7 @@ impl CodeGenerator<'_> {
             
             if let Some(local) = result.cloned() {
                 if !local.mode().can_write() {
-                    return Err("can't assign to immutable local variable".into());
+                    return Err(CompileErrorKind::CantAssignImmutable.into());
                 }
                 
                 self.emit_assign_local(local.index());
@@ -1310,14 +2278,14 @@ impl CodeGenerator<'_> {
             
             // nonlocal keyword is not required in the global frame
             if !allow_nonlocal && self.scopes().is_call_frame() {
-                return Err("can't assign to a non-local variable without the \"nonlocal\" keyword".into());
+                return Err(CompileErrorKind::CantAssignNonLocal.into());
             }
             
             // check if an upvalue is found or can be created...
             if self.scopes().is_call_frame() {
                 if let Some(upval) = self.scopes_mut().resolve_or_create_upval(&local_name)? {
                     if !upval.mode().can_write() {
-                        return Err("can't assign to immutable local variable".into());
+                        return Err(CompileErrorKind::CantAssignImmutable.into());
                     }
                     
                     let index = upval.index();
@@ -1333,11 +2301,28 @@ impl CodeGenerator<'_> {
         }
 
         // ...finally, try to assign to a global, which are late bound
+        self.compiler.global_uses.insert(*name);
         self.emit_load_const(Constant::from(*name))?;
         self.emit_instr(OpCode::StoreGlobal);
         Ok(())
     }
     
+    fn compile_assign_attribute(&mut self, target: &AttributePattern) -> CompileResult<()> {
+        // [ value receiver name ] => [ value ]
+        self.compile_primary(&target.receiver)?;
+        self.emit_load_const(Constant::from(target.name))?;
+        self.emit_instr(OpCode::SetAttr);
+        Ok(())
+    }
+    
+    fn compile_assign_index(&mut self, target: &IndexPattern) -> CompileResult<()> {
+        // [ value receiver index ] => [ value ]
+        self.compile_primary(&target.receiver)?;
+        self.compile_expr_with_symbol(&target.index)?;
+        self.emit_instr(OpCode::SetIndex);
+        Ok(())
+    }
+    
     fn emit_assign_local(&mut self, offset: LocalIndex) {
         if let Ok(offset) = u8::try_from(offset) {
             self.emit_instr_byte(OpCode::StoreLocal, offset);
@@ -1346,31 +2331,45 @@ impl CodeGenerator<'_> {
         }
     }
     
-    fn compile_assign_tuple(&mut self, action: MatchAction, item_targets: &[Pattern]) -> CompileResult<()> {
+    fn compile_assign_tuple(&mut self, action: MatchAction, item_targets: &[PatternMeta]) -> CompileResult<()> {
         // process tuple packing patterns
         
         let mut pack_targets = item_targets.iter().enumerate()
-            .filter_map(|(idx, target)| match target {
-                Pattern::Pack(pack_target) => Some((idx, pack_target.as_deref())),
+            .filter_map(|(idx, target)| match &target.pattern {
+                Pattern::Pack(pack_target) => Some((idx, pack_target.as_deref(), target.symbol)),
                 _ => None,
             });
             
-        let (idx, pack_target) = match pack_targets.next() {
+        let (idx, pack_target, pack_symbol) = match pack_targets.next() {
             Some(pack_target) => pack_target,
             None => return self.compile_assign_tuple_nopack(action, item_targets),
         };
         
         if !pack_targets.next().is_none() {
-            return Err("tuple assignment may contain only one \"...\" pack pattern".into())
+            return Err(CompileErrorKind::InvalidUnpack("tuple assignment may contain only one \"...\" pack pattern").into())
         }
         
         let (pre_pack, rest) = item_targets.split_at(idx);
         let (_, post_pack) = rest.split_at(1);
         
-        self.compile_assign_tuple_pack(action, pack_target, pre_pack, post_pack)
+        self.push_symbol(Some(pack_symbol));
+        let result = self.compile_assign_tuple_pack(action, pack_target, pre_pack, post_pack);
+        self.pop_symbol();
+        result
     }
     
-    fn compile_assign_tuple_pack(&mut self, action: MatchAction, pack: Option<&Pattern>, pre_pack: &[Pattern], post_pack: &[Pattern]) -> CompileResult<()> {
+    // assign an item using the tuple item's own symbol, so that each binding is reported at its own location
+    fn compile_assign_tuple_item(&mut self, action: MatchAction, target: &PatternMeta) -> CompileResult<()> {
+        debug_assert!(!matches!(target.pattern, Pattern::Pack(..)));
+        
+        self.push_symbol(Some(target.symbol));
+        let result = self.compile_assignment(action, &target.pattern);
+        self.pop_symbol();
+        
+        result.map_err(|error| error.with_symbol(target.symbol))
+    }
+    
+    fn compile_assign_tuple_pack(&mut self, action: MatchAction, pack: Option<&Pattern>, pre_pack: &[PatternMeta], post_pack: &[PatternMeta]) -> CompileResult<()> {
         let mut error_jump_sites = Vec::new();
         
         // assignment needs to preserve original value for expression result
@@ -1381,8 +2380,6 @@ impl CodeGenerator<'_> {
         
         // compile to unrolled iteration for pre-pack items
         for target in pre_pack.iter() {
-            debug_assert!(!matches!(target, Pattern::Pack(..)));
-            
             // check if there is an item left for this target
             let error_jump = self.emit_dummy_jump(Jump::IfFalse);
             error_jump_sites.push(error_jump);
@@ -1390,7 +2387,7 @@ impl CodeGenerator<'_> {
             // advance the iterator and put the item on the stack
             self.emit_instr(OpCode::IterNext);
             
-            self.compile_assignment(action, target)?;
+            self.compile_assign_tuple_item(action, target)?;
             self.emit_instr(OpCode::Pop);
         }
         
@@ -1419,7 +2416,7 @@ impl CodeGenerator<'_> {
                 
                 // calculate the pack length and store it
                 let post_len = IntType::try_from(post_pack.len())
-                    .map_err(|_| "unpack length limit exceeded")?;
+                    .map_err(|_| CompileErrorKind::InternalLimit("unpack length limit exceeded"))?;
                 
                 self.compile_integer(post_len)?;
                 self.emit_instr(OpCode::Sub);
@@ -1436,8 +2433,7 @@ impl CodeGenerator<'_> {
                 
                 // assign post-pack items
                 for target in post_pack.iter().rev() {
-                    debug_assert!(!matches!(target, Pattern::Pack(..)));
-                    self.compile_assignment(action, target)?;
+                    self.compile_assign_tuple_item(action, target)?;
                     self.emit_instr(OpCode::Pop);
                 }
                 
@@ -1476,7 +2472,7 @@ impl CodeGenerator<'_> {
         Ok(())
     }
     
-    fn compile_assign_tuple_nopack(&mut self, action: MatchAction, items: &[Pattern]) -> CompileResult<()> {
+    fn compile_assign_tuple_nopack(&mut self, action: MatchAction, items: &[PatternMeta]) -> CompileResult<()> {
         let mut error_jump_sites = Vec::new();
         
         // assignment needs to preserve original value for expression result
@@ -1487,8 +2483,6 @@ impl CodeGenerator<'_> {
         
         // compile to unrolled iteration
         for target in items.iter() {
-            debug_assert!(!matches!(target, Pattern::Pack(..)));
-            
             // check if there is an item left for this target
             let error_jump = self.emit_dummy_jump(Jump::IfFalse);
             error_jump_sites.push(error_jump);
@@ -1496,7 +2490,7 @@ impl CodeGenerator<'_> {
             // advance the iterator and put the item on the stack
             self.emit_instr(OpCode::IterNext);
             
-            self.compile_assignment(action, target)?;
+            self.compile_assign_tuple_item(action, target)?;
             self.emit_instr(OpCode::Pop);
         }
         
@@ -1530,10 +2524,10 @@ impl CodeGenerator<'_> {
 ///////// Blocks and If-Expressions /////////
 impl CodeGenerator<'_> {
 
-    fn compile_block_expression(&mut self, label: Option<&Label>, suite: &ExprBlock) -> CompileResult<()> {
+    fn compile_block_expression(&mut self, label: Option<&Label>, suite: &ExprBlock, tail: bool) -> CompileResult<()> {
         
         self.emit_begin_scope(label, ScopeTag::Block);
-        self.compile_expr_block(suite)?;
+        self.compile_expr_block(suite, tail)?;
         let block_scope = self.emit_end_scope();
         
         // finalize scope
@@ -1543,14 +2537,15 @@ impl CodeGenerator<'_> {
         Ok(())
     }
     
-    fn compile_if_expression(&mut self, branches: &[ConditionalBranch], else_clause: Option<&ExprBlock>) -> CompileResult<()> {
+    fn compile_if_expression(&mut self, branches: &[ConditionalBranch], else_clause: Option<&ExprBlock>, tail: bool) -> CompileResult<()> {
         debug_assert!(!branches.is_empty());
         
         // track the sites where we jump to the end, so we can patch them later
         let mut end_jump_sites = Vec::new();
         
-        // if there is no else branch, the last non-else branch won't have a jump to end, and should not pop the condition
-        // this is because if-expressions without an else clause evaluate to their condition when not entered
+        // each branch pops its condition when it is entered, and again on the way to the next branch when it is not.
+        // if there is no else branch, the last branch keeps its condition on the stack when it is not entered,
+        // because if-expressions without an else clause evaluate to their condition in that case
         let (last_branch, rest) = branches.split_last().unwrap();
         let iter_branches = rest.iter()
             .map(|branch| (false, branch))
@@ -1559,32 +2554,38 @@ impl CodeGenerator<'_> {
         for (is_last, branch) in iter_branches {
             let is_final_branch = is_last && else_clause.is_none();
             
+            self.check_constant_condition(branch.condition());
             self.compile_expr(branch.condition())?;
             
-            // need to keep condition value on the stack in case there is a break/continue
-            // inside the statement list
             let branch_jump_site = self.emit_dummy_jump(Jump::IfFalse);
             
+            // the condition is not needed once the branch is entered
+            self.emit_instr(OpCode::Pop);
+            
             self.emit_begin_scope(None, ScopeTag::Branch);
-            self.compile_expr_block(branch.suite())?;
+            self.compile_expr_block(branch.suite(), tail)?;
             self.emit_end_scope();
             
             // site for the jump to the end of if-expression
-            if !is_final_branch {
-                self.emit_instr(OpCode::Pop);
+            if !is_final_branch && self.reachable {
                 let jump_site = self.emit_dummy_jump(Jump::Uncond);
                 end_jump_sites.push(jump_site);
             }
             
             // target for the jump from the conditional of the now compiled branch
             self.patch_jump_instr(&branch_jump_site, self.current_offset())?;
+            
+            // branch not entered, discard the condition before moving on
+            if !is_final_branch {
+                self.emit_instr(OpCode::Pop);
+            }
         }
         
         // else clause
         if let Some(suite) = else_clause {
             
             self.emit_begin_scope(None, ScopeTag::Branch);
-            self.compile_expr_block(suite)?;
+            self.compile_expr_block(suite, tail)?;
             self.emit_end_scope();
             
         }
@@ -1598,26 +2599,218 @@ impl CodeGenerator<'_> {
         Ok(())
     }
     
-    fn compile_shortcircuit_and(&mut self, lhs: &Expr, rhs: &Expr) -> CompileResult<()> {
-        self.compile_expr(lhs)?;
+    fn compile_match_expression(&mut self, subject: &ExprMeta, cases: &[MatchCase], else_clause: Option<&ExprBlock>, tail: bool) -> CompileResult<()> {
+        // the subject is stored in a local so that each case can inspect it. This can't be a temporary,
+        // since those are hidden from "break" and "continue" in the body of a case
+        self.compile_expr_with_symbol(subject)?;
+        
+        self.emit_begin_scope(None, ScopeTag::Branch);
+        let subject = LocalIndex::from(self.scopes_mut().insert_local(Access::ReadOnly, LocalName::Anonymous, None)?);
+        self.emit_instr(OpCode::InsertLocal);
+        self.emit_instr(OpCode::Pop);
+        
+        let mut end_jump_sites = Vec::new();
+        
+        for case in cases.iter() {
+            // each check leaves a bool on the stack, the case is skipped as soon as one fails
+            let mut next_case_sites = Vec::new();
+            self.compile_case_checks(subject, &case.pattern, &mut Vec::new(), &mut next_case_sites)?;
+            
+            self.emit_begin_scope(None, ScopeTag::Branch);
+            self.push_symbol(Some(case.symbol));
+            let result = self.compile_case_bindings(subject, &case.pattern, &mut Vec::new());
+            self.pop_symbol();
+            result?;
+            
+            let guard_site = match &case.guard {
+                Some(guard) => {
+                    self.compile_expr(guard)?;
+                    Some(self.emit_dummy_jump(Jump::PopIfFalse))
+                },
+                None => None,
+            };
+            
+            self.compile_expr_block(&case.suite, tail)?;
+            let scope = self.emit_end_scope();
+            
+            if self.reachable {
+                let jump_site = self.emit_dummy_jump(Jump::Uncond);
+                end_jump_sites.push(jump_site);
+            }
+            
+            // the guard failed after the names were bound, so they must be discarded here
+            if let Some(guard_site) = guard_site {
+                self.patch_jump_instr(&guard_site, self.current_offset())?;
+                self.emit_scope_drop(&ScopeDrop::from(&scope));
+            }
+            
+            let next_case = self.current_offset();
+            for jump_site in next_case_sites.iter() {
+                self.patch_jump_instr(jump_site, next_case)?;
+            }
+        }
+        
+        // no case matched
+        if let Some(suite) = else_clause {
+            self.emit_begin_scope(None, ScopeTag::Branch);
+            self.compile_expr_block(suite, tail)?;
+            self.emit_end_scope();
+        } else {
+            self.emit_instr(OpCode::Nil);
+        }
+        
+        let end_target = self.current_offset();
+        for jump_site in end_jump_sites.iter() {
+            self.patch_jump_instr(jump_site, end_target)?;
+        }
+        
+        self.emit_end_scope();
+        Ok(())
+    }
+    
+    // load the part of the subject that is found by indexing it with each of the path indices in turn
+    fn emit_load_case_path(&mut self, subject: LocalIndex, path: &[IntType]) -> CompileResult<()> {
+        self.emit_load_local_index(subject);
+        for index in path.iter() {
+            self.compile_integer(*index)?;
+            self.emit_instr(OpCode::GetIndex);
+        }
+        Ok(())
+    }
+    
+    fn compile_case_checks(&mut self, subject: LocalIndex, pattern: &CasePattern, path: &mut Vec<IntType>, fail_sites: &mut Vec<JumpSite>) -> CompileResult<()> {
+        match pattern {
+            CasePattern::Wildcard | CasePattern::Binding(..) | CasePattern::Rest(..) => { },
+            
+            CasePattern::Literal(atom) => {
+                self.emit_load_case_path(subject, path)?;
+                self.compile_atom(atom)?;
+                self.emit_instr(OpCode::EQ);
+                fail_sites.push(self.emit_dummy_jump(Jump::PopIfFalse));
+            },
+            
+            CasePattern::Tuple(items) => {
+                let rest = items.iter().any(|item| matches!(item, CasePattern::Rest(..)));
+                let len = u8::try_from(items.len() - usize::from(rest))
+                    .map_err(|_| CompileErrorKind::InternalLimit("too many items in case pattern"))?;
+                
+                // check the length first, so that the items can be indexed safely
+                self.emit_load_case_path(subject, path)?;
+                self.emit_instr(OpCode::TupleLen);
+                self.emit_instr_byte(OpCode::UInt8, len);
+                self.emit_instr(if rest { OpCode::GE } else { OpCode::EQ });
+                fail_sites.push(self.emit_dummy_jump(Jump::PopIfFalse));
+                
+                for (index, item) in case_pattern_items(items) {
+                    path.push(index);
+                    self.compile_case_checks(subject, item, path, fail_sites)?;
+                    path.pop();
+                }
+            },
+        }
+        Ok(())
+    }
+    
+    fn compile_case_bindings(&mut self, subject: LocalIndex, pattern: &CasePattern, path: &mut Vec<IntType>) -> CompileResult<()> {
+        match pattern {
+            CasePattern::Wildcard | CasePattern::Literal(..) | CasePattern::Rest(..) => { },
+            
+            CasePattern::Binding(name) => {
+                self.emit_load_case_path(subject, path)?;
+                self.compile_decl_local_name(Access::ReadOnly, *name)?;
+                self.emit_instr(OpCode::Pop);
+            },
+            
+            CasePattern::Tuple(items) => {
+                for (index, item) in case_pattern_items(items) {
+                    path.push(index);
+                    self.compile_case_bindings(subject, item, path)?;
+                    path.pop();
+                }
+                
+                let rest = items.iter().enumerate()
+                    .find_map(|(idx, item)| match item {
+                        CasePattern::Rest(Some(name)) => Some((idx, *name)),
+                        _ => None,
+                    });
+                
+                if let Some((pre_len, name)) = rest {
+                    let post_len = u8::try_from(items.len() - pre_len - 1)
+                        .map_err(|_| CompileErrorKind::InternalLimit("too many items in case pattern"))?;
+                    self.compile_case_rest_binding(subject, path, pre_len, post_len, name)?;
+                }
+            },
+        }
+        Ok(())
+    }
+    
+    // collect the items that are not matched by the other patterns in a tuple pattern into a new tuple
+    fn compile_case_rest_binding(&mut self, subject: LocalIndex, path: &[IntType], pre_len: usize, post_len: u8, name: InternSymbol) -> CompileResult<()> {
+        self.emit_load_case_path(subject, path)?;
+        self.emit_instr(OpCode::IterInit);
+        
+        // skip the items before the rest
+        for _ in 0..pre_len {
+            self.emit_instr(OpCode::IterNext);
+            self.emit_instr(OpCode::Pop);
+        }
+        
+        self.emit_instr(OpCode::IterUnpack);
+        
+        // discard the items after the rest, keeping the number of items that are left
+        if post_len > 0 {
+            self.emit_instr_byte(OpCode::UInt8, post_len);
+            self.emit_instr(OpCode::Sub);
+            
+            self.emit_begin_scope(None, ScopeTag::Temporary);
+            let rest_len = self.emit_create_temporary(Access::ReadOnly)?;
+            self.emit_instr(OpCode::Pop);
+            
+            self.emit_instr_byte(OpCode::Drop, post_len);
+            self.emit_load_local_index(rest_len);
+            self.emit_end_scope();
+        }
+        
+        self.emit_instr(OpCode::TupleN);
+        self.compile_decl_local_name(Access::ReadOnly, name)?;
+        self.emit_instr(OpCode::Pop);
+        Ok(())
+    }
+    
+    fn compile_shortcircuit_and(&mut self, lhs: &ExprMeta, rhs: &ExprMeta) -> CompileResult<()> {
+        self.compile_expr_with_symbol(lhs)?;
         
         let shortcircuit = self.emit_dummy_jump(Jump::IfFalse);
         
         self.emit_instr(OpCode::Pop);
-        self.compile_expr(rhs)?;
+        self.compile_expr_with_symbol(rhs)?;
         
         self.patch_jump_instr(&shortcircuit, self.current_offset())?;
         
         Ok(())
     }
     
-    fn compile_shortcircuit_or(&mut self, lhs: &Expr, rhs: &Expr) -> CompileResult<()> {
-        self.compile_expr(lhs)?;
+    fn compile_shortcircuit_or(&mut self, lhs: &ExprMeta, rhs: &ExprMeta) -> CompileResult<()> {
+        self.compile_expr_with_symbol(lhs)?;
         
         let shortcircuit = self.emit_dummy_jump(Jump::IfTrue);
         
         self.emit_instr(OpCode::Pop);
-        self.compile_expr(rhs)?;
+        self.compile_expr_with_symbol(rhs)?;
+        
+        self.patch_jump_instr(&shortcircuit, self.current_offset())?;
+        
+        Ok(())
+    }
+    
+    // the rhs is only evaluated if the lhs is nil
+    fn compile_coalesce(&mut self, lhs: &ExprMeta, rhs: &ExprMeta) -> CompileResult<()> {
+        self.compile_expr_with_symbol(lhs)?;
+        
+        let shortcircuit = self.emit_dummy_jump(Jump::IfNotNil);
+        
+        self.emit_instr(OpCode::Pop);
+        self.compile_expr_with_symbol(rhs)?;
         
         self.patch_jump_instr(&shortcircuit, self.current_offset())?;
         
@@ -1625,9 +2818,30 @@ impl CodeGenerator<'_> {
     }
 }
 
+// the index of each item of a tuple pattern other than the rest, counting from the end for any items that come after it
+fn case_pattern_items(items: &[CasePattern]) -> impl Iterator<Item=(IntType, &CasePattern)> {
+    let rest = items.iter().position(|item| matches!(item, CasePattern::Rest(..)));
+    let len = items.len();
+    
+    items.iter().enumerate()
+        .filter(|(_, item)| !matches!(item, CasePattern::Rest(..)))
+        .map(move |(idx, item)| {
+            // tuple patterns with more than u8::MAX items are rejected by compile_case_checks()
+            let index = match rest {
+                Some(rest) if idx > rest => -IntType::try_from(len - idx).unwrap(),
+                _ => IntType::try_from(idx).unwrap(),
+            };
+            (index, item)
+        })
+}
+
 ///////// Function Definitions /////////
 impl CodeGenerator<'_> {
     fn compile_function_def(&mut self, fundef: &FunctionDef) -> CompileResult<()> {
+        self.compile_function(fundef, FunctionKind::Function)
+    }
+    
+    fn compile_function(&mut self, fundef: &FunctionDef, kind: FunctionKind) -> CompileResult<()> {
         // create a new chunk for the function
         let symbol = self.current_symbol();
         let info = ChunkInfo::Function { symbol };
@@ -1642,11 +2856,14 @@ impl CodeGenerator<'_> {
         
         // and a new local scope
         // don't need to emit new scope instructions, should handled by function call
-        chunk_gen.scopes_mut().push_frame(symbol.as_ref());
+        chunk_gen.scopes_mut().push_frame(symbol.as_ref(), kind);
         
         // don't need to generate IN_LOCAL instructions for these, the VM should include them automatically
-        chunk_gen.scopes_mut().insert_local(Access::ReadOnly, LocalName::Receiver)?;
-        chunk_gen.scopes_mut().insert_local(Access::ReadOnly, LocalName::NArgs)?;
+        // plain functions still get a receiver slot, but it is anonymous so that 
+        // "self" inside a closure will refer to the receiver of the enclosing method
+        let receiver = if kind.has_receiver() { LocalName::Receiver } else { LocalName::Anonymous };
+        chunk_gen.scopes_mut().insert_local(Access::ReadOnly, receiver, None)?;
+        chunk_gen.scopes_mut().insert_local(Access::ReadOnly, LocalName::NArgs, None)?;
         
         // prepare argument list
         chunk_gen.compile_function_preamble(fundef)?;
@@ -1654,20 +2871,37 @@ impl CodeGenerator<'_> {
         // function body
         chunk_gen.compile_stmt_block(fundef.body.stmt_list())?;
         
-        // function result
-        if let Some(expr) = fundef.body.result() {
-            chunk_gen.compile_expr_with_symbol(expr)?;
-        } else {
-            chunk_gen.emit_instr(OpCode::Nil);
+        // function result, unless the body always returns explicitly
+        let reachable = match fundef.body.result() {
+            Some(expr) => chunk_gen.check_reachable(Some(expr.debug_symbol())),
+            None => chunk_gen.reachable,
+        };
+        
+        if reachable {
+            if kind == FunctionKind::Constructor {
+                // constructors always return the new instance
+                if let Some(expr) = fundef.body.result() {
+                    chunk_gen.compile_expr_with_symbol(expr)?;
+                    chunk_gen.emit_instr(OpCode::Pop);
+                }
+                chunk_gen.emit_load_receiver();
+            } else if let Some(expr) = fundef.body.result() {
+                chunk_gen.compile_tail_expr_with_symbol(expr)?;
+            } else {
+                chunk_gen.emit_instr(OpCode::Nil);
+            }
         }
         
         // end the function scope
         // don't need to drop locals explicitly, that will be done when the VMCallFrame returns
         let frame = chunk_gen.scopes_mut().pop_frame();
+        chunk_gen.check_unused_locals(frame.iter_locals());
         
         // however we do still need to close upvalues before we return
-        for local in frame.iter_locals().filter(|local| local.captured()) {
-            chunk_gen.emit_close_upvalue(local.index());
+        if chunk_gen.reachable {
+            for local in frame.iter_locals().filter(|local| local.captured()) {
+                chunk_gen.emit_close_upvalue(local.index());
+            }
         }
         
         chunk_gen.finish();
@@ -1692,6 +2926,73 @@ impl CodeGenerator<'_> {
         Ok(())
     }
     
+    fn compile_class_def(&mut self, classdef: &ClassDef) -> CompileResult<()> {
+        // [ name parent method_name[0] method[0] ... ] => [ class ]
+        match classdef.name {
+            Some(name) => self.emit_load_const(Constant::from(name))?,
+            None => self.emit_instr(OpCode::Nil),
+        }
+        
+        // the parent class is stored in a local so that methods can capture it for "super"
+        if let Some(parent) = classdef.parent.as_deref() {
+            self.emit_begin_scope(None, ScopeTag::Block);
+            self.compile_expr_with_symbol(parent)?;
+            match self.scopes_mut().insert_local(Access::ReadOnly, LocalName::Super, None)? {
+                InsertLocal::CreateNew(..) => self.emit_instr(OpCode::InsertLocal),
+                InsertLocal::HideExisting(local_index) => self.emit_assign_local(local_index),
+            }
+        } else {
+            self.emit_instr(OpCode::Nil);
+        }
+        
+        let ctor_name = self.builder_mut().get_or_insert_str("new");
+        
+        let mut has_ctor = false;
+        for method in classdef.methods.iter() {
+            let name = method.signature.name.expect("unnamed method");
+            self.emit_load_const(Constant::from(name))?;
+            
+            let kind = 
+                if name.to_usize() == ctor_name {
+                    has_ctor = true;
+                    FunctionKind::Constructor
+                } else {
+                    FunctionKind::Method
+                };
+            
+            self.compile_function(method, kind)?;
+        }
+        
+        // classes without an explicit constructor inherit one from their parent,
+        // or if they have no parent get one that takes no arguments
+        let mut method_count = classdef.methods.len();
+        if !has_ctor && classdef.parent.is_none() {
+            let ctor = FunctionDef {
+                signature: SignatureDef {
+                    name: InternSymbol::try_from_usize(ctor_name),
+                    required: Box::new([]),
+                    default: Box::new([]),
+                    variadic: None,
+                },
+                body: Box::new(ExprBlock::from(StmtList::new(Vec::new(), None))),
+            };
+            
+            self.emit_load_const(Constant::String(ctor_name))?;
+            self.compile_function(&ctor, FunctionKind::Constructor)?;
+            method_count += 1;
+        }
+        
+        let method_count = u8::try_from(method_count)
+            .map_err(|_| CompileErrorKind::InternalLimit("too many methods in class"))?;
+        self.emit_instr_byte(OpCode::Class, method_count);
+        
+        if classdef.parent.is_some() {
+            self.emit_end_scope();
+        }
+        
+        Ok(())
+    }
+    
     fn compile_function_preamble(&mut self, fundef: &FunctionDef) -> CompileResult<()> {
         // process default and variadic arguments
         // this ensures that exactly `signature.param_count()` values are on the stack
@@ -1701,20 +3002,21 @@ impl CodeGenerator<'_> {
             return Ok(())
         }
         
+        // parameters are not checked for use, since callers may pass arguments that a function ignores
         for param in signature.required.iter() {
-            self.scopes_mut().insert_local(param.mode, LocalName::Symbol(param.name))?;
+            self.scopes_mut().insert_local(param.mode, LocalName::Symbol(param.name), None)?;
         }
         
         if !signature.default.is_empty() {
             self.compile_default_args(signature)?;
             for param in signature.default.iter() {
-                self.scopes_mut().insert_local(param.mode, LocalName::Symbol(param.name))?;
+                self.scopes_mut().insert_local(param.mode, LocalName::Symbol(param.name), None)?;
             }
         }
         
         if let Some(param) = &signature.variadic {
             self.compile_variadic_arg(signature)?;
-            self.scopes_mut().insert_local(param.mode, LocalName::Symbol(param.name))?;
+            self.scopes_mut().insert_local(param.mode, LocalName::Symbol(param.name), None)?;
         }
         
         self.emit_instr(OpCode::InsertArgs);
@@ -1726,13 +3028,12 @@ impl CodeGenerator<'_> {
         debug_assert!(!signature.default.is_empty());
         
         let mut jump_sites = Vec::new();
-        let mut jump_targets = Vec::new();
         
         // depending on the number of arguments, jump into the default argument sequence
         let required_count = u8::try_from(signature.required.len())
-            .map_err(|_| "parameter count limit exceeded")?;
+            .map_err(|_| CompileErrorKind::InternalLimit("parameter count limit exceeded"))?;
         let default_count = u8::try_from(signature.default.len())
-            .map_err(|_| "parameter count limit exceeded")?;
+            .map_err(|_| CompileErrorKind::InternalLimit("parameter count limit exceeded"))?;
         
         // "defaults passed" = NArgs - required_count
         self.try_emit_load_local(&LocalName::NArgs).unwrap();
@@ -1756,18 +3057,22 @@ impl CodeGenerator<'_> {
             self.emit_instr(OpCode::Clone);
             self.emit_instr_byte(OpCode::UInt8, count);
             self.emit_instr(OpCode::EQ);
+            let next_site = self.emit_dummy_jump(Jump::PopIfFalse);
             
-            let jump_site = self.emit_dummy_jump(Jump::PopIfTrue);
-            jump_sites.insert(count.into(), jump_site);
+            // "defaults passed" must be dropped before the default values are pushed
+            self.emit_instr(OpCode::Pop);
+            jump_sites.push(self.emit_dummy_jump(Jump::Uncond));
+            
+            self.patch_jump_instr(&next_site, self.current_offset())?;
         }
         
-        // if we get here, jump unconditionally to the end
-        let jump_site = self.emit_dummy_jump(Jump::Uncond);
-        jump_sites.insert(default_count.into(), jump_site);
+        // if we get here, all of the defaults were passed
+        self.emit_instr(OpCode::Pop);
+        let end_site = self.emit_dummy_jump(Jump::Uncond);
         
         // generate default arguments
-        for (idx, param) in signature.default.iter().enumerate() {
-            jump_targets.insert(idx, self.current_offset());
+        for (param, jump_site) in signature.default.iter().zip(jump_sites.iter()) {
+            self.patch_jump_instr(jump_site, self.current_offset())?;
             
             let symbol = param.default.debug_symbol();
             let expr = param.default.variant();
@@ -1776,14 +3081,7 @@ impl CodeGenerator<'_> {
             self.pop_symbol();
         }
         
-        jump_targets.insert(default_count.into(), self.current_offset());
-        self.emit_instr(OpCode::Pop);  // drop "defaults passed"
-        
-        // patch all jumps
-        debug_assert!(jump_sites.len() == jump_targets.len());
-        for (jump_site, jump_target) in jump_sites.iter().zip(jump_targets.into_iter()) {
-            self.patch_jump_instr(jump_site, jump_target)?;
-        }
+        self.patch_jump_instr(&end_site, self.current_offset())?;
         
         Ok(())
     }
@@ -1792,7 +3090,7 @@ impl CodeGenerator<'_> {
         debug_assert!(signature.variadic.is_some());
         
         let positional_count = u8::try_from(signature.required.len() + signature.default.len())
-            .map_err(|_| "parameter count limit exceeded")?;
+            .map_err(|_| CompileErrorKind::InternalLimit("parameter count limit exceeded"))?;
         
         // "variadic count" = NArgs - required_count - default_count
         self.try_emit_load_local(&LocalName::NArgs).unwrap();
@@ -1857,3 +3155,65 @@ impl CodeGenerator<'_> {
     }
     
 }
+
+
+///////// Diagnostics /////////
+impl CodeGenerator<'_> {
+    fn check_constant_condition(&mut self, condition: &Expr) {
+        if let Some(value) = constant_truth_value(condition) {
+            let message = format!("condition is always {}", value);
+            self.emit_warning(WarningKind::ConstantCondition, &message, self.current_symbol());
+        }
+    }
+    
+    fn check_unused_locals<'a>(&mut self, locals: impl Iterator<Item=&'a Local>) {
+        for local in locals.filter(|local| local.is_unused()) {
+            if let LocalName::Symbol(name) = local.name() {
+                let name = self.builder().resolve_str(name);
+                
+                // names starting with an underscore are meant to be unused
+                if !name.starts_with('_') {
+                    let message = format!("unused local variable \"{}\"", name);
+                    self.emit_warning(WarningKind::UnusedLocal, &message, local.debug_symbol().copied());
+                }
+            }
+        }
+    }
+}
+
+// the truth value of an expression, if it can be known without evaluating it
+fn constant_truth_value(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Atom(Atom::Nil) => Some(false),
+        Expr::Atom(Atom::BooleanLiteral(value)) => Some(*value),
+        
+        Expr::Atom(Atom::EmptyTuple)
+        | Expr::Atom(Atom::IntegerLiteral(..))
+        | Expr::Atom(Atom::BigIntegerLiteral(..))
+        | Expr::Atom(Atom::FloatLiteral(..))
+        | Expr::Atom(Atom::StringLiteral(..)) => Some(true),
+        
+        Expr::Atom(Atom::Group { modifier: None, inner }) => constant_truth_value(inner),
+        
+        _ => None,
+    }
+}
+
+// find the names that are declared by a pattern
+fn collect_decl_names(action: MatchAction, pattern: &Pattern, names: &mut Vec<InternSymbol>) {
+    match pattern {
+        Pattern::Identifier(name) => if matches!(action, MatchAction::DeclImmutable | MatchAction::DeclMutable) {
+            names.push(*name)
+        },
+        
+        Pattern::Modifier { modifier, pattern } => collect_decl_names(*modifier, pattern, names),
+        
+        Pattern::Tuple(items) => for item in items.iter() {
+            collect_decl_names(action, &item.pattern, names)
+        },
+        
+        Pattern::Pack(Some(pattern)) => collect_decl_names(action, pattern, names),
+        
+        Pattern::Pack(None) | Pattern::Attribute(..) | Pattern::Index(..) => { },
+    }
+}
@@ -1,16 +1,24 @@
 use core::str;
 use core::ops::Range;
 use std::collections::HashMap;
+use std::io::{self, Read, Write};
 use string_interner::Symbol as _;
-use crate::language::InternSymbol;
-use crate::runtime::{DefaultBuildHasher, STRING_TABLE};
+use crate::language::{InternSymbol, FloatType};
+use crate::runtime::Variant;
+use crate::runtime::DefaultBuildHasher;
+use crate::runtime::strings::with_string_table;
 use crate::runtime::strings::{StringInterner, StringSymbol};
 use crate::runtime::function::{Signature, Parameter};
 use crate::runtime::errors::ErrorKind;
+use crate::runtime::vm::InlineCache;
 use crate::codegen::consts::{Constant, ConstID, StringID};
 use crate::codegen::funproto::{FunctionProto, UnloadedFunction, UnloadedSignature, UnloadedParam, FunctionID};
-use crate::codegen::errors::CompileResult;
+use crate::codegen::opcodes::CacheIndex;
+use crate::codegen::errors::{CompileResult, ErrorKind as CompileErrorKind};
+use crate::codegen::serialize::{self, WriteBytes, ReadBytes};
+use crate::codegen::verify::{self, VerifyResult};
 use crate::debug::DebugSymbol;
+use crate::debug::symbol::{ChunkSymbols, DebugSymbolTable};
 
 
 
@@ -82,13 +90,6 @@ impl ChunkBuf {
         let target = &mut self.bytes[patch_range];
         target.copy_from_slice(patch);
     }
-    
-    /// anything previously inside the patch is overwritten
-    pub fn resize_patch(&mut self, offset: usize, from_len: usize, to_len: usize) {
-        let patch_range = offset..(offset + from_len);
-        let patch = core::iter::repeat(u8::default()).take(to_len);
-        self.bytes.splice(patch_range, patch);
-    }
 }
 
 
@@ -99,6 +100,7 @@ pub struct ChunkBuilder {
     functions: Vec<Option<UnloadedFunction>>,
     dedup: HashMap<Constant, ConstID, DefaultBuildHasher>,
     strings: StringInterner,
+    cache_slots: usize,
 }
 
 impl Default for ChunkBuilder {
@@ -120,6 +122,7 @@ impl ChunkBuilder {
             consts: Vec::new(),
             dedup: HashMap::with_hasher(DefaultBuildHasher::default()),
             strings,
+            cache_slots: 0,
         }
     }
     
@@ -127,7 +130,7 @@ impl ChunkBuilder {
     
     pub fn new_chunk(&mut self, info: ChunkInfo) -> CompileResult<Chunk> {
         let chunk_id = FunctionID::try_from(self.chunks.len())
-            .map_err(|_| "function count limit reached")?;
+            .map_err(|_| CompileErrorKind::InternalLimit("function count limit reached"))?;
         
         self.chunks.push(ChunkBuf::new(info));
         self.functions.push(None);
@@ -161,7 +164,7 @@ impl ChunkBuilder {
             Ok(*cid)
         } else {
             let cid = ConstID::try_from(self.consts.len())
-                .map_err(|_| "constant pool limit reached")?;
+                .map_err(|_| CompileErrorKind::InternalLimit("constant pool limit reached"))?;
             self.consts.push(value);
             self.dedup.insert(value, cid);
             Ok(cid)
@@ -173,6 +176,14 @@ impl ChunkBuilder {
         symbol.to_usize()
     }
     
+    pub fn strings(&self) -> &StringInterner { &self.strings }
+    
+    pub fn strings_mut(&mut self) -> &mut StringInterner { &mut self.strings }
+    
+    pub fn resolve_str(&self, symbol: InternSymbol) -> &str {
+        self.strings.resolve(symbol).expect("invalid symbol")
+    }
+    
     pub fn get_or_insert_error(&mut self, error: ErrorKind, message: &str) -> CompileResult<ConstID> {
         let message = self.get_or_insert_str(message);
         self.get_or_insert_const(Constant::Error { error, message })
@@ -187,21 +198,55 @@ impl ChunkBuilder {
         self.functions[fun_index].replace(fun_proto);
     }
     
+    // Inline Caches
+    
+    pub fn new_cache_slot(&mut self) -> CompileResult<CacheIndex> {
+        let index = CacheIndex::try_from(self.cache_slots)
+            .map_err(|_| CompileErrorKind::InternalLimit("inline cache limit reached"))?;
+        
+        self.cache_slots += 1;
+        Ok(index)
+    }
+    
+    // Checkpoints
+    
+    pub fn checkpoint(&self) -> BuilderCheckpoint {
+        BuilderCheckpoint {
+            main_len: self.main.len(),
+            chunk_count: self.chunks.len(),
+            cache_slots: self.cache_slots,
+        }
+    }
+    
+    /// Discard all bytecode, functions, and cache slots added since the checkpoint.
+    /// Constants and strings are kept, since they may have been deduplicated with ones that are still in use.
+    pub fn restore(&mut self, checkpoint: &BuilderCheckpoint) {
+        self.main.bytes.truncate(checkpoint.main_len);
+        self.chunks.truncate(checkpoint.chunk_count);
+        self.functions.truncate(checkpoint.chunk_count);
+        self.cache_slots = checkpoint.cache_slots;
+    }
+    
     // Output
     
     pub fn build(self) -> UnloadedProgram {
+        self.build_from(0)
+    }
+    
+    /// Like build(), except that the main chunk only contains the bytecode starting at the given offset
+    pub fn build_from(&self, main_offset: usize) -> UnloadedProgram {
         let bytes_len = self.chunks.iter().map(|chunk| chunk.bytes.len()).sum();
         let mut chunks = Vec::with_capacity(bytes_len);
         let mut chunk_index = Vec::with_capacity(self.chunks.len());
         
-        for chunk in self.chunks.into_iter() {
+        for chunk in self.chunks.iter() {
             let offset = chunks.len();
             let length = chunk.bytes.len();
-            chunks.extend(chunk.bytes);
+            chunks.extend(&chunk.bytes);
             
             let index = ChunkIndex {
                 offset, length,
-                info: chunk.info,
+                info: chunk.info.clone(),
             };
             chunk_index.push(index);
         }
@@ -220,7 +265,7 @@ impl ChunkBuilder {
             let index = StringIndex {
                 offset, length
             };
-            string_index.insert(symbol.to_usize(), index);
+            string_index[symbol.to_usize()] = index;
         }
         
         // truncate trailing `None` values
@@ -228,23 +273,38 @@ impl ChunkBuilder {
             .find_map(|(idx, fun)| fun.as_ref().map(|_| idx + 1))
             .unwrap_or(0);
         
-        let functions = self.functions.into_iter().take(fun_len)
-            .map(|fun| fun.expect("function ids must be contiguous"))
+        let functions = self.functions.iter().take(fun_len)
+            .map(|fun| fun.clone().expect("function ids must be contiguous"))
             .collect::<Vec<UnloadedFunction>>();
         
         UnloadedProgram {
-            main: self.main.bytes.into_boxed_slice(),
+            main: self.main.bytes[main_offset..].into(),
             chunks: chunks.into_boxed_slice(),
             chunk_index: chunk_index.into_boxed_slice(),
             strings: strings.into_boxed_slice(),
             string_index: string_index.into_boxed_slice(),
-            consts: self.consts.into_boxed_slice(),
+            consts: self.consts.as_slice().into(),
             functions: functions.into_boxed_slice(),
+            cache_slots: self.cache_slots,
         }
     }
 }
 
 
+/// The state of a ChunkBuilder at some point, which it can be restored to later
+#[derive(Debug, Clone)]
+pub struct BuilderCheckpoint {
+    main_len: usize,
+    chunk_count: usize,
+    cache_slots: usize,
+}
+
+impl BuilderCheckpoint {
+    pub fn main_len(&self) -> usize { self.main_len }
+    pub fn chunk_count(&self) -> usize { self.chunk_count }
+}
+
+
 // TODO store all chunk bytes in a single array
 // TODO figure out how debug symbols will work, esp. at runtime
 
@@ -290,6 +350,7 @@ pub struct UnloadedProgram {
     string_index: Box<[StringIndex]>,
     consts: Box<[Constant]>,
     functions: Box<[UnloadedFunction]>,
+    cache_slots: usize,
 }
 
 impl UnloadedProgram {
@@ -315,7 +376,7 @@ impl UnloadedProgram {
     }
     
     pub fn get_string(&self, string_id: StringID) -> &str {
-        let string_idx = &self.string_index[usize::from(string_id)];
+        let string_idx = &self.string_index[string_id];
         str::from_utf8(&self.strings[string_idx.as_range()]).expect("invalid string")
     }
     
@@ -333,6 +394,101 @@ impl UnloadedProgram {
     pub fn get_function(&self, index: FunctionID) -> &UnloadedFunction {
         &self.functions[usize::from(index)]
     }
+    
+    pub fn consts(&self) -> &[Constant] {
+        &self.consts
+    }
+    
+    pub fn functions(&self) -> &[UnloadedFunction] {
+        &self.functions
+    }
+    
+    /// The number of inline cache slots used by `GET_ATTR` and `LD_GLOBAL` instructions
+    pub fn cache_slots(&self) -> usize {
+        self.cache_slots
+    }
+    
+    /// Check that the program's bytecode is well-formed. See `codegen::verify`.
+    pub fn verify(&self) -> VerifyResult<()> {
+        verify::verify_program(self)
+    }
+    
+    /// Encode the program using a versioned binary format
+    pub fn write_to(&self, mut write: impl Write) -> io::Result<()> {
+        let write = &mut write;
+        
+        serialize::write_header(write)?;
+        write.write_bytes(&self.main)?;
+        
+        write.write_bytes(&self.chunks)?;
+        serialize::write_seq(write, &self.chunk_index, |write, index| {
+            write.write_len(index.offset)?;
+            write.write_len(index.length)?;
+            serialize::write_chunk_info(write, &index.info)
+        })?;
+        
+        write.write_bytes(&self.strings)?;
+        serialize::write_seq(write, &self.string_index, |write, index| {
+            write.write_len(index.offset)?;
+            write.write_len(index.length)
+        })?;
+        
+        serialize::write_seq(write, &self.consts, serialize::write_constant)?;
+        serialize::write_seq(write, &self.functions, serialize::write_function)?;
+        write.write_len(self.cache_slots)
+    }
+    
+    /// Decode a program written by `write_to()`.
+    /// The program's bytecode is verified, so it is safe to load even if it comes from an untrusted source.
+    pub fn read_from(mut read: impl Read) -> io::Result<Self> {
+        let read = &mut read;
+        
+        serialize::read_header(read)?;
+        let main = read.read_bytes()?;
+        
+        let chunks = read.read_bytes()?;
+        let chunk_index = read.read_seq(|read| Ok(ChunkIndex {
+            offset: read.read_len()?,
+            length: read.read_len()?,
+            info: serialize::read_chunk_info(read)?,
+        }))?;
+        
+        let strings = read.read_bytes()?;
+        let string_index = read.read_seq(|read| Ok(StringIndex {
+            offset: read.read_len()?,
+            length: read.read_len()?,
+        }))?;
+        
+        let consts = read.read_seq(serialize::read_constant)?;
+        let functions = read.read_seq(serialize::read_function)?;
+        let cache_slots = read.read_len()?;
+        
+        if chunk_index.iter().any(|index| !Self::is_valid_range(index.offset, index.length, chunks.len())) {
+            return Err(serialize::invalid_data("chunk index out of range"));
+        }
+        
+        for index in string_index.iter() {
+            if !Self::is_valid_range(index.offset, index.length, strings.len()) {
+                return Err(serialize::invalid_data("string index out of range"));
+            }
+            if str::from_utf8(&strings[index.as_range()]).is_err() {
+                return Err(serialize::invalid_data("invalid string"));
+            }
+        }
+        
+        let program = Self {
+            main, chunks, chunk_index, strings, string_index, consts, functions, cache_slots,
+        };
+        
+        program.verify()
+            .map_err(|error| serialize::invalid_data(&error.to_string()))?;
+        
+        Ok(program)
+    }
+    
+    fn is_valid_range(offset: usize, length: usize, len: usize) -> bool {
+        offset.checked_add(length).is_some_and(|end| end <= len)
+    }
 }
 
 
@@ -343,7 +499,10 @@ pub struct ProgramData {
     chunk_index: Box<[ChunkIndex]>,
     strings: Box<[StringSymbol]>,
     consts: Box<[Constant]>,
+    values: Box<[Variant]>,  // constants converted when the program is loaded, or nil if they can't be reused
     functions: Box<[FunctionProto]>,
+    caches: Box<[InlineCache]>,
+    symbols: Option<ChunkSymbols>,
 }
 
 impl ProgramData {
@@ -363,14 +522,29 @@ impl ProgramData {
         &self.consts[usize::from(index)]
     }
     
+    /// The value of a constant, unless it needs a new allocation every time it is loaded (i.e. errors)
+    #[inline(always)]
+    pub fn get_value(&self, index: ConstID) -> Option<Variant> {
+        let value = self.values[usize::from(index)];
+        if value.is_nil() { None } else { Some(value) }
+    }
+    
     pub fn get_string(&self, index: StringID) -> &StringSymbol {
-        &self.strings[usize::from(index)]
+        &self.strings[index]
     }
     
     pub fn get_function(&self, index: FunctionID) -> &FunctionProto {
         &self.functions[usize::from(index)]
     }
     
+    #[inline(always)]
+    pub fn get_cache(&self, index: CacheIndex) -> &InlineCache {
+        &self.caches[usize::from(index)]
+    }
+    
+    pub fn debug_symbols(&self, chunk_id: &Chunk) -> Option<&DebugSymbolTable> {
+        self.symbols.as_ref().and_then(|symbols| symbols.get(chunk_id))
+    }
 }
 
 
@@ -384,7 +558,7 @@ impl Program {
     pub fn load(program: UnloadedProgram) -> Self {
         
         // Convert strings to StringSymbols
-        let strings = STRING_TABLE.with(|string_table| {
+        let strings = with_string_table(|string_table| {
             let mut string_table = string_table.borrow_mut();
             
             let mut strings = Vec::with_capacity(program.strings.len());
@@ -402,24 +576,46 @@ impl Program {
             })
             .collect();
         
+        let values = program.consts.iter()
+            .map(|constant| Self::load_value(constant, &strings))
+            .collect();
+        
         Self {
             main: program.main,
             data: ProgramData {
                 chunks: program.chunks,
                 chunk_index: program.chunk_index,
                 consts: program.consts,
+                values,
                 functions: functions.into_boxed_slice(),
                 strings: strings.into_boxed_slice(),
+                caches: (0..program.cache_slots).map(|_| InlineCache::default()).collect(),
+                symbols: None,
             },
         }
     }
     
+    /// Attach debug symbols, allowing tracebacks to be resolved to source locations
+    pub fn with_symbols(mut self, symbols: ChunkSymbols) -> Self {
+        self.data.symbols.replace(symbols); self
+    }
+    
+    // none of these values refer to GC allocations, so they don't need to be traced
+    fn load_value(constant: &Constant, strings: &[StringSymbol]) -> Variant {
+        match constant {
+            Constant::Integer(value) => Variant::from(*value),
+            Constant::Float(bytes) => Variant::from(FloatType::from_le_bytes(*bytes)),
+            Constant::String(idx) => Variant::from(strings[*idx]),
+            Constant::BigInt(..) | Constant::Error { .. } => Variant::Nil,
+        }
+    }
+    
     fn load_name(const_id: ConstID, consts: &[Constant], strings: &[StringSymbol]) -> StringSymbol {
         let string_id = match consts[usize::from(const_id)] {
             Constant::String(symbol) => symbol,
             _ => panic!("invalid name constant")
         };
-        strings[usize::from(string_id)]
+        strings[string_id]
     }
     
     fn load_signature(signature: UnloadedSignature, consts: &[Constant], strings: &[StringSymbol]) -> Signature {
@@ -1,4 +1,4 @@
-/// Data structures for constant values that are compiled with chunks
+//! Data structures for constant values that are compiled with chunks
 
 use core::mem;
 use string_interner::Symbol;
@@ -16,6 +16,7 @@ pub enum Constant {
     Integer(IntType),
     Float([u8; mem::size_of::<FloatType>()]),  // we might store redundant floats, that's fine
     String(StringID),
+    BigInt(StringID),  // the source text of an integer literal, which is too large for IntType
     Error { error: ErrorKind, message: StringID },
 }
 
@@ -2,21 +2,79 @@ use core::fmt;
 use std::error::Error;
 
 use crate::utils;
-use crate::debug::{DebugSymbol, SourceError};
+use crate::debug::{DebugSymbol, SourceError, Severity};
+use crate::debug::codes::{self, ErrorCode};
 
 
 pub type CompileResult<T> = Result<T, CompileError>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    CantAssignImmutable,
+    CantAssignNonLocal,
+    InvalidAssignment(&'static str),
+    InvalidUnpack(&'static str),
+    CantResolveBreak { labeled: bool },
+    CantResolveContinue { labeled: bool },
+    InvalidBreakWithValue,
+    InvalidReturnValue,
+    InvalidSelf,
+    InvalidSuper(&'static str),
+    InvalidExport(&'static str),
+    InternalLimit(&'static str),
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InternalLimit(..) => codes::E0201,
+            Self::CantAssignImmutable => codes::E0202,
+            Self::CantAssignNonLocal => codes::E0203,
+            Self::InvalidAssignment(..) => codes::E0204,
+            Self::InvalidUnpack(..) => codes::E0205,
+            Self::CantResolveBreak {..} => codes::E0206,
+            Self::CantResolveContinue {..} => codes::E0207,
+            Self::InvalidBreakWithValue => codes::E0208,
+            Self::InvalidReturnValue => codes::E0209,
+            Self::InvalidSelf => codes::E0210,
+            Self::InvalidSuper(..) => codes::E0211,
+            Self::InvalidExport(..) => codes::E0212,
+        }
+    }
+    
+    fn message(&self) -> &'static str {
+        match self {
+            Self::CantAssignImmutable => "can't assign to immutable local variable",
+            Self::CantAssignNonLocal => "can't assign to a non-local variable without the \"nonlocal\" keyword",
+            
+            Self::CantResolveBreak { labeled } => 
+                if *labeled { "can't find loop or block with matching label for \"break\"" }
+                else { "\"break\" outside of loop or block" },
+            
+            Self::CantResolveContinue { labeled } => 
+                if *labeled { "can't find loop with matching label for \"continue\"" }
+                else { "\"continue\" outside of loop" },
+            
+            Self::InvalidBreakWithValue => "\"break\" with value outside of block expression",
+            Self::InvalidReturnValue => "can't return a value from a constructor",
+            Self::InvalidSelf => "\"self\" can only be used inside a method",
+            
+            Self::InvalidAssignment(message) | Self::InvalidUnpack(message) | Self::InvalidSuper(message)
+            | Self::InvalidExport(message) | Self::InternalLimit(message) => message,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CompileError {
-    message: String,
+    kind: ErrorKind,
     symbol: Option<DebugSymbol>,
     cause: Option<Box<dyn Error>>,
 }
 
 impl CompileError {
-    pub fn new(message: &str) -> Self {
-        Self { message: message.to_string(), symbol: None, cause: None }
+    pub fn new(kind: ErrorKind) -> Self {
+        Self { kind, symbol: None, cause: None }
     }
     
     pub fn with_symbol(mut self, symbol: DebugSymbol) -> Self {
@@ -26,11 +84,13 @@ impl CompileError {
     pub fn caused_by(mut self, error: impl Error + 'static) -> Self {
         self.cause.replace(Box::new(error)); self
     }
+    
+    pub fn kind(&self) -> &ErrorKind { &self.kind }
 }
 
-impl<S> From<S> for CompileError where S: AsRef<str> {
-    fn from(message: S) -> Self {
-        Self::new(message.as_ref())
+impl From<ErrorKind> for CompileError {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
     }
 }
 
@@ -42,34 +102,67 @@ impl Error for CompileError {
 
 impl SourceError for CompileError {
     fn debug_symbol(&self) -> Option<&DebugSymbol> { self.symbol.as_ref() }
+    
+    fn error_code(&self) -> Option<ErrorCode> { Some(self.kind.code()) }
 }
 
 impl fmt::Display for CompileError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        
-        // let message = match self.kind() {
-        //     ErrorKind::CantAssignImmutable => "can't assign to immutable local variable",
-        //     ErrorKind::CantAssignNonLocal => "can't assign to a non-local variable without the \"nonlocal\" keyword",
-        //     ErrorKind::TupleLenMismatch => "can't assign tuples of different lengths",
-        //     ErrorKind::CantUpdateAssignTuple => "can't use update-assigment when assigning to a tuple",
-            
-        //     ErrorKind::CantResolveBreak(label) => 
-        //         if label.is_some() { "can't find loop or block with matching label for \"break\"" }
-        //         else { "\"break\" outside of loop or block" },
-            
-        //     ErrorKind::CantResolveContinue(label) => 
-        //         if label.is_some() { "can't find loop with matching label for \"continue;\"" }
-        //         else { "\"continue\" outside of loop" },
-            
-        //     ErrorKind::InvalidBreakWithValue => "\"break\" with value outside of block expression",
-        //     ErrorKind::InvalidLValueModifier => "assignment modifier is not allowed here",
-        //     ErrorKind::InternalLimit(message) => message,
-        // };
-        
-        let message =
-            if self.message.is_empty() { None }
-            else { Some(self.message.as_str()) };
-        
-        utils::format_error(fmt, "Compile error", message, self.source())
+        utils::format_error(fmt, "Compile error", Some(self.kind.message()), self.source())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    UnreachableCode,
+    ConstantCondition,
+    ShadowedName,
+    UnusedLocal,
+    UnusedImport,
+}
+
+impl WarningKind {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnreachableCode => codes::W0201,
+            Self::ConstantCondition => codes::W0202,
+            Self::ShadowedName => codes::W0203,
+            Self::UnusedLocal => codes::W0204,
+            Self::UnusedImport => codes::W0205,
+        }
     }
-}
\ No newline at end of file
+}
+
+/// A problem with the source that does not prevent it from being compiled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileWarning {
+    kind: WarningKind,
+    message: String,
+    symbol: Option<DebugSymbol>,
+}
+
+impl CompileWarning {
+    pub fn new(kind: WarningKind, message: &str, symbol: Option<DebugSymbol>) -> Self {
+        Self { kind, message: message.to_string(), symbol }
+    }
+    
+    pub fn kind(&self) -> &WarningKind { &self.kind }
+    
+    pub fn message(&self) -> &str { self.message.as_str() }
+}
+
+impl Error for CompileWarning { }
+
+impl SourceError for CompileWarning {
+    fn debug_symbol(&self) -> Option<&DebugSymbol> { self.symbol.as_ref() }
+    
+    fn severity(&self) -> Severity { Severity::Warning }
+    
+    fn error_code(&self) -> Option<ErrorCode> { Some(self.kind.code()) }
+}
+
+impl fmt::Display for CompileWarning {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        utils::format_error(fmt, "Warning", Some(self.message.as_str()), None)
+    }
+}
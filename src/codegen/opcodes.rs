@@ -3,6 +3,12 @@ use core::mem::size_of;
 
 pub type LocalIndex = u16;
 pub type UpvalueIndex = u16;
+pub type CacheIndex = u16;
+
+// the mode operand of a register instruction has a bit for each source operand,
+// which is set if the operand is a constant index instead of a local index
+pub const REG_CONST_SRC1: u8 = 1 << 0;
+pub const REG_CONST_SRC2: u8 = 1 << 1;
 
 
 // Opcodes
@@ -18,12 +24,22 @@ const OP_NOP:              u8 = 0x00;
 const OP_EXIT:             u8 = 0x01;  // _ => !
 const OP_ERROR:            u8 = 0x02;  // T[ error ] => !
 
+const OP_PUSH_HANDLER:     u8 = 0x03;  // (i16); register an error handler at the jump target
+const OP_LPUSH_HANDLER:    u8 = 0x04;  // (i32); register an error handler at the jump target
+const OP_POP_HANDLER:      u8 = 0x05;  // discard the most recent error handler
+
+const OP_IMPORT:           u8 = 0x06;  // [ name ] => []; execute a module and bind its exports
+const OP_EXPORT:           u8 = 0x07;  // [ name ] => []; make a global visible to importers
+
 const OP_RETURN:           u8 = 0x08;  // T[ ...call frame... ret_value ] => [ ret_value ]
 
 // [ callee arg[0] ... arg[n] nargs ] => [ ret_value ] 
 const OP_CALL:             u8 = 0x09;
 const OP_IN_ARGS:          u8 = 0x0A;
 
+// same as CALL, but the callee may replace the current call frame. Always followed by RETURN
+const OP_TAIL_CALL:        u8 = 0x0B;
+
 // 0x10-17        Immediate Values
 
 const OP_POP:              u8 = 0x10;  // [ _ ] => []
@@ -33,6 +49,9 @@ const OP_CLONE:            u8 = 0x13;  // [ value ] => [ value value ]
 const OP_SWAP:             u8 = 0x14;  // (u8); [ value[A] ... value[B] ] => [ value[B] ... value[A] ]
 const OP_SHIFT:            u8 = 0x15;  // (u8); [ value[A] ... value[B] ] => [ ... value[B] value[A] ]
 
+const OP_LIST:             u8 = 0x16;  // (u8); [ item[0] ... item[N] ] => [ list ]
+const OP_LISTN:            u8 = 0x17;  // [ item[0] ... item[N] N ] => [ list ]
+
 const OP_TUPLE:            u8 = 0x18;  // (u8); [ item[0] ... item[N] ] => [ tuple ]
 const OP_TUPLEN:           u8 = 0x19;  // [ item[0] ... item[N] N ] => [ tuple ]
 
@@ -42,6 +61,16 @@ const OP_ITER_INIT:        u8 = 0x1A;  // [ iterable ] => [ iter state[0] ]
 const OP_ITER_NEXT:        u8 = 0x1B;  // [ iter state[N] ] => [ iter state[N+1] value[N] ]
 const OP_ITER_UNPACK:      u8 = 0x1C;  // [ iter state[N] ] => [ value[N] ... value[M] (M-N) ]
 
+const OP_TUPLE_LEN:        u8 = 0x1D;  // [ value ] => [ len ]; the length of a tuple, or -1 for any other value
+
+// 0x20-27        Member Access
+
+const OP_GET_ATTR:         u8 = 0x20;  // (u16); [ receiver name ] => [ value ]; the operand is an inline cache slot
+const OP_SET_ATTR:         u8 = 0x21;  // [ value receiver name ] => [ value ]
+const OP_GET_INDEX:        u8 = 0x22;  // [ receiver index ] => [ value ]
+const OP_SET_INDEX:        u8 = 0x23;  // [ value receiver index ] => [ value ]
+const OP_GET_SUPER:        u8 = 0x24;  // [ receiver class name ] => [ method ]
+
 // 0x40-5F        Load/Store
 
 const OP_LD_FUN:           u8 = 0x40;  // (u8);  _ => [ function ]
@@ -51,10 +80,14 @@ const OP_LD_CONST:         u8 = 0x42;  // (u8);  _ => [ value ]
 const OP_LD_CONST_16:      u8 = 0x43;  // (u16); _ => [ value ]
 // const OP_LD_CONST_32:   u8 = 0x44;  // (u32); _ => [ value ]
 
+const OP_CLASS:            u8 = 0x45;  // (u8); [ name parent method_name[0] method[0] ... method_name[N] method[N] ] => [ class ]
+const OP_DICT:             u8 = 0x46;  // (u8); [ key[0] value[0] ... key[N] value[N] ] => [ dict ]
+const OP_DICTN:            u8 = 0x47;  // [ key[0] value[0] ... key[N] value[N] N ] => [ dict ]
+
 const OP_IN_GLOBAL_IM:     u8 = 0x48;  // [ value name ] => [ value ]
 const OP_IN_GLOBAL_MUT:    u8 = 0x49;  // [ value name ] => [ value ]
 const OP_ST_GLOBAL:        u8 = 0x4A;  // [ value name ] => [ value ]
-const OP_LD_GLOBAL:        u8 = 0x4B;  // [ name ] => [ value ]
+const OP_LD_GLOBAL:        u8 = 0x4B;  // (u16); [ name ] => [ value ]; the operand is an inline cache slot
 const OP_DP_GLOBAL:        u8 = 0x4C;  // [ name ] => []
 
 const OP_IN_LOCAL:         u8 = 0x50;  // [ value ] => [ value ];
@@ -99,11 +132,16 @@ const OP_OR:               u8 = 0x7A;
 const OP_SHL:              u8 = 0x7B;
 const OP_SHR:              u8 = 0x7C;
 
+const OP_IS:               u8 = 0x7D;  // [ lhs rhs ] => [ result ]; compares identity, without invoking any metamethods
+
 const OP_ADD:              u8 = 0x80;
 const OP_SUB:              u8 = 0x81;
 const OP_MUL:              u8 = 0x82;
 const OP_DIV:              u8 = 0x83;
 const OP_MOD:              u8 = 0x84;
+const OP_FLOORDIV:         u8 = 0x85;
+const OP_EXP:              u8 = 0x86;
+const OP_CONCAT:           u8 = 0x87;  // (u8); [ value[0] ... value[N] ] => [ string ]
 
 const OP_EQ:               u8 = 0x88;
 const OP_NE:               u8 = 0x89;
@@ -111,6 +149,8 @@ const OP_LT:               u8 = 0x8A;
 const OP_LE:               u8 = 0x8B;
 const OP_GE:               u8 = 0x8C;
 const OP_GT:               u8 = 0x8D;
+const OP_IN:               u8 = 0x8E;  // [ item container ] => [ result ]
+const OP_NOT_IN:           u8 = 0x8F;
 
 // 0x90-9F      Jumps
 
@@ -119,12 +159,43 @@ const OP_JUMP_FALSE:       u8 = 0x91;  // (i16); [ cond ] => [ cond ]
 const OP_JUMP_TRUE:        u8 = 0x92;  // (i16); [ cond ] => [ cond ]
 const OP_PJMP_FALSE:       u8 = 0x93;  // (i16); [ cond ] => []
 const OP_PJMP_TRUE:        u8 = 0x94;  // (i16); [ cond ] => []
+const OP_JUMP_NIL:         u8 = 0x95;  // (i16); [ value ] => [ value ]
+const OP_JUMP_NOT_NIL:     u8 = 0x96;  // (i16); [ value ] => [ value ]
 
 const OP_LJUMP:            u8 = 0x98;  // (i32);
 const OP_LJUMP_FALSE:      u8 = 0x99;  // (i32); [ cond ] => [ cond ]
 const OP_LJUMP_TRUE:       u8 = 0x9A;  // (i32); [ cond ] => [ cond ]
 const OP_PLJMP_FALSE:      u8 = 0x9B;  // (i32); [ cond ] => []
 const OP_PLJMP_TRUE:       u8 = 0x9C;  // (i32); [ cond ] => []
+const OP_LJUMP_NIL:        u8 = 0x9D;  // (i32); [ value ] => [ value ]
+const OP_LJUMP_NOT_NIL:    u8 = 0x9E;  // (i32); [ value ] => [ value ]
+
+// 0xA0-AF      Superinstructions
+
+// fused versions of the instruction sequences that dominate the execution of tight loops
+
+const OP_ADD_LOCAL_CONST:  u8 = 0xA0;  // (u8 local, u8 const); _ => [ local + const ]
+const OP_SUB_LOCAL_CONST:  u8 = 0xA1;  // (u8 local, u8 const); _ => [ local - const ]
+
+// compare and jump. GT, GE, and NE are evaluated as the negation of LE, LT, and EQ, so they don't need their own
+const OP_JMP_EQ:           u8 = 0xA8;  // (i16); [ lhs rhs ] => []
+const OP_JMP_NOT_EQ:       u8 = 0xA9;  // (i16); [ lhs rhs ] => []
+const OP_JMP_LT:           u8 = 0xAA;  // (i16); [ lhs rhs ] => []
+const OP_JMP_NOT_LT:       u8 = 0xAB;  // (i16); [ lhs rhs ] => []
+const OP_JMP_LE:           u8 = 0xAC;  // (i16); [ lhs rhs ] => []
+const OP_JMP_NOT_LE:       u8 = 0xAD;  // (i16); [ lhs rhs ] => []
+
+// 0xB0-BF      Register Instructions
+
+// used by the experimental register backend. These operate on local variables and constants directly
+// and leave the stack untouched. The source operands are selected using the mode operand (see REG_CONST_SRC1)
+
+const OP_MOV_REG:          u8 = 0xB0;  // (u8 mode, u8 dst, u8 src); local[dst] = src
+const OP_ADD_REG:          u8 = 0xB1;  // (u8 mode, u8 dst, u8 lhs, u8 rhs); local[dst] = lhs + rhs
+const OP_SUB_REG:          u8 = 0xB2;  // (u8 mode, u8 dst, u8 lhs, u8 rhs); local[dst] = lhs - rhs
+const OP_MUL_REG:          u8 = 0xB3;  // (u8 mode, u8 dst, u8 lhs, u8 rhs); local[dst] = lhs * rhs
+const OP_DIV_REG:          u8 = 0xB4;  // (u8 mode, u8 dst, u8 lhs, u8 rhs); local[dst] = lhs / rhs
+const OP_MOD_REG:          u8 = 0xB5;  // (u8 mode, u8 dst, u8 lhs, u8 rhs); local[dst] = lhs % rhs
 
 // 0xF0-FF      Debugging/Tracing/Misc
 
@@ -141,10 +212,17 @@ pub enum OpCode {
     Nop = OP_NOP,
     Exit = OP_EXIT,
     Error = OP_ERROR,
+    PushHandler = OP_PUSH_HANDLER,
+    LongPushHandler = OP_LPUSH_HANDLER,
+    PopHandler = OP_POP_HANDLER,
+    
+    Import = OP_IMPORT,
+    Export = OP_EXPORT,
     
     Return = OP_RETURN, 
     Call = OP_CALL,
     InsertArgs = OP_IN_ARGS,
+    TailCall = OP_TAIL_CALL,
     
     Pop = OP_POP,
     Drop = OP_DROP,
@@ -153,10 +231,22 @@ pub enum OpCode {
     
     Tuple = OP_TUPLE,
     TupleN = OP_TUPLEN,
+    BuildList = OP_LIST,
+    BuildListN = OP_LISTN,
+    BuildDict = OP_DICT,
+    BuildDictN = OP_DICTN,
+    Concat = OP_CONCAT,
     
     IterInit = OP_ITER_INIT,
     IterNext = OP_ITER_NEXT,
     IterUnpack = OP_ITER_UNPACK,
+    TupleLen = OP_TUPLE_LEN,
+    
+    GetAttr = OP_GET_ATTR,
+    SetAttr = OP_SET_ATTR,
+    GetIndex = OP_GET_INDEX,
+    SetIndex = OP_SET_INDEX,
+    GetSuper = OP_GET_SUPER,
     
     LoadFunction = OP_LD_FUN,
     LoadFunction16 = OP_LD_FUN_16,
@@ -164,6 +254,8 @@ pub enum OpCode {
     LoadConst  = OP_LD_CONST,
     LoadConst16 = OP_LD_CONST_16,
     
+    Class = OP_CLASS,
+    
     InsertGlobal = OP_IN_GLOBAL_IM,
     InsertGlobalMut = OP_IN_GLOBAL_MUT,
     StoreGlobal = OP_ST_GLOBAL,
@@ -208,24 +300,50 @@ pub enum OpCode {
     Mul = OP_MUL,
     Div = OP_DIV,
     Mod = OP_MOD,
+    FloorDiv = OP_FLOORDIV,
+    Exp = OP_EXP,
     EQ = OP_EQ,
     NE = OP_NE,
     LT = OP_LT,
     LE = OP_LE,
     GE = OP_GE,
     GT = OP_GT,
+    In = OP_IN,
+    NotIn = OP_NOT_IN,
+    Is = OP_IS,
     
     Jump = OP_JUMP,
     JumpIfFalse = OP_JUMP_FALSE,
     JumpIfTrue = OP_JUMP_TRUE,
     PopJumpIfFalse = OP_PJMP_FALSE,
     PopJumpIfTrue = OP_PJMP_TRUE,
+    JumpIfNil = OP_JUMP_NIL,
+    JumpIfNotNil = OP_JUMP_NOT_NIL,
     
     LongJump = OP_LJUMP,
     LongJumpIfFalse = OP_LJUMP_FALSE,
     LongJumpIfTrue = OP_LJUMP_TRUE,
     PopLongJumpIfFalse = OP_PLJMP_FALSE,
     PopLongJumpIfTrue = OP_PLJMP_TRUE,
+    LongJumpIfNil = OP_LJUMP_NIL,
+    LongJumpIfNotNil = OP_LJUMP_NOT_NIL,
+    
+    AddLocalConst = OP_ADD_LOCAL_CONST,
+    SubLocalConst = OP_SUB_LOCAL_CONST,
+    
+    JumpIfEQ = OP_JMP_EQ,
+    JumpIfNotEQ = OP_JMP_NOT_EQ,
+    JumpIfLT = OP_JMP_LT,
+    JumpIfNotLT = OP_JMP_NOT_LT,
+    JumpIfLE = OP_JMP_LE,
+    JumpIfNotLE = OP_JMP_NOT_LE,
+    
+    MoveReg = OP_MOV_REG,
+    AddReg = OP_ADD_REG,
+    SubReg = OP_SUB_REG,
+    MulReg = OP_MUL_REG,
+    DivReg = OP_DIV_REG,
+    ModReg = OP_MOD_REG,
     
     Inspect = DBG_INSPECT,
     Assert = DBG_ASSERT,
@@ -238,10 +356,17 @@ impl OpCode {
             OP_NOP => Self::Nop,
             OP_EXIT => Self::Exit,
             OP_ERROR => Self::Error,
+            OP_PUSH_HANDLER => Self::PushHandler,
+            OP_LPUSH_HANDLER => Self::LongPushHandler,
+            OP_POP_HANDLER => Self::PopHandler,
+            
+            OP_IMPORT => Self::Import,
+            OP_EXPORT => Self::Export,
             
             OP_RETURN => Self::Return,
             OP_CALL => Self::Call,
             OP_IN_ARGS => Self::InsertArgs,
+            OP_TAIL_CALL => Self::TailCall,
             
             OP_POP => Self::Pop,
             OP_DROP => Self::Drop,
@@ -250,10 +375,22 @@ impl OpCode {
             
             OP_TUPLE => Self::Tuple,
             OP_TUPLEN => Self::TupleN,
+            OP_LIST => Self::BuildList,
+            OP_LISTN => Self::BuildListN,
+            OP_DICT => Self::BuildDict,
+            OP_DICTN => Self::BuildDictN,
+            OP_CONCAT => Self::Concat,
             
             OP_ITER_INIT => Self::IterInit,
             OP_ITER_NEXT => Self::IterNext,
             OP_ITER_UNPACK => Self::IterUnpack,
+            OP_TUPLE_LEN => Self::TupleLen,
+            
+            OP_GET_ATTR => Self::GetAttr,
+            OP_SET_ATTR => Self::SetAttr,
+            OP_GET_INDEX => Self::GetIndex,
+            OP_SET_INDEX => Self::SetIndex,
+            OP_GET_SUPER => Self::GetSuper,
             
             OP_LD_FUN => Self::LoadFunction,
             OP_LD_FUN_16 => Self::LoadFunction16,
@@ -261,6 +398,8 @@ impl OpCode {
             OP_LD_CONST => Self::LoadConst,
             OP_LD_CONST_16 => Self::LoadConst16,
             
+            OP_CLASS => Self::Class,
+            
             OP_IN_GLOBAL_IM => Self::InsertGlobal,
             OP_IN_GLOBAL_MUT => Self::InsertGlobalMut,
             OP_ST_GLOBAL => Self::StoreGlobal,
@@ -304,24 +443,50 @@ impl OpCode {
             OP_MUL => Self::Mul,
             OP_DIV => Self::Div,
             OP_MOD => Self::Mod,
+            OP_FLOORDIV => Self::FloorDiv,
+            OP_EXP => Self::Exp,
             OP_EQ => Self::EQ,
             OP_NE => Self::NE,
             OP_LT => Self::LT,
             OP_LE => Self::LE,
             OP_GE => Self::GE,
             OP_GT => Self::GT,
+            OP_IN => Self::In,
+            OP_NOT_IN => Self::NotIn,
+            OP_IS => Self::Is,
             
             OP_JUMP => Self::Jump,
             OP_JUMP_FALSE => Self::JumpIfFalse,
             OP_JUMP_TRUE => Self::JumpIfTrue,
             OP_PJMP_FALSE => Self::PopJumpIfFalse,
             OP_PJMP_TRUE => Self::PopJumpIfTrue,
+            OP_JUMP_NIL => Self::JumpIfNil,
+            OP_JUMP_NOT_NIL => Self::JumpIfNotNil,
             
             OP_LJUMP => Self::LongJump,
             OP_LJUMP_FALSE => Self::LongJumpIfFalse,
             OP_LJUMP_TRUE => Self::LongJumpIfTrue,
             OP_PLJMP_FALSE => Self::PopLongJumpIfFalse,
             OP_PLJMP_TRUE => Self::PopLongJumpIfTrue,
+            OP_LJUMP_NIL => Self::LongJumpIfNil,
+            OP_LJUMP_NOT_NIL => Self::LongJumpIfNotNil,
+            
+            OP_ADD_LOCAL_CONST => Self::AddLocalConst,
+            OP_SUB_LOCAL_CONST => Self::SubLocalConst,
+            
+            OP_JMP_EQ => Self::JumpIfEQ,
+            OP_JMP_NOT_EQ => Self::JumpIfNotEQ,
+            OP_JMP_LT => Self::JumpIfLT,
+            OP_JMP_NOT_LT => Self::JumpIfNotLT,
+            OP_JMP_LE => Self::JumpIfLE,
+            OP_JMP_NOT_LE => Self::JumpIfNotLE,
+            
+            OP_MOV_REG => Self::MoveReg,
+            OP_ADD_REG => Self::AddReg,
+            OP_SUB_REG => Self::SubReg,
+            OP_MUL_REG => Self::MulReg,
+            OP_DIV_REG => Self::DivReg,
+            OP_MOD_REG => Self::ModReg,
             
             DBG_INSPECT => Self::Inspect,
             DBG_ASSERT => Self::Assert,
@@ -359,6 +524,10 @@ impl OpCode {
             Self::CloseUpvalue16 => 1 + size_of::<u16>(),
             
             Self::Tuple          => 1 + size_of::<u8>(),
+            Self::BuildList      => 1 + size_of::<u8>(),
+            Self::BuildDict      => 1 + size_of::<u8>(),
+            Self::Concat         => 1 + size_of::<u8>(),
+            Self::Class          => 1 + size_of::<u8>(),
             Self::UInt8          => 1 + size_of::<u8>(),
             Self::Int8           => 1 + size_of::<i8>(),
             Self::Int16          => 1 + size_of::<i16>(),
@@ -368,6 +537,39 @@ impl OpCode {
             Self::JumpIfTrue     => 1 + size_of::<i16>(),
             Self::PopJumpIfFalse => 1 + size_of::<i16>(),
             Self::PopJumpIfTrue  => 1 + size_of::<i16>(),
+            Self::JumpIfNil      => 1 + size_of::<i16>(),
+            Self::JumpIfNotNil   => 1 + size_of::<i16>(),
+            
+            Self::LongJump           => 1 + size_of::<i32>(),
+            Self::LongJumpIfFalse    => 1 + size_of::<i32>(),
+            Self::LongJumpIfTrue     => 1 + size_of::<i32>(),
+            Self::PopLongJumpIfFalse => 1 + size_of::<i32>(),
+            Self::PopLongJumpIfTrue  => 1 + size_of::<i32>(),
+            Self::LongJumpIfNil      => 1 + size_of::<i32>(),
+            Self::LongJumpIfNotNil   => 1 + size_of::<i32>(),
+            
+            Self::PushHandler     => 1 + size_of::<i16>(),
+            Self::LongPushHandler => 1 + size_of::<i32>(),
+            
+            Self::AddLocalConst  => 1 + size_of::<u8>() + size_of::<u8>(),
+            Self::SubLocalConst  => 1 + size_of::<u8>() + size_of::<u8>(),
+            
+            Self::JumpIfEQ       => 1 + size_of::<i16>(),
+            Self::JumpIfNotEQ    => 1 + size_of::<i16>(),
+            Self::JumpIfLT       => 1 + size_of::<i16>(),
+            Self::JumpIfNotLT    => 1 + size_of::<i16>(),
+            Self::JumpIfLE       => 1 + size_of::<i16>(),
+            Self::JumpIfNotLE    => 1 + size_of::<i16>(),
+            
+            Self::MoveReg        => 1 + 3 * size_of::<u8>(),
+            Self::AddReg         => 1 + 4 * size_of::<u8>(),
+            Self::SubReg         => 1 + 4 * size_of::<u8>(),
+            Self::MulReg         => 1 + 4 * size_of::<u8>(),
+            Self::DivReg         => 1 + 4 * size_of::<u8>(),
+            Self::ModReg         => 1 + 4 * size_of::<u8>(),
+            
+            Self::GetAttr        => 1 + size_of::<CacheIndex>(),
+            Self::LoadGlobal     => 1 + size_of::<CacheIndex>(),
             
             _ => 1,
         }
@@ -400,10 +602,17 @@ impl core::fmt::Display for OpCode {
             Self::Nop => "NOP",
             Self::Exit => "EXIT",
             Self::Error => "ERROR",
+            Self::PushHandler => "PUSH_HANDLER",
+            Self::LongPushHandler => "LPUSH_HANDLER",
+            Self::PopHandler => "POP_HANDLER",
+            
+            Self::Import => "IMPORT",
+            Self::Export => "EXPORT",
             
             Self::Return => "RETURN",
             Self::Call => "CALL",
             Self::InsertArgs => "IN_ARGS",
+            Self::TailCall => "TAIL_CALL",
             
             Self::Pop => "POP",
             Self::Drop => "DROP",
@@ -412,10 +621,22 @@ impl core::fmt::Display for OpCode {
             
             Self::Tuple => "TUPLE",
             Self::TupleN => "TUPLEN",
+            Self::BuildList => "LIST",
+            Self::BuildListN => "LISTN",
+            Self::BuildDict => "DICT",
+            Self::BuildDictN => "DICTN",
+            Self::Concat => "CONCAT",
             
             Self::IterInit => "ITER_INIT",
             Self::IterNext => "ITER_NEXT",
             Self::IterUnpack => "ITER_UNPACK",
+            Self::TupleLen => "TUPLE_LEN",
+            
+            Self::GetAttr => "GET_ATTR",
+            Self::SetAttr => "SET_ATTR",
+            Self::GetIndex => "GET_INDEX",
+            Self::SetIndex => "SET_INDEX",
+            Self::GetSuper => "GET_SUPER",
             
             Self::LoadFunction => "LD_FUN",
             Self::LoadFunction16 => "LD_FUN_16",
@@ -423,6 +644,8 @@ impl core::fmt::Display for OpCode {
             Self::LoadConst => "LD_CONST",
             Self::LoadConst16 => "LD_CONST_16",
             
+            Self::Class => "CLASS",
+            
             Self::InsertGlobal => "IN_GLOBAL_IM",
             Self::InsertGlobalMut => "IN_GLOBAL_MUT",
             Self::StoreGlobal => "ST_GLOBAL",
@@ -466,24 +689,50 @@ impl core::fmt::Display for OpCode {
             Self::Mul => "MUL",
             Self::Div => "DIV",
             Self::Mod => "MOD",
+            Self::FloorDiv => "FLOORDIV",
+            Self::Exp => "EXP",
             Self::EQ => "CMP_EQ",
             Self::NE => "CMP_NE",
             Self::LT => "CMP_LT",
             Self::LE => "CMP_LE",
             Self::GE => "CMP_GE",
             Self::GT => "CMP_GT",
+            Self::In => "CMP_IN",
+            Self::NotIn => "CMP_NOT_IN",
+            Self::Is => "CMP_IS",
             
             Self::Jump => "JUMP",
             Self::JumpIfFalse => "JUMP_FALSE",
             Self::JumpIfTrue => "JUMP_TRUE",
             Self::PopJumpIfFalse => "PJMP_FALSE",
             Self::PopJumpIfTrue => "PJMP_TRUE",
+            Self::JumpIfNil => "JUMP_NIL",
+            Self::JumpIfNotNil => "JUMP_NOT_NIL",
             
             Self::LongJump => "LJUMP",
             Self::LongJumpIfFalse => "LJUMP_FALSE",
             Self::LongJumpIfTrue => "LJUMP_TRUE",
             Self::PopLongJumpIfFalse => "PLJMP_FALSE",
             Self::PopLongJumpIfTrue => "PLJMP_TRUE",
+            Self::LongJumpIfNil => "LJUMP_NIL",
+            Self::LongJumpIfNotNil => "LJUMP_NOT_NIL",
+            
+            Self::AddLocalConst => "ADD_LOCAL_CONST",
+            Self::SubLocalConst => "SUB_LOCAL_CONST",
+            
+            Self::JumpIfEQ => "JMP_EQ",
+            Self::JumpIfNotEQ => "JMP_NOT_EQ",
+            Self::JumpIfLT => "JMP_LT",
+            Self::JumpIfNotLT => "JMP_NOT_LT",
+            Self::JumpIfLE => "JMP_LE",
+            Self::JumpIfNotLE => "JMP_NOT_LE",
+            
+            Self::MoveReg => "MOV_R",
+            Self::AddReg => "ADD_R",
+            Self::SubReg => "SUB_R",
+            Self::MulReg => "MUL_R",
+            Self::DivReg => "DIV_R",
+            Self::ModReg => "MOD_R",
             
             Self::Inspect => "DBG_INSPECT",
             Self::Assert => "DBG_ASSERT",
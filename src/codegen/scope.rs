@@ -1,12 +1,12 @@
 // Scope Tracking
 
 use crate::language::{InternSymbol, Access};
-use crate::parser::stmt::Label;
+use crate::parser::stmt::{Label, StmtList};
 use crate::debug::symbol::DebugSymbol;
 use crate::codegen::JumpSite;
 use crate::codegen::opcodes::{LocalIndex, UpvalueIndex};
 use crate::codegen::funproto::UpvalueTarget;
-use crate::codegen::errors::CompileResult;
+use crate::codegen::errors::{CompileResult, ErrorKind as CompileErrorKind};
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -20,6 +20,9 @@ pub(super) enum LocalName {
     Receiver,  // inside a function call, this refers to the object that was called
     NArgs,     // inside a function call, the number of arguments passed at the call site
     
+    // created by the compiler
+    Super,     // inside a class body, the parent class
+    
     Anonymous, // for internal temporaries. excluded from local variable resolution, they can only be referred to by local index
 }
 
@@ -30,6 +33,8 @@ pub(super) struct Local {
     name: LocalName,
     index: LocalIndex,
     captured: bool, // tracks whether the local is being referenced by an upvalue
+    read: bool,     // tracks whether the value of the local is ever loaded
+    symbol: Option<DebugSymbol>, // where the local was declared. locals without a symbol are not checked for use
 }
 
 impl Local {
@@ -37,6 +42,12 @@ impl Local {
     pub(super) fn name(&self) -> LocalName { self.name }
     pub(super) fn index(&self) -> LocalIndex { self.index }
     pub(super) fn captured(&self) -> bool { self.captured }
+    pub(super) fn debug_symbol(&self) -> Option<&DebugSymbol> { self.symbol.as_ref() }
+    
+    // declared by the user but its value is never used
+    pub(super) fn is_unused(&self) -> bool {
+        self.symbol.is_some() && !self.read && !self.captured
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -81,17 +92,11 @@ impl ScopeTag {
     }
     
     fn hide_from_nro(&self) -> bool {
-        match self {
-            Self::Temporary => true,
-            _ => false,
-        }
+        matches!(self, Self::Temporary)
     }
     
     pub(super) fn is_expr_block(&self) -> bool {
-        match self {
-            Self::Block | Self:: Branch => true,
-            _ => false,
-        }
+        matches!(self, Self::Block)
     }
 }
 
@@ -128,6 +133,22 @@ impl ControlFlowTracker {
     }
 }
 
+// an error handler that is active for the duration of a scope
+#[derive(Debug, Clone)]
+pub(super) struct ErrorHandler {
+    finally: Option<StmtList>, // must be run whenever control flow leaves the scope
+}
+
+impl ErrorHandler {
+    pub(super) fn new(finally: Option<StmtList>) -> Self {
+        Self { finally }
+    }
+    
+    pub(super) fn finally(&self) -> Option<&StmtList> {
+        self.finally.as_ref()
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct Scope {
     tag: ScopeTag,
@@ -136,6 +157,7 @@ pub(super) struct Scope {
     prev_index: Option<LocalIndex>,
     locals: Vec<Local>,
     control_flow: ControlFlowTracker,
+    handler: Option<ErrorHandler>,
 }
 
 impl Scope {
@@ -171,6 +193,18 @@ impl Scope {
         &self.control_flow.break_sites
     }
     
+    pub(super) fn handler(&self) -> Option<&ErrorHandler> {
+        self.handler.as_ref()
+    }
+    
+    pub(super) fn set_handler(&mut self, handler: ErrorHandler) {
+        self.handler.replace(handler);
+    }
+    
+    pub(super) fn take_handler(&mut self) -> Option<ErrorHandler> {
+        self.handler.take()
+    }
+    
     fn control_flow_mut(&mut self) -> &mut ControlFlowTracker {
         &mut self.control_flow
     }
@@ -193,34 +227,36 @@ impl Scope {
         self.locals.iter_mut().find(|local| local.name == *name)
     }
     
-    fn push_local(&mut self, mode: Access, name: LocalName) -> CompileResult<&Local> {
+    fn push_local(&mut self, mode: Access, name: LocalName, symbol: Option<&DebugSymbol>) -> CompileResult<&Local> {
         let index = self.last_index().map_or(
             Ok(0),
             |index| index.checked_add(1)
-                .ok_or("local variable limit reached")
+                .ok_or(CompileErrorKind::InternalLimit("local variable limit reached"))
         )?;
         
         let local = Local {
             mode, name, index, 
             captured: false,
+            read: false,
+            symbol: symbol.copied(),
         };
         
         self.locals.push(local);
         Ok(self.locals.last().unwrap())
     }
     
-    fn insert_local(&mut self, mode: Access, name: LocalName) -> CompileResult<InsertLocal> {
+    fn insert_local(&mut self, mode: Access, name: LocalName, symbol: Option<&DebugSymbol>) -> CompileResult<InsertLocal> {
         // ensure only anonymous variables get inserted into hidden scopes
         if self.tag.hide_from_nro() {
             debug_assert!(name == LocalName::Anonymous);
         }
         
         // see if this local already exists in the current scope
-        if let Some(mut local) = self.find_local_mut(&name) {
-            (*local).mode = mode; // redeclare with new mutability
+        if let Some(local) = self.find_local_mut(&name) {
+            local.mode = mode; // redeclare with new mutability
             Ok(InsertLocal::HideExisting(local.index))
         } else {
-            let local = self.push_local(mode, name)?;
+            let local = self.push_local(mode, name, symbol)?;
             Ok(InsertLocal::CreateNew(local.index))
         }
     }
@@ -242,6 +278,7 @@ impl NestedScopes {
             symbol: symbol.copied(),
             locals: Vec::new(),
             control_flow: ControlFlowTracker::new(label),
+            handler: None,
         };
         
         Self {
@@ -272,6 +309,7 @@ impl NestedScopes {
             symbol: symbol.copied(),
             locals: Vec::new(),
             control_flow: ControlFlowTracker::new(label),
+            handler: None,
         };
         
         self.nested.push(scope);
@@ -312,20 +350,38 @@ impl Upvalue {
 }
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum FunctionKind {
+    Function,
+    Method,       // the receiver is the instance the method was accessed from
+    Constructor,  // like a method, but implicitly returns the receiver
+}
+
+impl FunctionKind {
+    pub(super) fn has_receiver(&self) -> bool {
+        matches!(self, Self::Method | Self::Constructor)
+    }
+}
+
+
 #[derive(Debug)]
 pub(super) struct CallFrame {
+    kind: FunctionKind,
     scopes: NestedScopes,
     upvalues: Vec<Upvalue>,
 }
 
 impl CallFrame {
-    fn new(symbol: Option<&DebugSymbol>) -> Self {
+    fn new(symbol: Option<&DebugSymbol>, kind: FunctionKind) -> Self {
         Self {
+            kind,
             scopes: NestedScopes::new(symbol, ScopeTag::Function, None),
             upvalues: Vec::new(),
         }
     }
     
+    pub(super) fn kind(&self) -> FunctionKind { self.kind }
+    
     pub(super) fn upvalues(&self) -> &[Upvalue] { self.upvalues.as_slice() }
     
     pub(super) fn iter_locals(&self) -> impl Iterator<Item=&Local> {
@@ -342,7 +398,7 @@ impl CallFrame {
     
     fn create_upval_for_local(&mut self, local: &mut Local) -> CompileResult<&Upvalue> {
         let index = UpvalueIndex::try_from(self.upvalues.len())
-            .map_err(|_| "upvalue limit reached")?;
+            .map_err(|_| CompileErrorKind::InternalLimit("upvalue limit reached"))?;
         
         let upval = Upvalue {
             index,
@@ -359,7 +415,7 @@ impl CallFrame {
     
     fn create_upval_for_upval(&mut self, upval: &Upvalue) -> CompileResult<&Upvalue> {
         let index = UpvalueIndex::try_from(self.upvalues.len())
-            .map_err(|_| "upvalue limit reached")?;
+            .map_err(|_| CompileErrorKind::InternalLimit("upvalue limit reached"))?;
         
         let upval = Upvalue {
             index,
@@ -402,8 +458,12 @@ impl ScopeTracker {
         !self.frames.is_empty()
     }
     
-    pub(super) fn push_frame(&mut self, symbol: Option<&DebugSymbol>) {
-        self.frames.push(CallFrame::new(symbol))
+    pub(super) fn push_frame(&mut self, symbol: Option<&DebugSymbol>, kind: FunctionKind) {
+        self.frames.push(CallFrame::new(symbol, kind))
+    }
+    
+    pub(super) fn function_kind(&self) -> Option<FunctionKind> {
+        self.frames.last().map(|frame| frame.kind())
     }
     
     pub(super) fn pop_frame(&mut self) -> CallFrame {
@@ -455,9 +515,9 @@ impl ScopeTracker {
     
     // local variables
     
-    pub(super) fn insert_local(&mut self, mode: Access, name: LocalName) -> CompileResult<InsertLocal> {
+    pub(super) fn insert_local(&mut self, mode: Access, name: LocalName, symbol: Option<&DebugSymbol>) -> CompileResult<InsertLocal> {
         self.get_current_scope_mut(name != LocalName::Anonymous)
-            .insert_local(mode, name)
+            .insert_local(mode, name, symbol)
     }
     
     pub(super) fn resolve_local(&self, name: &LocalName) -> Option<&Local> {
@@ -465,6 +525,15 @@ impl ScopeTracker {
             .iter_nro().find_map(|scope| scope.find_local(name))
     }
     
+    // like resolve_local(), but also records that the local's value was used
+    pub(super) fn read_local(&mut self, name: &LocalName) -> Option<&Local> {
+        let local = self.local_scopes_mut()
+            .iter_nro_mut().find_map(|scope| scope.find_local_mut(name))?;
+        
+        local.read = true;
+        Some(local)
+    }
+    
     // upvalues
     
     pub(super) fn resolve_or_create_upval(&mut self, name: &LocalName) -> CompileResult<Option<&Upvalue>> {
@@ -525,13 +594,9 @@ impl ScopeTracker {
     pub(super) fn resolve_control_flow(&self, target: ControlFlowTarget) -> Option<&Scope> {
         self.local_scopes()
             .iter_nro()
-            .find_map(|scope| {
-                if scope.tag().accepts_control_flow(target) {
-                    if target.label().is_none() || target.label() == scope.control_flow.label.as_ref() {
-                        return Some(scope)
-                    }
-                }
-                None
+            .find(|scope| {
+                scope.tag().accepts_control_flow(target)
+                && (target.label().is_none() || target.label() == scope.control_flow.label.as_ref())
             })
     }
     
@@ -542,4 +607,12 @@ impl ScopeTracker {
     pub(super) fn iter_scopes_mut(&mut self) -> impl Iterator<Item=&mut Scope> {
         self.local_scopes_mut().iter_nro_mut()
     }
+    
+    // every local in the current frame or an enclosing one, including those that haven't been captured as upvalues yet
+    pub(super) fn iter_visible_locals(&self) -> impl Iterator<Item=&Local> {
+        self.frames.iter().rev().map(CallFrame::scopes)
+            .chain(std::iter::once(&self.toplevel))
+            .flat_map(|scopes| scopes.iter_nro())
+            .flat_map(|scope| scope.locals().iter())
+    }
 }
\ No newline at end of file
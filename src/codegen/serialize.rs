@@ -0,0 +1,398 @@
+//! Binary encoding for compiled programs.
+//!
+//! All integers are written little-endian. Lengths and offsets are always written as `u64`
+//! so that the encoding does not depend on the platform's pointer width.
+//!
+//! Every encoded program starts with a header consisting of the `MAGIC` bytes followed by
+//! the `FORMAT_VERSION`. Programs written using a different format version are rejected.
+
+use core::mem;
+use std::io::{self, Read, Write};
+use crate::language::{IntType, FloatType, Access};
+use crate::runtime::errors::ErrorKind;
+use crate::debug::DebugSymbol;
+use crate::debug::symbol::{ChunkSymbols, DebugSymbolTable};
+use crate::codegen::chunk::{Chunk, ChunkInfo};
+use crate::codegen::consts::Constant;
+use crate::codegen::funproto::{UnloadedFunction, UnloadedSignature, UnloadedParam, UpvalueTarget};
+
+
+pub(super) const MAGIC: [u8; 4] = *b"SPHX";
+
+// This must be incremented whenever the encoding or the bytecode instruction set changes
+pub(super) const FORMAT_VERSION: u16 = 3;
+
+
+pub(super) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub(super) trait WriteBytes: Write {
+    fn write_u8(&mut self, value: u8) -> io::Result<()> {
+        self.write_all(&[value])
+    }
+    
+    fn write_u16(&mut self, value: u16) -> io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+    
+    fn write_u32(&mut self, value: u32) -> io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+    
+    fn write_u64(&mut self, value: u64) -> io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+    
+    fn write_i64(&mut self, value: i64) -> io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+    
+    fn write_len(&mut self, len: usize) -> io::Result<()> {
+        self.write_u64(u64::try_from(len).unwrap())
+    }
+    
+    fn write_bool(&mut self, value: bool) -> io::Result<()> {
+        self.write_u8(u8::from(value))
+    }
+    
+    // length-prefixed byte string
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write_len(bytes.len())?;
+        self.write_all(bytes)
+    }
+}
+
+impl<W> WriteBytes for W where W: Write { }
+
+
+pub(super) trait ReadBytes: Read {
+    fn read_fixed<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+    
+    fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_fixed::<1>()?[0])
+    }
+    
+    fn read_u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.read_fixed()?))
+    }
+    
+    fn read_u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.read_fixed()?))
+    }
+    
+    fn read_u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.read_fixed()?))
+    }
+    
+    fn read_i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.read_fixed()?))
+    }
+    
+    fn read_len(&mut self) -> io::Result<usize> {
+        usize::try_from(self.read_u64()?)
+            .map_err(|_| invalid_data("length out of range"))
+    }
+    
+    fn read_bool(&mut self) -> io::Result<bool> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool")),
+        }
+    }
+    
+    fn read_bytes(&mut self) -> io::Result<Box<[u8]>> {
+        let len = self.read_len()?;
+        
+        // don't trust the length enough to pre-allocate it
+        let mut buf = Vec::new();
+        self.take(u64::try_from(len).unwrap()).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(buf.into_boxed_slice())
+    }
+    
+    // read a length-prefixed sequence of items
+    fn read_seq<T>(&mut self, mut read_item: impl FnMut(&mut Self) -> io::Result<T>) -> io::Result<Box<[T]>> where Self: Sized {
+        let len = self.read_len()?;
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(read_item(self)?);
+        }
+        Ok(items.into_boxed_slice())
+    }
+}
+
+impl<R> ReadBytes for R where R: Read { }
+
+
+// Program components
+
+pub(super) fn write_header(write: &mut impl Write) -> io::Result<()> {
+    write.write_all(&MAGIC)?;
+    write.write_u16(FORMAT_VERSION)
+}
+
+pub(super) fn read_header(read: &mut impl Read) -> io::Result<()> {
+    if read.read_fixed::<4>()? != MAGIC {
+        return Err(invalid_data("not a compiled Sphinx program"));
+    }
+    
+    let version = read.read_u16()?;
+    if version != FORMAT_VERSION {
+        let message = format!("unsupported format version {} (expected {})", version, FORMAT_VERSION);
+        return Err(invalid_data(&message));
+    }
+    Ok(())
+}
+
+
+const CONST_INTEGER: u8 = 0;
+const CONST_FLOAT: u8 = 1;
+const CONST_STRING: u8 = 2;
+const CONST_ERROR: u8 = 3;
+const CONST_BIGINT: u8 = 4;
+
+pub(super) fn write_constant(write: &mut impl Write, value: &Constant) -> io::Result<()> {
+    match value {
+        #[allow(clippy::useless_conversion)]  // IntType may be 32-bit
+        Constant::Integer(value) => {
+            write.write_u8(CONST_INTEGER)?;
+            write.write_i64(i64::from(*value))
+        },
+        Constant::Float(bytes) => {
+            write.write_u8(CONST_FLOAT)?;
+            write.write_bytes(bytes)
+        },
+        Constant::String(index) => {
+            write.write_u8(CONST_STRING)?;
+            write.write_len(*index)
+        },
+        Constant::BigInt(index) => {
+            write.write_u8(CONST_BIGINT)?;
+            write.write_len(*index)
+        },
+        Constant::Error { error, message } => {
+            write.write_u8(CONST_ERROR)?;
+            write.write_u8(u8::from(*error))?;
+            write.write_len(*message)
+        },
+    }
+}
+
+pub(super) fn read_constant(read: &mut impl Read) -> io::Result<Constant> {
+    let value = match read.read_u8()? {
+        CONST_INTEGER => {
+            let value = IntType::try_from(read.read_i64()?)
+                .map_err(|_| invalid_data("integer constant out of range"))?;
+            Constant::Integer(value)
+        },
+        CONST_FLOAT => {
+            let bytes = <[u8; mem::size_of::<FloatType>()]>::try_from(&*read.read_bytes()?)
+                .map_err(|_| invalid_data("float constant has the wrong size"))?;
+            Constant::Float(bytes)
+        },
+        CONST_STRING => Constant::String(read.read_len()?),
+        
+        #[cfg(feature = "bigint")]
+        CONST_BIGINT => Constant::BigInt(read.read_len()?),
+        #[cfg(not(feature = "bigint"))]
+        CONST_BIGINT => return Err(invalid_data("big integer constants require the \"bigint\" feature")),
+        
+        CONST_ERROR => {
+            let error = ErrorKind::try_from(read.read_u8()?)
+                .map_err(|_| invalid_data("invalid error kind"))?;
+            Constant::Error { error, message: read.read_len()? }
+        },
+        _ => return Err(invalid_data("invalid constant type")),
+    };
+    Ok(value)
+}
+
+
+pub(super) fn write_chunk_info(write: &mut impl Write, info: &ChunkInfo) -> io::Result<()> {
+    match info {
+        ChunkInfo::ModuleMain => write.write_u8(0),
+        ChunkInfo::Function { symbol } => {
+            write.write_u8(1)?;
+            write_option(write, symbol.as_ref(), write_debug_symbol)
+        }
+    }
+}
+
+pub(super) fn read_chunk_info(read: &mut impl Read) -> io::Result<ChunkInfo> {
+    match read.read_u8()? {
+        0 => Ok(ChunkInfo::ModuleMain),
+        1 => Ok(ChunkInfo::Function { symbol: read_option(read, read_debug_symbol)? }),
+        _ => Err(invalid_data("invalid chunk info")),
+    }
+}
+
+pub(super) fn write_debug_symbol(write: &mut impl Write, symbol: &DebugSymbol) -> io::Result<()> {
+    write.write_u32(symbol.start())?;
+    write.write_u16(symbol.len())
+}
+
+pub(super) fn read_debug_symbol(read: &mut impl Read) -> io::Result<DebugSymbol> {
+    let start = read.read_u32()?;
+    let length = read.read_u16()?;
+    Ok(DebugSymbol::new(start, length))
+}
+
+
+pub(super) fn write_symbols(write: &mut impl Write, symbols: &ChunkSymbols) -> io::Result<()> {
+    // sort the chunks so that the output does not depend on the hash map's iteration order
+    let mut chunks = symbols.iter().collect::<Vec<_>>();
+    chunks.sort_by_key(|(chunk_id, _)| match chunk_id {
+        Chunk::Main => None,
+        Chunk::Function(fun_id) => Some(*fun_id),
+    });
+    
+    write.write_len(chunks.len())?;
+    for (chunk_id, table) in chunks.into_iter() {
+        write_chunk_id(write, chunk_id)?;
+        
+        let entries = table.iter().collect::<Vec<_>>();
+        write_seq(write, &entries, |write, (offset, symbol)| {
+            write.write_len(*offset)?;
+            write_debug_symbol(write, symbol)
+        })?;
+        
+        let name_hints = table.iter_name_hints().collect::<Vec<_>>();
+        write_seq(write, &name_hints, |write, (offset, name)| {
+            write.write_len(*offset)?;
+            write.write_bytes(name.as_bytes())
+        })?;
+    }
+    Ok(())
+}
+
+pub(super) fn read_symbols(read: &mut impl Read) -> io::Result<ChunkSymbols> {
+    let len = read.read_len()?;
+    let mut symbols = ChunkSymbols::new();
+    for _ in 0..len {
+        let chunk_id = read_chunk_id(read)?;
+        
+        let entries = read.read_seq(|read| Ok((read.read_len()?, read_debug_symbol(read)?)))?;
+        if entries.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(invalid_data("debug symbols are out of order"));
+        }
+        
+        let name_hints = read.read_seq(|read| Ok((read.read_len()?, read_string(read)?)))?;
+        if name_hints.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(invalid_data("name hints are out of order"));
+        }
+        
+        let mut table = DebugSymbolTable::new();
+        for (offset, symbol) in entries.into_vec().into_iter() {
+            table.insert(offset, symbol);
+        }
+        for (offset, name) in name_hints.iter() {
+            table.insert_name_hint(*offset, name);
+        }
+        symbols.insert(chunk_id, table);
+    }
+    Ok(symbols)
+}
+
+fn read_string(read: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read.read_bytes()?.into_vec())
+        .map_err(|_| invalid_data("invalid string"))
+}
+
+fn write_chunk_id(write: &mut impl Write, chunk_id: &Chunk) -> io::Result<()> {
+    match chunk_id {
+        Chunk::Main => write.write_u8(0),
+        Chunk::Function(fun_id) => {
+            write.write_u8(1)?;
+            write.write_u16(*fun_id)
+        }
+    }
+}
+
+fn read_chunk_id(read: &mut impl Read) -> io::Result<Chunk> {
+    match read.read_u8()? {
+        0 => Ok(Chunk::Main),
+        1 => Ok(Chunk::Function(read.read_u16()?)),
+        _ => Err(invalid_data("invalid chunk id")),
+    }
+}
+
+
+pub(super) fn write_function(write: &mut impl Write, function: &UnloadedFunction) -> io::Result<()> {
+    write.write_u16(function.fun_id)?;
+    
+    let signature = &function.signature;
+    write_option(write, signature.name.as_ref(), |write, name| write.write_u16(*name))?;
+    write_seq(write, &signature.required, write_param)?;
+    write_seq(write, &signature.default, write_param)?;
+    write_option(write, signature.variadic.as_ref(), write_param)?;
+    
+    write_seq(write, &function.upvalues, |write, upval| match upval {
+        UpvalueTarget::Local(index) => { write.write_u8(0)?; write.write_u16(*index) },
+        UpvalueTarget::Upvalue(index) => { write.write_u8(1)?; write.write_u16(*index) },
+    })
+}
+
+pub(super) fn read_function(read: &mut impl Read) -> io::Result<UnloadedFunction> {
+    let fun_id = read.read_u16()?;
+    
+    let signature = UnloadedSignature {
+        name: read_option(read, |read| read.read_u16())?,
+        required: read.read_seq(read_param)?,
+        default: read.read_seq(read_param)?,
+        variadic: read_option(read, read_param)?,
+    };
+    
+    let upvalues = read.read_seq(|read| match read.read_u8()? {
+        0 => Ok(UpvalueTarget::Local(read.read_u16()?)),
+        1 => Ok(UpvalueTarget::Upvalue(read.read_u16()?)),
+        _ => Err(invalid_data("invalid upvalue target")),
+    })?;
+    
+    Ok(UnloadedFunction { signature, upvalues, fun_id })
+}
+
+fn write_param(write: &mut impl Write, param: &UnloadedParam) -> io::Result<()> {
+    write.write_u16(param.name)?;
+    write.write_bool(matches!(param.mode, Access::ReadWrite))
+}
+
+fn read_param(read: &mut impl Read) -> io::Result<UnloadedParam> {
+    let name = read.read_u16()?;
+    let mode = if read.read_bool()? { Access::ReadWrite } else { Access::ReadOnly };
+    Ok(UnloadedParam { name, mode })
+}
+
+
+// Helpers
+
+pub(super) fn write_seq<W, T>(write: &mut W, items: &[T], mut write_item: impl FnMut(&mut W, &T) -> io::Result<()>) -> io::Result<()> where W: Write {
+    write.write_len(items.len())?;
+    for item in items.iter() {
+        write_item(write, item)?;
+    }
+    Ok(())
+}
+
+pub(super) fn write_option<W, T>(write: &mut W, item: Option<&T>, write_item: impl FnOnce(&mut W, &T) -> io::Result<()>) -> io::Result<()> where W: Write {
+    write.write_bool(item.is_some())?;
+    match item {
+        Some(item) => write_item(write, item),
+        None => Ok(()),
+    }
+}
+
+pub(super) fn read_option<R, T>(read: &mut R, read_item: impl FnOnce(&mut R) -> io::Result<T>) -> io::Result<Option<T>> where R: Read {
+    if read.read_bool()? {
+        Ok(Some(read_item(read)?))
+    } else {
+        Ok(None)
+    }
+}
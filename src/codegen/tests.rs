@@ -0,0 +1,958 @@
+#![cfg(test)]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::source::{ModuleSource, SourceText};
+use crate::codegen::{Compiler, CompiledProgram, CompileOptions, Backend, Program, UnloadedProgram, OpCode, Chunk, Constant, WarningKind};
+use crate::codegen::opcodes::{REG_CONST_SRC1, REG_CONST_SRC2};
+use crate::codegen::chunk::ChunkBuilder;
+use crate::codegen::consts::ConstID;
+use crate::codegen::verify::VerifyError;
+use crate::debug::SourceError;
+use crate::debug::codes::{self, ErrorCode};
+use crate::runtime::{Module, VirtualMachine, Runtime, Variant, Gc};
+use crate::runtime::gc::{GcHeap, gc_allocated, gc_stats};
+use crate::runtime::strings::{StringInterner, with_string_table};
+use crate::runtime::module::NamespaceEnv;
+use crate::runtime::errors::{ErrorKind, ExecResult};
+use crate::builtins;
+
+const TEST_SOURCE: &str = r#"
+    let PI = 3.14159
+    
+    class Point
+        fun new(x, y)
+            self.x = x
+            self.y = y
+        end
+    end
+    
+    fun make_counter(start, step = 1)
+        var count = start
+        fun() 
+            count += step
+        end
+    end
+    
+    let counter = make_counter(10, 1)
+    counter()
+    assert counter() == 12
+    assert Point(1, 2).y == 2
+    
+    fun first(items...)
+        let a, ... = items
+        a
+    end
+    assert first(1, 2, 3) == 1
+"#;
+
+fn compile(text: &str) -> CompiledProgram {
+    crate::build_module(&ModuleSource::String(text.to_string())).expect("build failed")
+}
+
+fn load_with_env(build: CompiledProgram, env: Gc<NamespaceEnv>) -> (Gc<Module>, Box<[u8]>) {
+    let program = Program::load(build.program).with_symbols(build.symbols);
+    (Module::with_env(None, program.data, env), program.main)
+}
+
+// runs the program in a new VM, after it has been configured
+fn with_vm<R>(build: CompiledProgram, f: impl FnOnce(VirtualMachine<'_>) -> R) -> R {
+    let (module, main) = load_with_env(build, builtins::create_prelude());
+    f(VirtualMachine::new(module, &main))
+}
+
+fn run(text: &str) -> ExecResult<Variant> {
+    with_vm(compile(text), |mut vm| vm.run())
+}
+
+fn encode(build: &CompiledProgram) -> Vec<u8> {
+    let mut buf = Vec::new();
+    build.write_to(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn compiled_program_round_trip() {
+    let build = compile(TEST_SOURCE);
+    let bytes = encode(&build);
+    
+    let decoded = CompiledProgram::read_from(bytes.as_slice()).unwrap();
+    assert_eq!(decoded.source_hash, build.source_hash);
+    assert_eq!(decoded.symbols.len(), build.symbols.len());
+    for (chunk_id, table) in build.symbols.iter() {
+        let other = &decoded.symbols[chunk_id];
+        assert!(table.iter().eq(other.iter()));
+    }
+    
+    // encoding is deterministic, so a decoded program re-encodes to the same bytes
+    assert_eq!(encode(&decoded), bytes);
+    
+    // and the decoded program still runs
+    with_vm(decoded, |mut vm| vm.run()).unwrap();
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn big_integer_constants_round_trip() {
+    let build = compile("assert 123456789012345678901234567890 - 1 == 123456789012345678901234567889");
+    assert!(build.program.consts().iter().any(|constant| matches!(constant, Constant::BigInt(..))));
+    
+    let decoded = CompiledProgram::read_from(encode(&build).as_slice()).unwrap();
+    with_vm(decoded, |mut vm| vm.run()).unwrap();
+}
+
+#[test]
+fn compiled_program_rejects_invalid_data() {
+    let bytes = encode(&compile(TEST_SOURCE));
+    
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    let error = CompiledProgram::read_from(bad_magic.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    
+    let mut bad_version = bytes.clone();
+    bad_version[4] = bad_version[4].wrapping_add(1);
+    let error = CompiledProgram::read_from(bad_version.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    
+    let truncated = &bytes[..bytes.len() / 2];
+    assert!(CompiledProgram::read_from(truncated).is_err());
+}
+
+fn collect_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if path.extension().is_some_and(|ext| ext == "sph") {
+            scripts.push(path);
+        }
+    }
+}
+
+#[test]
+fn compiled_scripts_pass_verification() {
+    let mut scripts = Vec::new();
+    collect_scripts(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests"), &mut scripts);
+    assert!(!scripts.is_empty());
+    
+    let mut failures = Vec::new();
+    for path in scripts.into_iter() {
+        // some scripts are expected to fail to build
+        if let Ok(build) = crate::build_module(&ModuleSource::File(path.clone())) {
+            if let Err(error) = build.program.verify() {
+                failures.push(format!("{}: {}", path.display(), error));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+fn build_main(bytes: &[u8]) -> UnloadedProgram {
+    let mut builder = ChunkBuilder::new();
+    builder.chunk_mut(Chunk::Main).extend_bytes(bytes);
+    builder.build()
+}
+
+fn verify_main(bytes: &[u8]) -> Result<(), VerifyError> {
+    build_main(bytes).verify()
+}
+
+fn assert_rejected(bytes: &[u8], offset: usize, message: &str) {
+    let error = verify_main(bytes).unwrap_err();
+    assert_eq!(error.offset(), Some(offset), "{}", error);
+    assert!(error.to_string().contains(message), "{}", error);
+}
+
+#[test]
+fn verify_accepts_valid_chunk() {
+    let bytes = [
+        OpCode::True.into(),
+        OpCode::PopJumpIfTrue.into(), 1, 0,
+        OpCode::Nop.into(),
+        OpCode::Nil.into(),
+        OpCode::Exit.into(),
+    ];
+    verify_main(&bytes).unwrap();
+}
+
+#[test]
+fn verify_rejects_invalid_instructions() {
+    assert_rejected(&[OpCode::Nil.into(), 0xEE], 1, "invalid opcode");
+    assert_rejected(&[OpCode::Nil.into(), OpCode::Drop.into()], 1, "truncated instruction");
+    assert_rejected(&[OpCode::LoadConst.into(), 0, OpCode::Exit.into()], 0, "constant index 0 out of range");
+    assert_rejected(&[OpCode::LoadFunction.into(), 0, OpCode::Exit.into()], 0, "function index 0 out of range");
+    assert_rejected(&[OpCode::LoadLocal.into(), 0, OpCode::Exit.into()], 0, "local index 0 out of range");
+    assert_rejected(&[OpCode::LoadUpvalue.into(), 0, OpCode::Exit.into()], 0, "upvalues are not available");
+    assert_rejected(&[OpCode::AddLocalConst.into(), 0, 0, OpCode::Exit.into()], 0, "local index 0 out of range");
+    assert_rejected(&[OpCode::MoveReg.into(), REG_CONST_SRC1, 0, 0, OpCode::Exit.into()], 0, "local index 0 out of range");
+    assert_rejected(&[OpCode::MoveReg.into(), REG_CONST_SRC2, 0, 0, OpCode::Exit.into()], 0, "invalid register mode");
+}
+
+#[test]
+fn verify_rejects_invalid_cache_index() {
+    let build_global_load = |index: u8| {
+        let mut builder = ChunkBuilder::new();
+        let name = builder.get_or_insert_str("name");
+        let cid = builder.get_or_insert_const(Constant::String(name)).unwrap();
+        builder.new_cache_slot().unwrap();
+        builder.chunk_mut(Chunk::Main).extend_bytes(&[
+            OpCode::LoadConst.into(), u8::try_from(cid).unwrap(),
+            OpCode::LoadGlobal.into(), index, 0,
+            OpCode::Exit.into(),
+        ]);
+        builder.build()
+    };
+    
+    assert!(build_global_load(0).verify().is_ok());
+    
+    let error = build_global_load(1).verify().unwrap_err();
+    assert_eq!(error.offset(), Some(2), "{}", error);
+    assert!(error.to_string().contains("inline cache index 1 out of range"), "{}", error);
+}
+
+#[test]
+fn verify_rejects_invalid_control_flow() {
+    // jump out of the chunk
+    assert_rejected(&[OpCode::Jump.into(), 0x10, 0], 0, "jump target outside of chunk");
+    
+    // jump into the operand of LD_U8, which happens to look like EXIT
+    let bytes = [
+        OpCode::True.into(),
+        OpCode::PopJumpIfTrue.into(), 1, 0,
+        OpCode::UInt8.into(), OpCode::Exit.into(),
+        OpCode::Pop.into(),
+        OpCode::Exit.into(),
+    ];
+    assert_rejected(&bytes, 5, "not the start of an instruction");
+    
+    // no instruction to stop execution at the end of the chunk
+    assert_rejected(&[OpCode::Nil.into()], 0, "past the end of the chunk");
+    
+    // error handlers must be popped in order
+    assert_rejected(&[OpCode::PopHandler.into(), OpCode::Exit.into()], 0, "no error handler");
+}
+
+#[test]
+fn verify_rejects_unbalanced_stack() {
+    assert_rejected(&[OpCode::Pop.into(), OpCode::Exit.into()], 0, "stack underflow");
+    assert_rejected(&[OpCode::Nil.into(), OpCode::Add.into(), OpCode::Exit.into()], 1, "stack underflow");
+    assert_rejected(&[OpCode::Nil.into(), OpCode::JumpIfLT.into(), 0, 0, OpCode::Exit.into()], 1, "stack underflow");
+    
+    // the jump skips over the LD_NIL, so the stack depth at EXIT depends on the path taken
+    let bytes = [
+        OpCode::True.into(),
+        OpCode::PopJumpIfTrue.into(), 1, 0,
+        OpCode::Nil.into(),
+        OpCode::Exit.into(),
+    ];
+    assert_rejected(&bytes, 5, "inconsistent stack depth");
+    
+    // GET_ATTR needs a name
+    let bytes = [
+        OpCode::Nil.into(),
+        OpCode::UInt8.into(), 0,
+        OpCode::GetAttr.into(), 0, 0,
+        OpCode::Exit.into(),
+    ];
+    assert_rejected(&bytes, 3, "expected a name operand");
+}
+
+// run a chunk that passes verification, returning the kind of error that it raises
+fn run_verified(program: UnloadedProgram) -> ErrorKind {
+    program.verify().unwrap();
+    
+    let program = Program::load(program);
+    let module = Module::with_env(None, program.data, builtins::create_prelude());
+    let error = VirtualMachine::new(module, &program.main).run().unwrap_err();
+    *error.kind()
+}
+
+#[test]
+fn verified_bytecode_raises_errors_instead_of_panicking() {
+    // after ITER_UNPACK the verifier no longer knows how deep the stack is, here it is empty
+    let unknown_depth = [
+        OpCode::Empty.into(),
+        OpCode::IterInit.into(),
+        OpCode::IterUnpack.into(),
+        OpCode::Pop.into(),
+    ];
+    let run_after_unpack = |bytes: &[u8]| run_verified(build_main(&[&unknown_depth, bytes].concat()));
+    
+    // empty stack
+    assert_eq!(run_after_unpack(&[OpCode::Pop.into(), OpCode::Exit.into()]), ErrorKind::InvalidBytecode);
+    assert_eq!(run_after_unpack(&[OpCode::Neg.into(), OpCode::Exit.into()]), ErrorKind::InvalidBytecode);
+    
+    // more values than are on the stack
+    assert_eq!(run_after_unpack(&[OpCode::UInt8.into(), 3, OpCode::TupleN.into(), OpCode::Exit.into()]), ErrorKind::InvalidBytecode);
+    assert_eq!(run_after_unpack(&[OpCode::UInt8.into(), 3, OpCode::DropN.into(), OpCode::Exit.into()]), ErrorKind::InvalidBytecode);
+    assert_eq!(run_after_unpack(&[OpCode::UInt8.into(), 2, OpCode::Call.into(), OpCode::Exit.into()]), ErrorKind::InvalidBytecode);
+    
+    // operands of the wrong kind
+    assert_eq!(run_after_unpack(&[OpCode::True.into(), OpCode::TupleN.into(), OpCode::Exit.into()]), ErrorKind::InvalidBytecode);
+    assert_eq!(run_after_unpack(&[OpCode::True.into(), OpCode::Export.into(), OpCode::Exit.into()]), ErrorKind::InvalidBytecode);
+    
+    let mut builder = ChunkBuilder::new();
+    let name = builder.get_or_insert_str("name");
+    let cid = builder.get_or_insert_const(Constant::String(name)).unwrap();
+    builder.chunk_mut(Chunk::Main).extend_bytes(&unknown_depth);
+    builder.chunk_mut(Chunk::Main).extend_bytes(&[
+        OpCode::Nil.into(),
+        OpCode::Nil.into(),  // not a class
+        OpCode::LoadConst.into(), u8::try_from(cid).unwrap(),
+        OpCode::GetSuper.into(),
+        OpCode::Exit.into(),
+    ]);
+    assert_eq!(run_verified(builder.build()), ErrorKind::InvalidBytecode);
+    
+    // iterator state that the iterator never produced
+    let bytes = [
+        OpCode::True.into(),
+        OpCode::Tuple.into(), 1,
+        OpCode::IterInit.into(),
+        OpCode::Pop.into(),
+        OpCode::UInt8.into(), 5,
+        OpCode::IterNext.into(),
+        OpCode::Exit.into(),
+    ];
+    assert_eq!(run_verified(build_main(&bytes)), ErrorKind::InvalidValue);
+}
+
+#[test]
+fn read_from_rejects_unverified_program() {
+    let mut bytes = Vec::new();
+    build_main(&[OpCode::Pop.into(), OpCode::Exit.into()]).write_to(&mut bytes).unwrap();
+    
+    let error = UnloadedProgram::read_from(bytes.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn unreachable_code_is_not_emitted() {
+    let build = compile(r#"
+        fun choose(x)
+            if x then
+                return "first"
+            else
+                return "second"
+            end
+            print("after if")
+        end
+        
+        fun forever()
+            loop end
+            print("after loop")
+        end
+        
+        assert choose(false) == "second"
+    "#);
+    
+    let program = &build.program;
+    program.verify().unwrap();
+    
+    let is_emitted = |string: &str| program.consts().iter().any(|value| matches!(
+        value, Constant::String(index) if program.get_string(*index) == string
+    ));
+    assert!(is_emitted("second"));
+    assert!(!is_emitted("after if"));
+    assert!(!is_emitted("after loop"));
+}
+
+#[test]
+fn compiler_reports_warnings() {
+    let build = compile(r#"
+        fun choose(x)
+            if x then
+                return 1
+            else
+                return 2
+            end
+            print("unreachable")
+            print("also unreachable")
+        end
+        
+        fun shadow()
+            let a = 1
+            begin
+                let a = 2
+                let a = 3   # hiding a local in the same scope is not shadowing
+                print(a)
+            end
+            a
+        end
+        
+        if false then print("never") end
+        while true do break end
+        while nil do end
+    "#);
+    
+    let messages = build.warnings.iter()
+        .map(|warning| warning.message())
+        .collect::<Vec<&str>>();
+    
+    assert_eq!(messages, [
+        "unreachable code",
+        "local \"a\" shadows a variable from an enclosing scope",
+        "condition is always false",
+        "condition is always false",
+    ]);
+    assert!(build.warnings.iter().all(|warning| warning.debug_symbol().is_some()));
+    
+    // each kind of warning can be told apart by its code
+    let codes = build.warnings.iter()
+        .map(|warning| warning.error_code().unwrap())
+        .collect::<Vec<ErrorCode>>();
+    assert_eq!(codes, [codes::W0201, codes::W0203, codes::W0202, codes::W0202]);
+}
+
+#[test]
+fn compiler_reports_unused_locals() {
+    let source = r#"
+        fun unused(ignored_param)
+            let unused = 1
+            var written = 2
+            written = 3
+            let _ignored = 4
+            
+            let read = 5
+            var updated = 6
+            updated += read
+            
+            let captured = 7
+            fun() captured end
+        end
+        
+        begin
+            let a, b = (1, 2)
+            a
+        end
+        
+        for item in (1, 2) do end
+        for _ in (1, 2) do end
+    "#;
+    let build = compile(source);
+    
+    let messages = build.warnings.iter()
+        .map(|warning| warning.message())
+        .collect::<Vec<&str>>();
+    
+    assert_eq!(messages, [
+        "unused local variable \"unused\"",
+        "unused local variable \"written\"",
+        "unused local variable \"b\"",
+        "unused local variable \"item\"",
+    ]);
+    assert!(build.warnings.iter().all(|warning| *warning.kind() == WarningKind::UnusedLocal));
+    assert!(build.warnings.iter().all(|warning| warning.error_code() == Some(codes::W0204)));
+    
+    // a destructured binding is reported at the binding itself, not the whole declaration
+    let symbol = build.warnings[2].debug_symbol().unwrap();
+    let offset = source.find("b = (1, 2)").unwrap();
+    assert_eq!((symbol.start() as usize, symbol.len()), (offset, 1));
+}
+
+#[test]
+fn compiler_reports_unused_imports() {
+    let build = compile(r#"
+        import foo.bar
+        
+        let x = 1
+        var y = x
+        fun f() nonlocal y = 2 end
+    "#);
+    
+    let messages = build.warnings.iter()
+        .map(|warning| warning.message())
+        .collect::<Vec<&str>>();
+    
+    assert_eq!(messages, ["unused import \"foo.bar\""]);
+    assert_eq!(build.warnings[0].error_code(), Some(codes::W0205));
+    
+    // any global that the program doesn't declare could have been bound by the import
+    let build = compile(r#"
+        import foo.bar
+        
+        let x = 1
+        baz(x)
+    "#);
+    assert!(build.warnings.iter().all(|warning| *warning.kind() != WarningKind::UnusedImport));
+}
+
+#[test]
+fn constant_pool_has_no_duplicates() {
+    let build = compile(r#"
+        let big = 1000000
+        let pi = 3.14159
+        fun f()
+            "hello" + "hello"
+        end
+        fun g()
+            (big, 1000000, 3.14159, "hello")
+        end
+        assert 1000000 == big and 3.14159 == pi
+    "#);
+    
+    let consts = build.program.consts();
+    for (index, value) in consts.iter().enumerate() {
+        assert!(!consts[index + 1 ..].contains(value), "duplicate constant: {:?}", value);
+    }
+    
+    // constants are shared between the main chunk and function chunks
+    let count = consts.iter().filter(|value| matches!(value, Constant::Integer(1000000))).count();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn loaded_constants_are_converted_once() {
+    let build = compile(r#"
+        let big = 1000000
+        let half = 0.5
+        let a, b = (big, "hello")
+    "#);
+    let consts = build.program.consts().to_vec();
+    assert!(consts.iter().any(|constant| matches!(constant, Constant::Error { .. })));
+    
+    let (module, _) = load_with_env(build, builtins::create_prelude());
+    
+    for (cid, constant) in consts.iter().enumerate() {
+        let cid = ConstID::try_from(cid).unwrap();
+        let value = module.get_const(cid);
+        match constant {
+            Constant::Integer(1000000) => assert!(matches!(value, Variant::Integer(1000000))),
+            Constant::Float(_) => assert!(matches!(value, Variant::Float(half) if half == 0.5)),
+            Constant::String(_) => assert!(value.as_strval().is_some()),
+            
+            // each error that is raised must be a new object
+            Constant::Error { .. } => match (value, module.get_const(cid)) {
+                (Variant::Error(first), Variant::Error(second)) => assert!(!Gc::ptr_eq(&first, &second)),
+                _ => panic!("not an error"),
+            },
+            
+            _ => { },
+        }
+    }
+}
+
+#[test]
+fn long_jumps_are_widened() {
+    // enough code that jumping over it needs more than a 16-bit offset
+    let block = "total += 1\n".repeat(5000);
+    let text = format!(r#"
+        var total = 0
+        let x = true
+        
+        if x then
+            {block}
+        else
+            total = -1
+        end
+        
+        var i = 0
+        while i < 2 do
+            {block}
+            i += 1
+        end
+        
+        try
+            {block}
+            if x then
+                1 / 0
+            end
+        except
+            total += 1
+        end
+        
+        assert total == 20001
+    "#, block = block);
+    
+    let build = compile(&text);
+    build.program.verify().unwrap();
+    assert!(build.program.main().len() > usize::from(u16::MAX));
+    
+    with_vm(build, |mut vm| vm.run()).unwrap();
+}
+
+fn chunk_opcodes(chunk: &[u8]) -> Vec<OpCode> {
+    let mut opcodes = Vec::new();
+    let mut offset = 0;
+    while offset < chunk.len() {
+        let opcode = OpCode::from_byte(chunk[offset]).unwrap();
+        opcodes.push(opcode);
+        offset += opcode.instr_len();
+    }
+    opcodes
+}
+
+#[test]
+fn hot_sequences_are_fused() {
+    let build = compile(r#"
+        fun count(n)
+            var i = 0
+            var total = 0
+            while i < n do
+                total += i - 1
+                i += 1
+            end
+            total
+        end
+        assert count(10) == 35
+    "#);
+    build.program.verify().unwrap();
+    
+    let opcodes = chunk_opcodes(build.program.get_chunk(0));
+    for opcode in [OpCode::JumpIfNotLT, OpCode::JumpIfLT, OpCode::AddLocalConst, OpCode::SubLocalConst] {
+        assert!(opcodes.contains(&opcode), "{:?} was not emitted", opcode);
+    }
+    for opcode in [OpCode::LT, OpCode::PopJumpIfFalse, OpCode::PopJumpIfTrue, OpCode::Sub] {
+        assert!(!opcodes.contains(&opcode), "{:?} was not fused", opcode);
+    }
+    
+    with_vm(build, |mut vm| vm.run()).unwrap();
+}
+
+#[test]
+fn register_backend_addresses_operands_directly() {
+    let text = r#"
+        fun count(n)
+            var i = 0
+            var total = 0
+            var step = nil
+            while i < n do
+                step = i * 2
+                total += step
+                i += 1
+            end
+            total
+        end
+        assert count(10) == 90
+    "#;
+    
+    let options = CompileOptions { backend: Backend::Register };
+    let build = crate::build_module_with_options(&ModuleSource::String(text.to_string()), options).expect("build failed");
+    build.program.verify().unwrap();
+    
+    let opcodes = chunk_opcodes(build.program.get_chunk(0));
+    for opcode in [OpCode::MulReg, OpCode::AddReg] {
+        assert!(opcodes.contains(&opcode), "{:?} was not emitted", opcode);
+    }
+    for opcode in [OpCode::StoreLocal, OpCode::Mul, OpCode::AddLocalConst] {
+        assert!(!opcodes.contains(&opcode), "{:?} was emitted", opcode);
+    }
+    
+    with_vm(build, |mut vm| vm.run()).unwrap();
+}
+
+#[test]
+fn wide_locals_and_upvalues() {
+    // more locals and upvalues than fit in a single byte operand
+    let count = 400;
+    let decls: String = (0..count).map(|i| format!("var v{} = {}\n", i, i)).collect();
+    let reads: String = (0..count).map(|i| format!("total += v{}\n", i)).collect();
+    let text = format!(r#"
+        fun outer()
+            {decls}
+            fun inner()
+                var total = 0
+                {reads}
+                nonlocal v{last} = -1
+                total
+            end
+            let total = inner()
+            assert v{last} == -1
+            total
+        end
+        assert outer() == {sum}
+        
+        begin
+            {decls}
+            v{last} = 0
+            assert v{last} + v0 == 0
+        end
+    "#, decls = decls, reads = reads, last = count - 1, sum = (0..count).sum::<usize>());
+    
+    let build = compile(&text);
+    build.program.verify().unwrap();
+    
+    with_vm(build, |mut vm| vm.run()).unwrap();
+}
+
+#[test]
+fn tail_calls_reuse_call_frame() {
+    let build = compile(r#"
+        fun count_down(n)
+            if n == 0 then
+                return "done"
+            end
+            count_down(n - 1)
+        end
+        assert count_down(100) == "done"
+    "#);
+    
+    let max_depth = with_vm(build, |vm| {
+        vm.run_steps().map(|snapshot| snapshot.unwrap().calls.len()).max()
+    });
+    assert_eq!(max_depth, Some(1));
+}
+
+#[test]
+fn call_depth_is_limited() {
+    let build = compile(r#"
+        fun depth(n)
+            if n == 0 then
+                return 0
+            end
+            1 + depth(n - 1)
+        end
+        depth(100)
+    "#);
+    
+    let error = with_vm(build, |mut vm| {
+        vm.set_max_call_depth(50);
+        vm.run()
+    }).unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::StackOverflow);
+}
+
+#[test]
+fn runtime_errors_point_to_subexpression() {
+    let cases = [
+        ("let a, b = 1, nil\nlet c = 3 + (a + b) * 2\n", "a + b"),
+        ("let x = 1 + missing * 2\n", "missing"),
+        ("let f = fun() nil end\nassert 2 > 1 and f().value\n", "f().value"),
+    ];
+    
+    for (text, expected) in cases {
+        let error = run(text).unwrap_err();
+        
+        let symbol = error.iter_trace().next().and_then(|site| site.debug_symbol()).expect("error has a symbol");
+        let span = text.chars().skip(symbol.start() as usize).take(symbol.len().into()).collect::<String>();
+        assert_eq!(span, expected);
+    }
+}
+
+#[test]
+fn instruction_budget_stops_execution() {
+    let build = compile(r#"
+        var count = 0
+        while true do
+            try
+                count += 1
+            except
+                break
+            end
+        end
+    "#);
+    
+    let error = with_vm(build, |mut vm| {
+        vm.set_instruction_budget(Some(1000));
+        vm.run()
+    }).unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::BudgetExceeded);
+    
+    // a program that finishes within the budget is unaffected
+    let build = compile("let x = 1 + 2\nassert x == 3\n");
+    with_vm(build, |mut vm| {
+        vm.set_instruction_budget(Some(1000));
+        vm.run()
+    }).unwrap();
+}
+
+#[test]
+fn interrupt_flag_raises_error() {
+    static INTERRUPT: AtomicBool = AtomicBool::new(false);
+    
+    let build = compile(r#"
+        var caught = nil
+        try
+            while true do end
+        except as error
+            caught = error
+        end
+        assert caught.kind == "InterruptedError"
+    "#);
+    
+    with_vm(build, |mut vm| {
+        vm.set_interrupt_flag(Some(&INTERRUPT));
+    
+        let interrupt = std::thread::spawn(|| {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            INTERRUPT.store(true, Ordering::Relaxed);
+        });
+        vm.run().unwrap();
+        interrupt.join().unwrap();
+    });
+    
+    // the flag is cleared once the interrupt is raised
+    assert!(!INTERRUPT.load(Ordering::Relaxed));
+}
+
+#[test]
+fn incremental_compile_continues_program() {
+    let mut compiler = Compiler::new(StringInterner::new());
+    let mut compile_next = |text: &str| {
+        let ast = crate::parse_source(compiler.strings_mut(), SourceText::from(text.to_string())).expect("parse failed");
+        compiler.compile_incremental(ast.iter())
+    };
+    
+    const FIRST: &str = "var total = 1\nfun add(n) total += n end\n";
+    const SECOND: &str = "add(41)\nassert total == 42\nfun twice(n) add(n); add(n) end\n";
+    let first = compile_next(FIRST).unwrap();
+    let second = compile_next(SECOND).unwrap();
+    
+    // a failed compile is discarded, including any scopes that it left open
+    let error = compile_next("begin let y = 1; y = 2 end").unwrap_err();
+    assert_eq!(error.len(), 1);
+    
+    let third = compile_next("twice(4)\nassert total == 50\n").unwrap();
+    
+    // jumps are widened by compiling just the new statements again
+    let block = "total += 1\n".repeat(5000);
+    let fourth = compile_next(&format!("if total == 50 then\n{}else total = -1 end\nassert total == 5050\n", block)).unwrap();
+    
+    // only the new statements are included, and functions keep their IDs
+    let combined = compile(&format!("{}{}", FIRST, SECOND));
+    assert_eq!(first.program.main().len() + second.program.main().len(), combined.program.main().len() + 1);  // each has an EXIT
+    assert_eq!(first.program.functions().len(), 1);
+    assert_eq!(second.program.functions().len(), 2);
+    assert_eq!(third.program.functions().len(), 2);
+    assert_eq!(fourth.program.functions().len(), 2);
+    
+    let env = builtins::create_prelude();
+    let mut vm = None;
+    for build in [first, second, third, fourth] {
+        build.program.verify().unwrap();
+        
+        let (module, main) = load_with_env(build, env);
+        let vm = vm.get_or_insert_with(|| VirtualMachine::new(module, &[]));
+        vm.reload_program(module, main);
+        vm.run().unwrap();
+    }
+}
+
+#[test]
+fn reloaded_vm_runs_next_program() {
+    let env = builtins::create_prelude();
+    
+    // leave the VM in the middle of a call
+    let build = compile(r#"
+        var total = 10
+        fun fail(n)
+            total += n
+            nope()
+        end
+        fail(5)
+    "#);
+    let (module, main) = load_with_env(build, env);
+    
+    let mut vm = VirtualMachine::new(module, &main);
+    vm.set_max_call_depth(50);
+    let error = vm.run().unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::NameNotDefined);
+    
+    let build = compile(r#"
+        fun depth(n)
+            if n == 0 then
+                return 0
+            end
+            1 + depth(n - 1)
+        end
+        assert total == 15
+        assert depth(40) == 40
+    "#);
+    let (module, main) = load_with_env(build, env);
+    vm.reload_program(module, main);
+    vm.run().unwrap();
+    
+    // the VM's settings are kept
+    let build = compile("depth(100)");
+    let (module, main) = load_with_env(build, env);
+    vm.reload_program(module, main);
+    let error = vm.run().unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::StackOverflow);
+}
+
+#[test]
+fn garbage_is_collected_at_safepoints() {
+    // nothing in the loop calls a function, so the GC can only run at the backwards jump
+    let build = compile(r#"
+        var items = ()
+        var i = 0
+        while i < 50000 do
+            items = (i, i)
+            i += 1
+        end
+    "#);
+    
+    let cycles = gc_stats().cycle_count;
+    with_vm(build, |mut vm| vm.run()).unwrap();
+    assert!(gc_stats().cycle_count > cycles);
+}
+
+#[test]
+fn memory_limit_raises_error() {
+    let build = compile(r#"
+        var caught = nil
+        try
+            var items = ()
+            while true do
+                items = (items, 1, 2, 3, 4, 5, 6, 7)
+            end
+        except as error
+            caught = error
+        end
+        assert caught.kind == "MemoryError"
+        
+        # the memory is released once the error is handled
+        var items = ()
+        for i in range(100) do
+            items = (items, i)
+        end
+    "#);
+    
+    with_vm(build, |mut vm| {
+        vm.set_memory_limit(Some(gc_allocated() + 64 * 1024));
+        vm.run()
+    }).unwrap();
+}
+
+
+#[test]
+fn vm_runs_in_isolated_heap() {
+    let default_count = gc_stats().box_count;
+    
+    let heap = GcHeap::new();
+    let heap = std::thread::spawn(move || {
+        let build = compile(TEST_SOURCE);
+        unsafe {
+            heap.enter(|| {
+                with_vm(build, |mut vm| vm.run()).unwrap();
+            });
+        }
+        heap
+    }).join().unwrap();
+    
+    assert!(unsafe { heap.enter(|| gc_stats().box_count) } > 0);
+    assert_eq!(gc_stats().box_count, default_count);
+}
+
+#[test]
+fn runtime_can_move_between_threads() {
+    let build = compile(TEST_SOURCE);
+    
+    let runtime = Runtime::new();
+    let runtime = std::thread::spawn(move || {
+        unsafe {
+            runtime.enter(|| {
+                with_vm(build, |mut vm| vm.run()).unwrap();
+            });
+        }
+        runtime
+    }).join().unwrap();
+    
+    // the program's symbols belong to the runtime, not the thread that loaded it
+    let is_interned = || with_string_table(|string_table| string_table.borrow().get("make_counter").is_some());
+    assert!(!is_interned());
+    assert!(unsafe { runtime.enter(is_interned) });
+}
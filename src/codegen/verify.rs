@@ -0,0 +1,834 @@
+//! Verification of bytecode that did not come directly from the compiler.
+//!
+//! The VM trusts the bytecode it executes and will panic if it encounters a malformed chunk,
+//! or when built with the `unchecked` feature, may have undefined behaviour instead.
+//! Programs that are read from a file (or from anywhere else) should be checked using
+//! `verify_program()` before they are loaded.
+//!
+//! Each chunk is checked by following every path that execution can take through it, while
+//! tracking the contents of the value stack along with the number of locals and error handlers.
+//! The number of locals is tracked as a lower bound, since paths that are about to raise an 
+//! error may arrive at the same instruction with a different number of locals.
+//! This ensures that:
+//!
+//! * every reachable instruction has a valid opcode and a complete operand;
+//! * constant, function, local, upvalue, and inline cache indices are in range;
+//! * jumps and error handlers target the start of an instruction inside the same chunk;
+//! * execution cannot run past the end of a chunk;
+//! * the stack and locals never underflow, and all paths that meet at the same instruction 
+//!   agree on the depth of the stack and on the number of error handlers;
+//! * operands used as names, counts, or class methods have the right kind of value.
+//!
+//! Some instructions (e.g. `ITER_UNPACK`) have a stack effect that depends on values that are
+//! only known at runtime. The stack can't be checked along a path after such an instruction,
+//! although all of the other checks still apply. Likewise, the stack of a function that
+//! has default or variadic parameters is only checked after `IN_ARGS`, since it relies on
+//! the function's preamble to leave exactly one value on the stack for each parameter.
+//! The kind of an operand is also only known if it was loaded from a constant.
+//!
+//! Whatever the verifier can't check is checked by the VM instead, which raises an
+//! `InvalidBytecodeError` if the stack underflows or an operand has the wrong kind.
+//! So a program that passes verification can fail, but it can't crash the VM.
+
+use core::fmt;
+use std::error::Error;
+use crate::codegen::OpCode;
+use crate::codegen::opcodes::{CacheIndex, REG_CONST_SRC1, REG_CONST_SRC2};
+use crate::codegen::chunk::{UnloadedProgram, Chunk};
+use crate::codegen::consts::{Constant, ConstID};
+use crate::codegen::funproto::{UnloadedFunction, FunctionID, UpvalueTarget};
+
+
+pub type VerifyResult<T> = Result<T, VerifyError>;
+
+#[derive(Debug, Clone)]
+pub struct VerifyError {
+    chunk_id: Option<Chunk>,  // None if the error is not inside a chunk
+    offset: Option<usize>,
+    message: String,
+}
+
+impl VerifyError {
+    fn new(chunk_id: Option<Chunk>, offset: Option<usize>, message: impl Into<String>) -> Self {
+        Self { chunk_id, offset, message: message.into() }
+    }
+    
+    pub fn chunk_id(&self) -> Option<Chunk> { self.chunk_id }
+    pub fn offset(&self) -> Option<usize> { self.offset }
+}
+
+impl Error for VerifyError { }
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.chunk_id {
+            None => fmt.write_str("invalid program")?,
+            Some(Chunk::Main) => fmt.write_str("invalid bytecode in main chunk")?,
+            Some(Chunk::Function(fun_id)) => write!(fmt, "invalid bytecode in chunk {}", fun_id)?,
+        }
+        
+        if let Some(offset) = self.offset {
+            write!(fmt, " at {:04X}", offset)?;
+        }
+        
+        write!(fmt, ": {}", self.message)
+    }
+}
+
+
+/// Check that a program can be loaded and executed without crashing the VM.
+pub fn verify_program(program: &UnloadedProgram) -> VerifyResult<()> {
+    verify_tables(program)
+        .map_err(|message| VerifyError::new(None, None, message))?;
+    
+    ChunkVerifier::new(program, Chunk::Main).verify()?;
+    
+    for (chunk_id, _) in program.iter_chunks() {
+        ChunkVerifier::new(program, chunk_id).verify()?;
+    }
+    
+    Ok(())
+}
+
+fn verify_tables(program: &UnloadedProgram) -> Result<(), String> {
+    let string_count = program.iter_strings().count();
+    
+    for constant in program.consts().iter() {
+        let string_id = match constant {
+            Constant::String(string_id) => string_id,
+            Constant::BigInt(string_id) => string_id,
+            Constant::Error { message, .. } => message,
+            Constant::Integer(..) | Constant::Float(..) => continue,
+        };
+        
+        if *string_id >= string_count {
+            return Err(format!("string index {} is out of range", string_id));
+        }
+    }
+    
+    // every cache slot must be addressable, since they are all allocated when the program is loaded
+    if program.cache_slots() > usize::from(CacheIndex::MAX) + 1 {
+        return Err(format!("too many inline cache slots ({})", program.cache_slots()));
+    }
+    
+    // every chunk must have a function, since chunks are located using the function ID
+    if program.functions().len() != program.iter_chunks().count() {
+        return Err("function table does not match chunk table".to_string());
+    }
+    
+    for (index, function) in program.functions().iter().enumerate() {
+        if usize::from(function.fun_id) != index {
+            return Err(format!("function {} has mismatched ID {}", index, function.fun_id));
+        }
+        
+        let signature = &function.signature;
+        let names = signature.name.iter()
+            .chain(signature.required.iter().map(|param| &param.name))
+            .chain(signature.default.iter().map(|param| &param.name))
+            .chain(signature.variadic.iter().map(|param| &param.name));
+        
+        for const_id in names {
+            if !matches!(program.consts().get(usize::from(*const_id)), Some(Constant::String(..))) {
+                return Err(format!("function {} has an invalid name constant", index));
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+
+// The kind of value held by a stack slot, as far as can be determined without executing anything
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Value,
+    Nil,
+    Name,  // a string constant
+    Count(usize),  // a non-negative integer constant
+    Function,
+}
+
+impl Slot {
+    fn merge(self, other: Slot) -> Slot {
+        if self == other { self } else { Slot::Value }
+    }
+}
+
+// The size of the stack and locals when an error handler was registered, which the VM restores when an error is caught
+#[derive(Debug, Clone, PartialEq)]
+struct HandlerState {
+    stack_len: Option<usize>,
+    locals_len: usize,
+}
+
+// The state of a call frame when execution reaches an instruction
+#[derive(Debug, Clone, PartialEq)]
+struct FrameState {
+    stack: Option<Vec<Slot>>,  // None if the stack depth is not known
+    locals: usize,  // the minimum number of locals
+    handlers: Vec<HandlerState>,
+}
+
+impl FrameState {
+    fn merge(&self, other: &FrameState) -> Result<FrameState, String> {
+        if self.handlers.len() != other.handlers.len() {
+            return Err("inconsistent number of error handlers".to_string());
+        }
+        
+        let mut handlers = Vec::with_capacity(self.handlers.len());
+        for (handler, other) in self.handlers.iter().zip(other.handlers.iter()) {
+            let stack_len = match (handler.stack_len, other.stack_len) {
+                (Some(stack_len), Some(other)) if stack_len != other =>
+                    return Err("inconsistent error handler state".to_string()),
+                (Some(stack_len), Some(..)) => Some(stack_len),
+                _ => None,
+            };
+            
+            let locals_len = handler.locals_len.min(other.locals_len);
+            handlers.push(HandlerState { stack_len, locals_len });
+        }
+        
+        let stack = match (&self.stack, &other.stack) {
+            (Some(stack), Some(other)) if stack.len() != other.len() =>
+                return Err("inconsistent stack depth".to_string()),
+            (Some(stack), Some(other)) => Some(
+                stack.iter().zip(other.iter()).map(|(slot, other)| slot.merge(*other)).collect()
+            ),
+            _ => None,
+        };
+        
+        Ok(FrameState { stack, locals: self.locals.min(other.locals), handlers })
+    }
+    
+    // the lowest the stack can go without discarding values that an error handler will restore
+    fn stack_floor(&self) -> usize {
+        self.handlers.last().and_then(|handler| handler.stack_len).unwrap_or(0)
+    }
+    
+    fn locals_floor(&self) -> usize {
+        self.handlers.last().map_or(0, |handler| handler.locals_len)
+    }
+    
+    // returns None if the stack is not being tracked
+    fn pop(&mut self) -> Result<Option<Slot>, String> {
+        let floor = self.stack_floor();
+        match self.stack.as_mut() {
+            None => Ok(None),
+            Some(stack) if stack.len() <= floor => Err("stack underflow".to_string()),
+            Some(stack) => Ok(stack.pop()),
+        }
+    }
+    
+    fn pop_many(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            self.pop()?;
+        }
+        Ok(())
+    }
+    
+    fn pop_name(&mut self) -> Result<(), String> {
+        match self.pop()? {
+            None | Some(Slot::Name) => Ok(()),
+            _ => Err("expected a name operand".to_string()),
+        }
+    }
+    
+    // returns None if the count is only known at runtime
+    fn pop_count(&mut self) -> Result<Option<usize>, String> {
+        match self.pop()? {
+            None | Some(Slot::Value) => Ok(None),
+            Some(Slot::Count(count)) => Ok(Some(count)),
+            _ => Err("expected a count operand".to_string()),
+        }
+    }
+    
+    fn peek(&self) -> Result<Option<Slot>, String> {
+        let floor = self.stack_floor();
+        match self.stack.as_ref() {
+            None => Ok(None),
+            Some(stack) if stack.len() <= floor => Err("stack underflow".to_string()),
+            Some(stack) => Ok(stack.last().copied()),
+        }
+    }
+    
+    fn push(&mut self, slot: Slot) {
+        if let Some(stack) = self.stack.as_mut() {
+            stack.push(slot);
+        }
+    }
+    
+    // pop a sequence of values whose length may only be known at runtime
+    fn pop_sequence(&mut self, count: Option<usize>) -> Result<(), String> {
+        match count {
+            Some(count) => self.pop_many(count),
+            None => { self.stack = None; Ok(()) },
+        }
+    }
+    
+    fn check_local(&self, index: usize) -> Result<(), String> {
+        if index >= self.locals {
+            return Err(format!("local index {} out of range", index));
+        }
+        Ok(())
+    }
+}
+
+
+// where execution can go after an instruction
+enum Flow {
+    Next,
+    Jump(usize),
+    Branch(usize),  // either the next instruction or the jump target
+    Handler(usize, FrameState),  // the next instruction, or the handler target if an error is caught
+    Stop,
+}
+
+fn read_operand<const N: usize>(data: &[u8]) -> Result<[u8; N], String> {
+    data.get(..N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "missing operand".to_string())
+}
+
+fn read_u8(data: &[u8]) -> Result<usize, String> {
+    Ok(usize::from(read_operand::<1>(data)?[0]))
+}
+
+fn read_register_mode(data: &[u8], allowed: u8) -> Result<u8, String> {
+    let mode = read_operand::<1>(data)?[0];
+    if mode & !allowed != 0 {
+        return Err(format!("invalid register mode {:#04X}", mode));
+    }
+    Ok(mode)
+}
+
+fn read_u16(data: &[u8]) -> Result<usize, String> {
+    Ok(usize::from(u16::from_le_bytes(read_operand(data)?)))
+}
+
+fn read_i16(data: &[u8]) -> Result<isize, String> {
+    Ok(isize::from(i16::from_le_bytes(read_operand(data)?)))
+}
+
+fn read_i32(data: &[u8]) -> Result<isize, String> {
+    isize::try_from(i32::from_le_bytes(read_operand(data)?))
+        .map_err(|_| "jump offset out of range".to_string())
+}
+
+
+struct ChunkVerifier<'p> {
+    program: &'p UnloadedProgram,
+    chunk_id: Chunk,
+    chunk: &'p [u8],
+    function: Option<&'p UnloadedFunction>,
+    states: Vec<Option<FrameState>>,  // the state on entry to the instruction at each offset
+    worklist: Vec<usize>,
+}
+
+impl<'p> ChunkVerifier<'p> {
+    fn new(program: &'p UnloadedProgram, chunk_id: Chunk) -> Self {
+        let (chunk, function) = match chunk_id {
+            Chunk::Main => (program.main(), None),
+            Chunk::Function(fun_id) => (program.get_chunk(fun_id), Some(program.get_function(fun_id))),
+        };
+        
+        Self {
+            program, chunk_id, chunk, function,
+            states: vec![None; chunk.len()],
+            worklist: Vec::new(),
+        }
+    }
+    
+    fn error(&self, offset: Option<usize>, message: impl Into<String>) -> VerifyError {
+        VerifyError::new(Some(self.chunk_id), offset, message)
+    }
+    
+    fn entry_state(&self) -> FrameState {
+        match self.function {
+            // the main chunk of a module starts with an empty frame
+            None => FrameState {
+                stack: Some(Vec::new()),
+                locals: 0,
+                handlers: Vec::new(),
+            },
+            
+            // functions start with the callee and arguments on the stack,
+            // and the receiver and argument count in locals
+            Some(function) => {
+                let signature = &function.signature;
+                let stack =
+                    if signature.default.is_empty() && signature.variadic.is_none() {
+                        Some(vec![Slot::Value; 1 + signature.required.len()])
+                    } else {
+                        None
+                    };
+                
+                FrameState { stack, locals: 2, handlers: Vec::new() }
+            }
+        }
+    }
+    
+    fn verify(mut self) -> VerifyResult<()> {
+        if self.chunk.is_empty() {
+            return Err(self.error(None, "empty chunk"));
+        }
+        
+        let entry = self.entry_state();
+        self.merge_state(0, 0, entry)?;
+        
+        while let Some(offset) = self.worklist.pop() {
+            let mut state = self.states[offset].clone().unwrap();
+            
+            let flow = self.step(offset, &mut state)
+                .map_err(|message| self.error(Some(offset), message))?;
+            
+            let next = offset + OpCode::from_byte(self.chunk[offset]).unwrap().instr_len();
+            
+            match flow {
+                Flow::Stop => { },
+                Flow::Next => self.merge_next(offset, next, state)?,
+                Flow::Jump(target) => self.merge_state(offset, target, state)?,
+                Flow::Branch(target) => {
+                    self.merge_state(offset, target, state.clone())?;
+                    self.merge_next(offset, next, state)?;
+                },
+                Flow::Handler(target, handler_state) => {
+                    self.merge_state(offset, target, handler_state)?;
+                    self.merge_next(offset, next, state)?;
+                },
+            }
+        }
+        
+        self.check_boundaries()
+    }
+    
+    fn merge_next(&mut self, offset: usize, next: usize, state: FrameState) -> VerifyResult<()> {
+        if next >= self.chunk.len() {
+            return Err(self.error(Some(offset), "execution runs past the end of the chunk"));
+        }
+        self.merge_state(offset, next, state)
+    }
+    
+    fn merge_state(&mut self, offset: usize, target: usize, state: FrameState) -> VerifyResult<()> {
+        let merged = match &self.states[target] {
+            None => state,
+            Some(existing) => {
+                let merged = existing.merge(&state)
+                    .map_err(|message| self.error(Some(target), message))?;
+                
+                if merged == *existing {
+                    return Ok(());
+                }
+                merged
+            }
+        };
+        
+        log::trace!("verify {:?} {:04X} -> {:04X}: {:?}", self.chunk_id, offset, target, merged);
+        
+        self.states[target] = Some(merged);
+        self.worklist.push(target);
+        Ok(())
+    }
+    
+    // jumps and error handlers must not land in the middle of another instruction
+    fn check_boundaries(&self) -> VerifyResult<()> {
+        let mut instr_end = 0;
+        for (offset, state) in self.states.iter().enumerate() {
+            if state.is_none() {
+                continue;
+            }
+            if offset < instr_end {
+                return Err(self.error(Some(offset), "jump target is not the start of an instruction"));
+            }
+            instr_end = offset + OpCode::from_byte(self.chunk[offset]).unwrap().instr_len();
+        }
+        Ok(())
+    }
+    
+    fn jump_target(&self, next: usize, offset: isize) -> Result<usize, String> {
+        let target =
+            if offset >= 0 { next.checked_add(offset.unsigned_abs()) }
+            else { next.checked_sub(offset.unsigned_abs()) };
+        
+        target.filter(|target| *target < self.chunk.len())
+            .ok_or_else(|| "jump target outside of chunk".to_string())
+    }
+    
+    fn check_upvalue(&self, index: usize) -> Result<(), String> {
+        let function = self.function
+            .ok_or_else(|| "upvalues are not available in the main chunk".to_string())?;
+        
+        if index >= function.upvalues.len() {
+            return Err(format!("upvalue index {} out of range", index));
+        }
+        Ok(())
+    }
+    
+    fn check_function(&self, fun_id: usize, state: &FrameState) -> Result<(), String> {
+        let function = FunctionID::try_from(fun_id).ok()
+            .filter(|fun_id| usize::from(*fun_id) < self.program.functions().len())
+            .map(|fun_id| self.program.get_function(fun_id))
+            .ok_or_else(|| format!("function index {} out of range", fun_id))?;
+        
+        // the new function will capture values from this call frame
+        for upvalue in function.upvalues.iter() {
+            match upvalue {
+                UpvalueTarget::Local(index) => state.check_local(usize::from(*index))?,
+                UpvalueTarget::Upvalue(index) => self.check_upvalue(usize::from(*index))?,
+            }
+        }
+        Ok(())
+    }
+    
+    // the source operand of a register instruction is a constant if its mode bit is set, otherwise a local
+    fn check_register(&self, state: &FrameState, const_bit: u8, operand: usize) -> Result<(), String> {
+        if const_bit != 0 {
+            self.load_const(operand)?;
+            Ok(())
+        } else {
+            state.check_local(operand)
+        }
+    }
+    
+    fn load_const(&self, cid: usize) -> Result<Slot, String> {
+        let constant = ConstID::try_from(cid).ok()
+            .and_then(|cid| self.program.consts().get(usize::from(cid)))
+            .ok_or_else(|| format!("constant index {} out of range", cid))?;
+        
+        let slot = match constant {
+            Constant::String(..) => Slot::Name,
+            Constant::Integer(value) => usize::try_from(*value).map_or(Slot::Value, Slot::Count),
+            Constant::Float(..) | Constant::BigInt(..) | Constant::Error { .. } => Slot::Value,
+        };
+        Ok(slot)
+    }
+    
+    fn check_cache(&self, index: usize) -> Result<(), String> {
+        if index >= self.program.cache_slots() {
+            return Err(format!("inline cache index {} out of range", index));
+        }
+        Ok(())
+    }
+    
+    fn param_count(&self) -> Result<usize, String> {
+        let function = self.function
+            .ok_or_else(|| "arguments are not available in the main chunk".to_string())?;
+        
+        let signature = &function.signature;
+        Ok(signature.required.len() + signature.default.len() + usize::from(signature.variadic.is_some()))
+    }
+    
+    // Apply the effects of a single instruction to the frame state
+    fn step(&self, offset: usize, state: &mut FrameState) -> Result<Flow, String> {
+        let op_byte = self.chunk[offset];
+        let opcode = OpCode::from_byte(op_byte)
+            .ok_or_else(|| format!("invalid opcode {:#04X}", op_byte))?;
+        
+        let next = offset + opcode.instr_len();
+        let data = self.chunk.get((offset + 1)..next)
+            .ok_or_else(|| "truncated instruction".to_string())?;
+        
+        match opcode {
+            OpCode::Nop => { },
+            
+            OpCode::Exit => {
+                if self.function.is_some() {
+                    return Err("exit outside of the main chunk".to_string());
+                }
+                return Ok(Flow::Stop);
+            },
+            
+            OpCode::Return | OpCode::Error => {
+                state.pop()?;
+                return Ok(Flow::Stop);
+            },
+            
+            OpCode::PushHandler | OpCode::LongPushHandler => {
+                let offset = match opcode {
+                    OpCode::PushHandler => read_i16(data)?,
+                    _ => read_i32(data)?,
+                };
+                let target = self.jump_target(next, offset)?;
+                
+                // when an error is caught, the frame is restored and the error is pushed onto the stack
+                let mut handler_state = state.clone();
+                handler_state.push(Slot::Value);
+                
+                state.handlers.push(HandlerState {
+                    stack_len: state.stack.as_ref().map(|stack| stack.len()),
+                    locals_len: state.locals,
+                });
+                
+                return Ok(Flow::Handler(target, handler_state));
+            },
+            OpCode::PopHandler => {
+                if state.handlers.pop().is_none() {
+                    return Err("no error handler to pop".to_string());
+                }
+            },
+            
+            OpCode::Import | OpCode::Export => state.pop_name()?,
+            
+            OpCode::Call | OpCode::TailCall => {
+                let nargs = state.pop_count()?;
+                state.pop_sequence(nargs)?;
+                state.pop()?;  // callee
+                state.push(Slot::Value);
+            },
+            
+            OpCode::InsertArgs => {
+                let param_count = self.param_count()?;
+                state.pop_many(param_count)?;
+                state.locals += param_count;
+                
+                // after the preamble, the only value left in the frame is the callee
+                if state.stack.is_none() {
+                    state.stack = Some(vec![Slot::Value]);
+                }
+            },
+            
+            OpCode::Pop => { state.pop()?; },
+            OpCode::Drop => state.pop_many(read_u8(data)?)?,
+            OpCode::DropN => {
+                let count = state.pop_count()?;
+                state.pop_sequence(count)?;
+            },
+            OpCode::Clone => {
+                if let Some(slot) = state.peek()? {
+                    state.push(slot);
+                }
+            },
+            
+            OpCode::Tuple | OpCode::BuildList | OpCode::Concat => {
+                state.pop_many(read_u8(data)?)?;
+                state.push(Slot::Value);
+            },
+            OpCode::BuildDict => {
+                state.pop_many(2 * read_u8(data)?)?;
+                state.push(Slot::Value);
+            },
+            OpCode::TupleN | OpCode::BuildListN => {
+                let count = state.pop_count()?;
+                state.pop_sequence(count)?;
+                state.push(Slot::Value);
+            },
+            OpCode::BuildDictN => {
+                let count = state.pop_count()?;
+                state.pop_sequence(count.map(|count| 2 * count))?;
+                state.push(Slot::Value);
+            },
+            
+            OpCode::IterInit => {
+                state.pop()?;
+                state.push(Slot::Value);
+                state.push(Slot::Value);
+            },
+            OpCode::IterNext => {
+                state.pop()?;
+                state.peek()?;
+                state.push(Slot::Value);
+                state.push(Slot::Value);
+            },
+            OpCode::IterUnpack => {
+                state.pop_many(2)?;
+                state.stack = None;
+            },
+            OpCode::TupleLen => {
+                state.pop()?;
+                state.push(Slot::Value);
+            },
+            
+            OpCode::GetAttr => {
+                state.pop_name()?;
+                state.pop()?;
+                self.check_cache(read_u16(data)?)?;
+                state.push(Slot::Value);
+            },
+            OpCode::SetAttr => {
+                state.pop_name()?;
+                state.pop()?;
+                state.peek()?;
+            },
+            OpCode::GetIndex => {
+                state.pop_many(2)?;
+                state.push(Slot::Value);
+            },
+            OpCode::SetIndex => {
+                state.pop_many(2)?;
+                state.peek()?;
+            },
+            OpCode::GetSuper => {
+                state.pop_name()?;
+                state.pop_many(2)?;
+                state.push(Slot::Value);
+            },
+            
+            OpCode::LoadFunction | OpCode::LoadFunction16 => {
+                let fun_id = match opcode {
+                    OpCode::LoadFunction => read_u8(data)?,
+                    _ => read_u16(data)?,
+                };
+                self.check_function(fun_id, state)?;
+                state.push(Slot::Function);
+            },
+            
+            OpCode::LoadConst | OpCode::LoadConst16 => {
+                let cid = match opcode {
+                    OpCode::LoadConst => read_u8(data)?,
+                    _ => read_u16(data)?,
+                };
+                state.push(self.load_const(cid)?);
+            },
+            
+            OpCode::Class => {
+                for _ in 0..read_u8(data)? {
+                    if !matches!(state.pop()?, None | Some(Slot::Function)) {
+                        return Err("expected a function for class method".to_string());
+                    }
+                    state.pop_name()?;
+                }
+                state.pop()?;  // parent
+                if !matches!(state.pop()?, None | Some(Slot::Name | Slot::Nil)) {
+                    return Err("expected a name or nil for class name".to_string());
+                }
+                state.push(Slot::Value);
+            },
+            
+            OpCode::InsertGlobal | OpCode::InsertGlobalMut | OpCode::StoreGlobal => {
+                state.pop_name()?;
+                state.peek()?;
+            },
+            OpCode::LoadGlobal => {
+                state.pop_name()?;
+                self.check_cache(read_u16(data)?)?;
+                state.push(Slot::Value);
+            },
+            
+            OpCode::InsertLocal => {
+                state.peek()?;
+                state.locals += 1;
+            },
+            OpCode::StoreLocal | OpCode::StoreLocal16 => {
+                let index = match opcode {
+                    OpCode::StoreLocal => read_u8(data)?,
+                    _ => read_u16(data)?,
+                };
+                state.check_local(index)?;
+                state.peek()?;
+            },
+            OpCode::LoadLocal | OpCode::LoadLocal16 => {
+                let index = match opcode {
+                    OpCode::LoadLocal => read_u8(data)?,
+                    _ => read_u16(data)?,
+                };
+                state.check_local(index)?;
+                state.push(Slot::Value);
+            },
+            OpCode::DropLocals => {
+                let count = read_u8(data)?;
+                state.locals = state.locals.checked_sub(count)
+                    .filter(|locals| *locals >= state.locals_floor())
+                    .ok_or_else(|| "locals underflow".to_string())?;
+            },
+            
+            OpCode::StoreUpvalue | OpCode::StoreUpvalue16 => {
+                let index = match opcode {
+                    OpCode::StoreUpvalue => read_u8(data)?,
+                    _ => read_u16(data)?,
+                };
+                self.check_upvalue(index)?;
+                state.peek()?;
+            },
+            OpCode::LoadUpvalue | OpCode::LoadUpvalue16 => {
+                let index = match opcode {
+                    OpCode::LoadUpvalue => read_u8(data)?,
+                    _ => read_u16(data)?,
+                };
+                self.check_upvalue(index)?;
+                state.push(Slot::Value);
+            },
+            OpCode::CloseUpvalue | OpCode::CloseUpvalue16 => {
+                let index = match opcode {
+                    OpCode::CloseUpvalue => read_u8(data)?,
+                    _ => read_u16(data)?,
+                };
+                state.check_local(index)?;
+            },
+            
+            OpCode::Nil => state.push(Slot::Nil),
+            OpCode::True | OpCode::False | OpCode::Empty => state.push(Slot::Value),
+            
+            OpCode::UInt8 => state.push(Slot::Count(read_u8(data)?)),
+            OpCode::Int8 => {
+                let value = i8::from_le_bytes(read_operand(data)?);
+                state.push(usize::try_from(value).map_or(Slot::Value, Slot::Count));
+            },
+            OpCode::Int16 => {
+                let value = i16::from_le_bytes(read_operand(data)?);
+                state.push(usize::try_from(value).map_or(Slot::Value, Slot::Count));
+            },
+            
+            OpCode::Neg | OpCode::Pos | OpCode::Inv | OpCode::Not => {
+                state.pop()?;
+                state.push(Slot::Value);
+            },
+            
+            OpCode::And | OpCode::Xor | OpCode::Or | OpCode::Shl | OpCode::Shr
+            | OpCode::Add | OpCode::Sub | OpCode::Mul | OpCode::Div | OpCode::Mod
+            | OpCode::FloorDiv | OpCode::Exp
+            | OpCode::EQ | OpCode::NE | OpCode::LT | OpCode::LE | OpCode::GE | OpCode::GT
+            | OpCode::In | OpCode::NotIn | OpCode::Is => {
+                state.pop_many(2)?;
+                state.push(Slot::Value);
+            },
+            
+            OpCode::Jump => return Ok(Flow::Jump(self.jump_target(next, read_i16(data)?)?)),
+            OpCode::LongJump => return Ok(Flow::Jump(self.jump_target(next, read_i32(data)?)?)),
+            
+            OpCode::JumpIfFalse | OpCode::JumpIfTrue | OpCode::JumpIfNil | OpCode::JumpIfNotNil => {
+                state.peek()?;
+                return Ok(Flow::Branch(self.jump_target(next, read_i16(data)?)?));
+            },
+            OpCode::LongJumpIfFalse | OpCode::LongJumpIfTrue | OpCode::LongJumpIfNil | OpCode::LongJumpIfNotNil => {
+                state.peek()?;
+                return Ok(Flow::Branch(self.jump_target(next, read_i32(data)?)?));
+            },
+            OpCode::PopJumpIfFalse | OpCode::PopJumpIfTrue => {
+                state.pop()?;
+                return Ok(Flow::Branch(self.jump_target(next, read_i16(data)?)?));
+            },
+            OpCode::PopLongJumpIfFalse | OpCode::PopLongJumpIfTrue => {
+                state.pop()?;
+                return Ok(Flow::Branch(self.jump_target(next, read_i32(data)?)?));
+            },
+            
+            OpCode::AddLocalConst | OpCode::SubLocalConst => {
+                state.check_local(read_u8(data)?)?;
+                self.load_const(read_u8(data.get(1..).unwrap_or_default())?)?;
+                state.push(Slot::Value);
+            },
+            
+            OpCode::JumpIfEQ | OpCode::JumpIfNotEQ | OpCode::JumpIfLT
+            | OpCode::JumpIfNotLT | OpCode::JumpIfLE | OpCode::JumpIfNotLE => {
+                state.pop_many(2)?;
+                return Ok(Flow::Branch(self.jump_target(next, read_i16(data)?)?));
+            },
+            
+            OpCode::MoveReg => {
+                let mode = read_register_mode(data, REG_CONST_SRC1)?;
+                state.check_local(read_u8(data.get(1..).unwrap_or_default())?)?;
+                self.check_register(state, mode & REG_CONST_SRC1, read_u8(data.get(2..).unwrap_or_default())?)?;
+            },
+            
+            OpCode::AddReg | OpCode::SubReg | OpCode::MulReg | OpCode::DivReg | OpCode::ModReg => {
+                let mode = read_register_mode(data, REG_CONST_SRC1 | REG_CONST_SRC2)?;
+                state.check_local(read_u8(data.get(1..).unwrap_or_default())?)?;
+                self.check_register(state, mode & REG_CONST_SRC1, read_u8(data.get(2..).unwrap_or_default())?)?;
+                self.check_register(state, mode & REG_CONST_SRC2, read_u8(data.get(3..).unwrap_or_default())?)?;
+            },
+            
+            OpCode::Inspect | OpCode::Assert => { state.peek()?; },
+        }
+        
+        Ok(Flow::Next)
+    }
+}
@@ -4,8 +4,10 @@ pub mod symbol;
 pub mod dasm;
 pub mod traceback;
 pub mod snapshot;
+pub mod codes;
 
 pub use symbol::{DebugSymbol, DebugSymbolResolver, TokenIndex, TokenLength};
+pub use codes::ErrorCode;
 
 mod tests;
 
@@ -13,4 +15,17 @@ mod tests;
 /// trait for syntax or compile errors that are directly related to a piece of source code
 pub trait SourceError: Error {
     fn debug_symbol(&self) -> Option<&DebugSymbol>;
+    
+    fn severity(&self) -> Severity { Severity::Error }
+    
+    fn error_code(&self) -> Option<ErrorCode> { None }
+    
+    /// What was being done when the error occurred, e.g. "while parsing a function definition"
+    fn context_note(&self) -> Option<String> { None }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
 }
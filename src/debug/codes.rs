@@ -0,0 +1,448 @@
+//! Stable codes that identify each kind of diagnostic, with longer explanations for `sphinx --explain`.
+//!
+//! Codes are grouped by the stage that reports them: E00xx for the lexer, E01xx for the parser,
+//! E02xx for the compiler and E03xx for runtime errors. Warnings use a W prefix instead.
+//! Once assigned, a code is never changed or given to a different kind of error.
+
+use core::fmt;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorCode(&'static str);
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str { self.0 }
+    
+    /// The longer description printed by `sphinx --explain`
+    pub fn explanation(&self) -> &'static str {
+        lookup(self.0).expect("every error code has an explanation").explanation
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(self.0)
+    }
+}
+
+
+pub struct CodeInfo {
+    pub code: ErrorCode,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+/// Find the code with the given name, ignoring case so that "e0301" works as well as "E0301"
+pub fn lookup(code: &str) -> Option<&'static CodeInfo> {
+    ERROR_CODES.iter().find(|info| info.code.0.eq_ignore_ascii_case(code))
+}
+
+pub fn iter_codes() -> impl Iterator<Item=&'static CodeInfo> {
+    ERROR_CODES.iter()
+}
+
+
+// lexer
+pub const E0001: ErrorCode = ErrorCode("E0001");
+pub const E0002: ErrorCode = ErrorCode("E0002");
+pub const E0003: ErrorCode = ErrorCode("E0003");
+pub const E0004: ErrorCode = ErrorCode("E0004");
+pub const E0005: ErrorCode = ErrorCode("E0005");
+pub const E0006: ErrorCode = ErrorCode("E0006");
+pub const E0007: ErrorCode = ErrorCode("E0007");
+pub const E0008: ErrorCode = ErrorCode("E0008");
+
+// parser
+pub const E0101: ErrorCode = ErrorCode("E0101");
+pub const E0102: ErrorCode = ErrorCode("E0102");
+pub const E0103: ErrorCode = ErrorCode("E0103");
+pub const E0104: ErrorCode = ErrorCode("E0104");
+pub const E0105: ErrorCode = ErrorCode("E0105");
+pub const E0106: ErrorCode = ErrorCode("E0106");
+pub const E0107: ErrorCode = ErrorCode("E0107");
+pub const E0108: ErrorCode = ErrorCode("E0108");
+
+// compiler
+pub const E0201: ErrorCode = ErrorCode("E0201");
+pub const E0202: ErrorCode = ErrorCode("E0202");
+pub const E0203: ErrorCode = ErrorCode("E0203");
+pub const E0204: ErrorCode = ErrorCode("E0204");
+pub const E0205: ErrorCode = ErrorCode("E0205");
+pub const E0206: ErrorCode = ErrorCode("E0206");
+pub const E0207: ErrorCode = ErrorCode("E0207");
+pub const E0208: ErrorCode = ErrorCode("E0208");
+pub const E0209: ErrorCode = ErrorCode("E0209");
+pub const E0210: ErrorCode = ErrorCode("E0210");
+pub const E0211: ErrorCode = ErrorCode("E0211");
+pub const E0212: ErrorCode = ErrorCode("E0212");
+pub const W0201: ErrorCode = ErrorCode("W0201");
+pub const W0202: ErrorCode = ErrorCode("W0202");
+pub const W0203: ErrorCode = ErrorCode("W0203");
+pub const W0204: ErrorCode = ErrorCode("W0204");
+pub const W0205: ErrorCode = ErrorCode("W0205");
+
+// runtime
+pub const E0301: ErrorCode = ErrorCode("E0301");
+pub const E0302: ErrorCode = ErrorCode("E0302");
+pub const E0303: ErrorCode = ErrorCode("E0303");
+pub const E0304: ErrorCode = ErrorCode("E0304");
+pub const E0305: ErrorCode = ErrorCode("E0305");
+pub const E0306: ErrorCode = ErrorCode("E0306");
+pub const E0307: ErrorCode = ErrorCode("E0307");
+pub const E0308: ErrorCode = ErrorCode("E0308");
+pub const E0309: ErrorCode = ErrorCode("E0309");
+pub const E0310: ErrorCode = ErrorCode("E0310");
+pub const E0311: ErrorCode = ErrorCode("E0311");
+pub const E0312: ErrorCode = ErrorCode("E0312");
+pub const E0313: ErrorCode = ErrorCode("E0313");
+pub const E0314: ErrorCode = ErrorCode("E0314");
+pub const E0315: ErrorCode = ErrorCode("E0315");
+pub const E0316: ErrorCode = ErrorCode("E0316");
+pub const E0317: ErrorCode = ErrorCode("E0317");
+pub const E0318: ErrorCode = ErrorCode("E0318");
+pub const E0319: ErrorCode = ErrorCode("E0319");
+pub const E0320: ErrorCode = ErrorCode("E0320");
+pub const E0321: ErrorCode = ErrorCode("E0321");
+pub const E0322: ErrorCode = ErrorCode("E0322");
+pub const E0323: ErrorCode = ErrorCode("E0323");
+pub const E0324: ErrorCode = ErrorCode("E0324");
+pub const E0325: ErrorCode = ErrorCode("E0325");
+
+
+static ERROR_CODES: &[CodeInfo] = &[
+    CodeInfo {
+        code: E0001,
+        title: "error reading source text",
+        explanation: "The source text could not be read, e.g. because the file is missing, could not be opened, \
+                      or is not valid UTF-8.",
+    },
+    CodeInfo {
+        code: E0002,
+        title: "unexpected end of file",
+        explanation: "The source text ended in the middle of a token, e.g. a string literal that was never closed.",
+    },
+    CodeInfo {
+        code: E0003,
+        title: "unrecognized token",
+        explanation: "The lexer found some text that doesn't start any token of the language, such as a character \
+                      that isn't used by any operator.",
+    },
+    CodeInfo {
+        code: E0004,
+        title: "invalid token",
+        explanation: "The text looked like a token but was not valid, e.g. a number that is too large for its \
+                      type or a string with an unknown escape sequence.",
+    },
+    CodeInfo {
+        code: E0005,
+        title: "max token length exceeded",
+        explanation: "A single token was longer than the lexer supports (65535 characters). \
+                      Very long strings can be built at runtime by joining shorter ones.",
+    },
+    CodeInfo {
+        code: E0006,
+        title: "max source length exceeded",
+        explanation: "The source text is too long for the positions of its tokens to be recorded. \
+                      Large programs should be split into several modules.",
+    },
+    CodeInfo {
+        code: E0007,
+        title: "block comment was never closed",
+        explanation: "A block comment was opened with \"#{\" but the source text ended before the matching \"}#\". \
+                      Block comments can be nested, so each \"#{\" inside of a comment needs its own \"}#\".",
+    },
+    CodeInfo {
+        code: E0008,
+        title: "interpolated strings are nested too deeply",
+        explanation: "An interpolated string contains an expression with another interpolated string inside of it, \
+                      nested too many levels deep to be read. Nested strings can be assigned to variables first.",
+    },
+    
+    CodeInfo {
+        code: E0101,
+        title: "unexpected end of input",
+        explanation: "The source text ended before a statement or expression was complete, e.g. a block \
+                      that is missing its \"end\".",
+    },
+    CodeInfo {
+        code: E0102,
+        title: "syntax error",
+        explanation: "The tokens don't form a valid statement or expression. The message describes what \
+                      the parser expected to find, and the note after it describes what was being parsed.",
+    },
+    
+    CodeInfo {
+        code: E0103,
+        title: "too many levels of nesting",
+        explanation: "Expressions, blocks or patterns are nested inside of each other more deeply than the parser \
+                      supports. Deeply nested code can be split up by assigning parts of it to variables or functions.",
+    },
+    CodeInfo {
+        code: E0104,
+        title: "statement is too long",
+        explanation: "A single statement spans more of the source text than its position can be recorded for. \
+                      Long statements can be split into several shorter ones.",
+    },
+    CodeInfo {
+        code: E0105,
+        title: "expected an expression",
+        explanation: "An expression was expected, but the parser found a token that can't start one, e.g. an operator \
+                      with a missing operand as in \"1 + * 2\", or a keyword such as \"end\" in the middle of a statement.",
+    },
+    CodeInfo {
+        code: E0106,
+        title: "expected a closing delimiter",
+        explanation: "A list of items that was opened by a bracket such as \"(\", \"[\" or \"{\" was not followed by \
+                      the matching closing bracket. This is often caused by a missing comma between two of the items.",
+    },
+    CodeInfo {
+        code: E0107,
+        title: "delimiter was never closed",
+        explanation: "The source text ended before the closing delimiter of a \"(\", \"[\", \"{\" or \"|\". The error \
+                      points at the delimiter that was opened.",
+    },
+    CodeInfo {
+        code: E0108,
+        title: "block was never closed",
+        explanation: "The source text ended before the \"end\" of a block, such as a function definition or a loop. \
+                      The error points at the keyword that started the block.",
+    },
+    
+    CodeInfo {
+        code: E0201,
+        title: "compiler limit exceeded",
+        explanation: "The program uses more of something than the bytecode format can represent, such as \
+                      local variables in one function, constants in one module, or parameters of a function.",
+    },
+    CodeInfo {
+        code: E0202,
+        title: "can't assign to immutable local variable",
+        explanation: "A local variable declared with \"let\" was assigned to. Use \"var\" to declare a variable \
+                      that can be changed.",
+    },
+    CodeInfo {
+        code: E0203,
+        title: "can't assign to non-local variable",
+        explanation: "A variable from an enclosing function or the global scope was assigned to as if it were \
+                      a local. Assignments to these variables need the \"nonlocal\" keyword.",
+    },
+    CodeInfo {
+        code: E0204,
+        title: "invalid assignment",
+        explanation: "The left side of an assignment is not something that can be assigned to, or an \
+                      update-assignment such as \"+=\" was used where only plain assignment is allowed.",
+    },
+    CodeInfo {
+        code: E0205,
+        title: "invalid unpacking",
+        explanation: "\"...\" was used somewhere that values can't be unpacked, or more than once in the \
+                      same assignment.",
+    },
+    CodeInfo {
+        code: E0206,
+        title: "\"break\" without a target",
+        explanation: "\"break\" was used outside of a loop or block, or with a label that no enclosing loop \
+                      or block has.",
+    },
+    CodeInfo {
+        code: E0207,
+        title: "\"continue\" without a target",
+        explanation: "\"continue\" was used outside of a loop, or with a label that no enclosing loop has.",
+    },
+    CodeInfo {
+        code: E0208,
+        title: "\"break\" with value outside of block",
+        explanation: "Only block expressions produce the value given to \"break\". Loops are statements, \
+                      so breaking out of them can't give a value.",
+    },
+    CodeInfo {
+        code: E0209,
+        title: "constructor returns a value",
+        explanation: "A class constructor always produces the new instance, so \"return\" inside of it \
+                      can't be given a value.",
+    },
+    CodeInfo {
+        code: E0210,
+        title: "\"self\" outside of method",
+        explanation: "\"self\" refers to the receiver of a method, so it can only be used inside of a method \
+                      or a function that is nested in one.",
+    },
+    CodeInfo {
+        code: E0211,
+        title: "invalid use of \"super\"",
+        explanation: "\"super\" can only be used to access a method of the parent class, inside of a method \
+                      of a class that has a parent.",
+    },
+    CodeInfo {
+        code: E0212,
+        title: "invalid export",
+        explanation: "\"export\" can only be used on declarations at the top level of a module.",
+    },
+    CodeInfo {
+        code: W0201,
+        title: "unreachable code",
+        explanation: "The code comes after a \"return\", \"break\" or \"continue\" in the same block, or after \
+                      branches that all do so, so it can never run. Warnings don't stop the program from running.",
+    },
+    CodeInfo {
+        code: W0202,
+        title: "condition is always true or false",
+        explanation: "The condition of an \"if\" or \"while\" is a literal value, so the same branch is always taken. \
+                      \"while true\" is not reported, since it is the usual way to write a loop that ends with \"break\".",
+    },
+    CodeInfo {
+        code: W0203,
+        title: "local variable shadows another variable",
+        explanation: "A local variable has the same name as a variable from an enclosing scope, which can't be used \
+                      until the scope of the new variable ends. Renaming one of them avoids using the wrong one by mistake.",
+    },
+    CodeInfo {
+        code: W0204,
+        title: "unused local variable",
+        explanation: "A local variable is declared but its value is never used. Names that start with an underscore, \
+                      such as \"_unused\", are not reported.",
+    },
+    CodeInfo {
+        code: W0205,
+        title: "unused import",
+        explanation: "A module is imported but none of the names it could have bound are used. Imported names are only \
+                      known at runtime, so an import is reported when the module never reads a global variable that it \
+                      doesn't declare itself.",
+    },
+    
+    CodeInfo {
+        code: E0301,
+        title: "invalid unary operand",
+        explanation: "A unary operator was applied to a value that doesn't support it, such as negating a string.",
+    },
+    CodeInfo {
+        code: E0302,
+        title: "invalid binary operands",
+        explanation: "A binary operator was applied to values that don't support it, such as adding an int and nil.",
+    },
+    CodeInfo {
+        code: E0303,
+        title: "arithmetic overflow",
+        explanation: "The result of an integer operation doesn't fit in an int. \
+                      Integers are the size of a pointer (usually 64 bits) unless the interpreter is built \
+                      with the \"bigint\" feature.",
+    },
+    CodeInfo {
+        code: E0304,
+        title: "divide by zero",
+        explanation: "An integer was divided by zero, with \"/\", \"//\", or \"%\".",
+    },
+    CodeInfo {
+        code: E0305,
+        title: "negative shift count",
+        explanation: "A value was shifted with \"<<\" or \">>\" by a negative number of bits.",
+    },
+    CodeInfo {
+        code: E0306,
+        title: "undefined variable",
+        explanation: "A global variable was used before it was declared, or it was never declared at all. \
+                      If a similar name exists, it is suggested in the message.",
+    },
+    CodeInfo {
+        code: E0307,
+        title: "attribute not found",
+        explanation: "A value has no attribute with the given name. \
+                      For class instances, check that the field was assigned in the constructor.",
+    },
+    CodeInfo {
+        code: E0308,
+        title: "can't assign to immutable variable",
+        explanation: "A variable declared with \"let\" was assigned to. Use \"var\" to declare a variable \
+                      that can be changed.",
+    },
+    CodeInfo {
+        code: E0309,
+        title: "unhashable value",
+        explanation: "A value that can change (e.g. a list) was used as a key in a dict or a member of a set.",
+    },
+    CodeInfo {
+        code: E0310,
+        title: "index out of bounds",
+        explanation: "An index was outside of the range of a sequence. Negative indexes count from the end.",
+    },
+    CodeInfo {
+        code: E0311,
+        title: "key not found",
+        explanation: "A dict or table was indexed with a key that isn't present.",
+    },
+    CodeInfo {
+        code: E0312,
+        title: "missing arguments",
+        explanation: "A function was called with fewer arguments than it requires.",
+    },
+    CodeInfo {
+        code: E0313,
+        title: "too many arguments",
+        explanation: "A function was called with more arguments than it takes. \
+                      A variadic parameter (e.g. \"args...\") can be used to accept any number of arguments.",
+    },
+    CodeInfo {
+        code: E0314,
+        title: "method not supported",
+        explanation: "A value doesn't support the operation that was used with it, such as iterating over an int.",
+    },
+    CodeInfo {
+        code: E0315,
+        title: "assertion failed",
+        explanation: "The expression in an \"assert\" statement was false.",
+    },
+    CodeInfo {
+        code: E0316,
+        title: "invalid value",
+        explanation: "A builtin function was given an argument of the right type but an unusable value, \
+                      such as parsing a string that isn't a number.",
+    },
+    CodeInfo {
+        code: E0317,
+        title: "unpack error",
+        explanation: "A value was unpacked into a different number of variables than it has items.",
+    },
+    CodeInfo {
+        code: E0318,
+        title: "import error",
+        explanation: "A module could not be found in the search path, could not be loaded, \
+                      or is imported by a module that it imports itself.",
+    },
+    CodeInfo {
+        code: E0319,
+        title: "stack overflow",
+        explanation: "The call depth limit was reached, usually because of recursion that never ends.",
+    },
+    CodeInfo {
+        code: E0320,
+        title: "budget exceeded",
+        explanation: "The program ran for more instructions than the budget it was given by the embedding application.",
+    },
+    CodeInfo {
+        code: E0321,
+        title: "memory limit exceeded",
+        explanation: "The program allocated more memory than the limit it was given, even after garbage collection.",
+    },
+    CodeInfo {
+        code: E0322,
+        title: "user error",
+        explanation: "An error that was raised by the program itself.",
+    },
+    CodeInfo {
+        code: E0323,
+        title: "unspecified error",
+        explanation: "An error that doesn't fit any of the other kinds.",
+    },
+    CodeInfo {
+        code: E0324,
+        title: "interrupted",
+        explanation: "The program was interrupted by the user (e.g. with Ctrl-C) while it was running.",
+    },
+    CodeInfo {
+        code: E0325,
+        title: "invalid bytecode",
+        explanation: "A compiled program tried to do something that its bytecode doesn't allow, such as using \
+                      more values than are on the stack. Programs from the compiler never do this, so the \
+                      compiled file was probably corrupted or modified.",
+    },
+];
@@ -5,10 +5,11 @@ use string_interner::Symbol as _;
 
 use crate::language::FloatType;
 use crate::codegen::OpCode;
+use crate::codegen::opcodes::{CacheIndex, REG_CONST_SRC1, REG_CONST_SRC2};
 use crate::codegen::chunk::{UnloadedProgram, Chunk};
 use crate::codegen::consts::{Constant, ConstID};
 use crate::codegen::funproto::{UnloadedFunction, FunctionID};
-use crate::debug::symbol::{DebugSymbol, DebugSymbolTable, ResolvedSymbol, ResolvedSymbolTable, ChunkSymbols};
+use crate::debug::symbol::{DebugSymbol, DebugSymbolTable, ResolvedSymbol, ResolvedSymbolTable, ChunkSymbols, byte_index};
 use crate::debug::symbol::errors::SymbolResolutionError;
 
 
@@ -119,13 +120,21 @@ impl<'c, 's> Disassembler<'c, 's> {
         } else { None }
     }
 
-    fn decode_instr(&self, fmt: &mut impl Write, offset: &usize, instr: &[u8], symbol: Option<Symbol>) -> Result<usize, fmt::Error> {        let mut line = String::new();
+    fn decode_instr(&self, fmt: &mut impl Write, offset: &usize, instr: &[u8], symbol: Option<Symbol>) -> Result<usize, fmt::Error> {
+        let mut line = String::new();
         
         write!(line, "{:04X} ", offset)?;
         
         
         let opcode = OpCode::from_byte(instr[0]);
         match opcode {
+            // don't try to decode the operand if the chunk ends in the middle of the instruction
+            Some(opcode) if instr.len() < opcode.instr_len() => {
+                write!(line, "{:16} (truncated)", opcode)?;
+                writeln!(fmt, "{}", line)?;
+                return Ok(offset + instr.len());
+            }
+            
             Some(opcode) => match opcode {
                 
                 OpCode::Drop | OpCode::DropLocals => {
@@ -184,11 +193,39 @@ impl<'c, 's> Disassembler<'c, 's> {
                     write!(line, "{:16} {: >4}", opcode, index)?;
                 }
                 
-                OpCode::Tuple => {
+                OpCode::Tuple | OpCode::BuildList | OpCode::BuildDict | OpCode::Concat => {
                     let len = instr[1];
                     write!(line, "{:16} {: >4}", opcode, len)?;
                 }
                 
+                OpCode::GetAttr | OpCode::LoadGlobal => {
+                    let index = CacheIndex::from_le_bytes(instr[1..=2].try_into().unwrap());
+                    write!(line, "{:16} {: >4}", opcode, index)?;
+                }
+                
+                OpCode::AddLocalConst | OpCode::SubLocalConst => {
+                    let index = instr[1];
+                    let cid = ConstID::from(instr[2]);
+                    write!(line, "{:16} {: >4} {: >4}    ", opcode, index, cid)?;
+                    self.write_const(&mut line, self.program.get_const(cid))?;
+                }
+                
+                // register operands are written as L<local index> or K<constant index>
+                OpCode::MoveReg => {
+                    write!(line, "{:16} L{} <- ", opcode, instr[2])?;
+                    self.write_registers(&mut line, instr[1], &[(REG_CONST_SRC1, instr[3])])?;
+                }
+                
+                OpCode::AddReg | OpCode::SubReg | OpCode::MulReg | OpCode::DivReg | OpCode::ModReg => {
+                    write!(line, "{:16} L{} <- ", opcode, instr[2])?;
+                    self.write_registers(&mut line, instr[1], &[(REG_CONST_SRC1, instr[3]), (REG_CONST_SRC2, instr[4])])?;
+                }
+                
+                OpCode::Class => {
+                    let count = instr[1];
+                    write!(line, "{:16} {: >4}", opcode, count)?;
+                }
+                
                 OpCode::UInt8 => {
                     let value = Constant::Integer(instr[1].into());
                     write!(line, "{:16}         ", opcode)?;
@@ -211,7 +248,16 @@ impl<'c, 's> Disassembler<'c, 's> {
                 OpCode::JumpIfFalse    |
                 OpCode::JumpIfTrue     |
                 OpCode::PopJumpIfFalse |
-                OpCode::PopJumpIfTrue  => {
+                OpCode::PopJumpIfTrue  |
+                OpCode::JumpIfNil      |
+                OpCode::JumpIfNotNil   |
+                OpCode::JumpIfEQ       |
+                OpCode::JumpIfNotEQ    |
+                OpCode::JumpIfLT       |
+                OpCode::JumpIfNotLT    |
+                OpCode::JumpIfLE       |
+                OpCode::JumpIfNotLE    |
+                OpCode::PushHandler    => {
                     let jmp = i16::from_le_bytes(instr[1..=2].try_into().unwrap());
                     let dest = i128::from(jmp) + i128::try_from(offset + opcode.instr_len()).expect("offset too large");
                     let relative = i64::from(jmp) + i64::try_from(opcode.instr_len()).unwrap();
@@ -222,7 +268,10 @@ impl<'c, 's> Disassembler<'c, 's> {
                 OpCode::LongJumpIfFalse    |
                 OpCode::LongJumpIfTrue     |
                 OpCode::PopLongJumpIfFalse |
-                OpCode::PopLongJumpIfTrue  => {
+                OpCode::PopLongJumpIfTrue  |
+                OpCode::LongJumpIfNil      |
+                OpCode::LongJumpIfNotNil   |
+                OpCode::LongPushHandler    => {
                     let jmp = i32::from_le_bytes(instr[1..=4].try_into().unwrap());
                     let dest = i128::from(jmp) + i128::try_from(offset + opcode.instr_len()).expect("offset too large");
                     let relative = i64::from(jmp) + i64::try_from(opcode.instr_len()).unwrap();
@@ -237,7 +286,7 @@ impl<'c, 's> Disassembler<'c, 's> {
         
         if let Some(symbol) = symbol {
             if line.len() < PAD_WIDTH {
-                line.extend(iter::repeat(' ').take(PAD_WIDTH - line.len()))
+                line.extend(std::iter::repeat_n(' ', PAD_WIDTH - line.len()))
             }
             match symbol {
                 Symbol::Unresolved(symbol) => self.write_unresolved_symbol(&mut line, symbol)?,
@@ -264,13 +313,13 @@ impl<'c, 's> Disassembler<'c, 's> {
                 write!(fmt, "{: >4}| ", symbol.lineno())?;
                 
                 let line = symbol.iter_whole_lines().next().unwrap_or("").trim_end();
+                let start = byte_index(line, symbol.start());
                 if symbol.is_multiline() {
-                    let (before, sym_text) = line.split_at(symbol.start());
+                    let (before, sym_text) = line.split_at(start);
                     write!(fmt, "{}`{}...`", before, sym_text)
                 } else {
-                    let (before, rest) = line.split_at(symbol.start());
-                    let (sym_text, after) = rest.split_at(symbol.end() - symbol.start());
-                    write!(fmt, "{}`{}`{}", before, sym_text, after)
+                    let end = byte_index(line, symbol.end());
+                    write!(fmt, "{}`{}`{}", &line[..start], &line[start..end], &line[end..])
                 }
             },
             
@@ -280,16 +329,52 @@ impl<'c, 's> Disassembler<'c, 's> {
         }
     }
     
+    // writes the source operands of a register instruction, followed by the values of any constants
+    fn write_registers(&self, fmt: &mut impl fmt::Write, mode: u8, operands: &[(u8, u8)]) -> fmt::Result {
+        let mut consts = Vec::new();
+        for (idx, (const_bit, operand)) in operands.iter().enumerate() {
+            if idx > 0 {
+                write!(fmt, ", ")?;
+            }
+            if mode & const_bit != 0 {
+                write!(fmt, "K{}", operand)?;
+                consts.push(ConstID::from(*operand));
+            } else {
+                write!(fmt, "L{}", operand)?;
+            }
+        }
+        
+        for cid in consts.into_iter() {
+            write!(fmt, "    ")?;
+            self.write_const(fmt, self.program.get_const(cid))?;
+        }
+        Ok(())
+    }
+    
     fn write_const(&self, fmt: &mut impl fmt::Write, value: &Constant) -> fmt::Result {
-        if let Constant::String(index) = value {
-            let string = self.program.get_string(*index);
-            if string.len() > 16 {
-                return write!(fmt, "\"{}...\"", &string[..13]);
+        match value {
+            Constant::String(index) => {
+                let string = self.program.get_string(*index);
+                self.write_str_abbrev(fmt, string)
+            }
+            
+            Constant::BigInt(index) => write!(fmt, "{}", self.program.get_string(*index)),
+            
+            Constant::Error { error, message } => {
+                write!(fmt, "{:?} ", error)?;
+                self.write_str_abbrev(fmt, self.program.get_string(*message))
             }
-            return write!(fmt, "\"{}\"", string);
+            
+            _ => write!(fmt, "{}", value),
         }
+    }
         
-        write!(fmt, "{}", value)
+    fn write_str_abbrev(&self, fmt: &mut impl fmt::Write, string: &str) -> fmt::Result {
+        if string.chars().count() > 16 {
+            let abbrev = string.chars().take(13).collect::<String>();
+            return write!(fmt, "\"{}...\"", abbrev);
+        }
+        write!(fmt, "\"{}\"", string)
     }
     
     fn write_function(&self, fmt: &mut impl fmt::Write, function: &UnloadedFunction) -> fmt::Result {
@@ -321,6 +406,7 @@ impl fmt::Display for Constant {
             Self::Integer(value) => write!(fmt, "{}", value),
             Self::Float(bytes) => write!(fmt, "{:.6}", FloatType::from_le_bytes(*bytes)),
             Self::String(symbol) => write!(fmt, "${}", symbol.to_usize() + 1),
+            Self::BigInt(symbol) => write!(fmt, "${}", symbol.to_usize() + 1),
             Self::Error { error, .. } => write!(fmt, "{:?}", error),
         }
     }
@@ -55,7 +55,7 @@ impl DebugSymbol {
 pub struct ResolvedSymbol {
     lines: Vec<Rc<String>>,
     lineno: usize,  // line number at the start of the symbol
-    start: usize,   // start,end indices into self.text
+    start: usize,   // start,end indices into self.text, in chars
     end: usize,
 }
 
@@ -71,11 +71,19 @@ impl ResolvedSymbol {
     pub fn start(&self) -> usize { self.start }
     pub fn end(&self) -> usize { self.end }
     
+    /// The line and column where the symbol starts, both counting from 1
+    pub fn location(&self) -> (usize, usize) { (self.lineno, self.start_col() + 1) }
+    
+    /// The line and column of the last char in the symbol, both counting from 1
+    pub fn end_location(&self) -> (usize, usize) {
+        (self.lineno + self.lines.len() - 1, usize::max(self.end_col(), 1))
+    }
+    
     pub fn start_col(&self) -> usize { self.start }
     pub fn end_col(&self) -> usize {
         let offset = self.lines.iter()
             .take(self.lines.len() - 1)
-            .map(|line| line.len())
+            .map(|line| line.chars().count())
             .reduce(|acc, n| acc + n)
             .unwrap_or(0);
         
@@ -89,18 +97,12 @@ impl ResolvedSymbol {
     // write just the symbol text itself
     pub fn iter_lines(&self) -> impl Iterator<Item=&str> { 
         self.lines.iter().scan(0, |cur_line_start, line| {
-            let cur_line_end = *cur_line_start + line.len();
+            let cur_line_end = *cur_line_start + line.chars().count();
             
-            let result;
-            if (*cur_line_start <= self.start) && (self.start < cur_line_end) {
-                let start = self.start - *cur_line_start;
-                result = &line[start..];
-            } else if (*cur_line_start <= self.end) && (self.end < cur_line_end) {
-                let end = self.end - *cur_line_start;
-                result = &line[..end];
-            } else {
-                result = line;
-            };
+            // the symbol may both start and end on the same line
+            let start = self.start.clamp(*cur_line_start, cur_line_end) - *cur_line_start;
+            let end = self.end.clamp(*cur_line_start, cur_line_end) - *cur_line_start;
+            let result = &line[byte_index(line, start)..byte_index(line, end)];
             
             *cur_line_start = cur_line_end;
             Some(result)
@@ -108,3 +110,9 @@ impl ResolvedSymbol {
     }
 
 }
+
+// convert an index in chars into a byte index
+pub(crate) fn byte_index(line: &str, char_index: usize) -> usize {
+    line.char_indices().nth(char_index)
+        .map_or(line.len(), |(index, _)| index)
+}
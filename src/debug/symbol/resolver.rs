@@ -8,12 +8,16 @@ use std::rc::Rc;
 use std::collections::{BinaryHeap, HashMap};
 
 use crate::source::{ModuleSource, SourceText};
+use crate::utils::ReadChars;
 use crate::debug::symbol::{DebugSymbol, ResolvedSymbol, TokenIndex};
 use crate::debug::symbol::errors::{SymbolResolutionError, ErrorKind};
 
 
 pub trait DebugSymbolResolver {
     fn resolve_symbols<'s, S>(&self, symbols: S) -> io::Result<ResolvedSymbolTable<'s>> where S: Iterator<Item=&'s DebugSymbol>;
+    
+    /// The name used to refer to the source when reporting locations, e.g. the path of a file
+    fn source_name(&self) -> Option<String> { None }
 }
 
 
@@ -21,7 +25,15 @@ impl DebugSymbolResolver for ModuleSource {
     fn resolve_symbols<'s, S>(&self, symbols: S) -> io::Result<ResolvedSymbolTable<'s>> where S: Iterator<Item=&'s DebugSymbol> {
         match self.read_text()? {
             SourceText::String(text) => Ok(resolve_debug_symbols(text.chars().map(Ok), symbols)),
-            SourceText::File(text) => Ok(resolve_debug_symbols(text, symbols))
+            SourceText::File(file) => Ok(resolve_debug_symbols(ReadChars::new(io::BufReader::new(file)), symbols)),
+            SourceText::Reader(reader) => Ok(resolve_debug_symbols(ReadChars::new(io::BufReader::new(reader)), symbols)),
+        }
+    }
+    
+    fn source_name(&self) -> Option<String> {
+        match self {
+            Self::File(path) => Some(path.display().to_string()),
+            Self::String(..) => None,
         }
     }
 }
@@ -98,6 +110,7 @@ fn resolve_debug_symbols<'s>(source: impl Iterator<Item=io::Result<char>>, symbo
     
     let mut lineno = 1;  // count lines
     let mut current_line = String::new();
+    let mut line_len = 0;  // length of the current line in chars, so that indices are consistent with DebugSymbol
     for (char_result, index) in source.zip(0..) {
         // check for io::Errors 
         let c = match char_result {
@@ -131,6 +144,7 @@ fn resolve_debug_symbols<'s>(source: impl Iterator<Item=io::Result<char>>, symbo
         
         // add the char to the current line
         current_line.push(c);
+        line_len += 1;
         
         // if we are at the start of a new symbol, open it
         while matches!(next_symbols.peek(), Some(&cmp::Reverse(IndexSort(sym,..))) if index == sym.start()) {
@@ -139,7 +153,7 @@ fn resolve_debug_symbols<'s>(source: impl Iterator<Item=io::Result<char>>, symbo
             active_symbols.entry(symbol).or_insert_with(|| {
                 open_symbols.push(cmp::Reverse(IndexSort(symbol, SortIndex::End)));
                 
-                let start_index = current_line.len() - 1;
+                let start_index = line_len - 1;
                 (Vec::new(), lineno, start_index)
             });
         }
@@ -152,11 +166,11 @@ fn resolve_debug_symbols<'s>(source: impl Iterator<Item=io::Result<char>>, symbo
                     
                     // calculate end_index
                     let total_len = lines.iter()
-                        .map(|line| line.len())
+                        .map(|line| line.chars().count())
                         .reduce(|acc, n| acc+n)
                         .unwrap_or(0);
                     
-                    let end_index = total_len + current_line.len() - 1;
+                    let end_index = total_len + line_len - 1;
                     
                     (lines, lineno, start_index, end_index)
                 });
@@ -183,6 +197,7 @@ fn resolve_debug_symbols<'s>(source: impl Iterator<Item=io::Result<char>>, symbo
             
             // prepare buffer for next line
             current_line.clear();
+            line_len = 0;
         }
         
         // println!("{}: {}", lineno, current_line);
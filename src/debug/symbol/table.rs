@@ -6,9 +6,10 @@ use crate::debug::symbol::DebugSymbol;
 pub type ChunkSymbols = HashMap<Chunk, DebugSymbolTable>;
 
 /// Maps bytecode offsets to DebugSymbols
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DebugSymbolTable {
     entries: Vec<SymbolTableEntry>,
+    name_hints: Vec<(usize, String)>,
 }
 
 impl Default for DebugSymbolTable {
@@ -17,7 +18,7 @@ impl Default for DebugSymbolTable {
 
 impl DebugSymbolTable {
     pub fn new() -> Self {
-        Self { entries: Vec::new() }
+        Self { entries: Vec::new(), name_hints: Vec::new() }
     }
     
     pub fn insert(&mut self, offset: usize, symbol: DebugSymbol) {
@@ -30,6 +31,24 @@ impl DebugSymbolTable {
         self.entries.push(entry)
     }
     
+    /// Records a local variable that a global variable lookup at the given offset may have been meant to refer to
+    pub fn insert_name_hint(&mut self, offset: usize, name: &str) {
+        if matches!(self.name_hints.last(), Some((last_offset, _)) if offset <= *last_offset) {
+            panic!("name hint inserted out of order");
+        }
+        
+        self.name_hints.push((offset, name.to_string()))
+    }
+    
+    /// Removes the symbols for all offsets at or after the given offset
+    pub fn truncate(&mut self, offset: usize) {
+        let len = self.entries.partition_point(|entry| entry.offset() < offset);
+        self.entries.truncate(len);
+        
+        let len = self.name_hints.partition_point(|(hint_offset, _)| *hint_offset < offset);
+        self.name_hints.truncate(len)
+    }
+    
     pub fn lookup(&self, offset: usize) -> Option<&DebugSymbol> {
         if let Ok(index) = self.entries.binary_search_by_key(&offset, SymbolTableEntry::offset) {
             Some(&self.entries[index].1)
@@ -38,6 +57,11 @@ impl DebugSymbolTable {
         }
     }
     
+    pub fn lookup_name_hint(&self, offset: usize) -> Option<&str> {
+        self.name_hints.binary_search_by_key(&offset, |(hint_offset, _)| *hint_offset)
+            .ok().map(|index| self.name_hints[index].1.as_str())
+    }
+    
     pub fn iter(&self) -> impl Iterator<Item=(usize, &DebugSymbol)> + '_ {
         self.entries.iter().map(|entry| {
             let SymbolTableEntry(offset, symbol) = entry;
@@ -48,6 +72,10 @@ impl DebugSymbolTable {
     pub fn symbols(&self) -> impl Iterator<Item=&DebugSymbol> {
         self.entries.iter().map(|entry| &entry.1)
     }
+    
+    pub fn iter_name_hints(&self) -> impl Iterator<Item=(usize, &str)> + '_ {
+        self.name_hints.iter().map(|(offset, name)| (*offset, name.as_str()))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -55,7 +83,7 @@ struct SymbolTableEntry(usize, DebugSymbol);
 
 impl PartialOrd for SymbolTableEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        usize::partial_cmp(&self.0, &other.0)
+        Some(self.cmp(other))
     }
 }
 
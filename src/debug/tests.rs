@@ -1,7 +1,10 @@
 #![cfg(test)]
 
-use crate::source::{ModuleSource};
+use crate::source::{ModuleSource, SourceText};
+use crate::codegen::{OpCode, Chunk};
+use crate::codegen::chunk::ChunkBuilder;
 use super::symbol::{DebugSymbol, DebugSymbolResolver};
+use super::dasm::Disassembler;
 
 #[test]
 fn debug_symbols_test_symbol_resolution() {
@@ -11,7 +14,7 @@ fn debug_symbols_test_symbol_resolution() {
     
     let module = ModuleSource::String(text.to_string());
     
-    let symbols = vec![
+    let symbols = [
         DebugSymbol::try_from((0, 7)).unwrap(),
         DebugSymbol::try_from((0, 20)).unwrap(),
         DebugSymbol::try_from((13, 24)).unwrap(),
@@ -26,3 +29,189 @@ fn debug_symbols_test_symbol_resolution() {
         }
     }
 }
+
+#[test]
+fn debug_symbols_test_resolution_counts_chars() {
+    let text = "let naïve = 変数\nlet x = naïve";
+    
+    let module = ModuleSource::String(text.to_string());
+    
+    // symbol indices count chars, not bytes
+    let symbols = [
+        DebugSymbol::try_from((12, 14)).unwrap(),
+        DebugSymbol::try_from((23, 28)).unwrap(),
+    ];
+    
+    let symbol_table = module.resolve_symbols(symbols.iter()).unwrap();
+    
+    let resolved = symbol_table.lookup(&symbols[0]).unwrap().unwrap();
+    assert_eq!(resolved.lineno(), 1);
+    assert_eq!(resolved.start_col(), 12);
+    assert_eq!(resolved.end_col(), 14);
+    assert_eq!(resolved.location(), (1, 13));
+    assert_eq!(resolved.end_location(), (1, 14));
+    assert_eq!(resolved.iter_lines().collect::<String>(), "変数");
+    
+    let resolved = symbol_table.lookup(&symbols[1]).unwrap().unwrap();
+    assert_eq!(resolved.lineno(), 2);
+    assert_eq!(resolved.start_col(), 8);
+    assert_eq!(resolved.end_col(), 13);
+    assert_eq!(resolved.location(), (2, 9));
+    assert_eq!(resolved.iter_lines().collect::<String>(), "naïve");
+}
+
+#[test]
+fn disassembler_shows_operands_and_source() {
+    let text = "let greeting = \"hello there, general kenobi\"\nfun twice(x) x * 2 end\ntwice(3)";
+    
+    let module = ModuleSource::String(text.to_string());
+    let build = crate::build_module(&module).unwrap();
+    
+    let symbols = build.symbols.values().flat_map(|table| table.symbols());
+    let symbol_table = module.resolve_symbols(symbols).unwrap();
+    
+    let dasm = Disassembler::new(&build.program)
+        .with_symbols(&build.symbols)
+        .with_symbol_table(&symbol_table)
+        .to_string();
+    
+    println!("{}", dasm);
+    
+    assert!(dasm.contains("main:"));
+    assert!(dasm.contains("chunk 0 (twice):"));
+    assert!(dasm.contains("0000 LD_CONST"));
+    assert!(dasm.contains("\"hello there, ...\""));
+    assert!(dasm.contains("'fun twice()'"));
+    assert!(dasm.contains("3| `twice(3)`"));
+}
+
+#[test]
+fn disassembler_shows_source_with_non_ascii_text() {
+    let text = "let naïve = \"日本語\"\nprint(naïve, \"ü\")";
+    
+    let module = ModuleSource::String(text.to_string());
+    let build = crate::build_module(&module).unwrap();
+    
+    let symbols = build.symbols.values().flat_map(|table| table.symbols());
+    let symbol_table = module.resolve_symbols(symbols).unwrap();
+    
+    let dasm = Disassembler::new(&build.program)
+        .with_symbols(&build.symbols)
+        .with_symbol_table(&symbol_table)
+        .to_string();
+    
+    println!("{}", dasm);
+    
+    assert!(dasm.contains("1| `let naïve = \"日本語\"`"));
+    assert!(dasm.contains("2| print(`naïve`, \"ü\")"));
+    assert!(dasm.contains("2| print(naïve, `\"ü\"`)"));
+}
+
+#[test]
+fn disassembler_handles_truncated_instruction() {
+    let mut builder = ChunkBuilder::new();
+    let chunk = builder.chunk_mut(Chunk::Main);
+    chunk.push_byte(OpCode::True);
+    chunk.push_byte(OpCode::Jump);
+    let program = builder.build();
+    
+    let dasm = Disassembler::new(&program).to_string();
+    println!("{}", dasm);
+    
+    assert!(dasm.contains("0000 LD_TRUE"));
+    assert!(dasm.contains("0001 JUMP             (truncated)"));
+}
+
+#[test]
+fn every_error_kind_has_a_documented_code() {
+    use std::collections::HashSet;
+    use crate::lexer::ErrorKind as LexerErrorKind;
+    use crate::runtime::errors::ErrorKind as RuntimeErrorKind;
+    use super::codes;
+    
+    let mut seen = HashSet::new();
+    for info in codes::iter_codes() {
+        assert!(seen.insert(info.code), "duplicate code {}", info.code);
+        assert!(!info.explanation.is_empty());
+    }
+    
+    let lexer_kinds = [
+        LexerErrorKind::IOError, LexerErrorKind::UnexpectedEOF, LexerErrorKind::NoMatchingRule,
+        LexerErrorKind::CouldNotReadToken, LexerErrorKind::MaxTokenLengthExceeded, LexerErrorKind::SourceTooLong,
+        LexerErrorKind::UnclosedComment,
+    ];
+    for kind in lexer_kinds.iter() {
+        assert!(codes::lookup(kind.code().as_str()).is_some(), "{:?}", kind);
+    }
+    
+    let runtime_kinds = (0..=u8::MAX).filter_map(|byte| RuntimeErrorKind::try_from(byte).ok());
+    for kind in runtime_kinds {
+        assert!(codes::lookup(kind.code().as_str()).is_some(), "{:?}", kind);
+    }
+    
+    assert_eq!(codes::lookup("e0306").map(|info| info.code), Some(codes::E0306));
+    assert!(codes::lookup("E9999").is_none());
+}
+
+#[test]
+fn build_errors_have_a_code_for_each_kind() {
+    use crate::BuildErrors;
+    use super::{SourceError, ErrorCode};
+    use super::codes;
+    
+    fn error_code(text: String) -> ErrorCode {
+        // deeply nested source needs more stack than a test thread has to parse
+        let build = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(move || match crate::build_source(SourceText::from(text)) {
+                Err(BuildErrors::Syntax(errors)) => errors[0].error_code(),
+                Err(BuildErrors::Compile(errors)) => errors[0].error_code(),
+                _ => None,
+            })
+            .unwrap().join().unwrap();
+        
+        build.expect("build should fail with a code")
+    }
+    
+    let defaults = (0..300).map(|idx| format!("a{} = 1", idx)).collect::<Vec<_>>().join(", ");
+    let cases = [
+        ("let 1 = 2".to_string(), codes::E0102),
+        (format!("x = {}1{}", "(".repeat(200), ")".repeat(200)), codes::E0103),
+        (format!("while false do {} end", "1;".repeat(40000)), codes::E0104),
+        ("x = 1 +* 2".to_string(), codes::E0105),
+        ("f(1 2)".to_string(), codes::E0106),
+        ("let x = (1".to_string(), codes::E0107),
+        ("fun f() 1".to_string(), codes::E0108),
+        (format!("fun f({}) end", defaults), codes::E0201),
+        ("fun f() let x = 1; x = 2 end".to_string(), codes::E0202),
+        ("fun f() x = 2 end".to_string(), codes::E0203),
+        ("var x += 1".to_string(), codes::E0204),
+        ("let a, ..., ... = (1, 2)".to_string(), codes::E0205),
+        ("fun f() break end".to_string(), codes::E0206),
+        ("fun f() continue end".to_string(), codes::E0207),
+        ("while true do break 1 end".to_string(), codes::E0208),
+        ("class A fun new() return 1 end end".to_string(), codes::E0209),
+        ("self".to_string(), codes::E0210),
+        ("class A fun f() super.f() end end".to_string(), codes::E0211),
+        ("begin export let x = 1 end".to_string(), codes::E0212),
+    ];
+    
+    for (text, code) in cases {
+        let summary = text.chars().take(40).collect::<String>();
+        assert_eq!(error_code(text), code, "{}", summary);
+        assert!(codes::lookup(code.as_str()).is_some(), "{}", code);
+    }
+}
+
+#[test]
+fn debug_symbols_test_multiline_location() {
+    let text = "let x = (1 +\n  2)\nx";
+    let module = ModuleSource::String(text.to_string());
+    
+    let symbol = DebugSymbol::try_from((8, 16)).unwrap();
+    let symbol_table = module.resolve_symbols(std::iter::once(&symbol)).unwrap();
+    
+    let resolved = symbol_table.lookup(&symbol).unwrap().unwrap();
+    assert_eq!(resolved.location(), (1, 9));
+    assert_eq!(resolved.end_location(), (2, 3));
+}
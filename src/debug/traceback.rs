@@ -1,7 +1,10 @@
 use core::fmt;
+use core::iter;
 use crate::source::ModuleSource;
 use crate::runtime::gc::{Gc, GcTrace};
 use crate::runtime::module::{Module, Chunk};
+use crate::runtime::strings::{StringValue, static_symbol};
+use crate::debug::symbol::{DebugSymbol, DebugSymbolResolver};
 
 
 /// Traceback information
@@ -23,6 +26,64 @@ unsafe impl GcTrace for TraceSite {
     }
 }
 
+impl TraceSite {
+    /// Returns the debug symbol for this site, if the module was loaded with debug symbols.
+    pub fn debug_symbol(&self) -> Option<&DebugSymbol> {
+        match self {
+            Self::Chunk { offset, module, chunk_id } => 
+                module.data().debug_symbols(chunk_id)?.lookup(*offset),
+            
+            Self::Native => None,
+        }
+    }
+    
+    /// The path of the source file, if the site is in a module that was loaded from a file.
+    pub fn file_name(&self) -> Option<String> {
+        match self {
+            Self::Chunk { module, .. } => match module.source() {
+                Some(ModuleSource::File(path)) => Some(path.display().to_string()),
+                _ => None,
+            },
+            
+            Self::Native => None,
+        }
+    }
+    
+    /// Resolves the line number of this site. This requires reading the module source.
+    pub fn lineno(&self) -> Option<usize> {
+        self.location().map(|(lineno, _)| lineno)
+    }
+    
+    /// Resolves the line and column of this site. This requires reading the module source.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        let (module, symbol) = match self {
+            Self::Chunk { module, .. } => (module, self.debug_symbol()?),
+            Self::Native => return None,
+        };
+        
+        let symbol_table = module.source()?.resolve_symbols(iter::once(symbol)).ok()?;
+        let resolved = symbol_table.lookup(symbol)?.ok()?;
+        Some(resolved.location())
+    }
+    
+    /// The name of the function or module that this site is in.
+    pub fn chunk_name(&self) -> StringValue {
+        match self {
+            Self::Chunk { chunk_id: Chunk::Main, .. } => StringValue::from(static_symbol!("<module>")),
+            
+            Self::Chunk { chunk_id: Chunk::Function(fun_id), module, .. } => {
+                let function = module.data().get_function(*fun_id);
+                match function.signature().name() {
+                    Some(name) => StringValue::from(name),
+                    None => StringValue::from(static_symbol!("<anonymous>")),
+                }
+            },
+            
+            Self::Native => StringValue::from(static_symbol!("<native>")),
+        }
+    }
+}
+
 
 pub struct FrameSummary<'a> {
     trace: &'a TraceSite,
@@ -33,9 +94,11 @@ impl fmt::Display for FrameSummary<'_> {
         match self.trace {
             TraceSite::Chunk { offset, module, chunk_id } => {
                 let module_desc = module_desc(module);
-                let loc_desc = format!("<@{:#X}>", offset);
                 let chunk_desc = chunk_desc(module, chunk_id);
-                write!(fmt, "{}, {} in {}", module_desc, loc_desc, chunk_desc)
+                match self.trace.location() {
+                    Some((lineno, colno)) => write!(fmt, "{}:{}:{} in {}", module_desc, lineno, colno, chunk_desc),
+                    None => write!(fmt, "{}, <@{:#X}> in {}", module_desc, offset, chunk_desc),
+                }
             },
             
             TraceSite::Native => {
@@ -47,7 +110,7 @@ impl fmt::Display for FrameSummary<'_> {
 
 fn module_desc(module: &Module) -> String {
     if let Some(ModuleSource::File(path)) = module.source() {
-        format!("File \"{}\"", path.display())
+        path.display().to_string()
     } else {
         "<anonymous module>".to_string()
     }
@@ -82,10 +145,34 @@ impl fmt::Display for Traceback<'_> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.write_str("Stack trace (most recent call last):\n")?;
         
+        // runaway recursion can produce a very long traceback, so a frame that repeats is only shown a few times
+        let mut prev_frame = None;
+        let mut repeats = 0;
         for (idx, frame) in self.frames.iter().enumerate().rev() {
-            writeln!(fmt, "#{} {}", idx + 1, frame)?;
+            let frame = frame.to_string();
+            if prev_frame.as_ref() == Some(&frame) {
+                repeats += 1;
+            } else {
+                write_repeats(fmt, repeats)?;
+                repeats = 0;
+            }
+            
+            if repeats < MAX_REPEATS {
+                writeln!(fmt, "#{} {}", idx + 1, frame)?;
+            }
+            prev_frame = Some(frame);
         }
+        write_repeats(fmt, repeats)?;
         
         Ok(())
     }
 }
+
+const MAX_REPEATS: usize = 3;
+
+fn write_repeats(fmt: &mut fmt::Formatter<'_>, repeats: usize) -> fmt::Result {
+    if repeats >= MAX_REPEATS {
+        writeln!(fmt, "[previous frame repeated {} more times]", repeats - MAX_REPEATS + 1)?;
+    }
+    Ok(())
+}
@@ -1,12 +1,23 @@
 //! output/error reporting and formatting
 
-use core::iter;
 use core::fmt::{self, Formatter};
-use std::error::Error;
+use core::iter;
 
 use crate::utils;
-use crate::debug::SourceError;
+use crate::source::ModuleSource;
+use crate::codegen::CompiledProgram;
+use crate::runtime::errors::RuntimeError;
+use crate::debug::{SourceError, Severity};
+use crate::debug::dasm::Disassembler;
+use crate::debug::traceback::TraceSite;
 use crate::debug::symbol::{ResolvedSymbol, DebugSymbolResolver};
+use highlight::RESET;
+
+pub mod lineedit;
+pub mod completion;
+pub mod highlight;
+pub mod format;
+pub mod json;
 
 pub fn print_source_errors<E>(resolver: &impl DebugSymbolResolver, errors: &[E]) where E: SourceError {
     let symbols = errors.iter().filter_map(|err| err.debug_symbol());
@@ -36,36 +47,126 @@ pub fn print_source_errors<E>(resolver: &impl DebugSymbolResolver, errors: &[E])
         || (1, 0), |resolved| (0, resolved.lineno())
     ));
     
+    let source_name = resolver.source_name();
+    let source_name = source_name.as_deref().unwrap_or("<input>");
+    
+    let color_enabled = highlight::color_enabled();
     for render in render_errors.iter() {
-        println!("{}", render);
+        let color = color_enabled.then(|| severity_color(render.0.severity()));
+        println!("{}", utils::make_display(|fmt| render.fmt_styled(fmt, Some(source_name), color)));
     }
 }
 
+/// Print a runtime error with its traceback, followed by the source code where it was raised if that can be found.
+pub fn print_runtime_error(error: &RuntimeError) {
+    let color = highlight::color_enabled().then(|| severity_color(Severity::Error));
+    
+    print!("{}", error.traceback());
+    let headline = format!("[{}] {}", error.kind().code(), error);
+    println!("{}", paint(&headline, color));
+    
+    let resolved = error.iter_trace().find_map(resolve_trace_site);
+    if let Some(resolved) = resolved {
+        println!("\n{}", utils::make_display(|fmt| fmt_source_lines(fmt, &resolved, color)));
+    }
+}
 
-pub struct RenderError<'e, 's, E>(pub &'e E, pub Option<&'s ResolvedSymbol>) where E: Error;
+fn resolve_trace_site(site: &TraceSite) -> Option<ResolvedSymbol> {
+    let symbol = site.debug_symbol()?;
+    
+    let resolved_table = match site {
+        TraceSite::Chunk { module, .. } => module.source()?.resolve_symbols(iter::once(symbol)),
+        TraceSite::Native => return None,
+    };
+    
+    let resolved = resolved_table.ok()?.lookup(symbol)?.ok()?.clone();
+    Some(resolved)
+}
 
-impl<E> fmt::Display for RenderError<'_, '_, E> where E: Error {
-    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+
+// colors used when rendering errors
+const STYLE_MARGIN: &str = "\x1B[34m";
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "\x1B[1;31m",
+        Severity::Warning => "\x1B[1;33m",
+    }
+}
+
+fn paint(text: &str, color: Option<&str>) -> String {
+    match color {
+        Some(color) => format!("{}{}{}", color, text, RESET),
+        None => text.to_string(),
+    }
+}
+
+
+/// Print the disassembly of a program, annotated with source lines if the source is available
+pub fn print_disassembly(build: &CompiledProgram, source: Option<&ModuleSource>) {
+    let symbols = build.symbols.values().flat_map(|table| table.symbols());
+    let symbol_table = source.map(|source| source.resolve_symbols(symbols));
+    
+    let mut dasm = Disassembler::new(&build.program)
+        .with_symbols(&build.symbols);
+    
+    match &symbol_table {
+        Some(Ok(symbol_table)) => dasm = dasm.with_symbol_table(symbol_table),
+        Some(Err(error)) => println!("Could not resolve debug symbols: {}", error),
+        None => { },
+    }
+    
+    println!("{}", dasm);
+}
+
+
+pub struct RenderError<'e, 's, E>(pub &'e E, pub Option<&'s ResolvedSymbol>) where E: SourceError;
+
+impl<E> RenderError<'_, '_, E> where E: SourceError {
+    // the headline and source markers are drawn in the color, if one is given
+    // if the name of the source is given, the location of the error is written as "name:line:col"
+    fn fmt_styled(&self, fmt: &mut fmt::Formatter<'_>, source_name: Option<&str>, color: Option<&str>) -> fmt::Result {
         let RenderError(error, source_lines) = self;
         
+        let mut headline = match error.error_code() {
+            Some(code) => format!("[{}] {}", code, error),
+            None => error.to_string(),
+        };
+        match error.context_note() {
+            Some(note) => headline.push_str(&format!(", {}.", note)),
+            None => headline.push('.'),
+        }
+        fmt.write_str(&paint(&headline, color))?;
+        
         if let Some(source_lines) = source_lines {
-            write!(fmt, "{}.\n\n{}", error, source_lines)
-        } else {
-            write!(fmt, "{}.", error)
+            if let Some(source_name) = source_name {
+                let (lineno, colno) = source_lines.location();
+                let location = format!("  --> {}:{}:{}", source_name, lineno, colno);
+                write!(fmt, "\n{}", paint(&location, color.and(Some(STYLE_MARGIN))))?;
+            }
+            fmt.write_str("\n\n")?;
+            fmt_source_lines(fmt, source_lines, color)?;
         }
+        Ok(())
+    }
+}
+
+impl<E> fmt::Display for RenderError<'_, '_, E> where E: SourceError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_styled(fmt, None, None)
     }
 }
 
 impl fmt::Display for ResolvedSymbol {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt_source_lines(fmt, self)
+        fmt_source_lines(fmt, self, None)
     }
 }
 
-fn fmt_source_lines(fmt: &mut Formatter<'_>, symbol: &ResolvedSymbol) -> fmt::Result {
+fn fmt_source_lines(fmt: &mut Formatter<'_>, symbol: &ResolvedSymbol, color: Option<&str>) -> fmt::Result {
     let mut start_idx = 0;
     for (num, raw_line) in symbol.iter_whole_lines().enumerate() {
-        let end_index = start_idx + raw_line.len(); // of current line
+        let end_index = start_idx + raw_line.chars().count(); // of current line
         
         let margin = format!("{: >3}", num + symbol.lineno());
         let source_line = raw_line.trim_end();
@@ -75,20 +176,21 @@ fn fmt_source_lines(fmt: &mut Formatter<'_>, symbol: &ResolvedSymbol) -> fmt::Re
             else { symbol.start() };
         
         let end_col =
-            if symbol.end() > end_index { source_line.len() } // ends on a next line
+            if symbol.end() > end_index { source_line.chars().count() } // ends on a next line
             else { symbol.end() - start_idx };
         
         let mut marker = String::new();
-        marker.extend(iter::repeat(' ').take(margin.len()));
+        marker.extend(std::iter::repeat_n(' ', margin.len()));
         marker.push_str("     ");
         
-        marker.extend(iter::repeat(' ').take(start_col));
-        marker.extend(iter::repeat('^').take(usize::max(end_col - start_col, 1))); // for single index symbols
+        marker.extend(std::iter::repeat_n(' ', start_col));
+        marker.extend(std::iter::repeat_n('^', usize::max(end_col - start_col, 1))); // for single index symbols
         
-        writeln!(fmt, "{}|    {}", margin, source_line)?;
-        writeln!(fmt, "{}", marker)?;
+        let margin = paint(&format!("{}|", margin), color.and(Some(STYLE_MARGIN)));
+        writeln!(fmt, "{}    {}", margin, source_line)?;
+        writeln!(fmt, "{}", paint(&marker, color))?;
         
-        start_idx += raw_line.len();
+        start_idx += raw_line.chars().count();
     }
     
     Ok(())
@@ -103,12 +205,12 @@ fn fmt_source_line_single(fmt: &mut Formatter<'_>, symbol: &ResolvedSymbol) -> f
         if !symbol.is_multiline() {
             symbol.end_col()
         } else {
-            source_line.len()
+            source_line.chars().count()
         };
     
     let mut marker = String::new();
-    marker.extend(iter::repeat(' ').take(margin.len() + start_col));
-    marker.extend(iter::repeat('^').take(usize::max(end_col - start_col, 1)));
+    marker.extend(std::iter::repeat_n(' ', margin.len() + start_col));
+    marker.extend(std::iter::repeat_n('^', usize::max(end_col - start_col, 1)));
     
     writeln!(fmt, "{}{}", margin, source_line)?;
     writeln!(fmt, "{}\n", marker)?;
@@ -146,3 +248,59 @@ fn fmt_symbol_single_line(fmt: &mut fmt::Formatter<'_>, symbol: &ResolvedSymbol)
     }
     Ok(())
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins;
+    use crate::source::SourceText;
+    use crate::codegen::Program;
+    use crate::runtime::{Module, VirtualMachine};
+    use crate::runtime::strings::StringInterner;
+    use crate::debug::symbol::resolver::BufferedResolver;
+    
+    #[test]
+    fn test_render_parser_error() {
+        let text = "fun f(x)\n    let y = x +\nend\n";
+        let errors = crate::parse_source(&mut StringInterner::new(), SourceText::from(text.to_string())).unwrap_err();
+        let error = &errors[0];
+        
+        let resolver = BufferedResolver::new(text);
+        let symbol = error.debug_symbol().unwrap();
+        let resolved_table = resolver.resolve_symbols(iter::once(symbol)).unwrap();
+        let resolved = resolved_table.lookup(symbol).unwrap().unwrap();
+        
+        let render = RenderError(error, Some(resolved));
+        assert_eq!(
+            render.to_string(),
+            "[E0105] Syntax error: expected an expression here, while parsing a binary operator.\n\n  3|    end\n        ^^^\n",
+        );
+        
+        let styled = utils::make_display(|fmt| render.fmt_styled(fmt, Some("test.sph"), Some(severity_color(Severity::Error)))).to_string();
+        assert!(styled.starts_with("\x1B[1;31m[E0105] Syntax error: expected an expression here"));
+        assert!(styled.contains(&format!("{}  --> test.sph:3:1{}", STYLE_MARGIN, RESET)));
+        assert!(styled.contains(&format!("{}  3|{}", STYLE_MARGIN, RESET)));
+    }    
+    #[test]
+    fn test_resolve_trace_in_string_module() {
+        // like each input entered into the REPL, the module has no file but keeps its source text
+        let source = ModuleSource::String("fun f()
+    1 // 0
+end
+f()
+".to_string());
+        let build = crate::build_module(&source).unwrap();
+        let program = Program::load(build.program).with_symbols(build.symbols);
+        
+        let module = Module::with_env(Some(source), program.data, builtins::create_prelude());
+        let error = VirtualMachine::new(module, &program.main).run().unwrap_err();
+        
+        let traceback = error.traceback().to_string();
+        assert!(traceback.contains("<anonymous module>:4:1 in <module>"), "{}", traceback);
+        assert!(traceback.contains("<anonymous module>:2:5 in fun f()"), "{}", traceback);
+        
+        let resolved = error.iter_trace().find_map(resolve_trace_site).unwrap();
+        assert_eq!(resolved.location(), (2, 5));
+    }
+}
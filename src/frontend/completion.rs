@@ -0,0 +1,137 @@
+//! Tab completion for the REPL.
+//!
+//! Identifiers are completed using the keywords of the language and the variables in the REPL's global environment.
+//! After a `.`, the expression before it is looked up (as long as it is just a variable followed by attributes)
+//! and the attributes of the resulting value are completed instead.
+
+use unicode_xid::UnicodeXID;
+use crate::language;
+use crate::runtime::{Variant, Gc};
+use crate::runtime::module::NamespaceEnv;
+use crate::runtime::strings::StringSymbol;
+use crate::frontend::lineedit::{Completer, Completion};
+
+
+pub struct ReplCompleter {
+    globals: Gc<NamespaceEnv>,
+    keywords: Vec<&'static str>,
+}
+
+impl ReplCompleter {
+    pub fn new(globals: Gc<NamespaceEnv>) -> Self {
+        Self {
+            globals,
+            keywords: language::create_default_lexer_rules().keywords().collect(),
+        }
+    }
+    
+    fn complete_name(&self, word: &str) -> Vec<String> {
+        let globals = self.globals.borrow();
+        let names = globals.names().map(|name| name.to_string());
+        let keywords = self.keywords.iter().map(|keyword| keyword.to_string());
+        
+        names.chain(keywords).filter(|name| name.starts_with(word)).collect()
+    }
+    
+    fn complete_attr(&self, receiver: &str, word: &str) -> Vec<String> {
+        let value = match self.evaluate(receiver) {
+            Some(value) => value,
+            None => return Vec::new(),
+        };
+        
+        value.attr_names().into_iter()
+            .map(|name| name.to_string())
+            .filter(|name| name.starts_with(word))
+            .collect()
+    }
+    
+    // looks up a variable followed by any number of attributes, like "a.b.c"
+    fn evaluate(&self, path: &str) -> Option<Variant> {
+        let mut names = path.split('.');
+        
+        let name = names.next().filter(|name| is_identifier(name))?;
+        let mut value = *self.globals.borrow().lookup(&StringSymbol::from(name)).ok()?;
+        
+        for name in names {
+            if !is_identifier(name) {
+                return None;
+            }
+            value = value.get_attr(&StringSymbol::from(name)).ok()?;
+        }
+        Some(value)
+    }
+}
+
+impl Completer for ReplCompleter {
+    fn complete(&self, text: &str) -> Option<Completion> {
+        let start = suffix_start(text, is_word_char);
+        let (before, word) = text.split_at(start);
+        
+        let mut candidates = match before.strip_suffix('.') {
+            Some(before) => {
+                let receiver_start = suffix_start(before, |ch| is_word_char(ch) || ch == '.');
+                self.complete_attr(&before[receiver_start..], word)
+            },
+            
+            None if word.is_empty() => return None,
+            None => self.complete_name(word),
+        };
+        
+        candidates.sort();
+        candidates.dedup();
+        Some(Completion { start, candidates })
+    }
+}
+
+// the byte offset where the chars at the end of the text that match the predicate start
+fn suffix_start(text: &str, predicate: impl Fn(char) -> bool) -> usize {
+    text.char_indices().rev()
+        .take_while(|(_, ch)| predicate(*ch))
+        .last()
+        .map_or(text.len(), |(index, _)| index)
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch == '_' || ch.is_xid_continue()
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(ch) if ch == '_' || ch.is_xid_start() => chars.all(is_word_char),
+        _ => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::Access;
+    use crate::runtime::types::List;
+    
+    fn candidates(completer: &ReplCompleter, text: &str) -> Option<Vec<String>> {
+        completer.complete(text).map(|completion| completion.candidates)
+    }
+    
+    #[test]
+    fn test_repl_completion() {
+        let globals = NamespaceEnv::new();
+        let list = Variant::List(Gc::new(List::from(Vec::new())));
+        globals.borrow_mut().create(StringSymbol::from("items"), Access::ReadOnly, list);
+        globals.borrow_mut().create(StringSymbol::from("iterate"), Access::ReadOnly, Variant::Nil);
+        
+        let completer = ReplCompleter::new(globals);
+        
+        assert_eq!(candidates(&completer, "let x = it"), Some(vec!["items".to_string(), "iterate".to_string()]));
+        assert_eq!(candidates(&completer, "nonl"), Some(vec!["nonlocal".to_string()]));
+        assert_eq!(candidates(&completer, "print(items.p"), Some(vec!["pop".to_string(), "push".to_string()]));
+        assert_eq!(completer.complete("items.p").map(|completion| completion.start), Some(6));
+        
+        // nothing to complete
+        assert_eq!(candidates(&completer, "x = "), None);
+        assert_eq!(candidates(&completer, "iterate.p"), Some(vec![]));
+        assert_eq!(candidates(&completer, "missing.p"), Some(vec![]));
+        assert_eq!(candidates(&completer, "1.5"), Some(vec![]));
+    }
+}
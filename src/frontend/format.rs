@@ -0,0 +1,1128 @@
+//! Renders the syntax tree back into source code, used by `sphinx fmt`.
+//!
+//! The output doesn't depend on how the source was laid out: each statement goes on its own line, indented by
+//! its nesting depth, and lists, argument lists, tables and dicts are broken up one item per line if they don't
+//! fit within the line width. Blocks that only hold a single expression (e.g. `if x then 1 else 2 end`) are kept
+//! on one line if they fit.
+//!
+//! Comments aren't part of the syntax tree, so the source is lexed again with comments kept. Each comment is
+//! placed before the statement that follows it, or at the end of the line if it trails a statement.
+//! Blank lines between statements are also kept, but runs of them are collapsed into one.
+
+use crate::utils::escape_str;
+use crate::language::{self, FloatType, InternSymbol};
+use crate::lexer::Token;
+use crate::lexer::rules::comments::{LineCommentRule, BlockCommentRule};
+use crate::source::SourceText;
+use crate::parser::ParserError;
+use crate::parser::stmt::{StmtMeta, Stmt, StmtList, ControlFlow, Label};
+use crate::parser::expr::{ExprMeta, Expr, ExprBlock, ConditionalBranch, TableItem, TableField, DictEntry};
+use crate::parser::primary::{Primary, Atom, AccessItem};
+use crate::parser::pattern::{Pattern, MatchAction, Assignment};
+use crate::parser::operator::UnaryOp;
+use crate::parser::fundefs::{FunctionDef, SignatureDef};
+use crate::parser::classdefs::ClassDef;
+use crate::parser::matchdefs::{MatchCase, CasePattern};
+use crate::runtime::strings::StringInterner;
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOptions {
+    pub indent_width: usize, // spaces per level of indentation
+    pub line_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent_width: 4, line_width: 100 }
+    }
+}
+
+/// Parse the source text and render it in canonical form, keeping comments
+pub fn format_source(text: &str, options: FormatOptions) -> Result<String, Vec<ParserError>> {
+    let mut interner = StringInterner::new();
+    let ast = crate::parse_source(&mut interner, SourceText::from(text.to_string()))?;
+    
+    let formatter = CodeFormatter::new(&interner, options).with_source(text);
+    Ok(formatter.format(&ast))
+}
+
+
+struct Comment {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+pub struct CodeFormatter<'s> {
+    interner: &'s StringInterner,
+    options: FormatOptions,
+    out: String,
+    level: usize,
+    
+    flat: bool,    // never break up items, used when measuring
+    inline: usize, // inside of a block that is written on one line, comments are left for the enclosing statement
+    
+    // taken from the source text, if it was given
+    comments: Vec<Comment>,
+    next_comment: usize,
+    closers: Vec<(usize, usize)>, // (start, end) of each keyword that ends a block, e.g. "end" or "else"
+    methods: Vec<usize>, // start of each "fun" keyword
+    newlines: Vec<usize>,
+    
+    cursor: usize, // the end of the last part of the source that was written
+    block_start: bool,
+}
+
+impl<'s> CodeFormatter<'s> {
+    pub fn new(interner: &'s StringInterner, options: FormatOptions) -> Self {
+        Self {
+            interner, options,
+            out: String::new(),
+            level: 0,
+            flat: false,
+            inline: 0,
+            comments: Vec::new(),
+            next_comment: 0,
+            closers: Vec::new(),
+            methods: Vec::new(),
+            newlines: Vec::new(),
+            cursor: 0,
+            block_start: true,
+        }
+    }
+    
+    /// Keep the comments and blank lines from the source text that the syntax tree was parsed from
+    pub fn with_source(mut self, text: &str) -> Self {
+        let lexer_factory = language::create_default_lexer_rules()
+            .set_skip_comments(false)
+            .add_rule(LineCommentRule::new(language::COMMENT_CHAR).excluding(language::NESTED_COMMENT_START))
+            .add_rule(BlockCommentRule::new(language::NESTED_COMMENT_START, language::NESTED_COMMENT_END));
+        
+        let chars = text.chars().collect::<Vec<char>>();
+        let mut lexer = lexer_factory.build(text);
+        while let Ok(token) = lexer.next_token() {
+            let start = token.symbol.start() as usize;
+            let end = token.symbol.end() as usize;
+            
+            match token.token {
+                Token::EOF => break,
+                
+                Token::Comment => {
+                    // line comments include the newline that ends them
+                    let text = chars[start..end].iter().collect::<String>().trim_end().to_string();
+                    let end = start + text.chars().count();
+                    self.comments.push(Comment { start, end, text });
+                },
+                
+                Token::End | Token::Elif | Token::Else | Token::Case | Token::Except | Token::Finally
+                    => self.closers.push((start, end)),
+                
+                Token::Fun => self.methods.push(start),
+                
+                _ => { },
+            }
+        }
+        
+        self.newlines = chars.iter().enumerate()
+            .filter_map(|(index, ch)| (*ch == '\n').then_some(index))
+            .collect();
+        
+        self
+    }
+    
+    pub fn format(mut self, ast: &[StmtMeta]) -> String {
+        for stmt in ast.iter() {
+            self.stmt(stmt);
+        }
+        self.flush_comments(usize::MAX);
+        
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+    
+    fn name(&self, symbol: &InternSymbol) -> &'s str {
+        self.interner.resolve(*symbol).expect("invalid symbol")
+    }
+    
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+    
+    fn newline(&mut self) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        let indent = self.level * self.options.indent_width;
+        self.out.extend(core::iter::repeat_n(' ', indent));
+    }
+    
+    fn col(&self) -> usize {
+        self.out.rsplit('\n').next().map_or(0, |line| line.chars().count())
+    }
+    
+    // render into a separate buffer without breaking up any items, starting from the current column
+    fn measure(&self, render: impl FnOnce(&mut Self)) -> String {
+        let col = self.col();
+        let mut scratch = Self {
+            out: " ".repeat(col),
+            flat: true,
+            ..Self::new(self.interner, self.options)
+        };
+        scratch.level = self.level;
+        scratch.inline = self.inline;
+        
+        render(&mut scratch);
+        scratch.out.split_off(col)
+    }
+    
+    /* Comments and Blank Lines */
+    
+    fn lines_between(&self, start: usize, end: usize) -> usize {
+        let before_end = self.newlines.partition_point(|index| *index < end);
+        before_end.saturating_sub(self.newlines.partition_point(|index| *index < start))
+    }
+    
+    fn next_comment(&self) -> Option<&Comment> {
+        self.comments.get(self.next_comment).filter(|_| self.inline == 0)
+    }
+    
+    fn blank_line(&mut self, start: usize) {
+        if !self.block_start && !self.out.is_empty() && self.lines_between(self.cursor, start) > 1 {
+            self.out.push('\n');
+        }
+    }
+    
+    // write any comments that come before the given position, each on its own line
+    fn flush_comments(&mut self, start: usize) {
+        while let Some(comment) = self.next_comment().filter(|comment| comment.start < start) {
+            let (start, end, text) = (comment.start, comment.end, comment.text.clone());
+            self.next_comment += 1;
+            
+            self.blank_line(start);
+            self.newline();
+            self.write(&text);
+            self.cursor = end;
+            self.block_start = false;
+        }
+    }
+    
+    // start a new line for an item (e.g. a statement) that starts at the given position in the source
+    fn begin_item(&mut self, start: Option<usize>) {
+        if let Some(start) = start {
+            if self.inline == 0 {
+                self.flush_comments(start);
+                self.blank_line(start);
+            }
+        }
+        self.newline();
+        self.block_start = false;
+    }
+    
+    fn end_item(&mut self, end: usize) {
+        self.cursor = self.cursor.max(end);
+        if self.inline > 0 {
+            return;
+        }
+        
+        // comments from inside of an item that spans several lines are moved after it
+        let mut inner = Vec::new();
+        while let Some(comment) = self.next_comment().filter(|comment| comment.start < end) {
+            inner.push(comment.text.clone());
+            self.next_comment += 1;
+        }
+        
+        self.trailing_comment(end);
+        for text in inner.iter() {
+            self.newline();
+            self.write(text);
+        }
+    }
+    
+    // a comment on the same line as the end of the last item stays at the end of that line,
+    // unless the line continues past the end of the block that the item is in
+    fn trailing_comment(&mut self, end: usize) {
+        let trailing = self.next_comment()
+            .filter(|comment| self.lines_between(end, comment.start) == 0)
+            .filter(|comment| self.next_closer(end).is_none_or(|closer| closer > comment.start))
+            .map(|comment| (comment.end, comment.text.clone()));
+        
+        if let Some((end, text)) = trailing {
+            self.next_comment += 1;
+            self.write("  ");
+            self.write(&text);
+            self.cursor = end;
+        }
+    }
+    
+    /* Blocks */
+    
+    fn open_block(&mut self) {
+        // a comment at the end of the first line of the block stays there
+        let next_closer = self.next_closer(self.cursor);
+        if self.next_comment().is_some_and(|comment| next_closer.is_none_or(|closer| comment.start < closer)) {
+            self.trailing_comment(self.cursor);
+        }
+        
+        self.level += 1;
+        self.block_start = true;
+    }
+    
+    // the start of the first keyword that ends a block (e.g. "end" or "else") after the given position
+    fn next_closer(&self, pos: usize) -> Option<usize> {
+        let index = self.closers.partition_point(|(start, _)| *start < pos);
+        self.closers.get(index).map(|(start, _)| *start)
+    }
+    
+    // find the keyword that ends the current block, and write any comments before it
+    fn close_block(&mut self) {
+        let index = self.closers.partition_point(|(start, _)| *start < self.cursor);
+        if let Some((start, end)) = self.closers.get(index).copied() {
+            self.flush_comments(start);
+            self.cursor = end;
+        }
+    }
+    
+    // move past the keyword that ends the current block, leaving any comments before it for the enclosing statement
+    fn skip_block(&mut self) {
+        self.inline += 1;
+        self.close_block();
+        self.inline -= 1;
+    }
+    
+    // write a block that can be written on a single line if it is simple enough and fits
+    fn block(&mut self, simple: bool, render: impl Fn(&mut Self, bool)) {
+        if simple {
+            let text = self.measure(|scratch| {
+                scratch.inline += 1;
+                render(scratch, true);
+            });
+            
+            if !text.contains('\n') && self.col() + text.chars().count() <= self.options.line_width {
+                let (len, cursor) = (self.out.len(), self.cursor);
+                
+                self.inline += 1;
+                render(self, true);
+                self.inline -= 1;
+                
+                // comments inside of the block can only be kept if it is written over several lines
+                if self.next_comment().is_none_or(|comment| comment.start >= self.cursor) {
+                    return;
+                }
+                self.out.truncate(len);
+                self.cursor = cursor;
+            }
+        }
+        render(self, false)
+    }
+    
+    // the body of a block, ending where the keyword that closes it should be written
+    fn suite(&mut self, stmt_list: &StmtList, result: Option<&ExprMeta>, inline: bool) {
+        if inline {
+            self.write(" ");
+            if let Some(result) = result {
+                self.expr(result.variant());
+                self.write(" ");
+            } else if let Some(control) = stmt_list.end_control() {
+                self.control_flow(control);
+                self.write(" ");
+            }
+            self.skip_block();
+            return;
+        }
+        
+        self.open_block();
+        
+        for stmt in stmt_list.iter() {
+            self.stmt(stmt);
+        }
+        
+        if let Some(result) = result {
+            let symbol = result.debug_symbol();
+            self.begin_item(Some(symbol.start() as usize));
+            self.expr(result.variant());
+            self.end_item(symbol.end() as usize);
+        }
+        
+        if let Some(control) = stmt_list.end_control() {
+            let symbol = control.debug_symbol();
+            self.begin_item(symbol.map(|symbol| symbol.start() as usize));
+            self.control_flow(control);
+            if let Some(symbol) = symbol {
+                self.end_item(symbol.end() as usize);
+            }
+        }
+        
+        self.close_block();
+        self.level -= 1;
+        self.newline();
+    }
+    
+    fn expr_suite(&mut self, block: &ExprBlock, inline: bool) {
+        self.suite(block.stmt_list(), block.result(), inline)
+    }
+    
+    fn label(&mut self, label: Option<&Label>) {
+        if let Some(label) = label {
+            self.write("::");
+            self.write(self.name(label.name()));
+            self.write(" ");
+        }
+    }
+    
+    /* Statements */
+    
+    fn stmt(&mut self, stmt: &StmtMeta) {
+        let symbol = stmt.debug_symbol();
+        self.begin_item(Some(symbol.start() as usize));
+        
+        match stmt.variant() {
+            Stmt::Expression(expr) => self.expr(expr),
+            
+            Stmt::Loop { label, body } => {
+                self.label(label.as_ref());
+                self.write("loop");
+                self.suite(body, None, false);
+                self.write("end");
+            },
+            
+            Stmt::WhileLoop { label, condition, body } => {
+                self.label(label.as_ref());
+                self.write("while ");
+                self.expr(condition);
+                self.write(" do");
+                self.suite(body, None, false);
+                self.write("end");
+            },
+            
+            Stmt::ForLoop { label, pattern, iter, body } => {
+                self.label(label.as_ref());
+                self.write("for ");
+                match pattern {
+                    Pattern::Modifier { modifier, pattern } => {
+                        self.write(match_keyword(*modifier));
+                        self.write(" ");
+                        self.pattern(pattern, false);
+                    },
+                    pattern => self.pattern(pattern, false),
+                }
+                self.write(" in ");
+                self.expr(iter);
+                self.write(" do");
+                self.suite(body, None, false);
+                self.write("end");
+            },
+            
+            Stmt::TryExcept { body, handler, finally } => {
+                self.write("try");
+                self.suite(body, None, false);
+                if let Some(handler) = handler {
+                    self.write("except");
+                    if let Some(name) = handler.name() {
+                        self.write(" as ");
+                        self.write(self.name(name));
+                    }
+                    self.suite(handler.body(), None, false);
+                }
+                if let Some(finally) = finally {
+                    self.write("finally");
+                    self.suite(finally, None, false);
+                }
+                self.write("end");
+            },
+            
+            Stmt::Assert(expr) => {
+                self.write("assert ");
+                self.expr(expr);
+            },
+            
+            Stmt::Import(path) => {
+                self.write("import ");
+                self.write(self.name(path));
+            },
+            
+            Stmt::Export(expr) => {
+                self.write("export ");
+                self.expr(expr);
+            },
+        }
+        
+        self.end_item(symbol.end() as usize);
+    }
+    
+    fn control_flow(&mut self, control: &ControlFlow) {
+        match control {
+            ControlFlow::Continue { label, .. } => {
+                self.write("continue");
+                self.control_label(label.as_ref());
+            },
+            
+            ControlFlow::Break { label, expr, .. } => {
+                self.write("break");
+                self.control_label(label.as_ref());
+                if let Some(expr) = expr {
+                    self.write(" ");
+                    self.expr(expr);
+                }
+            },
+            
+            ControlFlow::Return { expr, .. } => {
+                self.write("return");
+                if let Some(expr) = expr {
+                    self.write(" ");
+                    self.expr(expr);
+                }
+            },
+        }
+    }
+    
+    fn control_label(&mut self, label: Option<&Label>) {
+        if let Some(label) = label {
+            self.write(" ::");
+            self.write(self.name(label.name()));
+        }
+    }
+    
+    /* Expressions */
+    
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Atom(atom) => self.atom(atom),
+            
+            Expr::Primary(primary) => self.primary(primary),
+            
+            Expr::UnaryOp(op, operand) => {
+                let op = match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Pos => "+",
+                    UnaryOp::Inv => "~",
+                    UnaryOp::Not => "not ",
+                };
+                self.write(op);
+                self.expr(operand.variant());
+            },
+            
+            Expr::BinaryOp(op, operands) => {
+                let (lhs, rhs) = &**operands;
+                self.expr(lhs.variant());
+                self.write(&format!(" {} ", op));
+                self.expr(rhs.variant());
+            },
+            
+            Expr::Assignment(assign) => self.assignment(assign),
+            
+            Expr::Unpack(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+                self.write("...");
+            },
+            
+            Expr::Tuple(items) => {
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        self.write(", ");
+                    }
+                    self.expr(item.variant());
+                }
+                if items.len() == 1 {
+                    self.write(",");
+                }
+            },
+            
+            Expr::List(items) => self.delimited(("[", "]"), false, items, |this, item| this.expr(item.variant())),
+            
+            Expr::Table(items) => self.table(items),
+            
+            Expr::Dict(entries) => self.delimited(("{", "}"), true, entries, |this, entry| this.dict_entry(entry)),
+            
+            Expr::Concat(parts) => self.interpolated_string(parts),
+            
+            Expr::IfExpr { branches, else_clause } => {
+                let simple = branches.iter().map(ConditionalBranch::suite).chain(else_clause.as_deref())
+                    .all(is_simple);
+                
+                self.block(simple, |this, inline| this.if_expr(branches, else_clause.as_deref(), inline));
+            },
+            
+            Expr::Match { subject, cases, else_clause } => {
+                let simple = cases.iter().map(|case| &case.suite).chain(else_clause.as_deref())
+                    .all(is_simple);
+                
+                self.block(simple, |this, inline| this.match_expr(subject, cases, else_clause.as_deref(), inline));
+            },
+            
+            Expr::Block { label, suite } => {
+                self.block(is_simple(suite), |this, inline| {
+                    this.label(label.as_ref());
+                    this.write("begin");
+                    this.expr_suite(suite, inline);
+                    this.write("end");
+                });
+            },
+            
+            Expr::FunctionDef(fundef) => {
+                self.block(is_simple(&fundef.body), |this, inline| {
+                    this.write("fun");
+                    this.function_def(fundef, inline);
+                });
+            },
+            
+            Expr::Raise(expr) => {
+                self.write("raise ");
+                self.expr(expr);
+            },
+            
+            Expr::ClassDef(classdef) => {
+                self.write("class");
+                self.class_def(classdef);
+            },
+        }
+    }
+    
+    fn if_expr(&mut self, branches: &[ConditionalBranch], else_clause: Option<&ExprBlock>, inline: bool) {
+        for (idx, branch) in branches.iter().enumerate() {
+            self.write(if idx == 0 { "if " } else { "elif " });
+            self.expr(branch.condition());
+            self.write(" then");
+            self.expr_suite(branch.suite(), inline);
+        }
+        if let Some(else_clause) = else_clause {
+            self.write("else");
+            self.expr_suite(else_clause, inline);
+        }
+        self.write("end");
+    }
+    
+    fn match_expr(&mut self, subject: &ExprMeta, cases: &[MatchCase], else_clause: Option<&ExprBlock>, inline: bool) {
+        self.write("match ");
+        self.expr(subject.variant());
+        if inline {
+            self.write(" ");
+        } else {
+            // the cases are not inside of a block, so comments before the first one are written here
+            if let Some(case) = cases.first() {
+                self.cursor = self.cursor.max(subject.debug_symbol().end() as usize);
+                self.flush_comments(case.symbol.start() as usize);
+            }
+            self.newline();
+        }
+        for case in cases.iter() {
+            self.write("case ");
+            self.case_pattern(&case.pattern, true);
+            if let Some(guard) = &case.guard {
+                self.write(" if ");
+                self.expr(guard);
+            }
+            self.write(" then");
+            self.expr_suite(&case.suite, inline);
+        }
+        if let Some(else_clause) = else_clause {
+            self.write("else");
+            self.expr_suite(else_clause, inline);
+        }
+        self.write("end");
+    }
+    
+    // tuple patterns only need parentheses when they are nested
+    fn case_pattern(&mut self, pattern: &CasePattern, outer: bool) {
+        match pattern {
+            CasePattern::Wildcard => self.write("_"),
+            CasePattern::Binding(name) => self.write(self.name(name)),
+            CasePattern::Literal(atom) => self.atom(atom),
+            
+            CasePattern::Rest(name) => {
+                self.write(name.as_ref().map_or("_", |name| self.name(name)));
+                self.write("...");
+            },
+            
+            CasePattern::Tuple(items) if items.is_empty() => self.write("()"),
+            
+            CasePattern::Tuple(items) => {
+                if !outer {
+                    self.write("(");
+                }
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        self.write(", ");
+                    }
+                    self.case_pattern(item, false);
+                }
+                if matches!(&**items, [item] if !matches!(item, CasePattern::Rest(..))) {
+                    self.write(",");
+                }
+                if !outer {
+                    self.write(")");
+                }
+            },
+        }
+    }
+    
+    fn assignment(&mut self, assign: &Assignment) {
+        // "fun name() ... end" and "class Name ... end" are sugar for "let name = ..."
+        if is_declaration_sugar(assign) {
+            match &assign.rhs {
+                Expr::FunctionDef(fundef) => {
+                    self.write("fun ");
+                    self.pattern(&assign.lhs, false);
+                    self.function_def(fundef, is_empty(&fundef.body));
+                },
+                
+                Expr::ClassDef(classdef) => {
+                    self.write("class ");
+                    self.pattern(&assign.lhs, false);
+                    self.class_def(classdef);
+                },
+                
+                _ => unreachable!(),
+            }
+            return;
+        }
+        
+        if assign.action != MatchAction::AssignLocal {
+            self.write(match_keyword(assign.action));
+            self.write(" ");
+        }
+        
+        self.pattern(&assign.lhs, false);
+        match assign.op {
+            Some(op) => self.write(&format!(" {}= ", op)),
+            None => self.write(" = "),
+        }
+        self.expr(&assign.rhs);
+    }
+    
+    fn function_def(&mut self, fundef: &FunctionDef, inline: bool) {
+        self.write("(");
+        self.signature(&fundef.signature);
+        self.write(")");
+        self.expr_suite(&fundef.body, inline);
+        self.write("end");
+    }
+    
+    fn signature(&mut self, signature: &SignatureDef) {
+        let mut params = Vec::new();
+        
+        for param in signature.required.iter() {
+            params.push(format!("{}{}", access_keyword(param.mode), self.name(&param.name)));
+        }
+        
+        for param in signature.default.iter() {
+            let default = self.measure(|this| this.expr(param.default.variant()));
+            params.push(format!("{}{} = {}", access_keyword(param.mode), self.name(&param.name), default));
+        }
+        
+        if let Some(param) = &signature.variadic {
+            params.push(format!("{}{}...", access_keyword(param.mode), self.name(&param.name)));
+        }
+        
+        self.write(&params.join(", "));
+    }
+    
+    fn class_def(&mut self, classdef: &ClassDef) {
+        if let Some(parent) = &classdef.parent {
+            self.write("(");
+            self.expr(parent.variant());
+            self.write(")");
+        }
+        
+        if classdef.methods.is_empty() {
+            self.write(" ");
+            self.skip_block();
+            self.write("end");
+            return;
+        }
+        
+        self.open_block();
+        for method in classdef.methods.iter() {
+            let start = self.methods.iter().copied().find(|start| *start >= self.cursor);
+            self.begin_item(start);
+            
+            self.write("fun ");
+            self.write(self.name(method.signature.name.as_ref().expect("method name")));
+            self.function_def(method, is_empty(&method.body));
+        }
+        self.close_block();
+        self.level -= 1;
+        self.newline();
+        self.write("end");
+    }
+    
+    fn primary(&mut self, primary: &Primary) {
+        self.atom(primary.atom());
+        
+        // "?." takes the place of the "." that would otherwise come before an attribute
+        let mut chained = false;
+        for item in primary.path().iter() {
+            match item {
+                AccessItem::OptionalChain => self.write("?."),
+                
+                AccessItem::Attribute(name) => {
+                    if !chained {
+                        self.write(".");
+                    }
+                    self.write(self.name(name));
+                },
+                
+                AccessItem::Index(index) => {
+                    self.write("[");
+                    self.expr(index.variant());
+                    self.write("]");
+                },
+                
+                AccessItem::Invoke(args) => self.delimited(("(", ")"), false, args, |this, arg| this.expr(arg.variant())),
+                
+                AccessItem::InvokeTable(items) => {
+                    self.write(" ");
+                    self.table(items);
+                },
+            }
+            chained = matches!(item, AccessItem::OptionalChain);
+        }
+    }
+    
+    fn atom(&mut self, atom: &Atom) {
+        match atom {
+            Atom::Nil => self.write("nil"),
+            Atom::EmptyTuple => self.write("()"),
+            Atom::Self_ => self.write("self"),
+            Atom::Super => self.write("super"),
+            
+            Atom::Identifier(name) => self.write(self.name(name)),
+            Atom::BooleanLiteral(true) => self.write("true"),
+            Atom::BooleanLiteral(false) => self.write("false"),
+            Atom::IntegerLiteral(value) => self.write(&value.to_string()),
+            Atom::BigIntegerLiteral(text) => self.write(self.name(text)),
+            Atom::FloatLiteral(value) => self.write(&float_literal(*value)),
+            Atom::StringLiteral(value) => {
+                let literal = format!("\"{}\"", escape_str(&self.name(value), false));
+                self.write(&literal);
+            },
+            
+            // list, dict and table literals and interpolated strings are wrapped in a group by the parser
+            Atom::Group { modifier: None, inner } if matches!(**inner, Expr::List(..) | Expr::Dict(..) | Expr::Table(..) | Expr::Concat(..))
+                => self.expr(inner),
+            
+            Atom::Group { modifier, inner } => {
+                self.write("(");
+                if let Some(modifier) = modifier {
+                    self.write(match_keyword(*modifier));
+                    self.write(" ");
+                }
+                self.expr(inner);
+                self.write(")");
+            },
+        }
+    }
+    
+    fn table(&mut self, items: &[TableItem]) {
+        self.delimited(("{", "}"), true, items, |this, item| {
+            match &item.field {
+                TableField::Attribute(mode, name) => {
+                    this.write(access_keyword(*mode));
+                    this.write(this.name(name));
+                },
+                TableField::Index(index) => {
+                    this.write("[");
+                    this.expr(index.variant());
+                    this.write("]");
+                },
+            }
+            this.write(" = ");
+            this.expr(item.value.variant());
+        });
+    }
+    
+    fn dict_entry(&mut self, entry: &DictEntry) {
+        self.expr(entry.key.variant());
+        self.write(": ");
+        self.expr(entry.value.variant());
+    }
+    
+    fn interpolated_string(&mut self, parts: &[ExprMeta]) {
+        self.write("f\"");
+        for part in parts.iter() {
+            match part.variant() {
+                Expr::Atom(Atom::StringLiteral(value)) => self.write(&escape_str(&self.name(value), true).to_string()),
+                
+                expr => {
+                    let text = self.measure(|this| this.expr(expr));
+                    // a leading "{{" would be read as an escaped brace
+                    if text.starts_with('{') {
+                        self.write(&format!("{{ {}}}", text));
+                    } else {
+                        self.write(&format!("{{{}}}", text));
+                    }
+                },
+            }
+        }
+        self.write("\"");
+    }
+    
+    // Items are written on one line if they fit. Otherwise each one is written on its own line,
+    // unless only the last item spans several lines (e.g. a function passed as the last argument).
+    fn delimited<T>(&mut self, (open, close): (&str, &str), padded: bool, items: &[T], render: impl Fn(&mut Self, &T)) {
+        let pad = if padded && !items.is_empty() { " " } else { "" };
+        
+        if self.flat || self.fits_on_line((open, close), pad, items, &render) {
+            self.write(open);
+            self.write(pad);
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    self.write(", ");
+                }
+                render(self, item);
+            }
+            self.write(pad);
+            self.write(close);
+            return;
+        }
+        
+        self.write(open);
+        self.level += 1;
+        for (idx, item) in items.iter().enumerate() {
+            self.newline();
+            render(self, item);
+            if idx + 1 < items.len() {
+                self.write(",");
+            }
+        }
+        self.level -= 1;
+        self.newline();
+        self.write(close);
+    }
+    
+    fn fits_on_line<T>(&self, (open, close): (&str, &str), pad: &str, items: &[T], render: &impl Fn(&mut Self, &T)) -> bool {
+        let rendered = items.iter()
+            .map(|item| self.measure(|this| render(this, item)))
+            .collect::<Vec<String>>();
+        
+        let (last, rest) = match rendered.split_last() {
+            Some(split) => split,
+            None => return true,
+        };
+        
+        if rest.iter().any(|item| item.contains('\n')) {
+            return false;
+        }
+        
+        let mut width = self.col() + open.len() + pad.len();
+        width += rest.iter().map(|item| item.chars().count() + 2).sum::<usize>();
+        width += match last.split_once('\n') {
+            Some((first_line, _)) => first_line.chars().count(),
+            None => last.chars().count() + pad.len() + close.len(),
+        };
+        width <= self.options.line_width
+    }
+    
+    /* Patterns */
+    
+    // nested tuples need to be enclosed in parentheses
+    fn pattern(&mut self, pattern: &Pattern, nested: bool) {
+        match pattern {
+            Pattern::Identifier(name) => self.write(self.name(name)),
+            
+            Pattern::Attribute(target) => {
+                self.primary(&target.receiver);
+                self.write(".");
+                self.write(self.name(&target.name));
+            },
+            
+            Pattern::Index(target) => {
+                self.primary(&target.receiver);
+                self.write("[");
+                self.expr(target.index.variant());
+                self.write("]");
+            },
+            
+            Pattern::Tuple(items) => {
+                let parens = nested || items.len() == 1;
+                if parens {
+                    self.write("(");
+                }
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        self.write(", ");
+                    }
+                    self.pattern(&item.pattern, true);
+                }
+                if items.len() == 1 {
+                    self.write(",");
+                }
+                if parens {
+                    self.write(")");
+                }
+            },
+            
+            Pattern::Pack(pattern) => {
+                if let Some(pattern) = pattern {
+                    self.pattern(pattern, true);
+                }
+                self.write("...");
+            },
+            
+            Pattern::Modifier { modifier, pattern } => {
+                self.write("(");
+                self.write(match_keyword(*modifier));
+                self.write(" ");
+                self.pattern(pattern, false);
+                self.write(")");
+            },
+        }
+    }
+}
+
+
+// a block that can be written on a single line
+fn is_simple(block: &ExprBlock) -> bool {
+    block.stmt_list().iter().next().is_none()
+}
+
+fn is_empty(block: &ExprBlock) -> bool {
+    is_simple(block) && block.result().is_none() && block.stmt_list().end_control().is_none()
+}
+
+fn is_declaration_sugar(assign: &Assignment) -> bool {
+    if assign.action != MatchAction::DeclImmutable || assign.op.is_some() {
+        return false;
+    }
+    
+    let name = match &assign.rhs {
+        Expr::FunctionDef(fundef) => fundef.signature.name,
+        Expr::ClassDef(classdef) => classdef.name,
+        _ => return false,
+    };
+    
+    // the sugar only allows a name followed by attributes and indexes, and only records the name if there is just a name
+    let receiver = match &assign.lhs {
+        Pattern::Identifier(ident) => return name == Some(*ident),
+        Pattern::Attribute(target) => &target.receiver,
+        Pattern::Index(target) => &target.receiver,
+        _ => return false,
+    };
+    
+    name.is_none()
+    && matches!(receiver.atom(), Atom::Identifier(..))
+    && receiver.path().iter().all(|item| matches!(item, AccessItem::Attribute(..) | AccessItem::Index(..)))
+}
+
+fn match_keyword(action: MatchAction) -> &'static str {
+    match action {
+        MatchAction::AssignLocal => "local",
+        MatchAction::AssignNonLocal => "nonlocal",
+        MatchAction::DeclImmutable => "let",
+        MatchAction::DeclMutable => "var",
+    }
+}
+
+fn access_keyword(mode: language::Access) -> &'static str {
+    match mode {
+        language::Access::ReadOnly => "",
+        language::Access::ReadWrite => "var ",
+    }
+}
+
+// the debug format of floats always includes either a decimal point or an exponent
+fn float_literal(value: FloatType) -> String {
+    format!("{:?}", value)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    
+    fn format(text: &str) -> String {
+        format_source(text, FormatOptions::default()).unwrap()
+    }
+    
+    #[test]
+    fn test_format_source() {
+        let text = concat!(
+            "# header\n",
+            "let x=1   # one\n",
+            "\n\n\n",
+            "fun  f(a, var b, c = 2, rest...)\n",
+            "    # leading\n",
+            "    let y = a+b; if y>1 then return y end\n",
+            "    y\n",
+            "end\n",
+            "class Foo(Bar) fun get() return self.x end end\n",
+            "for k, v in {a: 1, \"b\": [x]} do echo(f\"{k}={v} {{}}\") end\n",
+            "(let a), b = 1, (2,)\n",
+        );
+        
+        assert_eq!(format(text), concat!(
+            "# header\n",
+            "let x = 1  # one\n",
+            "\n",
+            "fun f(a, var b, c = 2, rest...)\n",
+            "    # leading\n",
+            "    let y = a + b\n",
+            "    if y > 1 then return y end\n",
+            "    y\n",
+            "end\n",
+            "class Foo(Bar)\n",
+            "    fun get()\n",
+            "        return self.x\n",
+            "    end\n",
+            "end\n",
+            "for k, v in { a: 1, \"b\": [x] } do\n",
+            "    echo(f\"{k}={v} {{}}\")\n",
+            "end\n",
+            "(let a), b = 1, (2,)\n",
+        ));
+        
+        // comments inside of a block keep it from being written on one line
+        assert_eq!(format("let g = fun(x)\n    # add one\n    x + 1\nend"), "let g = fun(x)\n    # add one\n    x + 1\nend\n");
+        assert_eq!(format("let g = fun(x)\n    x + 1\nend  # add one"), "let g = fun(x) x + 1 end  # add one\n");
+    }
+    
+    #[test]
+    fn test_format_line_width() {
+        let text = "let items = [first, second, { key: value }]\nregister(\"name\", fun(x) let y = x; y end)";
+        let options = FormatOptions { indent_width: 2, line_width: 30 };
+        
+        assert_eq!(format_source(text, options).unwrap(), concat!(
+            "let items = [\n",
+            "  first,\n",
+            "  second,\n",
+            "  { key: value }\n",
+            "]\n",
+            "register(\"name\", fun(x)\n",
+            "  let y = x\n",
+            "  y\n",
+            "end)\n",
+        ));
+    }
+    
+    // formatting the test scripts must produce source that parses, and formatting it again must not change it
+    #[test]
+    fn test_format_is_stable() {
+        fn visit(dir: &Path, scripts: &mut Vec<String>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    visit(&path, scripts);
+                } else if path.extension().is_some_and(|ext| ext == "sph") {
+                    scripts.push(fs::read_to_string(path).unwrap());
+                }
+            }
+        }
+        
+        let mut scripts = Vec::new();
+        visit(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests"), &mut scripts);
+        assert!(!scripts.is_empty());
+        
+        for text in scripts.iter() {
+            // some of the scripts test syntax errors
+            if let Ok(formatted) = format_source(text, FormatOptions::default()) {
+                assert_eq!(format(&formatted), formatted);
+            }
+        }
+    }
+}
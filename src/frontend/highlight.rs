@@ -0,0 +1,135 @@
+//! Syntax highlighting for the REPL.
+//!
+//! The input is lexed using the same rules as the parser, except that comments are kept, and each token is
+//! colored according to its kind. Anything after a lexer error (e.g. an unterminated string) is left as it is.
+
+use std::env;
+use std::io::{self, IsTerminal};
+use crate::language::{self, TokenRole};
+use crate::lexer::{LexerBuilder, Token};
+use crate::lexer::rules::comments::{LineCommentRule, BlockCommentRule};
+use crate::frontend::lineedit::Highlighter;
+
+
+pub(super) const RESET: &str = "\x1B[0m";
+
+const STYLE_PROMPT:  &str = "\x1B[1;32m";
+const STYLE_KEYWORD: &str = "\x1B[35m";
+const STYLE_LITERAL: &str = "\x1B[36m";
+const STYLE_STRING:  &str = "\x1B[32m";
+const STYLE_LABEL:   &str = "\x1B[33m";
+const STYLE_COMMENT: &str = "\x1B[90m";
+
+/// Colors are only used when stdout is a terminal, and can be turned off by setting NO_COLOR
+pub fn color_enabled() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+pub struct ReplHighlighter {
+    lexer_factory: LexerBuilder,
+}
+
+impl Default for ReplHighlighter {
+    fn default() -> Self { Self::new() }
+}
+
+impl ReplHighlighter {
+    pub fn new() -> Self {
+        let lexer_factory = language::create_default_lexer_rules()
+            .set_skip_comments(false)
+            .add_rule(LineCommentRule::new(language::COMMENT_CHAR).excluding(language::NESTED_COMMENT_START))
+            .add_rule(BlockCommentRule::new(language::NESTED_COMMENT_START, language::NESTED_COMMENT_END));
+        
+        Self { lexer_factory }
+    }
+}
+
+impl Highlighter for ReplHighlighter {
+    fn highlight(&self, text: &str) -> String {
+        let chars = text.chars().collect::<Vec<char>>();
+        let mut lexer = self.lexer_factory.build(text);
+        
+        let mut output = String::new();
+        let mut pos = 0;
+        while let Ok(token) = lexer.next_token() {
+            if matches!(token.token, Token::EOF) {
+                break;
+            }
+            
+            let start = token.symbol.start() as usize;
+            let end = token.symbol.end() as usize;
+            output.extend(&chars[pos..start]);
+            
+            let token_text = chars[start..end].iter().collect::<String>();
+            match token_style(&token.token) {
+                Some(style) => {
+                    // the style is restarted after each newline, so that it doesn't extend over the continuation prompt
+                    output.push_str(style);
+                    output.push_str(&token_text.replace('\n', &format!("{}\n{}", RESET, style)));
+                    output.push_str(RESET);
+                },
+                None => output.push_str(&token_text),
+            }
+            pos = end;
+        }
+        
+        output.extend(&chars[pos..]);
+        output
+    }
+    
+    fn highlight_prompt(&self, prompt: &str) -> String {
+        format!("{}{}{}", STYLE_PROMPT, prompt, RESET)
+    }
+}
+
+fn token_style(token: &Token) -> Option<&'static str> {
+    let style = match token {
+        Token::IntegerLiteral(..) | Token::BigIntegerLiteral(..) | Token::FloatLiteral(..) => STYLE_LITERAL,
+        
+        Token::StringLiteral(..) | Token::InterpolatedString(..) => STYLE_STRING,
+        
+        Token::Label(..) => STYLE_LABEL,
+        
+        Token::Comment => STYLE_COMMENT,
+        
+        // keywords and keyword operators, like "and"
+        _ => {
+            let fixed = language::fixed_token(token)?;
+            match fixed.role {
+                TokenRole::Literal => STYLE_LITERAL,
+                _ if fixed.is_keyword() => STYLE_KEYWORD,
+                _ => return None,
+            }
+        },
+    };
+    Some(style)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_repl_highlighting() {
+        let highlighter = ReplHighlighter::new();
+        let styled = |style: &str, text: &str| format!("{}{}{}", style, text, RESET);
+        
+        assert_eq!(
+            highlighter.highlight("let x = 1  # one"),
+            format!("{} x = {}  {}", styled(STYLE_KEYWORD, "let"), styled(STYLE_LITERAL, "1"), styled(STYLE_COMMENT, "# one")),
+        );
+        
+        // each line of a multi-line token is styled separately
+        assert_eq!(
+            highlighter.highlight("x = \"a\nb\""),
+            format!("x = {}\n{}", styled(STYLE_STRING, "\"a"), styled(STYLE_STRING, "b\"")),
+        );
+        
+        // the rest of the input is left as is after a lexer error
+        assert_eq!(
+            highlighter.highlight("nil; \"abc"),
+            format!("{}; \"abc", styled(STYLE_LITERAL, "nil")),
+        );
+    }
+}
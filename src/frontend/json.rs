@@ -0,0 +1,555 @@
+//! Serializes the syntax tree to JSON, used by `sphinx-dasm -P --format=json` so that tools outside of Rust can
+//! consume the AST.
+//!
+//! Every node is an object with a "type" field holding the name of its variant. Statements and expressions that
+//! carry a debug symbol also have a "symbol" field with the start and end of their source text, as char offsets.
+//! Enums that don't hold any data (operators, access modes, etc.) are written as the name of their variant, and
+//! missing optional parts are written as null.
+
+use core::fmt::{self, Write};
+
+use crate::language::{IntType, FloatType, InternSymbol};
+use crate::debug::DebugSymbol;
+use crate::parser::stmt::{StmtMeta, Stmt, StmtList, ControlFlow, Label};
+use crate::parser::expr::{ExprMeta, Expr, ExprBlock, TableItem, TableField};
+use crate::parser::primary::{Primary, Atom, AccessItem};
+use crate::parser::pattern::Pattern;
+use crate::parser::fundefs::FunctionDef;
+use crate::parser::classdefs::ClassDef;
+use crate::parser::matchdefs::CasePattern;
+use crate::runtime::strings::StringInterner;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(IntType),
+    Float(FloatType),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl<T> From<Option<T>> for Json where T: Into<Json> {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self { Json::Bool(value) }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self { Json::String(value.to_string()) }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Self { Json::Array(items) }
+}
+
+/// The default format is compact, the alternate format (`{:#}`) puts each item on its own line
+impl fmt::Display for Json {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = fmt.alternate().then_some(0);
+        self.write_to(fmt, indent)
+    }
+}
+
+const INDENT: &str = "  ";
+
+impl Json {
+    fn write_to(&self, fmt: &mut fmt::Formatter<'_>, indent: Option<usize>) -> fmt::Result {
+        match self {
+            Self::Null => fmt.write_str("null"),
+            Self::Bool(value) => write!(fmt, "{}", value),
+            Self::Int(value) => write!(fmt, "{}", value),
+            
+            // JSON has no representation for inf or NaN
+            Self::Float(value) if !value.is_finite() => fmt.write_str("null"),
+            Self::Float(value) => write!(fmt, "{:?}", value),
+            
+            Self::String(value) => write_string(fmt, value),
+            
+            Self::Array(items) => write_items(fmt, indent, ('[', ']'), items, |fmt, item, indent| {
+                item.write_to(fmt, indent)
+            }),
+            
+            Self::Object(fields) => write_items(fmt, indent, ('{', '}'), fields, |fmt, (name, value), indent| {
+                write_string(fmt, name)?;
+                fmt.write_str(if indent.is_some() { ": " } else { ":" })?;
+                value.write_to(fmt, indent)
+            }),
+        }
+    }
+}
+
+fn write_items<T>(
+    fmt: &mut fmt::Formatter<'_>, indent: Option<usize>, (open, close): (char, char), items: &[T],
+    write_item: impl Fn(&mut fmt::Formatter<'_>, &T, Option<usize>) -> fmt::Result
+) -> fmt::Result {
+    fmt.write_char(open)?;
+    
+    let inner = indent.map(|level| level + 1);
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            fmt.write_char(',')?;
+        }
+        if let Some(level) = inner {
+            fmt.write_char('\n')?;
+            fmt.write_str(&INDENT.repeat(level))?;
+        }
+        write_item(fmt, item, inner)?;
+    }
+    
+    if let (Some(level), false) = (indent, items.is_empty()) {
+        fmt.write_char('\n')?;
+        fmt.write_str(&INDENT.repeat(level))?;
+    }
+    fmt.write_char(close)
+}
+
+fn write_string(fmt: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    fmt.write_char('"')?;
+    for ch in value.chars() {
+        match ch {
+            '"' => fmt.write_str("\\\"")?,
+            '\\' => fmt.write_str("\\\\")?,
+            '\n' => fmt.write_str("\\n")?,
+            '\r' => fmt.write_str("\\r")?,
+            '\t' => fmt.write_str("\\t")?,
+            ch if ch.is_control() => write!(fmt, "\\u{:04x}", ch as u32)?,
+            ch => fmt.write_char(ch)?,
+        }
+    }
+    fmt.write_char('"')
+}
+
+
+/// Convert a parsed module into JSON
+pub fn ast_to_json(interner: &StringInterner, ast: &[StmtMeta]) -> Json {
+    AstSerializer::new(interner).module(ast)
+}
+
+fn node<const N: usize>(kind: &'static str, fields: [(&'static str, Json); N]) -> Json {
+    let mut items = Vec::with_capacity(N + 1);
+    items.push(("type", Json::from(kind)));
+    items.extend(fields);
+    Json::Object(items)
+}
+
+// adds the "symbol" field to a node
+fn with_symbol(mut json: Json, symbol: Option<&DebugSymbol>) -> Json {
+    if let Json::Object(fields) = &mut json {
+        let symbol = symbol.map(|symbol| Json::Object(vec![
+            ("start", Json::Int(symbol.start() as IntType)),
+            ("end", Json::Int(symbol.end() as IntType)),
+        ]));
+        fields.push(("symbol", symbol.into()));
+    }
+    json
+}
+
+// the name of a unit variant
+fn variant_name(value: &impl fmt::Debug) -> Json {
+    Json::String(format!("{:?}", value))
+}
+
+
+pub struct AstSerializer<'s> {
+    interner: &'s StringInterner,
+}
+
+impl<'s> AstSerializer<'s> {
+    pub fn new(interner: &'s StringInterner) -> Self {
+        Self { interner }
+    }
+    
+    pub fn module(&self, ast: &[StmtMeta]) -> Json {
+        Json::Array(ast.iter().map(|stmt| self.stmt(stmt)).collect())
+    }
+    
+    fn name(&self, symbol: &InternSymbol) -> Json {
+        self.interner.resolve(*symbol).expect("invalid symbol").into()
+    }
+    
+    fn label(&self, label: Option<&Label>) -> Json {
+        label.map(|label| self.name(label.name())).into()
+    }
+    
+    fn exprs(&self, items: &[ExprMeta]) -> Json {
+        Json::Array(items.iter().map(|expr| self.expr_meta(expr)).collect())
+    }
+    
+    pub fn stmt(&self, stmt: &StmtMeta) -> Json {
+        let json = match stmt.variant() {
+            Stmt::Expression(expr) => node("Expression", [
+                ("expr", self.expr(expr)),
+            ]),
+            
+            Stmt::Loop { label, body } => node("Loop", [
+                ("label", self.label(label.as_ref())),
+                ("body", self.stmt_list(body)),
+            ]),
+            
+            Stmt::WhileLoop { label, condition, body } => node("WhileLoop", [
+                ("label", self.label(label.as_ref())),
+                ("condition", self.expr(condition)),
+                ("body", self.stmt_list(body)),
+            ]),
+            
+            Stmt::ForLoop { label, pattern, iter, body } => node("ForLoop", [
+                ("label", self.label(label.as_ref())),
+                ("pattern", self.pattern(pattern)),
+                ("iter", self.expr(iter)),
+                ("body", self.stmt_list(body)),
+            ]),
+            
+            Stmt::TryExcept { body, handler, finally } => {
+                let handler = handler.as_ref().map(|handler| Json::Object(vec![
+                    ("name", handler.name().map(|name| self.name(name)).into()),
+                    ("body", self.stmt_list(handler.body())),
+                ]));
+                
+                node("TryExcept", [
+                    ("body", self.stmt_list(body)),
+                    ("handler", handler.into()),
+                    ("finally", finally.as_ref().map(|finally| self.stmt_list(finally)).into()),
+                ])
+            },
+            
+            Stmt::Assert(expr) => node("Assert", [
+                ("expr", self.expr(expr)),
+            ]),
+            
+            Stmt::Import(path) => node("Import", [
+                ("path", self.name(path)),
+            ]),
+            
+            Stmt::Export(expr) => node("Export", [
+                ("expr", self.expr(expr)),
+            ]),
+        };
+        with_symbol(json, Some(stmt.debug_symbol()))
+    }
+    
+    fn stmt_list(&self, stmt_list: &StmtList) -> Json {
+        Json::Object(vec![
+            ("stmts", Json::Array(stmt_list.iter().map(|stmt| self.stmt(stmt)).collect())),
+            ("control", stmt_list.end_control().map(|control| self.control_flow(control)).into()),
+        ])
+    }
+    
+    fn control_flow(&self, control: &ControlFlow) -> Json {
+        let json = match control {
+            ControlFlow::Continue { label, .. } => node("Continue", [
+                ("label", self.label(label.as_ref())),
+            ]),
+            
+            ControlFlow::Break { label, expr, .. } => node("Break", [
+                ("label", self.label(label.as_ref())),
+                ("expr", expr.as_ref().map(|expr| self.expr(expr)).into()),
+            ]),
+            
+            ControlFlow::Return { expr, .. } => node("Return", [
+                ("expr", expr.as_ref().map(|expr| self.expr(expr)).into()),
+            ]),
+        };
+        with_symbol(json, control.debug_symbol())
+    }
+    
+    fn expr_block(&self, block: &ExprBlock) -> Json {
+        let stmt_list = block.stmt_list();
+        Json::Object(vec![
+            ("stmts", Json::Array(stmt_list.iter().map(|stmt| self.stmt(stmt)).collect())),
+            ("control", stmt_list.end_control().map(|control| self.control_flow(control)).into()),
+            ("result", block.result().map(|expr| self.expr_meta(expr)).into()),
+        ])
+    }
+    
+    pub fn expr_meta(&self, expr: &ExprMeta) -> Json {
+        with_symbol(self.expr(expr.variant()), Some(expr.debug_symbol()))
+    }
+    
+    pub fn expr(&self, expr: &Expr) -> Json {
+        match expr {
+            Expr::Atom(atom) => self.atom(atom),
+            
+            Expr::Primary(primary) => self.primary(primary),
+            
+            Expr::UnaryOp(op, operand) => node("UnaryOp", [
+                ("op", variant_name(op)),
+                ("operand", self.expr_meta(operand)),
+            ]),
+            
+            Expr::BinaryOp(op, operands) => {
+                let (lhs, rhs) = &**operands;
+                node("BinaryOp", [
+                    ("op", variant_name(op)),
+                    ("lhs", self.expr_meta(lhs)),
+                    ("rhs", self.expr_meta(rhs)),
+                ])
+            },
+            
+            Expr::Assignment(assignment) => node("Assignment", [
+                ("action", variant_name(&assignment.action)),
+                ("op", assignment.op.as_ref().map(variant_name).into()),
+                ("lhs", self.pattern(&assignment.lhs)),
+                ("rhs", self.expr(&assignment.rhs)),
+            ]),
+            
+            Expr::Unpack(expr) => node("Unpack", [
+                ("expr", expr.as_ref().map(|expr| self.expr(expr)).into()),
+            ]),
+            
+            Expr::Tuple(items) => node("Tuple", [("items", self.exprs(items))]),
+            
+            Expr::List(items) => node("List", [("items", self.exprs(items))]),
+            
+            Expr::Concat(items) => node("Concat", [("items", self.exprs(items))]),
+            
+            Expr::Table(items) => node("Table", [("items", self.table_items(items))]),
+            
+            Expr::Dict(entries) => {
+                let entries = entries.iter().map(|entry| Json::Object(vec![
+                    ("key", self.expr_meta(&entry.key)),
+                    ("value", self.expr_meta(&entry.value)),
+                ]));
+                node("Dict", [("entries", Json::Array(entries.collect()))])
+            },
+            
+            Expr::IfExpr { branches, else_clause } => {
+                let branches = branches.iter().map(|branch| Json::Object(vec![
+                    ("condition", self.expr(branch.condition())),
+                    ("suite", self.expr_block(branch.suite())),
+                ]));
+                
+                node("IfExpr", [
+                    ("branches", Json::Array(branches.collect())),
+                    ("else_clause", else_clause.as_ref().map(|suite| self.expr_block(suite)).into()),
+                ])
+            },
+            
+            Expr::Match { subject, cases, else_clause } => {
+                let cases = cases.iter().map(|case| Json::Object(vec![
+                    ("pattern", self.case_pattern(&case.pattern)),
+                    ("guard", case.guard.as_ref().map(|guard| self.expr(guard)).into()),
+                    ("suite", self.expr_block(&case.suite)),
+                ]));
+                
+                node("Match", [
+                    ("subject", self.expr_meta(subject)),
+                    ("cases", Json::Array(cases.collect())),
+                    ("else_clause", else_clause.as_ref().map(|suite| self.expr_block(suite)).into()),
+                ])
+            },
+            
+            Expr::Block { label, suite } => node("Block", [
+                ("label", self.label(label.as_ref())),
+                ("suite", self.expr_block(suite)),
+            ]),
+            
+            Expr::FunctionDef(fundef) => self.function_def(fundef),
+            
+            Expr::Raise(expr) => node("Raise", [
+                ("expr", self.expr(expr)),
+            ]),
+            
+            Expr::ClassDef(classdef) => self.class_def(classdef),
+        }
+    }
+    
+    fn atom(&self, atom: &Atom) -> Json {
+        match atom {
+            Atom::Nil => node("Nil", []),
+            Atom::EmptyTuple => node("EmptyTuple", []),
+            Atom::Self_ => node("Self", []),
+            Atom::Super => node("Super", []),
+            
+            Atom::Identifier(name) => node("Identifier", [("name", self.name(name))]),
+            Atom::BooleanLiteral(value) => node("Boolean", [("value", Json::Bool(*value))]),
+            Atom::IntegerLiteral(value) => node("Integer", [("value", Json::Int(*value))]),
+            Atom::BigIntegerLiteral(text) => node("BigInteger", [("value", self.name(text))]),
+            Atom::FloatLiteral(value) => node("Float", [("value", Json::Float(*value))]),
+            Atom::StringLiteral(value) => node("String", [("value", self.name(value))]),
+            
+            Atom::Group { modifier, inner } => node("Group", [
+                ("modifier", modifier.as_ref().map(variant_name).into()),
+                ("inner", self.expr(inner)),
+            ]),
+        }
+    }
+    
+    fn primary(&self, primary: &Primary) -> Json {
+        let path = primary.path().iter().map(|item| match item {
+            AccessItem::OptionalChain => node("OptionalChain", []),
+            AccessItem::Attribute(name) => node("Attribute", [("name", self.name(name))]),
+            AccessItem::Index(index) => node("Index", [("index", self.expr_meta(index))]),
+            AccessItem::Invoke(args) => node("Invoke", [("args", self.exprs(args))]),
+            AccessItem::InvokeTable(items) => node("InvokeTable", [("items", self.table_items(items))]),
+        });
+        
+        node("Primary", [
+            ("atom", self.atom(primary.atom())),
+            ("path", Json::Array(path.collect())),
+        ])
+    }
+    
+    fn table_items(&self, items: &[TableItem]) -> Json {
+        let items = items.iter().map(|item| {
+            let field = match &item.field {
+                TableField::Attribute(mode, name) => node("Attribute", [
+                    ("mode", variant_name(mode)),
+                    ("name", self.name(name)),
+                ]),
+                TableField::Index(index) => node("Index", [("index", self.expr_meta(index))]),
+            };
+            
+            Json::Object(vec![
+                ("field", field),
+                ("value", self.expr_meta(&item.value)),
+            ])
+        });
+        Json::Array(items.collect())
+    }
+    
+    fn pattern(&self, pattern: &Pattern) -> Json {
+        match pattern {
+            Pattern::Identifier(name) => node("Identifier", [("name", self.name(name))]),
+            
+            Pattern::Attribute(target) => node("Attribute", [
+                ("receiver", self.primary(&target.receiver)),
+                ("name", self.name(&target.name)),
+            ]),
+            
+            Pattern::Index(target) => node("Index", [
+                ("receiver", self.primary(&target.receiver)),
+                ("index", self.expr_meta(&target.index)),
+            ]),
+            
+            Pattern::Tuple(items) => node("Tuple", [
+                ("items", Json::Array(items.iter().map(|item| self.pattern(&item.pattern)).collect())),
+            ]),
+            
+            Pattern::Pack(pattern) => node("Pack", [
+                ("pattern", pattern.as_ref().map(|pattern| self.pattern(pattern)).into()),
+            ]),
+            
+            Pattern::Modifier { modifier, pattern } => node("Modifier", [
+                ("modifier", variant_name(modifier)),
+                ("pattern", self.pattern(pattern)),
+            ]),
+        }
+    }
+    
+    fn case_pattern(&self, pattern: &CasePattern) -> Json {
+        match pattern {
+            CasePattern::Wildcard => node("Wildcard", []),
+            
+            CasePattern::Binding(name) => node("Binding", [("name", self.name(name))]),
+            
+            CasePattern::Literal(atom) => node("Literal", [("value", self.atom(atom))]),
+            
+            CasePattern::Tuple(items) => node("Tuple", [
+                ("items", Json::Array(items.iter().map(|item| self.case_pattern(item)).collect())),
+            ]),
+            
+            CasePattern::Rest(name) => node("Rest", [
+                ("name", name.as_ref().map(|name| self.name(name)).into()),
+            ]),
+        }
+    }
+    
+    fn function_def(&self, fundef: &FunctionDef) -> Json {
+        let signature = &fundef.signature;
+        
+        let required = signature.required.iter().map(|param| Json::Object(vec![
+            ("name", self.name(&param.name)),
+            ("mode", variant_name(&param.mode)),
+        ]));
+        
+        let default = signature.default.iter().map(|param| Json::Object(vec![
+            ("name", self.name(&param.name)),
+            ("mode", variant_name(&param.mode)),
+            ("default", self.expr_meta(&param.default)),
+        ]));
+        
+        let variadic = signature.variadic.as_ref().map(|param| Json::Object(vec![
+            ("name", self.name(&param.name)),
+            ("mode", variant_name(&param.mode)),
+        ]));
+        
+        node("FunctionDef", [
+            ("name", signature.name.as_ref().map(|name| self.name(name)).into()),
+            ("required", Json::Array(required.collect())),
+            ("default", Json::Array(default.collect())),
+            ("variadic", variadic.into()),
+            ("body", self.expr_block(&fundef.body)),
+        ])
+    }
+    
+    fn class_def(&self, classdef: &ClassDef) -> Json {
+        node("ClassDef", [
+            ("name", classdef.name.as_ref().map(|name| self.name(name)).into()),
+            ("parent", classdef.parent.as_ref().map(|parent| self.expr_meta(parent)).into()),
+            ("methods", Json::Array(classdef.methods.iter().map(|method| self.function_def(method)).collect())),
+        ])
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::SourceText;
+    
+    fn to_json(text: &str) -> String {
+        let mut interner = StringInterner::new();
+        let ast = crate::parse_source(&mut interner, SourceText::from(text.to_string())).unwrap();
+        ast_to_json(&interner, &ast).to_string()
+    }
+    
+    #[test]
+    fn test_ast_to_json() {
+        assert_eq!(to_json("-x * 2"), concat!(
+            r#"[{"type":"Expression","expr":{"type":"BinaryOp","op":"Mul","#,
+            r#""lhs":{"type":"UnaryOp","op":"Neg","operand":{"type":"Identifier","name":"x","symbol":{"start":1,"end":2}},"#,
+            r#""symbol":{"start":0,"end":2}},"#,
+            r#""rhs":{"type":"Integer","value":2,"symbol":{"start":5,"end":6}}},"#,
+            r#""symbol":{"start":0,"end":6}}]"#,
+        ));
+        
+        assert_eq!(to_json("let a = f(1, 2.5)"), concat!(
+            r#"[{"type":"Expression","expr":{"type":"Assignment","action":"DeclImmutable","op":null,"#,
+            r#""lhs":{"type":"Identifier","name":"a"},"rhs":{"type":"Primary","atom":{"type":"Identifier","name":"f"},"#,
+            r#""path":[{"type":"Invoke","args":["#,
+            r#"{"type":"Integer","value":1,"symbol":{"start":10,"end":11}},"#,
+            r#"{"type":"Float","value":2.5,"symbol":{"start":13,"end":16}}"#,
+            r#"]}]}},"symbol":{"start":0,"end":17}}]"#,
+        ));
+    }
+    
+    #[test]
+    fn test_json_format() {
+        let json = Json::Object(vec![
+            ("text", Json::from("a \"b\"\n\\\u{1}")),
+            ("items", Json::Array(vec![Json::Int(1), Json::Float(FloatType::NAN), Json::Null])),
+            ("empty", Json::Array(Vec::new())),
+        ]);
+        
+        assert_eq!(json.to_string(), r#"{"text":"a \"b\"\n\\\u0001","items":[1,null,null],"empty":[]}"#);
+        
+        assert_eq!(format!("{:#}", json), concat!(
+            "{\n",
+            "  \"text\": \"a \\\"b\\\"\\n\\\\\\u0001\",\n",
+            "  \"items\": [\n",
+            "    1,\n",
+            "    null,\n",
+            "    null\n",
+            "  ],\n",
+            "  \"empty\": []\n",
+            "}",
+        ));
+    }
+}
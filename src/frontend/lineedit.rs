@@ -0,0 +1,913 @@
+//! A small line editor for the REPL.
+//!
+//! When stdin and stdout are both a terminal (on unix), input is read in raw mode so that it can be edited:
+//!
+//! * Left/Right, Home/End, Ctrl-B/F, Ctrl-A/E, and Alt-B/F move the cursor;
+//! * Backspace, Delete, Ctrl-D, Ctrl-K, Ctrl-U, and Ctrl-W delete text;
+//! * Up/Down or Ctrl-P/N go through the history, Ctrl-R searches it;
+//! * Tab completes the word before the cursor using the `Completer`, if there is one;
+//! * Ctrl-C discards the input, Ctrl-D on an empty line ends it, and Ctrl-L clears the screen.
+//!
+//! The input and prompts can also be colored as they are drawn, using a `Highlighter`.
+//!
+//! Otherwise lines are read as they are, so that input can be piped into the REPL.
+//!
+//! An entry in the history may span more than one line (e.g. a block that needed continuation lines),
+//! in which case it is recalled and edited as a whole.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+
+/// The maximum number of entries that are kept in the history
+pub const HISTORY_LIMIT: usize = 1000;
+
+pub enum ReadResult {
+    Line(String),
+    Interrupted,  // the input was discarded with Ctrl-C
+    Eof,
+}
+
+/// Provides the candidates for Tab completion
+pub trait Completer {
+    /// Complete the word that ends the text, which is all of the input before the cursor.
+    /// If there is no word to complete this returns `None`, and Tab inserts indentation instead.
+    fn complete(&self, text: &str) -> Option<Completion>;
+}
+
+pub struct Completion {
+    pub start: usize,  // the byte offset in the text where the word to be completed starts
+    pub candidates: Vec<String>,
+}
+
+/// Colors the input while it is edited.
+/// Other than adding ANSI escape sequences, the highlighted text must be the same as the original.
+pub trait Highlighter {
+    /// If the text has more than one line, the colors should be reset at the end of each line
+    fn highlight(&self, text: &str) -> String;
+    
+    fn highlight_prompt(&self, prompt: &str) -> String {
+        prompt.to_string()
+    }
+}
+
+pub struct LineEditor {
+    history: History,
+    continue_prompt: String,  // shown in front of each line after the first of a multi-line entry
+    completer: Option<Box<dyn Completer>>,
+    highlighter: Option<Box<dyn Highlighter>>,
+}
+
+impl LineEditor {
+    pub fn new(continue_prompt: &str) -> Self {
+        Self {
+            history: History::new(),
+            continue_prompt: continue_prompt.to_string(),
+            completer: None,
+            highlighter: None,
+        }
+    }
+    
+    pub fn with_completer(mut self, completer: impl Completer + 'static) -> Self {
+        self.completer = Some(Box::new(completer)); self
+    }
+    
+    pub fn with_highlighter(mut self, highlighter: impl Highlighter + 'static) -> Self {
+        self.highlighter = Some(Box::new(highlighter)); self
+    }
+    
+    /// Load the history from a file, which is also updated whenever an entry is added
+    pub fn with_history_file(mut self, path: PathBuf) -> Self {
+        self.history.load(path); self
+    }
+    
+    pub fn history(&self) -> &[String] { &self.history.entries }
+    
+    /// Add a complete input to the history, which may have more than one line
+    pub fn add_history(&mut self, entry: &str) {
+        self.history.push(entry)
+    }
+    
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<ReadResult> {
+        #[cfg(unix)]
+        if terminal::is_terminal() {
+            let raw_mode = terminal::RawMode::enable()?;
+            let result = Editor::new(self, prompt).run();
+            drop(raw_mode);
+            return result;
+        }
+        
+        read_line_plain(prompt)
+    }
+}
+
+fn read_line_plain(prompt: &str) -> io::Result<ReadResult> {
+    let mut stdout = io::stdout();
+    stdout.write_all(prompt.as_bytes())?;
+    stdout.flush()?;
+    
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(ReadResult::Eof);
+    }
+    
+    if line.ends_with('\n') {
+        line.pop();
+    }
+    Ok(ReadResult::Line(line))
+}
+
+
+// History
+
+struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    fn new() -> Self {
+        Self { entries: Vec::new(), path: None }
+    }
+    
+    // a missing or unreadable history file just means there is no history yet
+    fn load(&mut self, path: PathBuf) {
+        if let Ok(contents) = fs::read_to_string(&path) {
+            self.entries = contents.lines()
+                .filter(|line| !line.is_empty())
+                .map(unescape_entry)
+                .collect();
+            self.truncate();
+        }
+        self.path.replace(path);
+    }
+    
+    fn push(&mut self, entry: &str) {
+        let entry = entry.trim_end();
+        if entry.is_empty() || self.entries.last().map(String::as_str) == Some(entry) {
+            return;
+        }
+        
+        self.entries.push(entry.to_string());
+        self.truncate();
+        
+        // failing to save the history should not interrupt the session
+        if let Err(error) = self.save() {
+            log::warn!("could not save history: {}", error);
+        }
+    }
+    
+    fn truncate(&mut self) {
+        if self.entries.len() > HISTORY_LIMIT {
+            self.entries.drain(..(self.entries.len() - HISTORY_LIMIT));
+        }
+    }
+    
+    fn save(&self) -> io::Result<()> {
+        let path = match self.path.as_ref() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        
+        let mut contents = String::new();
+        for entry in self.entries.iter() {
+            contents.push_str(&escape_entry(entry));
+            contents.push('\n');
+        }
+        fs::write(path, contents)
+    }
+    
+    // find the most recent entry before the given index that contains the query
+    fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before].iter().rposition(|entry| entry.contains(query))
+    }
+}
+
+// history entries are saved one per line, so newlines are escaped
+fn escape_entry(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_entry(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            entry.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => entry.push('\n'),
+            Some(other) => entry.push(other),
+            None => entry.push('\\'),
+        }
+    }
+    entry
+}
+
+
+// Editing
+
+#[derive(Debug, Default)]
+struct LineBuffer {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl LineBuffer {
+    fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+    
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+    
+    fn is_empty(&self) -> bool { self.chars.is_empty() }
+    
+    fn insert(&mut self, ch: char) {
+        self.chars.insert(self.cursor, ch);
+        self.cursor += 1;
+    }
+    
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+    
+    fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+    
+    fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+    
+    fn move_right(&mut self) {
+        self.cursor = usize::min(self.cursor + 1, self.chars.len());
+    }
+    
+    // home and end apply to the line that the cursor is on
+    fn move_home(&mut self) {
+        self.cursor = self.chars[..self.cursor].iter().rposition(|ch| *ch == '\n')
+            .map_or(0, |index| index + 1);
+    }
+    
+    fn move_end(&mut self) {
+        self.cursor = self.chars[self.cursor..].iter().position(|ch| *ch == '\n')
+            .map_or(self.chars.len(), |index| self.cursor + index);
+    }
+    
+    fn word_start(&self) -> usize {
+        let mut index = self.cursor;
+        while index > 0 && !self.chars[index - 1].is_alphanumeric() {
+            index -= 1;
+        }
+        while index > 0 && self.chars[index - 1].is_alphanumeric() {
+            index -= 1;
+        }
+        index
+    }
+    
+    fn word_end(&self) -> usize {
+        let mut index = self.cursor;
+        while index < self.chars.len() && !self.chars[index].is_alphanumeric() {
+            index += 1;
+        }
+        while index < self.chars.len() && self.chars[index].is_alphanumeric() {
+            index += 1;
+        }
+        index
+    }
+    
+    fn move_word_left(&mut self) {
+        self.cursor = self.word_start();
+    }
+    
+    fn move_word_right(&mut self) {
+        self.cursor = self.word_end();
+    }
+    
+    fn delete_word(&mut self) {
+        let start = self.word_start();
+        self.chars.drain(start..self.cursor);
+        self.cursor = start;
+    }
+    
+    fn kill_to_end(&mut self) {
+        let cursor = self.cursor;
+        self.move_end();
+        self.chars.drain(cursor..self.cursor);
+        self.cursor = cursor;
+    }
+    
+    fn kill_to_start(&mut self) {
+        let cursor = self.cursor;
+        self.move_home();
+        self.chars.drain(self.cursor..cursor);
+    }
+    
+    fn indent(&mut self) {
+        (0..4).for_each(|_| self.insert(' '))
+    }
+    
+    // replaces the word before the cursor with the longest prefix shared by all of the candidates,
+    // returns false if that would not add anything to the word
+    fn complete(&mut self, completion: &Completion) -> bool {
+        let text = self.chars[..self.cursor].iter().collect::<String>();
+        let word_len = text[completion.start..].chars().count();
+        
+        let prefix = common_prefix(&completion.candidates);
+        if prefix.chars().count() <= word_len {
+            return false;
+        }
+        
+        self.chars.drain(self.cursor - word_len..self.cursor);
+        self.cursor -= word_len;
+        prefix.chars().for_each(|ch| self.insert(ch));
+        true
+    }
+}
+
+fn common_prefix(candidates: &[String]) -> &str {
+    let mut prefix = match candidates.first() {
+        Some(first) => first.as_str(),
+        None => return "",
+    };
+    
+    for candidate in candidates[1..].iter() {
+        let len = prefix.char_indices().zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(usize::min(prefix.len(), candidate.len()), |((index, _), _)| index);
+        prefix = &prefix[..len];
+    }
+    prefix
+}
+
+// lists the candidates in as many columns as will fit in the width, with a newline after each row
+fn format_candidates(candidates: &[String], width: usize) -> String {
+    let column_width = candidates.iter().map(|candidate| candidate.chars().count()).max().unwrap_or(0) + 2;
+    let columns = usize::max(1, width / column_width);
+    
+    let mut output = String::new();
+    for row in candidates.chunks(columns) {
+        for (index, candidate) in row.iter().enumerate() {
+            if index + 1 < row.len() {
+                output.push_str(&format!("{:1$}", candidate, column_width));
+            } else {
+                output.push_str(candidate);
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    WordLeft,
+    WordRight,
+    Ctrl(char),
+    Unknown,
+}
+
+#[cfg(unix)]
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+// returns None at the end of input
+#[cfg(unix)]
+fn read_key(input: &mut impl Read) -> io::Result<Option<Key>> {
+    let byte = match read_byte(input)? {
+        Some(byte) => byte,
+        None => return Ok(None),
+    };
+    
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x08 | 0x7F => Key::Backspace,
+        0x1B => read_escape(input)?,
+        0x01..=0x1A => Key::Ctrl(char::from(b'a' + byte - 1)),
+        0x00..=0x1F => Key::Unknown,
+        
+        _ => {
+            // decode the rest of a UTF-8 sequence
+            let len = match byte {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                match read_byte(input)? {
+                    Some(byte) => bytes.push(byte),
+                    None => break,
+                }
+            }
+            match String::from_utf8(bytes) {
+                Ok(string) => string.chars().next().map_or(Key::Unknown, Key::Char),
+                Err(..) => Key::Unknown,
+            }
+        }
+    };
+    Ok(Some(key))
+}
+
+#[cfg(unix)]
+fn read_escape(input: &mut impl Read) -> io::Result<Key> {
+    let key = match read_byte(input)? {
+        Some(b'b') => Key::WordLeft,
+        Some(b'f') => Key::WordRight,
+        
+        Some(b'O') => match read_byte(input)? {
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            _ => Key::Unknown,
+        },
+        
+        Some(b'[') => {
+            // CSI sequences end with a byte in the range 0x40-0x7E
+            let mut params = Vec::new();
+            let last = loop {
+                match read_byte(input)? {
+                    Some(byte @ 0x40..=0x7E) => break byte,
+                    Some(byte) => params.push(byte),
+                    None => return Ok(Key::Unknown),
+                }
+            };
+            
+            match (params.as_slice(), last) {
+                (_, b'A') => Key::Up,
+                (_, b'B') => Key::Down,
+                (b"1;3" | b"1;5", b'C') => Key::WordRight,
+                (b"1;3" | b"1;5", b'D') => Key::WordLeft,
+                (_, b'C') => Key::Right,
+                (_, b'D') => Key::Left,
+                (_, b'H') => Key::Home,
+                (_, b'F') => Key::End,
+                (b"1" | b"7", b'~') => Key::Home,
+                (b"4" | b"8", b'~') => Key::End,
+                (b"3", b'~') => Key::Delete,
+                _ => Key::Unknown,
+            }
+        },
+        
+        _ => Key::Unknown,
+    };
+    Ok(key)
+}
+
+
+// the state of a single call to read_line() in raw mode
+#[cfg(unix)]
+struct Editor<'e> {
+    editor: &'e mut LineEditor,
+    prompt: &'e str,
+    buffer: LineBuffer,
+    draft: String,  // the input that was being edited before moving through the history
+    history_index: usize,
+    search: Option<String>,  // the query while searching the history
+    cursor_row: usize,  // the row of the cursor, relative to the first row of the prompt
+}
+
+#[cfg(unix)]
+impl<'e> Editor<'e> {
+    fn new(editor: &'e mut LineEditor, prompt: &'e str) -> Self {
+        let history_index = editor.history.entries.len();
+        Self {
+            editor, prompt, history_index,
+            buffer: LineBuffer::default(),
+            draft: String::new(),
+            search: None,
+            cursor_row: 0,
+        }
+    }
+    
+    fn run(mut self) -> io::Result<ReadResult> {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        
+        self.refresh()?;
+        loop {
+            let key = match read_key(&mut input)? {
+                Some(key) => key,
+                None => return Ok(ReadResult::Eof),
+            };
+            
+            if self.search.is_some() && !self.search_key(&key) {
+                self.refresh()?;
+                continue;
+            }
+            
+            match key {
+                Key::Enter => {
+                    self.buffer.cursor = self.buffer.chars.len();
+                    self.refresh()?;
+                    io::stdout().write_all(b"\r\n")?;
+                    return Ok(ReadResult::Line(self.buffer.text()));
+                },
+                
+                Key::Ctrl('c') => {
+                    self.buffer.cursor = self.buffer.chars.len();
+                    self.refresh()?;
+                    io::stdout().write_all(b"^C\r\n")?;
+                    return Ok(ReadResult::Interrupted);
+                },
+                
+                Key::Ctrl('d') if self.buffer.is_empty() => {
+                    io::stdout().write_all(b"\r\n")?;
+                    return Ok(ReadResult::Eof);
+                },
+                
+                Key::Tab => self.complete()?,
+                
+                key => self.edit(key),
+            }
+            self.refresh()?;
+        }
+    }
+    
+    fn edit(&mut self, key: Key) {
+        let buffer = &mut self.buffer;
+        match key {
+            Key::Char(ch) => buffer.insert(ch),
+            Key::Backspace => buffer.backspace(),
+            Key::Delete | Key::Ctrl('d') => buffer.delete(),
+            Key::Left | Key::Ctrl('b') => buffer.move_left(),
+            Key::Right | Key::Ctrl('f') => buffer.move_right(),
+            Key::Home | Key::Ctrl('a') => buffer.move_home(),
+            Key::End | Key::Ctrl('e') => buffer.move_end(),
+            Key::WordLeft => buffer.move_word_left(),
+            Key::WordRight => buffer.move_word_right(),
+            Key::Ctrl('k') => buffer.kill_to_end(),
+            Key::Ctrl('u') => buffer.kill_to_start(),
+            Key::Ctrl('w') => buffer.delete_word(),
+            Key::Up | Key::Ctrl('p') => self.recall(self.history_index.checked_sub(1)),
+            Key::Down | Key::Ctrl('n') => self.recall(self.history_index.checked_add(1)),
+            Key::Ctrl('r') => self.search = Some(String::new()),
+            Key::Ctrl('l') => {
+                // the prompt is redrawn at the top of the screen
+                let _ = io::stdout().write_all(b"\x1B[H\x1B[2J");
+                self.cursor_row = 0;
+            },
+            _ => { },
+        }
+    }
+    
+    fn complete(&mut self) -> io::Result<()> {
+        let text = self.buffer.chars[..self.buffer.cursor].iter().collect::<String>();
+        let completion = self.editor.completer.as_ref()
+            .and_then(|completer| completer.complete(&text));
+        
+        let completion = match completion {
+            Some(completion) => completion,
+            None => {
+                self.buffer.indent();
+                return Ok(());
+            },
+        };
+        
+        if self.buffer.complete(&completion) {
+            return Ok(());
+        }
+        match completion.candidates.len() {
+            0 => io::stdout().write_all(b"\x07"),  // nothing matched, ring the bell
+            1 => Ok(()),
+            _ => self.show_candidates(&completion.candidates),
+        }
+    }
+    
+    // lists the candidates below the input, which is then redrawn after them
+    fn show_candidates(&mut self, candidates: &[String]) -> io::Result<()> {
+        let layout = self.layout(self.prompt);
+        let (end_row, _) = layout.position(&self.buffer.chars);
+        
+        let mut output = String::new();
+        if end_row > self.cursor_row {
+            output.push_str(&format!("\x1B[{}B", end_row - self.cursor_row));
+        }
+        output.push_str("\r\n");
+        output.push_str(&format_candidates(candidates, layout.width).replace('\n', "\r\n"));
+        self.cursor_row = 0;
+        
+        let mut stdout = io::stdout();
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()
+    }
+    
+    fn recall(&mut self, index: Option<usize>) {
+        let entries = &self.editor.history.entries;
+        let index = match index {
+            Some(index) if index <= entries.len() => index,
+            _ => return,
+        };
+        
+        if self.history_index == entries.len() {
+            self.draft = self.buffer.text();
+        }
+        self.history_index = index;
+        
+        match entries.get(index) {
+            Some(entry) => self.buffer.set(entry),
+            None => self.buffer.set(&self.draft),
+        }
+    }
+    
+    // handles a key while searching, returns true if the search is over and the key should be handled normally
+    fn search_key(&mut self, key: &Key) -> bool {
+        let query = self.search.as_mut().unwrap();
+        let start = match key {
+            Key::Char(ch) => {
+                query.push(*ch);
+                self.history_index + 1
+            },
+            Key::Backspace => {
+                query.pop();
+                self.editor.history.entries.len()
+            },
+            Key::Ctrl('r') => self.history_index,
+            Key::Ctrl('g') => {
+                self.search = None;
+                self.buffer.set(&self.draft);
+                self.history_index = self.editor.history.entries.len();
+                return false;
+            },
+            
+            // accept the match
+            _ => {
+                self.search = None;
+                return true;
+            },
+        };
+        
+        let history = &self.editor.history;
+        let start = usize::min(start, history.entries.len());
+        if self.history_index == history.entries.len() {
+            self.draft = self.buffer.text();
+        }
+        if let Some(index) = history.search(query, start) {
+            self.history_index = index;
+            self.buffer.set(&history.entries[index]);
+        }
+        false
+    }
+    
+    fn layout(&self, prompt: &str) -> Layout {
+        Layout {
+            width: terminal::width(),
+            prompt: prompt.chars().count(),
+            continue_prompt: self.editor.continue_prompt.chars().count(),
+        }
+    }
+    
+    // redraw everything, starting from the first row of the prompt
+    fn refresh(&mut self) -> io::Result<()> {
+        let search_prompt;
+        let prompt = match self.search.as_ref() {
+            Some(query) => {
+                search_prompt = format!("(reverse-i-search)`{}': ", query);
+                search_prompt.as_str()
+            },
+            None => self.prompt,
+        };
+        
+        let highlighter = self.editor.highlighter.as_deref();
+        let continue_prompt = &self.editor.continue_prompt;
+        let (styled_prompt, continue_prompt, input) = match highlighter {
+            Some(highlighter) => (
+                if self.search.is_some() { prompt.to_string() } else { highlighter.highlight_prompt(prompt) },
+                highlighter.highlight_prompt(continue_prompt),
+                highlighter.highlight(&self.buffer.text()),
+            ),
+            None => (prompt.to_string(), continue_prompt.clone(), self.buffer.text()),
+        };
+        
+        let mut output = String::new();
+        if self.cursor_row > 0 {
+            output.push_str(&format!("\x1B[{}A", self.cursor_row));
+        }
+        output.push_str("\r\x1B[J");
+        output.push_str(&styled_prompt);
+        output.push_str(&input.replace('\n', &format!("\r\n{}", continue_prompt)));
+        
+        let layout = self.layout(prompt);
+        let (end_row, end_col) = layout.position(&self.buffer.chars);
+        let (cursor_row, cursor_col) = layout.position(&self.buffer.chars[..self.buffer.cursor]);
+        
+        // the terminal doesn't move to the next row until something is written there
+        if end_col == 0 && end_row > 0 && self.buffer.chars.last() != Some(&'\n') {
+            output.push_str("\r\n");
+        }
+        
+        if end_row > cursor_row {
+            output.push_str(&format!("\x1B[{}A", end_row - cursor_row));
+        }
+        output.push('\r');
+        if cursor_col > 0 {
+            output.push_str(&format!("\x1B[{}C", cursor_col));
+        }
+        self.cursor_row = cursor_row;
+        
+        let mut stdout = io::stdout();
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()
+    }
+}
+
+// computes where text ends up on the screen, assuming every character is one column wide
+struct Layout {
+    width: usize,
+    prompt: usize,
+    continue_prompt: usize,
+}
+
+impl Layout {
+    // the row and column after the given text, relative to the start of the prompt
+    fn position(&self, text: &[char]) -> (usize, usize) {
+        let (mut row, mut col) = (self.prompt / self.width, self.prompt % self.width);
+        
+        // a line that exactly fills the width only wraps when the next character is written,
+        // so a newline right after it doesn't start another row
+        let mut pending_wrap = false;
+        for ch in text.iter() {
+            if *ch == '\n' {
+                if !pending_wrap {
+                    row += 1;
+                }
+                col = self.continue_prompt;
+            } else {
+                col += 1;
+            }
+            
+            pending_wrap = false;
+            if col >= self.width {
+                row += col / self.width;
+                col %= self.width;
+                pending_wrap = col == 0;
+            }
+        }
+        (row, col)
+    }
+}
+
+
+#[cfg(unix)]
+mod terminal {
+    use std::io;
+    
+    pub(super) fn is_terminal() -> bool {
+        unsafe { libc::isatty(libc::STDIN_FILENO) == 1 && libc::isatty(libc::STDOUT_FILENO) == 1 }
+    }
+    
+    pub(super) fn width() -> usize {
+        let mut size: libc::winsize = unsafe { core::mem::zeroed() };
+        let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if result == 0 && size.ws_col > 0 { usize::from(size.ws_col) } else { 80 }
+    }
+    
+    /// Puts the terminal into raw mode until dropped
+    pub(super) struct RawMode {
+        original: libc::termios,
+    }
+    
+    impl RawMode {
+        pub(super) fn enable() -> io::Result<Self> {
+            let mut original: libc::termios = unsafe { core::mem::zeroed() };
+            if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            
+            // output processing is left on, so that "\n" still moves to the start of the next line
+            let mut raw = original;
+            raw.c_iflag &= !(libc::BRKINT | libc::ICRNL | libc::INPCK | libc::ISTRIP | libc::IXON);
+            raw.c_cflag |= libc::CS8;
+            raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            
+            if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { original })
+        }
+    }
+    
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original); }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn buffer(text: &str, cursor: usize) -> LineBuffer {
+        LineBuffer { chars: text.chars().collect(), cursor }
+    }
+    
+    #[test]
+    fn test_history_entry_escaping() {
+        for entry in ["if x then\n    y\nend", "\"a\\nb\"", "trailing \\"] {
+            let escaped = escape_entry(entry);
+            assert!(!escaped.contains('\n'));
+            assert_eq!(unescape_entry(&escaped), entry);
+        }
+    }
+    
+    #[test]
+    fn test_history_search() {
+        let mut history = History::new();
+        for entry in ["let x = 1", "x + 1", "let y = x", "let y = x"] {
+            history.push(entry);
+        }
+        assert_eq!(history.entries.len(), 3);  // consecutive duplicates are skipped
+        
+        assert_eq!(history.search("let", 3), Some(2));
+        assert_eq!(history.search("let", 2), Some(0));
+        assert_eq!(history.search("z", 3), None);
+    }
+    
+    #[test]
+    fn test_line_buffer_editing() {
+        let mut line = buffer("let value = 1", 11);
+        line.delete_word();
+        assert_eq!(line.text(), "let  1");
+        assert_eq!(line.cursor, 4);
+        
+        line.move_word_right();
+        assert_eq!(line.cursor, 6);
+        line.kill_to_start();
+        assert_eq!(line.text(), "");
+        
+        // home and end stay on the current line of a multi-line entry
+        let mut line = buffer("if x then\n    y\nend", 14);
+        line.move_home();
+        assert_eq!(line.cursor, 10);
+        line.move_end();
+        assert_eq!(line.cursor, 15);
+        line.kill_to_start();
+        assert_eq!(line.text(), "if x then\n\nend");
+    }
+    
+    #[test]
+    fn test_line_buffer_completion() {
+        let completion = |start, candidates: &[&str]| Completion {
+            start, candidates: candidates.iter().map(|s| s.to_string()).collect(),
+        };
+        
+        let mut line = buffer("print(le)", 8);
+        assert!(line.complete(&completion(6, &["len"])));
+        assert_eq!(line.text(), "print(len)");
+        assert_eq!(line.cursor, 9);
+        
+        // only the shared prefix is inserted
+        let mut line = buffer("x.co", 4);
+        assert!(!line.complete(&completion(2, &["contains", "count"])));
+        
+        let mut line = buffer("x.", 2);
+        assert!(line.complete(&completion(2, &["contains", "count"])));
+        assert_eq!(line.text(), "x.co");
+        
+        let candidates = ["push", "pop", "print"].map(String::from);
+        assert_eq!(format_candidates(&candidates, 80), "push   pop    print\n");
+        assert_eq!(format_candidates(&candidates, 16), "push   pop\nprint\n");
+    }
+    
+    #[test]
+    fn test_layout_wraps_long_lines() {
+        let layout = Layout { width: 10, prompt: 4, continue_prompt: 4 };
+        let text = |s: &str| s.chars().collect::<Vec<char>>();
+        
+        assert_eq!(layout.position(&text("abc")), (0, 7));
+        assert_eq!(layout.position(&text("abcdef")), (1, 0));
+        assert_eq!(layout.position(&text("abcdefghijk")), (1, 5));
+        assert_eq!(layout.position(&text("ab\ncd")), (1, 6));
+        assert_eq!(layout.position(&text("abcdef\ncd")), (1, 6));
+    }
+}
@@ -1,11 +1,14 @@
+use core::mem::{self, Discriminant};
+use std::collections::HashMap;
 use string_interner::symbol::SymbolUsize;
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell};
 
 use crate::lexer::{LexerBuilder, Token};
 use crate::lexer::rules::{SingleCharRule, MultiCharRule};
-use crate::lexer::rules::keywords::KeywordRule;
+use crate::lexer::rules::keywords::KeywordTrieRule;
 use crate::lexer::rules::literals::*;
 use crate::lexer::rules::literals::string::*;
+use crate::parser::operator::{UnaryOp, BinaryOp, Precedence, PRECEDENCE_START, PRECEDENCE_END};
 
 
 // Internal representation for integers
@@ -31,6 +34,9 @@ pub static COMMENT_CHAR: char = '#';
 pub static NESTED_COMMENT_START: &str = "#{";
 pub static NESTED_COMMENT_END:   &str = "}#";
 
+// A line at the very start of a script that begins with this is ignored, so that scripts can be made executable
+pub static SHEBANG: &str = "#!";
+
 
 // Variable access modes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -63,6 +69,7 @@ pub fn all_escape_sequences() -> impl Iterator<Item=&'static dyn EscapeSequence>
                 Box::new(CharMapEscape::new('n', "\n")),
                 Box::new(CharMapEscape::new('r', "\r")),
                 Box::new(HexByteEscape::new()),
+                Box::new(UnicodeEscape::new()),
             ];
             
             escapes
@@ -72,94 +79,225 @@ pub fn all_escape_sequences() -> impl Iterator<Item=&'static dyn EscapeSequence>
 }
 
 
-// Tokens
-pub fn create_default_lexer_rules() -> LexerBuilder {
-    LexerBuilder::new()
+// Keywords, Operators, and Punctuation
+
+// What a fixed token is used for, which determines how it is parsed and highlighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenRole {
+    Punctuation,
+    Keyword,
+    
+    // keywords that are values, like "true"
+    Literal,
     
+    Operator {
+        unary: Option<UnaryOp>,
+        binary: Option<(BinaryOp, Precedence)>,
+    },
+    
+    // "=" and the augmented assignment operators, with the operation that is applied when assigning
+    Assignment(Option<BinaryOp>),
+}
+
+// A token that is always spelled the same way
+pub struct FixedToken {
+    pub token: Token,
+    pub text: &'static str,
+    pub role: TokenRole,
+}
+
+impl FixedToken {
+    // keywords must start at a word boundary, so that they don't match the start of an identifier
+    pub fn is_keyword(&self) -> bool {
+        self.text.starts_with(char::is_alphabetic)
+    }
+}
+
+const fn punctuation(token: Token, text: &'static str) -> FixedToken {
+    FixedToken { token, text, role: TokenRole::Punctuation }
+}
+
+const fn keyword(token: Token, text: &'static str) -> FixedToken {
+    FixedToken { token, text, role: TokenRole::Keyword }
+}
+
+const fn literal(token: Token, text: &'static str) -> FixedToken {
+    FixedToken { token, text, role: TokenRole::Literal }
+}
+
+const fn unary(token: Token, text: &'static str, op: UnaryOp) -> FixedToken {
+    FixedToken { token, text, role: TokenRole::Operator { unary: Some(op), binary: None } }
+}
+
+const fn binary(token: Token, text: &'static str, op: BinaryOp, level: Precedence) -> FixedToken {
+    FixedToken { token, text, role: TokenRole::Operator { unary: None, binary: Some((op, level)) } }
+}
+
+const fn unary_binary(token: Token, text: &'static str, unary: UnaryOp, binary: BinaryOp, level: Precedence) -> FixedToken {
+    FixedToken { token, text, role: TokenRole::Operator { unary: Some(unary), binary: Some((binary, level)) } }
+}
+
+const fn assignment(token: Token, text: &'static str, op: Option<BinaryOp>) -> FixedToken {
+    FixedToken { token, text, role: TokenRole::Assignment(op) }
+}
+
+// The lexer rules, the parser's operator precedence, and the syntax highlighter are all derived from this table.
+// Binary operators with a lower precedence level bind more tightly.
+pub static FIXED_TOKENS: &[FixedToken] = &[
+
     // Punctuation
-    .add_rule(SingleCharRule::new(Token::OpenParen,       '('))
-    .add_rule(SingleCharRule::new(Token::CloseParen,      ')'))
-    .add_rule(SingleCharRule::new(Token::OpenBrace,       '{'))
-    .add_rule(SingleCharRule::new(Token::CloseBrace,      '}'))
-    .add_rule(SingleCharRule::new(Token::OpenSquare,      '['))
-    .add_rule(SingleCharRule::new(Token::CloseSquare,     ']'))
-    .add_rule(SingleCharRule::new(Token::Comma,           ','))
-    .add_rule(SingleCharRule::new(Token::Colon,           ':'))
-    .add_rule(SingleCharRule::new(Token::Semicolon,       ';'))
-    .add_rule(SingleCharRule::new(Token::Decorator,       '@'))
-    
-    .add_rule(MultiCharRule::new(Token::Ellipsis,         "..."))
-    
-    // Assignment and access operators
-    .add_rule(SingleCharRule::new(Token::OpAssign,        '='))
-    .add_rule(SingleCharRule::new(Token::OpAccess,        '.'))
+    punctuation(Token::OpenParen,       "("),
+    punctuation(Token::CloseParen,      ")"),
+    punctuation(Token::OpenBrace,       "{"),
+    punctuation(Token::CloseBrace,      "}"),
+    punctuation(Token::OpenSquare,      "["),
+    punctuation(Token::CloseSquare,     "]"),
+    punctuation(Token::Comma,           ","),
+    punctuation(Token::Colon,           ":"),
+    punctuation(Token::Semicolon,       ";"),
+    punctuation(Token::Decorator,       "@"),
+    punctuation(Token::Ellipsis,        "..."),
+    punctuation(Token::OpAccess,        "."),
+    punctuation(Token::OpOptAccess,     "?."),
     
     // Arithmetic and comparison operators
-    .add_rule(MultiCharRule::new(Token::OpExp,            "**"))
-    
-    .add_rule(SingleCharRule::new(Token::OpAdd,           '+'))
-    .add_rule(SingleCharRule::new(Token::OpSub,           '-'))
-    .add_rule(SingleCharRule::new(Token::OpMul,           '*'))
-    .add_rule(SingleCharRule::new(Token::OpDiv,           '/'))
-    .add_rule(SingleCharRule::new(Token::OpMod,           '%'))
-    .add_rule(SingleCharRule::new(Token::OpInv,           '~'))
-    .add_rule(SingleCharRule::new(Token::OpAnd,           '&'))
-    .add_rule(SingleCharRule::new(Token::OpOr,            '|'))
-    .add_rule(SingleCharRule::new(Token::OpXor,           '^'))
-    
-    .add_rule(SingleCharRule::new(Token::OpLT,            '<'))
-    .add_rule(SingleCharRule::new(Token::OpGT,            '>'))
-    
-    .add_rule(MultiCharRule::new(Token::OpLE,             "<="))
-    .add_rule(MultiCharRule::new(Token::OpGE,             ">="))
-    .add_rule(MultiCharRule::new(Token::OpEQ,             "=="))
-    .add_rule(MultiCharRule::new(Token::OpNE,             "!="))
-    
-    .add_rule(MultiCharRule::new(Token::OpAddAssign,      "+="))
-    .add_rule(MultiCharRule::new(Token::OpSubAssign,      "-="))
-    .add_rule(MultiCharRule::new(Token::OpMulAssign,      "*="))
-    .add_rule(MultiCharRule::new(Token::OpDivAssign,      "/="))
-    .add_rule(MultiCharRule::new(Token::OpModAssign,      "%="))
-    .add_rule(MultiCharRule::new(Token::OpAndAssign,      "&="))
-    .add_rule(MultiCharRule::new(Token::OpOrAssign,       "|="))
-    .add_rule(MultiCharRule::new(Token::OpXorAssign,       "^="))
-    .add_rule(MultiCharRule::new(Token::OpLShiftAssign,   "<<="))
-    .add_rule(MultiCharRule::new(Token::OpRShiftAssign,   ">>="))
-    
-    .add_rule(MultiCharRule::new(Token::OpLShift,         "<<"))
-    .add_rule(MultiCharRule::new(Token::OpRShift,         ">>"))
+    binary(Token::OpExp,                "**",   BinaryOp::Exp,      PRECEDENCE_END),
+    
+    binary(Token::OpMul,                "*",    BinaryOp::Mul,      1),
+    binary(Token::OpDiv,                "/",    BinaryOp::Div,      1),
+    binary(Token::OpMod,                "%",    BinaryOp::Mod,      1),
+    binary(Token::OpFloorDiv,           "//",   BinaryOp::FloorDiv, 1),
+    
+    unary_binary(Token::OpAdd,          "+",    UnaryOp::Pos,   BinaryOp::Add,  2),
+    unary_binary(Token::OpSub,          "-",    UnaryOp::Neg,   BinaryOp::Sub,  2),
+    
+    binary(Token::OpLShift,             "<<",   BinaryOp::LShift,   3),
+    binary(Token::OpRShift,             ">>",   BinaryOp::RShift,   3),
+    
+    binary(Token::OpAnd,                "&",    BinaryOp::BitAnd,   4),
+    binary(Token::OpXor,                "^",    BinaryOp::BitXor,   5),
+    binary(Token::OpOr,                 "|",    BinaryOp::BitOr,    6),
+    
+    // "x |> f(y)" is parsed as "f(x, y)"
+    binary(Token::OpPipe,               "|>",   BinaryOp::Pipe,     7),
+    
+    binary(Token::OpLT,                 "<",    BinaryOp::LT,       8),
+    binary(Token::OpGT,                 ">",    BinaryOp::GT,       8),
+    binary(Token::OpLE,                 "<=",   BinaryOp::LE,       8),
+    binary(Token::OpGE,                 ">=",   BinaryOp::GE,       8),
+    binary(Token::In,                   "in",   BinaryOp::In,       8),
+    
+    // "not" followed by "in" is the "not in" operator
+    unary_binary(Token::Not,            "not",  UnaryOp::Not,   BinaryOp::NotIn,    8),
+    
+    binary(Token::OpEQ,                 "==",   BinaryOp::EQ,       9),
+    binary(Token::OpNE,                 "!=",   BinaryOp::NE,       9),
+    binary(Token::Is,                   "is",   BinaryOp::Is,       9),
+    
+    binary(Token::And,                  "and",  BinaryOp::And,      10),
+    binary(Token::Or,                   "or",   BinaryOp::Or,       11),
+    binary(Token::OpCoalesce,           "??",   BinaryOp::Coalesce, PRECEDENCE_START),
+    
+    unary(Token::OpInv,                 "~",    UnaryOp::Inv),
+    
+    // Assignment operators
+    assignment(Token::OpAssign,         "=",    None),
+    assignment(Token::OpAddAssign,      "+=",   Some(BinaryOp::Add)),
+    assignment(Token::OpSubAssign,      "-=",   Some(BinaryOp::Sub)),
+    assignment(Token::OpMulAssign,      "*=",   Some(BinaryOp::Mul)),
+    assignment(Token::OpDivAssign,      "/=",   Some(BinaryOp::Div)),
+    assignment(Token::OpModAssign,      "%=",   Some(BinaryOp::Mod)),
+    assignment(Token::OpFloorDivAssign, "//=",  Some(BinaryOp::FloorDiv)),
+    assignment(Token::OpExpAssign,      "**=",  Some(BinaryOp::Exp)),
+    assignment(Token::OpAndAssign,      "&=",   Some(BinaryOp::BitAnd)),
+    assignment(Token::OpOrAssign,       "|=",   Some(BinaryOp::BitOr)),
+    assignment(Token::OpXorAssign,      "^=",   Some(BinaryOp::BitXor)),
+    assignment(Token::OpLShiftAssign,   "<<=",  Some(BinaryOp::LShift)),
+    assignment(Token::OpRShiftAssign,   ">>=",  Some(BinaryOp::RShift)),
     
     // Keywords
-    .add_rule(KeywordRule::new(Token::And,                "and"))
-    .add_rule(KeywordRule::new(Token::Or,                 "or"))
-    .add_rule(KeywordRule::new(Token::Not,                "not"))
-    .add_rule(KeywordRule::new(Token::True,               "true"))
-    .add_rule(KeywordRule::new(Token::False,              "false"))
-    .add_rule(KeywordRule::new(Token::Nil,                "nil"))
-    .add_rule(KeywordRule::new(Token::Let,                "let"))
-    .add_rule(KeywordRule::new(Token::Var,                "var"))
-    .add_rule(KeywordRule::new(Token::Local,              "local"))
-    .add_rule(KeywordRule::new(Token::NonLocal,           "nonlocal"))
-    .add_rule(KeywordRule::new(Token::Del,                "del"))
-    .add_rule(KeywordRule::new(Token::Begin,              "begin"))
-    .add_rule(KeywordRule::new(Token::If,                 "if"))
-    .add_rule(KeywordRule::new(Token::Then,               "then"))
-    .add_rule(KeywordRule::new(Token::Elif,               "elif"))
-    .add_rule(KeywordRule::new(Token::Else,               "else"))
-    .add_rule(KeywordRule::new(Token::Loop,               "loop"))
-    .add_rule(KeywordRule::new(Token::While,              "while"))
-    .add_rule(KeywordRule::new(Token::For,                "for"))
-    .add_rule(KeywordRule::new(Token::In,                 "in"))
-    .add_rule(KeywordRule::new(Token::Do,                 "do"))
-    .add_rule(KeywordRule::new(Token::Continue,           "continue"))
-    .add_rule(KeywordRule::new(Token::Break,              "break"))
-    .add_rule(KeywordRule::new(Token::Return,             "return"))
-    .add_rule(KeywordRule::new(Token::Fun,                "fun"))
-    .add_rule(KeywordRule::new(Token::Class,              "class"))
-    // .add_rule(KeywordRule::new(Token::Self_,              "self"))
-    // .add_rule(KeywordRule::new(Token::Super,              "super"))
-    .add_rule(KeywordRule::new(Token::Assert,             "assert"))
-    .add_rule(KeywordRule::new(Token::End,                "end"))
+    literal(Token::True,                "true"),
+    literal(Token::False,               "false"),
+    literal(Token::Nil,                 "nil"),
+    keyword(Token::Let,                 "let"),
+    keyword(Token::Var,                 "var"),
+    keyword(Token::Local,               "local"),
+    keyword(Token::NonLocal,            "nonlocal"),
+    keyword(Token::Del,                 "del"),
+    keyword(Token::Begin,               "begin"),
+    keyword(Token::If,                  "if"),
+    keyword(Token::Then,                "then"),
+    keyword(Token::Elif,                "elif"),
+    keyword(Token::Else,                "else"),
+    keyword(Token::Loop,                "loop"),
+    keyword(Token::While,               "while"),
+    keyword(Token::For,                 "for"),
+    keyword(Token::Do,                  "do"),
+    keyword(Token::Continue,            "continue"),
+    keyword(Token::Break,               "break"),
+    keyword(Token::Return,              "return"),
+    keyword(Token::Try,                 "try"),
+    keyword(Token::Except,              "except"),
+    keyword(Token::Finally,             "finally"),
+    keyword(Token::Raise,               "raise"),
+    keyword(Token::As,                  "as"),
+    keyword(Token::Fun,                 "fun"),
+    keyword(Token::Class,               "class"),
+    keyword(Token::Match,               "match"),
+    keyword(Token::Case,                "case"),
+    keyword(Token::Self_,               "self"),
+    keyword(Token::Super,               "super"),
+    keyword(Token::Assert,              "assert"),
+    keyword(Token::Import,              "import"),
+    keyword(Token::Export,              "export"),
+    keyword(Token::End,                 "end"),
+];
+
+static TOKEN_LOOKUP: Lazy<HashMap<Discriminant<Token>, &'static FixedToken>> = Lazy::new(|| {
+    FIXED_TOKENS.iter().map(|fixed| (mem::discriminant(&fixed.token), fixed)).collect()
+});
+
+static BINARY_PRECEDENCE: Lazy<HashMap<BinaryOp, Precedence>> = Lazy::new(|| {
+    FIXED_TOKENS.iter()
+        .filter_map(|fixed| match fixed.role {
+            TokenRole::Operator { binary, .. } => binary,
+            _ => None,
+        })
+        .collect()
+});
+
+pub fn fixed_token(token: &Token) -> Option<&'static FixedToken> {
+    TOKEN_LOOKUP.get(&mem::discriminant(token)).copied()
+}
+
+pub fn token_role(token: &Token) -> Option<TokenRole> {
+    fixed_token(token).map(|fixed| fixed.role)
+}
+
+pub fn binary_precedence(op: BinaryOp) -> Precedence {
+    *BINARY_PRECEDENCE.get(&op).expect("every binary operator has a token")
+}
+
+
+// Tokens
+pub fn create_default_lexer_rules() -> LexerBuilder {
+    let mut builder = LexerBuilder::new();
+    
+    // Punctuation and operators
+    for fixed in FIXED_TOKENS.iter().filter(|fixed| !fixed.is_keyword()) {
+        let mut chars = fixed.text.chars();
+        builder = match (chars.next(), chars.next()) {
+            (Some(ch), None) => builder.add_rule(SingleCharRule::new(fixed.token.clone(), ch)),
+            _ => builder.add_rule(MultiCharRule::new(fixed.token.clone(), fixed.text)),
+        };
+    }
+    
+    // Keywords
+    let keywords = FIXED_TOKENS.iter()
+        .filter(|fixed| fixed.is_keyword())
+        .map(|fixed| (fixed.token.clone(), fixed.text));
+    
+    builder.add_rule(KeywordTrieRule::new(keywords))
     
     // Identifiers and literals
     .add_rule(IdentifierRule::new())
@@ -169,6 +307,8 @@ pub fn create_default_lexer_rules() -> LexerBuilder {
     .add_rule(PrefixedIntegerLiteralRule::new("0b", 2))
     .add_rule(FloatLiteralRule::new())
     .add_rule(StringLiteralRule::new(all_escape_sequences()))
+    .add_rule(TripleQuotedStringRule::new(all_escape_sequences()))
+    .add_rule(InterpolatedStringRule::new(all_escape_sequences()))
     .add_rule(LabelRule::new("::"))
     
 }
@@ -1,11 +1,15 @@
 use std::io;
-use core::iter::{Iterator, Peekable};
+use std::rc::Rc;
+use core::iter::Iterator;
+use once_cell::unsync::OnceCell;
 use crate::language;
 use crate::debug::{DebugSymbol, TokenIndex, TokenLength};
 
 
 mod token;
 mod errors;
+mod input;
+mod stream;
 mod tests;
 
 pub mod rules;
@@ -15,6 +19,8 @@ use rules::comments::{LineCommentRule, BlockCommentRule};
 
 pub use token::*;
 pub use errors::*;
+pub use input::*;
+pub use stream::*;
 
 
 // Lexer Builder
@@ -27,6 +33,7 @@ pub struct LexerOptions {
 pub struct LexerBuilder {
     rules: Vec<Box<dyn LexerRule>>,
     options: LexerOptions,
+    dispatch: OnceCell<Rc<DispatchTable>>, // built on demand and shared by every Lexer from this builder
 }
 
 impl Default for LexerBuilder {
@@ -39,7 +46,8 @@ impl LexerBuilder {
             rules: Vec::new(),
             options: LexerOptions {
                 skip_comments: true,
-            }
+            },
+            dispatch: OnceCell::new(),
         }
     }
     
@@ -58,11 +66,13 @@ impl LexerBuilder {
     pub fn add_rule<R>(mut self, rule: R) -> Self
     where R: LexerRule + 'static {
         self.rules.push(Box::new(rule));
+        self.dispatch.take();
         self
     }
     
     pub fn insert_rule<R>(mut self, index: usize, rule: impl LexerRule + 'static) -> Self {
         self.rules.insert(index, Box::new(rule));
+        self.dispatch.take();
         self
     }
     
@@ -70,23 +80,103 @@ impl LexerBuilder {
         for rule in rules {
             self.rules.push(Box::new(rule));
         }
+        self.dispatch.take();
         self
     }
     
+    /// The keywords matched by the rules, in the order they were added
+    pub fn keywords(&self) -> impl Iterator<Item=&'static str> + '_ {
+        self.rules.iter().flat_map(|rule| rule.keywords().iter().copied())
+    }
+    
+    fn dispatch_table(&self) -> Rc<DispatchTable> {
+        self.dispatch.get_or_init(|| Rc::new(DispatchTable::new(&self.rules))).clone()
+    }
+    
     // less expensive than build(), but invalidates self
-    pub fn build_once<S>(self, source: S) -> Lexer<S> where S: Iterator<Item=io::Result<char>> {
+    pub fn build_once(self, source: &str) -> Lexer<StrInput<'_>> {
+        let dispatch = self.dispatch_table();
+        Lexer::with_dispatch(StrInput::new(source), self.options, self.rules, dispatch)
+    }
+    
+    pub fn build<'s>(&self, source: &'s str) -> Lexer<StrInput<'s>> {
+        self.build_input(StrInput::new(source))
+    }
+    
+    // lex text as it is read, without reading all of it up front
+    pub fn build_reader<R>(&self, reader: R) -> Lexer<ReaderInput<R>> where R: io::Read {
+        self.build_input(ReaderInput::new(reader))
+    }
+    
+    pub fn build_input<S>(&self, input: S) -> Lexer<S> where S: LexerInput {
+        Lexer::with_dispatch(input, self.options.clone(), self.rules.clone(), self.dispatch_table())
+    }
+}
+        
+// Dispatch Table
         
-        Lexer::new(source, self.options, self.rules.into_iter())
+// The rules that could match a token, looked up by the first char of the token.
+// Most tokens start with an ASCII char, so only those get an entry. Any other char falls back to trying every rule.
+struct DispatchTable {
+    ascii: Vec<Box<[RuleID]>>,
+    all: Box<[RuleID]>,
+}
+
+impl DispatchTable {
+    fn new(rules: &[Box<dyn LexerRule>]) -> Self {
+        let ascii = (0..=0x7F_u8).map(char::from)
+            .map(|ch| (0..rules.len()).filter(|&rule_id| rules[rule_id].can_start_with(ch)).collect())
+            .collect();
         
+        DispatchTable {
+            ascii,
+            all: (0..rules.len()).collect(),
+        }
     }
     
-    pub fn build<S>(&self, source: S) -> Lexer<S> where S: Iterator<Item=io::Result<char>> {
-        
-        Lexer::new(source, self.options.clone(), self.rules.clone().into_iter())
+    // rule ids are kept in order so priority is preserved
+    fn candidates(&self, first: char) -> &[RuleID] {
+        if first.is_ascii() {
+            &self.ascii[usize::from(first as u8)]
+        } else {
+            &self.all
+        }
+    }
+}
+
+// Embedded Source
+
+// embedded source can itself contain interpolated strings, each lexed by another nested lexer.
+// Past this depth the input is rejected, so that pathological nesting can't overflow the stack
+const MAX_EMBEDDED_DEPTH: usize = 100;
+
+// Lex the source text of an expression that is embedded inside of another token
+// The resulting tokens will have symbols relative to the start of the enclosing source text
+fn lex_embedded_source<S>(source: &str, start: TokenIndex, outer: &Lexer<S>) -> Result<Vec<TokenMeta>, LexerError> where S: LexerInput {
+    if outer.embedded >= MAX_EMBEDDED_DEPTH {
+        return Err(LexerError::new(ErrorKind::TooManyNestedStrings, DebugSymbol::new(start, 0)));
+    }
+    
+    let input = StrInput::new(source);
+    let mut lexer = Lexer::with_dispatch(input, outer.options.clone(), outer.rules.clone(), outer.dispatch.clone());
+    lexer.current = start;
+    lexer.newline = false;
+    lexer.embedded = outer.embedded + 1;
+    
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token()?;
+        let is_eof = matches!(token.token, Token::EOF);
+        tokens.push(token);
         
+        if is_eof {
+            break;
+        }
     }
+    Ok(tokens)
 }
 
+
 // Lexer
 
 fn split_array_pair_mut<T>(pair: &mut [T; 2]) -> (&mut T, &mut T) {
@@ -99,14 +189,16 @@ fn split_array_pair_mut<T>(pair: &mut [T; 2]) -> (&mut T, &mut T) {
 // instead of passing around references, we pass indices into the rules Vec instead
 type RuleID = usize;
 
-pub struct Lexer<S> where S: Iterator<Item=io::Result<char>> {
-    source: Peekable<S>,
+pub struct Lexer<S> where S: LexerInput {
+    source: S,
     options: LexerOptions,
     rules: Vec<Box<dyn LexerRule>>,
+    dispatch: Rc<DispatchTable>,
     
     current: TokenIndex, // one ahead of current char
     last: Option<char>,
     newline: bool,
+    embedded: usize, // how many levels of embedded source this lexer is nested inside of
     
     // internal state used by next_token(). 
     // putting these here instead to avoid unnecessary allocations
@@ -119,7 +211,7 @@ const THIS_CYCLE: usize = 0;
 const NEXT_CYCLE: usize = 1;
 
 
-impl<S> Iterator for Lexer<S> where S: Iterator<Item=io::Result<char>> {
+impl<S> Iterator for Lexer<S> where S: LexerInput {
     type Item = Result<TokenMeta, LexerError>;
     
     fn next(&mut self) -> Option<Self::Item> { Some(self.next_token()) }
@@ -127,85 +219,72 @@ impl<S> Iterator for Lexer<S> where S: Iterator<Item=io::Result<char>> {
 
 type PrevNextChars = (Option<char>, Option<char>);
 
-impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
+impl<S> Lexer<S> where S: LexerInput {
     
     pub fn new(source: S, options: LexerOptions, rules: impl Iterator<Item=Box<dyn LexerRule>>) -> Self {
+        let rules = rules.collect::<Vec<_>>();
+        let dispatch = Rc::new(DispatchTable::new(&rules));
+        Self::with_dispatch(source, options, rules, dispatch)
+    }
+    
+    fn with_dispatch(source: S, options: LexerOptions, rules: Vec<Box<dyn LexerRule>>, dispatch: Rc<DispatchTable>) -> Self {
         Lexer {
+            source,
             options,
-            source: source.peekable(),
-            rules: rules.collect(),
+            rules,
+            dispatch,
             
             current: 0,
             last: None,
             newline: true,
+            embedded: 0,
             active:   [Vec::new(), Vec::new()],
             complete: [Vec::new(), Vec::new()],
         }
     }
     
-    // grab the next character from source, transposing any io::Error and mapping it to LexerError
-    fn get_next(&mut self) -> Result<Option<char>, LexerError> {
-        match self.source.next() {
-            None => Ok(None),
-            Some(result) => match result {
-                Ok(c) => Ok(Some(c)),
-                Err(error) => Err(self.error(ErrorKind::IOError, self.current).caused_by(Box::new(error))),
-            },
-        }
-    }
-    
+    // peek at the next character from source, mapping any io::Error to LexerError
     fn peek_next(&mut self) -> Result<Option<char>, LexerError> {
-        let result = match self.source.peek() {
-            None => Ok(None),
-            Some(Ok(c)) => Ok(Some(*c)),
-            
-            // if there is an IO error we cannot process it yet, since peek() merely borrows the error
-            Some(Err(..)) => Err(()),
-        };
-        
-        result.map_err(|_| {
-            let ioerror = self.source.next().unwrap().unwrap_err();
-            self.error(ErrorKind::IOError, self.current).caused_by(Box::new(ioerror))
-        })
+        self.source.peek_char()
+            .map_err(|error| self.error(ErrorKind::IOError, self.current).caused_by(Box::new(error)))
     }
     
-    fn advance(&mut self) -> Result<PrevNextChars, LexerError> {
-        self.last = self.peek_next()?;
-        let next = self.get_next()?;
-        
-        if next.is_some() {
+    fn advance(&mut self) -> Result<Option<char>, LexerError> {
+        let next = self.peek_next()?;
+            
+        if let Some(ch) = next {
             if self.current == TokenIndex::MAX {
                 return Err(self.error(ErrorKind::SourceTooLong, self.current));
             }
             self.current += 1;
+            self.source.consume_char(ch);
         }
         
-        Ok((self.last, next))
+        self.last = next;
+        Ok(next)
     }
     
-    // these have to be &mut self because they can mutate the source iterator
+    // these have to be &mut self because they can read from the source
     fn peek(&mut self) -> Result<PrevNextChars, LexerError> {
         Ok((self.last, self.peek_next()?))
     }
     
     pub fn at_eof(&mut self) -> bool {
-        self.source.peek().is_none()
+        matches!(self.source.peek_char(), Ok(None))
     }
     
     fn skip_whitespace(&mut self) -> Result<(), LexerError> {
-        let mut next = self.peek_next()?;
-        while next.is_some() && next.unwrap().is_whitespace() {
+        while matches!(self.peek_next()?, Some(ch) if ch.is_whitespace()) {
             // consume whitespace and update self.newline
-            if let (_, Some('\n')) = self.advance()? {
+            if let Some('\n') = self.advance()? {
                 self.newline = true;
             }
-            next = self.peek_next()?;
         }
         Ok(())
     }
     
     fn skip_comments(&mut self) -> Result<bool, LexerError> {
-        let line_rule = LineCommentRule::new(language::COMMENT_CHAR);
+        let line_rule = LineCommentRule::new(language::COMMENT_CHAR).excluding(language::NESTED_COMMENT_START);
         let block_rule = BlockCommentRule::new(language::NESTED_COMMENT_START, language::NESTED_COMMENT_END);
         
         let mut line = Some(line_rule);
@@ -236,29 +315,51 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
             }
             
             // consume comment char and update self.newline
-            if let (_, Some('\n')) = self.advance()? {
+            if let Some('\n') = self.advance()? {
                 self.newline = true;
             }
         }
         
+        // only reachable at EOF, so report the comment instead of silently discarding the rest of the source
+        if matches!(block, Some(rule) if rule.depth() > 0) {
+            let length = TokenLength::try_from(self.current - start_pos).unwrap_or(TokenLength::MAX);
+            return Err(LexerError::new(ErrorKind::UnclosedComment, DebugSymbol::new(start_pos, length)));
+        }
+        
         // continue skipping if we are at not at EOF and we advanced
         let continue_ = !self.at_eof() && self.current > start_pos;
         
         Ok(continue_)
     }
 
-    fn reset_rules(&mut self) {
-        for rule in self.rules.iter_mut() {
-            rule.reset();
-        }
-        
+    fn clear_cycles(&mut self) {
         for idx in 0..2 {
             self.active[idx].clear();
             self.complete[idx].clear();
         }
     }
     
+    // the shebang line is only recognized at the very start of the source, so it is handled here instead of by a rule
+    fn skip_shebang(&mut self) -> Result<bool, LexerError> {
+        let is_shebang = self.source.starts_with(language::SHEBANG)
+            .map_err(|error| self.error(ErrorKind::IOError, self.current).caused_by(Box::new(error)))?;
+        
+        if !is_shebang {
+            return Ok(false);
+        }
+        
+        // like line comments, include the newline that ends it
+        while !matches!(self.advance()?, Some('\n') | None) { }
+        Ok(true)
+    }
+    
     pub fn next_token(&mut self) -> Result<TokenMeta, LexerError> {
+        if self.current == 0 && self.skip_shebang()? && !self.options.skip_comments {
+            let result = self.token_data(Token::Comment, 0);
+            self.newline = true;
+            return result;
+        }
+        
         self.skip_whitespace()?;
         
         if self.options.skip_comments {
@@ -277,7 +378,7 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
         
         //starting a new token
         let token_start = self.current;
-        self.reset_rules();
+        self.clear_cycles();
         
         // grab the next char, and feed it to all the rules
         // any rules that no longer match are discarded
@@ -301,8 +402,12 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
             },
         };
         
-        // generate rule ids
-        self.active[THIS_CYCLE].extend(0..self.rules.len());
+        // only the rules that can start with the first char need to be considered
+        let candidates = self.dispatch.candidates(next);
+        for &rule_id in candidates.iter() {
+            self.rules[rule_id].reset();
+        }
+        self.active[THIS_CYCLE].extend_from_slice(candidates);
         
         loop {
             
@@ -344,9 +449,8 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
                     
                     // if there is more than one complete rule, the lowest index takes priority!
                     let rule_id = *complete.iter().min().unwrap();
-                    let matching_rule = &mut self.rules[rule_id];
-                    let token = matching_rule.get_token()
-                        .map_err(|err| self.error(ErrorKind::CouldNotReadToken, token_start).caused_by(err))?;
+                    let token = self.rules[rule_id].get_token()
+                        .map_err(|err| self.token_error(rule_id, token_start, err))?;
                     
                     return self.token_data(token, token_start);
                 
@@ -360,7 +464,7 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
                 let next_active = &self.active[NEXT_CYCLE];
                 
                 if next_active.is_empty() {
-                    return Err(self.error(ErrorKind::NoMatchingRule, token_start));
+                    return Err(self.invalid_token(token_start)?);
                 } 
                 if next_active.len() == 1 {
                     let rule_id = next_active[0];
@@ -384,9 +488,8 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
             
             // if there is more than one complete rule, the lowest index takes priority!
             let rule_id = *next_complete.iter().min().unwrap();
-            let matching_rule = &mut self.rules[rule_id];
-            let token = matching_rule.get_token()
-                .map_err(|err| self.error(ErrorKind::CouldNotReadToken, token_start).caused_by(err))?;
+            let token = self.rules[rule_id].get_token()
+                .map_err(|err| self.token_error(rule_id, token_start, err))?;
             
             return self.token_data(token, token_start);
         }
@@ -420,7 +523,7 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
         let rule = &mut self.rules[rule_id];
         if matches!(rule.current_state(), MatchResult::CompleteMatch) {
             let token = rule.get_token()
-                .map_err(|err| self.error(ErrorKind::CouldNotReadToken, token_start).caused_by(err))?;
+                .map_err(|err| self.token_error(rule_id, token_start, err))?;
             
             return self.token_data(token, token_start);
         }
@@ -428,10 +531,44 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
         if self.at_eof() {
             Err(self.error(ErrorKind::UnexpectedEOF, token_start))
         } else {
-            Err(self.error(ErrorKind::NoMatchingRule, token_start))
+            Err(self.invalid_token(token_start)?)
         }
     }
     
+    // skip ahead to the next place a token could start, so that a run of invalid chars produces only one error
+    fn invalid_token(&mut self, token_start: TokenIndex) -> Result<LexerError, LexerError> {
+        loop {
+            let (prev, next) = self.peek()?;
+            let next = match next {
+                Some(ch) => ch,
+                None => break,
+            };
+            
+            if next.is_whitespace() || self.is_comment_start(next) || self.can_start_token(prev, next) {
+                break;
+            }
+            self.advance()?;
+        }
+        
+        Ok(self.error(ErrorKind::NoMatchingRule, token_start))
+    }
+    
+    fn is_comment_start(&self, next: char) -> bool {
+        self.options.skip_comments && (next == language::COMMENT_CHAR || language::NESTED_COMMENT_START.starts_with(next))
+    }
+    
+    // leaves the state of the rules that were tried behind, but scan_token() resets them anyways
+    fn can_start_token(&mut self, prev: Option<char>, next: char) -> bool {
+        for &rule_id in self.dispatch.candidates(next).iter() {
+            let rule = &mut self.rules[rule_id];
+            rule.reset();
+            if rule.try_match(prev, next).is_match() {
+                return true;
+            }
+        }
+        false
+    }
+    
     fn get_symbol(start_idx: TokenIndex, end_idx: TokenIndex) -> Result<DebugSymbol, LexerError> {
         let length = TokenLength::try_from(end_idx.saturating_sub(start_idx));
         let symbol = DebugSymbol::new(start_idx, length.unwrap_or(0));
@@ -445,12 +582,49 @@ impl<S> Lexer<S> where S: Iterator<Item=io::Result<char>> {
     
     fn token_data(&self, token: Token, token_start: TokenIndex) -> Result<TokenMeta, LexerError> {
         let symbol = Self::get_symbol(token_start, self.current)?;
+        
+        let token = match token {
+            Token::InterpolatedString(fragments) => {
+                let fragments = fragments.into_iter()
+                    .map(|fragment| self.lex_fragment(fragment, token_start))
+                    .collect::<Result<Vec<StrFragment>, LexerError>>()?;
+                
+                Token::InterpolatedString(fragments)
+            },
+            
+            token => token,
+        };
+        
         Ok(TokenMeta { token, symbol, newline: self.newline })
     }
     
+    fn lex_fragment(&self, fragment: StrFragment, token_start: TokenIndex) -> Result<StrFragment, LexerError> {
+        if let StrFragment::Source(source, offset) = fragment {
+            let start = TokenIndex::try_from(offset).ok()
+                .and_then(|offset| token_start.checked_add(offset))
+                .ok_or_else(|| self.error(ErrorKind::SourceTooLong, token_start))?;
+            
+            let tokens = lex_embedded_source(source.as_str(), start, self)?;
+            return Ok(StrFragment::Tokens(tokens));
+        }
+        Ok(fragment)
+    }
+    
     fn error(&self, kind: ErrorKind, token_start: TokenIndex) -> LexerError {
         let length = TokenLength::try_from(self.current.saturating_sub(token_start));
         let symbol = DebugSymbol::new(token_start, length.unwrap_or(0));
         LexerError::new(kind, symbol)
     }
+    
+    // if the rule can tell us which part of the token caused the error, point to just that part
+    fn token_error(&self, rule_id: RuleID, token_start: TokenIndex, err: Box<dyn std::error::Error>) -> LexerError {
+        let span = match self.rules[rule_id].error_span() {
+            Some(span) => span,
+            None => return self.error(ErrorKind::CouldNotReadToken, token_start).caused_by(err),
+        };
+        
+        let start = token_start.saturating_add(TokenIndex::try_from(span.offset).unwrap_or(TokenIndex::MAX));
+        let length = TokenLength::try_from(span.length).unwrap_or(TokenLength::MAX);
+        LexerError::new(ErrorKind::CouldNotReadToken, DebugSymbol::new(start, length)).caused_by(err)
+    }
 }
@@ -1,11 +1,13 @@
 use core::fmt;
+use std::rc::Rc;
 use std::error::Error;
 use crate::debug::DebugSymbol;
+use crate::debug::codes::{self, ErrorCode};
 
 
 // Lexer Errors
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ErrorKind {
     IOError,
     UnexpectedEOF,
@@ -13,6 +15,8 @@ pub enum ErrorKind {
     CouldNotReadToken,
     MaxTokenLengthExceeded,
     SourceTooLong,
+    UnclosedComment,
+    TooManyNestedStrings,
 }
 
 impl fmt::Display for ErrorKind {
@@ -24,17 +28,35 @@ impl fmt::Display for ErrorKind {
             Self::CouldNotReadToken => "invalid token",
             Self::MaxTokenLengthExceeded => "max token length exceeded",
             Self::SourceTooLong => "max source length exceeded",
+            Self::UnclosedComment => "block comment was never closed",
+            Self::TooManyNestedStrings => "interpolated strings are nested too deeply",
         };
         fmt.write_str(msg)
     }
 }
 
+impl ErrorKind {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::IOError => codes::E0001,
+            Self::UnexpectedEOF => codes::E0002,
+            Self::NoMatchingRule => codes::E0003,
+            Self::CouldNotReadToken => codes::E0004,
+            Self::MaxTokenLengthExceeded => codes::E0005,
+            Self::SourceTooLong => codes::E0006,
+            Self::UnclosedComment => codes::E0007,
+            Self::TooManyNestedStrings => codes::E0008,
+        }
+    }
+}
+
 
-#[derive(Debug)]
+// cloneable so that a TokenStream can replay errors after rolling back
+#[derive(Debug, Clone)]
 pub struct LexerError {
     kind: ErrorKind,
     symbol: DebugSymbol,
-    cause: Option<Box<dyn Error>>,
+    cause: Option<Rc<dyn Error>>,
 }
 
 impl LexerError {
@@ -46,7 +68,7 @@ impl LexerError {
     }
     
     pub fn caused_by(mut self, cause: Box<dyn Error>) -> Self {
-        self.cause = Some(cause); self
+        self.cause = Some(Rc::from(cause)); self
     }
     
     pub fn kind(&self) -> &ErrorKind { &self.kind }
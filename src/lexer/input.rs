@@ -0,0 +1,149 @@
+use std::io;
+use core::str;
+
+
+// The source of the chars consumed by a Lexer
+
+pub trait LexerInput {
+    // look at the next char without consuming it, or None at the end of the input
+    fn peek_char(&mut self) -> io::Result<Option<char>>;
+    
+    // consume the char that was returned by the last call to peek_char()
+    fn consume_char(&mut self, ch: char);
+    
+    // check if the remaining input starts with the given text, without consuming anything
+    fn starts_with(&mut self, prefix: &str) -> io::Result<bool>;
+}
+
+
+// Lexes text that is already in memory
+
+pub struct StrInput<'s> {
+    source: &'s str,
+    position: usize, // byte offset of the next char in source
+}
+
+impl<'s> StrInput<'s> {
+    pub fn new(source: &'s str) -> Self {
+        StrInput { source, position: 0 }
+    }
+}
+
+impl LexerInput for StrInput<'_> {
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        let next = match self.source.as_bytes().get(self.position) {
+            None => None,
+            Some(&byte) if byte.is_ascii() => Some(char::from(byte)),
+            Some(..) => self.source[self.position..].chars().next(),
+        };
+        Ok(next)
+    }
+    
+    fn consume_char(&mut self, ch: char) {
+        self.position += ch.len_utf8();
+    }
+    
+    fn starts_with(&mut self, prefix: &str) -> io::Result<bool> {
+        Ok(self.source[self.position..].starts_with(prefix))
+    }
+}
+
+
+// Lexes text as it is read, one chunk at a time, so that the whole text never has to be held in memory
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+pub struct ReaderInput<R> where R: io::Read {
+    reader: R,
+    buf: Vec<u8>,
+    position: usize, // byte offset of the next char in buf
+    done: bool, // the reader is exhausted, or failed and should not be read from again
+}
+
+impl<R> ReaderInput<R> where R: io::Read {
+    pub fn new(reader: R) -> Self {
+        ReaderInput {
+            reader,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            position: 0,
+            done: false,
+        }
+    }
+    
+    fn available(&self) -> usize { self.buf.len() - self.position }
+    
+    // read until at least `count` bytes are available, or the reader is exhausted
+    fn fill(&mut self, count: usize) -> io::Result<()> {
+        while self.available() < count && !self.done {
+            // discard the bytes that have already been consumed
+            self.buf.drain(..self.position);
+            self.position = 0;
+            
+            let len = self.buf.len();
+            self.buf.resize(len + CHUNK_SIZE, 0);
+            let result = self.reader.read(&mut self.buf[len..]);
+            
+            match result {
+                Ok(0) => {
+                    self.buf.truncate(len);
+                    self.done = true;
+                },
+                Ok(read) => self.buf.truncate(len + read),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => self.buf.truncate(len),
+                Err(error) => return Err(self.fail(error)),
+            }
+        }
+        Ok(())
+    }
+    
+    // nothing further is read after an error, so that the lexer can't get stuck repeating it
+    fn fail(&mut self, error: io::Error) -> io::Error {
+        self.buf.clear();
+        self.position = 0;
+        self.done = true;
+        error
+    }
+}
+
+// the number of bytes in the UTF-8 sequence started by the given byte (invalid bytes are left to from_utf8() to reject)
+fn utf8_width(first: u8) -> usize {
+    match first {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    }
+}
+
+impl<R> LexerInput for ReaderInput<R> where R: io::Read {
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        self.fill(1)?;
+        let first = match self.buf.get(self.position) {
+            None => return Ok(None),
+            Some(&byte) if byte.is_ascii() => return Ok(Some(char::from(byte))),
+            Some(&byte) => byte,
+        };
+        
+        // a multi-byte char may be split across chunks
+        let width = utf8_width(first);
+        self.fill(width)?;
+        
+        let end = self.buf.len().min(self.position + width);
+        match str::from_utf8(&self.buf[self.position..end]) {
+            Ok(decoded) if decoded.len() == width => Ok(decoded.chars().next()),
+            _ => {
+                let error = io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8");
+                Err(self.fail(error))
+            },
+        }
+    }
+    
+    fn consume_char(&mut self, ch: char) {
+        self.position += ch.len_utf8();
+    }
+    
+    fn starts_with(&mut self, prefix: &str) -> io::Result<bool> {
+        self.fill(prefix.len())?;
+        Ok(self.buf[self.position..].starts_with(prefix.as_bytes()))
+    }
+}
@@ -3,38 +3,30 @@ pub mod general;
 pub mod literals;
 pub mod keywords;
 pub mod comments;
+pub mod regex;
 
 pub use general::*;
 
 
 use std::error::Error;
+use unicode_xid::UnicodeXID;
 use crate::lexer::Token;
 
 // Helpers
 
-// "word" characters are the alphabetic/alphanumeric chars + '_' (underscore)
+// "word" characters are those that can appear in identifiers, following the Unicode XID classes + '_' (underscore)
 trait WordChar {
-    fn is_word_alphanumeric(&self) -> bool;
-    fn is_word_alphabetic(&self) -> bool;
-    fn is_word_ascii_alphanumeric(&self) -> bool;
-    fn is_word_ascii_alphabetic(&self) -> bool;
+    fn is_word_start(&self) -> bool;
+    fn is_word_continue(&self) -> bool;
 }
 
 impl WordChar for char {
-    fn is_word_alphanumeric(&self) -> bool {
-        *self == '_' || self.is_alphanumeric()
+    fn is_word_start(&self) -> bool {
+        *self == '_' || self.is_xid_start()
     }
-
-    fn is_word_alphabetic(&self) -> bool {
-        *self == '_' || self.is_alphabetic()
-    }
-
-    fn is_word_ascii_alphanumeric(&self) -> bool {
-        *self == '_' || self.is_ascii_alphanumeric()
-    }
-
-    fn is_word_ascii_alphabetic(&self) -> bool {
-        *self == '_' || self.is_ascii_alphabetic()
+    
+    fn is_word_continue(&self) -> bool {
+        self.is_xid_continue()
     }
 }
 
@@ -69,6 +61,13 @@ impl MatchResult {
 // Lexer Rules
 type TokenError = Box<dyn Error + 'static>;
 
+// identifies a part of a token, in chars relative to the start of the token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+}
+
 pub trait LexerRule: __LexerRule_Clone {
     fn reset(&mut self);
     
@@ -82,6 +81,21 @@ pub trait LexerRule: __LexerRule_Clone {
     // and produce an error if the Token could not be produced for some other reason
     // e.g. attempting to read an integer literal that overflows
     fn get_token(&self) -> Result<Token, TokenError>;
+    
+    // if get_token() produced an error, this can be used to narrow down where in the token the error occurred
+    fn error_span(&self) -> Option<Span> { None }
+    
+    // whether a token matched by this rule could start with the given char, used by the lexer to skip rules
+    // the default tries the char on a fresh copy of the rule, which assumes that `prev` is only ever used to
+    // reject the first char of a token (this is true of all the built-in rules)
+    fn can_start_with(&self, ch: char) -> bool {
+        let mut rule = self.__clone_box();
+        rule.reset();
+        rule.try_match(None, ch).is_match()
+    }
+    
+    // the keywords matched by this rule, if it only matches keywords
+    fn keywords(&self) -> &[&'static str] { &[] }
 }
 
 
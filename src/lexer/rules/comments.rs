@@ -10,12 +10,20 @@ pub struct LineCommentRule {
     // start -> if the comment has started
     // end   -> if the comment has ended
     state: (bool, bool),
-    comment: char
+    comment: char,
+    exclude: Option<StrMatcher<'static>>,
 }
 
 impl LineCommentRule {
     pub fn new(comment: char) -> Self {
-        LineCommentRule { comment, state: (false, false) }
+        LineCommentRule { comment, state: (false, false), exclude: None }
+    }
+    
+    // don't match comments that start with the given text, e.g. the start of a block comment
+    // otherwise a block comment that ends before the end of the line would lose to the longer line comment
+    pub fn excluding(mut self, prefix: &'static str) -> Self {
+        self.exclude = Some(StrMatcher::case_sensitive(prefix));
+        self
     }
     
     fn match_state(&self, state: (bool, bool)) -> MatchResult {
@@ -53,6 +61,9 @@ impl LineCommentRule {
 impl LexerRule for LineCommentRule {
     fn reset(&mut self) {
         self.state = (false, false);
+        if let Some(exclude) = self.exclude.as_mut() {
+            exclude.reset();
+        }
     }
     
     fn current_state(&self) -> MatchResult {
@@ -60,6 +71,12 @@ impl LexerRule for LineCommentRule {
     }
     
     fn try_match(&mut self, _prev: Option<char>, next: char) -> MatchResult {
+        if let Some(exclude) = self.exclude.as_mut() {
+            if exclude.last_match_result().is_match() && exclude.update_match(next).is_complete_match() {
+                return MatchResult::NoMatch;
+            }
+        }
+        
         let state = self.next_state(self.state, next);
         let match_result = self.match_state(state);
         
@@ -79,7 +96,7 @@ impl LexerRule for LineCommentRule {
 
 #[derive(Clone)]
 pub struct BlockCommentRule {
-    nestlevel: u32,
+    depth: u32, // how many block comments are open
     start: StrMatcher<'static>,
     end: StrMatcher<'static>,
 }
@@ -87,22 +104,25 @@ pub struct BlockCommentRule {
 impl BlockCommentRule {
     pub fn new(start: &'static str, end: &'static str) -> Self {
         BlockCommentRule {
-            nestlevel: 0,
+            depth: 0,
             start: StrMatcher::case_sensitive(start),
             end: StrMatcher::case_sensitive(end),
         }
     }
+    
+    // the number of nested comments that have been opened and not yet closed
+    pub fn depth(&self) -> u32 { self.depth }
 }
 
 impl LexerRule for BlockCommentRule {
     fn reset(&mut self) {
-        self.nestlevel = 0;
+        self.depth = 0;
         self.start.reset();
         self.end.reset();
     }
     
     fn current_state(&self) -> MatchResult {
-        if self.nestlevel > 0 {
+        if self.depth > 0 {
             return MatchResult::IncompleteMatch;
         }
         
@@ -117,17 +137,17 @@ impl LexerRule for BlockCommentRule {
 
         let start_result = self.start.try_match(next);
         if start_result.is_complete_match() {
-            self.nestlevel += 1;
+            self.depth += 1;
             self.start.reset();
             return MatchResult::IncompleteMatch;
         }
         
-        if self.nestlevel > 0 {
+        if self.depth > 0 {
             let end_result = self.end.try_match(next);
             if end_result.is_complete_match() {
-                self.nestlevel -= 1;
+                self.depth -= 1;
                 
-                if self.nestlevel > 0 {
+                if self.depth > 0 {
                     self.end.reset();
                 } else {
                     return MatchResult::CompleteMatch;
@@ -1,3 +1,4 @@
+use std::rc::Rc;
 use crate::lexer::Token;
 use crate::lexer::rules::{MatchResult, LexerRule, WordChar, TokenError};
 use crate::lexer::rules::strmatcher::StrMatcher;
@@ -8,6 +9,7 @@ use crate::lexer::rules::strmatcher::StrMatcher;
 #[derive(Clone)]
 pub struct KeywordRule {
     result: Token,
+    keyword: &'static str,
     matcher: StrMatcher<'static>,
 }
 
@@ -17,6 +19,7 @@ impl KeywordRule {
         
         KeywordRule {
             result,
+            keyword: target,
             matcher: StrMatcher::case_sensitive(target),
         }
     }
@@ -34,7 +37,7 @@ impl LexerRule for KeywordRule {
     fn try_match(&mut self, prev: Option<char>, next: char) -> MatchResult {
         if self.matcher.count() == 0 {
             let at_word_boundary = match prev {
-                Some(ch) => !ch.is_word_alphanumeric(),
+                Some(ch) => !ch.is_word_continue(),
                 None => true,
             };
             if !at_word_boundary {
@@ -49,4 +52,122 @@ impl LexerRule for KeywordRule {
         debug_assert!(self.current_state().is_complete_match());
         Ok(self.result.clone())
     }
-}
\ No newline at end of file
+    
+    fn keywords(&self) -> &[&'static str] { core::slice::from_ref(&self.keyword) }
+}
+
+// Matches any keyword from a set of keywords, stored in a trie.
+// This is equivalent to a KeywordRule for each keyword, but only has to follow one path through the trie
+// instead of feeding every char to every keyword
+
+#[derive(Clone)]
+pub struct KeywordTrieRule {
+    trie: Rc<KeywordTrie>,
+    node: NodeID,
+}
+
+type NodeID = usize;
+const ROOT: NodeID = 0;
+
+#[derive(Default)]
+struct TrieNode {
+    children: Vec<(char, NodeID)>,
+    result: Option<Token>,
+}
+
+struct KeywordTrie {
+    nodes: Vec<TrieNode>,
+    keywords: Vec<&'static str>,
+}
+
+impl KeywordTrie {
+    fn child(&self, node: NodeID, next: char) -> Option<NodeID> {
+        self.nodes[node].children.iter()
+            .find(|(ch, _)| *ch == next)
+            .map(|(_, child)| *child)
+    }
+    
+    fn insert(&mut self, result: Token, keyword: &'static str) {
+        let mut node = ROOT;
+        for next in keyword.chars() {
+            node = match self.child(node, next) {
+                Some(child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(TrieNode::default());
+                    self.nodes[node].children.push((next, child));
+                    child
+                }
+            };
+        }
+        
+        // if a keyword is repeated, the first one takes priority
+        if self.nodes[node].result.is_none() {
+            self.nodes[node].result = Some(result);
+        }
+        self.keywords.push(keyword);
+    }
+}
+
+impl KeywordTrieRule {
+    pub fn new(keywords: impl IntoIterator<Item=(Token, &'static str)>) -> Self {
+        let mut trie = KeywordTrie {
+            nodes: vec![ TrieNode::default() ],
+            keywords: Vec::new(),
+        };
+        
+        for (result, keyword) in keywords.into_iter() {
+            debug_assert!(!keyword.is_empty());
+            trie.insert(result, keyword);
+        }
+        
+        KeywordTrieRule {
+            trie: Rc::new(trie),
+            node: ROOT,
+        }
+    }
+}
+
+impl LexerRule for KeywordTrieRule {
+    fn reset(&mut self) {
+        self.node = ROOT;
+    }
+    
+    fn current_state(&self) -> MatchResult {
+        match self.trie.nodes[self.node].result {
+            Some(..) => MatchResult::CompleteMatch,
+            None => MatchResult::IncompleteMatch,
+        }
+    }
+    
+    fn try_match(&mut self, prev: Option<char>, next: char) -> MatchResult {
+        if self.node == ROOT {
+            let at_word_boundary = match prev {
+                Some(ch) => !ch.is_word_continue(),
+                None => true,
+            };
+            if !at_word_boundary {
+                return MatchResult::NoMatch; // must start first char at word boundary
+            }
+        }
+        
+        match self.trie.child(self.node, next) {
+            Some(child) => {
+                self.node = child;
+                self.current_state()
+            },
+            None => MatchResult::NoMatch,
+        }
+    }
+    
+    fn get_token(&self) -> Result<Token, TokenError> {
+        debug_assert!(self.current_state().is_complete_match());
+        Ok(self.trie.nodes[self.node].result.clone().unwrap())
+    }
+    
+    fn can_start_with(&self, ch: char) -> bool {
+        self.trie.child(ROOT, ch).is_some()
+    }
+    
+    fn keywords(&self) -> &[&'static str] { &self.trie.keywords }
+}
@@ -1,4 +1,6 @@
 use core::str::FromStr;
+#[cfg(feature = "bigint")]
+use core::num::IntErrorKind;
 use crate::language;
 use crate::lexer::Token;
 use crate::lexer::rules::{MatchResult, LexerRule, WordChar, TokenError};
@@ -23,7 +25,7 @@ impl IdentifierRule {
     }
 }
 
-// Identifiers are ( :alphanumeric: | '_' ), first character cannot be a digit
+// Identifiers are ( XID_Start | '_' ) XID_Continue*, so the first character cannot be a digit
 impl LexerRule for IdentifierRule {
     fn reset(&mut self) {
         self.buf.clear();
@@ -41,10 +43,10 @@ impl LexerRule for IdentifierRule {
         
         let valid;
         if self.buf.is_empty() {
-            let at_word_start = prev.map(|c| !c.is_word_ascii_alphanumeric()).unwrap_or(true);
-            valid = at_word_start && next.is_word_ascii_alphabetic();
+            let at_word_start = prev.map(|c| !c.is_word_continue()).unwrap_or(true);
+            valid = at_word_start && next.is_word_start();
         } else {
-            valid = next.is_word_ascii_alphanumeric();
+            valid = next.is_word_continue();
         }
         
         if valid {
@@ -97,7 +99,7 @@ impl LexerRule for LabelRule {
     
     fn try_match(&mut self, prev: Option<char>, next: char) -> MatchResult {
         // don't match if the last char was word alphanumeric
-        let at_word_start = prev.map(|c| !c.is_word_ascii_alphanumeric()).unwrap_or(true);
+        let at_word_start = prev.map(|c| !c.is_word_continue()).unwrap_or(true);
         
         if self.buf.is_empty() && self.prefix.count() == 0 && !at_word_start {
             return MatchResult::NoMatch;
@@ -111,7 +113,7 @@ impl LexerRule for LabelRule {
             return match_result;
         }
         
-        if next.is_word_ascii_alphanumeric() {
+        if next.is_word_continue() {
             self.buf.push(next);
             
             MatchResult::CompleteMatch
@@ -177,6 +179,9 @@ impl LexerRule for IntegerLiteralRule {
         match conversion {
             Ok(value) => Ok(Token::IntegerLiteral(value)),
             
+            #[cfg(feature = "bigint")]
+            Err(err) if *err.kind() == IntErrorKind::PosOverflow => Ok(Token::BigIntegerLiteral(self.buf.clone())),
+            
             // most likely the value overflowed language::IntType
             Err(err) => Err(Box::new(err)),
         }
@@ -240,6 +245,12 @@ impl LexerRule for PrefixedIntegerLiteralRule {
         match conversion {
             Ok(value) => Ok(Token::IntegerLiteral(value)),
             
+            #[cfg(feature = "bigint")]
+            Err(err) if *err.kind() == IntErrorKind::PosOverflow => {
+                let text = format!("{}{}", self.prefix.target(), self.buf);
+                Ok(Token::BigIntegerLiteral(text))
+            },
+            
             // most likely the value overflowed language::IntType
             Err(err) => Err(Box::new(err)),
         }
@@ -249,12 +260,24 @@ impl LexerRule for PrefixedIntegerLiteralRule {
 
 // Floating-Point Literals
 
+// the part of the float literal that is currently being read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FloatPart {
+    Start,
+    Integer,       // digits before the decimal point
+    LeadingPoint,  // a decimal point without any preceding digits, e.g. ".5"
+    Fraction,      // the decimal point, and any digits after it
+    ExpStart,      // "e" or "E"
+    ExpSign,       // "+" or "-" after the "e"
+    Exponent,      // digits after the "e"
+}
+
+// Float literals are of the form ( digits [ '.' digits? ] | '.' digits ) [ ( 'e' | 'E' ) [ '+' | '-' ] digits ]
+// an integer with an exponent, like 1e9, is also a float literal
 #[derive(Clone)]
 pub struct FloatLiteralRule {
     buf: String,
-    point: bool,
-    exp: bool,
-    last: Option<char>,
+    part: FloatPart,
 }
 
 impl Default for FloatLiteralRule {
@@ -265,26 +288,43 @@ impl FloatLiteralRule {
     pub fn new() -> Self {
         Self { 
             buf: String::new(), 
-            point: false,
-            exp: false,
-            last: None,
+            part: FloatPart::Start,
         }
     }
+    
+    fn next_part(&self, next: char) -> Option<FloatPart> {
+        let part = match (self.part, next) {
+            (FloatPart::Start, '.') => FloatPart::LeadingPoint,
+            (FloatPart::Integer, '.') => FloatPart::Fraction,
+            
+            // an exponent may not directly follow a decimal point, so that "1.e" is not ambiguous with attribute access
+            (FloatPart::Integer, 'e' | 'E') => FloatPart::ExpStart,
+            (FloatPart::Fraction, 'e' | 'E') if !self.buf.ends_with('.') => FloatPart::ExpStart,
+            
+            (FloatPart::ExpStart, '+' | '-') => FloatPart::ExpSign,
+            
+            (FloatPart::Start | FloatPart::Integer, ch) if ch.is_ascii_digit() => FloatPart::Integer,
+            (FloatPart::LeadingPoint | FloatPart::Fraction, ch) if ch.is_ascii_digit() => FloatPart::Fraction,
+            (FloatPart::ExpStart | FloatPart::ExpSign | FloatPart::Exponent, ch) if ch.is_ascii_digit() => FloatPart::Exponent,
+            
+            _ => return None,
+        };
+        Some(part)
+    }
 }
 
 impl LexerRule for FloatLiteralRule {
     fn reset(&mut self) {
         self.buf.clear();
-        self.point = false;
-        self.exp = false;
-        self.last = None;
+        self.part = FloatPart::Start;
     }
     
     fn current_state(&self) -> MatchResult {
-        if self.buf.is_empty() || matches!(self.last, Some('e' | 'E')) {
-            MatchResult::IncompleteMatch
-        } else {
-            MatchResult::CompleteMatch
+        match self.part {
+            FloatPart::Fraction | FloatPart::Exponent => MatchResult::CompleteMatch,
+            
+            // without a decimal point or exponent, this is just an integer
+            _ => MatchResult::IncompleteMatch,
         }
     }
     
@@ -293,34 +333,13 @@ impl LexerRule for FloatLiteralRule {
             return MatchResult::NoMatch;
         }
         
-        if next == '.' {
-            if self.point || self.exp {
-                return MatchResult::NoMatch;
-            }
-            
-            self.point = true;
-            self.buf.push(next);
-            self.last = Some(next);
-            return MatchResult::CompleteMatch;
-        }
-        
-        if matches!(next, 'e' | 'E') {
-            if self.exp {
-                return MatchResult::NoMatch;
-            }
-            
-            self.exp = true;
-            self.buf.push(next);
-            self.last = Some(next);
-            return MatchResult::IncompleteMatch;
-        }
-        
-        if next.is_ascii_digit() {
-            self.buf.push(next);
-            self.last = Some(next);
-            MatchResult::CompleteMatch
-        } else {
-            MatchResult::NoMatch
+        match self.next_part(next) {
+            Some(part) => {
+                self.part = part;
+                self.buf.push(next);
+                self.current_state()
+            },
+            None => MatchResult::NoMatch,
         }
     }
     
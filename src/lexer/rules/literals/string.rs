@@ -1,10 +1,12 @@
-use crate::lexer::Token;
-use crate::lexer::rules::{MatchResult, LexerRule, TokenError};
+use crate::lexer::{Token, StrFragment};
+use crate::lexer::rules::{MatchResult, LexerRule, TokenError, Span};
 
-// supports escape sequences that consist of a single-character tag (e.g. \t) and an optional fixed-length argument (e.g. \u0FFE, \xFE)
+// supports escape sequences that consist of a single-character tag (e.g. \t) and an optional argument (e.g. \xFE, \u{0FFE})
 pub trait EscapeSequence: Send + Sync {
     fn tag(&self) -> char;
-    fn arglen(&self) -> u8;
+    
+    // given the argument characters read so far, check if the argument is complete
+    fn is_arg_complete(&self, arg: &str) -> bool;
     
     // produce a string that will replace the escape sequence in the source literal
     fn transform(&self, arg: &str) -> Result<String, StringEscapeError>;
@@ -24,8 +26,8 @@ impl CharMapEscape {
 
 impl EscapeSequence for CharMapEscape {
     fn tag(&self) -> char { self.tag }
-    fn arglen(&self) -> u8 { 0 }
-    fn transform(&self, _arg: &str) -> Result<String, StringEscapeError> { 
+    fn is_arg_complete(&self, _arg: &str) -> bool { true }
+    fn transform(&self, _arg: &str) -> Result<String, StringEscapeError> {
         Ok(self.output.to_string())
     }
 }
@@ -44,12 +46,14 @@ impl HexByteEscape {
 const HEX_ESCAPE_TAG: char = 'x';
 impl EscapeSequence for HexByteEscape {
     fn tag(&self) -> char { HEX_ESCAPE_TAG }
-    fn arglen(&self) -> u8 { 2 }
-    fn transform(&self, arg: &str) -> Result<String, StringEscapeError> { 
-        debug_assert!(arg.len() == 2);
-        
+    fn is_arg_complete(&self, arg: &str) -> bool { arg.len() >= 2 }
+    fn transform(&self, arg: &str) -> Result<String, StringEscapeError> {
         let create_error = || StringEscapeError::new(StringEscapeErrorKind::InvalidEscapeArg, self.tag(), Some(arg.to_string()));
         
+        if arg.len() != 2 {
+            return Err(create_error());
+        }
+        
         let value = u8::from_str_radix(arg, 16)
             .map_err(|_err| create_error())?;
         
@@ -60,7 +64,45 @@ impl EscapeSequence for HexByteEscape {
     }
 }
 
-// TODO unicode escapes
+// \u{0} \u{10FFFF}
+pub struct UnicodeEscape {}
+
+impl Default for UnicodeEscape {
+    fn default() -> Self { Self::new() }
+}
+
+impl UnicodeEscape {
+    pub fn new() -> Self { UnicodeEscape { } }
+}
+
+const UNICODE_ESCAPE_TAG: char = 'u';
+const UNICODE_MAX_DIGITS: usize = 6;
+impl EscapeSequence for UnicodeEscape {
+    fn tag(&self) -> char { UNICODE_ESCAPE_TAG }
+    fn is_arg_complete(&self, arg: &str) -> bool {
+        match arg.chars().next() {
+            None => false,
+            Some('{') => arg.ends_with('}') || arg.len() > UNICODE_MAX_DIGITS + 1,
+            Some(..) => true,  // invalid, will produce an error
+        }
+    }
+    fn transform(&self, arg: &str) -> Result<String, StringEscapeError> {
+        let create_error = || StringEscapeError::new(StringEscapeErrorKind::InvalidEscapeArg, self.tag(), Some(arg.to_string()));
+        
+        let digits = arg.strip_prefix('{')
+            .and_then(|arg| arg.strip_suffix('}'))
+            .filter(|digits| !digits.is_empty() && digits.len() <= UNICODE_MAX_DIGITS)
+            .ok_or_else(create_error)?;
+        
+        let value = u32::from_str_radix(digits, 16)
+            .map_err(|_err| create_error())?;
+        
+        match char::from_u32(value) {
+            Some(ch) => Ok(ch.to_string()),
+            None => Err(create_error()),
+        }
+    }
+}
 
 
 const ESCAPE_CHAR: char = '\\';
@@ -79,18 +121,98 @@ impl core::ops::Deref for ActiveEscape {
     fn deref(&self) -> &'static Self::Target { self.escape }
 }
 
+enum Escaped {
+    Pending, // the char was consumed as part of an incomplete escape sequence
+    Output(String),
+    Error(StringEscapeError, Span),
+}
+
+// Processes escape sequences one char at a time, shared by all rules that produce string literals
+#[derive(Clone)]
+struct EscapeReader {
+    start: Option<usize>, // the position of the escape char, if an escape sequence is being read
+    active: Option<ActiveEscape>, // the escape sequence being read, once the tag is known
+    escapes: Vec<&'static dyn EscapeSequence>,
+}
+
+impl EscapeReader {
+    fn new(escapes: impl Iterator<Item=&'static dyn EscapeSequence>) -> Self {
+        EscapeReader {
+            start: None,
+            active: None,
+            escapes: escapes.collect(),
+        }
+    }
+    
+    fn reset(&mut self) {
+        self.start = None;
+        self.active = None;
+    }
+    
+    fn in_escape(&self) -> bool { self.start.is_some() }
+    
+    fn reading_arg(&self) -> bool { self.active.is_some() }
+    
+    // start reading an escape sequence, given the position of the escape char
+    fn begin(&mut self, start: usize) {
+        debug_assert!(!self.in_escape());
+        self.start = Some(start);
+    }
+    
+    fn lookup_escape_for_tag(&self, tag: char) -> Option<&'static dyn EscapeSequence> {
+        self.escapes.iter()
+            .find(|escape| tag == escape.tag())
+            .copied()
+    }
+    
+    fn feed(&mut self, next: char) -> Escaped {
+        debug_assert!(self.in_escape());
+        
+        if let Some(active) = self.active.as_mut() {
+            active.argbuf.push(next);
+        } else if let Some(escape) = self.lookup_escape_for_tag(next) {
+            self.active = Some(ActiveEscape { escape, argbuf: String::new() });
+        } else {
+            let span = Span { offset: self.start.take().unwrap(), length: 2 };
+            let error = StringEscapeError::new(StringEscapeErrorKind::InvalidEscapeTag, next, None);
+            return Escaped::Error(error, span);
+        }
+        
+        let active = self.active.as_ref().unwrap();
+        if active.is_arg_complete(active.argbuf.as_str()) {
+            self.finish()
+        } else {
+            Escaped::Pending
+        }
+    }
+    
+    // finishes the current escape sequence, even if the argument is not complete
+    fn finish(&mut self) -> Escaped {
+        let start = self.start.take().unwrap();
+        let active = self.active.take().unwrap();
+        
+        match active.transform(active.argbuf.as_str()) {
+            Ok(output) => Escaped::Output(output),
+            Err(error) => {
+                let span = Span { offset: start, length: 2 + active.argbuf.chars().count() };
+                Escaped::Error(error, span)
+            }
+        }
+    }
+}
+
+
 #[derive(Clone)]
 pub struct StringLiteralRule {
     raw_buf: String,
     escaped_buf: String,
+    count: usize, // number of chars consumed
     quote: Option<char>,
     closed: bool,
     
     raw: bool,
-    escape: Option<ActiveEscape>, // the currently active escape sequence, if any
-    error: Option<StringEscapeError>, // hold the first error to occur when processing an escape
-    
-    escapes: Vec<&'static dyn EscapeSequence>,
+    escapes: EscapeReader,
+    error: Option<(StringEscapeError, Span)>, // hold the first error to occur when processing an escape
 }
 
 impl StringLiteralRule {
@@ -98,44 +220,38 @@ impl StringLiteralRule {
         StringLiteralRule {
             raw_buf: String::new(),
             escaped_buf: String::new(),
+            count: 0,
             quote: None,
             closed: false,
             raw: false,
             
-            escape: None,
+            escapes: EscapeReader::new(escapes),
             error: None,
-            
-            escapes: escapes.collect(),
         }
     }
     
-    fn lookup_escape_for_tag(&self, tag: char) -> Option<&'static dyn EscapeSequence> {
-        self.escapes.iter()
-            .find(|escape| tag == escape.tag())
-            .copied()
-    }
-    
-    // can't take a reference to the escape or argument by parameter since it would cause borrow issues
-    // so we take an index to get the escape
-    // and we just have to get the argument directly from self
-    fn process_escape(&mut self, escape: &'static dyn EscapeSequence, arg: &str) {
-        match escape.transform(arg) {
-            Ok(output) => self.escaped_buf.push_str(output.as_str()),
-            Err(err) => self.error = Some(err),
-        };
+    fn process_escape(&mut self, escaped: Escaped) {
+        match escaped {
+            Escaped::Pending => { },
+            Escaped::Output(output) => self.escaped_buf.push_str(output.as_str()),
+            Escaped::Error(error, span) => if self.error.is_none() {
+                self.error = Some((error, span));
+            },
+        }
     }
 }
 
 impl LexerRule for StringLiteralRule {
-    
+
     fn reset(&mut self) {
         self.raw_buf.clear();
         self.escaped_buf.clear();
+        self.count = 0;
         self.quote = None;
         self.closed = false;
         self.raw = false;
         
-        self.escape = None;
+        self.escapes.reset();
         self.error = None;
     }
     
@@ -144,69 +260,58 @@ impl LexerRule for StringLiteralRule {
             None if self.closed => MatchResult::NoMatch,  // did not find initial quote
             None => MatchResult::IncompleteMatch,  // initial state
             
-            Some(..) if self.closed && self.escape.is_none() => MatchResult::CompleteMatch,
+            Some(..) if self.closed => MatchResult::CompleteMatch,
             Some(..) => MatchResult::IncompleteMatch,
         }
     }
     
-    fn try_match(&mut self, prev: Option<char>, next: char) -> MatchResult {
+    fn try_match(&mut self, _prev: Option<char>, next: char) -> MatchResult {
         if self.closed {
             return MatchResult::NoMatch;  // dont accept any further input
         }
         
+        let index = self.count;
+        
         // if we haven't read the first quote yet
         if self.quote.is_none() {
-            return match next {
-                
-                RAW_PREFIX => MatchResult::IncompleteMatch,
+            let result = match next {
+            
+                RAW_PREFIX if index == 0 => {
+                    self.raw = true;
+                    MatchResult::IncompleteMatch
+                },
                 
                 SINGLE_QUOTE | DOUBLE_QUOTE => {
                     self.quote = Some(next);
-                    if let Some(RAW_PREFIX) = prev {
-                        self.raw = true;
-                    }
-                    
                     MatchResult::IncompleteMatch
                 },
                 
                 _ => MatchResult::NoMatch,
-                
+            
             };
+            
+            if result.is_match() {
+                self.count += 1;
+            }
+            return result;
         }
         
-        // note: if there was an error, skip all escape handling and keep reading as if a raw string
-        if !self.raw && self.error.is_none() {
-            
-            // if we are already in an escape sequence
-            if let Some(ref mut active) = self.escape {
-                if active.argbuf.len() < active.arglen().into() {
-                    active.argbuf.push(next);
-                    
-                    self.raw_buf.push(next);
-                    return MatchResult::IncompleteMatch;
-                }
-                
-                // if we get here, argbuf already has enough characters without adding the next one
-                // process the escape and then do not return so that the next char is processed as normal
-                
-                let active = self.escape.take().unwrap(); // take out of self.escape as we are done with it
-                self.process_escape(active.escape, active.argbuf.as_str());
-                
-            // check for escape sequence start
-            } else if let Some(ESCAPE_CHAR) = prev {
-                
-                if let Some(escape) = self.lookup_escape_for_tag(next) {
-                    self.escape = Some(ActiveEscape { escape, argbuf: String::new() });
-                } else {
-                    self.error = Some(StringEscapeError::new(
-                        StringEscapeErrorKind::InvalidEscapeTag, next, None
-                    ));
-                }
+        self.count += 1;
+        
+        // if we are already in an escape sequence
+        if self.escapes.in_escape() {
+        
+            // a closing quote ends the escape sequence argument early
+            if self.escapes.reading_arg() && next == self.quote.unwrap() {
+                let escaped = self.escapes.finish();
+                self.process_escape(escaped);
+            } else {
+                let escaped = self.escapes.feed(next);
+                self.process_escape(escaped);
                 
                 self.raw_buf.push(next);
                 return MatchResult::IncompleteMatch;
             }
-            
         }
         
         // check for terminating quote
@@ -215,8 +320,10 @@ impl LexerRule for StringLiteralRule {
             return MatchResult::CompleteMatch;
         }
         
-        // inside the string
-        if next != ESCAPE_CHAR {
+        // check for escape sequence start
+        if next == ESCAPE_CHAR && !self.raw {
+            self.escapes.begin(index);
+        } else {
             self.escaped_buf.push(next);
         }
         self.raw_buf.push(next);
@@ -227,19 +334,493 @@ impl LexerRule for StringLiteralRule {
     fn get_token(&self) -> Result<Token, TokenError> {
         debug_assert!(self.current_state().is_complete_match());
         
-        if let Some(ref error) = self.error {
+        if let Some((ref error, ..)) = self.error {
             Err(Box::new(error.clone().with_raw(self.raw_buf.clone())))
         } else if self.raw {
             Ok(Token::StringLiteral(self.raw_buf.clone()))
         } else {
             Ok(Token::StringLiteral(self.escaped_buf.clone()))
         }
+    
+    }
+    
+    fn error_span(&self) -> Option<Span> {
+        self.error.as_ref().map(|(_, span)| *span)
+    }
+
+}
+
+
+// Triple-quoted string literals, e.g. """text""", which may span multiple lines
+
+const TRIPLE_QUOTE_LEN: usize = 3;
+
+#[derive(Clone)]
+pub struct TripleQuotedStringRule {
+    raw_buf: String,
+    escaped_buf: String,
+    count: usize, // number of chars consumed
+    quote: Option<char>,
+    open_quotes: usize, // number of opening quotes read so far
+    close_quotes: usize, // number of consecutive unescaped quotes read, that may be the closing quotes
+    closed: bool,
+    
+    raw: bool,
+    escapes: EscapeReader,
+    error: Option<(StringEscapeError, Span)>, // hold the first error to occur when processing an escape
+}
+
+impl TripleQuotedStringRule {
+    pub fn new(escapes: impl Iterator<Item=&'static dyn EscapeSequence>) -> Self {
+        TripleQuotedStringRule {
+            raw_buf: String::new(),
+            escaped_buf: String::new(),
+            count: 0,
+            quote: None,
+            open_quotes: 0,
+            close_quotes: 0,
+            closed: false,
+            raw: false,
+            
+            escapes: EscapeReader::new(escapes),
+            error: None,
+        }
+    }
+    
+    fn process_escape(&mut self, escaped: Escaped) {
+        match escaped {
+            Escaped::Pending => { },
+            Escaped::Output(output) => self.escaped_buf.push_str(output.as_str()),
+            Escaped::Error(error, span) => if self.error.is_none() {
+                self.error = Some((error, span));
+            },
+        }
+    }
+    
+    // quotes that turned out not to be the closing quotes are part of the string
+    fn flush_quotes(&mut self) {
+        let quote = self.quote.unwrap();
+        for _ in 0..self.close_quotes {
+            self.raw_buf.push(quote);
+            self.escaped_buf.push(quote);
+        }
+        self.close_quotes = 0;
+    }
+    
+    fn match_opening(&mut self, index: usize, next: char) -> MatchResult {
+        match next {
+            RAW_PREFIX if index == 0 => {
+                self.raw = true;
+                MatchResult::IncompleteMatch
+            },
+            
+            SINGLE_QUOTE | DOUBLE_QUOTE if self.quote.is_none() => {
+                self.quote = Some(next);
+                self.open_quotes = 1;
+                MatchResult::IncompleteMatch
+            },
+            
+            _ if Some(next) == self.quote => {
+                self.open_quotes += 1;
+                MatchResult::IncompleteMatch
+            },
+            
+            _ => MatchResult::NoMatch,
+        }
+    }
+}
+
+impl LexerRule for TripleQuotedStringRule {
+
+    fn reset(&mut self) {
+        self.raw_buf.clear();
+        self.escaped_buf.clear();
+        self.count = 0;
+        self.quote = None;
+        self.open_quotes = 0;
+        self.close_quotes = 0;
+        self.closed = false;
+        self.raw = false;
         
+        self.escapes.reset();
+        self.error = None;
+    }
+    
+    fn current_state(&self) -> MatchResult {
+        if self.closed {
+            MatchResult::CompleteMatch
+        } else {
+            MatchResult::IncompleteMatch
+        }
+    }
+    
+    fn try_match(&mut self, _prev: Option<char>, next: char) -> MatchResult {
+        if self.closed {
+            return MatchResult::NoMatch;  // dont accept any further input
+        }
+        
+        let index = self.count;
+        
+        if self.open_quotes < TRIPLE_QUOTE_LEN {
+            let result = self.match_opening(index, next);
+            if result.is_match() {
+                self.count += 1;
+            }
+            return result;
+        }
+        
+        self.count += 1;
+        
+        let quote = self.quote.unwrap();
+        
+        // if we are already in an escape sequence
+        if self.escapes.in_escape() {
+        
+            // a quote ends the escape sequence argument early
+            if self.escapes.reading_arg() && next == quote {
+                let escaped = self.escapes.finish();
+                self.process_escape(escaped);
+            } else {
+                let escaped = self.escapes.feed(next);
+                self.process_escape(escaped);
+                
+                self.raw_buf.push(next);
+                return MatchResult::IncompleteMatch;
+            }
+        }
+        
+        // check for terminating quotes
+        if next == quote {
+            self.close_quotes += 1;
+            if self.close_quotes == TRIPLE_QUOTE_LEN {
+                self.closed = true;
+                return MatchResult::CompleteMatch;
+            }
+            return MatchResult::IncompleteMatch;
+        }
+        self.flush_quotes();
+        
+        // check for escape sequence start
+        if next == ESCAPE_CHAR && !self.raw {
+            self.escapes.begin(index);
+        } else {
+            self.escaped_buf.push(next);
+        }
+        self.raw_buf.push(next);
+        
+        MatchResult::IncompleteMatch
+    }
+    
+    fn get_token(&self) -> Result<Token, TokenError> {
+        debug_assert!(self.current_state().is_complete_match());
+        
+        if let Some((ref error, ..)) = self.error {
+            Err(Box::new(error.clone().with_raw(self.raw_buf.clone())))
+        } else if self.raw {
+            Ok(Token::StringLiteral(self.raw_buf.clone()))
+        } else {
+            Ok(Token::StringLiteral(self.escaped_buf.clone()))
+        }
+    }
+    
+    fn error_span(&self) -> Option<Span> {
+        self.error.as_ref().map(|(_, span)| *span)
     }
 
 }
 
 
+// Interpolated string literals, e.g. f"value: {value}", which may also be triple-quoted
+
+const INTERPOLATE_PREFIX: char = 'f';
+const OPEN_BRACE: char = '{';
+const CLOSE_BRACE: char = '}';
+
+// This rule only collects the source text of each embedded expression.
+// Tokenizing the embedded expressions is left to the lexer, which has access to all of the lexer rules.
+#[derive(Clone)]
+pub struct InterpolatedStringRule {
+    fragments: Vec<StrFragment>,
+    buf: String,  // text of the current fragment
+    count: usize, // number of chars consumed
+    expr_start: usize,
+    quote: Option<char>,
+    open_quotes: usize, // number of opening quotes read so far
+    close_quotes: usize, // number of consecutive quotes read in a triple-quoted string, that may be the closing quotes
+    closed: bool,
+    
+    escapes: EscapeReader,
+    brace: Option<(char, usize)>, // a brace that may be doubled, and its position
+    depth: usize,  // nesting depth of braces in the current embedded expression, zero if not inside one
+    inner_quote: Option<char>, // the quote of a string literal inside an embedded expression
+    inner_escaped: bool, // the previous char was an escape char inside of an embedded string literal
+    error: Option<(InterpolationError, Span)>, // hold the first error to occur
+}
+
+impl InterpolatedStringRule {
+    pub fn new(escapes: impl Iterator<Item=&'static dyn EscapeSequence>) -> Self {
+        InterpolatedStringRule {
+            fragments: Vec::new(),
+            buf: String::new(),
+            count: 0,
+            expr_start: 0,
+            quote: None,
+            open_quotes: 0,
+            close_quotes: 0,
+            closed: false,
+            
+            escapes: EscapeReader::new(escapes),
+            brace: None,
+            depth: 0,
+            inner_quote: None,
+            inner_escaped: false,
+            error: None,
+        }
+    }
+    
+    fn set_error(&mut self, error: InterpolationError, span: Span) {
+        if self.error.is_none() {
+            self.error = Some((error, span));
+        }
+    }
+    
+    fn process_escape(&mut self, escaped: Escaped) {
+        match escaped {
+            Escaped::Pending => { },
+            Escaped::Output(output) => self.buf.push_str(output.as_str()),
+            Escaped::Error(error, span) => self.set_error(InterpolationError::InvalidEscape(error), span),
+        }
+    }
+    
+    fn is_triple_quoted(&self) -> bool { self.open_quotes == TRIPLE_QUOTE_LEN }
+    
+    // two quotes are an empty string, unless they turn out to be the start of a triple-quoted string
+    fn is_empty_string(&self) -> bool { self.open_quotes == 2 }
+    
+    // quotes that turned out not to be the closing quotes are part of the string
+    fn flush_quotes(&mut self) {
+        let quote = self.quote.unwrap();
+        for _ in 0..self.close_quotes {
+            self.buf.push(quote);
+        }
+        self.close_quotes = 0;
+    }
+    
+    fn end_literal(&mut self) {
+        if !self.buf.is_empty() {
+            let literal = core::mem::take(&mut self.buf);
+            self.fragments.push(StrFragment::Literal(literal));
+        }
+    }
+    
+    fn end_expr(&mut self) {
+        if self.buf.trim().is_empty() {
+            // include the enclosing braces
+            let span = Span { offset: self.expr_start - 1, length: self.buf.chars().count() + 2 };
+            self.set_error(InterpolationError::EmptyExpression, span);
+        }
+        
+        let source = core::mem::take(&mut self.buf);
+        self.fragments.push(StrFragment::Source(source, self.expr_start));
+    }
+    
+    fn match_literal(&mut self, index: usize, next: char) -> MatchResult {
+        if Some(next) != self.quote {
+            self.flush_quotes();
+        }
+        
+        if let Some((brace, brace_index)) = self.brace.take() {
+            // doubled braces are just the brace character
+            if next == brace {
+                self.buf.push(next);
+                return MatchResult::IncompleteMatch;
+            }
+            
+            if brace == CLOSE_BRACE {
+                let span = Span { offset: brace_index, length: 1 };
+                self.set_error(InterpolationError::UnmatchedBrace, span);
+            } else {
+                self.end_literal();
+                self.depth = 1;
+                self.expr_start = index;
+                return self.match_expr(next);
+            }
+        }
+        
+        if self.escapes.in_escape() {
+            if !(self.escapes.reading_arg() && Some(next) == self.quote) {
+                let escaped = self.escapes.feed(next);
+                self.process_escape(escaped);
+                return MatchResult::IncompleteMatch;
+            }
+            
+            // a closing quote ends the escape sequence argument early
+            let escaped = self.escapes.finish();
+            self.process_escape(escaped);
+        }
+        
+        match next {
+            ESCAPE_CHAR => self.escapes.begin(index),
+            
+            OPEN_BRACE | CLOSE_BRACE => self.brace = Some((next, index)),
+            
+            _ if Some(next) == self.quote => {
+                if self.is_triple_quoted() {
+                    self.close_quotes += 1;
+                    if self.close_quotes < TRIPLE_QUOTE_LEN {
+                        return MatchResult::IncompleteMatch;
+                    }
+                    self.close_quotes = 0;
+                }
+                
+                self.end_literal();
+                self.closed = true;
+                return MatchResult::CompleteMatch;
+            },
+            
+            _ => self.buf.push(next),
+        }
+        
+        MatchResult::IncompleteMatch
+    }
+    
+    fn match_expr(&mut self, next: char) -> MatchResult {
+        if let Some(quote) = self.inner_quote {
+            if self.inner_escaped {
+                self.inner_escaped = false;
+            } else if next == ESCAPE_CHAR {
+                self.inner_escaped = true;
+            } else if next == quote {
+                self.inner_quote = None;
+            }
+            
+            self.buf.push(next);
+            return MatchResult::IncompleteMatch;
+        }
+        
+        match next {
+            SINGLE_QUOTE | DOUBLE_QUOTE => self.inner_quote = Some(next),
+            
+            OPEN_BRACE => self.depth += 1,
+            
+            CLOSE_BRACE => {
+                self.depth -= 1;
+                if self.depth == 0 {
+                    self.end_expr();
+                    return MatchResult::IncompleteMatch;
+                }
+            },
+            
+            _ => { },
+        }
+        
+        self.buf.push(next);
+        MatchResult::IncompleteMatch
+    }
+}
+
+impl LexerRule for InterpolatedStringRule {
+    fn reset(&mut self) {
+        self.fragments.clear();
+        self.buf.clear();
+        self.count = 0;
+        self.expr_start = 0;
+        self.quote = None;
+        self.open_quotes = 0;
+        self.close_quotes = 0;
+        self.closed = false;
+        
+        self.escapes.reset();
+        self.brace = None;
+        self.depth = 0;
+        self.inner_quote = None;
+        self.inner_escaped = false;
+        self.error = None;
+    }
+    
+    fn current_state(&self) -> MatchResult {
+        if self.closed || self.is_empty_string() {
+            MatchResult::CompleteMatch
+        } else {
+            MatchResult::IncompleteMatch
+        }
+    }
+    
+    fn try_match(&mut self, _prev: Option<char>, next: char) -> MatchResult {
+        if self.closed {
+            return MatchResult::NoMatch;  // dont accept any further input
+        }
+        
+        let index = self.count;
+        let result = if self.quote.is_none() {
+            match next {
+                INTERPOLATE_PREFIX if index == 0 => MatchResult::IncompleteMatch,
+                
+                SINGLE_QUOTE | DOUBLE_QUOTE if index == 1 => {
+                    self.quote = Some(next);
+                    self.open_quotes = 1;
+                    MatchResult::IncompleteMatch
+                },
+                
+                _ => MatchResult::NoMatch,
+            }
+        } else if self.open_quotes < TRIPLE_QUOTE_LEN && index == self.open_quotes + 1 && Some(next) == self.quote {
+            self.open_quotes += 1;
+            if self.is_empty_string() {
+                MatchResult::CompleteMatch
+            } else {
+                MatchResult::IncompleteMatch
+            }
+        } else if self.is_empty_string() {
+            MatchResult::NoMatch
+        } else if self.depth > 0 {
+            self.match_expr(next)
+        } else {
+            self.match_literal(index, next)
+        };
+        
+        if result.is_match() {
+            self.count += 1;
+        }
+        result
+    }
+    
+    fn get_token(&self) -> Result<Token, TokenError> {
+        debug_assert!(self.current_state().is_complete_match());
+        
+        if let Some((ref error, ..)) = self.error {
+            Err(Box::new(error.clone()))
+        } else {
+            Ok(Token::InterpolatedString(self.fragments.clone()))
+        }
+    }
+    
+    fn error_span(&self) -> Option<Span> {
+        self.error.as_ref().map(|(_, span)| *span)
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub enum InterpolationError {
+    EmptyExpression,
+    UnmatchedBrace,
+    InvalidEscape(StringEscapeError),
+}
+
+impl std::error::Error for InterpolationError { }
+
+impl core::fmt::Display for InterpolationError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::EmptyExpression => fmt.write_str("empty expression in interpolated string"),
+            Self::UnmatchedBrace => fmt.write_str("single '}' in interpolated string, use '}}' instead"),
+            Self::InvalidEscape(error) => write!(fmt, "{}", error),
+        }
+    }
+}
+
+
 #[derive(Debug, Clone)]
 pub enum StringEscapeErrorKind {
     InvalidEscapeTag,
@@ -266,9 +847,9 @@ impl StringEscapeError {
 
 impl std::error::Error for StringEscapeError { }
 
-impl core::fmt::Display for StringEscapeError { 
+impl core::fmt::Display for StringEscapeError {
     fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let render_escape = format!("{}{}{}", 
+        let render_escape = format!("{}{}{}",
             ESCAPE_CHAR, self.tag, self.arg.as_ref().map_or("", |arg| arg.as_str())
         );
         
@@ -277,4 +858,4 @@ impl core::fmt::Display for StringEscapeError {
             StringEscapeErrorKind::InvalidEscapeArg => write!(fmt, "invalid escape sequence '{}'", render_escape),
         }
     }
-}
\ No newline at end of file
+}
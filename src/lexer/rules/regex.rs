@@ -0,0 +1,551 @@
+//! A lexer rule that matches tokens using a regular expression.
+//!
+//! The pattern is compiled into an NFA which is simulated one char at a time, so that it fits the incremental
+//! `LexerRule` interface: as long as any state is still alive the chars seen so far could be extended into a match.
+//! Patterns are always anchored to the start of the token, and like the other rules a match cannot back off to
+//! a shorter one if the token doesn't end where the pattern allows it to.
+//!
+//! The supported syntax is a subset of the usual one: literal chars, `.` (any char except `'\n'`), classes such
+//! as `[a-z_]` or `[^"]`, the shorthand classes `\d`, `\w` and `\s` and their negations `\D`, `\W` and `\S`,
+//! groups `(...)` or `(?:...)`, alternation `|`, and the quantifiers `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}`.
+//! There are no captures, anchors, or lazy quantifiers.
+
+use core::fmt;
+use std::error::Error;
+use std::rc::Rc;
+use crate::lexer::Token;
+use crate::lexer::rules::{MatchResult, LexerRule, TokenError};
+
+
+// larger repetitions are almost certainly a mistake, and would make the program very large
+const MAX_REPEAT: u32 = 1000;
+const MAX_PROGRAM_LEN: usize = 10000;
+
+
+#[derive(Debug, Clone)]
+pub struct RegexError {
+    message: String,
+    offset: usize,
+}
+
+impl RegexError {
+    fn new(message: impl Into<String>, offset: usize) -> Self {
+        Self { message: message.into(), offset }
+    }
+    
+    /// The position in the pattern where the error was found, in chars
+    pub fn offset(&self) -> usize { self.offset }
+}
+
+impl Error for RegexError { }
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{} at position {} of pattern", self.message, self.offset)
+    }
+}
+
+
+type TokenFn = dyn Fn(&str) -> Result<Token, TokenError>;
+
+#[derive(Clone)]
+pub struct RegexRule {
+    program: Rc<Program>,
+    make_token: Rc<TokenFn>,
+    states: Vec<usize>,
+    buf: String,
+    state: MatchResult,
+}
+
+impl RegexRule {
+    /// A rule that produces the same token whenever the pattern matches
+    pub fn new(pattern: &str, result: Token) -> Result<Self, RegexError> {
+        Self::with_token_fn(pattern, move |_| Ok(result.clone()))
+    }
+    
+    /// A rule that produces its token from the matched text, e.g. to parse the value of a literal
+    pub fn with_token_fn<F>(pattern: &str, make_token: F) -> Result<Self, RegexError>
+    where F: Fn(&str) -> Result<Token, TokenError> + 'static {
+        let program = Program::compile(pattern)?;
+        let states = program.start.clone();
+        
+        Ok(Self {
+            program: Rc::new(program),
+            make_token: Rc::new(make_token),
+            states,
+            buf: String::new(),
+            state: MatchResult::IncompleteMatch,
+        })
+    }
+}
+
+impl LexerRule for RegexRule {
+    fn reset(&mut self) {
+        self.states.clone_from(&self.program.start);
+        self.buf.clear();
+        self.state = MatchResult::IncompleteMatch;
+    }
+    
+    fn current_state(&self) -> MatchResult { self.state }
+    
+    fn try_match(&mut self, _prev: Option<char>, next: char) -> MatchResult {
+        let states = self.program.step(&self.states, next);
+        if states.is_empty() {
+            return MatchResult::NoMatch;
+        }
+        
+        let match_result = if self.program.is_match(&states) {
+            MatchResult::CompleteMatch
+        } else {
+            MatchResult::IncompleteMatch
+        };
+        
+        self.states = states;
+        self.buf.push(next);
+        self.state = match_result;
+        match_result
+    }
+    
+    fn get_token(&self) -> Result<Token, TokenError> {
+        debug_assert!(self.current_state().is_complete_match());
+        (self.make_token)(&self.buf)
+    }
+}
+
+
+// Character Sets
+
+#[derive(Debug, Clone, Copy)]
+enum PerlClass {
+    Digit,  // \d, ASCII digits only
+    Word,   // \w, letters, digits and '_'
+    Space,  // \s
+}
+
+impl PerlClass {
+    fn contains(&self, ch: char) -> bool {
+        match self {
+            Self::Digit => ch.is_ascii_digit(),
+            Self::Word => ch == '_' || ch.is_alphanumeric(),
+            Self::Space => ch.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Perl(PerlClass, bool),  // a shorthand class, and whether it is negated
+}
+
+impl ClassItem {
+    fn contains(&self, ch: char) -> bool {
+        match self {
+            Self::Range(first, last) => (*first..=*last).contains(&ch),
+            Self::Perl(class, negated) => class.contains(ch) != *negated,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CharSet {
+    items: Vec<ClassItem>,
+    negated: bool,
+}
+
+impl CharSet {
+    fn single(ch: char) -> Self {
+        Self { items: vec![ClassItem::Range(ch, ch)], negated: false }
+    }
+    
+    fn any() -> Self {
+        Self { items: vec![ClassItem::Range('\n', '\n')], negated: true }
+    }
+    
+    fn contains(&self, ch: char) -> bool {
+        self.items.iter().any(|item| item.contains(ch)) != self.negated
+    }
+}
+
+
+// Pattern Parsing
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Chars(CharSet),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+struct PatternParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl PatternParser {
+    fn parse(pattern: &str) -> Result<Node, RegexError> {
+        let mut parser = Self { chars: pattern.chars().collect(), pos: 0 };
+        
+        let node = parser.parse_alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unmatched \")\""));
+        }
+        Ok(node)
+    }
+    
+    fn error(&self, message: &str) -> RegexError {
+        RegexError::new(message, self.pos)
+    }
+    
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    
+    fn next(&mut self) -> Option<char> {
+        let next = self.peek();
+        if next.is_some() {
+            self.pos += 1;
+        }
+        next
+    }
+    
+    fn eat(&mut self, ch: char) -> bool {
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    
+    fn parse_alternation(&mut self) -> Result<Node, RegexError> {
+        let mut branches = vec![ self.parse_concat()? ];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(Node::Alternate(branches))
+        }
+    }
+    
+    fn parse_concat(&mut self) -> Result<Node, RegexError> {
+        let mut items = Vec::new();
+        while !matches!(self.peek(), None | Some('|') | Some(')')) {
+            items.push(self.parse_repeat()?);
+        }
+        
+        match items.len() {
+            0 => Ok(Node::Empty),
+            1 => Ok(items.pop().unwrap()),
+            _ => Ok(Node::Concat(items)),
+        }
+    }
+    
+    fn parse_repeat(&mut self) -> Result<Node, RegexError> {
+        let mut node = self.parse_atom()?;
+        
+        while let Some(quantifier @ ('*' | '+' | '?' | '{')) = self.peek() {
+            self.pos += 1;
+            
+            let (min, max) = match quantifier {
+                '*' => (0, None),
+                '+' => (1, None),
+                '?' => (0, Some(1)),
+                _ => self.parse_bounds()?,
+            };
+            node = Node::Repeat { node: Box::new(node), min, max };
+        }
+        
+        Ok(node)
+    }
+    
+    // the bounds of a repetition like "{n}", "{n,}" or "{n,m}", after the opening brace
+    fn parse_bounds(&mut self) -> Result<(u32, Option<u32>), RegexError> {
+        let min = self.parse_count()?
+            .ok_or_else(|| self.error("expected a number"))?;
+        
+        let max = if self.eat(',') {
+            self.parse_count()?
+        } else {
+            Some(min)
+        };
+        
+        if !self.eat('}') {
+            return Err(self.error("expected \"}\""));
+        }
+        if matches!(max, Some(max) if max < min) {
+            return Err(self.error("invalid repetition bounds"));
+        }
+        Ok((min, max))
+    }
+    
+    fn parse_count(&mut self) -> Result<Option<u32>, RegexError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(ch) if ch.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+        
+        let digits = self.chars[start..self.pos].iter().collect::<String>();
+        match digits.parse::<u32>() {
+            Ok(count) if count <= MAX_REPEAT => Ok(Some(count)),
+            _ => Err(RegexError::new("repetition count is too large", start)),
+        }
+    }
+    
+    fn parse_atom(&mut self) -> Result<Node, RegexError> {
+        let ch = self.next().unwrap();
+        let node = match ch {
+            '(' => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                
+                let inner = self.parse_alternation()?;
+                if !self.eat(')') {
+                    return Err(self.error("expected \")\""));
+                }
+                inner
+            },
+            
+            '[' => Node::Chars(self.parse_class()?),
+            
+            '.' => Node::Chars(CharSet::any()),
+            
+            '\\' => Node::Chars(CharSet { items: vec![ self.parse_escape()? ], negated: false }),
+            
+            '*' | '+' | '?' | '{' => {
+                self.pos -= 1;
+                return Err(self.error("nothing to repeat"));
+            },
+            
+            '^' | '$' => {
+                self.pos -= 1;
+                return Err(self.error("anchors are not supported"));
+            },
+            
+            ch => Node::Chars(CharSet::single(ch)),
+        };
+        Ok(node)
+    }
+    
+    // after the opening "["
+    fn parse_class(&mut self) -> Result<CharSet, RegexError> {
+        let negated = self.eat('^');
+        
+        let mut items = Vec::new();
+        loop {
+            let ch = self.next()
+                .ok_or_else(|| self.error("unclosed character class"))?;
+            
+            // a "]" at the very start is just a literal char
+            if ch == ']' && !items.is_empty() {
+                break;
+            }
+            
+            let first = match ch {
+                '\\' => match self.parse_escape()? {
+                    ClassItem::Range(ch, _) => ch,
+                    item => {
+                        items.push(item);
+                        continue;
+                    },
+                },
+                ch => ch,
+            };
+            
+            // a "-" just before the closing "]" is also a literal char
+            let is_range = self.peek() == Some('-') && !matches!(self.chars.get(self.pos + 1), None | Some(']'));
+            if !is_range {
+                items.push(ClassItem::Range(first, first));
+                continue;
+            }
+            
+            self.pos += 1; // consume "-"
+            let last = match self.next().unwrap() {
+                '\\' => match self.parse_escape()? {
+                    ClassItem::Range(ch, _) => ch,
+                    _ => return Err(self.error("invalid range in character class")),
+                },
+                ch => ch,
+            };
+            
+            if last < first {
+                return Err(self.error("invalid range in character class"));
+            }
+            items.push(ClassItem::Range(first, last));
+        }
+        
+        Ok(CharSet { items, negated })
+    }
+    
+    // after the "\"
+    fn parse_escape(&mut self) -> Result<ClassItem, RegexError> {
+        let ch = self.next()
+            .ok_or_else(|| self.error("incomplete escape sequence"))?;
+        
+        let ch = match ch {
+            'd' => return Ok(ClassItem::Perl(PerlClass::Digit, false)),
+            'D' => return Ok(ClassItem::Perl(PerlClass::Digit, true)),
+            'w' => return Ok(ClassItem::Perl(PerlClass::Word, false)),
+            'W' => return Ok(ClassItem::Perl(PerlClass::Word, true)),
+            's' => return Ok(ClassItem::Perl(PerlClass::Space, false)),
+            'S' => return Ok(ClassItem::Perl(PerlClass::Space, true)),
+            
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            
+            ch if ch.is_ascii_punctuation() || ch == ' ' => ch,
+            
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("unknown escape sequence"));
+            },
+        };
+        Ok(ClassItem::Range(ch, ch))
+    }
+}
+
+
+// Compiled NFA
+
+#[derive(Debug)]
+enum Inst {
+    Chars(CharSet),
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug)]
+struct Program {
+    insts: Vec<Inst>,
+    start: Vec<usize>,  // the states before any chars have been matched
+}
+
+impl Program {
+    fn compile(pattern: &str) -> Result<Self, RegexError> {
+        let node = PatternParser::parse(pattern)?;
+        
+        let mut insts = Vec::new();
+        Self::emit(&mut insts, &node)?;
+        insts.push(Inst::Match);
+        
+        let mut program = Self { insts, start: Vec::new() };
+        program.start = program.closure(&[0]);
+        Ok(program)
+    }
+    
+    fn emit(insts: &mut Vec<Inst>, node: &Node) -> Result<(), RegexError> {
+        if insts.len() > MAX_PROGRAM_LEN {
+            return Err(RegexError::new("pattern is too large", 0));
+        }
+        
+        match node {
+            Node::Empty => { },
+            
+            Node::Chars(set) => insts.push(Inst::Chars(set.clone())),
+            
+            Node::Concat(items) => for item in items.iter() {
+                Self::emit(insts, item)?;
+            },
+            
+            Node::Alternate(branches) => {
+                let (last, branches) = branches.split_last().unwrap();
+                
+                let mut jumps = Vec::new();
+                for branch in branches.iter() {
+                    let split = insts.len();
+                    insts.push(Inst::Split(split + 1, 0));
+                    Self::emit(insts, branch)?;
+                    
+                    jumps.push(insts.len());
+                    insts.push(Inst::Jump(0));
+                    insts[split] = Inst::Split(split + 1, insts.len());
+                }
+                Self::emit(insts, last)?;
+                
+                let end = insts.len();
+                for jump in jumps {
+                    insts[jump] = Inst::Jump(end);
+                }
+            },
+            
+            Node::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    Self::emit(insts, node)?;
+                }
+                
+                match max {
+                    None => {
+                        let split = insts.len();
+                        insts.push(Inst::Split(split + 1, 0));
+                        Self::emit(insts, node)?;
+                        insts.push(Inst::Jump(split));
+                        insts[split] = Inst::Split(split + 1, insts.len());
+                    },
+                    
+                    Some(max) => for _ in *min..*max {
+                        let split = insts.len();
+                        insts.push(Inst::Split(split + 1, 0));
+                        Self::emit(insts, node)?;
+                        insts[split] = Inst::Split(split + 1, insts.len());
+                    },
+                }
+            },
+        }
+        Ok(())
+    }
+    
+    // follow the jumps and splits from the given states, returning the states that match a char or the end
+    fn closure(&self, states: &[usize]) -> Vec<usize> {
+        let mut visited = vec![false; self.insts.len()];
+        let mut stack = states.to_vec();
+        let mut closure = Vec::new();
+        
+        while let Some(pc) = stack.pop() {
+            if visited[pc] {
+                continue;
+            }
+            visited[pc] = true;
+            
+            match self.insts[pc] {
+                Inst::Jump(target) => stack.push(target),
+                
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                },
+                
+                Inst::Chars(..) | Inst::Match => closure.push(pc),
+            }
+        }
+        closure
+    }
+    
+    fn step(&self, states: &[usize], next: char) -> Vec<usize> {
+        let advanced = states.iter()
+            .filter(|&&pc| matches!(&self.insts[pc], Inst::Chars(set) if set.contains(next)))
+            .map(|pc| pc + 1)
+            .collect::<Vec<usize>>();
+        
+        self.closure(&advanced)
+    }
+    
+    fn is_match(&self, states: &[usize]) -> bool {
+        states.iter().any(|&pc| matches!(self.insts[pc], Inst::Match))
+    }
+}
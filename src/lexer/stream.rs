@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use crate::lexer::{TokenMeta, LexerError};
+
+
+// A buffered stream of tokens that supports looking any number of tokens ahead,
+// and backtracking to a checkpoint if a speculative parse doesn't work out.
+
+pub type TokenResult = Result<TokenMeta, LexerError>;
+
+pub struct TokenStream<T> where T: Iterator<Item=TokenResult> {
+    tokens: T,
+    buffer: VecDeque<TokenResult>,
+    cursor: usize, // index into buffer of the next token
+    checkpoints: Vec<usize>, // cursor positions that may be rolled back to, innermost last
+}
+
+// Checkpoints must be released in the reverse order that they were created, by either rollback() or commit()
+#[must_use]
+#[derive(Debug)]
+pub struct Checkpoint {
+    cursor: usize,
+    depth: usize,
+}
+
+impl<T> TokenStream<T> where T: Iterator<Item=TokenResult> {
+    pub fn new(tokens: T) -> Self {
+        TokenStream {
+            tokens,
+            buffer: VecDeque::new(),
+            cursor: 0,
+            checkpoints: Vec::new(),
+        }
+    }
+    
+    // read tokens until the buffer holds at least `len` of them, or the underlying tokens run out
+    fn fill(&mut self, len: usize) {
+        while self.buffer.len() < len {
+            match self.tokens.next() {
+                Some(next) => self.buffer.push_back(next),
+                None => break,
+            }
+        }
+    }
+    
+    pub fn peek(&mut self) -> Option<&TokenResult> {
+        self.peek_nth(0)
+    }
+    
+    // look at the token `n` places after the next one, without consuming anything
+    pub fn peek_nth(&mut self, n: usize) -> Option<&TokenResult> {
+        self.fill(self.cursor + n + 1);
+        self.buffer.get(self.cursor + n)
+    }
+    
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let checkpoint = Checkpoint {
+            cursor: self.cursor,
+            depth: self.checkpoints.len(),
+        };
+        self.checkpoints.push(self.cursor);
+        checkpoint
+    }
+    
+    // un-consume every token that was consumed since the checkpoint was created
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.release(&checkpoint);
+        self.cursor = checkpoint.cursor;
+        self.discard_consumed();
+    }
+    
+    // keep the tokens that were consumed since the checkpoint was created
+    pub fn commit(&mut self, checkpoint: Checkpoint) {
+        self.release(&checkpoint);
+        self.discard_consumed();
+    }
+    
+    fn release(&mut self, checkpoint: &Checkpoint) {
+        assert!(checkpoint.depth + 1 == self.checkpoints.len(), "checkpoints must be released innermost first");
+        self.checkpoints.pop();
+    }
+    
+    // consumed tokens only need to be kept while there is a checkpoint that could roll back to them
+    fn discard_consumed(&mut self) {
+        if self.checkpoints.is_empty() {
+            self.buffer.drain(..self.cursor);
+            self.cursor = 0;
+        }
+    }
+}
+
+impl<T> Iterator for TokenStream<T> where T: Iterator<Item=TokenResult> {
+    type Item = TokenResult;
+    
+    fn next(&mut self) -> Option<TokenResult> {
+        if self.checkpoints.is_empty() && self.cursor == 0 {
+            return self.buffer.pop_front()
+                .or_else(|| self.tokens.next());
+        }
+        
+        self.fill(self.cursor + 1);
+        let next = self.buffer.get(self.cursor).cloned();
+        if next.is_some() {
+            self.cursor += 1;
+        }
+        next
+    }
+}
@@ -60,3 +60,4 @@ macro_rules! assert_token_sequence {
 mod comments;
 mod lexerrules;
 mod literals;
+mod stream;
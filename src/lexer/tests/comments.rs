@@ -3,6 +3,8 @@
 use crate::lexer::{LexerBuilder, Token, TokenMeta};
 use crate::lexer::rules::MultiCharRule;
 use crate::lexer::rules::comments::*;
+use crate::lexer::errors::ErrorKind;
+use crate::lexer::tests::ErrorData;
 
 #[test]
 fn lexer_test_comments() {
@@ -26,7 +28,7 @@ fn lexer_test_comments() {
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "baz"))
         .add_rule(LineCommentRule::new('#'))
         .add_rule(BlockCommentRule::new("#{", "}#"))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
     
@@ -66,7 +68,7 @@ fn lexer_test_comments() {
             ..
         } "bar",
     
-        token if symbol.len() == 0 => {
+        token if symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
@@ -97,7 +99,7 @@ fn lexer_test_skip_comments() {
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "baz"))
         .add_rule(LineCommentRule::new('#'))
         .add_rule(BlockCommentRule::new("#{", "}#"))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
     
@@ -119,11 +121,83 @@ fn lexer_test_skip_comments() {
             ..
         } "bar",
         
-        token if symbol.len() == 0 => {
+        token if symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
         } "EOF",
     
     );
-}
\ No newline at end of file
+}
+#[test]
+fn lexer_test_shebang() {
+    let source = "#!/usr/bin/env sphinx\nfoo #!bar";
+    
+    let builder = LexerBuilder::new()
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(0), "foo"))
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "bar"));
+    
+    let mut lexer = builder.build(source);
+    assert_token_sequence!(lexer,
+        token if symbol.start() == 22 && symbol.len() == 3 => {
+            token: Token::IntegerLiteral(0),
+            symbol,
+            newline: true,
+        } "foo",
+    );
+    
+    let mut lexer = builder.set_skip_comments(false).build_once(source);
+    assert_token_sequence!(lexer,
+        token if symbol.start() == 0 && symbol.len() == 22 => {
+            token: Token::Comment,
+            symbol,
+            ..
+        } "#!/usr/bin/env sphinx",
+        
+        token if symbol.start() == 22 && symbol.len() == 3 => {
+            token: Token::IntegerLiteral(0),
+            symbol,
+            newline: true,
+        } "foo",
+    );
+    
+    // only the first line is a shebang, so there are no rules for the second "#!"
+    assert!(lexer.next_token().is_err());
+}
+
+#[test]
+fn lexer_test_unclosed_block_comment() {
+    let source = "foo #{ #{ nested }# }# bar #{ baz #{ }#\nbar";
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(0), "foo"))
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "bar"))
+        .build_once(source);
+    
+    assert_token_sequence!(lexer,
+        token if symbol.start() == 0 && symbol.len() == 3 => {
+            token: Token::IntegerLiteral(0),
+            symbol,
+            ..
+        } "foo",
+        
+        token if symbol.start() == 23 && symbol.len() == 3 => {
+            token: Token::IntegerLiteral(1),
+            symbol,
+            ..
+        } "bar",
+        
+        // the error spans from the comment that was left open to the end of the source
+        error if symbol.start() == 27 && symbol.len() == 16 => {
+            kind: ErrorKind::UnclosedComment,
+            symbol,
+            ..
+        } "unclosed comment",
+        
+        token if symbol.start() == 43 => {
+            token: Token::EOF,
+            symbol,
+            ..
+        } "EOF",
+    );
+}
@@ -12,7 +12,7 @@ fn lexer_matches_tokens_1() {
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(0), "foo"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "bar"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "baz"))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
         token if symbol.start() == 0 && symbol.len() == 3 => {
@@ -27,7 +27,7 @@ fn lexer_matches_tokens_1() {
             newline: false,
         } "bar",
         
-        token if symbol.start() == 6 && symbol.len() == 0 => {
+        token if symbol.start() == 6 && symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             newline: false,
@@ -42,7 +42,7 @@ fn lexer_skips_whitespace() {
     let mut lexer = LexerBuilder::new()
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "foo"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "bar"))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
         token if symbol.start() == 2 && symbol.len() == 3 => {
@@ -67,7 +67,7 @@ fn lexer_tracks_line_numbers() {
     let mut lexer = LexerBuilder::new()
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "foo"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "bar"))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
         token if symbol.start() == 2 && symbol.len() == 3 => {
@@ -97,7 +97,7 @@ fn single_char_rule_matches_chars_and_dont_match_invalid() {
         .add_rule(SingleCharRule::new(Token::IntegerLiteral(1), 'a'))
         .add_rule(SingleCharRule::new(Token::IntegerLiteral(2), 'b'))
         .add_rule(SingleCharRule::new(Token::IntegerLiteral(3), 'c'))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
         
@@ -125,7 +125,7 @@ fn single_char_rule_matches_chars_and_dont_match_invalid() {
             ..
         } "d",
         
-        token if symbol.start() == 5 && symbol.len() == 0 => {
+        token if symbol.start() == 5 && symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
@@ -142,7 +142,7 @@ fn rule_substring_tokens_match_1() {
         .add_rule(SingleCharRule::new(Token::IntegerLiteral(0), 'a'))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "ab"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "abc"))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
         
@@ -164,7 +164,7 @@ fn rule_substring_tokens_match_1() {
             ..
         } "abc",
         
-        token if symbol.start() == 8 && symbol.len() == 0 => {
+        token if symbol.start() == 8 && symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
@@ -182,7 +182,7 @@ fn rule_substring_tokens_match_2() {
         .add_rule(SingleCharRule::new(Token::IntegerLiteral(0), 'a'))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "ab"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "abc"))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
         
@@ -204,7 +204,7 @@ fn rule_substring_tokens_match_2() {
             ..
         } "ab",
         
-        token if symbol.start() == 5 && symbol.len() == 0 => {
+        token if symbol.start() == 5 && symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
@@ -221,7 +221,7 @@ fn rule_substring_tokens_match_eof() {
     let mut lexer = LexerBuilder::new()
         .add_rule(SingleCharRule::new(Token::IntegerLiteral(0), '+'))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "+="))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
     
@@ -237,7 +237,7 @@ fn rule_substring_tokens_match_eof() {
             newline: true,
         },
         
-        token if symbol.start() == 4 && symbol.len() == 0 => {
+        token if symbol.start() == 4 && symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             newline: false,
@@ -255,7 +255,7 @@ fn lexer_test_matches_tokens_2() {
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "or"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "and"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(3), "+="))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
     
@@ -277,7 +277,7 @@ fn lexer_test_matches_tokens_2() {
             ..
         } "or",
         
-        token if symbol.start() == 8 && symbol.len() == 0 => {
+        token if symbol.start() == 8 && symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
@@ -295,7 +295,7 @@ fn lexer_error_invalid_token() {
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "foo"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "bar"))
         .add_rule(MultiCharRule::new(Token::IntegerLiteral(3), "baz"))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
     
@@ -329,7 +329,7 @@ fn lexer_error_invalid_token() {
             ..
         } "baz",
         
-        token if symbol.start() == 16 && symbol.len() == 0 => {
+        token if symbol.start() == 16 && symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
@@ -337,3 +337,310 @@ fn lexer_error_invalid_token() {
     
     );
 }
+
+
+use crate::language::IntType;
+use crate::lexer::rules::regex::RegexRule;
+
+#[test]
+fn regex_rule_matches_tokens() {
+    let source = "0b1010_01 foo_1 if 0b2";
+    
+    let binary = RegexRule::with_token_fn(r"0[bB][01]+(_[01]+)*", |text| {
+        let digits = text[2..].replace('_', "");
+        Ok(Token::IntegerLiteral(IntType::from_str_radix(&digits, 2)?))
+    }).unwrap();
+    
+    let identifier = RegexRule::with_token_fn(r"[a-z_]\w*", |text| Ok(Token::Identifier(text.to_string()))).unwrap();
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(MultiCharRule::new(Token::If, "if"))
+        .add_rule(binary)
+        .add_rule(identifier)
+        .build_once(source);
+    
+    assert_token_sequence!(lexer,
+        token if symbol.start() == 0 && symbol.len() == 9 => {
+            token: Token::IntegerLiteral(41),
+            symbol,
+            ..
+        } "0b1010_01",
+        
+        token if symbol.start() == 10 && symbol.len() == 5 && name == "foo_1" => {
+            token: Token::Identifier(name),
+            symbol,
+            ..
+        } "foo_1",
+        
+        // the rule added first takes priority when both match
+        token if symbol.start() == 16 && symbol.len() == 2 => {
+            token: Token::If,
+            symbol,
+            ..
+        } "if",
+        
+        error if symbol.start() == 19 => {
+            kind: ErrorKind::NoMatchingRule,
+            symbol,
+            ..
+        } "0b2",
+    );
+}
+
+#[test]
+fn regex_rule_syntax() {
+    let matches = |pattern: &str, text: &str| {
+        let rule = RegexRule::new(pattern, Token::Nil).unwrap();
+        let mut lexer = LexerBuilder::new().add_rule(rule).build_once(text);
+        matches!(lexer.next_token(), Ok(TokenMeta { symbol, .. }) if symbol.len() as usize == text.chars().count())
+    };
+    
+    assert!(matches(r"a|bc|d", "bc"));
+    assert!(matches(r"(?:ab)+c?", "ababc"));
+    assert!(matches(r"x{2,3}", "xxx"));
+    assert!(!matches(r"x{2,3}", "x"));
+    assert!(matches(r"x{2,}", "xxxxx"));
+    assert!(matches(r#""([^"\\]|\\.)*""#, r#""a\"b""#));
+    assert!(matches(r"[-+]?\d+\.\d*", "-12.5"));
+    assert!(matches(r"[]a-]+", "]-a"));
+    assert!(matches(r"\S\s\W", "é\t!"));
+    assert!(!matches(r".", "\n"));
+    
+    for pattern in ["a(b", "a)b", "[a-", "*a", "a{3,1}", "a{2", r"\q", "^a", "[z-a]", "a{5000}"] {
+        assert!(RegexRule::new(pattern, Token::Nil).is_err(), "{}", pattern);
+    }
+}
+
+
+use crate::lexer::rules::keywords::KeywordTrieRule;
+use crate::lexer::rules::literals::IdentifierRule;
+
+#[test]
+fn keyword_trie_rule_matches_keywords() {
+    let source = "in int import iffy if";
+    
+    let keywords = KeywordTrieRule::new([
+        (Token::In, "in"),
+        (Token::Import, "import"),
+        (Token::If, "if"),
+    ]);
+    
+    let builder = LexerBuilder::new()
+        .add_rule(keywords)
+        .add_rule(IdentifierRule::new());
+    
+    assert_eq!(builder.keywords().collect::<Vec<_>>(), vec!["in", "import", "if"]);
+    
+    let mut lexer = builder.build(source);
+    
+    assert_token_sequence!(lexer,
+        token if symbol.start() == 0 && symbol.len() == 2 => {
+            token: Token::In,
+            symbol,
+            ..
+        } "in",
+        
+        token if symbol.start() == 3 && symbol.len() == 3 && name == "int" => {
+            token: Token::Identifier(name),
+            symbol,
+            ..
+        } "int",
+        
+        token if symbol.start() == 7 && symbol.len() == 6 => {
+            token: Token::Import,
+            symbol,
+            ..
+        } "import",
+        
+        token if symbol.start() == 14 && symbol.len() == 4 && name == "iffy" => {
+            token: Token::Identifier(name),
+            symbol,
+            ..
+        } "iffy",
+        
+        token if symbol.start() == 19 && symbol.len() == 2 => {
+            token: Token::If,
+            symbol,
+            ..
+        } "if",
+    );
+}
+
+#[test]
+fn lexer_symbols_count_chars_not_bytes() {
+    let source = "été + ü";
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(SingleCharRule::new(Token::OpAdd, '+'))
+        .add_rule(IdentifierRule::new())
+        .build_once(source);
+    
+    assert_token_sequence!(lexer,
+        token if symbol.start() == 0 && symbol.len() == 3 && name == "été" => {
+            token: Token::Identifier(name),
+            symbol,
+            ..
+        } "été",
+        
+        token if symbol.start() == 4 && symbol.len() == 1 => {
+            token: Token::OpAdd,
+            symbol,
+            ..
+        } "+",
+        
+        token if symbol.start() == 6 && symbol.len() == 1 && name == "ü" => {
+            token: Token::Identifier(name),
+            symbol,
+            ..
+        } "ü",
+        
+        token if symbol.start() == 7 && symbol.is_empty() => {
+            token: Token::EOF,
+            symbol,
+            ..
+        } "EOF",
+    );
+}
+
+
+// hands out at most one byte per read, so that every char is split across reads
+struct TrickleReader<'a>(&'a [u8]);
+
+impl std::io::Read for TrickleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.split_first() {
+            Some((&byte, rest)) if !buf.is_empty() => {
+                buf[0] = byte;
+                self.0 = rest;
+                Ok(1)
+            },
+            _ => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn lexer_reads_chunked_input() {
+    let source = "été + ü";
+    
+    let builder = LexerBuilder::new()
+        .add_rule(SingleCharRule::new(Token::OpAdd, '+'))
+        .add_rule(IdentifierRule::new());
+    
+    let expected = builder.build(source)
+        .take(4).map(|token| token.unwrap())
+        .collect::<Vec<TokenMeta>>();
+    
+    let tokens = builder.build_reader(TrickleReader(source.as_bytes()))
+        .take(4).map(|token| token.unwrap())
+        .collect::<Vec<TokenMeta>>();
+    
+    assert_eq!(format!("{:?}", tokens), format!("{:?}", expected));
+    assert!(matches!(tokens[3].token, Token::EOF));
+}
+
+#[test]
+fn lexer_reader_error_invalid_utf8() {
+    let source = b"ab \xE9t\xC3";
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(IdentifierRule::new())
+        .build_reader(TrickleReader(source));
+    
+    assert_token_sequence!(lexer,
+        token if symbol.start() == 0 && symbol.len() == 2 => {
+            token: Token::Identifier(..),
+            symbol,
+            ..
+        } "ab",
+        
+        error if symbol.start() == 3 => {
+            kind: ErrorKind::IOError,
+            symbol,
+            ..
+        } "invalid byte",
+        
+        // nothing more is read after an error
+        token => {
+            token: Token::EOF,
+            ..
+        } "EOF",
+    );
+}
+
+#[test]
+fn lexer_error_resynchronizes() {
+    let source = "foo $%$+bar $$#$ comment\nbaz";
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(SingleCharRule::new(Token::IntegerLiteral(0), '+'))
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "foo"))
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "bar"))
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(3), "baz"))
+        .build_once(source);
+    
+    assert_token_sequence!(lexer,
+        token if symbol.start() == 0 && symbol.len() == 3 => {
+            token: Token::IntegerLiteral(1),
+            symbol,
+            ..
+        } "foo",
+        
+        // skip to the next char that can start a token
+        error if symbol.start() == 4 && symbol.len() == 3 => {
+            kind: ErrorKind::NoMatchingRule,
+            symbol,
+            ..
+        } "$%$",
+        
+        token if symbol.start() == 7 && symbol.len() == 1 => {
+            token: Token::IntegerLiteral(0),
+            symbol,
+            ..
+        } "+",
+        
+        token if symbol.start() == 8 && symbol.len() == 3 => {
+            token: Token::IntegerLiteral(2),
+            symbol,
+            ..
+        } "bar",
+        
+        // comments are skipped as usual
+        error if symbol.start() == 12 && symbol.len() == 2 => {
+            kind: ErrorKind::NoMatchingRule,
+            symbol,
+            ..
+        } "$$",
+        
+        token if symbol.start() == 25 && symbol.len() == 3 => {
+            token: Token::IntegerLiteral(3),
+            symbol,
+            newline: true,
+        } "baz",
+    );
+}
+
+
+use crate::language::{self, FIXED_TOKENS, TokenRole};
+use crate::parser::operator::{PRECEDENCE_START, PRECEDENCE_END};
+
+#[test]
+fn default_rules_lex_every_fixed_token() {
+    let builder = language::create_default_lexer_rules();
+    
+    for fixed in FIXED_TOKENS.iter() {
+        let mut lexer = builder.build(fixed.text);
+        
+        let next = lexer.next_token().expect(fixed.text);
+        assert_eq!(core::mem::discriminant(&next.token), core::mem::discriminant(&fixed.token), "{}", fixed.text);
+        assert_eq!(usize::from(next.symbol.len()), fixed.text.chars().count(), "{}", fixed.text);
+        assert!(matches!(lexer.next_token().unwrap().token, Token::EOF), "{}", fixed.text);
+        
+        assert_eq!(language::token_role(&fixed.token), Some(fixed.role), "{}", fixed.text);
+        
+        if let TokenRole::Operator { binary: Some((op, level)), .. } = fixed.role {
+            assert_eq!(op.precedence_level(), level);
+            assert!((PRECEDENCE_END..=PRECEDENCE_START).contains(&level));
+        }
+    }
+}
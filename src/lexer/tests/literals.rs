@@ -1,10 +1,12 @@
 #![cfg(test)]
 
-use crate::lexer::{LexerBuilder, Token, TokenMeta, ErrorKind};
+use crate::lexer::{LexerBuilder, Token, TokenMeta, StrFragment, ErrorKind};
 use crate::lexer::rules::SingleCharRule;
 use crate::lexer::rules::literals::*;
+use crate::lexer::rules::literals::string::{StringLiteralRule, TripleQuotedStringRule, InterpolatedStringRule};
 use crate::lexer::rules::keywords::KeywordRule;
 use crate::lexer::tests::ErrorData;
+use crate::language;
 
 #[test]
 fn lexer_test_identifiers() {
@@ -20,7 +22,7 @@ fn lexer_test_identifiers() {
     let mut lexer = LexerBuilder::new()
         .add_rule(IdentifierRule::new())
         .add_rule(SingleCharRule::new(Token::IntegerLiteral(0), '+'))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     
     assert_token_sequence!(lexer,
@@ -61,23 +63,12 @@ fn lexer_test_identifiers() {
             ..
         } "valid2",
         
-        error if symbol.len() == 1 => {
+        // the whole invalid run is a single error
+        error if symbol.len() == 3 => {
             kind: ErrorKind::NoMatchingRule,
             symbol,
             ..
-        } "0no - 0",
-        
-        error if symbol.len() == 1 => {
-            kind: ErrorKind::NoMatchingRule,
-            symbol,
-            ..
-        } "0no - n",
-
-        error if symbol.len() == 1 => {
-            kind: ErrorKind::NoMatchingRule,
-            symbol,
-            ..
-        } "0no - o",
+        } "0no",
 
         token if s == "_0valid" && symbol.len() == 7 => {
             token: Token::Identifier(s),
@@ -85,7 +76,7 @@ fn lexer_test_identifiers() {
             ..
         } "_0valid",
 
-        token if symbol.len() == 0 => {
+        token if symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
@@ -95,6 +86,56 @@ fn lexer_test_identifiers() {
 
 }
 
+#[test]
+fn lexer_test_unicode_identifiers() {
+    let source = " naïve 変数 _ñ9 Δx+éclair ";
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(IdentifierRule::new())
+        .add_rule(SingleCharRule::new(Token::IntegerLiteral(0), '+'))
+        .build_once(source);
+    
+    // symbols count chars, not bytes
+    assert_token_sequence!(lexer,
+        
+        token if s == "naïve" && symbol.start() == 1 && symbol.len() == 5 => {
+            token: Token::Identifier(s),
+            symbol,
+            ..
+        } "naïve",
+        
+        token if s == "変数" && symbol.start() == 7 && symbol.len() == 2 => {
+            token: Token::Identifier(s),
+            symbol,
+            ..
+        } "変数",
+        
+        token if s == "_ñ9" && symbol.start() == 10 && symbol.len() == 3 => {
+            token: Token::Identifier(s),
+            symbol,
+            ..
+        } "_ñ9",
+        
+        token if s == "Δx" && symbol.start() == 14 && symbol.len() == 2 => {
+            token: Token::Identifier(s),
+            symbol,
+            ..
+        } "Δx",
+        
+        token => {
+            token: Token::IntegerLiteral(0),
+            ..
+        } "+",
+        
+        token if s == "éclair" && symbol.start() == 17 && symbol.len() == 6 => {
+            token: Token::Identifier(s),
+            symbol,
+            ..
+        } "éclair",
+        
+    );
+}
+
 #[test]
 fn lexer_test_keywords_and_identifiers() {
     let source = " k   _k  9k k9 ";
@@ -102,7 +143,7 @@ fn lexer_test_keywords_and_identifiers() {
     let mut lexer = LexerBuilder::new()
         .add_rule(KeywordRule::new(Token::Fun, "k"))
         .add_rule(IdentifierRule::new())
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
     
@@ -118,17 +159,11 @@ fn lexer_test_keywords_and_identifiers() {
             ..
         } "_k",
         
-        error if symbol.len() == 1 => {
-            kind: ErrorKind::NoMatchingRule,
-            symbol,
-            ..
-        } "9k.1",
-        
-        error if symbol.len() == 1 => {
+        error if symbol.len() == 2 => {
             kind: ErrorKind::NoMatchingRule,
             symbol,
             ..
-        } "9k.2",
+        } "9k",
         
         token if s == "k9" && symbol.len() == 2 => {
             token: Token::Identifier(s),
@@ -145,7 +180,7 @@ fn lexer_test_keyword_at_eof() {
     let mut lexer = LexerBuilder::new()
         .add_rule(KeywordRule::new(Token::Fun, "k"))
         .add_rule(IdentifierRule::new())
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
         
     assert_token_sequence!(lexer,
         
@@ -155,7 +190,7 @@ fn lexer_test_keyword_at_eof() {
             ..
         } "k",
         
-        token if symbol.start() == 2 && symbol.len() == 0 => {
+        token if symbol.start() == 2 && symbol.is_empty() => {
             token: Token::EOF,
             symbol,
             ..
@@ -172,7 +207,7 @@ fn lexer_test_integer_literals() {
     let mut lexer = LexerBuilder::new()
         .add_rule(IntegerLiteralRule::new())
         .add_rule(PrefixedIntegerLiteralRule::new("0x", 16))
-        .build_once(source.chars().map(|c| Ok(c)));
+        .build_once(source);
     
     assert_token_sequence!(lexer,
         
@@ -182,23 +217,286 @@ fn lexer_test_integer_literals() {
             ..
         } "01123",
         
-        error if symbol.len() == 1 => {
+        error if symbol.len() == 2 => {
             kind: ErrorKind::NoMatchingRule,
             symbol,
             ..
-        } "x",
+        } "xA",
+        
+        token if n == 0xFACE && symbol.len() == 6 => {
+            token: Token::IntegerLiteral(n),
+            symbol,
+            ..
+        } "0xFACE",
+        
+    );
+}
+
+#[test]
+fn lexer_test_float_literals() {
+    let source = " 1e9 2.5e-3 .5 3. 1e+ x.exp ";
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(SingleCharRule::new(Token::OpAccess, '.'))
+        .add_rule(IdentifierRule::new())
+        .add_rule(IntegerLiteralRule::new())
+        .add_rule(FloatLiteralRule::new())
+        .build_once(source);
+    
+    assert_token_sequence!(lexer,
+        
+        token if value == 1e9 && symbol.start() == 1 && symbol.len() == 3 => {
+            token: Token::FloatLiteral(value),
+            symbol,
+            ..
+        } "1e9",
+        
+        token if value == 2.5e-3 && symbol.start() == 5 && symbol.len() == 6 => {
+            token: Token::FloatLiteral(value),
+            symbol,
+            ..
+        } "2.5e-3",
         
-        error if symbol.len() == 1 => {
+        token if value == 0.5 && symbol.start() == 12 && symbol.len() == 2 => {
+            token: Token::FloatLiteral(value),
+            symbol,
+            ..
+        } ".5",
+        
+        token if value == 3.0 && symbol.len() == 2 => {
+            token: Token::FloatLiteral(value),
+            symbol,
+            ..
+        } "3.",
+        
+        error if symbol.start() == 18 && symbol.len() == 3 => {
             kind: ErrorKind::NoMatchingRule,
             symbol,
             ..
-        } "A",
+        } "1e+",
         
-        token if n == 0xFACE && symbol.len() == 6 => {
-            token: Token::IntegerLiteral(n),
+        token if s == "x" => {
+            token: Token::Identifier(s),
+            ..
+        } "x",
+        
+        token => {
+            token: Token::OpAccess,
+            ..
+        } ".",
+        
+        token if s == "exp" => {
+            token: Token::Identifier(s),
+            ..
+        } "exp",
+        
+    );
+}
+
+#[test]
+fn lexer_test_string_escapes() {
+    let source = r#" "a\tb\u{1F600}\x41" r"a\tb" bar"\t" "#;
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(IdentifierRule::new())
+        .add_rule(StringLiteralRule::new(language::all_escape_sequences()))
+        .build_once(source);
+    
+    assert_token_sequence!(lexer,
+        
+        token if s == "a\tb\u{1F600}A" && symbol.len() == 19 => {
+            token: Token::StringLiteral(s),
             symbol,
             ..
-        } "0xFACE",
+        } "escapes",
+        
+        token if s == "a\\tb" && symbol.len() == 7 => {
+            token: Token::StringLiteral(s),
+            symbol,
+            ..
+        } "raw string",
+        
+        token if s == "bar" => {
+            token: Token::Identifier(s),
+            ..
+        } "identifier ending in r",
+        
+        token if s == "\t" => {
+            token: Token::StringLiteral(s),
+            ..
+        } "not a raw string",
+        
+    );
+}
+
+#[test]
+fn lexer_test_string_escape_errors() {
+    let source = r#" "\q" "ab\u{110000}" "\u{12" "#;
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(StringLiteralRule::new(language::all_escape_sequences()))
+        .build_once(source);
+    
+    // errors should point to just the invalid escape sequence
+    assert_token_sequence!(lexer,
+        
+        error if symbol.start() == 2 && symbol.len() == 2 => {
+            kind: ErrorKind::CouldNotReadToken,
+            symbol,
+            ..
+        } "invalid tag",
+        
+        error if symbol.start() == 9 && symbol.len() == 10 => {
+            kind: ErrorKind::CouldNotReadToken,
+            symbol,
+            ..
+        } "invalid codepoint",
+        
+        error if symbol.start() == 22 && symbol.len() == 5 => {
+            kind: ErrorKind::CouldNotReadToken,
+            symbol,
+            ..
+        } "unclosed argument",
+        
+    );
+}
+
+#[test]
+fn lexer_test_triple_quoted_strings() {
+    let source = " \"\"\"line 1\n\"quoted\" \"\"\n\"\"\" foo\n'''it's''' \"\"\"\"\"\" r'''\\n''' ";
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(IdentifierRule::new())
+        .add_rule(StringLiteralRule::new(language::all_escape_sequences()))
+        .add_rule(TripleQuotedStringRule::new(language::all_escape_sequences()))
+        .build_once(source);
+    
+    assert_token_sequence!(lexer,
+        
+        token if s == "line 1\n\"quoted\" \"\"\n" && symbol.start() == 1 && symbol.len() == 25 => {
+            token: Token::StringLiteral(s),
+            symbol,
+            ..
+        } "multiline",
+        
+        token if s == "foo" && symbol.start() == 27 => {
+            token: Token::Identifier(s),
+            symbol,
+            newline: false,
+        } "same line as closing quotes",
+        
+        token if s == "it's" => {
+            token: Token::StringLiteral(s),
+            newline: true,
+            ..
+        } "single quotes",
+        
+        token if s.is_empty() => {
+            token: Token::StringLiteral(s),
+            ..
+        } "empty",
+        
+        token if s == "\\n" => {
+            token: Token::StringLiteral(s),
+            ..
+        } "raw",
         
     );
-}
\ No newline at end of file
+}
+
+#[test]
+fn lexer_test_interpolated_strings() {
+    let source = r#" f"a {b + c}!{{}}" "#;
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(IdentifierRule::new())
+        .add_rule(SingleCharRule::new(Token::OpAdd, '+'))
+        .add_rule(InterpolatedStringRule::new(core::iter::empty()))
+        .build_once(source);
+    
+    let out = lexer.next_token().unwrap();
+    assert_eq!(out.symbol.len(), 17);
+    
+    let fragments = match out.token {
+        Token::InterpolatedString(fragments) => fragments,
+        token => panic!("unexpected token {:?}", token),
+    };
+    
+    assert_eq!(fragments.len(), 3);
+    assert!(matches!(&fragments[0], StrFragment::Literal(s) if s == "a "));
+    assert!(matches!(&fragments[2], StrFragment::Literal(s) if s == "!{}"));
+    
+    // embedded tokens have symbols relative to the enclosing source
+    let tokens = match &fragments[1] {
+        StrFragment::Tokens(tokens) => tokens,
+        fragment => panic!("unexpected fragment {:?}", fragment),
+    };
+    
+    assert_eq!(tokens.len(), 4);
+    assert!(matches!(&tokens[0], TokenMeta { token: Token::Identifier(s), symbol, .. } if s == "b" && symbol.start() == 6));
+    assert!(matches!(&tokens[1], TokenMeta { token: Token::OpAdd, symbol, .. } if symbol.start() == 8));
+    assert!(matches!(&tokens[2], TokenMeta { token: Token::Identifier(s), symbol, .. } if s == "c" && symbol.start() == 10));
+    assert!(matches!(&tokens[3], TokenMeta { token: Token::EOF, .. }));
+    
+    assert!(matches!(lexer.next_token().unwrap().token, Token::EOF));
+}
+
+#[test]
+fn lexer_test_triple_quoted_interpolated_strings() {
+    let source = " f\"\"\"a \"{b}\"\n\"\"\" f\"\" foo f\"\"\"\"\"\" ";
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(IdentifierRule::new())
+        .add_rule(InterpolatedStringRule::new(core::iter::empty()))
+        .build_once(source);
+    
+    let out = lexer.next_token().unwrap();
+    assert_eq!(out.symbol.len(), 15);
+    
+    let fragments = match out.token {
+        Token::InterpolatedString(fragments) => fragments,
+        token => panic!("unexpected token {:?}", token),
+    };
+    
+    assert_eq!(fragments.len(), 3);
+    assert!(matches!(&fragments[0], StrFragment::Literal(s) if s == "a \""));
+    assert!(matches!(&fragments[1], StrFragment::Tokens(tokens) if tokens.len() == 2));
+    assert!(matches!(&fragments[2], StrFragment::Literal(s) if s == "\"\n"));
+    
+    // two quotes are still an empty string
+    assert!(matches!(lexer.next_token().unwrap().token, Token::InterpolatedString(fragments) if fragments.is_empty()));
+    assert!(matches!(lexer.next_token().unwrap().token, Token::Identifier(s) if s == "foo"));
+    assert!(matches!(lexer.next_token().unwrap().token, Token::InterpolatedString(fragments) if fragments.is_empty()));
+    
+    assert!(matches!(lexer.next_token().unwrap().token, Token::EOF));
+}
+
+#[test]
+fn lexer_test_interpolated_string_errors() {
+    let source = r#" f"{}" f"}" f"{1}\q" "#;
+    
+    let mut lexer = LexerBuilder::new()
+        .add_rule(InterpolatedStringRule::new(language::all_escape_sequences()))
+        .build_once(source);
+    
+    assert_token_sequence!(lexer,
+        
+        error if symbol.start() == 3 && symbol.len() == 2 => {
+            kind: ErrorKind::CouldNotReadToken,
+            symbol,
+            ..
+        } "empty expression",
+        
+        error if symbol.start() == 9 && symbol.len() == 1 => {
+            kind: ErrorKind::CouldNotReadToken,
+            symbol,
+            ..
+        } "unmatched brace",
+        
+        error if symbol.start() == 17 && symbol.len() == 2 => {
+            kind: ErrorKind::CouldNotReadToken,
+            symbol,
+            ..
+        } "invalid escape",
+    );
+}
@@ -0,0 +1,65 @@
+#![cfg(test)]
+
+use crate::lexer::{LexerBuilder, Token, TokenMeta, TokenStream, TokenResult, ErrorKind};
+use crate::lexer::rules::{SingleCharRule, MultiCharRule};
+
+fn token_stream(source: &str) -> TokenStream<impl Iterator<Item=TokenResult> + '_> {
+    let lexer = LexerBuilder::new()
+        .add_rule(SingleCharRule::new(Token::OpAdd, '+'))
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(1), "one"))
+        .add_rule(MultiCharRule::new(Token::IntegerLiteral(2), "two"))
+        .build_once(source);
+    
+    TokenStream::new(lexer)
+}
+
+fn next_token(stream: &mut TokenStream<impl Iterator<Item=TokenResult>>) -> Token {
+    stream.next().unwrap().unwrap().token
+}
+
+#[test]
+fn token_stream_peeks_ahead() {
+    let mut stream = token_stream("one + two");
+    
+    assert!(matches!(stream.peek_nth(2), Some(Ok(TokenMeta { token: Token::IntegerLiteral(2), .. }))));
+    assert!(matches!(stream.peek_nth(3), Some(Ok(TokenMeta { token: Token::EOF, .. }))));
+    assert!(matches!(stream.peek(), Some(Ok(TokenMeta { token: Token::IntegerLiteral(1), .. }))));
+    
+    assert!(matches!(next_token(&mut stream), Token::IntegerLiteral(1)));
+    assert!(matches!(next_token(&mut stream), Token::OpAdd));
+    assert!(matches!(stream.peek_nth(1), Some(Ok(TokenMeta { token: Token::EOF, .. }))));
+    assert!(matches!(next_token(&mut stream), Token::IntegerLiteral(2)));
+}
+
+#[test]
+fn token_stream_rollback() {
+    let mut stream = token_stream("one $ two + one");
+    
+    assert!(matches!(next_token(&mut stream), Token::IntegerLiteral(1)));
+    
+    let outer = stream.checkpoint();
+    assert!(matches!(stream.next(), Some(Err(error)) if matches!(error.kind(), ErrorKind::NoMatchingRule)));
+    
+    let inner = stream.checkpoint();
+    assert!(matches!(next_token(&mut stream), Token::IntegerLiteral(2)));
+    stream.commit(inner);
+    assert!(matches!(next_token(&mut stream), Token::OpAdd));
+    
+    // errors are replayed too
+    stream.rollback(outer);
+    assert!(matches!(stream.next(), Some(Err(error)) if matches!(error.kind(), ErrorKind::NoMatchingRule)));
+    assert!(matches!(next_token(&mut stream), Token::IntegerLiteral(2)));
+    assert!(matches!(next_token(&mut stream), Token::OpAdd));
+    assert!(matches!(next_token(&mut stream), Token::IntegerLiteral(1)));
+    assert!(matches!(next_token(&mut stream), Token::EOF));
+}
+
+#[test]
+#[should_panic]
+fn token_stream_checkpoints_are_nested() {
+    let mut stream = token_stream("one two");
+    
+    let outer = stream.checkpoint();
+    let _inner = stream.checkpoint();
+    stream.rollback(outer);
+}
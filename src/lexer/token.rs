@@ -19,31 +19,36 @@ pub enum Token {
     Decorator,
     
     // Operator Symbols
-    OpAdd, OpSub, OpMul, OpDiv, OpMod, OpExp,
-    OpInv, OpAnd, OpOr, OpXor, OpLShift, OpRShift,
+    OpAdd, OpSub, OpMul, OpDiv, OpMod, OpFloorDiv, OpExp,
+    OpInv, OpAnd, OpOr, OpXor, OpLShift, OpRShift, OpPipe,
     
-    OpAddAssign, OpSubAssign, OpMulAssign, OpDivAssign, OpModAssign,
+    OpAddAssign, OpSubAssign, OpMulAssign, OpDivAssign, OpModAssign, OpFloorDivAssign, OpExpAssign,
     OpAndAssign, OpOrAssign, OpXorAssign, OpLShiftAssign, OpRShiftAssign,
     
     OpLT, OpLE, OpGT, OpGE, OpEQ, OpNE,
-    OpAssign, OpAccess,
+    OpAssign, OpAccess, OpOptAccess, OpCoalesce,
     
     // Keywords
-    And, Or, Not,
+    And, Or, Not, Is,
     True, False, Nil,
     Let, Var, Local, NonLocal, Del,
     If, Then, Elif, Else,
     Begin, Loop, While, For, In, Do,
     Continue, Break, Return,
+    Try, Except, Finally, Raise, As,
     Fun, Class,
-    // Self_, Super,
+    Match, Case,
+    Self_, Super,
     Assert,
+    Import, Export,
     End,
     
     // Literals
     Identifier(String),
     StringLiteral(String),
+    InterpolatedString(Vec<StrFragment>),
     IntegerLiteral(IntType),
+    BigIntegerLiteral(String),  // the source text of an integer literal that is too large for IntType
     FloatLiteral(FloatType),
     
     // Misc
@@ -53,6 +58,19 @@ pub enum Token {
 }
 
 
+/// A piece of an interpolated string literal
+#[derive(Clone, Debug)]
+pub enum StrFragment {
+    Literal(String),
+    
+    // the source text of an embedded expression and its offset (in chars) from the start of the token
+    Source(String, usize),
+    
+    // an embedded expression after it has been tokenized
+    Tokens(Vec<TokenMeta>),
+}
+
+
 /// Token Output
 #[derive(Clone, Debug)]
 pub struct TokenMeta {
@@ -1,5 +1,6 @@
 // disable these until we have a working system
 #![allow(dead_code)]
+#![allow(clippy::needless_late_init, clippy::len_without_is_empty, clippy::new_without_default)]
 // #![allow(unused_imports)]
 #![feature(ptr_metadata)]
 
@@ -26,7 +27,7 @@ pub mod debug;
 use source::{SourceText, ModuleSource, ParseContext};
 use parser::ParserError;
 use parser::stmt::StmtMeta;
-use codegen::{CompiledProgram, Compiler, CompileError};
+use codegen::{CompiledProgram, Compiler, CompileOptions, CompileError, CompileWarning};
 use runtime::strings::StringInterner;
 
 #[derive(Debug)]
@@ -38,13 +39,26 @@ pub enum BuildErrors {
 }
 
 pub fn build_module(source: &ModuleSource) -> Result<CompiledProgram, BuildErrors> {
+    build_module_with_options(source, CompileOptions::default())
+}
+
+pub fn build_module_with_options(source: &ModuleSource, options: CompileOptions) -> Result<CompiledProgram, BuildErrors> {
     let source_text = source.read_text()
         .map_err(BuildErrors::Source)?;
     
-    build_source(source_text)
+    let source_hash = source.source_hash()
+        .map_err(BuildErrors::Source)?;
+    
+    let mut program = build_source_with_options(source_text, options)?;
+    program.source_hash.replace(source_hash);
+    Ok(program)
 }
 
 pub fn build_source(source_text: SourceText) -> Result<CompiledProgram, BuildErrors> {
+    build_source_with_options(source_text, CompileOptions::default())
+}
+
+pub fn build_source_with_options(source_text: SourceText, options: CompileOptions) -> Result<CompiledProgram, BuildErrors> {
     let mut interner = StringInterner::new();
     
     // parsing
@@ -55,7 +69,8 @@ pub fn build_source(source_text: SourceText) -> Result<CompiledProgram, BuildErr
     }
     
     // compilation
-    let compile_result = compile_ast(interner, parse_result.unwrap());
+    let compiler = Compiler::with_options(interner, options);
+    let compile_result = compiler.compile_program(parse_result.unwrap().iter());
     
     if let Err(errors) = compile_result {
         return Err(BuildErrors::Compile(errors.into_boxed_slice()));
@@ -98,4 +113,11 @@ pub fn print_build_errors(errors: &BuildErrors, source: &ModuleSource) {
         }
     }
     
-}
\ No newline at end of file
+}
+
+pub fn print_build_warnings(warnings: &[CompileWarning], source: &ModuleSource) {
+    if !warnings.is_empty() {
+        println!("Warnings in {}:\n", source);
+        frontend::print_source_errors(source, warnings);
+    }
+}
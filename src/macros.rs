@@ -6,16 +6,16 @@ macro_rules! __namespace_item {
     ( $namespace:expr, let $name:tt $value:tt ) => {
         $namespace.create(
             stringify!($name).into(), 
-            crate::runtime::module::Access::ReadOnly, 
-            crate::runtime::Variant::from($value)
+            $crate::language::Access::ReadOnly, 
+            $crate::runtime::Variant::from($value)
         );
     };
     
     ( $namespace:expr, var $name:tt $value:tt ) => {
         $namespace.create(
             stringify!($name).into(), 
-            crate::runtime::module::Access::ReadWrite, 
-            crate::runtime::Variant::from($value)
+            $crate::language::Access::ReadWrite, 
+            $crate::runtime::Variant::from($value)
         );
     };
     
@@ -24,16 +24,16 @@ macro_rules! __namespace_item {
         let name = func.signature().name().unwrap();
         $namespace.create(
             name, 
-            crate::language::Access::ReadOnly, 
-            crate::runtime::Variant::from(func)
+            $crate::language::Access::ReadOnly, 
+            $crate::runtime::Variant::from(func)
         );
     };
     
     ( $namespace:expr, fun $name:tt $func:expr ) => {
         $namespace.create(
             stringify!($name).into(), 
-            crate::runtime::module::Access::ReadOnly, 
-            crate::runtime::Variant::from($func)
+            $crate::language::Access::ReadOnly, 
+            $crate::runtime::Variant::from($func)
         );
     };
 }
@@ -63,8 +63,8 @@ macro_rules! __count {
 macro_rules! __variadic {
     () => { None };
     ( $name:tt ) => {
-        Some(crate::runtime::function::Parameter::new(
-            stringify!($name), crate::language::Access::ReadWrite
+        Some($crate::runtime::function::Parameter::new(
+            stringify!($name), $crate::language::Access::ReadWrite
         ))
     };
 }
@@ -74,7 +74,7 @@ macro_rules! __variadic {
 macro_rules! __defaults {
     () => { None };
     ( $( $default_value:tt )+ ) => { 
-        Some(vec![ $( crate::runtime::Variant::from($default_value) ),+ ].into_boxed_slice())
+        Some(vec![ $( $crate::runtime::Variant::from($default_value) ),+ ].into_boxed_slice())
     };
 }
 
@@ -84,17 +84,17 @@ macro_rules! native_function {
     // with default params
     ( $func_name:tt, $env:expr $( , this ( $self_name:tt ) )? $( , vm ( $vm_name:tt ) )? $( , params ( $( $required:tt ),+ ) )? $( , defaults ( $( $default:tt = $default_value:expr ),+ ) )? $( , variadic ( $variadic:tt ) )? => $body:expr ) => {
         {
-            type Variant = crate::runtime::Variant;
-            type Signature = crate::runtime::function::Signature;
-            type Parameter = crate::runtime::function::Parameter;
-            type NativeFunction = crate::runtime::function::NativeFunction;
-            type VirtualMachine<'a> = crate::runtime::vm::VirtualMachine<'a>;
-            type ExecResult<T> = crate::runtime::errors::ExecResult<T>;
+            type Variant = $crate::runtime::Variant;
+            type Signature = $crate::runtime::function::Signature;
+            type Parameter = $crate::runtime::function::Parameter;
+            type NativeFunction = $crate::runtime::function::NativeFunction;
+            type VirtualMachine<'a> = $crate::runtime::vm::VirtualMachine<'a>;
+            type ExecResult<T> = $crate::runtime::errors::ExecResult<T>;
             
             let signature = Signature::new(
                 Some(stringify!($func_name)),
-                vec![ $( $( Parameter::new(stringify!($required), crate::language::Access::ReadWrite) ),+ )? ],
-                vec![ $( $( Parameter::new(stringify!($default), crate::language::Access::ReadWrite) ),+ )? ],
+                vec![ $( $( Parameter::new(stringify!($required), $crate::language::Access::ReadWrite) ),+ )? ],
+                vec![ $( $( Parameter::new(stringify!($default), $crate::language::Access::ReadWrite) ),+ )? ],
                 __variadic!( $( $variadic )? ),
             );
             
@@ -2,10 +2,10 @@ use std::collections::VecDeque;
 
 use log::debug;
 
-use crate::language::{InternSymbol, Access};
-use crate::lexer::{TokenMeta, Token, LexerError};
+use crate::language::{self, InternSymbol, Access, TokenRole};
+use crate::lexer::{TokenMeta, Token, StrFragment, LexerError, TokenStream};
 use crate::runtime::strings::StringInterner;
-use crate::debug::{SourceError, TokenIndex};
+use crate::debug::{SourceError, DebugSymbol, TokenIndex, TokenLength};
 
 
 pub mod expr;
@@ -14,27 +14,43 @@ pub mod primary;
 pub mod pattern;
 pub mod operator;
 pub mod fundefs;
+pub mod classdefs;
+pub mod matchdefs;
+pub mod visitor;
 pub mod errors;
 mod tests;
 
 pub use errors::{ParserError, ParseResult};
 
-use expr::{ExprMeta, Expr, ExprBlock, ConditionalBranch, TableItem, TableField};
-use stmt::{StmtMeta, StmtList, Stmt, Label, ControlFlow};
+use expr::{ExprMeta, Expr, ExprBlock, ConditionalBranch, TableItem, TableField, DictEntry};
+use stmt::{StmtMeta, StmtList, Stmt, Label, ControlFlow, ExceptClause};
 use primary::{Primary, Atom, AccessItem};
 use pattern::{Pattern, MatchAction, Assignment};
 use operator::{UnaryOp, BinaryOp, Precedence, PRECEDENCE_START, PRECEDENCE_END};
 use fundefs::{FunctionDef, SignatureDef, ParamDef, DefaultDef};
-use errors::{ErrorKind, ErrorContext, ContextTag};
+use classdefs::ClassDef;
+use matchdefs::{MatchCase, CasePattern};
+use errors::{ErrorKind, ErrorContext, ContextFrame, ContextTag};
 
 
 // Recursive descent parser
 
+// after this many errors in a single top-level statement, any more are likely to be cascading from the first ones
+const MAX_ERRORS_PER_STMT: usize = 5;
+
+// how deeply the parser can recurse into nested expressions, statements and patterns before the input is rejected,
+// so that pathological nesting can't overflow the stack
+const MAX_NESTING_DEPTH: usize = 128;
+
 pub struct Parser<'h, T> where T: Iterator<Item=Result<TokenMeta, LexerError>> {
     interner: &'h mut StringInterner,
-    tokens: T,
-    next: Option<Result<TokenMeta, LexerError>>,
+    tokens: TokenStream<T>,
     errors: VecDeque<ParserError>,
+    stmt_errors: usize,  // errors found in the current top-level statement
+    block_depth: usize,  // the number of blocks that have been opened but not closed by an "end"
+    prev_end: TokenIndex,  // the end of the last token that was consumed
+    no_in: bool,  // "in" is not an operator while parsing the target of a for-loop
+    nesting: usize,  // the number of nested expressions, statements and patterns currently being parsed
 }
 
 impl<T> Iterator for Parser<'_, T> where T: Iterator<Item=Result<TokenMeta, LexerError>> {
@@ -46,9 +62,14 @@ impl<'h, I> Parser<'h, I> where I: Iterator<Item=Result<TokenMeta, LexerError>>
     
     pub fn new(interner: &'h mut StringInterner, tokens: I) -> Self {
         Parser {
-            tokens, interner,
-            next: None,
+            tokens: TokenStream::new(tokens),
+            interner,
             errors: VecDeque::new(),
+            stmt_errors: 0,
+            block_depth: 0,
+            prev_end: 0,
+            no_in: false,
+            nesting: 0,
         }
     }
 }
@@ -61,11 +82,11 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
     }
     
     fn advance(&mut self) -> ParseResult<TokenMeta> {
-        let next = self.next.take()
-            .or_else(|| self.tokens.next());
-        
-        if let Some(result) = next {
-            Ok(result?)
+        if let Some(result) = self.tokens.next() {
+            let next = result?;
+            self.track_block_depth(&next.token);
+            self.prev_end = next.symbol.end();
+            Ok(next)
         } else {
             Err(ErrorKind::EndofTokenStream.into())
         }
@@ -74,22 +95,40 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
     // peek() will consume any errors it encounters (i.e. peek() acts like advance() if the next token was a lexer error)
     // this is so that we don't have to do a complex map_err() every single time we call self.peek()
     fn peek(&mut self) -> ParseResult<&TokenMeta> {
-        if self.next.is_none() {
-            self.next = self.tokens.next();
-            if self.next.is_none() {
-                return Err(ErrorKind::EndofTokenStream.into());
-            }
+        match self.tokens.peek() {
+            None => return Err(ErrorKind::EndofTokenStream.into()),
+            Some(Err(..)) => return Err(self.advance().unwrap_err()),
+            Some(Ok(..)) => { },
         }
         
         // This is needed to finagle a reference in one branch while advancing the 
         // token iterator and taking ownership of the ParserError in the other
-        if self.next.as_ref().unwrap().is_ok() {
-            Ok(self.next.as_ref().unwrap().as_ref().unwrap()) // yes, the repetition is required
-        } else {
-            Err(self.advance().unwrap_err())
+        Ok(self.tokens.peek().unwrap().as_ref().unwrap()) // yes, the repetition is required
+    }
+    
+    // check the token `n` places after the next one, without consuming anything
+    fn is_next_nth(&mut self, n: usize, predicate: impl Fn(&Token) -> bool) -> bool {
+        matches!(self.tokens.peek_nth(n), Some(Ok(next)) if predicate(&next.token))
+    }
+    
+    // every keyword that starts a block is eventually followed by a matching "end"
+    fn track_block_depth(&mut self, token: &Token) {
+        match token {
+            Token::If | Token::While | Token::For | Token::Loop | Token::Begin 
+            | Token::Try | Token::Fun | Token::Class | Token::Match => self.block_depth += 1,
+            
+            Token::End => self.block_depth = self.block_depth.saturating_sub(1),
+            
+            _ => { },
         }
     }
     
+    // the span from the given index to the end of the last token that was consumed
+    fn span_from(&self, start: TokenIndex) -> DebugSymbol {
+        let length = TokenLength::try_from(self.prev_end.saturating_sub(start)).unwrap_or(TokenLength::MAX);
+        DebugSymbol::new(start, length)
+    }
+    
     fn intern_str(&mut self, string: impl AsRef<str>) -> InternSymbol {
         self.interner.get_or_intern(string)
     }
@@ -120,17 +159,18 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             }
         }
         
+        self.stmt_errors = 0;
         let result = match self.parse_stmt(&mut ctx) {
             Ok(stmt) => {
                 debug!("parser: {:?}", stmt); 
                 Ok(stmt)
             },
             Err(error) => {
-                self.errors.push_back(error);
+                self.push_error(error);
                 let error = self.errors.pop_front().unwrap();
                 let error = Self::process_error(ctx, error);
                 
-                self.synchronize_stmt(false);
+                self.synchronize_stmt(0, false);
                 
                 Err(error)
             },
@@ -151,7 +191,64 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         error
     }
     
-    fn catch_error_and_sync<T>(&mut self, ctx: &ErrorContext, result: ParseResult<T>, inside_block: bool) -> Option<ParseResult<T>> {
+    fn push_error(&mut self, error: ParserError) {
+        if self.stmt_errors < MAX_ERRORS_PER_STMT {
+            self.errors.push_back(error);
+        }
+        self.stmt_errors += 1;
+    }
+    
+    // every recursive descent into a nested construct goes through here, so that the depth can be limited
+    fn parse_nested<R>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<R>) -> ParseResult<R> {
+        if self.nesting >= MAX_NESTING_DEPTH {
+            // consume the token so that error recovery can't get stuck on it
+            let next = self.peek()?;
+            let symbol = next.symbol;
+            if !matches!(next.token, Token::EOF) {
+                self.advance().unwrap();
+            }
+            return Err(ParserError::from(ErrorKind::NestingLimit).with_symbol(symbol));
+        }
+        
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        result
+    }
+    
+    // the span of a statement or expression can be too long to fit in a debug symbol
+    fn frame_symbol(frame: &ContextFrame) -> ParseResult<DebugSymbol> {
+        frame.as_debug_symbol().ok_or_else(|| {
+            let error = ParserError::from(ErrorKind::StatementTooLong);
+            match frame.start() {
+                Some(start) => error.with_symbol(*start),
+                None => error,
+            }
+        })
+    }
+    
+    // At the end of the input, the location of an unclosed block or delimiter is more helpful than the end of the file
+    fn unclosed_block_error(ctx: &ErrorContext) -> ParserError {
+        let block = ctx.frames().find_map(|frame| {
+            let keyword = frame.context().block_keyword()?;
+            Some((keyword, frame.start()))
+        });
+        
+        match block {
+            Some((keyword, Some(start))) => ParserError::from(ErrorKind::UnclosedBlock(keyword)).with_symbol(*start),
+            _ => "expected \"end\" before the end of the input".into(),
+        }
+    }
+    
+    fn unclosed_delimiter_error(next: &TokenMeta, open: (&'static str, Option<&DebugSymbol>), message: &'static str) -> ParserError {
+        match (&next.token, open) {
+            (Token::EOF, (delimiter, Some(start))) => ParserError::from(ErrorKind::UnclosedDelimiter(delimiter)).with_symbol(*start),
+            _ => ErrorKind::ExpectedClosing(message).into(),
+        }
+    }
+    
+    // the context is restored to the given depth, discarding the frames that were left behind by the error
+    fn catch_error_and_sync<T>(&mut self, ctx: &mut ErrorContext, ctx_depth: usize, result: ParseResult<T>, block_depth: usize) -> Option<ParseResult<T>> {
         match result {
             Ok(..) => Some(result),
             Err(error) => {
@@ -159,8 +256,13 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                     return Some(Err(error));
                 }
                 
-                self.errors.push_back(error.with_symbol_from_ctx(ctx));
-                self.synchronize_stmt(inside_block);
+                let error = error.with_symbol_from_ctx(ctx).with_context_tags(ctx);
+                while ctx.depth() > ctx_depth {
+                    ctx.pop_extend();
+                }
+                
+                self.push_error(error);
+                self.synchronize_stmt(block_depth, true);
                 
                 // if the next token is EOF there is no point catching an error
                 // since there is no more source code to examine anyways
@@ -177,14 +279,25 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
     
     // If we hit an error we need to synchronize back to a likely-valid state before we continue parsing again
     // To do this, just keep discarding tokens until we think we're at the start of a new statement
-    fn synchronize_stmt(&mut self, inside_block: bool) {
+    fn synchronize_stmt(&mut self, block_depth: usize, inside_block: bool) {
         // Check for either: a token that only appears at the start of a new statement
         // OR try to parse an expression. If we can do it without errors, assume we're in a good state. The expression can be discarded.
         debug!("sync to next stmt...");
         
-        let mut ctx = ErrorContext::new(ContextTag::Sync);
+        // first, skip to the end of any blocks that were opened by the statement that failed,
+        // otherwise their contents and "end" are mistaken for more errors
+        while self.block_depth > block_depth {
+            match self.peek() {
+                Err(error) if matches!(error.kind(), ErrorKind::EndofTokenStream) => return,
+                Err(..) => continue,
+                Ok(TokenMeta { token: Token::EOF, .. }) => return,
+                Ok(..) => { self.advance().unwrap(); },
+            }
+        }
         
         loop {
+            // a fresh context for each attempt, since a failed parse leaves its frames behind
+            let mut ctx = ErrorContext::new(ContextTag::Sync);
 
             let next = match self.peek() {
                 // no more tokens...
@@ -198,9 +311,9 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
 
             match next.token {
                 Token::EOF | Token::Semicolon |
-                Token::While  | Token::Loop | Token::For |
+                Token::While  | Token::Loop | Token::For | Token::Try |
                 Token::Continue | Token::Break | Token::Return | 
-                Token::Label(..) | Token::Assert
+                Token::Label(..) | Token::Assert | Token::Import | Token::Export
                     => break,
                 
                 Token::End if inside_block => break,
@@ -226,9 +339,8 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         debug!("parsing stmt at index {}...", self.current_index());
         
         ctx.push(ContextTag::StmtMeta);
-        
-        let stmt = self.parse_stmt_variant(ctx)?;
-        let symbol = ctx.frame().as_debug_symbol().unwrap();
+        let stmt = self.parse_nested(|parser| parser.parse_stmt_variant(ctx))?;
+        let symbol = Self::frame_symbol(ctx.frame())?;
         
         ctx.pop_extend();
         Ok(StmtMeta::new(stmt, symbol))
@@ -241,6 +353,8 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             Token::While => self.parse_while_loop(ctx, None)?,
             Token::For => self.parse_for_loop(ctx, None)?,
             
+            Token::Try => self.parse_try_except(ctx)?,
+            
             Token::Label(..) => self.parse_stmt_label(ctx)?,
             
             Token::Assert => {
@@ -248,6 +362,13 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 Stmt::Assert(self.parse_expr_variant(ctx)?)
             }
             
+            Token::Import => self.parse_import(ctx)?,
+            
+            Token::Export => {
+                ctx.set_start(&self.advance().unwrap());
+                Stmt::Export(self.parse_expr_variant(ctx)?)
+            }
+            
             Token::Continue | Token::Break | Token::Return => {
                 let next = self.advance().unwrap();
                 
@@ -335,7 +456,10 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         debug_assert!(matches!(next.token, Token::For));
         
         // parse pattern list
-        let pattern = self.parse_lvalue_list(ctx)?;
+        self.no_in = true;
+        let pattern = self.parse_lvalue_list(ctx);
+        self.no_in = false;
+        let pattern = pattern?;
         
         let next = self.advance()?;
         ctx.set_end(&next);
@@ -369,6 +493,93 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         Ok(for_loop)
     }
     
+    /*
+        import-statement ::= "import" identifier ( "." identifier )* ;
+    */
+    fn parse_import(&mut self, ctx: &mut ErrorContext) -> ParseResult<Stmt> {
+        let next = self.advance()?;
+        
+        ctx.push(ContextTag::Import);
+        ctx.set_start(&next);
+        debug_assert!(matches!(next.token, Token::Import));
+        
+        let mut path = String::new();
+        loop {
+            let next = self.advance()?;
+            ctx.set_end(&next);
+            
+            if let Token::Identifier(name) = next.token {
+                path.push_str(&name);
+            } else {
+                return Err("expected a module name after \"import\"".into());
+            }
+            
+            if !matches!(self.peek()?.token, Token::OpAccess) {
+                break;
+            }
+            
+            ctx.set_end(&self.advance().unwrap()); // consume "."
+            path.push('.');
+        }
+        
+        ctx.pop_extend();
+        Ok(Stmt::Import(self.intern_str(path)))
+    }
+    
+    /*
+        try-statement ::= "try" statement-list ( "except" ( "as" identifier )? statement-list )? ( "finally" statement-list )? "end" ;
+        
+        At least one of "except" or "finally" must be present.
+    */
+    fn parse_try_except(&mut self, ctx: &mut ErrorContext) -> ParseResult<Stmt> {
+        let next = self.advance()?;
+        
+        ctx.push(ContextTag::TryExcept);
+        ctx.set_start(&next);
+        debug_assert!(matches!(next.token, Token::Try));
+        
+        let body = self.parse_stmt_list(ctx, |token| matches!(token, Token::Except | Token::Finally | Token::End))?;
+        
+        let mut handler = None;
+        if matches!(self.peek()?.token, Token::Except) {
+            ctx.set_end(&self.advance().unwrap()); // consume "except"
+            
+            let mut name = None;
+            if matches!(self.peek()?.token, Token::As) {
+                ctx.set_end(&self.advance().unwrap()); // consume "as"
+                
+                let next = self.advance()?;
+                ctx.set_end(&next);
+                
+                if let Token::Identifier(ident) = next.token {
+                    name.replace(self.intern_str(ident));
+                } else {
+                    return Err("expected an identifier after \"as\"".into());
+                }
+            }
+            
+            let body = self.parse_stmt_list(ctx, |token| matches!(token, Token::Finally | Token::End))?;
+            handler.replace(ExceptClause::new(name, body));
+        }
+        
+        let mut finally = None;
+        if matches!(self.peek()?.token, Token::Finally) {
+            ctx.set_end(&self.advance().unwrap()); // consume "finally"
+            
+            let body = self.parse_stmt_list(ctx, |token| matches!(token, Token::End))?;
+            finally.replace(body);
+        }
+        
+        ctx.set_end(&self.advance().unwrap()); // consume "end"
+        
+        if handler.is_none() && finally.is_none() {
+            return Err("expected \"except\" or \"finally\" in try-statement".into());
+        }
+        
+        ctx.pop_extend();
+        Ok(Stmt::TryExcept { body, handler, finally })
+    }
+    
     fn parse_lvalue_list(&mut self, ctx: &mut ErrorContext) -> ParseResult<Pattern> {
         let modifier = self.try_parse_assign_keyword(ctx)?;
         
@@ -386,6 +597,8 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
     /// Parses a list of statements, stopping when the given closure returns true. The final token is not consumed.
     fn parse_stmt_list(&mut self, ctx: &mut ErrorContext, end_list: impl Fn(&Token) -> bool) -> ParseResult<StmtList> {
         ctx.push(ContextTag::StmtList);
+        let ctx_depth = ctx.depth();
+        let block_depth = self.block_depth;
         
         let mut suite = Vec::new();
         let mut control = None;
@@ -403,9 +616,12 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             if end_list(&next.token) {
                 break;
             }
+            if matches!(next.token, Token::EOF) {
+                return Err(Self::unclosed_block_error(ctx));
+            }
             
             let parse_result = self.try_parse_control_flow(ctx);
-            control = match self.catch_error_and_sync(ctx, parse_result, true) {
+            control = match self.catch_error_and_sync(ctx, ctx_depth, parse_result, block_depth) {
                 Some(result) => result?,
                 None => continue,
             };
@@ -415,6 +631,10 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 
                 let next = self.peek()?;
                 if !end_list(&next.token) {
+                    if matches!(next.token, Token::EOF) {
+                        return Err(Self::unclosed_block_error(ctx));
+                    }
+                    
                     // consume the unexpected token so that it is included in the error message
                     ctx.set_end(&self.advance().unwrap());
                     
@@ -428,14 +648,14 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                     let error = ParserError::from(ErrorKind::SyntaxError(message))
                         .with_symbol_from_ctx(ctx);
                     
-                    self.errors.push_back(error);
+                    self.push_error(error);
                 }
                 
                 break;
             }
             
             let parse_result = self.parse_stmt(ctx);
-            let stmt = match self.catch_error_and_sync(ctx, parse_result, true) {
+            let stmt = match self.catch_error_and_sync(ctx, ctx_depth, parse_result, block_depth) {
                 Some(result) => result?,
                 None => continue,
             };
@@ -487,7 +707,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 let label = self.try_parse_label(ctx)?;
                 
                 let expr = 
-                    if !matches!(self.peek()?.token, Token::End | Token::Elif | Token::Else | Token::Semicolon ) {
+                    if !matches!(self.peek()?.token, Token::End | Token::Elif | Token::Else | Token::Case | Token::Except | Token::Finally | Token::Semicolon ) {
                         Some(Box::new(self.parse_expr_variant(ctx)?))
                     } else { None };
                 
@@ -502,7 +722,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 ctx.set_start(&self.advance().unwrap());
                 
                 let expr = 
-                    if !matches!(self.peek()?.token, Token::End | Token::Elif | Token::Else | Token::Semicolon ) {
+                    if !matches!(self.peek()?.token, Token::End | Token::Elif | Token::Else | Token::Case | Token::Except | Token::Finally | Token::Semicolon ) {
                         Some(Box::new(self.parse_expr_variant(ctx)?))
                     } else { None };
                 
@@ -525,7 +745,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         ctx.push(ContextTag::ExprMeta);
         
         let variant = self.parse_expr_variant(ctx)?;
-        let symbol = ctx.frame().as_debug_symbol().unwrap();
+        let symbol = Self::frame_symbol(ctx.frame())?;
         
         ctx.pop_extend();
         Ok(ExprMeta::new(variant, symbol))
@@ -542,7 +762,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         pattern ::= identifier | primary index-access | primary member-access ;
 
         pattern-expression ::= pattern | pattern-list | "(" pattern ")" ;   (* basically just lvalues, and tuples of lvalues *)
-        pattern-list ::= pattern-expression ( "," pattern-expression )* ;
+        pattern-list ::= pattern-expression ( "," pattern-expression )* ","? ;
 
         pattern-annotated ::= pattern-expression ( ":" type-expression )? ; 
 
@@ -619,7 +839,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             if let Some(first_expr) = first_expr.take() {
                 // retroactivly get debug symbol
                 let frame = ctx.pop();
-                let symbol = frame.as_debug_symbol().unwrap();
+                let symbol = Self::frame_symbol(&frame)?;
                 tuple_exprs.push(ExprMeta::new(first_expr, symbol));
                 
                 ctx.push_continuation(ContextTag::TupleCtor, Some(frame)); // enter the tuple context
@@ -627,14 +847,15 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             
             ctx.set_end(&self.advance().unwrap()); // consume comma
             
+            // allow a trailing comma
             let next = self.peek()?;
-            if matches!(next.token, Token::CloseParen) {
+            if Self::is_tuple_end(&next.token) {
                 break;
             }
             
             ctx.push(ContextTag::ExprMeta);
             let next_expr = self.parse_inner_expr(ctx)?;
-            let symbol = ctx.frame().as_debug_symbol().unwrap();
+            let symbol = Self::frame_symbol(ctx.frame())?;
             ctx.pop_extend();
             
             tuple_exprs.push(ExprMeta::new(next_expr, symbol));
@@ -649,6 +870,13 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         }
     }
     
+    // Tokens that can follow a trailing comma without making the end of the tuple ambiguous.
+    // A bare tuple can't end with a comma at the end of a statement, since the next statement could be taken as another item.
+    fn is_tuple_end(token: &Token) -> bool {
+        matches!(token, Token::CloseParen | Token::CloseSquare)
+        || Self::which_assignment_op(token).is_some()
+    }
+    
     // parse an expression in a position where bare (unparenthesized) tuples and assignments are not allowed
     fn parse_inner_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
         self.parse_binop_expr(ctx)
@@ -669,8 +897,15 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             return self.parse_unary_expr(ctx);  // exit binop precedence recursion
         }
         
-        let mut expr = self.parse_binop_expr_levels(ctx, level - 1)?;
+        // operands keep their own spans, so that errors can point to the operand that caused them
+        let start = self.peek()?.symbol.start();
+        let expr = self.parse_binop_expr_levels(ctx, level - 1)?;
         
+        // each nesting level passes through every precedence level, so the loop is kept out of this frame
+        self.parse_binop_chain(ctx, level, start, expr)
+    }
+    
+    fn parse_binop_chain(&mut self, ctx: &mut ErrorContext, level: Precedence, start: TokenIndex, mut expr: Expr) -> ParseResult<Expr> {
         let mut push_ctx = false;
         loop {
             let next = self.peek()?;
@@ -685,13 +920,38 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 break;
             }
             
+            if matches!(binary_op, BinaryOp::In | BinaryOp::NotIn) && self.no_in {
+                break;
+            }
+            
+            // "not" is only a binary operator when it is followed by "in"
+            if matches!(binary_op, BinaryOp::NotIn) && !self.is_next_nth(1, |token| matches!(token, Token::In)) {
+                break;
+            }
+            
+            // stop a long chain of operators as soon as it is too long for a debug symbol,
+            // instead of building an ever deeper tree
+            if self.prev_end.saturating_sub(start) > TokenIndex::from(TokenLength::MAX) {
+                return Err(ParserError::from(ErrorKind::StatementTooLong).with_symbol(DebugSymbol::new(start, 0)));
+            }
+            
+            let lhs_expr = ExprMeta::new(expr, self.span_from(start));
+            
             push_ctx = true;
             ctx.push_continuation(ContextTag::BinaryOpExpr, None);
             ctx.set_end(&self.advance().unwrap()); // consume binary_op token
+            if matches!(binary_op, BinaryOp::NotIn) {
+                ctx.set_end(&self.advance().unwrap()); // consume "in"
+            }
             
+            let rhs_start = self.peek()?.symbol.start();
             let rhs_expr = self.parse_binop_expr_levels(ctx, level - 1)?;
+            let rhs_expr = ExprMeta::new(rhs_expr, self.span_from(rhs_start));
             
-            expr = Expr::BinaryOp(binary_op, Box::new((expr, rhs_expr)));
+            expr = match binary_op {
+                BinaryOp::Pipe => Self::pipe_into_call(lhs_expr, rhs_expr),
+                _ => Expr::BinaryOp(binary_op, Box::new((lhs_expr, rhs_expr))),
+            };
         }
         
         if push_ctx {
@@ -704,7 +964,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
     /*
         Unary operator syntax:
         
-        unary-expression ::= ( "-" | "+" | "not" ) unary | primary ;
+        unary-expression ::= ( "-" | "+" | "not" ) unary | power ;
     */
     fn parse_unary_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
         let next = self.peek()?;
@@ -712,74 +972,84 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             ctx.push(ContextTag::UnaryOpExpr);
             ctx.set_start(&self.advance().unwrap()); // consume unary_op token
             
-            let expr = self.parse_unary_expr(ctx)?;
+            let start = self.peek()?.symbol.start();
+            let expr = self.parse_nested(|parser| parser.parse_unary_expr(ctx))?;
+            let expr = ExprMeta::new(expr, self.span_from(start));
             
             ctx.pop_extend();
             return Ok(Expr::UnaryOp(unary_op, Box::new(expr)));
         }
         
-        self.parse_primary_expr(ctx)
+        self.parse_nested(|parser| parser.parse_power_expr(ctx))
+    }
+    
+    /*
+        Exponentiation binds tighter than unary operators on the left, but not on the right:
+        
+        power ::= primary ( "**" unary )? ;
+    */
+    fn parse_power_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
+        let start = self.peek()?.symbol.start();
+        let expr = self.parse_primary_expr(ctx)?;
+        
+        if !matches!(self.peek()?.token, Token::OpExp) {
+            return Ok(expr);
+        }
+        let expr = ExprMeta::new(expr, self.span_from(start));
+        
+        ctx.push_continuation(ContextTag::BinaryOpExpr, None);
+        ctx.set_end(&self.advance().unwrap()); // consume "**"
+        
+        let rhs_start = self.peek()?.symbol.start();
+        let rhs_expr = self.parse_unary_expr(ctx)?;
+        let rhs_expr = ExprMeta::new(rhs_expr, self.span_from(rhs_start));
+        
+        ctx.pop_extend();
+        Ok(Expr::BinaryOp(BinaryOp::Exp, Box::new((expr, rhs_expr))))
     }
 
+    // Operators are looked up in the language's token table, which also determines their precedence
     fn which_unary_op(token: &Token) -> Option<UnaryOp> {
-        let op = match token {
-            Token::OpAdd => UnaryOp::Pos,
-            Token::OpSub => UnaryOp::Neg,
-            Token::OpInv => UnaryOp::Inv,
-            Token::Not   => UnaryOp::Not,
-            
-            _ => return None,
+        match language::token_role(token)? {
+            TokenRole::Operator { unary, .. } => unary,
+            _ => None,
+        }
+    }
+    
+    // "x |> f(a, b)" becomes "f(x, a, b)", and "x |> f" where f is anything other than a call becomes "f(x)"
+    fn pipe_into_call(lhs: ExprMeta, rhs: ExprMeta) -> Expr {
+        let (atom, mut path) = match rhs.take_variant() {
+            Expr::Primary(primary) => primary.take(),
+            Expr::Atom(atom) => (atom, Vec::new()),
+            other => (Atom::Group { modifier: None, inner: Box::new(other) }, Vec::new()),
         };
         
-        Some(op)
+        let args = match path.pop() {
+            Some(AccessItem::Invoke(args)) => core::iter::once(lhs).chain(args.into_vec()).collect(),
+            Some(item) => {
+                path.push(item);
+                vec![lhs]
+            },
+            None => vec![lhs],
+        };
+        
+        path.push(AccessItem::Invoke(args.into_boxed_slice()));
+        Expr::Primary(Primary::new(atom, path))
     }
     
     fn which_binary_op(token: &Token) -> Option<BinaryOp> {
-        let op = match token {
-            Token::OpMul => BinaryOp::Mul,
-            Token::OpDiv => BinaryOp::Div,
-            Token::OpMod => BinaryOp::Mod,
-            Token::OpAdd => BinaryOp::Add,
-            Token::OpSub => BinaryOp::Sub,
-            Token::OpLShift => BinaryOp::LShift,
-            Token::OpRShift => BinaryOp::RShift,
-            Token::OpAnd => BinaryOp::BitAnd,
-            Token::OpXor => BinaryOp::BitXor,
-            Token::OpOr => BinaryOp::BitOr,
-            Token::OpLT => BinaryOp::LT,
-            Token::OpGT => BinaryOp::GT,
-            Token::OpLE => BinaryOp::LE,
-            Token::OpGE => BinaryOp::GE,
-            Token::OpEQ => BinaryOp::EQ,
-            Token::OpNE => BinaryOp::NE,
-            Token::And => BinaryOp::And,
-            Token::Or => BinaryOp::Or,
-            
-            _ => return None,
-        };
-        
-        Some(op)
+        match language::token_role(token)? {
+            TokenRole::Operator { binary, .. } => binary.map(|(op, _)| op),
+            _ => None,
+        }
     }
     
     // Helper to see if the a token is an assignment operator and if so, which one it is.
     fn which_assignment_op(token: &Token) -> Option<Option<BinaryOp>> {
-        let op = match token {
-            Token::OpAssign    => None,
-            Token::OpAddAssign => Some(BinaryOp::Add),
-            Token::OpSubAssign => Some(BinaryOp::Sub),
-            Token::OpMulAssign => Some(BinaryOp::Mul),
-            Token::OpDivAssign => Some(BinaryOp::Div),
-            Token::OpModAssign => Some(BinaryOp::Mod),
-            Token::OpAndAssign => Some(BinaryOp::BitAnd),
-            Token::OpOrAssign  => Some(BinaryOp::BitOr),
-            Token::OpXorAssign => Some(BinaryOp::BitXor),
-            Token::OpLShiftAssign => Some(BinaryOp::LShift),
-            Token::OpRShiftAssign => Some(BinaryOp::RShift),
-            
-            _ => return None,
-        };
-        
-        Some(op)
+        match language::token_role(token)? {
+            TokenRole::Assignment(op) => Some(op),
+            _ => None,
+        }
     }
     
     /*
@@ -788,13 +1058,18 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
     */
     fn parse_primary_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
         let expr = match self.peek()?.token {
-            Token::Class => unimplemented!(),
+            Token::Class => self.parse_class_decl_expr(ctx)?,
             Token::Fun => self.parse_function_decl_expr(ctx)?,
+            Token::OpOr => self.parse_lambda_expr(ctx)?,
             
             Token::If => self.parse_if_expr(ctx)?,
+            Token::Match => self.parse_match_expr(ctx)?,
             Token::Begin => self.parse_block_expr(ctx, None)?,
             
-            Token::OpenBrace => self.parse_table_expr(ctx)?,
+            Token::Raise => {
+                ctx.set_end(&self.advance().unwrap()); // consume "raise"
+                Expr::Raise(Box::new(self.parse_inner_expr(ctx)?))
+            },
             
             Token::Label(..) => self.parse_expr_label(ctx)?,
             
@@ -914,6 +1189,200 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         Ok(if_expr)
     }
     
+    /*
+        match-expression ::= "match" expression match-case* ( "else" statement-list )? "end" ;
+        match-case ::= "case" case-pattern-list ( "if" expression )? "then" statement-list ;
+        
+        case-pattern-list ::= case-pattern ( "," case-pattern )* ","? ;
+        case-pattern ::= "_" | IDENTIFIER | literal | "-" number | "(" ")" | "(" case-pattern-list ")" | IDENTIFIER? "..." ;
+    */
+    fn parse_match_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
+        let next = self.advance()?;
+        
+        ctx.push(ContextTag::MatchExpr);
+        ctx.set_start(&next);
+        
+        debug_assert!(matches!(next.token, Token::Match));
+        
+        let subject = self.parse_expr(ctx)?;
+        
+        let mut cases = Vec::new();
+        let mut else_clause = None;
+        
+        loop {
+            // statement separators
+            while matches!(self.peek()?.token, Token::Semicolon) {
+                ctx.set_end(&self.advance().unwrap());
+            }
+            
+            let next = self.advance()?;
+            ctx.set_end(&next);
+            
+            match next.token {
+                Token::Case => cases.push(self.parse_match_case(ctx)?),
+                
+                Token::Else => {
+                    let stmt_list = self.parse_stmt_list(ctx, |token| matches!(token, Token::End))?;
+                    else_clause.replace(ExprBlock::from(stmt_list));
+                    
+                    ctx.set_end(&self.advance().unwrap()); // consume "end"
+                    
+                    break;
+                },
+                
+                Token::End => break,
+                
+                Token::EOF => return Err(Self::unclosed_block_error(ctx)),
+                
+                _ => return Err("expected \"case\", \"else\", or \"end\" in match expression".into()),
+            }
+        }
+        
+        ctx.pop_extend();
+        
+        let match_expr = Expr::Match {
+            subject: Box::new(subject),
+            cases: cases.into_boxed_slice(),
+            else_clause: else_clause.map(Box::new),
+        };
+        Ok(match_expr)
+    }
+    
+    fn parse_match_case(&mut self, ctx: &mut ErrorContext) -> ParseResult<MatchCase> {
+        ctx.push(ContextTag::MatchCase);
+        
+        let pattern = self.parse_case_pattern_list(ctx)?;
+        
+        let bindings = pattern.bindings();
+        if bindings.iter().enumerate().any(|(idx, name)| bindings[..idx].contains(name)) {
+            return Err("a name can only be bound once in a case pattern".into());
+        }
+        
+        let guard =
+            if matches!(self.peek()?.token, Token::If) {
+                ctx.set_end(&self.advance().unwrap()); // consume "if"
+                Some(self.parse_expr_variant(ctx)?)
+            } else { None };
+        
+        let next = self.advance()?;
+        ctx.set_end(&next);
+        
+        if !matches!(next.token, Token::Then) {
+            return Err("expected \"then\" after case pattern".into());
+        }
+        
+        let symbol = Self::frame_symbol(ctx.frame())?;
+        ctx.pop_extend();
+        
+        let stmt_list = self.parse_stmt_list(ctx, |token| matches!(token, Token::Case | Token::Else | Token::End))?;
+        
+        let case = MatchCase {
+            pattern,
+            guard,
+            suite: stmt_list.into(),
+            symbol,
+        };
+        Ok(case)
+    }
+    
+    // several patterns separated by commas are a tuple pattern, as is a lone "name..."
+    fn parse_case_pattern_list(&mut self, ctx: &mut ErrorContext) -> ParseResult<CasePattern> {
+        let first = self.parse_case_pattern(ctx)?;
+        
+        if !matches!(self.peek()?.token, Token::Comma) {
+            if matches!(first, CasePattern::Rest(..)) {
+                return Ok(CasePattern::Tuple(Box::new([first])));
+            }
+            return Ok(first);
+        }
+        
+        let mut items = vec![first];
+        while matches!(self.peek()?.token, Token::Comma) {
+            ctx.set_end(&self.advance().unwrap()); // consume comma
+            
+            // allow a trailing comma
+            if matches!(self.peek()?.token, Token::CloseParen | Token::If | Token::Then) {
+                break;
+            }
+            
+            items.push(self.parse_case_pattern(ctx)?);
+        }
+        
+        if items.iter().filter(|item| matches!(item, CasePattern::Rest(..))).count() > 1 {
+            return Err("a tuple pattern may contain only one \"...\"".into());
+        }
+        
+        Ok(CasePattern::Tuple(items.into_boxed_slice()))
+    }
+    
+    fn parse_case_pattern(&mut self, ctx: &mut ErrorContext) -> ParseResult<CasePattern> {
+        let next = self.advance()?;
+        ctx.set_end(&next);
+        
+        let pattern = match next.token {
+            Token::Identifier(name) => {
+                let is_rest = matches!(self.peek()?.token, Token::Ellipsis);
+                if is_rest {
+                    ctx.set_end(&self.advance().unwrap()); // consume "..."
+                }
+                
+                match (name.as_str(), is_rest) {
+                    ("_", false) => CasePattern::Wildcard,
+                    ("_", true) => CasePattern::Rest(None),
+                    (_, false) => CasePattern::Binding(self.intern_str(name)),
+                    (_, true) => CasePattern::Rest(Some(self.intern_str(name))),
+                }
+            },
+            
+            Token::Ellipsis => CasePattern::Rest(None),
+            
+            // Literals
+            Token::Nil   => CasePattern::Literal(Atom::Nil),
+            Token::True  => CasePattern::Literal(Atom::BooleanLiteral(true)),
+            Token::False => CasePattern::Literal(Atom::BooleanLiteral(false)),
+            
+            Token::IntegerLiteral(value) => CasePattern::Literal(Atom::IntegerLiteral(value)),
+            Token::BigIntegerLiteral(text) => CasePattern::Literal(Atom::BigIntegerLiteral(self.intern_str(text))),
+            Token::FloatLiteral(value)   => CasePattern::Literal(Atom::FloatLiteral(value)),
+            Token::StringLiteral(value)  => CasePattern::Literal(Atom::StringLiteral(self.intern_str(value))),
+            
+            Token::OpSub => {
+                let next = self.advance()?;
+                ctx.set_end(&next);
+                
+                match next.token {
+                    Token::IntegerLiteral(value) => CasePattern::Literal(Atom::IntegerLiteral(-value)),
+                    Token::BigIntegerLiteral(text) => {
+                        let text = format!("-{}", text);
+                        CasePattern::Literal(Atom::BigIntegerLiteral(self.intern_str(text)))
+                    },
+                    Token::FloatLiteral(value)   => CasePattern::Literal(Atom::FloatLiteral(-value)),
+                    _ => return Err("expected a number after \"-\" in case pattern".into()),
+                }
+            },
+            
+            Token::OpenParen => {
+                if matches!(self.peek()?.token, Token::CloseParen) {
+                    ctx.set_end(&self.advance().unwrap());
+                    return Ok(CasePattern::Tuple(Box::new([])));
+                }
+                
+                let pattern = self.parse_nested(|parser| parser.parse_case_pattern_list(ctx))?;
+                
+                let close = self.advance()?;
+                ctx.set_end(&close);
+                if !matches!(close.token, Token::CloseParen) {
+                    return Err(Self::unclosed_delimiter_error(&close, ("(", Some(&next.symbol)), "expected closing \")\" in case pattern"));
+                }
+                pattern
+            },
+            
+            _ => return Err("invalid case pattern".into()),
+        };
+        
+        Ok(pattern)
+    }
+    
     fn parse_function_decl_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
         let next = self.advance()?;
         
@@ -953,6 +1422,114 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         }
     }
     
+    fn parse_class_decl_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
+        let next = self.advance()?;
+        
+        ctx.push(ContextTag::ClassDefExpr);
+        ctx.set_start(&next);
+        debug_assert!(matches!(next.token, Token::Class));
+        
+        // if the next token is an identifier, it must be the class name
+        let next = self.peek()?;
+        let name_lvalue =
+            if let Token::Identifier(..) = next.token {
+                Some(self.parse_function_assignment_target(ctx)?)
+            } else { None };
+        
+        // optional parent class
+        let parent =
+            if matches!(self.peek()?.token, Token::OpenParen) {
+                Some(self.parse_class_parent(ctx)?)
+            } else { None };
+        
+        let mut class_def = self.parse_class_def(ctx)?;
+        class_def.parent = parent.map(Box::new);
+        
+        ctx.pop_extend();
+        
+        // SYNTACTIC SUGAR: class name .. end => let name = class .. end
+        if let Some(pattern) = name_lvalue {
+            if let Pattern::Identifier(name) = &pattern {
+                class_def.name.replace(*name);
+            }
+            
+            let class_decl = Assignment {
+                action: MatchAction::DeclImmutable,
+                op: None,
+                lhs: pattern,
+                rhs: Expr::ClassDef(class_def),
+            };
+            
+            Ok(Expr::Assignment(Box::new(class_decl)))
+            
+        } else {
+            
+            Ok(Expr::ClassDef(class_def))
+        }
+    }
+    
+    fn parse_class_parent(&mut self, ctx: &mut ErrorContext) -> ParseResult<ExprMeta> {
+        let open = self.advance()?;
+        ctx.set_end(&open);
+        debug_assert!(matches!(open.token, Token::OpenParen));
+        
+        let parent = self.parse_expr(ctx)?;
+        
+        let next = self.advance()?;
+        ctx.set_end(&next);
+        if !matches!(next.token, Token::CloseParen) {
+            return Err(Self::unclosed_delimiter_error(&next, ("(", Some(&open.symbol)), "expected closing \")\" after parent class"));
+        }
+        
+        Ok(parent)
+    }
+    
+    fn parse_class_def(&mut self, ctx: &mut ErrorContext) -> ParseResult<ClassDef> {
+        let mut methods = Vec::<FunctionDef>::new();
+        
+        loop {
+            // statement separators
+            while matches!(self.peek()?.token, Token::Semicolon) {
+                ctx.set_end(&self.advance().unwrap());
+            }
+            
+            let next = self.advance()?;
+            ctx.set_end(&next);
+            
+            match next.token {
+                Token::End => break,
+                
+                Token::Fun => {
+                    let next = self.advance()?;
+                    ctx.set_end(&next);
+                    
+                    let name = 
+                        if let Token::Identifier(name) = next.token { self.intern_str(name) }
+                        else { return Err("expected a method name after \"fun\"".into()); };
+                    
+                    if methods.iter().any(|method| method.signature.name == Some(name)) {
+                        return Err("duplicate method definition in class body".into());
+                    }
+                    
+                    let mut method = self.parse_function_def(ctx)?;
+                    method.signature.name.replace(name);
+                    methods.push(method);
+                },
+                
+                Token::EOF => return Err(Self::unclosed_block_error(ctx)),
+                
+                _ => return Err("expected a method definition or \"end\" in class body".into()),
+            }
+        }
+        
+        let class_def = ClassDef {
+            name: None,
+            parent: None,
+            methods: methods.into_boxed_slice(),
+        };
+        Ok(class_def)
+    }
+    
     // similar to parse_primary(), except we only allow member access and index access, and convert to an Pattern after
     fn parse_function_assignment_target(&mut self, ctx: &mut ErrorContext) -> ParseResult<Pattern> {
         ctx.push(ContextTag::PrimaryExpr);
@@ -986,20 +1563,20 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
     
     fn parse_function_def(&mut self, ctx: &mut ErrorContext) -> ParseResult<FunctionDef> {
         // expect open paren now
-        let next = self.advance().unwrap();
-        ctx.set_end(&next);
+        let open = self.advance().unwrap();
+        ctx.set_end(&open);
         
         // function parameter list
         
-        if !matches!(next.token, Token::OpenParen) {
+        if !matches!(open.token, Token::OpenParen) {
             return Err("expected opening \"(\" before parameter list".into());
         }
         
-        let signature = self.parse_function_param_list(ctx)?;
+        let signature = self.parse_function_param_list(ctx, |token| matches!(token, Token::CloseParen), PRECEDENCE_START)?;
         
         let next = self.advance()?;
         if !matches!(next.token, Token::CloseParen) {
-            return Err("expected closing \")\" after parameter list".into());
+            return Err(Self::unclosed_delimiter_error(&next, ("(", Some(&open.symbol)), "expected closing \")\" after parameter list"));
         }
         
         // function body
@@ -1015,7 +1592,38 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         Ok(fundef)
     }
     
-    fn parse_function_param_list(&mut self, ctx: &mut ErrorContext) -> ParseResult<SignatureDef> {
+    // default values are parsed as binary operator expressions at the given precedence level
+    // lambda ::= "|" parameter-list "|" expression ;
+    fn parse_lambda_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
+        let open = self.advance()?;
+        
+        ctx.push(ContextTag::LambdaExpr);
+        ctx.set_start(&open);
+        debug_assert!(matches!(open.token, Token::OpOr));
+        
+        // a default value containing "|" must be parenthesized, otherwise it would close the parameter list
+        let default_level = BinaryOp::BitOr.precedence_level() - 1;
+        let signature = self.parse_function_param_list(ctx, |token| matches!(token, Token::OpOr), default_level)?;
+        
+        let next = self.advance()?;
+        ctx.set_end(&next);
+        if !matches!(next.token, Token::OpOr) {
+            return Err(Self::unclosed_delimiter_error(&next, ("|", Some(&open.symbol)), "expected closing \"|\" after parameter list"));
+        }
+        
+        // the body is a single expression, which is also the return value
+        let body = self.parse_initializer_expr(ctx)?;
+        
+        let fundef = FunctionDef {
+            signature,
+            body: Box::new(ExprBlock::from(body)),
+        };
+        
+        ctx.pop_extend();
+        Ok(Expr::FunctionDef(fundef))
+    }
+    
+    fn parse_function_param_list(&mut self, ctx: &mut ErrorContext, end_list: impl Fn(&Token) -> bool, default_level: Precedence) -> ParseResult<SignatureDef> {
 
         let mut required = Vec::new();
         let mut default = Vec::new();
@@ -1024,7 +1632,8 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         loop {
             let next = self.peek()?;
             
-            if matches!(next.token, Token::CloseParen) {
+            // an unclosed parameter list is reported by the caller
+            if end_list(&next.token) || matches!(next.token, Token::EOF) {
                 break;
             }
             
@@ -1073,13 +1682,18 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 Token::OpAssign => {
                     ctx.set_end(&self.advance().unwrap());
                     
-                    Some(Box::new(self.parse_expr(ctx)?))
+                    ctx.push(ContextTag::ExprMeta);
+                    let variant = self.parse_binop_expr_levels(ctx, default_level)?;
+                    let symbol = Self::frame_symbol(ctx.frame())?;
+                    ctx.pop_extend();
+                    
+                    Some(Box::new(ExprMeta::new(variant, symbol)))
                 },
                 
                 _ => None,
             };
             
-            // expect either a comma "," or the closing ")"
+            // expect either a comma "," or the end of the parameter list
             let next = self.peek()?;
 
             let mode = mode.unwrap_or(Access::ReadOnly);
@@ -1089,13 +1703,13 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                     return Err("a variadic parameter must appear last in the parameter list".into());
                 }
                 
-                Token::CloseParen if is_variadic => {
+                ref token if is_variadic && end_list(token) => {
                     debug_assert!(default_value.is_none());
                     variadic.replace(ParamDef { name, mode });
                 },
                 
                 // normal parameter
-                Token::Comma | Token::CloseParen if !is_variadic => {
+                ref token if !is_variadic && (matches!(token, Token::Comma) || end_list(token)) => {
                     if let Some(default_expr) = default_value {
                         default.push(DefaultDef { name, mode, default: default_expr });
                     } else {
@@ -1106,6 +1720,11 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                     }
                 },
                 
+                Token::EOF => {
+                    ctx.pop_extend();
+                    break;
+                },
+                
                 _ => return Err("invalid parameter".into()),
             }
             
@@ -1129,31 +1748,207 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
     /*
         Object Constructor syntax:
         
-        object-constructor ::= "{" member-initializer ( "," member-initializer )* "}" ;
+        object-constructor ::= "{" member-initializer ( "," member-initializer )* ","? "}" ;
         member-initializer ::= ( IDENTIFIER | "[" primary "]" ) ":" expression ;
     
     */
-    fn parse_table_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
-        let items = self.parse_table_literal(ctx)?;
-        Ok(Expr::Table(items.into_boxed_slice()))
+    // list ::= "[" "]" | "[" expression ( "," expression )* ","? "]" ;
+    fn parse_list_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
+        ctx.push(ContextTag::ListCtor);
+        
+        let next = self.advance().unwrap();
+        ctx.set_start(&next);
+        debug_assert!(matches!(next.token, Token::OpenSquare));
+        
+        let mut items = Vec::new();
+        
+        // check for the empty list
+        if matches!(self.peek()?.token, Token::CloseSquare) {
+            
+            ctx.set_end(&self.advance().unwrap());
+            
+        } else {
+            
+            // same as argument lists, a tuple expression becomes the list contents
+            let (expr, symbol) = self.parse_expr(ctx)?.take();
+            
+            if let Expr::Tuple(exprs) = expr {
+                items.extend(exprs.into_vec());
+            } else {
+                items.push(ExprMeta::new(expr, symbol));
+            }
+            
+            let next = self.advance()?;
+            ctx.set_end(&next);
+            if !matches!(next.token, Token::CloseSquare) {
+                return Err(Self::unclosed_delimiter_error(&next, ("[", ctx.frame().start()), "expected closing \"]\""));
+            }
+        }
+        
+        ctx.pop_extend();
+        Ok(Expr::List(items.into_boxed_slice()))
     }
     
-    fn parse_table_literal(&mut self, ctx: &mut ErrorContext) -> ParseResult<Vec<TableItem>> {
-        ctx.push(ContextTag::TableCtor);
+    // Both tables and dicts are enclosed in "{" "}". The first item decides which one this is.
+    // dict ::= "{" "}" | "{" expression ":" expression ( "," expression ":" expression )* ","? "}" ;
+    // interpolated strings become a concatenation of their fragments
+    fn parse_interpolated_string(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
+        ctx.push(ContextTag::InterpolatedString);
         
+        let next = self.advance().unwrap();
+        ctx.set_start(&next);
+        
+        let fragments = match next.token {
+            Token::InterpolatedString(fragments) => fragments,
+            _ => panic!("expected interpolated string"),
+        };
+        
+        let mut parts = Vec::new();
+        for fragment in fragments.into_iter() {
+            match fragment {
+                StrFragment::Literal(value) => {
+                    let atom = Atom::StringLiteral(self.intern_str(value));
+                    parts.push(ExprMeta::new(Expr::Atom(atom), next.symbol));
+                },
+                
+                StrFragment::Tokens(tokens) => {
+                    let mut parser = Parser::new(self.interner, tokens.into_iter().map(Ok));
+                    parser.nesting = self.nesting;
+                    parts.push(parser.parse_expr(ctx)?);
+                    
+                    let next = parser.advance()?;
+                    if !matches!(next.token, Token::EOF) {
+                        ctx.set_end(&next);
+                        return Err("unexpected token in interpolated string".into());
+                    }
+                },
+                
+                StrFragment::Source(..) => panic!("embedded expression was not tokenized"),
+            }
+        }
+        
+        ctx.pop_extend();
+        Ok(Expr::Concat(parts.into_boxed_slice()))
+    }
+    
+    fn parse_table_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> {
         let next = self.advance().unwrap();
         ctx.set_start(&next);
         debug_assert!(matches!(next.token, Token::OpenBrace));
         
-        let mut items = Vec::new();
+        let next = self.peek()?;
+        let key = match next.token {
+            // empty dict
+            Token::CloseBrace => {
+                ctx.set_end(&self.advance().unwrap());
+                return Ok(Expr::Dict(Box::new([])));
+            },
+            
+            // these can only start a table field
+            Token::Var | Token::Let | Token::OpenSquare => None,
+            
+            _ => Some(self.parse_initializer_expr(ctx)?),
+        };
+        
+        let first_field = match key {
+            None => None,
+            
+            Some(key) => if matches!(self.peek()?.token, Token::Colon) {
+                let entries = self.parse_dict_entries(ctx, key)?;
+                return Ok(Expr::Dict(entries.into_boxed_slice()));
+            } else if let Expr::Atom(Atom::Identifier(name)) = key.variant() {
+                Some(TableField::Attribute(Access::ReadOnly, *name))
+            } else {
+                return Err("expected \":\" after dict key".into());
+            },
+        };
+        
+        let items = self.parse_table_items(ctx, first_field)?;
+        Ok(Expr::Table(items.into_boxed_slice()))
+    }
+    
+    fn parse_dict_entries(&mut self, ctx: &mut ErrorContext, first_key: ExprMeta) -> ParseResult<Vec<DictEntry>> {
+        ctx.push(ContextTag::DictCtor);
+        
+        let mut entries = Vec::new();
+        let mut key = Some(first_key);
         
         loop {
+            let key = match key.take() {
+                Some(key) => key,
+                None => {
+                    if matches!(self.peek()?.token, Token::CloseBrace) {
+                        break;
+                    }
+                    self.parse_initializer_expr(ctx)?
+                }
+            };
+            
+            let next = self.advance()?;
+            ctx.set_end(&next);
+            if !matches!(next.token, Token::Colon) {
+                return Err("missing \":\" in dict entry".into())
+            }
+            
+            let value = self.parse_initializer_expr(ctx)?;
+            entries.push(DictEntry { key, value });
+            
             let next = self.peek()?;
-            if matches!(next.token, Token::CloseBrace) {
+            if matches!(next.token, Token::Comma) {
+                ctx.set_end(&self.advance().unwrap())
+            } else {
                 break;
             }
-            
-            let field = self.parse_table_field(ctx)?;
+        }
+        
+        let next = self.advance()?;
+        ctx.set_end(&next);
+        
+        // the "{" was recorded by the enclosing frame, before it was known whether this is a table or a dict
+        if !matches!(next.token, Token::CloseBrace) {
+            let open = ctx.frames().nth(1).and_then(ContextFrame::start);
+            return Err(Self::unclosed_delimiter_error(&next, ("{", open), "expected closing \"}\""));
+        }
+        
+        ctx.pop_extend();
+        Ok(entries)
+    }
+    
+    fn parse_initializer_expr(&mut self, ctx: &mut ErrorContext) -> ParseResult<ExprMeta> {
+        ctx.push(ContextTag::ExprMeta);
+        
+        let variant = self.parse_inner_expr(ctx)?;
+        let symbol = Self::frame_symbol(ctx.frame())?;
+        
+        ctx.pop_extend();
+        Ok(ExprMeta::new(variant, symbol))
+    }
+    
+    fn parse_table_literal(&mut self, ctx: &mut ErrorContext) -> ParseResult<Vec<TableItem>> {
+        let next = self.advance().unwrap();
+        ctx.set_start(&next);
+        debug_assert!(matches!(next.token, Token::OpenBrace));
+        
+        self.parse_table_items(ctx, None)
+    }
+    
+    // parse the contents of a table literal after the opening "{"
+    fn parse_table_items(&mut self, ctx: &mut ErrorContext, mut first_field: Option<TableField>) -> ParseResult<Vec<TableItem>> {
+        ctx.push(ContextTag::TableCtor);
+        
+        let mut items = Vec::new();
+        
+        loop {
+            let field = match first_field.take() {
+                Some(field) => field,
+                None => {
+                    let next = self.peek()?;
+                    if matches!(next.token, Token::CloseBrace) {
+                        break;
+                    }
+                    self.parse_table_field(ctx)?
+                }
+            };
             
             let next = self.advance()?;
             ctx.set_end(&next);
@@ -1161,16 +1956,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 return Err("missing \"=\" in initializer".into())
             }
             
-            let expr = {
-                ctx.push(ContextTag::ExprMeta);
-                
-                let variant = self.parse_inner_expr(ctx)?;
-                let symbol = ctx.frame().as_debug_symbol().unwrap();
-                
-                ctx.pop_extend();
-                ExprMeta::new(variant, symbol)
-            };
-            
+            let expr = self.parse_initializer_expr(ctx)?;
             items.push(TableItem { field, value: expr });
             
             let next = self.peek()?;
@@ -1184,8 +1970,10 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         let next = self.advance()?;
         ctx.set_end(&next);
         
+        // the "{" was recorded by the enclosing frame, before it was known whether this is a table or a dict
         if !matches!(next.token, Token::CloseBrace) {
-            return Err("expected closing \"}\"".into());
+            let open = ctx.frames().nth(1).and_then(ContextFrame::start);
+            return Err(Self::unclosed_delimiter_error(&next, ("{", open), "expected closing \"}\""));
         }
         Ok(items)
     }
@@ -1228,18 +2016,19 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 Ok(TableField::Attribute(Access::ReadOnly, name))
             }
             
-            _ => return Err("invalid initializer".into())
+            _ => Err("invalid initializer".into())
         }
     }
     
     /*
         Primary expression syntax:
         
-        primary ::= atom ( access | subscript | invocation | object-constructor )* ;
+        primary ::= atom ( access | subscript | optional-access | invocation | object-constructor )* ;
         subscript ::= "[" expression "]" ;
         access ::= "." IDENTIFIER ;
+        optional-access ::= "?." ( IDENTIFIER | subscript ) ;
         invocation ::= "(" ... ")" ;  (* WIP *)
-        object-constructor ::= "{" member-initializer ( "," member-initializer )* "}" ;
+        object-constructor ::= "{" member-initializer ( "," member-initializer )* ","? "}" ;
     */
     fn parse_primary(&mut self, ctx: &mut ErrorContext) -> ParseResult<Expr> { 
         ctx.push(ContextTag::PrimaryExpr);
@@ -1255,11 +2044,23 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 Token::OpAccess => 
                     items.push(self.parse_member_access(ctx)?),
                 
+                // optional-access ::= "?." ( IDENTIFIER | subscript ) ;
+                Token::OpOptAccess => {
+                    items.push(AccessItem::OptionalChain);
+                    if self.is_next_nth(1, |token| matches!(token, Token::OpenSquare)) {
+                        self.advance().unwrap(); // consume "?."
+                        items.push(self.parse_index_access(ctx)?);
+                    } else {
+                        items.push(self.parse_member_access(ctx)?);
+                    }
+                }
+                
                 // subscript ::= "[" expression "]" ;
-                Token::OpenSquare => 
+                // like invocations, not allowed on a separate line so that the next line may start with a list
+                Token::OpenSquare if !next.newline => 
                     items.push(self.parse_index_access(ctx)?),
                                 
-                // invocation ::= "(" ")" | "(" argument ( "," argument )* ")" ; 
+                // invocation ::= "(" ")" | "(" argument ( "," argument )* ","? ")" ; 
                 // argument ::= expression ( "..." )? ;  (* "..." is for argument unpacking syntax *)
                 // invocations are not allowed to be on a separate line from the invocation receiver
                 Token::OpenParen if !next.newline => 
@@ -1290,7 +2091,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         
         ctx.push(ContextTag::MemberAccess);
         ctx.set_start(&next);
-        debug_assert!(matches!(next.token, Token::OpAccess));
+        debug_assert!(matches!(next.token, Token::OpAccess | Token::OpOptAccess));
         
         let next = self.advance()?;
         ctx.set_end(&next);
@@ -1324,7 +2125,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         ctx.set_end(&next);
         
         if !matches!(next.token, Token::CloseSquare) {
-            return Err("expected closing \"]\"".into());
+            return Err(Self::unclosed_delimiter_error(&next, ("[", ctx.frame().start()), "expected closing \"]\""));
         }
         
         ctx.pop_extend();
@@ -1354,7 +2155,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             let (expr, symbol) = self.parse_expr(ctx)?.take();
             
             if let Expr::Tuple(items) = expr {
-                args.extend(items.into_vec().into_iter());
+                args.extend(items.into_vec());
             } else {
                 args.push(ExprMeta::new(expr, symbol));
             }
@@ -1363,7 +2164,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
             let next = self.advance()?;
             ctx.set_end(&next);
             if !matches!(next.token, Token::CloseParen) {
-                return Err("expected closing \")\" after argument list".into());
+                return Err(Self::unclosed_delimiter_error(&next, ("(", ctx.frame().start()), "expected closing \")\" after argument list"));
             }
         }
         
@@ -1373,12 +2174,27 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         Ok(invocation)
     }
     
-    // atom ::= LITERAL | IDENTIFIER | "(" expression ")" ;
+    // atom ::= LITERAL | IDENTIFIER | "(" expression ")" | list | dict | object-constructor ;
     fn parse_atom(&mut self, ctx: &mut ErrorContext) -> ParseResult<Atom> { 
         
-        if let Token::OpenParen = self.peek()?.token {
+        let next = self.peek()?;
+        if let Token::OpenParen = next.token {
             Ok(self.parse_group_expr(ctx)?)  // Groups
             
+        } else if let Token::OpenSquare = next.token {
+            // list literals are wrapped in a group so that they can be the receiver of an access item
+            let list = self.parse_list_expr(ctx)?;
+            Ok(Atom::Group { modifier: None, inner: Box::new(list) })
+        
+        } else if let Token::OpenBrace = next.token {
+            // same for dicts and tables
+            let table = self.parse_table_expr(ctx)?;
+            Ok(Atom::Group { modifier: None, inner: Box::new(table) })
+            
+        } else if let Token::InterpolatedString(..) = next.token {
+            let concat = self.parse_interpolated_string(ctx)?;
+            Ok(Atom::Group { modifier: None, inner: Box::new(concat) })
+            
         } else { 
             ctx.push(ContextTag::Atom);
             
@@ -1391,22 +2207,27 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                     Atom::Identifier(self.intern_str(name))
                 },
                 
+                Token::Self_ => Atom::Self_,
+                Token::Super => Atom::Super,
+                
                 // Literals
                 Token::Nil   => Atom::Nil,
                 Token::True  => Atom::BooleanLiteral(true),
                 Token::False => Atom::BooleanLiteral(false),
                 
                 Token::IntegerLiteral(value) => Atom::IntegerLiteral(value),
+                Token::BigIntegerLiteral(text) => Atom::BigIntegerLiteral(self.intern_str(text)),
                 Token::FloatLiteral(value)   => Atom::FloatLiteral(value),
                 Token::StringLiteral(value)   => {
                     Atom::StringLiteral(self.intern_str(value))
                 },
                 
                 // Error productions
-                Token::Class | Token::Fun | Token::If | Token::Var | Token::Let | Token::Begin | Token::Label(..) => {
+                Token::Class | Token::Fun | Token::If | Token::Match | Token::Var | Token::Let | Token::Begin | Token::Label(..) => {
                     let name = match next.token {
                         Token::Class => "class definitions",
                         Token::Fun => "function definitions",
+                        Token::Match => "match expressions",
                         Token::Let => "\"let\"",
                         Token::Var => "\"var\"",
                         Token::Local => "\"local\"",
@@ -1423,7 +2244,7 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
                 Token::CloseSquare => return Err("unmatched \"]\"".into()),
                 Token::CloseBrace => return Err("unmatched \"}\"".into()),
                 
-                _ => { return Err("expected an expression here".into()) },
+                _ => { return Err(ErrorKind::ExpectedExpression.into()) },
             };
             
             ctx.pop_extend();
@@ -1453,18 +2274,19 @@ impl<I> Parser<'_, I> where I: Iterator<Item=Result<TokenMeta, LexerError>> {
         let mut expr = self.parse_expr_variant(ctx)?;
         
         // if inner expression is an assignment, transfer our modifier to it
-        match (&mut expr, modifier) {
+        let modifier = match (&mut expr, modifier) {
             (Expr::Assignment(assign), Some(modifier)) => {
                 assign.action = modifier;
+                None
             },
-            _ => { },
-        }
+            (_, modifier) => modifier,
+        };
         
         // Consume and check closing paren
         let next = self.advance()?;
         ctx.set_end(&next);
         if !matches!(next.token, Token::CloseParen) {
-            return Err("expected closing \")\"".into());
+            return Err(Self::unclosed_delimiter_error(&next, ("(", ctx.frame().start()), "expected closing \")\""));
         }
         
         ctx.pop_extend();
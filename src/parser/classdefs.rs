@@ -0,0 +1,18 @@
+use crate::language::InternSymbol;
+use crate::parser::expr::ExprMeta;
+use crate::parser::fundefs::FunctionDef;
+
+
+// Class Definitions
+#[derive(Debug, Clone)]
+pub struct ClassDef {
+    pub name: Option<InternSymbol>,
+    pub parent: Option<Box<ExprMeta>>,
+    pub methods: Box<[FunctionDef]>,
+}
+
+impl ClassDef {
+    pub fn method_names(&self) -> impl Iterator<Item=&InternSymbol> {
+        self.methods.iter().filter_map(|method| method.signature.name.as_ref())
+    }
+}
@@ -4,6 +4,7 @@ use std::error::Error;
 use crate::utils;
 use crate::lexer::{TokenMeta, LexerError};
 use crate::debug::SourceError;
+use crate::debug::codes::{self, ErrorCode};
 use crate::debug::symbol::DebugSymbol;
 
 
@@ -13,6 +14,12 @@ pub type ParseResult<T> = Result<T, ParserError>;
 pub enum ErrorKind {
     LexerError,
     EndofTokenStream,
+    NestingLimit,
+    StatementTooLong,
+    ExpectedExpression,
+    ExpectedClosing(&'static str),  // the message, since it can say what the delimiter closes
+    UnclosedDelimiter(&'static str),  // the opening delimiter, at the end of the input
+    UnclosedBlock(&'static str),  // the keyword that opened the block, at the end of the input
     SyntaxError(String),
 }
 
@@ -34,12 +41,18 @@ pub enum ContextTag {
     Loop,
     WhileLoop,
     ForLoop,
+    TryExcept,
+    Import,
     ExprMeta,
     ExprList,
     Expr,
     BlockExpr,
     IfExpr,
+    MatchExpr,
+    MatchCase,
     FunDefExpr,
+    LambdaExpr,
+    ClassDefExpr,
     FunParam,
     AssignmentExpr,
     BinaryOpExpr,
@@ -49,17 +62,77 @@ pub enum ContextTag {
     IndexAccess,
     Invocation,
     TupleCtor,
+    ListCtor,
     TableCtor,
+    InterpolatedString,
+    DictCtor,
     Atom,
     Group,
     Pattern,
     Label,
 }
 
+impl ContextTag {
+    // a description of the syntactic construct, if it is specific enough to help locate an error
+    pub fn description(&self) -> Option<&'static str> {
+        let desc = match self {
+            Self::ControlFlow => "a control flow statement",
+            Self::Loop => "a loop",
+            Self::WhileLoop => "a while loop",
+            Self::ForLoop => "a for loop",
+            Self::TryExcept => "a try block",
+            Self::Import => "an import statement",
+            Self::BlockExpr => "a block",
+            Self::IfExpr => "an if expression",
+            Self::MatchExpr => "a match expression",
+            Self::MatchCase => "a case pattern",
+            Self::FunDefExpr => "a function definition",
+            Self::LambdaExpr => "a lambda",
+            Self::ClassDefExpr => "a class definition",
+            Self::FunParam => "a function parameter",
+            Self::AssignmentExpr => "an assignment",
+            Self::BinaryOpExpr => "a binary operator",
+            Self::UnaryOpExpr => "a unary operator",
+            Self::MemberAccess => "an attribute access",
+            Self::IndexAccess => "an index",
+            Self::Invocation => "a function call",
+            Self::TupleCtor => "a tuple",
+            Self::ListCtor => "a list",
+            Self::TableCtor => "a table",
+            Self::InterpolatedString => "an interpolated string",
+            Self::DictCtor => "a dict",
+            Self::Group => "a parenthesized expression",
+            Self::Pattern => "an assignment target",
+            Self::Label => "a label",
+            
+            Self::Token | Self::TopLevel | Self::Sync | Self::StmtMeta | Self::StmtList
+            | Self::ExprMeta | Self::ExprList | Self::Expr | Self::PrimaryExpr | Self::Atom => return None,
+        };
+        Some(desc)
+    }
+    
+    // the keyword that opens the construct, if it is a block that must be closed by "end"
+    pub fn block_keyword(&self) -> Option<&'static str> {
+        let keyword = match self {
+            Self::Loop => "loop",
+            Self::WhileLoop => "while",
+            Self::ForLoop => "for",
+            Self::TryExcept => "try",
+            Self::BlockExpr => "begin",
+            Self::IfExpr => "if",
+            Self::MatchExpr => "match",
+            Self::FunDefExpr => "fun",
+            Self::ClassDefExpr => "class",
+            _ => return None,
+        };
+        Some(keyword)
+    }
+}
+
 impl From<ErrorKind> for ParserError {
     fn from(kind: ErrorKind) -> Self {
         Self { 
-            kind, context: None, symbol: None, cause: None,
+            kind, context: None, construct: None, symbol: None, cause: None,
         }
     }
 }
@@ -68,7 +141,7 @@ impl From<&str> for ParserError {
     fn from(message: &str) -> Self {
         Self { 
             kind: message.into(), 
-            context: None, symbol: None, cause: None,
+            context: None, construct: None, symbol: None, cause: None,
         }
     }
 }
@@ -78,6 +151,7 @@ impl From<LexerError> for ParserError {
         Self { 
             kind: ErrorKind::LexerError, 
             context: None,
+            construct: None,
             symbol: Some(*error.debug_symbol()),
             cause: Some(Box::new(error)),
         }
@@ -88,6 +162,7 @@ impl From<LexerError> for ParserError {
 pub struct ParserError {
     kind: ErrorKind,
     context: Option<ContextTag>,
+    construct: Option<ContextTag>,  // the innermost context that has a description
     symbol: Option<DebugSymbol>,
     cause: Option<Box<dyn Error>>,
 }
@@ -102,8 +177,8 @@ impl ParserError {
     }
     
     pub fn with_symbol_from_ctx(mut self, ctx: &ErrorContext) -> Self {
-        if let Some(symbol) = ctx.frame().as_debug_symbol() {
-            self.symbol.replace(symbol);
+        if self.symbol.is_none() {
+            self.symbol = ctx.frame().as_debug_symbol();
         }
         self
     }
@@ -112,19 +187,48 @@ impl ParserError {
         self.cause.replace(Box::new(error)); self
     }
     
-    // fill in fields from context if not already set
-    pub fn with_error_context(mut self, context: ErrorContext) -> Self {
+    // fill in the context tags if not already set
+    pub fn with_context_tags(mut self, context: &ErrorContext) -> Self {
         if self.context.is_none() {
             self.context.replace(context.frame().context());
         }
-        if self.symbol.is_none() {
-            self.symbol.replace(context.take_debug_symbol());
+        if self.construct.is_none() {
+            self.construct = context.stack.iter().rev()
+                .map(|frame| frame.context())
+                .find(|tag| tag.description().is_some());
         }
         self
     }
     
+    // fill in fields from context if not already set
+    pub fn with_error_context(self, context: ErrorContext) -> Self {
+        let mut error = self.with_context_tags(&context);
+        if error.symbol.is_none() {
+            error.symbol.replace(context.take_debug_symbol());
+        }
+        error
+    }
+    
     pub fn kind(&self) -> &ErrorKind { &self.kind }
     pub fn context(&self) -> Option<&ContextTag> { self.context.as_ref() }
+    
+    pub fn code(&self) -> ErrorCode {
+        match self.kind {
+            // use the code of the lexer error that caused this one
+            ErrorKind::LexerError => self.cause.as_ref()
+                .and_then(|cause| cause.downcast_ref::<LexerError>())
+                .map_or(codes::E0004, |error| error.kind().code()),
+            
+            ErrorKind::EndofTokenStream => codes::E0101,
+            ErrorKind::SyntaxError(..) => codes::E0102,
+            ErrorKind::NestingLimit => codes::E0103,
+            ErrorKind::StatementTooLong => codes::E0104,
+            ErrorKind::ExpectedExpression => codes::E0105,
+            ErrorKind::ExpectedClosing(..) => codes::E0106,
+            ErrorKind::UnclosedDelimiter(..) => codes::E0107,
+            ErrorKind::UnclosedBlock(..) => codes::E0108,
+        }
+    }
 }
 
 
@@ -136,18 +240,31 @@ impl Error for ParserError {
 
 impl SourceError for ParserError {
     fn debug_symbol(&self) -> Option<&DebugSymbol> { self.symbol.as_ref() }
+    
+    fn error_code(&self) -> Option<ErrorCode> { Some(self.code()) }
+    
+    fn context_note(&self) -> Option<String> {
+        let desc = self.construct.or(self.context)?.description()?;
+        Some(format!("while parsing {}", desc))
+    }
 }
 
 impl fmt::Display for ParserError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         
         let message = match self.kind() {
-            ErrorKind::LexerError => "",
-            ErrorKind::EndofTokenStream => "unexpected end of token stream",
-            ErrorKind::SyntaxError(message) => message,
+            ErrorKind::LexerError => String::new(),
+            ErrorKind::EndofTokenStream => "unexpected end of token stream".to_string(),
+            ErrorKind::NestingLimit => "too many levels of nesting".to_string(),
+            ErrorKind::StatementTooLong => "statement is too long".to_string(),
+            ErrorKind::ExpectedExpression => "expected an expression here".to_string(),
+            ErrorKind::ExpectedClosing(message) => message.to_string(),
+            ErrorKind::UnclosedDelimiter(delimiter) => format!("\"{}\" was never closed", delimiter),
+            ErrorKind::UnclosedBlock(keyword) => format!("\"{}\" was never closed, expected \"end\"", keyword),
+            ErrorKind::SyntaxError(message) => message.clone(),
         };
         
-        utils::format_error(fmt, "Syntax error", Some(message), self.source())
+        utils::format_error(fmt, "Syntax error", Some(&message), self.source())
     }
 }
 // "unpacking may only be used once in an assignment or declaration"
@@ -159,13 +276,15 @@ pub struct ErrorContext {
     stack: Vec<ContextFrame>,
 }
 
-impl<'m> ErrorContext {
+impl ErrorContext {
     pub fn new(base: ContextTag) -> Self {
         ErrorContext {
             stack: vec![ ContextFrame::new(base) ],
         }
     }
     
+    pub fn depth(&self) -> usize { self.stack.len() }
+    
     pub fn frame(&self) -> &ContextFrame { 
         self.stack.last().unwrap() 
     }
@@ -174,6 +293,11 @@ impl<'m> ErrorContext {
         self.stack.last_mut().unwrap() 
     }
     
+    // iterate from the innermost frame outwards
+    pub fn frames(&self) -> impl Iterator<Item=&ContextFrame> {
+        self.stack.iter().rev()
+    }
+    
     pub fn push(&mut self, tag: ContextTag) { 
         log::debug!("Push frame: {:0>3} {:?}", self.stack.len()+1, tag);
         self.stack.push(ContextFrame::new(tag)) 
@@ -1,9 +1,11 @@
 use crate::debug::DebugSymbol;
 use crate::language::{InternSymbol, Access};
 use crate::parser::operator::{BinaryOp, UnaryOp};
-use crate::parser::primary::{Atom, Primary};
+use crate::parser::primary::{Atom, Primary, AccessItem};
 use crate::parser::pattern::Assignment;
 use crate::parser::fundefs::FunctionDef;
+use crate::parser::classdefs::ClassDef;
+use crate::parser::matchdefs::MatchCase;
 use crate::parser::stmt::{StmtMeta, Stmt, Label, StmtList};
 
 // TODO replace Vecs with boxed slices
@@ -14,17 +16,25 @@ pub enum Expr {
     
     Primary(Primary),
     
-    UnaryOp(UnaryOp, Box<Expr>),
+    // operands keep their debug symbols, so that errors can point to the operand instead of the whole expression
+    UnaryOp(UnaryOp, Box<ExprMeta>),
     
-    BinaryOp(BinaryOp, Box<(Expr, Expr)>),
+    BinaryOp(BinaryOp, Box<(ExprMeta, ExprMeta)>),
     
     Assignment(Box<Assignment>),
     Unpack(Option<Box<Expr>>),
     
     Tuple(Box<[ExprMeta]>),
     
+    List(Box<[ExprMeta]>),
+    
     Table(Box<[TableItem]>),
     
+    Dict(Box<[DictEntry]>),
+    
+    // concatenates the string form of each part, used for interpolated strings
+    Concat(Box<[ExprMeta]>),
+    
     // ObjectCtor(Box<ObjectConstructor>),
     
     IfExpr {
@@ -32,6 +42,12 @@ pub enum Expr {
         else_clause: Option<Box<ExprBlock>>,
     },
     
+    Match {
+        subject: Box<ExprMeta>,
+        cases: Box<[MatchCase]>,
+        else_clause: Option<Box<ExprBlock>>,
+    },
+    
     Block {
         label: Option<Label>, 
         suite: Box<ExprBlock>,
@@ -39,8 +55,10 @@ pub enum Expr {
     
     FunctionDef(FunctionDef),
     
-    // ClassDef
+    Raise(Box<Expr>),
     
+    ClassDef(ClassDef),
+
 }
 
 // Tables
@@ -57,6 +75,14 @@ pub struct TableItem {
     pub value: ExprMeta,
 }
 
+// Dicts
+
+#[derive(Debug, Clone)]
+pub struct DictEntry {
+    pub key: ExprMeta,
+    pub value: ExprMeta,
+}
+
 // Statement Block Expressions
 
 /// represents a statement list used as an expression
@@ -83,9 +109,19 @@ impl From<StmtList> for ExprBlock {
     }
 }
 
+impl From<ExprMeta> for ExprBlock {
+    fn from(expr: ExprMeta) -> Self {
+        let stmt_list = StmtList::new(Vec::new(), None);
+        Self { stmt_list, result: Some(expr) }
+    }
+}
+
 impl ExprBlock {
     pub fn stmt_list(&self) -> &StmtList { &self.stmt_list }
     pub fn result(&self) -> Option<&ExprMeta> { self.result.as_ref() }
+    
+    pub fn stmt_list_mut(&mut self) -> &mut StmtList { &mut self.stmt_list }
+    pub fn result_mut(&mut self) -> Option<&mut ExprMeta> { self.result.as_mut() }
 }
 
 // Conditionals
@@ -103,6 +139,9 @@ impl ConditionalBranch {
     
     pub fn condition(&self) -> &Expr { &self.condition }
     pub fn suite(&self) -> &ExprBlock { &self.suite }
+    
+    pub fn condition_mut(&mut self) -> &mut Expr { &mut self.condition }
+    pub fn suite_mut(&mut self) -> &mut ExprBlock { &mut self.suite }
 }
 
 
@@ -119,16 +158,72 @@ impl ExprMeta {
     }
     
     pub fn variant(&self) -> &Expr { &self.variant }
-    pub fn take_variant(self) -> Expr { self.variant }
+    pub fn variant_mut(&mut self) -> &mut Expr { &mut self.variant }
+    pub fn take_variant(mut self) -> Expr { self.replace_variant() }
     
     pub fn debug_symbol(&self) -> &DebugSymbol { &self.symbol }
     pub fn take_symbol(self) -> DebugSymbol { self.symbol }
     
-    pub fn take(self) -> (Expr, DebugSymbol) { (self.variant, self.symbol) }
+    pub fn take(mut self) -> (Expr, DebugSymbol) { (self.replace_variant(), self.symbol) }
+    
+    fn replace_variant(&mut self) -> Expr {
+        core::mem::replace(&mut self.variant, Expr::Atom(Atom::Nil))
+    }
 }
 
 impl From<ExprMeta> for (Expr, DebugSymbol) {
-    fn from(expr: ExprMeta) -> Self { (expr.variant, expr.symbol) }
+    fn from(expr: ExprMeta) -> Self { expr.take() }
+}
+
+// A chain of operators or calls is nested as deeply as it is long, so dropping it recursively can overflow the stack.
+// Instead the operands are moved out and dropped one at a time.
+impl Drop for ExprMeta {
+    fn drop(&mut self) {
+        if !self.variant.has_operands() {
+            return;
+        }
+        
+        let mut pending = vec![ self.replace_variant() ];
+        while let Some(mut expr) = pending.pop() {
+            expr.take_operands(&mut pending);
+        }
+    }
+}
+
+impl Expr {
+    fn has_operands(&self) -> bool {
+        matches!(self, Self::UnaryOp(..) | Self::BinaryOp(..) | Self::Primary(..) | Self::Atom(Atom::Group { .. }))
+    }
+    
+    fn take_operands(&mut self, operands: &mut Vec<Expr>) {
+        match self {
+            Self::UnaryOp(_, operand) => operands.push(operand.replace_variant()),
+            
+            Self::BinaryOp(_, operand_pair) => {
+                let (lhs, rhs) = &mut **operand_pair;
+                operands.push(lhs.replace_variant());
+                operands.push(rhs.replace_variant());
+            },
+            
+            Self::Primary(primary) => {
+                if let Atom::Group { inner, .. } = primary.atom_mut() {
+                    operands.push(core::mem::replace(&mut **inner, Expr::Atom(Atom::Nil)));
+                }
+                
+                for item in primary.path_mut().iter_mut() {
+                    match item {
+                        AccessItem::Index(index) => operands.push(index.replace_variant()),
+                        AccessItem::Invoke(args) => operands.extend(args.iter_mut().map(ExprMeta::replace_variant)),
+                        _ => { },
+                    }
+                }
+            },
+            
+            Self::Atom(Atom::Group { inner, .. }) => operands.push(core::mem::replace(&mut **inner, Expr::Atom(Atom::Nil))),
+            
+            _ => { },
+        }
+    }
 }
 
 
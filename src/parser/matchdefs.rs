@@ -0,0 +1,51 @@
+use crate::language::InternSymbol;
+use crate::debug::DebugSymbol;
+use crate::parser::expr::{Expr, ExprBlock};
+use crate::parser::primary::Atom;
+
+
+// Match Expressions
+#[derive(Debug, Clone)]
+pub struct MatchCase {
+    pub pattern: CasePattern,
+    pub guard: Option<Expr>,
+    pub suite: ExprBlock,
+    pub symbol: DebugSymbol, // the "case ... then" part, used to report problems with the bindings
+}
+
+#[derive(Debug, Clone)]
+pub enum CasePattern {
+    // "_" matches anything without binding it
+    Wildcard,
+    
+    // a name matches anything, and binds it for the guard and the body of the case
+    Binding(InternSymbol),
+    
+    // nil, booleans, numbers, and strings match values that are equal to them
+    Literal(Atom),
+    
+    // matches a tuple whose items match each of the patterns
+    Tuple(Box<[CasePattern]>),
+    
+    // "name..." or "..." inside of a tuple pattern, matches any number of items
+    Rest(Option<InternSymbol>),
+}
+
+impl CasePattern {
+    // the names bound by this pattern, in the order that they appear
+    pub fn bindings(&self) -> Vec<InternSymbol> {
+        let mut names = Vec::new();
+        self.collect_bindings(&mut names);
+        names
+    }
+    
+    fn collect_bindings(&self, names: &mut Vec<InternSymbol>) {
+        match self {
+            Self::Binding(name) | Self::Rest(Some(name)) => names.push(*name),
+            Self::Tuple(items) => for item in items.iter() {
+                item.collect_bindings(names);
+            },
+            Self::Wildcard | Self::Literal(..) | Self::Rest(None) => { },
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use core::fmt;
+use crate::language;
 
 
 // Unary Operators
@@ -10,10 +11,13 @@ pub enum UnaryOp {
 
 // Binary Operators
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BinaryOp {
+    // binds tighter than unary operators
+    Exp,
+    
     // precedence level 1
-    Mul, Div, Mod,
+    Mul, Div, Mod, FloorDiv,
     
     // precedence level 2
     Add, Sub,
@@ -31,51 +35,44 @@ pub enum BinaryOp {
     BitOr,
     
     // precedence level 7
-    LT, GT, LE, GE,
+    Pipe,
     
     // precedence level 8
-    EQ, NE,
+    LT, GT, LE, GE, In, NotIn,
     
     // precedence level 9
-    And,
+    EQ, NE, Is,
     
     // precedence level 10
+    And,
+    
+    // precedence level 11
     Or,
+    
+    // precedence level 12
+    Coalesce,
 }
 
 pub type Precedence = u8;
 pub const PRECEDENCE_END: Precedence = 0; // tightest binding
-pub const PRECEDENCE_START: Precedence = 10; // weakest binding
+pub const PRECEDENCE_START: Precedence = 12; // weakest binding
 
 impl BinaryOp {
     
-    pub const fn precedence_level(&self) -> Precedence {
-        match self {
-            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 1,
-            
-            BinaryOp::Add | BinaryOp::Sub => 2,
-            
-            BinaryOp::LShift | BinaryOp::RShift => 3,
-            
-            BinaryOp::BitAnd => 4,
-            BinaryOp::BitXor => 5,
-            BinaryOp::BitOr => 6,
-            
-            BinaryOp::LT | BinaryOp::GT | BinaryOp::LE | BinaryOp::GE  => 7,
-            BinaryOp::EQ | BinaryOp::NE => 8,
-            
-            BinaryOp::And => 9,
-            BinaryOp::Or => 10,
-        }
+    // the precedence of each operator is given by language::FIXED_TOKENS
+    pub fn precedence_level(&self) -> Precedence {
+        language::binary_precedence(*self)
     }
 }
 
 impl fmt::Display for BinaryOp {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let symbol = match self {
+            BinaryOp::Exp    => "**",
             BinaryOp::Mul    => "*", 
             BinaryOp::Div    => "/", 
             BinaryOp::Mod    => "%",
+            BinaryOp::FloorDiv => "//",
             BinaryOp::Add    => "+",
             BinaryOp::Sub    => "-",
             BinaryOp::LShift => "<<", 
@@ -83,14 +80,19 @@ impl fmt::Display for BinaryOp {
             BinaryOp::BitAnd => "&",
             BinaryOp::BitXor => "^",
             BinaryOp::BitOr  => "|",
+            BinaryOp::Pipe   => "|>",
             BinaryOp::LT     => "<",
             BinaryOp::GT     => ">",
             BinaryOp::LE     => "<=",
             BinaryOp::GE     => ">=",
+            BinaryOp::In     => "in",
+            BinaryOp::NotIn  => "not in",
             BinaryOp::EQ     => "==",
             BinaryOp::NE     => "!=",
+            BinaryOp::Is     => "is",
             BinaryOp::And    => "and",
             BinaryOp::Or     => "or",
+            BinaryOp::Coalesce => "??",
         };
         fmt.write_str(symbol)
     }
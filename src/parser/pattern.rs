@@ -1,4 +1,5 @@
 
+use crate::debug::DebugSymbol;
 use crate::language::InternSymbol;
 use crate::parser::primary::{Primary, AccessItem, Atom};
 use crate::parser::operator::BinaryOp;
@@ -19,7 +20,7 @@ pub enum Pattern {
     Identifier(InternSymbol),
     Attribute(Box<AttributePattern>), // receiver, attribute name
     Index(Box<IndexPattern>), // receiver, index expression
-    Tuple(Box<[Pattern]>),
+    Tuple(Box<[PatternMeta]>),
     Pack(Option<Box<Pattern>>),
     
     Modifier {
@@ -30,6 +31,13 @@ pub enum Pattern {
 
 // Pattern Data
 
+// tuple items keep their own symbol so that each binding can be reported separately
+#[derive(Debug, Clone)]
+pub struct PatternMeta {
+    pub pattern: Pattern,
+    pub symbol: DebugSymbol,
+}
+
 #[derive(Debug, Clone)]
 pub struct AttributePattern {
     pub receiver: Primary,
@@ -92,6 +100,12 @@ impl TryFrom<Primary> for Pattern {
     fn try_from(primary: Primary) -> Result<Self, Self::Error> {
         // remove the last item so that primary will eval to the reciever
         let (atom, mut path) = primary.take();
+        
+        // there would be nothing to assign to if the receiver was skipped
+        if path.iter().any(|item| matches!(item, AccessItem::OptionalChain)) {
+            return Err(IntoPatternError);
+        }
+        
         let tail = path.pop();
         let receiver = Primary::new(atom, path);
         
@@ -124,8 +138,9 @@ impl TryFrom<Expr> for Pattern {
             Expr::Tuple(items) if !items.is_empty() => {
                 let mut lvalue_items = Vec::new();
                 for expr in items.into_vec().into_iter() {
-                    let pattern = Pattern::try_from(expr.take_variant())?;
-                    lvalue_items.push(pattern);
+                    let (expr, symbol) = expr.take();
+                    let pattern = Pattern::try_from(expr)?;
+                    lvalue_items.push(PatternMeta { pattern, symbol });
                 }
                 
                 Ok(Self::Tuple(lvalue_items.into_boxed_slice()))
@@ -9,12 +9,13 @@ use crate::parser::pattern::MatchAction;
 pub enum Atom {
     Nil,
     EmptyTuple,
-    // Self_,
-    // Super,
+    Self_,
+    Super,
     
     Identifier(InternSymbol),
     BooleanLiteral(bool),
     IntegerLiteral(IntType),
+    BigIntegerLiteral(InternSymbol),  // the digits, which may be prefixed by a sign and a radix
     FloatLiteral(FloatType),
     StringLiteral(InternSymbol),
     
@@ -27,6 +28,8 @@ pub enum Atom {
 // These are the highest precedence operations in the language
 #[derive(Debug, Clone)]
 pub enum AccessItem {
+    // "?." - the rest of the path is skipped if the receiver is nil, so that the whole path evaluates to nil
+    OptionalChain,
     Attribute(InternSymbol),
     Index(ExprMeta),
     Invoke(Box<[ExprMeta]>),
@@ -49,6 +52,7 @@ impl Primary {
     }
     
     pub fn atom(&self) -> &Atom { &self.atom }
+    pub fn atom_mut(&mut self) -> &mut Atom { &mut self.atom }
     
     pub fn path(&self) -> &[AccessItem] { &self.path }
     pub fn path_mut(&mut self) -> &mut [AccessItem] { &mut self.path }
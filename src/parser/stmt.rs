@@ -35,7 +35,37 @@ pub enum Stmt {
         body: StmtList,
     },
     
+    TryExcept {
+        body: StmtList,
+        handler: Option<ExceptClause>,
+        finally: Option<StmtList>,
+    },
+    
     Assert(Expr),
+    
+    // the dotted path of the module, e.g. "foo.bar"
+    Import(InternSymbol),
+    
+    // a declaration whose names are visible to importers
+    Export(Expr),
+}
+
+
+// Exception handling
+#[derive(Debug, Clone)]
+pub struct ExceptClause {
+    name: Option<InternSymbol>,
+    body: StmtList,
+}
+
+impl ExceptClause {
+    pub fn new(name: Option<InternSymbol>, body: StmtList) -> Self {
+        Self { name, body }
+    }
+    
+    pub fn name(&self) -> Option<&InternSymbol> { self.name.as_ref() }
+    pub fn body(&self) -> &StmtList { &self.body }
+    pub fn body_mut(&mut self) -> &mut StmtList { &mut self.body }
 }
 
 
@@ -89,7 +119,12 @@ impl StmtList {
         self.suite.iter()
     }
     
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=&mut StmtMeta> {
+        self.suite.iter_mut()
+    }
+    
     pub fn end_control(&self) -> Option<&ControlFlow> { self.control.as_ref() }
+    pub fn end_control_mut(&mut self) -> Option<&mut ControlFlow> { self.control.as_mut() }
     
     pub fn take(self) -> (Vec<StmtMeta>, Option<ControlFlow>) {
         (self.suite.into_vec(), self.control)
@@ -110,6 +145,7 @@ impl StmtMeta {
     }
     
     pub fn variant(&self) -> &Stmt { &self.variant }
+    pub fn variant_mut(&mut self) -> &mut Stmt { &mut self.variant }
     pub fn take_variant(self) -> Stmt { self.variant }
     
     pub fn debug_symbol(&self) -> &DebugSymbol { &self.symbol }
@@ -1,4 +1,289 @@
 #![cfg(test)]
 
+use crate::source::SourceText;
+use crate::runtime::strings::StringInterner;
+use crate::debug::SourceError;
+use crate::language::{InternSymbol, IntType};
+use super::ParserError;
+use super::stmt::StmtMeta;
+use super::expr::Expr;
+use super::primary::Atom;
+use super::operator::BinaryOp;
+use super::visitor::{self, Visitor, VisitorMut};
 
 
+fn parse_errors(text: &str) -> Vec<ParserError> {
+    let mut interner = StringInterner::new();
+    crate::parse_source(&mut interner, SourceText::from(text.to_string())).unwrap_err()
+}
+
+// the char index where each error starts, which is the same as the byte index for ASCII text
+fn error_offsets(errors: &[ParserError]) -> Vec<usize> {
+    errors.iter()
+        .map(|error| error.debug_symbol().unwrap().start() as usize)
+        .collect()
+}
+
+#[test]
+fn errors_in_block_headers_dont_cascade() {
+    let text = "\
+if a == then
+    print(1)
+end
+while x < do
+    x += 1
+end
+fun f(a, , b)
+    return a
+end
+print(\"ok\")
+";
+    let errors = parse_errors(text);
+    assert_eq!(error_offsets(&errors), vec![
+        text.find("then").unwrap(),
+        text.find("do").unwrap(),
+        text.find(")\n    return").unwrap(),
+    ]);
+}
+
+#[test]
+fn errors_in_nested_blocks_are_reported_independently() {
+    let text = "\
+fun f(x)
+    let y = (x + )
+    if y then
+        let z = ]
+    end
+    return y
+end
+let c = 1 +
+";
+    let errors = parse_errors(text);
+    assert_eq!(errors.len(), 3);
+    
+    let offsets = error_offsets(&errors);
+    assert_eq!(offsets[0], text.find("+ )").unwrap() + 2);
+    assert_eq!(offsets[1], text.find("]").unwrap());
+}
+
+#[test]
+fn cascading_errors_are_capped() {
+    let body = "    let a = )\n    print(a)\n".repeat(10);
+    let text = format!("fun f()\n{}end\nlet b = )\n", body);
+    
+    // the errors in the function are capped, but the statement after it is still checked
+    let errors = parse_errors(&text);
+    assert_eq!(errors.len(), super::MAX_ERRORS_PER_STMT + 1);
+}
+
+#[test]
+fn unclosed_blocks_point_to_their_opening_keyword() {
+    let text = "\
+fun f(x)
+    if x then
+        print(x)
+    end
+    return x
+";
+    let errors = parse_errors(text);
+    assert_eq!(error_offsets(&errors), vec![ text.find("fun").unwrap() ]);
+    assert!(errors[0].to_string().contains("\"fun\" was never closed"));
+    
+    // the innermost block that is still open is reported
+    let text = "while true do\n    begin\n        x = 1\n";
+    let errors = parse_errors(text);
+    assert_eq!(error_offsets(&errors), vec![ text.find("begin").unwrap() ]);
+    
+    let text = "class A\n    fun f() end\n";
+    let errors = parse_errors(text);
+    assert_eq!(error_offsets(&errors), vec![ 0 ]);
+    
+    let text = "let y = match x\ncase 1, _ then\n    x\n";
+    let errors = parse_errors(text);
+    assert_eq!(error_offsets(&errors), vec![ text.find("match").unwrap() ]);
+    assert!(errors[0].to_string().contains("\"match\" was never closed"));
+}
+
+#[test]
+fn unclosed_delimiters_point_to_their_opening_token() {
+    let cases = [
+        ("print((1 + 2)\n", "("),
+        ("let x = [1, 2,\n    3\n", "["),
+        ("let t = { a = 1\n", "{"),
+        ("let d = { \"a\": 1\n", "{"),
+        ("fun g(a, b\n", "("),
+        ("let f = |a, b\n", "|"),
+        ("x = a[1\n", "["),
+    ];
+    
+    for (text, open) in cases {
+        let errors = parse_errors(text);
+        assert_eq!(error_offsets(&errors), vec![ text.find(open).unwrap() ], "{:?}", text);
+        assert!(errors[0].to_string().contains(&format!("\"{}\" was never closed", open)), "{:?}", text);
+    }
+}
+
+#[test]
+fn deeply_nested_input_is_rejected() {
+    // the nesting limit fits within a typical main thread stack, even in a debug build
+    fn on_main_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(f)
+            .unwrap().join().unwrap()
+    }
+    let parse_on_main_stack = |text: String| on_main_stack(move || {
+        parse_errors(&text).into_iter().map(|error| error.to_string()).collect::<Vec<_>>()
+    });
+    
+    let depth = 2000;
+    let cases = [
+        format!("x = {}1{}", "(".repeat(depth), ")".repeat(depth)),
+        format!("x = {}1", "-".repeat(depth)),
+        format!("x = {}1{}", "[".repeat(depth), "]".repeat(depth)),
+        format!("x = {}1{}", "{1: ".repeat(depth), "}".repeat(depth)),
+        format!("{}1{}", "begin ".repeat(depth), " end".repeat(depth)),
+        format!("{}1{}", "while x do ".repeat(depth), " end".repeat(depth)),
+        format!("{}1{}", "try ".repeat(depth), " finally 1 end".repeat(depth)),
+        format!("match x case {}y{} then 1 end", "(".repeat(depth), ")".repeat(depth)),
+        
+        // long enough that the enclosing statements can't be given a debug symbol
+        format!("{}1{}", "if true then ".repeat(20000), " end".repeat(20000)),
+    ];
+    
+    for text in cases {
+        let errors = parse_on_main_stack(text);
+        assert!(errors[0].contains("too many levels of nesting"), "{:?}", errors[0]);
+    }
+    
+    let text = (0..3000).fold("1".to_string(), |inner, _| format!("f\"{{{}}}\"", inner));
+    let errors = parse_on_main_stack(format!("print({})", text));
+    assert!(errors[0].contains("interpolated strings are nested too deeply"), "{:?}", errors[0]);
+    
+    // ordinary nesting is unaffected, and long flat expressions don't count as nesting at all
+    let cases = [
+        format!("x = {}1{}", "(".repeat(60), ")".repeat(60)),
+        format!("{}1{}", "if true then ".repeat(30), " end".repeat(30)),
+        format!("print({})", (0..40).fold("1".to_string(), |inner, _| format!("f\"{{{}}}\"", inner))),
+        format!("print({})", vec!["1"; 300].join(" + ")),
+    ];
+    for text in cases {
+        let parsed = on_main_stack(move || {
+            let mut interner = StringInterner::new();
+            crate::parse_source(&mut interner, SourceText::from(text)).is_ok()
+        });
+        assert!(parsed);
+    }
+}
+
+#[test]
+fn overlong_statements_are_rejected() {
+    // statements and the expressions in them are too long to be given a debug symbol
+    let cases = [
+        format!("while false do {} end", "1;".repeat(40000)),
+        format!("print({})", vec!["1"; 40000].join(",")),
+        format!("x = [{}]", vec!["1"; 40000].join(", ")),
+        format!("match x case y then {} end", vec!["(1, 1)"; 10000].join(", ")),
+        // flat operator chains are stopped before they can build a tree that is too deep to drop
+        format!("let x = 1{}", " + 1".repeat(150000)),
+        format!("let x = 1{}", " |> f".repeat(100000)),
+    ];
+    
+    for text in cases {
+        let errors = parse_errors(&text);
+        assert!(errors[0].to_string().contains("statement is too long"), "{:?}", errors[0].to_string());
+    }
+}
+
+#[test]
+fn parse_source_from_reader() {
+    let text = "let s = \"ünïcode\"\nfun f(x) x * 2 end\necho f(3)\n";
+    
+    let mut interner = StringInterner::new();
+    let expected = parse_ast(&mut interner, text);
+    
+    let reader = std::io::Cursor::new(text.as_bytes().to_vec());
+    let ast = crate::parse_source(&mut interner, SourceText::Reader(Box::new(reader))).unwrap();
+    assert_eq!(format!("{:?}", ast), format!("{:?}", expected));
+}
+
+
+fn parse_ast(interner: &mut StringInterner, text: &str) -> Vec<StmtMeta> {
+    crate::parse_source(interner, SourceText::from(text.to_string())).unwrap()
+}
+
+#[derive(Default)]
+struct IdentifierCollector {
+    names: Vec<InternSymbol>,
+}
+
+impl Visitor<'_> for IdentifierCollector {
+    fn visit_atom(&mut self, atom: &Atom) {
+        if let Atom::Identifier(name) = atom {
+            self.names.push(*name);
+        }
+        visitor::walk_atom(self, atom);
+    }
+}
+
+#[test]
+fn visitor_reaches_nested_expressions() {
+    let mut interner = StringInterner::new();
+    let ast = parse_ast(&mut interner, "\
+fun f(x = a)
+    while b do
+        x.y[c] = { key = d }
+    end
+    return [e, g(h), if true then i else j end]
+end
+");
+
+    let mut collector = IdentifierCollector::default();
+    for stmt in ast.iter() {
+        collector.visit_stmt_meta(stmt);
+    }
+    
+    let names = collector.names.iter()
+        .map(|name| interner.resolve(*name).unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b", "x", "c", "d", "e", "g", "h", "i", "j"]);
+}
+
+// folds additions of integer literals, as an example of a pass that rewrites the tree
+struct AddFolder;
+
+impl VisitorMut for AddFolder {
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        visitor::walk_expr_mut(self, expr);
+        
+        if let Expr::BinaryOp(BinaryOp::Add, operands) = expr {
+            let (lhs, rhs) = &**operands;
+            if let (Expr::Atom(Atom::IntegerLiteral(lhs)), Expr::Atom(Atom::IntegerLiteral(rhs))) = (lhs.variant(), rhs.variant()) {
+                *expr = Expr::Atom(Atom::IntegerLiteral(lhs + rhs));
+            }
+        }
+    }
+}
+
+#[test]
+fn mutable_visitor_rewrites_in_place() {
+    let mut interner = StringInterner::new();
+    let mut ast = parse_ast(&mut interner, "let f = fun() 1 + 2 + 3 end");
+    
+    for stmt in ast.iter_mut() {
+        AddFolder.visit_stmt_meta(stmt);
+    }
+    
+    let mut literals = Vec::new();
+    struct LiteralCollector<'a>(&'a mut Vec<IntType>);
+    impl Visitor<'_> for LiteralCollector<'_> {
+        fn visit_atom(&mut self, atom: &Atom) {
+            if let Atom::IntegerLiteral(value) = atom {
+                self.0.push(*value);
+            }
+        }
+    }
+    
+    LiteralCollector(&mut literals).visit_stmt_meta(&ast[0]);
+    assert_eq!(literals, [6]);
+}
@@ -0,0 +1,435 @@
+//! Traversal of the syntax tree.
+//!
+//! `Visitor` walks the tree by reference and `VisitorMut` walks it by mutable reference, so that a pass can
+//! rewrite nodes in place. Every method defaults to the matching `walk_*` function, which visits the children of
+//! the node. Override only the methods for the nodes of interest, and call the `walk_*` function from the
+//! override to keep descending into that node's children.
+
+use crate::parser::stmt::{StmtMeta, Stmt, StmtList, ControlFlow};
+use crate::parser::expr::{ExprMeta, Expr, ExprBlock, TableItem, TableField};
+use crate::parser::primary::{Primary, Atom, AccessItem};
+use crate::parser::pattern::Pattern;
+use crate::parser::fundefs::FunctionDef;
+use crate::parser::classdefs::ClassDef;
+
+
+pub trait Visitor<'a> {
+    fn visit_stmt_meta(&mut self, stmt: &'a StmtMeta) { self.visit_stmt(stmt.variant()) }
+    fn visit_stmt(&mut self, stmt: &'a Stmt) { walk_stmt(self, stmt) }
+    fn visit_stmt_list(&mut self, stmt_list: &'a StmtList) { walk_stmt_list(self, stmt_list) }
+    fn visit_control_flow(&mut self, control: &'a ControlFlow) { walk_control_flow(self, control) }
+    
+    fn visit_expr_meta(&mut self, expr: &'a ExprMeta) { self.visit_expr(expr.variant()) }
+    fn visit_expr(&mut self, expr: &'a Expr) { walk_expr(self, expr) }
+    fn visit_expr_block(&mut self, block: &'a ExprBlock) { walk_expr_block(self, block) }
+    fn visit_primary(&mut self, primary: &'a Primary) { walk_primary(self, primary) }
+    fn visit_atom(&mut self, atom: &'a Atom) { walk_atom(self, atom) }
+    fn visit_pattern(&mut self, pattern: &'a Pattern) { walk_pattern(self, pattern) }
+    
+    fn visit_function_def(&mut self, fundef: &'a FunctionDef) { walk_function_def(self, fundef) }
+    fn visit_class_def(&mut self, classdef: &'a ClassDef) { walk_class_def(self, classdef) }
+}
+
+pub fn walk_stmt<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a Stmt) {
+    match stmt {
+        Stmt::Expression(expr) | Stmt::Assert(expr) | Stmt::Export(expr) => visitor.visit_expr(expr),
+        
+        Stmt::Loop { body, .. } => visitor.visit_stmt_list(body),
+        
+        Stmt::WhileLoop { condition, body, .. } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt_list(body);
+        },
+        
+        Stmt::ForLoop { pattern, iter, body, .. } => {
+            visitor.visit_pattern(pattern);
+            visitor.visit_expr(iter);
+            visitor.visit_stmt_list(body);
+        },
+        
+        Stmt::TryExcept { body, handler, finally } => {
+            visitor.visit_stmt_list(body);
+            if let Some(handler) = handler {
+                visitor.visit_stmt_list(handler.body());
+            }
+            if let Some(finally) = finally {
+                visitor.visit_stmt_list(finally);
+            }
+        },
+        
+        Stmt::Import(..) => { },
+    }
+}
+
+pub fn walk_stmt_list<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt_list: &'a StmtList) {
+    for stmt in stmt_list.iter() {
+        visitor.visit_stmt_meta(stmt);
+    }
+    if let Some(control) = stmt_list.end_control() {
+        visitor.visit_control_flow(control);
+    }
+}
+
+pub fn walk_control_flow<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, control: &'a ControlFlow) {
+    match control {
+        ControlFlow::Break { expr: Some(expr), .. } | ControlFlow::Return { expr: Some(expr), .. }
+            => visitor.visit_expr(expr),
+        
+        _ => { },
+    }
+}
+
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &'a Expr) {
+    match expr {
+        Expr::Atom(atom) => visitor.visit_atom(atom),
+        
+        Expr::Primary(primary) => visitor.visit_primary(primary),
+        
+        Expr::UnaryOp(_, operand) => visitor.visit_expr_meta(operand),
+        Expr::Raise(expr) => visitor.visit_expr(expr),
+        
+        Expr::BinaryOp(_, operands) => {
+            let (lhs, rhs) = &**operands;
+            visitor.visit_expr_meta(lhs);
+            visitor.visit_expr_meta(rhs);
+        },
+        
+        Expr::Assignment(assign) => {
+            visitor.visit_pattern(&assign.lhs);
+            visitor.visit_expr(&assign.rhs);
+        },
+        
+        Expr::Unpack(Some(expr)) => visitor.visit_expr(expr),
+        Expr::Unpack(None) => { },
+        
+        Expr::Tuple(items) | Expr::List(items) | Expr::Concat(items) => {
+            for item in items.iter() {
+                visitor.visit_expr_meta(item);
+            }
+        },
+        
+        Expr::Table(items) => walk_table_items(visitor, items),
+        
+        Expr::Dict(entries) => {
+            for entry in entries.iter() {
+                visitor.visit_expr_meta(&entry.key);
+                visitor.visit_expr_meta(&entry.value);
+            }
+        },
+        
+        Expr::IfExpr { branches, else_clause } => {
+            for branch in branches.iter() {
+                visitor.visit_expr(branch.condition());
+                visitor.visit_expr_block(branch.suite());
+            }
+            if let Some(else_clause) = else_clause {
+                visitor.visit_expr_block(else_clause);
+            }
+        },
+        
+        Expr::Match { subject, cases, else_clause } => {
+            visitor.visit_expr_meta(subject);
+            for case in cases.iter() {
+                if let Some(guard) = &case.guard {
+                    visitor.visit_expr(guard);
+                }
+                visitor.visit_expr_block(&case.suite);
+            }
+            if let Some(else_clause) = else_clause {
+                visitor.visit_expr_block(else_clause);
+            }
+        },
+        
+        Expr::Block { suite, .. } => visitor.visit_expr_block(suite),
+        
+        Expr::FunctionDef(fundef) => visitor.visit_function_def(fundef),
+        
+        Expr::ClassDef(classdef) => visitor.visit_class_def(classdef),
+    }
+}
+
+fn walk_table_items<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, items: &'a [TableItem]) {
+    for item in items.iter() {
+        if let TableField::Index(index) = &item.field {
+            visitor.visit_expr_meta(index);
+        }
+        visitor.visit_expr_meta(&item.value);
+    }
+}
+
+pub fn walk_expr_block<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, block: &'a ExprBlock) {
+    visitor.visit_stmt_list(block.stmt_list());
+    if let Some(result) = block.result() {
+        visitor.visit_expr_meta(result);
+    }
+}
+
+pub fn walk_primary<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, primary: &'a Primary) {
+    visitor.visit_atom(primary.atom());
+    for item in primary.path().iter() {
+        match item {
+            AccessItem::OptionalChain | AccessItem::Attribute(..) => { },
+            AccessItem::Index(index) => visitor.visit_expr_meta(index),
+            AccessItem::Invoke(args) => {
+                for arg in args.iter() {
+                    visitor.visit_expr_meta(arg);
+                }
+            },
+            AccessItem::InvokeTable(items) => walk_table_items(visitor, items),
+        }
+    }
+}
+
+pub fn walk_atom<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, atom: &'a Atom) {
+    if let Atom::Group { inner, .. } = atom {
+        visitor.visit_expr(inner);
+    }
+}
+
+pub fn walk_pattern<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, pattern: &'a Pattern) {
+    match pattern {
+        Pattern::Identifier(..) | Pattern::Pack(None) => { },
+        
+        Pattern::Attribute(target) => visitor.visit_primary(&target.receiver),
+        
+        Pattern::Index(target) => {
+            visitor.visit_primary(&target.receiver);
+            visitor.visit_expr_meta(&target.index);
+        },
+        
+        Pattern::Tuple(items) => {
+            for item in items.iter() {
+                visitor.visit_pattern(&item.pattern);
+            }
+        },
+        
+        Pattern::Pack(Some(pattern)) | Pattern::Modifier { pattern, .. } => visitor.visit_pattern(pattern),
+    }
+}
+
+pub fn walk_function_def<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, fundef: &'a FunctionDef) {
+    for param in fundef.signature.default.iter() {
+        visitor.visit_expr_meta(&param.default);
+    }
+    visitor.visit_expr_block(&fundef.body);
+}
+
+pub fn walk_class_def<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, classdef: &'a ClassDef) {
+    if let Some(parent) = &classdef.parent {
+        visitor.visit_expr_meta(parent);
+    }
+    for method in classdef.methods.iter() {
+        visitor.visit_function_def(method);
+    }
+}
+
+
+pub trait VisitorMut {
+    fn visit_stmt_meta(&mut self, stmt: &mut StmtMeta) { self.visit_stmt(stmt.variant_mut()) }
+    fn visit_stmt(&mut self, stmt: &mut Stmt) { walk_stmt_mut(self, stmt) }
+    fn visit_stmt_list(&mut self, stmt_list: &mut StmtList) { walk_stmt_list_mut(self, stmt_list) }
+    fn visit_control_flow(&mut self, control: &mut ControlFlow) { walk_control_flow_mut(self, control) }
+    
+    fn visit_expr_meta(&mut self, expr: &mut ExprMeta) { self.visit_expr(expr.variant_mut()) }
+    fn visit_expr(&mut self, expr: &mut Expr) { walk_expr_mut(self, expr) }
+    fn visit_expr_block(&mut self, block: &mut ExprBlock) { walk_expr_block_mut(self, block) }
+    fn visit_primary(&mut self, primary: &mut Primary) { walk_primary_mut(self, primary) }
+    fn visit_atom(&mut self, atom: &mut Atom) { walk_atom_mut(self, atom) }
+    fn visit_pattern(&mut self, pattern: &mut Pattern) { walk_pattern_mut(self, pattern) }
+    
+    fn visit_function_def(&mut self, fundef: &mut FunctionDef) { walk_function_def_mut(self, fundef) }
+    fn visit_class_def(&mut self, classdef: &mut ClassDef) { walk_class_def_mut(self, classdef) }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Expression(expr) | Stmt::Assert(expr) | Stmt::Export(expr) => visitor.visit_expr(expr),
+        
+        Stmt::Loop { body, .. } => visitor.visit_stmt_list(body),
+        
+        Stmt::WhileLoop { condition, body, .. } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt_list(body);
+        },
+        
+        Stmt::ForLoop { pattern, iter, body, .. } => {
+            visitor.visit_pattern(pattern);
+            visitor.visit_expr(iter);
+            visitor.visit_stmt_list(body);
+        },
+        
+        Stmt::TryExcept { body, handler, finally } => {
+            visitor.visit_stmt_list(body);
+            if let Some(handler) = handler {
+                visitor.visit_stmt_list(handler.body_mut());
+            }
+            if let Some(finally) = finally {
+                visitor.visit_stmt_list(finally);
+            }
+        },
+        
+        Stmt::Import(..) => { },
+    }
+}
+
+pub fn walk_stmt_list_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt_list: &mut StmtList) {
+    for stmt in stmt_list.iter_mut() {
+        visitor.visit_stmt_meta(stmt);
+    }
+    if let Some(control) = stmt_list.end_control_mut() {
+        visitor.visit_control_flow(control);
+    }
+}
+
+pub fn walk_control_flow_mut<V: VisitorMut + ?Sized>(visitor: &mut V, control: &mut ControlFlow) {
+    match control {
+        ControlFlow::Break { expr: Some(expr), .. } | ControlFlow::Return { expr: Some(expr), .. }
+            => visitor.visit_expr(expr),
+        
+        _ => { },
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Atom(atom) => visitor.visit_atom(atom),
+        
+        Expr::Primary(primary) => visitor.visit_primary(primary),
+        
+        Expr::UnaryOp(_, operand) => visitor.visit_expr_meta(operand),
+        Expr::Raise(expr) => visitor.visit_expr(expr),
+        
+        Expr::BinaryOp(_, operands) => {
+            let (lhs, rhs) = &mut **operands;
+            visitor.visit_expr_meta(lhs);
+            visitor.visit_expr_meta(rhs);
+        },
+        
+        Expr::Assignment(assign) => {
+            visitor.visit_pattern(&mut assign.lhs);
+            visitor.visit_expr(&mut assign.rhs);
+        },
+        
+        Expr::Unpack(Some(expr)) => visitor.visit_expr(expr),
+        Expr::Unpack(None) => { },
+        
+        Expr::Tuple(items) | Expr::List(items) | Expr::Concat(items) => {
+            for item in items.iter_mut() {
+                visitor.visit_expr_meta(item);
+            }
+        },
+        
+        Expr::Table(items) => walk_table_items_mut(visitor, items),
+        
+        Expr::Dict(entries) => {
+            for entry in entries.iter_mut() {
+                visitor.visit_expr_meta(&mut entry.key);
+                visitor.visit_expr_meta(&mut entry.value);
+            }
+        },
+        
+        Expr::IfExpr { branches, else_clause } => {
+            for branch in branches.iter_mut() {
+                visitor.visit_expr(branch.condition_mut());
+                visitor.visit_expr_block(branch.suite_mut());
+            }
+            if let Some(else_clause) = else_clause {
+                visitor.visit_expr_block(else_clause);
+            }
+        },
+        
+        Expr::Match { subject, cases, else_clause } => {
+            visitor.visit_expr_meta(subject);
+            for case in cases.iter_mut() {
+                if let Some(guard) = &mut case.guard {
+                    visitor.visit_expr(guard);
+                }
+                visitor.visit_expr_block(&mut case.suite);
+            }
+            if let Some(else_clause) = else_clause {
+                visitor.visit_expr_block(else_clause);
+            }
+        },
+        
+        Expr::Block { suite, .. } => visitor.visit_expr_block(suite),
+        
+        Expr::FunctionDef(fundef) => visitor.visit_function_def(fundef),
+        
+        Expr::ClassDef(classdef) => visitor.visit_class_def(classdef),
+    }
+}
+
+fn walk_table_items_mut<V: VisitorMut + ?Sized>(visitor: &mut V, items: &mut [TableItem]) {
+    for item in items.iter_mut() {
+        if let TableField::Index(index) = &mut item.field {
+            visitor.visit_expr_meta(index);
+        }
+        visitor.visit_expr_meta(&mut item.value);
+    }
+}
+
+pub fn walk_expr_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut ExprBlock) {
+    visitor.visit_stmt_list(block.stmt_list_mut());
+    if let Some(result) = block.result_mut() {
+        visitor.visit_expr_meta(result);
+    }
+}
+
+pub fn walk_primary_mut<V: VisitorMut + ?Sized>(visitor: &mut V, primary: &mut Primary) {
+    visitor.visit_atom(primary.atom_mut());
+    for item in primary.path_mut().iter_mut() {
+        match item {
+            AccessItem::OptionalChain | AccessItem::Attribute(..) => { },
+            AccessItem::Index(index) => visitor.visit_expr_meta(index),
+            AccessItem::Invoke(args) => {
+                for arg in args.iter_mut() {
+                    visitor.visit_expr_meta(arg);
+                }
+            },
+            AccessItem::InvokeTable(items) => walk_table_items_mut(visitor, items),
+        }
+    }
+}
+
+pub fn walk_atom_mut<V: VisitorMut + ?Sized>(visitor: &mut V, atom: &mut Atom) {
+    if let Atom::Group { inner, .. } = atom {
+        visitor.visit_expr(inner);
+    }
+}
+
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(visitor: &mut V, pattern: &mut Pattern) {
+    match pattern {
+        Pattern::Identifier(..) | Pattern::Pack(None) => { },
+        
+        Pattern::Attribute(target) => visitor.visit_primary(&mut target.receiver),
+        
+        Pattern::Index(target) => {
+            visitor.visit_primary(&mut target.receiver);
+            visitor.visit_expr_meta(&mut target.index);
+        },
+        
+        Pattern::Tuple(items) => {
+            for item in items.iter_mut() {
+                visitor.visit_pattern(&mut item.pattern);
+            }
+        },
+        
+        Pattern::Pack(Some(pattern)) | Pattern::Modifier { pattern, .. } => visitor.visit_pattern(pattern),
+    }
+}
+
+pub fn walk_function_def_mut<V: VisitorMut + ?Sized>(visitor: &mut V, fundef: &mut FunctionDef) {
+    for param in fundef.signature.default.iter_mut() {
+        visitor.visit_expr_meta(&mut param.default);
+    }
+    visitor.visit_expr_block(&mut fundef.body);
+}
+
+pub fn walk_class_def_mut<V: VisitorMut + ?Sized>(visitor: &mut V, classdef: &mut ClassDef) {
+    if let Some(parent) = &mut classdef.parent {
+        visitor.visit_expr_meta(parent);
+    }
+    for method in classdef.methods.iter_mut() {
+        visitor.visit_function_def(method);
+    }
+}
@@ -10,12 +10,15 @@ pub mod function;
 pub mod iter;
 pub mod module;
 pub mod errors;
+pub mod random;
+mod slots;
+mod context;
 
 mod tests;
 
-pub use gc::Gc;
+pub use gc::{Gc, GcHeap};
 pub use vm::VirtualMachine;
-pub use strings::STRING_TABLE;
+pub use context::Runtime;
 pub use variant::{Variant, VariantKey};
 pub use module::Module;
 pub use errors::{RuntimeError, ExecResult};
@@ -24,4 +27,5 @@ pub use errors::{RuntimeError, ExecResult};
 
 pub type DefaultHasher = AHasher;
 pub type DefaultBuildHasher = ahash::RandomState;
-pub type HashMap<K, V> = std::collections::HashMap<K,V, DefaultBuildHasher>;
\ No newline at end of file
+pub type HashMap<K, V> = std::collections::HashMap<K,V, DefaultBuildHasher>;
+pub type HashSet<T> = std::collections::HashSet<T, DefaultBuildHasher>;
\ No newline at end of file
@@ -0,0 +1,48 @@
+use core::cell::RefCell;
+use crate::runtime::gc::GcHeap;
+use crate::runtime::strings::{StringTable, enter_string_table};
+
+
+/// An isolated instance of the Sphinx runtime, owning a garbage collected heap and a string table.
+///
+/// Nothing is shared between runtimes, or with the default heap and string table of the thread.
+/// Compiled programs carry their own strings, so the same `UnloadedProgram` can be loaded into any runtime
+/// and its symbols are bound to the string table of the runtime that was entered when it is loaded.
+/// A runtime that is not entered can be sent to another thread.
+pub struct Runtime {
+    heap: GcHeap,
+    strings: RefCell<StringTable>,
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Self {
+            heap: GcHeap::new(),
+            strings: RefCell::new(StringTable::new()),
+        }
+    }
+    
+    pub fn heap(&self) -> &GcHeap { &self.heap }
+    
+    pub fn strings(&self) -> &RefCell<StringTable> { &self.strings }
+    
+    /// Makes this the current runtime of the thread while the closure runs.
+    /// Everything that is allocated or interned in the closure belongs to this runtime.
+    ///
+    /// # Safety
+    ///
+    /// The same as `GcHeap::enter()`. No value from the runtime may be used outside of the closure, unless the
+    /// runtime is entered again, and values from outside of the runtime must not be used inside the closure.
+    pub unsafe fn enter<R>(&self, f: impl FnOnce() -> R) -> R where R: Send {
+        // SAFETY: upheld by the caller
+        enter_string_table(&self.strings, || unsafe { self.heap.enter(f) })
+    }
+}
+
+impl Drop for Runtime {
+    // allocations can refer to symbols, so free them while the string table is still current
+    fn drop(&mut self) {
+        let heap = core::mem::take(&mut self.heap);
+        enter_string_table(&self.strings, || drop(heap))
+    }
+}
@@ -46,6 +46,13 @@ impl RuntimeError {
     
     pub fn kind(&self) -> &ErrorKind { &self.kind }
     
+    pub fn message(&self) -> &StringValue { &self.message }
+    
+    /// Iterate over the trace sites of this error, starting from the most recent call.
+    pub fn iter_trace(&self) -> impl DoubleEndedIterator<Item=&TraceSite> {
+        self.traceback.iter()
+    }
+    
     pub fn traceback(&self) -> Traceback<'_> {
         Traceback::build(self.traceback.iter())
     }
@@ -54,7 +61,7 @@ impl RuntimeError {
 impl Error for RuntimeError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         self.cause.as_ref().map(
-            |error| &*error as &RuntimeError as &dyn Error
+            |error| error as &RuntimeError as &dyn Error
         )
     }
 }
@@ -1,11 +1,14 @@
 //! Error constructor functions
 
+use core::fmt;
 use crate::utils;
+use crate::language::IntType;
 use crate::runtime::Variant;
 use crate::runtime::function::Signature;
 use crate::runtime::types::MethodTag;
 use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
 use crate::runtime::errors::RuntimeError;
+use crate::debug::codes::{self, ErrorCode};
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -16,18 +19,69 @@ pub enum ErrorKind {
     DivideByZero,
     NegativeShiftCount,
     NameNotDefined,
+    AttributeNotFound,
     CantAssignImmutable,
     UnhashableValue,
+    IndexOutOfBounds,
+    KeyNotFound,
     MissingArguments,
     TooManyArguments,
     MethodNotSupported,
     AssertFailed,
     InvalidValue,
     UnpackError,
+    ImportError,
+    StackOverflow,
+    BudgetExceeded,
+    MemoryError,
+    UserError,
     Unspecified,
+    Interrupted,
+    InvalidBytecode,  // kinds are serialized as bytes, so new ones are only added at the end
+}
+
+impl From<ErrorKind> for u8 {
+    fn from(kind: ErrorKind) -> Self { kind as u8 }
+}
+
+impl TryFrom<u8> for ErrorKind {
+    type Error = u8;
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        ErrorKind::ALL.iter().copied()
+            .find(|kind| u8::from(*kind) == byte)
+            .ok_or(byte)
+    }
 }
 
 impl ErrorKind {
+    const ALL: &'static [ErrorKind] = &[
+        Self::InvalidUnaryOperand,
+        Self::InvalidBinaryOperand,
+        Self::OverflowError,
+        Self::DivideByZero,
+        Self::NegativeShiftCount,
+        Self::NameNotDefined,
+        Self::AttributeNotFound,
+        Self::CantAssignImmutable,
+        Self::UnhashableValue,
+        Self::IndexOutOfBounds,
+        Self::KeyNotFound,
+        Self::MissingArguments,
+        Self::TooManyArguments,
+        Self::MethodNotSupported,
+        Self::AssertFailed,
+        Self::InvalidValue,
+        Self::UnpackError,
+        Self::ImportError,
+        Self::StackOverflow,
+        Self::BudgetExceeded,
+        Self::MemoryError,
+        Self::UserError,
+        Self::Unspecified,
+        Self::Interrupted,
+        Self::InvalidBytecode,
+    ];
+    
     pub fn name(&self) -> StringValue {
         let name = match self {
             Self::InvalidUnaryOperand => static_symbol!("InvalidUnaryOperandError"),
@@ -36,18 +90,58 @@ impl ErrorKind {
             Self::DivideByZero => static_symbol!("DivideByZeroError"),
             Self::NegativeShiftCount => static_symbol!("NegativeShiftCountError"),
             Self::NameNotDefined => static_symbol!("NameNotDefinedError"),
+            Self::AttributeNotFound => static_symbol!("AttributeNotFoundError"),
             Self::CantAssignImmutable => static_symbol!("CantAssignImmutableError"),
             Self::UnhashableValue => static_symbol!("UnhashableValueError"),
+            Self::IndexOutOfBounds => static_symbol!("IndexOutOfBoundsError"),
+            Self::KeyNotFound => static_symbol!("KeyNotFoundError"),
             Self::MissingArguments => static_symbol!("MissingArgumentsError"),
             Self::TooManyArguments => static_symbol!("TooManyArgumentsError"),
             Self::MethodNotSupported => static_symbol!("MethodNotSupportedError"),
             Self::AssertFailed => static_symbol!("AssertFailedError"),
             Self::InvalidValue => static_symbol!("InvalidValueError"),
             Self::UnpackError => static_symbol!("UnpackError"),
+            Self::ImportError => static_symbol!("ImportError"),
+            Self::StackOverflow => static_symbol!("StackOverflowError"),
+            Self::BudgetExceeded => static_symbol!("BudgetExceededError"),
+            Self::MemoryError => static_symbol!("MemoryError"),
+            Self::UserError => static_symbol!("UserError"),
             Self::Unspecified => static_symbol!("UnspecifiedError"),
+            Self::Interrupted => static_symbol!("InterruptedError"),
+            Self::InvalidBytecode => static_symbol!("InvalidBytecodeError"),
         };
         name.into()
     }
+    
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidUnaryOperand => codes::E0301,
+            Self::InvalidBinaryOperand => codes::E0302,
+            Self::OverflowError => codes::E0303,
+            Self::DivideByZero => codes::E0304,
+            Self::NegativeShiftCount => codes::E0305,
+            Self::NameNotDefined => codes::E0306,
+            Self::AttributeNotFound => codes::E0307,
+            Self::CantAssignImmutable => codes::E0308,
+            Self::UnhashableValue => codes::E0309,
+            Self::IndexOutOfBounds => codes::E0310,
+            Self::KeyNotFound => codes::E0311,
+            Self::MissingArguments => codes::E0312,
+            Self::TooManyArguments => codes::E0313,
+            Self::MethodNotSupported => codes::E0314,
+            Self::AssertFailed => codes::E0315,
+            Self::InvalidValue => codes::E0316,
+            Self::UnpackError => codes::E0317,
+            Self::ImportError => codes::E0318,
+            Self::StackOverflow => codes::E0319,
+            Self::BudgetExceeded => codes::E0320,
+            Self::MemoryError => codes::E0321,
+            Self::UserError => codes::E0322,
+            Self::Unspecified => codes::E0323,
+            Self::Interrupted => codes::E0324,
+            Self::InvalidBytecode => codes::E0325,
+        }
+    }
 }
 
 
@@ -70,7 +164,7 @@ impl RuntimeError {
 
     pub fn invalid_binary_operands(lhs: &Variant, rhs: &Variant) -> Box<Self> {
         Box::new(Self::new(
-            ErrorKind::InvalidUnaryOperand,
+            ErrorKind::InvalidBinaryOperand,
             StringValue::new_uninterned(format!(
                 "unsupported operands: '{}' and '{}'", 
                 format_type(lhs), format_type(rhs)
@@ -99,13 +193,53 @@ impl RuntimeError {
         ))
     }
 
-    pub fn name_not_defined(name: StringSymbol) -> Box<Self> {
+    pub fn name_not_defined(name: StringSymbol, suggestion: Option<&str>) -> Box<Self> {
+        let message = match suggestion {
+            Some(suggestion) => format!("undefined variable \"{}\", did you mean \"{}\"?", name, suggestion),
+            None => format!("undefined variable \"{}\"", name),
+        };
         Box::new(Self::new(
             ErrorKind::NameNotDefined,
-            StringValue::new_uninterned(format!("undefined variable \"{}\"", name)),
+            StringValue::new_uninterned(message),
         ))
     }
 
+    pub fn attribute_not_found(receiver: &Variant, name: StringSymbol) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::AttributeNotFound,
+            StringValue::new_uninterned(format!(
+                "'{}' has no attribute \"{}\"", format_type(receiver), name
+            )),
+        ))
+    }
+
+    pub fn invalid_parent_class(parent: &Variant) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::InvalidValue,
+            StringValue::new_uninterned(format!(
+                "can't inherit from '{}'", format_type(parent)
+            )),
+        ))
+    }
+
+    pub fn not_a_type(value: &Variant) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::InvalidValue,
+            StringValue::new_uninterned(format!(
+                "expected a type or a class, not '{}'", format_type(value)
+            )),
+        ))
+    }
+    
+    pub fn invalid_format_result(method: StringSymbol, value: &Variant) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::InvalidValue,
+            StringValue::new_uninterned(format!(
+                "{}() must return a string, not '{}'", method, format_type(value)
+            )),
+        ))
+    }
+    
     pub fn cant_assign_immutable(name: StringSymbol) -> Box<Self> {
         Box::new(Self::new(
             ErrorKind::CantAssignImmutable,
@@ -120,6 +254,20 @@ impl RuntimeError {
         ))
     }
 
+    pub fn index_out_of_bounds(index: IntType, len: usize) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::IndexOutOfBounds,
+            StringValue::new_uninterned(format!("index {} is out of bounds for length {}", index, len)),
+        ))
+    }
+
+    pub fn key_not_found(key: &Variant) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::KeyNotFound,
+            StringValue::new_uninterned(format!("key {} not found", key.display_echo())),
+        ))
+    }
+
     pub fn assert_failed(message: Option<StringValue>) -> Box<Self> {
         Box::new(Self::new(
             ErrorKind::AssertFailed,
@@ -175,6 +323,11 @@ impl RuntimeError {
             MethodTag::AsInt => format!("can't interpret '{}' as int", receiver),
             MethodTag::AsFloat => format!("can't interpret '{}' as float", receiver),
             MethodTag::Invoke => format!("type '{}' is not callable", receiver),
+            MethodTag::GetAttr | MethodTag::SetAttr
+                => format!("type '{}' does not have attributes", receiver),
+            
+            MethodTag::Len => format!("type '{}' has no length", receiver),
+            MethodTag::Contains => format!("type '{}' does not support \"in\"", receiver),
             
             MethodTag::IterInit => format!("type '{}' is not iterable", receiver),
             MethodTag::IterNext | MethodTag::IterItem
@@ -196,6 +349,66 @@ impl RuntimeError {
         ))
     }
 
+    pub fn module_not_found(name: &str) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::ImportError,
+            StringValue::new_uninterned(format!("could not find module '{}'", name)),
+        ))
+    }
+    
+    pub fn circular_import(name: &str) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::ImportError,
+            StringValue::new_uninterned(format!("module '{}' is already being imported", name)),
+        ))
+    }
+    
+    pub fn import_failed(name: &str, reason: impl fmt::Display) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::ImportError,
+            StringValue::new_uninterned(format!("failed to import module '{}': {}", name, reason)),
+        ))
+    }
+    
+    pub fn stack_overflow(max_depth: usize) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::StackOverflow,
+            StringValue::new_uninterned(format!("maximum call depth of {} exceeded", max_depth)),
+        ))
+    }
+    
+    pub fn budget_exceeded() -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::BudgetExceeded,
+            StringValue::new_uninterned("instruction budget exhausted"),
+        ))
+    }
+    
+    pub fn out_of_memory(allocated: usize) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::MemoryError,
+            StringValue::new_uninterned(format!("memory limit exceeded ({} bytes in use)", allocated)),
+        ))
+    }
+    
+    pub fn interrupted() -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::Interrupted,
+            StringValue::new_uninterned("interrupted"),
+        ))
+    }
+    
+    pub fn invalid_bytecode(message: &str) -> Box<Self> {
+        Box::new(Self::new(
+            ErrorKind::InvalidBytecode,
+            StringValue::new_uninterned(format!("invalid bytecode: {}", message)),
+        ))
+    }
+    
+    pub fn user_error(message: StringValue) -> Box<Self> {
+        Box::new(Self::new(ErrorKind::UserError, message))
+    }
+
     pub fn other(message: impl AsRef<str>) -> Box<Self> {
         Box::new(Self::new(
             ErrorKind::Unspecified,
@@ -1,6 +1,7 @@
 use core::fmt;
 use core::cell::Cell;
 use crate::codegen::{FunctionID, FunctionProto};
+use crate::language::Access;
 use crate::runtime::Variant;
 use crate::runtime::module::{Module, NamespaceEnv};
 use crate::runtime::vm::VirtualMachine;
@@ -14,16 +15,24 @@ pub use crate::codegen::opcodes::UpvalueIndex;
 
 
 /// Call directive
-
 pub enum Call {
     Chunk {
-        module: Gc<Module>,
-        chunk_id: FunctionID,
+        function: Gc<Function>,
+        receiver: Variant,  // placed in the receiver slot of the new call frame
     },
     Native {
         func: Gc<NativeFunction>,
         nargs: usize,
     },
+    NativeMethod {
+        method: &'static NativeMethod,
+        receiver: Variant,
+        nargs: usize,
+    },
+    NativeCtor {
+        ctor: &'static NativeCtor,
+        nargs: usize,
+    },
 }
 
 pub trait Callable {
@@ -51,6 +60,10 @@ impl Function {
         Self { fun_id, module, upvalues }
     }
     
+    pub fn fun_id(&self) -> FunctionID { self.fun_id }
+    
+    pub fn module(&self) -> Gc<Module> { self.module }
+    
     pub fn upvalues(&self) -> &[Upvalue] { &self.upvalues }
     
     pub fn proto(&self) -> &FunctionProto {
@@ -62,24 +75,22 @@ impl Function {
     }
 }
 
-impl Callable for Function {
+impl Callable for Gc<Function> {
     fn signature(&self) -> &Signature { self.proto().signature() }
     
     fn raw_call(&self, _args: &[Variant]) -> Call {
         Call::Chunk {
-            module: self.module,
-            chunk_id: self.fun_id,
+            function: *self,
+            receiver: Variant::Function(*self),
         }
     }
 }
 
-impl Callable for Gc<Function> {
-    fn signature(&self) -> &Signature {
-        <Function as Callable>::signature(self)
-    }
-    
-    fn raw_call(&self, args: &[Variant]) -> Call {
-        <Function as Callable>::raw_call(self, args)
+impl Gc<Function> {
+    /// Call the function with the given receiver instead of the function itself, as is done for methods
+    pub fn method_call(&self, receiver: Variant, args: &[Variant]) -> ExecResult<Call> {
+        self.signature().check_args(args)?;
+        Ok(Call::Chunk { function: *self, receiver })
     }
 }
 
@@ -158,7 +169,7 @@ impl NativeFunction {
     
     pub fn defaults(&self) -> &[Variant] {
         match self.defaults.as_ref() {
-            Some(defaults) => &*defaults,
+            Some(defaults) => defaults,
             None => &[],
         }
     }
@@ -181,6 +192,81 @@ impl Callable for Gc<NativeFunction> {
     }
 }
 
+// Native Methods
+
+pub type NativeMethodFn = fn(receiver: &Variant, args: &[Variant]) -> ExecResult<Variant>;
+
+/// Methods of builtin types. These are bound to their receiver when accessed as an attribute.
+#[derive(Debug)]
+pub struct NativeMethod {
+    pub name: &'static str,
+    pub params: &'static [&'static str],
+    pub func: NativeMethodFn,
+}
+
+impl NativeMethod {
+    // only built when needed, for error messages
+    pub fn signature(&self) -> Signature {
+        let required = self.params.iter()
+            .map(|param| Parameter::new(*param, Access::ReadWrite))
+            .collect();
+        
+        Signature::new(Some(self.name), required, Vec::new(), None)
+    }
+    
+    pub fn method_call(&'static self, receiver: Variant, args: &[Variant]) -> ExecResult<Call> {
+        if args.len() != self.params.len() {
+            self.signature().check_args(args)?;
+        }
+        Ok(Call::NativeMethod { method: self, receiver, nargs: args.len() })
+    }
+    
+    /// actually execute a native method
+    pub fn exec_method(&self, receiver: &Variant, args: &[Variant]) -> ExecResult<Variant> {
+        (self.func)(receiver, args)
+    }
+}
+
+// Native Constructors
+
+pub type NativeCtorFn = fn(vm: &mut VirtualMachine<'_>, args: &[Variant]) -> ExecResult<Variant>;
+
+/// Constructors of builtin types. These are called through the type object, e.g. `int("10")`.
+/// Omitted default arguments are simply left out of the arguments given to the constructor.
+#[derive(Debug)]
+pub struct NativeCtor {
+    pub name: &'static str,
+    pub params: &'static [&'static str],
+    pub defaults: &'static [&'static str],
+    pub variadic: Option<&'static str>,
+    pub func: NativeCtorFn,
+}
+
+impl NativeCtor {
+    // only built when needed, for error messages
+    pub fn signature(&self) -> Signature {
+        let to_params = |names: &[&'static str]| names.iter()
+            .map(|name| Parameter::new(*name, Access::ReadWrite))
+            .collect();
+        
+        let variadic = self.variadic.map(|name| Parameter::new(name, Access::ReadWrite));
+        Signature::new(Some(self.name), to_params(self.params), to_params(self.defaults), variadic)
+    }
+    
+    pub fn call(&'static self, args: &[Variant]) -> ExecResult<Call> {
+        let max_arity = self.params.len() + self.defaults.len();
+        if args.len() < self.params.len() || (self.variadic.is_none() && args.len() > max_arity) {
+            self.signature().check_args(args)?;
+        }
+        Ok(Call::NativeCtor { ctor: self, nargs: args.len() })
+    }
+    
+    /// actually execute a native constructor
+    pub fn exec_ctor(&self, vm: &mut VirtualMachine<'_>, args: &[Variant]) -> ExecResult<Variant> {
+        (self.func)(vm, args)
+    }
+}
+
 unsafe impl GcTrace for NativeFunction {
     fn trace(&self) {
         self.env.mark_trace();
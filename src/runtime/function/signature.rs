@@ -1,7 +1,7 @@
 use core::fmt;
 use crate::language::Access;
 use crate::runtime::Variant;
-use crate::runtime::strings::{StringValue, StringSymbol, StrBuffer, STRING_TABLE};
+use crate::runtime::strings::{StringValue, StringSymbol, StrBuffer, with_string_table};
 use crate::runtime::errors::{ExecResult, RuntimeError};
 
 
@@ -47,11 +47,11 @@ impl Signature {
         
         let mut buf = StrBuffer::<64>::new();
         if write_name(self.name, &mut buf).is_ok() {
-            StringValue::new_maybe_interned(buf)
+            StringValue::new_uninterned(buf)
         } else {
             let mut buf = String::new();
             write_name(self.name, &mut buf).ok();
-            StringValue::new_maybe_interned(buf)
+            StringValue::new_uninterned(buf)
         }
     }
     
@@ -158,7 +158,7 @@ impl fmt::Display for Signature {
 }
 
 fn format_signature(name: Option<&StringSymbol>, required: &[Parameter], default: &[Parameter], variadic: Option<&Parameter>) -> String {
-    STRING_TABLE.with(|string_table| {
+    with_string_table(|string_table| {
         let string_table = string_table.borrow();
         
         let name = name
@@ -1,10 +1,22 @@
-///! The Sphinx language garbage collector.
-///! Most of the "public" API is centered around the `Gc<T>` smart pointer. 
-///! See the documentation for the [runtime::gc::handle].
+//! The Sphinx language garbage collector.
+//! Most of the "public" API is centered around the `Gc<T>` smart pointer. 
+//! See the documentation for the [runtime::gc::handle].
+//!
+//! Collection is incremental. A cycle is split into a mark phase and a sweep phase, and each call to
+//! `gc_collect()` performs a small amount of work in proportion to the memory allocated since the last step.
+//! Marking uses a "snapshot-at-the-beginning" scheme: everything reachable from the root when the cycle begins 
+//! is kept alive, and allocations made while a cycle is in progress are considered marked. For this to hold, any
+//! code that overwrites or removes a GC reference held inside GC'd data must first pass the old value to `gc_barrier()`.
+//!
+//! Allocations are made in the current heap of the thread. Each thread has a default heap, and a separate `GcHeap` 
+//! can be entered to run an interpreter in isolation from anything else on the thread.
 
 use core::fmt;
 use core::ptr::NonNull;
 use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::io;
+use std::time::{Duration, Instant};
 use log;
 
 mod trace;
@@ -12,6 +24,7 @@ mod ptrmeta;
 mod gcbox;
 mod handle;
 mod weak;
+mod dump;
 
 pub use trace::GcTrace;
 pub use handle::{Gc, GcWeak};
@@ -20,49 +33,263 @@ use gcbox::{GcBox, GcBoxPtr};
 
 
 thread_local! {
-    static GC_STATE: RefCell<GcState> = RefCell::new(GcState::default());
+    // used when no other heap has been entered
+    static DEFAULT_HEAP: HeapData = HeapData::default();
+    
+    static CURRENT_HEAP: Cell<Option<NonNull<HeapData>>> = const { Cell::new(None) };
+}
+
+struct HeapData {
+    // identifies the heap that an allocation belongs to, since a GcHeap can move between uses
+    id: usize,
+    
+    state: RefCell<GcState>,
+    
+    phase: Cell<GcPhase>,
+    
+    // The value of the header mark flag that currently means "marked". Flipped at the end of every cycle
+    // so that the survivors of a cycle don't need to be individually unmarked.
+    epoch: Cell<bool>,
+    
+    // Marked allocations whose contents have not been traced yet
+    gray: RefCell<Vec<GcBoxPtr>>,
+}
+
+impl Default for HeapData {
+    fn default() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            state: RefCell::default(),
+            phase: Cell::default(),
+            epoch: Cell::default(),
+            gray: RefCell::default(),
+        }
+    }
 }
 
+#[inline]
+fn with_heap<R>(f: impl FnOnce(&HeapData) -> R) -> R {
+    match CURRENT_HEAP.with(Cell::get) {
+        // SAFETY: the pointer is only set while the GcHeap is borrowed by GcHeap::enter()
+        Some(heap) => f(unsafe { heap.as_ref() }),
+        None => DEFAULT_HEAP.with(f),
+    }
+}
+
+#[inline]
+fn current_heap_id() -> usize {
+    with_heap(|heap| heap.id)
+}
+
+#[inline]
+fn with_state<R>(f: impl FnOnce(&RefCell<GcState>) -> R) -> R {
+    with_heap(|heap| f(&heap.state))
+}
+
+
+/// An isolated garbage collected heap.
+///
+/// While a heap is entered, all allocations on the thread are made in that heap and all GC functions operate on it.
+/// Dropping the heap frees everything allocated from it. Nothing prevents a handle from outliving its heap, 
+/// so entering a heap is unsafe, see `GcHeap::enter()`. With debug assertions enabled, using a handle while a 
+/// different heap is current will panic.
+///
+/// A heap that is not entered can be sent to another thread, taking all of its allocations with it.
+/// Note that interned strings are owned by the string table and not the heap, see `Runtime`.
+#[derive(Default)]
+pub struct GcHeap {
+    data: HeapData,
+}
+
+// SAFETY: handles are not Send, and the caller of GcHeap::enter() must not use any handle allocated in the heap
+// outside of the closure, so the allocations can only be accessed from the thread that currently has the heap entered
+unsafe impl Send for GcHeap { }
+
+impl GcHeap {
+    pub fn new() -> Self {
+        Self { data: HeapData::default() }
+    }
+    
+    /// Makes this the current heap of the thread while the closure runs.
+    ///
+    /// # Safety
+    ///
+    /// Handles to allocations made in this heap must not be used once the closure returns, until the heap is 
+    /// entered again. The closure is free to capture references to outer state, and nothing stops it from storing 
+    /// a handle there (or in a thread local), so this is up to the caller. A handle that is kept past the heap being 
+    /// dropped is left dangling. 
+    ///
+    /// Likewise, handles from other heaps must not be used or stored in an allocation while the closure runs.
+    pub unsafe fn enter<R>(&self, f: impl FnOnce() -> R) -> R where R: Send {
+        struct Restore(Option<NonNull<HeapData>>);
+        
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT_HEAP.with(|current| current.set(self.0))
+            }
+        }
+        
+        let previous = CURRENT_HEAP.with(|current| current.replace(Some(NonNull::from(&self.data))));
+        let _restore = Restore(previous);
+        f()
+    }
+}
+
+impl Drop for GcHeap {
+    fn drop(&mut self) {
+        // SAFETY: nothing allocated here survives the heap being freed
+        unsafe { self.enter(|| self.data.state.borrow_mut().free_all()) }
+    }
+}
+
+/// Performs an incremental step of garbage collection if enough memory has been allocated since the last step.
+/// The same root should be used for every step of a cycle.
 pub fn gc_collect(root: &impl GcTrace) {
-    GC_STATE.with(|gc| {
+    let finalize = with_state(|gc| {
         let mut gc = gc.borrow_mut();
         if gc.should_collect() {
-            gc.collect_garbage(root)
+            let start = Instant::now();
+            gc.collect_step(root);
+            gc.record_pause(start.elapsed());
         }
-    })
+        gc.take_finalize_queue()
+    });
+    run_finalizers(finalize);
 }
 
+/// Finishes any cycle in progress and then performs a complete collection.
 pub fn gc_force(root: &impl GcTrace) {
-    GC_STATE.with(|gc| {
+    let finalize = with_state(|gc| {
         let mut gc = gc.borrow_mut();
-        gc.collect_garbage(root)
-    })
+        let start = Instant::now();
+        
+        // a cycle that is already underway won't free anything that became garbage after it started
+        if gc_phase() != GcPhase::Idle {
+            gc.finish_cycle(root);
+        }
+        gc.finish_cycle(root);
+        gc.record_pause(start.elapsed());
+        gc.take_finalize_queue()
+    });
+    run_finalizers(finalize);
+}
+
+// finalizers are run outside of the GcState borrow so that they are free to allocate
+fn run_finalizers(finalize: Vec<Finalizer>) {
+    for (gcbox, finalizer) in finalize.into_iter() {
+        finalizer(gcbox)
+    }
+}
+
+/// Must be called with any GC reference that is about to be overwritten or removed from GC'd data.
+/// This ensures that the referent is still marked if it was reachable when the current cycle began.
+#[inline]
+pub fn gc_barrier(value: &impl GcTrace) {
+    if gc_phase() == GcPhase::Mark {
+        value.trace()
+    }
+}
+
+/// Writes a report of every allocation that is reachable from the root, along with the allocations it references.
+/// Unlike a collection, this does not free anything and can be done in the middle of a cycle.
+pub fn gc_dump_heap(root: &impl GcTrace, out: &mut impl io::Write) -> io::Result<()> {
+    let graph = with_state(|gc| gc.borrow().inspect(root));
+    graph.write_report(out)
+}
+
+/// The number of bytes currently held by GC allocations in the current heap, including garbage that has not been collected yet
+pub fn gc_allocated() -> usize {
+    with_state(|gc| gc.borrow().stats.allocated)
+}
+
+/// A snapshot of the garbage collector's statistics for the current heap
+pub fn gc_stats() -> GcStats {
+    with_state(|gc| gc.borrow().stats.clone())
+}
+
+/// The percentage of the memory in use after a cycle that must be reached before the next cycle begins
+pub fn gc_pause_factor() -> u16 {
+    with_state(|gc| gc.borrow().config.pause_factor)
+}
+
+/// Sets the pause factor. Values of 100 or less will start a new cycle as soon as the previous one ends.
+/// The new value is used to compute the threshold when the current or next cycle is finished.
+pub fn gc_set_pause_factor(percent: u16) {
+    with_state(|gc| gc.borrow_mut().config.pause_factor = percent)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum GcPhase {
+    #[default]
+    Idle,
+    Mark,
+    Sweep,
+}
+
+#[inline]
+fn gc_phase() -> GcPhase {
+    with_heap(|heap| heap.phase.get())
+}
+
+#[inline]
+fn gc_epoch() -> bool {
+    with_heap(|heap| heap.epoch.get())
 }
 
+fn push_gray(gcbox: GcBoxPtr) {
+    with_heap(|heap| heap.gray.borrow_mut().push(gcbox))
+}
+
+fn pop_gray() -> Option<GcBoxPtr> {
+    with_heap(|heap| heap.gray.borrow_mut().pop())
+}
+
+#[inline]
+fn set_gc_phase(phase: GcPhase) {
+    with_heap(|heap| heap.phase.set(phase))
+}
+
+type Finalizer = (GcBoxPtr, Box<dyn Fn(GcBoxPtr)>);
+
 struct GcState {
     stats: GcStats,
     config: GcConfig,
-    threshold: usize,
+    debt: usize, // bytes allocated since the last incremental step
     boxes_start: Option<GcBoxPtr>,
+    sweep_start: Option<GcBoxPtr>, // allocations that have yet to be swept in the current cycle
+    finalizers: Vec<Finalizer>,
+    finalize_queue: Vec<Finalizer>, // unreachable allocations whose finalizers have not been run yet
 }
 
-#[derive(Debug)]
-struct GcStats {
-    allocated: usize,
-    box_count: usize,
-    cycle_count: usize,
+#[derive(Debug, Clone)]
+pub struct GcStats {
+    pub allocated: usize,           // bytes currently held, including uncollected garbage
+    pub box_count: usize,           // number of allocations currently held
+    pub cycle_count: usize,         // number of completed cycles
+    pub total_allocated: usize,     // bytes allocated since the thread started
+    pub total_freed: usize,         // bytes freed since the thread started
+    pub threshold: usize,           // the next cycle will begin once this many bytes are held
+    pub last_pause: Duration,       // time spent in the most recent collection step
+    pub max_pause: Duration,
+    pub total_pause: Duration,
 }
 
 struct GcConfig {
     threshold: u16,
     pause_factor: u16,  // percent memory use relative to last cycle before starting a new cycle
+    step_size: u16,     // bytes allocated between incremental steps
+    step_factor: u16,   // percent of work done in each step relative to the bytes allocated since the last step
 }
 
 impl Default for GcConfig {
     fn default() -> Self {
         Self {
-            threshold: 512, // Small because of stop-the-world. If we go incremental increase this to 8 kiB
+            threshold: 8192,
             pause_factor: 160,
+            step_size: 1024,
+            step_factor: 200,
         }
     }
 }
@@ -79,21 +306,33 @@ impl GcState {
         
         Self {
             config,
-            threshold,
+            debt: 0,
             
             stats: GcStats {
                 allocated: 0,
                 box_count: 0,
                 cycle_count: 0,
+                total_allocated: 0,
+                total_freed: 0,
+                threshold,
+                last_pause: Duration::ZERO,
+                max_pause: Duration::ZERO,
+                total_pause: Duration::ZERO,
             },
             
             boxes_start: None,
+            sweep_start: None,
+            finalizers: Vec::new(),
+            finalize_queue: Vec::new(),
         }
     }
     
     #[inline]
     fn should_collect(&self) -> bool {
-        self.stats.allocated > self.threshold
+        match gc_phase() {
+            GcPhase::Idle => self.stats.allocated > self.stats.threshold,
+            _ => self.debt >= self.config.step_size as usize,
+        }
     }
     
     fn insert<T>(&mut self, mut gcbox: NonNull<GcBox<T>>) where T: GcTrace + ?Sized {
@@ -101,69 +340,189 @@ impl GcState {
             let size = gcbox.as_ref().header().size();
             log::debug!("{:#X} allocate {} bytes", gcbox.as_ptr() as *const () as usize, size);
             
-            gcbox.as_mut().header_mut().set_next(self.boxes_start.take());
+            // allocations made during a cycle survive it
+            let header = gcbox.as_mut().header_mut();
+            header.set_marked(gc_phase() != GcPhase::Idle);
+            header.set_next(self.boxes_start.take());
+            
             self.boxes_start = Some(gcbox.into());
             self.stats.allocated += size;
+            self.stats.total_allocated = self.stats.total_allocated.saturating_add(size);
             self.stats.box_count += 1;
+            self.debt += size;
+        }
+    }
+    
+    fn record_pause(&mut self, pause: Duration) {
+        self.stats.last_pause = pause;
+        self.stats.max_pause = self.stats.max_pause.max(pause);
+        self.stats.total_pause += pause;
+    }
+    
+    fn register_finalizer(&mut self, gcbox: GcBoxPtr, finalizer: Box<dyn Fn(GcBoxPtr)>) {
+        self.finalizers.push((gcbox, finalizer))
+    }
+    
+    // finalizers are deferred until the cycle is finished, so that they never see a partially swept heap
+    fn take_finalize_queue(&mut self) -> Vec<Finalizer> {
+        if gc_phase() != GcPhase::Idle {
+            return Vec::new();
         }
+        core::mem::take(&mut self.finalize_queue)
     }
     
     /// frees the GcBox, yielding it's next pointer
     fn free(&mut self, gcbox: GcBoxPtr) -> Option<GcBoxPtr> {
         let size = unsafe { gcbox.header().size() };
         self.stats.allocated -= size;
+        self.stats.total_freed = self.stats.total_freed.saturating_add(size);
         self.stats.box_count -= 1;
         log::debug!("{:#X} free {} bytes", gcbox.as_ptr() as *const () as usize, size);
         
         unsafe { gcbox.free() }
     }
     
-    fn collect_garbage(&mut self, root: &impl GcTrace) {
-        log::debug!("GC cycle begin ---");
+    fn collect_step(&mut self, root: &impl GcTrace) {
+        if gc_phase() == GcPhase::Idle {
+            self.begin_cycle(root);
+        }
         
-        let allocated = self.stats.allocated;
-        let box_count = self.stats.box_count;
+        let mut work = (self.debt * self.config.step_factor as usize) / 100;
+        work = work.max(self.config.step_size as usize);
+        self.debt = 0;
+        
+        if gc_phase() == GcPhase::Mark {
+            work = work.saturating_sub(self.mark(work));
+            if work > 0 {
+                self.begin_sweep(root);
+            }
+        }
+        
+        if gc_phase() == GcPhase::Sweep {
+            unsafe { self.sweep(work); }
+        }
+    }
+    
+    /// runs the current cycle to completion, starting a new one if idle
+    fn finish_cycle(&mut self, root: &impl GcTrace) {
+        if gc_phase() == GcPhase::Idle {
+            self.begin_cycle(root);
+        }
+        if gc_phase() == GcPhase::Mark {
+            self.mark(usize::MAX);
+            self.begin_sweep(root);
+        }
+        unsafe { self.sweep(usize::MAX); }
+        self.debt = 0;
+    }
+    
+    fn begin_cycle(&mut self, root: &impl GcTrace) {
+        log::debug!("GC cycle begin ---");
         log::debug!("{}", self.stats);
         
-        // mark
+        set_gc_phase(GcPhase::Mark);
         root.trace();
         
-        // sweep
-        unsafe { self.sweep(); }
-        self.stats.cycle_count = self.stats.cycle_count.wrapping_add(1);
+        // finalizers that are still waiting to run are also roots
+        for (gcbox, _) in self.finalize_queue.iter() {
+            Self::mark_box(*gcbox);
+        }
+    }
+    
+    fn mark_box(mut gcbox: GcBoxPtr) {
+        unsafe {
+            if !gcbox.header().is_marked() {
+                gcbox.header_mut().set_marked(true);
+                push_gray(gcbox);
+            }
+        }
+    }
         
-        let freed = allocated - self.stats.allocated;
-        let dropped = box_count - self.stats.box_count;
-        log::debug!("Freed {} bytes ({} allocations)", freed, dropped);
-        log::debug!("{}", self.stats);
+    /// traces gray allocations until the worklist is empty or the given amount of work is done, returning the work done
+    fn mark(&mut self, work: usize) -> usize {
+        let mut done = 0;
+        while done < work {
+            let gcbox = match pop_gray() {
+                Some(gcbox) => gcbox,
+                None => break,
+            };
+            
+            unsafe {
+                done += gcbox.header().size();
+                gcbox.trace();
+            }
+        }
+        done
+    }
+    
+    fn begin_sweep(&mut self, root: &impl GcTrace) {
+        // the root may have changed since the cycle began, so trace it again before finishing marking
+        root.trace();
+        self.mark(usize::MAX);
         
-        self.threshold = (self.stats.allocated * self.config.pause_factor as usize) / 100;
-        log::debug!("Next collection at {} bytes", self.threshold);
+        // unreachable allocations that have finalizers are kept alive until their finalizer has run,
+        // along with everything they reference
+        let (unreachable, reachable) = self.finalizers.drain(..)
+            .partition::<Vec<_>, _>(|(gcbox, _)| unsafe { !gcbox.header().is_marked() });
         
-        log::debug!("GC cycle end ---");
+        self.finalizers = reachable;
+        for (gcbox, _) in unreachable.iter() {
+            Self::mark_box(*gcbox);
+        }
+        self.finalize_queue.extend(unreachable);
+        self.mark(usize::MAX);
+        
+        set_gc_phase(GcPhase::Sweep);
+        self.sweep_start = self.boxes_start.take();
     }
     
-    unsafe fn sweep(&mut self) {
+    /// frees unmarked allocations until the sweep list is exhausted or the given amount of work is done
+    unsafe fn sweep(&mut self, work: usize) {
         let _guard = DropGuard::new();
         
-        let mut prev_box = None;
-        let mut next_box = self.boxes_start;
-        while let Some(mut gcbox) = next_box {
+        let mut done = 0;
+        while done < work {
+            let mut gcbox = match self.sweep_start {
+                Some(gcbox) => gcbox,
+                None => break,
+            };
+            
+            done += gcbox.header().size();
             if gcbox.header().is_marked() {
-                gcbox.header_mut().set_marked(false);
-                
-                next_box = gcbox.header().next();
-                prev_box.replace(gcbox);
-                
+                self.sweep_start = gcbox.header().next();
+                gcbox.header_mut().set_next(self.boxes_start.take());
+                self.boxes_start = Some(gcbox);
             } else {
-                
+                self.sweep_start = self.free(gcbox);
+            }
+        }
+        
+        if self.sweep_start.is_none() {
+            self.end_cycle();
+        }
+    }
+    
+    fn end_cycle(&mut self) {
+        // everything that survived is now unmarked for the next cycle
+        with_heap(|heap| heap.epoch.set(!heap.epoch.get()));
+        set_gc_phase(GcPhase::Idle);
+        
+        self.stats.cycle_count = self.stats.cycle_count.wrapping_add(1);
+        log::debug!("{}", self.stats);
+        
+        self.stats.threshold = (self.stats.allocated * self.config.pause_factor as usize) / 100;
+        log::debug!("Next collection at {} bytes", self.stats.threshold);
+        
+        log::debug!("GC cycle end ---");
+    }
+    
+    unsafe fn free_all(&mut self) {
+        let _guard = DropGuard::new();
+        
+        for list in [self.sweep_start.take(), self.boxes_start.take()] {
+            let mut next_box = list;
+            while let Some(gcbox) = next_box {
                 next_box = self.free(gcbox);
-                if let Some(mut prev_box) = prev_box {
-                    prev_box.header_mut().set_next(next_box);
-                } else {
-                    self.boxes_start = next_box;
-                }
-                
             }
         }
     }
@@ -171,13 +530,13 @@ impl GcState {
 
 impl Drop for GcState {
     fn drop(&mut self) {
-        unsafe { self.sweep() }
+        unsafe { self.free_all() }
     }
 }
 
 
 // Whether or not the thread is currently in the sweep phase of garbage collection.
-thread_local!(pub static GC_SWEEP: Cell<bool> = Cell::new(false));
+thread_local!(pub static GC_SWEEP: Cell<bool> = const { Cell::new(false) });
 
 struct DropGuard;
 
@@ -207,3 +566,263 @@ impl fmt::Display for GcStats {
         )
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    struct Node {
+        next: RefCell<Option<Gc<Node>>>,
+    }
+    
+    impl Node {
+        fn new(next: Option<Gc<Node>>) -> Gc<Node> {
+            Gc::new(Node { next: RefCell::new(next) })
+        }
+    }
+    
+    unsafe impl GcTrace for Node {
+        fn trace(&self) {
+            if let Some(next) = *self.next.borrow() {
+                next.mark_trace()
+            }
+        }
+    }
+    
+    unsafe impl GcTrace for Gc<Node> {
+        fn trace(&self) {
+            self.mark_trace()
+        }
+    }
+    
+    struct Roots(Vec<Gc<Node>>, Vec<GcWeak<Node>>);
+    
+    unsafe impl GcTrace for Roots {
+        fn trace(&self) {
+            for weak in self.1.iter() {
+                weak.mark_trace()
+            }
+            for node in self.0.iter() {
+                node.mark_trace()
+            }
+        }
+    }
+    
+    fn with_gc(f: impl FnOnce(&mut GcState)) {
+        with_state(|gc| f(&mut gc.borrow_mut()))
+    }
+    
+    #[test]
+    fn test_barrier_preserves_snapshot() {
+        let target = Node::new(None);
+        let weak = target.weakref();
+        
+        let src = Node::new(Some(target));
+        let dst = Node::new(None);
+        let roots = Roots(vec![ Node::new(Some(src)), dst ], vec![ weak ]);
+        
+        // the gray worklist is LIFO, so this traces dst before the target is moved out of src
+        with_gc(|gc| {
+            gc.begin_cycle(&roots);
+            gc.mark(1);
+        });
+        
+        let moved = src.next.take();
+        gc_barrier(&moved);
+        dst.next.replace(moved);
+        
+        with_gc(|gc| gc.finish_cycle(&roots));
+        
+        assert!(weak.is_valid());
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_allocations_during_cycle_survive() {
+        let mut roots = Roots(vec![ Node::new(None) ], Vec::new());
+        
+        with_gc(|gc| gc.begin_cycle(&roots));
+        
+        let weak = Node::new(None).weakref();
+        roots.1.push(weak);
+        
+        with_gc(|gc| gc.finish_cycle(&roots));
+        assert!(weak.is_valid());
+        
+        gc_force(&roots);
+        assert!(!weak.is_valid());
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_weak_ref_unswept_garbage() {
+        let weak = Node::new(None).weakref();
+        weak.mark_trace();
+        
+        with_gc(|gc| {
+            gc.begin_cycle(&0);
+            gc.mark(usize::MAX);
+            gc.begin_sweep(&0);
+        });
+        
+        // the referent has not been freed yet but is no longer reachable
+        assert_eq!(gc_phase(), GcPhase::Sweep);
+        assert!(!weak.is_valid());
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    thread_local! {
+        static FINALIZED: Cell<usize> = const { Cell::new(0) };
+    }
+    
+    fn count_finalized(node: Gc<Node>) {
+        // everything the node references must still be valid
+        assert!(node.next.borrow().is_some_and(|next| next.next.borrow().is_none()));
+        FINALIZED.with(|count| count.set(count.get() + 1));
+    }
+    
+    #[test]
+    fn test_finalizer_runs_once() {
+        let node = Node::new(Some(Node::new(None)));
+        node.set_finalizer(count_finalized);
+        let roots = Roots(Vec::new(), vec![ node.weakref() ]);
+        
+        gc_force(&roots);
+        assert_eq!(FINALIZED.with(Cell::get), 1);
+        assert!(roots.1[0].is_valid());
+        
+        gc_force(&roots);
+        assert_eq!(FINALIZED.with(Cell::get), 1);
+        assert!(!roots.1[0].is_valid());
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_finalizer_deferred_until_cycle_ends() {
+        let node = Node::new(Some(Node::new(None)));
+        node.set_finalizer(count_finalized);
+        
+        let roots = Roots(Vec::new(), Vec::new());
+        while FINALIZED.with(Cell::get) == 0 {
+            gc_collect(&roots);
+            
+            // the finalizer must not run while the cycle that found it is still sweeping
+            assert!(gc_phase() != GcPhase::Sweep || FINALIZED.with(Cell::get) == 0);
+            with_gc(|gc| gc.debt = usize::from(gc.config.step_size));
+            let _ = Node::new(None);
+        }
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_incremental_steps_complete_cycle() {
+        let garbage = (0..100).map(|_| Node::new(None).weakref()).collect::<Vec<_>>();
+        let roots = Roots(vec![ Node::new(None) ], garbage.clone());
+        
+        let cycle_count = with_state(|gc| gc.borrow().stats.cycle_count);
+        while with_state(|gc| gc.borrow().stats.cycle_count) == cycle_count {
+            with_gc(|gc| gc.collect_step(&roots));
+        }
+        
+        assert!(garbage.iter().all(|weak| !weak.is_valid()));
+        assert_eq!(gc_phase(), GcPhase::Idle);
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_stats_track_freed_bytes() {
+        let roots = Roots(vec![ Node::new(None) ], Vec::new());
+        gc_force(&roots);
+        
+        let before = gc_stats();
+        for _ in 0..10 {
+            let _ = Node::new(None);
+        }
+        gc_force(&roots);
+        let after = gc_stats();
+        
+        let size = before.allocated / before.box_count;
+        assert_eq!(after.total_allocated - before.total_allocated, 10 * size);
+        assert_eq!(after.total_freed - before.total_freed, 10 * size);
+        assert_eq!(after.allocated, before.allocated);
+        assert!(after.cycle_count > before.cycle_count);
+        assert!(after.max_pause >= after.last_pause);
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_heaps_are_isolated() {
+        let roots = Roots(vec![ Node::new(None) ], Vec::new());
+        let default_stats = gc_stats();
+        
+        let heap = GcHeap::new();
+        unsafe {
+            heap.enter(|| {
+                let _ = Node::new(None);
+                assert_eq!(gc_stats().box_count, 1);
+            
+                // collecting in another heap doesn't see the roots of the default heap
+                gc_force(&0);
+                assert_eq!(gc_stats().box_count, 0);
+            });
+        }
+        
+        assert_eq!(gc_stats().box_count, default_stats.box_count);
+        gc_force(&roots);
+        assert_eq!(gc_stats().box_count, 1);
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_heap_frees_on_drop() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        
+        struct Tracked(u8);
+        
+        unsafe impl GcTrace for Tracked {
+            fn trace(&self) { }
+        }
+        
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        
+        let heap = GcHeap::new();
+        unsafe { heap.enter(|| { let _ = Gc::new(Tracked(0)); }); }
+        
+        // the heap can be moved to another thread while it is not entered
+        let heap = std::thread::spawn(move || {
+            unsafe { heap.enter(|| assert_eq!(gc_stats().box_count, 1)); }
+            heap
+        }).join().unwrap();
+        
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 0);
+        drop(heap);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "handle used outside of the heap it was allocated from")]
+    fn test_handle_from_other_heap_is_rejected() {
+        let heap = GcHeap::new();
+        
+        // breaks the contract of enter() on purpose, while the heap is still alive to catch it
+        let mut escaped = None;
+        unsafe { heap.enter(|| escaped = Some(Node::new(None))); }
+        
+        let _ = escaped.unwrap().next.borrow().is_some();
+    }
+}
@@ -0,0 +1,255 @@
+use std::io::{self, Write};
+use crate::runtime::{HashMap, HashSet};
+use crate::runtime::gc::{GcState, GcPhase, with_heap, pop_gray};
+use crate::runtime::gc::gcbox::GcBoxPtr;
+use crate::runtime::gc::trace::GcTrace;
+
+
+/// A snapshot of every allocation held by the GC and the allocations that each one references
+pub(super) struct HeapGraph {
+    roots: Vec<usize>,
+    nodes: Vec<HeapNode>,
+}
+
+struct HeapNode {
+    address: usize,
+    type_name: String,
+    size: usize,
+    referents: Vec<usize>,
+}
+
+fn address(gcbox: &GcBoxPtr) -> usize {
+    gcbox.as_ptr() as *const () as usize
+}
+
+impl GcState {
+    pub(super) fn inspect(&self, root: &impl GcTrace) -> HeapGraph {
+        // inspection borrows the mark flags and the gray worklist, so any cycle in progress must not see it
+        let (phase, gray) = with_heap(|heap| (heap.phase.replace(GcPhase::Idle), heap.gray.take()));
+        
+        let mut boxes = Vec::new();
+        for list in [self.sweep_start, self.boxes_start] {
+            let mut next_box = list;
+            while let Some(gcbox) = next_box {
+                boxes.push(gcbox);
+                next_box = unsafe { gcbox.header().next() };
+            }
+        }
+        
+        let marked = boxes.iter_mut()
+            .map(|gcbox| unsafe {
+                let marked = gcbox.header().is_marked();
+                gcbox.header_mut().set_marked(false);
+                marked
+            })
+            .collect::<Vec<bool>>();
+        
+        let roots = trace_referents(|| root.trace());
+        
+        let nodes = boxes.iter().zip(marked.iter())
+            .map(|(gcbox, marked)| unsafe {
+                let header = gcbox.header();
+                
+                // unmarked allocations waiting to be swept may reference allocations that were already freed
+                let referents =
+                    if phase == GcPhase::Sweep && !marked { Vec::new() }
+                    else { trace_referents(|| gcbox.trace()) };
+                
+                HeapNode {
+                    address: address(gcbox),
+                    type_name: short_type_name(header.type_name()),
+                    size: header.size(),
+                    referents,
+                }
+            })
+            .collect::<Vec<HeapNode>>();
+        
+        for (gcbox, marked) in boxes.iter_mut().zip(marked) {
+            unsafe { gcbox.header_mut().set_marked(marked) }
+        }
+        with_heap(|heap| {
+            heap.gray.replace(gray);
+            heap.phase.set(phase);
+        });
+        
+        HeapGraph { roots, nodes }
+    }
+}
+
+// collects the allocations that are marked by the trace, then unmarks them again
+fn trace_referents(trace: impl FnOnce()) -> Vec<usize> {
+    trace();
+    
+    let mut referents = Vec::new();
+    while let Some(mut gcbox) = pop_gray() {
+        unsafe { gcbox.header_mut().set_marked(false) }
+        referents.push(address(&gcbox));
+    }
+    referents
+}
+
+// strips module paths, e.g. "core::cell::Cell<sphinx::runtime::variant::Variant>" becomes "Cell<Variant>"
+fn short_type_name(type_name: &str) -> String {
+    let mut result = String::with_capacity(type_name.len());
+    let mut segment_start = 0;
+    let mut chars = type_name.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            result.truncate(segment_start);
+            continue;
+        }
+        
+        if !(ch.is_alphanumeric() || ch == '_') {
+            segment_start = result.len() + ch.len_utf8();
+        }
+        result.push(ch);
+    }
+    result
+}
+
+impl HeapGraph {
+    fn reachable(&self) -> HashSet<usize> {
+        let index = self.nodes.iter()
+            .map(|node| (node.address, node))
+            .collect::<HashMap<usize, &HeapNode>>();
+        
+        let mut reachable = HashSet::default();
+        let mut worklist = self.roots.clone();
+        while let Some(address) = worklist.pop() {
+            if reachable.insert(address) {
+                if let Some(node) = index.get(&address) {
+                    worklist.extend(node.referents.iter().copied());
+                }
+            }
+        }
+        reachable
+    }
+    
+    /// Writes a summary of memory use by type, followed by every allocation that is reachable from the root
+    pub(super) fn write_report(&self, out: &mut impl Write) -> io::Result<()> {
+        let reachable = self.reachable();
+        let (live, garbage): (Vec<&HeapNode>, Vec<&HeapNode>) = self.nodes.iter()
+            .partition(|node| reachable.contains(&node.address));
+        
+        let live_size = live.iter().map(|node| node.size).sum::<usize>();
+        let garbage_size = garbage.iter().map(|node| node.size).sum::<usize>();
+        writeln!(out, "Heap dump: {} reachable allocations ({} bytes), {} unreachable ({} bytes)",
+            live.len(), live_size, garbage.len(), garbage_size)?;
+        
+        let mut by_type = HashMap::<&str, (usize, usize)>::default();
+        for node in live.iter() {
+            let (count, size) = by_type.entry(node.type_name.as_str()).or_default();
+            *count += 1;
+            *size += node.size;
+        }
+        
+        let mut by_type = by_type.into_iter().collect::<Vec<_>>();
+        by_type.sort_by(|(a_name, (_, a_size)), (b_name, (_, b_size))| b_size.cmp(a_size).then(a_name.cmp(b_name)));
+        
+        writeln!(out)?;
+        writeln!(out, "{:>8} {:>10}  type", "count", "bytes")?;
+        for (type_name, (count, size)) in by_type.iter() {
+            writeln!(out, "{:>8} {:>10}  {}", count, size, type_name)?;
+        }
+        
+        writeln!(out)?;
+        write!(out, "roots:")?;
+        write_addresses(out, &self.roots)?;
+        for node in live.iter() {
+            write!(out, "{:#X} {} ({} bytes)", node.address, node.type_name, node.size)?;
+            if !node.referents.is_empty() {
+                write!(out, " ->")?;
+            }
+            write_addresses(out, &node.referents)?;
+        }
+        
+        Ok(())
+    }
+}
+
+fn write_addresses(out: &mut impl Write, addresses: &[usize]) -> io::Result<()> {
+    for address in addresses.iter() {
+        write!(out, " {:#X}", address)?;
+    }
+    writeln!(out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use crate::runtime::gc::{with_state, Gc, gc_force, gc_stats, gc_dump_heap};
+    use super::*;
+    
+    struct Link(RefCell<Option<Gc<Link>>>);
+    
+    unsafe impl GcTrace for Link {
+        fn trace(&self) {
+            if let Some(next) = *self.0.borrow() {
+                next.mark_trace()
+            }
+        }
+    }
+    
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!(short_type_name("core::cell::Cell<sphinx::runtime::variant::Variant>"), "Cell<Variant>");
+        assert_eq!(short_type_name("dyn sphinx::runtime::types::misc::UserData"), "dyn UserData");
+        assert_eq!(short_type_name("[sphinx::runtime::variant::Variant]"), "[Variant]");
+    }
+    
+    #[test]
+    fn test_heap_dump_lists_referents() {
+        let tail = Gc::new(Link(RefCell::new(None)));
+        let head = Gc::new(Link(RefCell::new(Some(tail))));
+        let garbage = Gc::new(Link(RefCell::new(None)));
+        let root = Link(RefCell::new(Some(head)));
+        
+        let mut report = Vec::new();
+        gc_dump_heap(&root, &mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        
+        let head_id = Gc::as_id(&head);
+        let tail_id = Gc::as_id(&tail);
+        assert!(report.contains("2 reachable allocations"));
+        assert!(report.contains(&format!("roots: {:#X}\n", head_id)));
+        assert!(report.contains(&format!("{:#X} Link ({} bytes) -> {:#X}\n", head_id, gc_stats().allocated / 3, tail_id)));
+        assert!(!report.contains(&format!("{:#X} Link", Gc::as_id(&garbage))));
+        
+        // inspection must not disturb collection
+        gc_force(&root);
+        assert_eq!(gc_stats().box_count, 2);
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_heap_dump_during_sweep() {
+        let root = Link(RefCell::new(Some(Gc::new(Link(RefCell::new(None))))));
+        
+        // the sweep list is newest first, so the referent is freed before the referrer
+        let referrer = Gc::new(Link(RefCell::new(None)));
+        let referent = Gc::new(Link(RefCell::new(None)));
+        referrer.0.replace(Some(referent));
+        
+        with_state(|gc| {
+            let mut gc = gc.borrow_mut();
+            gc.begin_cycle(&root);
+            gc.mark(usize::MAX);
+            gc.begin_sweep(&root);
+            unsafe { gc.sweep(1); }
+        });
+        assert_eq!(gc_stats().box_count, 2);
+        
+        let mut report = Vec::new();
+        gc_dump_heap(&root, &mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("1 reachable allocations"));
+        
+        with_state(|gc| gc.borrow_mut().finish_cycle(&root));
+        assert_eq!(gc_stats().box_count, 1);
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+}
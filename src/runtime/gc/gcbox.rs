@@ -5,6 +5,7 @@ use core::ptr::{self, NonNull, Pointee};
 use std::alloc::{self, alloc, dealloc};
 use log;
 
+use crate::runtime::gc::{gc_epoch, push_gray, current_heap_id};
 use crate::runtime::gc::trace::GcTrace;
 use crate::runtime::gc::ptrmeta::PtrMetadata;
 
@@ -21,9 +22,11 @@ use crate::runtime::gc::ptrmeta::PtrMetadata;
 /// In practice this means:
 ///
 /// - Reading and mutating the "marked" flag.
+/// - Tracing the data (for allocations taken off the gray worklist).
 /// - Freeing the allocation (including running destructors).
 /// - Getting the "next" allocation (for use as an intrusive list).
 /// - Getting the DST metadata (to support the [`Gc<T>`] thin pointer representation).
+/// - Getting the name of the data's type (for heap dumps).
 ///
 #[derive(Debug, Clone, Copy, Hash)]
 pub(super) struct GcBoxPtr {
@@ -66,6 +69,18 @@ impl GcBoxPtr {
         );
         unsafe { NonNull::new_unchecked(ptr) }
     }
+    
+    #[inline]
+    pub(super) unsafe fn trace(&self) {
+        (self.header().tracer)(*self)
+    }
+}
+
+unsafe fn trace_gcbox<T>(ptr: GcBoxPtr) where 
+    T: GcTrace + ?Sized + 'static,
+    PtrMetadata: TryInto<<GcBox<T> as Pointee>::Metadata>
+{
+    ptr.to_gcbox_ptr::<T>().as_ref().value().trace()
 }
 
 
@@ -77,21 +92,27 @@ pub(super) trait WeakCell: GcTrace {
 pub(super) struct GcBoxHeader {
     next: Option<GcBoxPtr>,
     marked: bool,
+    heap: usize,
     size: usize,
     layout: Layout,
     metadata: PtrMetadata,
     weak: Option<NonNull<GcBox<dyn WeakCell>>>,
+    tracer: unsafe fn(GcBoxPtr),
+    type_name: &'static str,
     destructor: Option<Box<dyn Fn(GcBoxPtr)>>,
 }
 
 impl GcBoxHeader {
-    fn new(size: usize, layout: Layout, metadata: PtrMetadata, destructor: Box<dyn Fn(GcBoxPtr)>) -> Self {
+    fn new(size: usize, layout: Layout, metadata: PtrMetadata, tracer: unsafe fn(GcBoxPtr), type_name: &'static str, destructor: Box<dyn Fn(GcBoxPtr)>) -> Self {
         Self {
             next: None,
-            marked: false,
+            marked: !gc_epoch(),
+            heap: current_heap_id(),
             size, layout,
             metadata,
             weak: None,
+            tracer,
+            type_name,
             destructor: Some(destructor),
         }
     }
@@ -106,6 +127,12 @@ impl GcBoxHeader {
         self.next = next
     }
     
+    /// The id of the heap that the allocation belongs to
+    #[inline]
+    pub(super) fn heap(&self) -> usize {
+        self.heap
+    }
+    
     #[inline]
     pub(super) fn size(&self) -> usize {
         self.size
@@ -116,14 +143,20 @@ impl GcBoxHeader {
         self.metadata
     }
     
+    #[inline]
+    pub(super) fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+    
+    // the meaning of the mark flag is flipped after every GC cycle, see `GC_EPOCH`
     #[inline]
     pub(super) fn is_marked(&self) -> bool {
-        self.marked
+        self.marked == gc_epoch()
     }
     
     #[inline]
     pub(super) fn set_marked(&mut self, marked: bool) {
-        self.marked = marked
+        self.marked = marked == gc_epoch()
     }
     
     #[inline]
@@ -156,6 +189,7 @@ impl fmt::Debug for GcBoxHeader {
         fmt.debug_struct("GcBoxHeader")
             .field("next", &self.next)
             .field("size", &self.size)
+            .field("type_name", &self.type_name)
             .field("layout", &self.layout)
             .field("destructor", &self.destructor.as_ref()
                 .map(|destfn| ptr::addr_of!(*destfn)))
@@ -181,11 +215,12 @@ impl<T> GcBox<T> where T: GcTrace + ?Sized {
     #[inline]
     pub(super) fn value(&self) -> &T { &self.data }
     
+    /// Marks the allocation and schedules its contents to be traced later
     #[inline]
     pub(super) fn mark_trace(&mut self) {
         if !self.header.is_marked() {
             self.header.set_marked(true);
-            self.data.trace();
+            push_gray(NonNull::from(&*self).into());
         }
     }
 }
@@ -196,6 +231,7 @@ impl<T> GcBox<T> where T: GcTrace + ?Sized {
 impl<T> GcBox<T> where 
     T: GcTrace + Pointee,
     T::Metadata: Into<PtrMetadata>,
+    PtrMetadata: TryInto<<GcBox<T> as Pointee>::Metadata>,
 {
     pub(super) fn new(data: T) -> NonNull<GcBox<T>> {
         if mem::size_of::<T>() == 0 {
@@ -214,6 +250,8 @@ impl<T> GcBox<T> where
             size,
             layout,
             ptr_meta.into(),
+            trace_gcbox::<T>,
+            core::any::type_name::<T>(),
             Box::new(destructor)
         );
         
@@ -226,7 +264,8 @@ impl<T> GcBox<T> where
 impl<T> GcBox<T> where 
     T: GcTrace + ?Sized + Pointee + 'static,
     T::Metadata: Into<PtrMetadata>,
-    GcBox<T>: Pointee<Metadata = T::Metadata> 
+    GcBox<T>: Pointee<Metadata = T::Metadata>,
+    PtrMetadata: TryInto<<GcBox<T> as Pointee>::Metadata>,
 {
     pub(super) fn from_box(data: Box<T>) -> NonNull<GcBox<T>> {
         let size_hint = data.size_hint();
@@ -271,6 +310,8 @@ impl<T> GcBox<T> where
             layout.size() + size_hint, 
             layout, 
             ptr_meta.into(),
+            trace_gcbox::<T>,
+            core::any::type_name::<T>(),
             Box::new(destructor)
         );
         
@@ -1,11 +1,13 @@
-///! The "public API" for the gc is the `Gc<T>` struct, which is a smart pointer to GCed data.
-///! Data can be "inserted" into the GC using `Gc::new()` for `Sized` types or `Gc::from_box()` for `?Sized` types.
-///! Data is accessed using `Deref`. 
-///! Mutable access is not supported, so mutable data that needs to be GCed must use interior mutability.
-///! As well, all GCed data must `impl GcTrace`.
-///! Weak references to GCed data can be obtained using `Gc::weakref()`.
-///! 
-///! `Gc<T>` supports a "thin pointer" representation and should not be wider than a single `usize`.
+//! The "public API" for the gc is the `Gc<T>` struct, which is a smart pointer to GCed data.
+//! Data can be "inserted" into the GC using `Gc::new()` for `Sized` types or `Gc::from_box()` for `?Sized` types.
+//! Data is accessed using `Deref`. 
+//! Mutable access is not supported, so mutable data that needs to be GCed must use interior mutability.
+//! As well, all GCed data must `impl GcTrace`.
+//! Weak references to GCed data can be obtained using `Gc::weakref()`.
+//! A weak reference does not keep its referent alive, and can be upgraded back into a `Gc<T>` using `GcWeak::upgrade()`
+//! until the referent is collected, at which point the weak reference is cleared.
+//! 
+//! `Gc<T>` supports a "thin pointer" representation and should not be wider than a single `usize`.
 
 use core::fmt;
 use core::ops::Deref;
@@ -15,14 +17,14 @@ use core::hash::{Hash, Hasher};
 use core::marker::PhantomData;
 use std::rc::Rc;
 
-use crate::runtime::gc::{GC_STATE, deref_safe};
+use crate::runtime::gc::{with_state, deref_safe, current_heap_id};
 use crate::runtime::gc::trace::GcTrace;
 use crate::runtime::gc::gcbox::{GcBox, GcBoxPtr};
 use crate::runtime::gc::ptrmeta::PtrMetadata;
 use crate::runtime::gc::weak::GcWeakCell;
 
 
-///! Smart pointer to GCed data. See the module-level documentation for more details.
+/// Smart pointer to GCed data. See the module-level documentation for more details.
 pub struct Gc<T> where T: GcTrace + ?Sized + 'static {
     ptr: GcBoxPtr,
     _marker: PhantomData<Rc<GcBox<T>>>,
@@ -30,7 +32,7 @@ pub struct Gc<T> where T: GcTrace + ?Sized + 'static {
 
 impl<T: GcTrace> Gc<T> {
     pub fn new(data: T) -> Self {
-        GC_STATE.with(|gc| {
+        with_state(|gc| {
             let mut gc = gc.borrow_mut();
             
             let gcbox = GcBox::new(data);
@@ -43,10 +45,11 @@ impl<T: GcTrace> Gc<T> {
 impl<T> Gc<T> where 
     T: GcTrace + ?Sized + Pointee, 
     T::Metadata: Into<PtrMetadata>,
-    GcBox<T>: Pointee<Metadata = T::Metadata> 
+    GcBox<T>: Pointee<Metadata = T::Metadata>,
+    PtrMetadata: TryInto<<GcBox<T> as Pointee>::Metadata>,
 {
     pub fn from_box(data: Box<T>) -> Self {
-        GC_STATE.with(|gc| {
+        with_state(|gc| {
             let mut gc = gc.borrow_mut();
             
             let gcbox = GcBox::from_box(data);
@@ -90,15 +93,22 @@ impl<T> Gc<T> where
     fn inner(&self) -> &GcBox<T> {
         // must not deref during sweep. This should only be possible if called inside a Drop impl
         debug_assert!(deref_safe());
+        debug_assert!(self.in_current_heap(), "handle used outside of the heap it was allocated from");
         unsafe { self.ptr.to_gcbox_ptr().as_ref() }
     }
     
     #[inline]
     fn inner_mut(&mut self) -> &mut GcBox<T> {
         debug_assert!(deref_safe());
+        debug_assert!(self.in_current_heap(), "handle used outside of the heap it was allocated from");
         unsafe { self.ptr.to_gcbox_ptr().as_mut() }
     }
     
+    // this can only catch handles from a heap that still exists, using any others is already UB
+    fn in_current_heap(&self) -> bool {
+        unsafe { self.ptr.header().heap() == current_heap_id() }
+    }
+    
     /// Create a weak reference from this GC handle
     pub fn weakref(&self) -> GcWeak<T> {
         let weak_ptr = GcBox::get_or_make_weak(self.ptr.to_gcbox_ptr());
@@ -108,6 +118,15 @@ impl<T> Gc<T> where
     pub fn mark_trace(mut self) {
         self.inner_mut().mark_trace()
     }
+    
+    /// Register a function to be called once the data has become unreachable.
+    /// The data (and everything it references) is kept alive until the finalizer has run, 
+    /// which happens after the GC cycle that found it unreachable is finished.
+    /// Each finalizer is only called once, after which the data is freed normally unless it was made reachable again.
+    pub fn set_finalizer(&self, finalizer: fn(Gc<T>)) {
+        let finalizer = move |ptr: GcBoxPtr| finalizer(Gc::from_raw(ptr.to_gcbox_ptr()));
+        with_state(|gc| gc.borrow_mut().register_finalizer(self.ptr, Box::new(finalizer)))
+    }
 }
 
 impl<T> From<Gc<T>> for Gc<dyn GcTrace> where T: GcTrace {
@@ -150,12 +169,7 @@ impl<T> Deref for Gc<T> where
 }
 
 impl<T> Clone for Gc<T> where T: GcTrace + ?Sized {
-    fn clone(&self) -> Self {
-        Self {
-            ptr: self.ptr,
-            _marker: PhantomData,
-        }
-    }
+    fn clone(&self) -> Self { *self }
 }
 
 impl<T> Copy for Gc<T> where T: GcTrace + ?Sized { }
@@ -175,6 +189,7 @@ impl<T> fmt::Debug for Gc<T> where T: GcTrace + ?Sized {
 }
 
 
+/// Weak reference to GCed data. See the module-level documentation for more details.
 pub struct GcWeak<T> where T: GcTrace + ?Sized + 'static {
     gc_weak: Gc<GcWeakCell<T>>,
 }
@@ -197,6 +212,11 @@ impl<T> GcWeak<T> where T: GcTrace + ?Sized {
         })
     }
     
+    /// Get a strong handle to the referent, or `None` if it has been collected.
+    pub fn upgrade(&self) -> Option<Gc<T>> {
+        self.gc_weak.get().map(Gc::from_raw)
+    }
+    
     // This marks the allocation for the weak reference - NOT the referent of the weak reference
     pub fn mark_trace(&self) {
         self.gc_weak.mark_trace()
@@ -212,14 +232,16 @@ impl<T> GcWeak<T> where T: GcTrace + ?Sized {
     }
 }
 
-impl<T> Clone for GcWeak<T> where T: GcTrace + ?Sized {
-    fn clone(&self) -> Self {
-        Self {
-            gc_weak: self.gc_weak.clone()
-        }
+unsafe impl<T> GcTrace for GcWeak<T> where T: GcTrace + ?Sized {
+    fn trace(&self) {
+        self.mark_trace()
     }
 }
 
+impl<T> Clone for GcWeak<T> where T: GcTrace + ?Sized {
+    fn clone(&self) -> Self { *self }
+}
+
 impl<T> Copy for GcWeak<T> where T: GcTrace + ?Sized { }
 
 impl<T> fmt::Debug for GcWeak<T> where T: GcTrace + ?Sized {
@@ -283,6 +305,39 @@ mod tests {
         gc_force(&0); //cleanup so miri doesn't complain about leaks
     }
     
+    #[test]
+    fn test_weak_ref_upgrade() {
+        let data = Gc::new(5);
+        let weak = data.weakref();
+        
+        let upgraded = weak.upgrade().unwrap();
+        assert!(Gc::ptr_eq(&data, &upgraded));
+        assert_eq!(*upgraded, 5);
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
+    #[test]
+    fn test_weak_ref_upgrade_keeps_alive() {
+        let weak = Gc::new(6).weakref();
+        
+        let upgraded = weak.upgrade().unwrap();
+        upgraded.mark_trace();
+        weak.mark_trace();
+        
+        gc_force(&0);
+        
+        assert!(weak.is_valid());
+        assert!(matches!(weak.upgrade().as_deref(), Some(6)));
+        
+        weak.mark_trace();
+        gc_force(&0);
+        
+        assert!(weak.upgrade().is_none());
+        
+        gc_force(&0); //cleanup so miri doesn't complain about leaks
+    }
+    
     #[test]
     fn test_weak_ref_reclaimed() {
         let data = Gc::new(4);
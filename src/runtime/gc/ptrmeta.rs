@@ -1,4 +1,4 @@
-///! Support for thin pointers to dynamically sized GC data.
+//! Support for thin pointers to dynamically sized GC data.
 
 use core::ptr::DynMetadata;
 use core::convert::Infallible;
@@ -5,6 +5,10 @@ use core::cell::{Cell, RefCell};
 /// Unsafe because if the `trace()` implementation fails to call `Gc::mark_trace()` 
 /// and `GcWeak::mark_trace()` on all of the `Gc` and `GcWeak` pointers that it can reach,
 /// the GC will free memory that is still in use.
+///
+/// # Safety
+/// If the receiver also impls `Drop`, the `drop()` impl must not deref any `Gc` or `GcWeak` pointers.
+/// Any `Gc` or `GcWeak` pointer that is overwritten or removed from the receiver must first be passed to `gc_barrier()`.
 /// SAFETY: If the receiver also impls `Drop`, the `drop()` impl must not deref any `Gc` or `GcWeak` pointers
 pub unsafe trait GcTrace {
     
@@ -33,6 +37,19 @@ unsafe impl<T> GcTrace for [T] where T: GcTrace {
     }
 }
 
+// Option
+unsafe impl<T> GcTrace for Option<T> where T: GcTrace {
+    fn trace(&self) {
+        if let Some(value) = self {
+            value.trace()
+        }
+    }
+    
+    fn size_hint(&self) -> usize {
+        self.as_ref().map_or(0, GcTrace::size_hint)
+    }
+}
+
 // Cells
 unsafe impl<T> GcTrace for Cell<T> where T: GcTrace + Copy {
     fn trace(&self) {
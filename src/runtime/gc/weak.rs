@@ -1,6 +1,6 @@
 use core::cell::Cell;
 use core::ptr::NonNull;
-use crate::runtime::gc::GC_STATE;
+use crate::runtime::gc::{GcPhase, with_state, gc_phase};
 use crate::runtime::gc::trace::GcTrace;
 use crate::runtime::gc::gcbox::{GcBox, WeakCell};
 
@@ -21,7 +21,17 @@ impl<T> GcWeakCell<T> where T: GcTrace + ?Sized {
     }
     
     pub(super) fn get(&self) -> Option<NonNull<GcBox<T>>> {
-        self.ptr.get()
+        let mut gcbox = self.ptr.get()?;
+        match gc_phase() {
+            // the referent may be unreachable from the snapshot of the current cycle, so it must be marked now
+            GcPhase::Mark => unsafe { gcbox.as_mut() }.mark_trace(),
+            
+            // unmarked allocations are garbage that has not been freed yet
+            GcPhase::Sweep if !unsafe { gcbox.as_ref() }.header().is_marked() => return None,
+            
+            _ => { },
+        }
+        Some(gcbox)
     }
     
     pub(super) fn invalidate(&self) {
@@ -66,7 +76,7 @@ impl<T> GcBox<T> where T: GcTrace + ?Sized {
                 .set_weak(unsafe { Some(NonNull::new_unchecked(dyn_ptr)) });
             
             // insert the new GcBox<GcWeakCell<T>> into GC tracking
-            GC_STATE.with(|gc| gc.borrow_mut().insert(gcbox_weak));
+            with_state(|gc| gc.borrow_mut().insert(gcbox_weak));
             
             gcbox_weak
         }
@@ -1,5 +1,5 @@
 use crate::runtime::Variant;
-use crate::runtime::gc::GcTrace;
+use crate::runtime::gc::{GcTrace, gc_barrier};
 use crate::runtime::errors::{ExecResult};
 
 /*
@@ -77,7 +77,9 @@ impl IterState {
     // go to the next state *in place*
     #[inline]
     pub fn advance(&mut self) -> ExecResult<()> {
-        self.state = self.iter.iter_next(&self.state)?;
+        let next_state = self.iter.iter_next(&self.state)?;
+        gc_barrier(&self.state);
+        self.state = next_state;
         Ok(())
     }
 }
@@ -1,22 +1,31 @@
-///! Modules are the top level environment in Sphinx.
-///! All top-level names live in a module, there are no "universal" global variables.
-///!
-///! They are also the top-level unit of execution. 
-///! All Sphinx programs produce a module as a result of execution (even if it is discarded). 
-///! Importing a Sphinx module simply means executing a Sphinx sub-program and binding the
-///! resulting module to a name.
+//! Modules are the top level environment in Sphinx.
+//! All top-level names live in a module, there are no "universal" global variables.
+//!
+//! They are also the top-level unit of execution. 
+//! All Sphinx programs produce a module as a result of execution (even if it is discarded). 
+//! Importing a Sphinx module simply means executing a Sphinx sub-program and binding the
+//! resulting module's globals into the importing module.
 
 use core::fmt;
 use core::cell::{RefCell, Ref, RefMut};
 use core::hash::{Hash, Hasher, BuildHasher};
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use once_cell::sync::Lazy;
+use crate::utils;
 use crate::source::ModuleSource;
-use crate::language::{FloatType, Access};
+use crate::codegen::{Program, CompiledProgram};
+use crate::language::Access;
 use crate::runtime::{Variant, HashMap, DefaultBuildHasher};
-use crate::runtime::gc::{Gc, GcTrace};
+use crate::runtime::gc::{Gc, GcTrace, gc_barrier};
+use crate::runtime::slots::SlotMap;
 use crate::runtime::strings::StringSymbol;
 use crate::runtime::errors::{ExecResult, RuntimeError};
+#[cfg(feature = "bigint")]
+use crate::runtime::strings::StringValue;
+#[cfg(feature = "bigint")]
+use crate::runtime::types::int_from_str;
 
 pub use crate::codegen::{ProgramData, Constant, Chunk, FunctionProto, ConstID, FunctionID};
 
@@ -25,11 +34,12 @@ pub use crate::codegen::{ProgramData, Constant, Chunk, FunctionProto, ConstID, F
 pub struct Variable {
     access: Access,
     value: Variant,
+    exported: bool,  // visible to modules that import this one
 }
 
 #[derive(Debug, Clone)]
 pub struct Namespace {
-    store: HashMap<StringSymbol, Variable>,
+    store: SlotMap<Variable>,
 }
 
 impl Default for Namespace {
@@ -39,7 +49,7 @@ impl Default for Namespace {
 impl Namespace {
     pub fn new() -> Self {
         Self { 
-            store: HashMap::with_hasher(DefaultBuildHasher::default()),
+            store: SlotMap::new(),
         }
     }
     
@@ -53,36 +63,76 @@ impl Namespace {
     
     // if the variable already exists, it is overwritten
     pub fn create(&mut self, name: StringSymbol, access: Access, value: Variant) {
-        self.store.insert(name, Variable { access, value });
+        let old_variable = self.store.insert(name, Variable { access, value, exported: false });
+        gc_barrier(&old_variable.map(|var| var.value));
+    }
+    
+    pub fn export(&mut self, name: &StringSymbol) -> ExecResult<()> {
+        let variable = match self.store.get_mut(name) {
+            Some(variable) => variable,
+            None => return Err(self.name_not_defined(name)),
+        };
+        
+        variable.exported = true;
+        Ok(())
+    }
+    
+    pub fn is_exported(&self, name: &StringSymbol) -> bool {
+        self.store.get(name).is_some_and(|var| var.exported)
     }
     
     pub fn delete(&mut self, name: &StringSymbol) -> ExecResult<()> {
-        if self.store.remove(name).is_none() {
-            return Err(RuntimeError::name_not_defined(*name))
-        }
+        let variable = self.store.remove(name)
+            .ok_or_else(|| self.name_not_defined(name))?;
+        
+        gc_barrier(&variable.value);
         Ok(())
     }
     
     pub fn lookup<'a>(&'a self, name: &StringSymbol) -> ExecResult<&'a Variant> {
         self.store.get(name)
             .map(|var| &var.value)
-            .ok_or_else(|| RuntimeError::name_not_defined(*name))
+            .ok_or_else(|| self.name_not_defined(name))
+    }
+    
+    /// Look up a variable at a slot, which is used by inline caches. See `SlotMap`.
+    #[inline]
+    pub fn lookup_slot(&self, slot: usize, name: &StringSymbol) -> Option<&Variant> {
+        self.store.get_slot(slot, name).map(|var| &var.value)
+    }
+    
+    pub fn lookup_full<'a>(&'a self, name: &StringSymbol) -> ExecResult<(usize, &'a Variant)> {
+        self.store.get_full(name)
+            .map(|(slot, var)| (slot, &var.value))
+            .ok_or_else(|| self.name_not_defined(name))
     }
     
     pub fn lookup_mut<'a>(&'a mut self, name: &StringSymbol) -> ExecResult<&'a mut Variant> {
-        let variable = self.store.get_mut(name)
-            .ok_or_else(|| RuntimeError::name_not_defined(*name))?;
+        let slot = self.store.slot_of(name)
+            .ok_or_else(|| self.name_not_defined(name))?;
+        let variable = self.store.get_slot_mut(slot, name).unwrap();
             
         if variable.access != Access::ReadWrite {
             return Err(RuntimeError::cant_assign_immutable(*name));
         }
         
+        // the caller is expected to overwrite the value
+        gc_barrier(&variable.value);
         Ok(&mut variable.value)
     }
     
+    // suggests a similarly named variable, in case the name was misspelled
+    fn name_not_defined(&self, name: &StringSymbol) -> Box<RuntimeError> {
+        let names = self.store.keys().map(|name| name.to_string());
+        let suggestion = utils::closest_match(&name.to_string(), names);
+        
+        RuntimeError::name_not_defined(*name, suggestion.as_deref())
+    }
+    
     pub fn extend(&mut self, other: &Namespace) {
         for (name, variable) in other.store.iter() {
-            self.create(*name, variable.access, variable.value)
+            let old_variable = self.store.insert(*name, variable.clone());
+            gc_barrier(&old_variable.map(|var| var.value));
         }
     }
 }
@@ -113,11 +163,11 @@ impl NamespaceEnv {
         self.namespace.get_mut()
     }
     
-    pub fn borrow(&self) -> Ref<Namespace> {
+    pub fn borrow(&self) -> Ref<'_, Namespace> {
         self.namespace.borrow()
     }
     
-    pub fn borrow_mut(&self) -> RefMut<Namespace> {
+    pub fn borrow_mut(&self) -> RefMut<'_, Namespace> {
         self.namespace.borrow_mut()
     }
 }
@@ -182,17 +232,24 @@ impl Module {
     
     #[inline]
     pub fn get_const(&self, cid: ConstID) -> Variant {
+        if let Some(value) = self.data.get_value(cid) {
+            return value;
+        }
+        
         match self.data.get_const(cid) {
-            Constant::Integer(value) => Variant::from(*value),
-            
-            Constant::Float(bytes) => FloatType::from_le_bytes(*bytes).into(),
-            
-            Constant::String(idx) => Variant::from(*self.data.get_string(*idx)),
-            
             Constant::Error { error, message: idx } => {
                 let message = *self.data.get_string(*idx);
                 Variant::Error(Gc::new(RuntimeError::new(*error, message.into())))
             }
+            
+            #[cfg(feature = "bigint")]
+            Constant::BigInt(idx) => {
+                let text = StringValue::from(*self.data.get_string(*idx));
+                text.with_str(|text| int_from_str(text, None))
+                    .expect("invalid big integer constant")
+            }
+            
+            _ => unreachable!(),
         }
     }
     
@@ -210,7 +267,7 @@ impl fmt::Display for Module {
 
 
 /// Used to uniquely identify a module
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ModuleIdent {
     SourcePath(PathBuf), // canonicalized path to the source file
     SourceHash(u64),  // hash of source text
@@ -229,9 +286,9 @@ impl From<&ModuleSource> for ModuleIdent {
             },
             
             ModuleSource::String(text) => {
-                let mut state = MODULE_IDENT_HASH.build_hasher();
-                text.hash(&mut state);
-                let hash = state.finish();
+                
+                
+                let hash = MODULE_IDENT_HASH.hash_one(text);
                 Self::SourceHash(hash)
             }
         }
@@ -265,4 +322,163 @@ impl fmt::Display for ModuleIdent {
             Self::RefHash(hash) => write!(fmt, "&{:016X}", hash),
         }
     }
-}
\ No newline at end of file
+}
+
+
+/// The file extension used for Sphinx source files
+pub const SOURCE_EXT: &str = "sph";
+
+/// The file extension used for compiled modules, which are stored next to their source file
+pub const COMPILED_EXT: &str = "sphc";
+
+/// Locates, compiles, and caches the modules imported by a running program.
+///
+/// Module names are dotted paths, e.g. `foo.bar` refers to the file `foo/bar.sph` 
+/// relative to one of the directories in the search path.
+#[derive(Debug)]
+pub struct ModuleLoader {
+    search_path: Vec<PathBuf>,
+    prelude: Option<Gc<NamespaceEnv>>,  // created on first load, since it is fairly expensive
+    modules: HashMap<ModuleIdent, LoadedModule>,
+}
+
+#[derive(Debug)]
+struct LoadedModule {
+    module: Gc<Module>,
+    main: Box<[u8]>,
+    ready: bool,  // set once the main chunk has finished executing
+}
+
+unsafe impl GcTrace for ModuleLoader {
+    fn trace(&self) {
+        if let Some(prelude) = self.prelude {
+            prelude.mark_trace();
+        }
+        for loaded in self.modules.values() {
+            loaded.module.mark_trace();
+        }
+    }
+}
+
+impl ModuleLoader {
+    pub fn new(search_path: Vec<PathBuf>) -> Self {
+        Self {
+            search_path,
+            prelude: None,
+            modules: HashMap::with_hasher(DefaultBuildHasher::default()),
+        }
+    }
+    
+    /// Create a loader that searches the directory containing the given module's source file,
+    /// or the current directory if the module was not loaded from a file.
+    pub fn for_module(module: &Module) -> Self {
+        let search_dir = match module.source() {
+            Some(ModuleSource::File(path)) => path.parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+            
+            _ => PathBuf::from("."),
+        };
+        
+        Self::new(vec![ search_dir ])
+    }
+    
+    pub fn search_path(&self) -> &[PathBuf] { &self.search_path }
+    
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        self.search_path.push(path.into())
+    }
+    
+    /// Find the source file for a dotted module name, trying each directory in the search path in order
+    pub fn find_module(&self, name: &str) -> Option<PathBuf> {
+        let mut rel_path: PathBuf = name.split('.').collect();
+        rel_path.set_extension(SOURCE_EXT);
+        
+        self.search_path.iter()
+            .map(|dir| dir.join(&rel_path))
+            .find(|path| path.is_file())
+    }
+    
+    /// Get a module that has already been imported and finished executing
+    pub fn get_cached(&self, ident: &ModuleIdent) -> Option<Gc<Module>> {
+        self.modules.get(ident)
+            .filter(|loaded| loaded.ready)
+            .map(|loaded| loaded.module)
+    }
+    
+    /// Load a module and store it in the cache, compiling it from source if there is no up-to-date
+    /// compiled artifact next to the source file.
+    /// Returns the new module along with its main chunk, which must be executed before
+    /// the module is marked as ready.
+    pub fn load_module(&mut self, name: &str, path: PathBuf) -> ExecResult<(Gc<Module>, &[u8])> {
+        let source = ModuleSource::File(path);
+        
+        let build = match Self::load_compiled(&source) {
+            Some(build) => build,
+            None => {
+                let build = crate::build_module(&source)
+                    .map_err(|errors| {
+                        crate::print_build_errors(&errors, &source);
+                        RuntimeError::import_failed(name, "could not build module")
+                    })?;
+                
+                crate::print_build_warnings(&build.warnings, &source);
+                build
+            }
+        };
+        
+        let program = Program::load(build.program).with_symbols(build.symbols);
+        
+        let prelude = *self.prelude.get_or_insert_with(crate::builtins::create_prelude);
+        let globals = Gc::new(NamespaceEnv::from(prelude.borrow().clone()));
+        let module = Module::with_env(Some(source), program.data, globals);
+        
+        // replaces any module left over from a previous attempt that failed to execute
+        let loaded = LoadedModule {
+            module,
+            main: program.main,
+            ready: false,
+        };
+        self.modules.insert(module.ident().clone(), loaded);
+        
+        let loaded = self.modules.get(module.ident()).unwrap();
+        Ok((loaded.module, &loaded.main))
+    }
+    
+    fn load_compiled(source: &ModuleSource) -> Option<CompiledProgram> {
+        let path = match source {
+            ModuleSource::File(path) => path.with_extension(COMPILED_EXT),
+            ModuleSource::String(..) => return None,
+        };
+        
+        let file = fs::File::open(&path).ok()?;
+        let build = CompiledProgram::read_from(io::BufReader::new(file))
+            .map_err(|error| log::warn!("could not read \"{}\": {}", path.display(), error))
+            .ok()?;
+        
+        // fall back to the source text if it has changed since the artifact was compiled
+        let source_hash = source.source_hash().ok()?;
+        if build.source_hash != Some(source_hash) {
+            log::debug!("ignoring stale compiled module \"{}\"", path.display());
+            return None;
+        }
+        
+        Some(build)
+    }
+    
+    pub fn set_ready(&mut self, ident: &ModuleIdent) {
+        if let Some(loaded) = self.modules.get_mut(ident) {
+            loaded.ready = true;
+        }
+    }
+    
+    /// Bind the exported globals of an imported module into another namespace.
+    /// The bound names are private to the importing module.
+    pub fn bind_exports(&self, module: &Module, namespace: &mut Namespace) {
+        for (name, variable) in module.globals().borrow().store.iter() {
+            if variable.exported {
+                namespace.create(*name, variable.access, variable.value);
+            }
+        }
+    }
+}
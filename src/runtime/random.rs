@@ -0,0 +1,93 @@
+//! Pseudorandom number generation for the `random` builtin module.
+//!
+//! Each VM owns its own generator, so that seeding it makes a program's results reproducible
+//! no matter what else is running. The generator is xoshiro256**, which is fast and has good
+//! statistical quality, but should never be used for anything that needs to be unpredictable.
+
+use core::hash::{BuildHasher, Hasher};
+use std::collections::hash_map::RandomState;
+use crate::language::{IntType, FloatType};
+
+
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: [u64; 4],
+}
+
+impl Random {
+    pub fn from_seed(seed: u64) -> Self {
+        // xoshiro must not be seeded with all zeros, which splitmix64 never produces from any seed
+        let mut seed = seed;
+        let mut next_seed = || {
+            seed = seed.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^ (z >> 31)
+        };
+        
+        Self {
+            state: [next_seed(), next_seed(), next_seed(), next_seed()],
+        }
+    }
+    
+    /// Seed the generator differently every time, using the random keys that std generates for hash tables
+    pub fn from_entropy() -> Self {
+        Self::from_seed(RandomState::new().build_hasher().finish())
+    }
+    
+    pub fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        
+        let t = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+        
+        result
+    }
+    
+    /// A float in the half-open interval [0, 1)
+    pub fn next_float(&mut self) -> FloatType {
+        // use only as many bits as the float can represent exactly, so that the result is never rounded up to 1
+        let bits = FloatType::MANTISSA_DIGITS;
+        let value = self.next_u64() >> (64 - bits);
+        value as FloatType / (1u64 << bits) as FloatType
+    }
+    
+    /// An integer in the half-open interval [start, stop), or None if that is empty
+    pub fn next_range(&mut self, start: IntType, stop: IntType) -> Option<IntType> {
+        if start >= stop {
+            return None;
+        }
+        
+        // the width of the range always fits in a u64, even if it doesn't fit in an IntType
+        let width = (i128::from(stop) - i128::from(start)) as u64;
+        let result = i128::from(start) + i128::from(self.next_below(width));
+        Some(IntType::try_from(result).unwrap())
+    }
+    
+    /// An index into a sequence with the given length, or None if it is empty
+    pub fn next_index(&mut self, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        let index = self.next_below(len as u64);
+        Some(usize::try_from(index).unwrap())
+    }
+    
+    fn next_below(&mut self, bound: u64) -> u64 {
+        // reject values from the incomplete interval at the top, which would otherwise bias the result
+        let limit = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return value % bound;
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::runtime::{HashMap, DefaultBuildHasher};
+use crate::runtime::strings::StringSymbol;
+
+
+/// A map from names to values that also keeps each value at a numbered slot.
+///
+/// Slots allow inline caches to skip the hash lookup. A slot index is only a hint, since
+/// removing an entry moves the last entry into its slot, so every lookup by index is checked
+/// against the name stored in the slot. This also means that a slot index found in one map
+/// can be safely tried in any other map, which is useful when many maps are filled in the same order
+/// (e.g. the fields of instances created by the same constructor).
+#[derive(Debug, Clone)]
+pub struct SlotMap<V> {
+    index: HashMap<StringSymbol, usize>,
+    slots: Vec<(StringSymbol, V)>,
+}
+
+impl<V> Default for SlotMap<V> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<V> SlotMap<V> {
+    pub fn new() -> Self {
+        Self {
+            index: HashMap::with_hasher(DefaultBuildHasher::default()),
+            slots: Vec::new(),
+        }
+    }
+    
+    pub fn len(&self) -> usize { self.slots.len() }
+    
+    pub fn is_empty(&self) -> bool { self.slots.is_empty() }
+    
+    pub fn capacity(&self) -> usize { self.slots.capacity() }
+    
+    pub fn iter(&self) -> impl Iterator<Item=(&StringSymbol, &V)> {
+        self.slots.iter().map(|(name, value)| (name, value))
+    }
+    
+    pub fn keys(&self) -> impl Iterator<Item=&StringSymbol> {
+        self.slots.iter().map(|(name, _)| name)
+    }
+    
+    pub fn values(&self) -> impl Iterator<Item=&V> {
+        self.slots.iter().map(|(_, value)| value)
+    }
+    
+    pub fn slot_of(&self, name: &StringSymbol) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+    
+    pub fn get_full(&self, name: &StringSymbol) -> Option<(usize, &V)> {
+        self.slot_of(name).map(|slot| (slot, &self.slots[slot].1))
+    }
+    
+    pub fn get(&self, name: &StringSymbol) -> Option<&V> {
+        self.slot_of(name).map(|slot| &self.slots[slot].1)
+    }
+    
+    pub fn get_mut(&mut self, name: &StringSymbol) -> Option<&mut V> {
+        self.slot_of(name).map(|slot| &mut self.slots[slot].1)
+    }
+    
+    /// Get the value at a slot, as long as the slot still holds the given name
+    #[inline]
+    pub fn get_slot(&self, slot: usize, name: &StringSymbol) -> Option<&V> {
+        self.slots.get(slot)
+            .filter(|(slot_name, _)| slot_name == name)
+            .map(|(_, value)| value)
+    }
+    
+    #[inline]
+    pub fn get_slot_mut(&mut self, slot: usize, name: &StringSymbol) -> Option<&mut V> {
+        self.slots.get_mut(slot)
+            .filter(|(slot_name, _)| slot_name == name)
+            .map(|(_, value)| value)
+    }
+    
+    /// Replaces the value if the name is already present, otherwise adds it to a new slot
+    pub fn insert(&mut self, name: StringSymbol, value: V) -> Option<V> {
+        if let Some(slot) = self.slot_of(&name) {
+            return Some(core::mem::replace(&mut self.slots[slot].1, value));
+        }
+        
+        self.index.insert(name, self.slots.len());
+        self.slots.push((name, value));
+        None
+    }
+    
+    pub fn remove(&mut self, name: &StringSymbol) -> Option<V> {
+        let slot = self.index.remove(name)?;
+        let (_, value) = self.slots.swap_remove(slot);
+        if let Some((moved, _)) = self.slots.get(slot) {
+            self.index.insert(*moved, slot);
+        }
+        Some(value)
+    }
+}
@@ -7,10 +7,10 @@ use crate::runtime::errors::ExecResult;
 pub mod intern;
 pub mod buffer;
 
-pub use intern::{StringSymbol, StringInterner, static_symbol, STRING_TABLE};
+pub use intern::{StringSymbol, StringInterner, StringTable, static_symbol, with_string_table};
+pub(crate) use intern::enter_string_table;
 pub use buffer::StrBuffer;
 
-use intern::StringTable;
 
 
 // need to build on a 32-bit machine to find out what will fit
@@ -53,26 +53,9 @@ impl From<Gc<str>> for StringValue {
     }
 }
 
-const INTERN_THRESHOLD: usize = 40;
-
 // different constructors for different interning policies
+// Strings that are created at runtime should not be interned, since the string table is never purged
 impl StringValue {
-    pub fn new_maybe_interned<S>(s: S) -> Self where S: AsRef<str> {
-        let string = s.as_ref();
-        
-        // first, try inlining
-        if let Ok(inline) = InlineStr::try_from(string) {
-            return Self::Inline(inline);
-        }
-        
-        // always intern short-ish strings
-        if string.len() <= INTERN_THRESHOLD {
-            return Self::Intern(StringSymbol::intern(string))
-        }
-        
-        Self::Gc(Gc::from_box(string.to_string().into_boxed_str()))
-    }
-    
     pub fn new_uninterned<S>(s: S) -> Self where S: AsRef<str> {
         let string = s.as_ref();
         
@@ -89,12 +72,12 @@ impl StringValue {
     }
 }
 
-/// evaluates an expression using a StringValue, accessing the STRING_TABLE only if needed
+/// evaluates an expression using a StringValue, accessing the string table only if needed
 macro_rules! with_str {
     ($strval:expr, $string:ident => $expr:expr) => {
         match $strval.try_str() {
             Ok($string) => $expr,
-            Err(symbol) => STRING_TABLE.with(|string_table| {
+            Err(symbol) => with_string_table(|string_table| {
                 let string_table = string_table.borrow();
                 let $string = string_table.resolve(&symbol);
                 $expr
@@ -114,8 +97,8 @@ impl StringValue {
     pub fn write(&self, buf: &mut impl fmt::Write) -> fmt::Result {
         match self {
             Self::Intern(symbol) => symbol.write(buf),
-            Self::Inline(inline) => buf.write_str(&*inline),
-            Self::Gc(gc_str) => buf.write_str(&**gc_str),
+            Self::Inline(inline) => buf.write_str(inline),
+            Self::Gc(gc_str) => buf.write_str(gc_str),
         }
     }
     
@@ -123,8 +106,8 @@ impl StringValue {
     pub fn as_intern(&self) -> StringSymbol {
         match self {
             Self::Intern(symbol) => *symbol,
-            Self::Inline(inline) => StringSymbol::intern(&*inline),
-            Self::Gc(gc_str) => StringSymbol::intern(&**gc_str),
+            Self::Inline(inline) => StringSymbol::intern(inline),
+            Self::Gc(gc_str) => StringSymbol::intern(gc_str),
         }
     }
     
@@ -141,8 +124,8 @@ impl StringValue {
     fn resolve_str<'s, 'h>(&'s self, string_table: &'h StringTable) -> &'s str where 'h: 's {
         match self {
             Self::Intern(symbol) => string_table.resolve(symbol),
-            Self::Inline(inline) => &*inline,
-            Self::Gc(gc_str) => &**gc_str,
+            Self::Inline(inline) => inline,
+            Self::Gc(gc_str) => gc_str,
         }
     }
     
@@ -152,7 +135,7 @@ impl StringValue {
     
     fn try_str(&self) -> Result<&str, StringSymbol> {
         match self {
-            Self::Inline(inline) => Ok(&*inline),
+            Self::Inline(inline) => Ok(inline),
             Self::Gc(gc_str) => Ok(&**gc_str),
             Self::Intern(symbol) => Err(*symbol),
         }
@@ -174,13 +157,13 @@ impl StringValue {
             with_str!(self, s => buf.try_push_str(s).unwrap());
             with_str!(other, s => buf.try_push_str(s).unwrap());
             
-            Ok(StringValue::new_maybe_interned(buf))
+            Ok(StringValue::new_uninterned(buf))
         } else {
             let mut buf = String::new();
             with_str!(self, s => buf.push_str(s));
             with_str!(other, s => buf.push_str(s));
             
-            Ok(StringValue::new_maybe_interned(buf.as_str()))
+            Ok(StringValue::new_uninterned(buf.as_str()))
         }
     }
 }
@@ -198,15 +181,15 @@ impl StringValue {
 // is only truly doubled when hashing non-interned strings). This ensures that hashes are consistent.
 impl Hash for StringValue {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        STRING_TABLE.with(|string_table| match self {
+        with_string_table(|string_table| match self {
             Self::Intern(symbol) =>
                 string_table.borrow().lookup_hash(symbol).hash(state),
             
             Self::Inline(inline) =>
-                string_table.borrow().hash_str(&*inline).hash(state),
+                string_table.borrow().hash_str(inline).hash(state),
             
             Self::Gc(gc_str) => 
-                string_table.borrow().hash_str(&**gc_str).hash(state),
+                string_table.borrow().hash_str(gc_str).hash(state),
             
         })
     }
@@ -219,7 +202,7 @@ impl PartialEq for StringValue {
             (Self::Intern(a), Self::Intern(b)) => a == b,
             
             // one interned
-            (Self::Intern(symbol), strval) | (strval, Self::Intern(symbol)) => STRING_TABLE.with(|string_table| {
+            (Self::Intern(symbol), strval) | (strval, Self::Intern(symbol)) => with_string_table(|string_table| {
                 string_table.borrow().resolve(symbol) == strval.try_str().unwrap()
             }),
             
@@ -233,15 +216,7 @@ impl Eq for StringValue { }
 
 impl PartialOrd for StringValue {
     fn partial_cmp(&self, other: &StringValue) -> Option<cmp::Ordering> {
-        if let (Ok(a), Ok(b)) = (self.try_str(), other.try_str()) {
-            return a.partial_cmp(b)
-        }
-        
-        STRING_TABLE.with(|string_table| {
-            let string_table = string_table.borrow();
-            self.resolve_str(&string_table)
-                .partial_cmp(other.resolve_str(&string_table))
-        })
+        Some(self.cmp(other))
     }
 }
 
@@ -251,7 +226,7 @@ impl Ord for StringValue {
             return a.cmp(b)
         }
         
-        STRING_TABLE.with(|string_table| {
+        with_string_table(|string_table| {
             let string_table = string_table.borrow();
             self.resolve_str(&string_table)
                 .cmp(other.resolve_str(&string_table))
@@ -119,7 +119,7 @@ impl<const N: usize> StrBuffer<N> {
             self.len += s_ref.len() as u8;
             Ok(())
         } else {
-            Err(fmt::Error::default())
+            Err(fmt::Error)
         }
     }
 }
@@ -142,11 +142,11 @@ impl<const N: usize> core::ops::Deref for StrBuffer<N> {
 }
 
 impl<const N: usize> AsRef<str> for StrBuffer<N> {
-    fn as_ref(&self) -> &str { &*self }
+    fn as_ref(&self) -> &str { self }
 }
 
 impl<const N: usize> Borrow<str> for StrBuffer<N> {
-    fn borrow(&self) -> &str { &*self }
+    fn borrow(&self) -> &str { self }
 }
 
 impl<const N: usize> fmt::Write for StrBuffer<N> {
@@ -1,8 +1,10 @@
 use core::fmt;
 use core::cmp;
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
+use core::ptr::NonNull;
 use core::marker::PhantomData;
-use core::hash::{Hash, Hasher, BuildHasher};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::hash::{Hash, BuildHasher};
 use string_interner::{self, DefaultBackend};
 use string_interner::symbol::Symbol;
 
@@ -11,7 +13,35 @@ use crate::runtime::DefaultBuildHasher;
 
 
 thread_local! {
-    pub static STRING_TABLE: RefCell<StringTable> = RefCell::new(StringTable::new());
+    // used when no runtime has been entered
+    static DEFAULT_TABLE: RefCell<StringTable> = RefCell::new(StringTable::new());
+    
+    static CURRENT_TABLE: Cell<Option<NonNull<RefCell<StringTable>>>> = const { Cell::new(None) };
+}
+
+/// Access the string table of the current runtime, or the thread's own table if no runtime has been entered
+#[inline]
+pub fn with_string_table<R>(f: impl FnOnce(&RefCell<StringTable>) -> R) -> R {
+    match CURRENT_TABLE.with(Cell::get) {
+        // SAFETY: the pointer is only set while the table is borrowed by enter_string_table()
+        Some(table) => f(unsafe { table.as_ref() }),
+        None => DEFAULT_TABLE.with(f),
+    }
+}
+
+/// Makes the table the current string table of the thread while the closure runs
+pub(crate) fn enter_string_table<R>(table: &RefCell<StringTable>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<NonNull<RefCell<StringTable>>>);
+    
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT_TABLE.with(|current| current.set(self.0))
+        }
+    }
+    
+    let previous = CURRENT_TABLE.with(|current| current.replace(Some(NonNull::from(table))));
+    let _restore = Restore(previous);
+    f()
 }
 
 // Helper macro for static interned strings
@@ -19,11 +49,22 @@ thread_local! {
 macro_rules! static_symbol {
     ($str:expr) => {
         {
-            type StringSymbol = crate::runtime::strings::StringSymbol;
+            type StringSymbol = $crate::runtime::strings::StringSymbol;
+            
+            // the symbol is only valid for the table that it was interned in
             thread_local! {
-                static SYMBOL: StringSymbol = StringSymbol::from($str);
+                static SYMBOL: core::cell::Cell<Option<(u64, StringSymbol)>> = const { core::cell::Cell::new(None) };
             }
-            SYMBOL.with(|symbol| *symbol)
+            
+            let table_id = $crate::runtime::strings::with_string_table(|string_table| string_table.borrow().id());
+            SYMBOL.with(|cached| match cached.get() {
+                Some((cached_id, symbol)) if cached_id == table_id => symbol,
+                _ => {
+                    let symbol = StringSymbol::from($str);
+                    cached.set(Some((table_id, symbol)));
+                    symbol
+                }
+            })
         }
     };
 }
@@ -37,7 +78,7 @@ type PhantomUnsend = PhantomData<*mut ()>;
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct StringSymbol(InternSymbol, PhantomUnsend);
 
-// Not Send because we depend on the thread-local string table.
+// Not Send because we depend on the current string table of the thread.
 // (We can Send strings with some extra work, just not StringSymbols)
 // impl !Send for StringSymbol { }
 
@@ -48,11 +89,11 @@ impl StringSymbol {
     
     /// Interns a string slice, creating a `StringSymbol`
     pub fn intern(string: &str) -> Self {
-        STRING_TABLE.with(|string_table| string_table.borrow_mut().get_or_intern(string))
+        with_string_table(|string_table| string_table.borrow_mut().get_or_intern(string))
     }
     
     pub fn write(&self, buf: &mut impl fmt::Write) -> fmt::Result {
-        STRING_TABLE.with(|string_table| buf.write_str(
+        with_string_table(|string_table| buf.write_str(
             string_table.borrow().resolve(self)
         ))
     }
@@ -82,21 +123,13 @@ impl From<&str> for StringSymbol {
 // Lexicographical ordering of strings
 impl PartialOrd for StringSymbol {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        STRING_TABLE.with(|string_table| {
-            let string_table = string_table.borrow();
-            
-            <str as PartialOrd>::partial_cmp(
-                string_table.resolve(self),
-                string_table.resolve(other),
-            )
-        })
-
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for StringSymbol {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        STRING_TABLE.with(|string_table| {
+        with_string_table(|string_table| {
             let string_table = string_table.borrow();
             
             <str as Ord>::cmp(
@@ -130,8 +163,8 @@ pub type StringInterner = string_interner::StringInterner<InternBackend, Default
 
 pub type StringBuildHasher = DefaultBuildHasher;
 
-#[derive(Clone)]
 pub struct StringTable {
+    id: u64,  // identifies the table for caching symbols, see `static_symbol!`
     interner: StringInterner,
     hasher_factory: StringBuildHasher,
     hashes: Vec<u64>,  // hash cache
@@ -141,23 +174,27 @@ impl Default for StringTable {
     fn default() -> Self { Self::new() }
 }
 
+static NEXT_TABLE_ID: AtomicU64 = AtomicU64::new(0);
+
 impl StringTable {
     pub fn new() -> Self {
         StringTable {
+            id: NEXT_TABLE_ID.fetch_add(1, Ordering::Relaxed),
             interner: StringInterner::new(),
             hasher_factory: StringBuildHasher::default(),
             hashes: Vec::new(),
         }
     }
     
+    #[inline]
+    pub fn id(&self) -> u64 { self.id }
+    
     pub fn hasher(&self) -> &impl BuildHasher {
         &self.hasher_factory
     }
     
     pub fn hash_str(&self, string: &str) -> u64 {
-        let mut state = self.hasher_factory.build_hasher();
-        string.hash(&mut state);
-        state.finish()
+        self.hasher_factory.hash_one(string)
     }
     
     pub fn get(&self, string: &str) -> Option<StringSymbol> {
@@ -191,6 +228,18 @@ impl StringTable {
     
 }
 
+// a clone can diverge from the original, so it needs its own id
+impl Clone for StringTable {
+    fn clone(&self) -> Self {
+        Self {
+            id: NEXT_TABLE_ID.fetch_add(1, Ordering::Relaxed),
+            interner: self.interner.clone(),
+            hasher_factory: self.hasher_factory.clone(),
+            hashes: self.hashes.clone(),
+        }
+    }
+}
+
 impl<'s> Extend<&'s str> for StringTable {
     fn extend<T>(&mut self, iter: T) where T: IntoIterator<Item=&'s str> {
         for string in iter.into_iter() {
@@ -25,4 +25,536 @@ fn hash_intern_hasher_is_stable() {
     }
     
     
-}
\ No newline at end of file
+}
+
+mod userdata_ops {
+    use crate::language::IntType;
+    use crate::runtime::{Gc, Variant, ExecResult};
+    use crate::runtime::gc::GcTrace;
+    use crate::runtime::strings::StringValue;
+    use crate::runtime::types::{Type, MetaObject, UserData};
+    
+    macro_rules! userdata {
+        ( $name:ident ) => {
+            struct $name(IntType);
+            
+            unsafe impl GcTrace for $name {
+                fn trace(&self) { }
+            }
+            
+            impl UserData for $name { }
+        };
+    }
+    
+    // only knows how to add itself to an int
+    userdata!(Lhs);
+    impl MetaObject for Lhs {
+        fn type_tag(&self) -> Type { Type::UserData }
+        fn fmt_repr(&self) -> ExecResult<StringValue> { Ok(StringValue::new_uninterned("Lhs")) }
+        
+        fn op_add(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+            match rhs {
+                Variant::Integer(value) => Some(Ok(Variant::from(self.0 + value))),
+                _ => None,
+            }
+        }
+    }
+    
+    // only supports reflected addition
+    userdata!(Rhs);
+    impl MetaObject for Rhs {
+        fn type_tag(&self) -> Type { Type::UserData }
+        fn fmt_repr(&self) -> ExecResult<StringValue> { Ok(StringValue::new_uninterned("Rhs")) }
+        
+        fn op_radd(&self, _lhs: &Variant) -> Option<ExecResult<Variant>> {
+            Some(Ok(Variant::from(self.0)))
+        }
+        
+        fn cmp_lt(&self, other: &Variant) -> Option<ExecResult<bool>> {
+            match other {
+                Variant::Integer(value) => Some(Ok(self.0 < *value)),
+                _ => None,
+            }
+        }
+        
+        fn cmp_le(&self, other: &Variant) -> Option<ExecResult<bool>> {
+            match other {
+                Variant::Integer(value) => Some(Ok(self.0 <= *value)),
+                _ => None,
+            }
+        }
+    }
+    
+    fn new_userdata(data: Box<dyn UserData>) -> Variant {
+        Variant::UserData(Gc::from_box(data))
+    }
+    
+    #[test]
+    fn binary_op_uses_lhs_metamethod() {
+        let lhs = new_userdata(Box::new(Lhs(2)));
+        let result = lhs.apply_add(&Variant::from(3)).unwrap();
+        assert!(matches!(result, Variant::Integer(5)));
+    }
+    
+    #[test]
+    fn binary_op_falls_back_to_reflected() {
+        let rhs = new_userdata(Box::new(Rhs(7)));
+        let result = Variant::from(3).apply_add(&rhs).unwrap();
+        assert!(matches!(result, Variant::Integer(7)));
+    }
+    
+    #[test]
+    fn binary_op_reflected_between_userdata_types() {
+        let lhs = new_userdata(Box::new(Lhs(2)));
+        let rhs = new_userdata(Box::new(Rhs(7)));
+        let result = lhs.apply_add(&rhs).unwrap();
+        assert!(matches!(result, Variant::Integer(7)));
+    }
+    
+    #[test]
+    fn binary_op_not_supported() {
+        let lhs = new_userdata(Box::new(Rhs(2)));
+        let rhs = new_userdata(Box::new(Rhs(7)));
+        assert!(lhs.apply_add(&rhs).is_err());
+    }
+    
+    #[test]
+    fn userdata_equality_falls_back_to_identity() {
+        let data = new_userdata(Box::new(Lhs(1)));
+        let other = new_userdata(Box::new(Lhs(1)));
+        assert!(data.cmp_eq(&data).unwrap());
+        assert!(!data.cmp_eq(&other).unwrap());
+        assert!(data.cmp_ne(&other).unwrap());
+        assert!(!data.cmp_eq(&Variant::from(1)).unwrap());
+    }
+    
+    #[test]
+    fn comparison_uses_metamethods() {
+        let data = new_userdata(Box::new(Rhs(2)));
+        assert!(data.cmp_lt(&Variant::from(3)).unwrap());
+        assert!(data.cmp_gt(&Variant::from(1)).unwrap());
+        
+        // reflected
+        assert!(Variant::from(3).cmp_gt(&data).unwrap());
+        assert!(Variant::from(2).cmp_ge(&data).unwrap());
+        assert!(!Variant::from(2).cmp_lt(&data).unwrap());
+    }
+    
+    #[test]
+    fn comparison_not_supported() {
+        let data = new_userdata(Box::new(Lhs(2)));
+        assert!(data.cmp_lt(&Variant::from(3)).is_err());
+    }
+}
+
+
+mod string_tables {
+    use crate::language::FloatType;
+    use crate::runtime::{Runtime, Variant};
+    use crate::runtime::gc::gc_force;
+    use crate::runtime::strings::{StringSymbol, StringValue, static_symbol, with_string_table};
+    
+    fn is_interned(string: &str) -> bool {
+        with_string_table(|string_table| string_table.borrow().get(string).is_some())
+    }
+    
+    #[test]
+    fn runtime_interns_separately() {
+        let runtime = Runtime::new();
+        unsafe { runtime.enter(|| { StringSymbol::intern("only_in_runtime"); }); }
+        
+        assert!(!is_interned("only_in_runtime"));
+        assert!(unsafe { runtime.enter(|| is_interned("only_in_runtime")) });
+    }
+    
+    #[test]
+    fn static_symbol_is_cached_per_table() {
+        fn symbol() -> StringSymbol { static_symbol!("static_symbol_test") }
+        
+        // intern some other strings first so that the symbols differ between tables
+        let runtime = Runtime::new();
+        unsafe { runtime.enter(|| { StringSymbol::intern("padding"); }); }
+        
+        let outside = symbol();
+        assert_eq!(outside.to_string(), "static_symbol_test");
+        
+        unsafe {
+            runtime.enter(|| {
+                assert_ne!(symbol(), outside);
+                assert_eq!(symbol().to_string(), "static_symbol_test");
+            });
+        }
+        
+        assert_eq!(symbol(), outside);
+    }
+    
+    #[test]
+    fn runtime_strings_are_not_interned() {
+        let lhs = StringValue::new_uninterned("a string that is");
+        let rhs = StringValue::new_uninterned(" too long to inline");
+        let result = lhs.concat(&rhs).unwrap();
+        
+        assert!(matches!(result, StringValue::Gc(..)));
+        assert!(result == StringValue::new_interned("a string that is too long to inline"));
+        
+        let formatted = Variant::from(FloatType::powi(2.0, 60)).fmt_str().unwrap();
+        formatted.with_str(|s| assert!(!is_interned(s)));
+        
+        gc_force(&0);
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "handle used outside of the heap it was allocated from")]
+    fn runtime_values_cannot_leave_runtime() {
+        let runtime = Runtime::new();
+        
+        // breaks the contract of enter() on purpose, while the runtime is still alive to catch it
+        let mut escaped = None;
+        unsafe {
+            runtime.enter(|| {
+                let lhs = StringValue::new_uninterned("a string that is");
+                let rhs = StringValue::new_uninterned(" too long to inline");
+                escaped = Some(lhs.concat(&rhs).unwrap());
+            });
+        }
+        
+        escaped.unwrap().with_str(|s| s.len());
+    }
+}
+
+mod module_loader {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::builtins;
+    use crate::source::ModuleSource;
+    use crate::codegen::Program;
+    use crate::runtime::{Module, VirtualMachine};
+    use crate::runtime::module::{SOURCE_EXT, COMPILED_EXT};
+    
+    fn make_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sphinx-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    
+    fn run_with_search_path(text: &str, search_dir: &Path) {
+        let source = ModuleSource::String(text.to_string());
+        let build = crate::build_module(&source).expect("build failed");
+        let program = Program::load(build.program).with_symbols(build.symbols);
+        
+        let main_module = Module::with_env(Some(source), program.data, builtins::create_prelude());
+        let mut vm = VirtualMachine::new(main_module, &program.main);
+        vm.loader_mut().add_search_path(search_dir);
+        
+        if let Err(error) = vm.run() {
+            panic!("{}{}", error.traceback(), error);
+        }
+    }
+    
+    #[test]
+    fn import_uses_compiled_module_unless_stale() {
+        let dir = make_test_dir("precompiled");
+        let source_path = dir.join("precompiled").with_extension(SOURCE_EXT);
+        
+        let source_text = "export let value = \"source\"";
+        fs::write(&source_path, source_text).unwrap();
+        
+        // compile different code, but record the hash of the source file so that the artifact looks up to date
+        let mut build = crate::build_module(&ModuleSource::String("export let value = \"compiled\"".to_string())).unwrap();
+        build.source_hash = Some(ModuleSource::File(source_path.clone()).source_hash().unwrap());
+        
+        let artifact = fs::File::create(source_path.with_extension(COMPILED_EXT)).unwrap();
+        build.write_to(artifact).unwrap();
+        
+        run_with_search_path("import precompiled; assert value == \"compiled\"", &dir);
+        
+        // once the source changes the artifact is ignored
+        fs::write(&source_path, format!("{}\n", source_text)).unwrap();
+        run_with_search_path("import precompiled; assert value == \"source\"", &dir);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod inline_caches {
+    use crate::language::{Access, IntType};
+    use crate::runtime::Variant;
+    use crate::runtime::module::Namespace;
+    use crate::runtime::vm::InlineCache;
+    use crate::runtime::strings::StringSymbol;
+    use crate::runtime::errors::ErrorKind;
+    
+    fn load(cache: &InlineCache, namespace: &Namespace, name: &StringSymbol) -> Option<IntType> {
+        cache.load_global(namespace, name).ok()
+            .map(|value| value.as_int().unwrap())
+    }
+    
+    #[test]
+    fn global_cache_follows_moved_slots() {
+        let names = ["cache_a", "cache_b", "cache_c"].map(StringSymbol::intern);
+        let mut namespace = Namespace::new();
+        for (value, name) in names.iter().enumerate() {
+            namespace.create(*name, Access::ReadOnly, Variant::from(IntType::try_from(value).unwrap()));
+        }
+        
+        let cache = InlineCache::default();
+        assert_eq!(load(&cache, &namespace, &names[2]), Some(2));
+        
+        // removing a variable moves the last variable into the empty slot
+        namespace.delete(&names[0]).unwrap();
+        assert_eq!(load(&cache, &namespace, &names[2]), Some(2));
+        assert_eq!(load(&cache, &namespace, &names[1]), Some(1));
+        
+        namespace.delete(&names[1]).unwrap();
+        let error = cache.load_global(&namespace, &names[1]).unwrap_err();
+        assert_eq!(*error.kind(), ErrorKind::NameNotDefined);
+        
+        // the cache is never used for a different name, even in another namespace
+        let mut other = Namespace::new();
+        other.create(names[0], Access::ReadOnly, Variant::from(IntType::from(10)));
+        other.create(names[2], Access::ReadOnly, Variant::from(IntType::from(12)));
+        assert_eq!(load(&cache, &namespace, &names[2]), Some(2));
+        assert_eq!(load(&cache, &other, &names[2]), Some(12));
+    }
+}
+
+mod name_suggestions {
+    use crate::utils;
+    use crate::builtins;
+    use crate::source::ModuleSource;
+    use crate::codegen::{Program, CompiledProgram};
+    use crate::runtime::{Module, VirtualMachine};
+    use crate::language::Access;
+    use crate::runtime::Variant;
+    use crate::runtime::module::Namespace;
+    use crate::runtime::strings::StringSymbol;
+    
+    #[test]
+    fn closest_match_picks_nearest_name() {
+        assert_eq!(utils::edit_distance("kitten", "sitting"), 3);
+        assert_eq!(utils::edit_distance("", "abc"), 3);
+        assert_eq!(utils::edit_distance("héllo", "hello"), 1);
+        assert_eq!(utils::edit_distance("cuont", "count"), 1);
+        
+        let names = ["count", "counter", "print", "x"];
+        assert_eq!(utils::closest_match("cuont", names), Some("count"));
+        assert_eq!(utils::closest_match("pritn", names), Some("print"));
+        assert_eq!(utils::closest_match("counters", names), Some("counter"));
+        
+        // too different, or too short to guess
+        assert_eq!(utils::closest_match("total", names), None);
+        assert_eq!(utils::closest_match("y", names), None);
+    }
+    
+    #[test]
+    fn undefined_global_suggests_similar_name() {
+        let mut namespace = Namespace::new();
+        namespace.create(StringSymbol::intern("total_count"), Access::ReadOnly, Variant::Nil);
+        
+        let error = namespace.lookup(&StringSymbol::intern("total_cuont")).unwrap_err();
+        assert_eq!(error.to_string(), "Runtime error: undefined variable \"total_cuont\", did you mean \"total_count\"?");
+        
+        let error = namespace.lookup(&StringSymbol::intern("unrelated")).unwrap_err();
+        assert_eq!(error.to_string(), "Runtime error: undefined variable \"unrelated\"");
+    }
+    
+    fn run_error(text: &str) -> String {
+        let source = ModuleSource::String(text.to_string());
+        let build = crate::build_module(&source).expect("build failed");
+        
+        // the suggestions are kept when the program is written to a compiled artifact
+        let mut bytes = Vec::new();
+        build.write_to(&mut bytes).unwrap();
+        let build = CompiledProgram::read_from(bytes.as_slice()).unwrap();
+        
+        let program = Program::load(build.program).with_symbols(build.symbols);
+        let main_module = Module::with_env(Some(source), program.data, builtins::create_prelude());
+        let mut vm = VirtualMachine::new(main_module, &program.main);
+        vm.run().expect_err("program should fail").to_string()
+    }
+    
+    #[test]
+    fn undefined_global_suggests_similar_local() {
+        let error = run_error(concat!(
+            "fun f()\n",
+            "    let count = 1\n",
+            "    cuont + 1\n",
+            "end\n",
+            "f()\n",
+        ));
+        assert_eq!(error, "Runtime error: undefined variable \"cuont\", did you mean \"count\"?");
+        
+        // locals of an enclosing function can be captured as upvalues
+        let error = run_error(concat!(
+            "fun outer()\n",
+            "    let total_count = 0\n",
+            "    fun inner() total_cuont end\n",
+            "    inner()\n",
+            "end\n",
+            "outer()\n",
+        ));
+        assert_eq!(error, "Runtime error: undefined variable \"total_cuont\", did you mean \"total_count\"?");
+        
+        // a local is preferred over a global with a similar name
+        let error = run_error(concat!(
+            "let counter_a = 0\n",
+            "fun f(counter_b) counter_c end\n",
+            "f(1)\n",
+        ));
+        assert_eq!(error, "Runtime error: undefined variable \"counter_c\", did you mean \"counter_b\"?");
+        
+        // locals that have gone out of scope are not suggested
+        let error = run_error(concat!(
+            "fun f()\n",
+            "    begin let count = 1 end\n",
+            "    cuont\n",
+            "end\n",
+            "f()\n",
+        ));
+        assert_eq!(error, "Runtime error: undefined variable \"cuont\"");
+    }
+}
+
+mod program_io {
+    use crate::builtins;
+    use crate::source::ModuleSource;
+    use crate::codegen::Program;
+    use crate::runtime::{Module, VirtualMachine};
+    
+    fn run_with_input(text: &str, input: &str) -> String {
+        let source = ModuleSource::String(text.to_string());
+        let build = crate::build_module(&source).expect("build failed");
+        let program = Program::load(build.program).with_symbols(build.symbols);
+        
+        let mut output = Vec::new();
+        let main_module = Module::with_env(Some(source), program.data, builtins::create_prelude());
+        let mut vm = VirtualMachine::new(main_module, &program.main);
+        vm.set_output(&mut output);
+        vm.set_input(input.as_bytes());
+        
+        if let Err(error) = vm.run() {
+            panic!("{}{}", error.traceback(), error);
+        }
+        drop(vm);
+        
+        String::from_utf8(output).unwrap()
+    }
+    
+    #[test]
+    fn print_writes_to_vm_output() {
+        let output = run_with_input(concat!(
+            "print(\"a\", 1, [\"b\"])\n",
+            "print()\n",
+            "write(\", \", \";\", 1, 2, 3)\n",
+            "write(\"\", \"\\n\")\n",
+        ), "");
+        assert_eq!(output, "a 1 [\"b\"]\n\n1, 2, 3;\n");
+    }
+    
+    #[test]
+    fn print_writes_dicts_as_values() {
+        let output = run_with_input(concat!(
+            "print({\"end\": \"x\"})\n",
+            "let d = {\"sep\": \"-\"}\n",
+            "print(d)\n",
+            "print(1, d)\n",
+        ), "");
+        assert_eq!(output, "{\"end\": \"x\"}\n{\"sep\": \"-\"}\n1 {\"sep\": \"-\"}\n");
+    }
+    
+    #[test]
+    fn print_formats_instances_with_str_method() {
+        let output = run_with_input(concat!(
+            "class Point\n",
+            "    fun new(x, y) self.x = x; self.y = y end\n",
+            "    fun __str__() f\"({self.x}, {self.y})\" end\n",
+            "end\n",
+            "print(\"p =\", Point(1, 2))\n",
+            "write(\"; \", \"\\n\", Point(3, 4), Point(5, 6))\n",
+        ), "");
+        assert_eq!(output, "p = (1, 2)\n(3, 4); (5, 6)\n");
+    }
+    
+    #[test]
+    fn exit_in_str_method_stops_the_program() {
+        let output = run_with_input(concat!(
+            "class Quit\n",
+            "    fun __str__() os.exit(0); \"unreachable\" end\n",
+            "end\n",
+            "print(\"before\")\n",
+            "print(Quit())\n",
+            "print(\"after\")\n",
+        ), "");
+        assert_eq!(output, "before\n");
+    }
+    
+    #[test]
+    fn input_reads_lines_from_vm_input() {
+        let output = run_with_input(concat!(
+            "let name = input(\"name? \")\n",
+            "print(f\"hello {name}\")\n",
+            "assert input() == \"windows\"\n",
+            "assert input() == \"\"\n",
+            "assert input() == \"last\"\n",
+            "assert input() == nil\n",
+        ), "world\nwindows\r\n\nlast");
+        assert_eq!(output, "name? hello world\n");
+    }
+    
+    #[test]
+    fn exit_stops_the_program() {
+        let source = ModuleSource::String("print(os.args())\nos.exit(os.args()[0] == \"-v\" and 7 or 1)\nprint(\"unreachable\")".to_string());
+        let build = crate::build_module(&source).expect("build failed");
+        let program = Program::load(build.program).with_symbols(build.symbols);
+        
+        let mut output = Vec::new();
+        let main_module = Module::with_env(Some(source), program.data, builtins::create_prelude());
+        let mut vm = VirtualMachine::new(main_module, &program.main);
+        vm.set_output(&mut output);
+        vm.set_args(vec!["-v".to_string()]);
+        
+        assert!(vm.run().is_ok());
+        assert_eq!(vm.exit_code(), Some(7));
+        drop(vm);
+        
+        assert_eq!(String::from_utf8(output).unwrap(), "(\"-v\",)\n");
+    }
+}
+
+mod random {
+    use crate::runtime::random::Random;
+    
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        let mut a = Random::from_seed(99);
+        let mut b = Random::from_seed(99);
+        let mut c = Random::from_seed(100);
+        
+        let first = (0..8).map(|_| a.next_u64()).collect::<Vec<_>>();
+        assert_eq!(first, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(first, (0..8).map(|_| c.next_u64()).collect::<Vec<_>>());
+    }
+    
+    #[test]
+    fn values_stay_in_bounds() {
+        let mut random = Random::from_seed(0);
+        let mut seen = [false; 5];
+        for _ in 0..1000 {
+            let value = random.next_float();
+            assert!((0.0..1.0).contains(&value));
+            
+            let index = random.next_range(-2, 3).unwrap();
+            assert!((-2..3).contains(&index));
+            seen[usize::try_from(index + 2).unwrap()] = true;
+        }
+        assert!(seen.iter().all(|seen| *seen));
+        
+        assert_eq!(random.next_range(4, 4), None);
+        assert_eq!(random.next_index(0), None);
+        assert_eq!(random.next_index(1), Some(0));
+        
+        // the full range of integers is wider than an IntType can hold
+        assert!(random.next_range(crate::language::IntType::MIN, crate::language::IntType::MAX).is_some());
+    }
+}
@@ -1,9 +1,10 @@
 use core::fmt;
+use core::cmp::Ordering;
 use crate::language::{IntType, FloatType};
 use crate::runtime::Variant;
 use crate::runtime::iter::IterState;
 use crate::runtime::function::Call;
-use crate::runtime::strings::{StringValue, static_symbol};
+use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
 use crate::runtime::errors::{ExecResult, RuntimeError};
 
 
@@ -12,15 +13,27 @@ mod dispatch;
 mod metatable;
 mod boolean;
 mod numeric;
+#[cfg(feature = "bigint")]
+mod bigint;
 mod string;
 mod tuple;
+mod list;
+mod dict;
+mod set;
 mod iterator;
 mod misc;
+mod class;
 
 pub use tuple::Tuple;
+pub use list::List;
+pub use dict::Dict;
+pub use set::Set;
 pub use misc::{Marker, UserData};
 pub use numeric::{int_from_str, float_from_str};
+#[cfg(feature = "bigint")]
+pub use bigint::BigInt;
 pub use iterator::UserIterator;
+pub use class::{Class, Instance, BoundMethod, Method, take_pending_finalizers};
 
 use misc::Nil;
 
@@ -37,12 +50,17 @@ pub enum Type {
     Float,
     String,
     Tuple,
+    List,
+    Dict,
+    Set,
     Function,
+    Class,
     Iterator,
     Metatable,
     Object,
     Error,
     UserData,
+    Type,
 }
 
 impl Type {
@@ -55,12 +73,17 @@ impl Type {
             Self::Float => static_symbol!("float"),
             Self::String => static_symbol!("string"),
             Self::Tuple => static_symbol!("tuple"),
+            Self::List => static_symbol!("list"),
+            Self::Dict => static_symbol!("dict"),
+            Self::Set => static_symbol!("set"),
             Self::Function => static_symbol!("function"),
+            Self::Class => static_symbol!("class"),
             Self::Iterator => static_symbol!("iterator"),
             Self::Metatable => static_symbol!("metatable"),
             Self::Object => static_symbol!("object"),
             Self::Error => static_symbol!("error"),
             Self::UserData => static_symbol!("userdata"),
+            Self::Type => static_symbol!("type"),
         };
         name.into()
     }
@@ -82,7 +105,10 @@ pub trait MetaObject {
     }
     
     // formatting
+    // fmt_repr() should produce source text that evaluates to an equal value, where possible (used by the REPL and repr())
+    // fmt_str() produces the human-friendly form used by print() and str()
     fn fmt_repr(&self) -> ExecResult<StringValue>;
+    fn fmt_str(&self) -> ExecResult<StringValue> { self.fmt_repr() }
     
     // primitive coercions
     fn as_bool(&self) -> ExecResult<bool> { Ok(true) }
@@ -99,12 +125,19 @@ pub trait MetaObject {
     
     // collections
     fn len(&self) -> Option<ExecResult<usize>> { None }
-    //fn getitem(&self, item: &Variant) -> Option<ExecResult<Variant>> { None }
-    //fn setitem(&self, item: &Variant) -> Option<ExecResult<Variant>> { None }
+    fn get_index(&self, index: &Variant) -> Option<ExecResult<Variant>> { None }
+    fn set_index(&self, index: &Variant, value: Variant) -> Option<ExecResult<()>> { None }
+    fn has_item(&self, item: &Variant) -> Option<ExecResult<bool>> { None }
     
     // callable
     fn invoke(&self, args: &[Variant]) -> Option<ExecResult<Call>> { None }
     
+    // attribute access
+    fn get_attr(&self, name: &StringSymbol) -> Option<ExecResult<Variant>> { None }
+    fn set_attr(&self, name: &StringSymbol, value: Variant) -> Option<ExecResult<()>> { None }
+    // the names that get_attr() can find, for introspection (e.g. REPL completion)
+    fn attr_names(&self) -> Vec<StringSymbol> { Vec::new() }
+    
     // unary operators
     fn op_neg(&self) -> Option<ExecResult<Variant>> { None }
     fn op_pos(&self) -> Option<ExecResult<Variant>> { None }
@@ -120,6 +153,12 @@ pub trait MetaObject {
     fn op_mod(&self, rhs: &Variant) -> Option<ExecResult<Variant>> { None }
     fn op_rmod(&self, lhs: &Variant) -> Option<ExecResult<Variant>> { None }
     
+    fn op_floordiv(&self, rhs: &Variant) -> Option<ExecResult<Variant>> { None }
+    fn op_rfloordiv(&self, lhs: &Variant) -> Option<ExecResult<Variant>> { None }
+    
+    fn op_exp(&self, rhs: &Variant) -> Option<ExecResult<Variant>> { None }
+    fn op_rexp(&self, lhs: &Variant) -> Option<ExecResult<Variant>> { None }
+    
     fn op_add(&self, rhs: &Variant) -> Option<ExecResult<Variant>> { None }
     fn op_radd(&self, lhs: &Variant) -> Option<ExecResult<Variant>> { None }
     
@@ -181,6 +220,16 @@ impl Variant {
         Ok(self.len()? == 0)
     }
     
+    pub fn get_index(&self, index: &Variant) -> ExecResult<Variant> {
+        self.as_meta().get_index(index)
+            .ok_or_else(|| RuntimeError::metamethod_not_supported(self, MethodTag::GetIndex))?
+    }
+    
+    pub fn set_index(&self, index: &Variant, value: Variant) -> ExecResult<()> {
+        self.as_meta().set_index(index, value)
+            .ok_or_else(|| RuntimeError::metamethod_not_supported(self, MethodTag::SetIndex))?
+    }
+    
     pub fn iter_init(&self) -> ExecResult<IterState> {
         self.as_meta().iter_init()
             .ok_or_else(|| RuntimeError::metamethod_not_supported(self, MethodTag::IterInit))?
@@ -204,13 +253,76 @@ impl Variant {
     pub fn fmt_repr(&self) -> ExecResult<StringValue> {
         self.as_meta().fmt_repr()
     }
+    
+    pub fn fmt_str(&self) -> ExecResult<StringValue> {
+        self.as_meta().fmt_str()
+    }
+    
+    pub fn get_attr(&self, name: &StringSymbol) -> ExecResult<Variant> {
+        self.as_meta().get_attr(name)
+            .ok_or_else(|| RuntimeError::metamethod_not_supported(self, MethodTag::GetAttr))?
+    }
+    
+    pub fn set_attr(&self, name: &StringSymbol, value: Variant) -> ExecResult<()> {
+        self.as_meta().set_attr(name, value)
+            .ok_or_else(|| RuntimeError::metamethod_not_supported(self, MethodTag::SetAttr))?
+    }
+    
+    pub fn attr_names(&self) -> Vec<StringSymbol> {
+        self.as_meta().attr_names()
+    }
+}
+
+// Converts an index into a position within a sequence of the given length.
+// Negative indices count backwards from the end of the sequence.
+fn sequence_index(index: &Variant, len: usize) -> ExecResult<usize> {
+    let index = index.as_int()?;
+    
+    let position = 
+        if index < 0 { IntType::try_from(len).ok().and_then(|len| len.checked_add(index)) }
+        else { Some(index) };
+    
+    position.and_then(|position| usize::try_from(position).ok())
+        .filter(|position| *position < len)
+        .ok_or_else(|| RuntimeError::index_out_of_bounds(index, len))
+}
+
+// Compares two sequences lexicographically.
+fn sequence_cmp(lhs: &[Variant], rhs: &[Variant]) -> ExecResult<Ordering> {
+    for (a, b) in lhs.iter().zip(rhs.iter()) {
+        if a.cmp_lt(b)? {
+            return Ok(Ordering::Less)
+        }
+        if !a.cmp_eq(b)? {
+            return Ok(Ordering::Greater)
+        }
+    }
+    
+    // if we get here then all tested elements were equal
+    // in which case the longer sequence is considered greater
+    Ok(lhs.len().cmp(&rhs.len()))
+}
+
+// Searches a sequence for an item that compares equal to the given one.
+fn sequence_contains(items: &[Variant], item: &Variant) -> ExecResult<bool> {
+    for other in items.iter() {
+        if item.cmp_eq(other)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
 
 // Set of supported metamethods
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MethodTag {
     Invoke,
+    GetAttr,
+    SetAttr,
     Len,
+    GetIndex,
+    SetIndex,
+    Contains,
     IterInit,
     IterNext,
     IterItem,
@@ -226,6 +338,10 @@ impl MethodTag {
         match self {
             Self::Invoke => "call",
             
+            // attribute access
+            Self::GetAttr => "getattr",
+            Self::SetAttr => "setattr",
+            
             // iterators and iterables
             Self::IterInit => "iter_init",
             Self::IterNext => "iter_next",
@@ -233,6 +349,9 @@ impl MethodTag {
             
             // sequences
             Self::Len => "len",
+            Self::GetIndex => "getindex",
+            Self::SetIndex => "setindex",
+            Self::Contains => "contains",
             
             // primitive coercion
             Self::AsBool => "bool",
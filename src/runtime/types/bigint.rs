@@ -0,0 +1,466 @@
+//! Arbitrary-precision integers, used when integer arithmetic would overflow `IntType`.
+//!
+//! BigInt values are always normalized so that any value that fits in an `IntType`
+//! is represented by `Variant::Integer` instead.
+
+use core::cmp::Ordering;
+use core::fmt::{self, Write};
+use crate::language::{IntType, FloatType};
+use crate::runtime::Variant;
+use crate::runtime::gc::{Gc, GcTrace};
+use crate::runtime::strings::StringValue;
+use crate::runtime::types::{Type, MetaObject};
+use crate::runtime::errors::{ExecResult, RuntimeError};
+
+
+type Digit = u32;
+type DoubleDigit = u64;
+const DIGIT_BITS: usize = Digit::BITS as usize;
+
+/// A signed integer stored as sign and magnitude.
+/// The magnitude is stored as little-endian base 2^32 digits, with no trailing zero digits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<Digit>,
+}
+
+unsafe impl GcTrace for BigInt {
+    fn trace(&self) { }
+    
+    fn size_hint(&self) -> usize {
+        core::mem::size_of::<Digit>() * self.digits.capacity()
+    }
+}
+
+impl From<IntType> for BigInt {
+    fn from(value: IntType) -> Self {
+        #[allow(clippy::useless_conversion)]  // IntType may be 32-bit
+        let mut magnitude = u64::from(value.unsigned_abs());
+        let mut digits = Vec::new();
+        while magnitude > 0 {
+            digits.push(magnitude as Digit);
+            magnitude >>= DIGIT_BITS;
+        }
+        Self { negative: value < 0, digits }
+    }
+}
+
+// demote to an integer if possible
+impl From<BigInt> for Variant {
+    fn from(value: BigInt) -> Self {
+        match value.to_int() {
+            Some(value) => Variant::Integer(value),
+            None => Variant::BigInt(Gc::new(value)),
+        }
+    }
+}
+
+impl BigInt {
+    fn new(negative: bool, digits: Vec<Digit>) -> Self {
+        let digits = trim(digits);
+        let negative = negative && !digits.is_empty();
+        Self { negative, digits }
+    }
+    
+    pub fn is_zero(&self) -> bool { self.digits.is_empty() }
+    
+    pub fn is_negative(&self) -> bool { self.negative }
+    
+    pub fn to_int(&self) -> Option<IntType> {
+        if self.digits.len() > IntType::BITS as usize / DIGIT_BITS {
+            return None;
+        }
+        
+        let magnitude = self.digits.iter().rev()
+            .fold(0u64, |acc, digit| (acc << DIGIT_BITS) | u64::from(*digit));
+        
+        let value = i128::from(magnitude);
+        IntType::try_from(if self.negative { -value } else { value }).ok()
+    }
+    
+    pub fn to_float(&self) -> FloatType {
+        let magnitude = self.digits.iter().rev()
+            .fold(0.0, |acc, digit| acc * (DIGIT_BITS as FloatType).exp2() + FloatType::from(*digit));
+        
+        if self.negative { -magnitude } else { magnitude }
+    }
+    
+    pub fn neg(&self) -> BigInt {
+        Self::new(!self.negative, self.digits.clone())
+    }
+    
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return Self::new(self.negative, add_magnitude(&self.digits, &other.digits));
+        }
+        
+        // signs differ, subtract the smaller magnitude from the larger one
+        match cmp_magnitude(&self.digits, &other.digits) {
+            Ordering::Less => Self::new(other.negative, sub_magnitude(&other.digits, &self.digits)),
+            _ => Self::new(self.negative, sub_magnitude(&self.digits, &other.digits)),
+        }
+    }
+    
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+    
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        Self::new(self.negative != other.negative, mul_magnitude(&self.digits, &other.digits))
+    }
+    
+    pub fn pow(&self, mut exp: u32) -> BigInt {
+        let mut result = BigInt::from(1);
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 != 0 {
+                result = result.mul(&base);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+    
+    /// Division that truncates towards zero. The remainder has the same sign as the dividend.
+    pub fn div_rem(&self, other: &BigInt) -> ExecResult<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return Err(RuntimeError::divide_by_zero());
+        }
+        
+        let (quot, rem) = divmod_magnitude(&self.digits, &other.digits);
+        Ok((
+            Self::new(self.negative != other.negative, quot),
+            Self::new(self.negative, rem),
+        ))
+    }
+    
+    /// Division that rounds towards negative infinity.
+    pub fn floor_div(&self, other: &BigInt) -> ExecResult<BigInt> {
+        let (quot, rem) = self.div_rem(other)?;
+        if !rem.is_zero() && (rem.negative != other.negative) {
+            Ok(quot.sub(&BigInt::from(1)))
+        } else {
+            Ok(quot)
+        }
+    }
+    
+    pub fn div(&self, other: &BigInt) -> ExecResult<BigInt> {
+        self.div_rem(other).map(|(quot, _)| quot)
+    }
+    
+    pub fn rem(&self, other: &BigInt) -> ExecResult<BigInt> {
+        self.div_rem(other).map(|(_, rem)| rem)
+    }
+    
+    /// The remainder of floor division. The result has the same sign as the divisor.
+    pub fn floor_mod(&self, other: &BigInt) -> ExecResult<BigInt> {
+        let rem = self.rem(other)?;
+        if !rem.is_zero() && (rem.negative != other.negative) {
+            Ok(rem.add(other))
+        } else {
+            Ok(rem)
+        }
+    }
+    
+    /// Parses digits in the given radix, which must be between 2 and 36, after an optional "-" sign.
+    pub fn from_str_radix(s: &str, radix: u32) -> Option<BigInt> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        
+        if digits.is_empty() {
+            return None;
+        }
+        
+        let mut magnitude = Vec::new();
+        for c in digits.chars() {
+            mul_add_digit(&mut magnitude, radix, c.to_digit(radix)?);
+        }
+        Some(Self::new(negative, magnitude))
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.digits, &other.digits),
+            (true, true) => cmp_magnitude(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // convert to base 10^9 digits, which can each be written directly
+        const DECIMAL_BASE: Digit = 1_000_000_000;
+        
+        let mut magnitude = self.digits.clone();
+        let mut chunks = Vec::new();
+        while !magnitude.is_empty() {
+            let rem = divmod_digit(&mut magnitude, DECIMAL_BASE);
+            chunks.push(rem);
+        }
+        
+        if self.negative {
+            fmt.write_char('-')?;
+        }
+        
+        let mut chunks = chunks.iter().rev();
+        match chunks.next() {
+            Some(first) => write!(fmt, "{}", first)?,
+            None => fmt.write_char('0')?,
+        }
+        for chunk in chunks {
+            write!(fmt, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+
+// Magnitude arithmetic
+
+fn trim(mut digits: Vec<Digit>) -> Vec<Digit> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+fn cmp_magnitude(lhs: &[Digit], rhs: &[Digit]) -> Ordering {
+    lhs.len().cmp(&rhs.len())
+        .then_with(|| lhs.iter().rev().cmp(rhs.iter().rev()))
+}
+
+fn add_magnitude(lhs: &[Digit], rhs: &[Digit]) -> Vec<Digit> {
+    let (long, short) = if lhs.len() >= rhs.len() { (lhs, rhs) } else { (rhs, lhs) };
+    
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0;
+    for (idx, digit) in long.iter().enumerate() {
+        let sum = DoubleDigit::from(*digit) + DoubleDigit::from(short.get(idx).copied().unwrap_or(0)) + carry;
+        result.push(sum as Digit);
+        carry = sum >> DIGIT_BITS;
+    }
+    if carry > 0 {
+        result.push(carry as Digit);
+    }
+    result
+}
+
+// requires lhs >= rhs
+fn sub_magnitude(lhs: &[Digit], rhs: &[Digit]) -> Vec<Digit> {
+    debug_assert!(cmp_magnitude(lhs, rhs) != Ordering::Less);
+    
+    let mut result = Vec::with_capacity(lhs.len());
+    let mut borrow = false;
+    for (idx, digit) in lhs.iter().enumerate() {
+        let (diff, overflow1) = digit.overflowing_sub(rhs.get(idx).copied().unwrap_or(0));
+        let (diff, overflow2) = diff.overflowing_sub(Digit::from(borrow));
+        result.push(diff);
+        borrow = overflow1 || overflow2;
+    }
+    trim(result)
+}
+
+fn mul_magnitude(lhs: &[Digit], rhs: &[Digit]) -> Vec<Digit> {
+    if lhs.is_empty() || rhs.is_empty() {
+        return Vec::new();
+    }
+    
+    let mut result = vec![0; lhs.len() + rhs.len()];
+    for (i, a) in lhs.iter().enumerate() {
+        let mut carry = 0;
+        for (j, b) in rhs.iter().enumerate() {
+            let prod = DoubleDigit::from(*a) * DoubleDigit::from(*b) + DoubleDigit::from(result[i + j]) + carry;
+            result[i + j] = prod as Digit;
+            carry = prod >> DIGIT_BITS;
+        }
+        result[i + rhs.len()] = carry as Digit;
+    }
+    trim(result)
+}
+
+// multiplies the magnitude in place by a single digit, then adds another
+fn mul_add_digit(digits: &mut Vec<Digit>, mul: Digit, add: Digit) {
+    let mut carry = DoubleDigit::from(add);
+    for digit in digits.iter_mut() {
+        let acc = DoubleDigit::from(*digit) * DoubleDigit::from(mul) + carry;
+        *digit = acc as Digit;
+        carry = acc >> DIGIT_BITS;
+    }
+    if carry > 0 {
+        digits.push(carry as Digit);
+    }
+}
+
+// divides the magnitude in place by a single digit, returning the remainder
+fn divmod_digit(digits: &mut Vec<Digit>, divisor: Digit) -> Digit {
+    let mut rem: DoubleDigit = 0;
+    for digit in digits.iter_mut().rev() {
+        let acc = (rem << DIGIT_BITS) | DoubleDigit::from(*digit);
+        *digit = (acc / DoubleDigit::from(divisor)) as Digit;
+        rem = acc % DoubleDigit::from(divisor);
+    }
+    *digits = trim(core::mem::take(digits));
+    rem as Digit
+}
+
+// shift-and-subtract long division, requires divisor to be non-zero
+fn divmod_magnitude(dividend: &[Digit], divisor: &[Digit]) -> (Vec<Digit>, Vec<Digit>) {
+    debug_assert!(!divisor.is_empty());
+    
+    if cmp_magnitude(dividend, divisor) == Ordering::Less {
+        return (Vec::new(), dividend.to_vec());
+    }
+    
+    if let [divisor] = divisor {
+        let mut quot = dividend.to_vec();
+        let rem = divmod_digit(&mut quot, *divisor);
+        return (quot, trim(vec![rem]));
+    }
+    
+    let mut quot = vec![0; dividend.len()];
+    let mut rem = Vec::new();
+    for bit in (0..dividend.len() * DIGIT_BITS).rev() {
+        // rem = (rem << 1) | next bit of dividend
+        let next_bit = (dividend[bit / DIGIT_BITS] >> (bit % DIGIT_BITS)) & 1;
+        let mut carry = next_bit;
+        for digit in rem.iter_mut() {
+            let shifted = (*digit << 1) | carry;
+            carry = *digit >> (DIGIT_BITS - 1);
+            *digit = shifted;
+        }
+        if carry > 0 {
+            rem.push(carry);
+        }
+        
+        if cmp_magnitude(&rem, divisor) != Ordering::Less {
+            rem = sub_magnitude(&rem, divisor);
+            quot[bit / DIGIT_BITS] |= 1 << (bit % DIGIT_BITS);
+        }
+    }
+    (trim(quot), rem)
+}
+
+
+// Integer operands are promoted to BigInt. Other types are left to the reflected operator.
+fn to_bigint(value: &Variant) -> Option<ExecResult<BigInt>> {
+    match value {
+        Variant::BigInt(value) => Some(Ok((**value).clone())),
+        Variant::Integer(value) => Some(Ok(BigInt::from(*value))),
+        _ => value.as_meta().as_int().map(|value| value.map(BigInt::from)),
+    }
+}
+
+fn big_exp(lhs: &BigInt, rhs: &BigInt) -> ExecResult<Variant> {
+    // negative exponents produce a fractional result
+    if rhs.is_negative() {
+        return Ok(Variant::from(lhs.to_float().powf(rhs.to_float())));
+    }
+    
+    let exp = rhs.to_int().and_then(|exp| u32::try_from(exp).ok())
+        .ok_or_else(RuntimeError::overflow_error)?;
+    Ok(lhs.pow(exp).into())
+}
+
+impl MetaObject for Gc<BigInt> {
+    fn type_tag(&self) -> Type { Type::Integer }
+    
+    // BigInts never fit in an IntType, so conversion will always overflow
+    fn as_bits(&self) -> Option<ExecResult<IntType>> { Some(Err(RuntimeError::overflow_error())) }
+    fn as_int(&self) -> Option<ExecResult<IntType>> { Some(Err(RuntimeError::overflow_error())) }
+    fn as_float(&self) -> Option<ExecResult<FloatType>> { Some(Ok(self.to_float())) }
+    
+    fn op_neg(&self) -> Option<ExecResult<Variant>> { Some(Ok(BigInt::neg(self).into())) }
+    fn op_pos(&self) -> Option<ExecResult<Variant>> { Some(Ok(Variant::BigInt(*self))) }
+    
+    fn op_mul(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(rhs).map(|rhs| Ok(BigInt::mul(self, &rhs?).into()))
+    }
+    
+    fn op_rmul(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        self.op_mul(lhs)
+    }
+    
+    fn op_div(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(rhs).map(|rhs| BigInt::div(self, &rhs?).map(Variant::from))
+    }
+    
+    fn op_rdiv(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(lhs).map(|lhs| lhs?.div(self).map(Variant::from))
+    }
+    
+    fn op_mod(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(rhs).map(|rhs| BigInt::floor_mod(self, &rhs?).map(Variant::from))
+    }
+    
+    fn op_rmod(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(lhs).map(|lhs| lhs?.floor_mod(self).map(Variant::from))
+    }
+    
+    fn op_floordiv(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(rhs).map(|rhs| BigInt::floor_div(self, &rhs?).map(Variant::from))
+    }
+    
+    fn op_rfloordiv(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(lhs).map(|lhs| lhs?.floor_div(self).map(Variant::from))
+    }
+    
+    fn op_exp(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(rhs).map(|rhs| big_exp(self, &rhs?))
+    }
+    
+    fn op_rexp(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(lhs).map(|lhs| big_exp(&lhs?, self))
+    }
+    
+    fn op_add(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(rhs).map(|rhs| Ok(BigInt::add(self, &rhs?).into()))
+    }
+    
+    fn op_radd(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        self.op_add(lhs)
+    }
+    
+    fn op_sub(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(rhs).map(|rhs| Ok(BigInt::sub(self, &rhs?).into()))
+    }
+    
+    fn op_rsub(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        to_bigint(lhs).map(|lhs| Ok(lhs?.sub(self).into()))
+    }
+    
+    fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        match other {
+            Variant::BigInt(other) => Some(Ok(**self == **other)),
+            Variant::Integer(..) => Some(Ok(false)),  // BigInts are normalized
+            _ => None,
+        }
+    }
+    
+    fn cmp_lt(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        to_bigint(other).map(|other| Ok(**self < other?))
+    }
+    
+    fn cmp_le(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        to_bigint(other).map(|other| Ok(**self <= other?))
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        Ok(StringValue::new_uninterned(self.to_string()))
+    }
+}
@@ -0,0 +1,343 @@
+use core::cell::{RefCell, Ref};
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::runtime::{Variant, HashMap, DefaultBuildHasher};
+use crate::runtime::gc::{Gc, GcTrace, gc_barrier};
+use crate::runtime::slots::SlotMap;
+use crate::runtime::function::{Call, Function, NativeMethod};
+use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
+use crate::runtime::types::{Type, MetaObject};
+use crate::runtime::errors::{ExecResult, RuntimeError};
+
+
+/// A user-defined type. Calling a class creates a new instance and passes it to the constructor.
+#[derive(Debug)]
+pub struct Class {
+    id: u64,
+    name: Option<StringSymbol>,
+    parent: Option<Gc<Class>>,
+    methods: HashMap<StringSymbol, Gc<Function>>,
+}
+
+unsafe impl GcTrace for Class {
+    fn trace(&self) {
+        if let Some(parent) = self.parent {
+            parent.mark_trace();
+        }
+        for method in self.methods.values() {
+            method.mark_trace();
+        }
+    }
+    
+    fn size_hint(&self) -> usize {
+        core::mem::size_of::<(StringSymbol, Gc<Function>)>() * self.methods.capacity()
+    }
+}
+
+static NEXT_CLASS_ID: AtomicU64 = AtomicU64::new(0);
+
+impl Class {
+    pub fn new(name: Option<StringSymbol>, parent: Option<Gc<Class>>, methods: impl Iterator<Item=(StringSymbol, Gc<Function>)>) -> Self {
+        let mut method_table = HashMap::with_hasher(DefaultBuildHasher::default());
+        method_table.extend(methods);
+        
+        Self {
+            id: NEXT_CLASS_ID.fetch_add(1, Ordering::Relaxed),
+            name, parent, methods: method_table,
+        }
+    }
+    
+    /// Uniquely identifies this class, even after it has been freed.
+    /// Classes can't be modified once created, so method lookups can be cached using this ID.
+    pub fn id(&self) -> u64 { self.id }
+    
+    pub fn name(&self) -> Option<StringSymbol> { self.name }
+    
+    pub fn parent(&self) -> Option<Gc<Class>> { self.parent }
+    
+    /// Check if this class is the given class or inherits from it
+    pub fn is_subclass(&self, ancestor: &Class) -> bool {
+        let mut class = Some(self);
+        while let Some(next) = class {
+            if next.id == ancestor.id {
+                return true;
+            }
+            class = next.parent.as_deref();
+        }
+        false
+    }
+    
+    /// Find a method defined by this class or inherited from one of its ancestors
+    pub fn lookup_method(&self, name: &StringSymbol) -> Option<Gc<Function>> {
+        let mut class = self;
+        loop {
+            if let Some(method) = class.methods.get(name) {
+                return Some(*method);
+            }
+            class = class.parent.as_deref()?;
+        }
+    }
+    
+    /// The names of all methods defined by this class or inherited from its ancestors
+    pub fn method_names(&self) -> Vec<StringSymbol> {
+        let mut names = Vec::new();
+        let mut class = Some(self);
+        while let Some(next) = class {
+            // overridden methods are only listed once
+            for name in next.methods.keys() {
+                if !names.contains(name) {
+                    names.push(*name);
+                }
+            }
+            class = next.parent.as_deref();
+        }
+        names
+    }
+    
+    fn fmt_name(&self) -> StringValue {
+        match self.name {
+            Some(name) => StringValue::from(name),
+            None => StringValue::from(static_symbol!("<anonymous>")),
+        }
+    }
+}
+
+impl MetaObject for Gc<Class> {
+    fn type_tag(&self) -> Type { Type::Class }
+    
+    fn invoke(&self, args: &[Variant]) -> Option<ExecResult<Call>> {
+        let ctor = match self.lookup_method(&static_symbol!("new")) {
+            Some(ctor) => ctor,
+            None => return Some(Err(RuntimeError::metamethod_not_supported(&Variant::Class(*self), super::MethodTag::Invoke))),
+        };
+        
+        let instance = Gc::new(Instance::new(*self));
+        if self.lookup_method(&static_symbol!("__del__")).is_some() {
+            instance.set_finalizer(Instance::finalize);
+        }
+        
+        Some(ctor.method_call(Variant::Instance(instance), args))
+    }
+    
+    fn get_attr(&self, name: &StringSymbol) -> Option<ExecResult<Variant>> {
+        let result = self.lookup_method(name)
+            .map(Variant::Function)
+            .ok_or_else(|| RuntimeError::attribute_not_found(&Variant::Class(*self), *name));
+        
+        Some(result)
+    }
+    
+    fn attr_names(&self) -> Vec<StringSymbol> {
+        self.method_names()
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        let result = format!("<class \"{}\">", self.fmt_name());
+        Ok(StringValue::new_uninterned(result))
+    }
+    
+    fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        match other {
+            Variant::Class(other) => Some(Ok(Gc::ptr_eq(self, other))),
+            _ => Some(Ok(false)),
+        }
+    }
+}
+
+
+/// An instance of a user-defined class
+#[derive(Debug)]
+pub struct Instance {
+    class: Gc<Class>,
+    fields: RefCell<SlotMap<Variant>>,
+}
+
+unsafe impl GcTrace for Instance {
+    fn trace(&self) {
+        self.class.mark_trace();
+        for value in self.fields.borrow().values() {
+            value.trace();
+        }
+    }
+    
+    fn size_hint(&self) -> usize {
+        core::mem::size_of::<(StringSymbol, Variant)>() * self.fields.borrow().capacity()
+    }
+}
+
+impl Instance {
+    pub fn new(class: Gc<Class>) -> Self {
+        Self {
+            class,
+            fields: RefCell::new(SlotMap::new()),
+        }
+    }
+    
+    pub fn class(&self) -> Gc<Class> { self.class }
+    
+    pub(crate) fn fields(&self) -> Ref<'_, SlotMap<Variant>> { self.fields.borrow() }
+    
+    // the __del__ method can only be called by the VM, so just queue up the instance
+    fn finalize(instance: Gc<Instance>) {
+        PENDING_FINALIZERS.with(|pending| pending.borrow_mut().push(instance))
+    }
+}
+
+thread_local! {
+    static PENDING_FINALIZERS: RefCell<Vec<Gc<Instance>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Take the unreachable instances whose `__del__` method needs to be called.
+/// Pending instances are not traced by the GC, so they should be taken right after every collection.
+pub fn take_pending_finalizers() -> Vec<Gc<Instance>> {
+    PENDING_FINALIZERS.with(|pending| pending.take())
+}
+
+impl MetaObject for Gc<Instance> {
+    fn type_tag(&self) -> Type { Type::Object }
+    
+    fn type_name(&self) -> ExecResult<StringValue> {
+        Ok(self.class.fmt_name())
+    }
+    
+    // fields shadow methods
+    fn get_attr(&self, name: &StringSymbol) -> Option<ExecResult<Variant>> {
+        if let Some(value) = self.fields.borrow().get(name) {
+            return Some(Ok(*value));
+        }
+        
+        let receiver = Variant::Instance(*self);
+        let result = self.class.lookup_method(name)
+            .map(|method| Variant::BoundMethod(Gc::new(BoundMethod::new(receiver, method.into()))))
+            .ok_or_else(|| RuntimeError::attribute_not_found(&receiver, *name));
+        
+        Some(result)
+    }
+    
+    fn set_attr(&self, name: &StringSymbol, value: Variant) -> Option<ExecResult<()>> {
+        let old_value = self.fields.borrow_mut().insert(*name, value);
+        gc_barrier(&old_value);
+        Some(Ok(()))
+    }
+    
+    fn attr_names(&self) -> Vec<StringSymbol> {
+        let mut names = self.fields.borrow().keys().copied().collect::<Vec<_>>();
+        for name in self.class.method_names() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        let result = format!(
+            "<{} object at {:#X}>", self.class.fmt_name(), Gc::as_id(self),
+        );
+        Ok(StringValue::new_uninterned(result))
+    }
+    
+    fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        match other {
+            Variant::Instance(other) => Some(Ok(Gc::ptr_eq(self, other))),
+            _ => None,
+        }
+    }
+}
+
+
+/// The function called by a bound method
+#[derive(Debug, Clone, Copy)]
+pub enum Method {
+    Function(Gc<Function>),
+    Native(&'static NativeMethod),
+}
+
+impl From<Gc<Function>> for Method {
+    fn from(function: Gc<Function>) -> Self { Self::Function(function) }
+}
+
+impl From<&'static NativeMethod> for Method {
+    fn from(method: &'static NativeMethod) -> Self { Self::Native(method) }
+}
+
+impl Method {
+    pub fn fmt_signature(&self) -> StringValue {
+        match self {
+            Self::Function(function) => function.signature().fmt_signature(),
+            Self::Native(method) => method.signature().fmt_signature(),
+        }
+    }
+    
+    fn fmt_name(&self) -> StringValue {
+        match self {
+            Self::Function(function) => function.signature().fmt_name(),
+            Self::Native(method) => StringValue::new_uninterned(format!("method \"{}()\"", method.name)),
+        }
+    }
+    
+    fn is_same(&self, other: &Method) -> bool {
+        match (self, other) {
+            (Self::Function(a), Self::Function(b)) => Gc::ptr_eq(a, b),
+            (Self::Native(a), Self::Native(b)) => core::ptr::eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+
+/// A method that has been accessed through an instance
+#[derive(Debug)]
+pub struct BoundMethod {
+    receiver: Variant,
+    method: Method,
+}
+
+unsafe impl GcTrace for BoundMethod {
+    fn trace(&self) {
+        self.receiver.trace();
+        if let Method::Function(function) = self.method {
+            function.mark_trace();
+        }
+    }
+}
+
+impl BoundMethod {
+    pub fn new(receiver: Variant, method: Method) -> Self {
+        Self { receiver, method }
+    }
+    
+    pub fn receiver(&self) -> &Variant { &self.receiver }
+    
+    pub fn method(&self) -> Method { self.method }
+}
+
+impl MetaObject for Gc<BoundMethod> {
+    fn type_tag(&self) -> Type { Type::Function }
+    
+    fn invoke(&self, args: &[Variant]) -> Option<ExecResult<Call>> {
+        let call = match self.method {
+            Method::Function(function) => function.method_call(self.receiver, args),
+            Method::Native(method) => method.method_call(self.receiver, args),
+        };
+        Some(call)
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        let result = format!(
+            "<bound {} of {}>", self.method.fmt_name(), self.receiver.fmt_repr()?,
+        );
+        Ok(StringValue::new_uninterned(result))
+    }
+    
+    // bound methods are equal if they bind the same method to the same receiver
+    fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        match other {
+            Variant::BoundMethod(other) => {
+                let result = self.method.is_same(&other.method)
+                    && self.receiver.cmp_eq(&other.receiver).unwrap_or(false);
+                Some(Ok(result))
+            },
+            _ => Some(Ok(false)),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use core::cell::RefCell;
+use core::fmt::Write;
+use crate::runtime::{Variant, VariantKey, HashMap, DefaultBuildHasher};
+use crate::runtime::gc::{Gc, GcTrace, gc_barrier};
+use crate::runtime::function::NativeMethod;
+use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
+use crate::runtime::iter::IterState;
+use crate::runtime::types::{Type, MetaObject, Tuple, BoundMethod};
+use crate::runtime::errors::{ExecResult, RuntimeError};
+
+
+/// A mutable mapping from hashable keys to values
+#[derive(Debug)]
+pub struct Dict {
+    entries: RefCell<HashMap<VariantKey, Variant>>,
+}
+
+unsafe impl GcTrace for Dict {
+    fn trace(&self) {
+        for (key, value) in self.entries.borrow().iter() {
+            key.trace();
+            value.trace();
+        }
+    }
+    
+    fn size_hint(&self) -> usize {
+        core::mem::size_of::<(VariantKey, Variant)>() * self.entries.borrow().capacity()
+    }
+}
+
+impl Default for Dict {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl Dict {
+    pub fn with_capacity(capacity: usize) -> Self {
+        let entries = HashMap::with_capacity_and_hasher(capacity, DefaultBuildHasher::default());
+        Self { entries: RefCell::new(entries) }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+    
+    pub fn get(&self, key: &VariantKey) -> Option<Variant> {
+        self.entries.borrow().get(key).copied()
+    }
+    
+    pub fn insert(&self, key: VariantKey, value: Variant) -> Option<Variant> {
+        let old_value = self.entries.borrow_mut().insert(key, value);
+        gc_barrier(&old_value);
+        old_value
+    }
+    
+    pub fn remove(&self, key: &VariantKey) -> Option<Variant> {
+        let (key, value) = self.entries.borrow_mut().remove_entry(key)?;
+        gc_barrier(&key);
+        gc_barrier(&value);
+        Some(value)
+    }
+    
+    pub fn contains_key(&self, key: &VariantKey) -> bool {
+        self.entries.borrow().contains_key(key)
+    }
+    
+    // copy the current contents, so that the dict is not borrowed while the entries are being used
+    pub fn to_vec(&self) -> Vec<(Variant, Variant)> {
+        self.entries.borrow().iter()
+            .map(|(key, value)| (*key.value(), *value))
+            .collect()
+    }
+}
+
+
+// Methods
+
+fn into_dict(receiver: &Variant) -> Gc<Dict> {
+    match receiver {
+        Variant::Dict(dict) => *dict,
+        _ => panic!("invalid receiver"),
+    }
+}
+
+static DICT_GET: NativeMethod = NativeMethod {
+    name: "get", params: &["key"],
+    func: |receiver, args| {
+        let key = VariantKey::try_from(args[0])?;
+        Ok(into_dict(receiver).get(&key).unwrap_or(Variant::Nil))
+    },
+};
+
+static DICT_SET: NativeMethod = NativeMethod {
+    name: "set", params: &["key", "value"],
+    func: |receiver, args| {
+        let key = VariantKey::try_from(args[0])?;
+        into_dict(receiver).insert(key, args[1]);
+        Ok(Variant::Nil)
+    },
+};
+
+static DICT_DELETE: NativeMethod = NativeMethod {
+    name: "delete", params: &["key"],
+    func: |receiver, args| {
+        let key = VariantKey::try_from(args[0])?;
+        into_dict(receiver).remove(&key)
+            .ok_or_else(|| RuntimeError::key_not_found(&args[0]))
+    },
+};
+
+static DICT_CONTAINS: NativeMethod = NativeMethod {
+    name: "contains", params: &["key"],
+    func: |receiver, args| {
+        let key = VariantKey::try_from(args[0])?;
+        Ok(Variant::from(into_dict(receiver).contains_key(&key)))
+    },
+};
+
+static DICT_METHODS: &[&NativeMethod] = &[&DICT_GET, &DICT_SET, &DICT_DELETE, &DICT_CONTAINS];
+
+fn get_method(name: &StringSymbol) -> Option<&'static NativeMethod> {
+    if *name == static_symbol!("get") {
+        return Some(&DICT_GET);
+    }
+    if *name == static_symbol!("set") {
+        return Some(&DICT_SET);
+    }
+    if *name == static_symbol!("delete") {
+        return Some(&DICT_DELETE);
+    }
+    if *name == static_symbol!("contains") {
+        return Some(&DICT_CONTAINS);
+    }
+    None
+}
+
+
+impl MetaObject for Gc<Dict> {
+    fn type_tag(&self) -> Type { Type::Dict }
+    
+    fn len(&self) -> Option<ExecResult<usize>> {
+        Some(Ok(Dict::len(self)))
+    }
+    
+    fn get_index(&self, index: &Variant) -> Option<ExecResult<Variant>> {
+        let result = VariantKey::try_from(*index).and_then(
+            |key| self.get(&key).ok_or_else(|| RuntimeError::key_not_found(index))
+        );
+        Some(result)
+    }
+    
+    fn set_index(&self, index: &Variant, value: Variant) -> Option<ExecResult<()>> {
+        let result = VariantKey::try_from(*index)
+            .map(|key| { self.insert(key, value); });
+        Some(result)
+    }
+    
+    // dicts contain their keys
+    fn has_item(&self, item: &Variant) -> Option<ExecResult<bool>> {
+        let result = VariantKey::try_from(*item)
+            .map(|key| self.contains_key(&key));
+        Some(result)
+    }
+    
+    fn get_attr(&self, name: &StringSymbol) -> Option<ExecResult<Variant>> {
+        let receiver = Variant::Dict(*self);
+        let result = get_method(name)
+            .map(|method| Variant::BoundMethod(Gc::new(BoundMethod::new(receiver, method.into()))))
+            .ok_or_else(|| RuntimeError::attribute_not_found(&receiver, *name));
+        
+        Some(result)
+    }
+    
+    fn attr_names(&self) -> Vec<StringSymbol> {
+        DICT_METHODS.iter().map(|method| StringSymbol::from(method.name)).collect()
+    }
+    
+    // iterates over a snapshot of the entries as (key, value) tuples
+    fn iter_init(&self) -> Option<ExecResult<IterState>> {
+        let entries = self.to_vec().into_iter()
+            .map(|(key, value)| Variant::Tuple(Tuple::from(vec![key, value].into_boxed_slice())))
+            .collect::<Vec<Variant>>();
+        
+        Tuple::from(entries.into_boxed_slice()).iter_init()
+    }
+    
+    fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        if let Variant::Dict(other) = other {
+            if Gc::ptr_eq(self, other) {
+                return Some(Ok(true));
+            }
+            return Some(dict_eq(self, other));
+        }
+        None
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        let entries = self.to_vec();
+        
+        let mut buf = String::new();
+        buf.push('{');
+        
+        for (idx, (key, value)) in entries.iter().enumerate() {
+            if idx > 0 {
+                buf.push_str(", ");
+            }
+            write!(&mut buf, "{}: {}", key.fmt_repr()?, value.fmt_repr()?)
+                .map_err(|err| RuntimeError::other(err.to_string()))?;
+        }
+        buf.push('}');
+        
+        Ok(StringValue::new_uninterned(buf))
+    }
+}
+
+fn dict_eq(lhs: &Dict, rhs: &Dict) -> ExecResult<bool> {
+    if lhs.len() != rhs.len() {
+        return Ok(false);
+    }
+    
+    for (key, value) in lhs.to_vec().iter() {
+        let other = VariantKey::try_from(*key)
+            .ok().and_then(|key| rhs.get(&key));
+        
+        match other {
+            Some(other) if value.cmp_eq(&other)? => { },
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
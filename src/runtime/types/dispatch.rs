@@ -1,13 +1,15 @@
-///! Enum-based static dispatch for `MetaObject`
+//! Enum-based static dispatch for `MetaObject`
 
 use crate::language::{IntType, FloatType};
 use crate::runtime::Variant;
 use crate::runtime::gc::Gc;
 use crate::runtime::function::{Call, Function, NativeFunction};
-use crate::runtime::strings::StringValue;
+use crate::runtime::strings::{StringValue, StringSymbol};
 use crate::runtime::iter::IterState;
-use crate::runtime::types::{Type, MetaObject, Tuple, UserData, Nil, Marker, UserIterator};
+use crate::runtime::types::{Type, MetaObject, Tuple, UserData, Nil, Marker, List, Dict, Set, UserIterator, Class, Instance, BoundMethod};
 use crate::runtime::errors::{ExecResult, RuntimeError};
+#[cfg(feature = "bigint")]
+use crate::runtime::types::BigInt;
 
 
 /// Newtype wrapper for `Variant` that impls `MetaObject` using enum-based static dispatch.
@@ -37,24 +39,34 @@ macro_rules! static_dispatch {
                 Variant::BoolFalse => <bool as MetaObject>::$name(&false, $( $arg ),* ),
                 
                 Variant::Marker(marker) => <Marker as MetaObject>::$name(marker, $( $arg ),* ),
+                Variant::Type(ty) => <Type as MetaObject>::$name(ty, $( $arg ),* ),
                 
                 Variant::Integer(value) => <IntType as MetaObject>::$name(value, $( $arg ),* ),
                 Variant::Float(value) => <FloatType as MetaObject>::$name(value, $( $arg ),* ),
+                #[cfg(feature = "bigint")]
+                Variant::BigInt(value) => <Gc<BigInt> as MetaObject>::$name(value, $( $arg ),* ),
                 
                 Variant::InternStr(symbol) => <StringValue as MetaObject>::$name(&(*symbol).into(), $( $arg ),* ),
                 Variant::InlineStr(inline) => <StringValue as MetaObject>::$name(&(*inline).into(), $( $arg ),* ),
                 Variant::GCStr(gc_str) => <StringValue as MetaObject>::$name(&(*gc_str).into(), $( $arg ),* ),
                 
                 Variant::Tuple(tuple) => <Tuple as MetaObject>::$name(tuple, $( $arg ),* ),
+                Variant::List(list) => <Gc<List> as MetaObject>::$name(list, $( $arg ),* ),
+                Variant::Dict(dict) => <Gc<Dict> as MetaObject>::$name(dict, $( $arg ),* ),
+                Variant::Set(set) => <Gc<Set> as MetaObject>::$name(set, $( $arg ),* ),
                 
                 Variant::Function(fun) => <Gc<Function> as MetaObject>::$name(fun, $( $arg ),* ),
                 Variant::NativeFunction(fun) => <Gc<NativeFunction> as MetaObject>::$name(fun, $( $arg ),* ),
+                Variant::BoundMethod(method) => <Gc<BoundMethod> as MetaObject>::$name(method, $( $arg ),* ),
+                
+                Variant::Class(class) => <Gc<Class> as MetaObject>::$name(class, $( $arg ),* ),
+                Variant::Instance(instance) => <Gc<Instance> as MetaObject>::$name(instance, $( $arg ),* ),
                 
                 Variant::Error(error) => <Gc<RuntimeError> as MetaObject>::$name(error, $( $arg ),* ),
                 
                 Variant::Iterator(iter) => <Gc<dyn UserIterator> as MetaObject>::$name(iter, $( $arg ),* ),
                 
-                Variant::UserData(data) => <(dyn UserData + 'static) as MetaObject>::$name(&**data, $( $arg ),* ),
+                Variant::UserData(data) => <dyn UserData as MetaObject>::$name(&**data, $( $arg ),* ),
             }
         }
     };
@@ -66,6 +78,7 @@ impl MetaObject for MetaDispatch<'_> {
     static_dispatch!{ fn type_name() -> ExecResult<StringValue> }
     
     static_dispatch!{ fn fmt_repr() -> ExecResult<StringValue> }
+    static_dispatch!{ fn fmt_str() -> ExecResult<StringValue> }
     
     static_dispatch!{ fn as_bool() -> ExecResult<bool> }
     static_dispatch!{ fn as_bits() -> Option<ExecResult<IntType>> }
@@ -79,10 +92,18 @@ impl MetaObject for MetaDispatch<'_> {
     
     // collections
     static_dispatch!{ fn len() -> Option<ExecResult<usize>> }
+    static_dispatch!{ fn get_index(index: &Variant) -> Option<ExecResult<Variant>> }
+    static_dispatch!{ fn set_index(index: &Variant, value: Variant) -> Option<ExecResult<()>> }
+    static_dispatch!{ fn has_item(item: &Variant) -> Option<ExecResult<bool>> }
     
     // callable
     static_dispatch!{ fn invoke(args: &[Variant]) -> Option<ExecResult<Call>> }
     
+    // attribute access
+    static_dispatch!{ fn get_attr(name: &StringSymbol) -> Option<ExecResult<Variant>> }
+    static_dispatch!{ fn set_attr(name: &StringSymbol, value: Variant) -> Option<ExecResult<()>> }
+    static_dispatch!{ fn attr_names() -> Vec<StringSymbol> }
+    
     // unary operators
     static_dispatch!{ fn op_neg() -> Option<ExecResult<Variant>> }
     static_dispatch!{ fn op_pos() -> Option<ExecResult<Variant>> }
@@ -98,6 +119,12 @@ impl MetaObject for MetaDispatch<'_> {
     static_dispatch!{ fn op_mod(rhs: &Variant) -> Option<ExecResult<Variant>> }
     static_dispatch!{ fn op_rmod(lhs: &Variant) -> Option<ExecResult<Variant>> }
     
+    static_dispatch!{ fn op_floordiv(rhs: &Variant) -> Option<ExecResult<Variant>> }
+    static_dispatch!{ fn op_rfloordiv(lhs: &Variant) -> Option<ExecResult<Variant>> }
+    
+    static_dispatch!{ fn op_exp(rhs: &Variant) -> Option<ExecResult<Variant>> }
+    static_dispatch!{ fn op_rexp(lhs: &Variant) -> Option<ExecResult<Variant>> }
+    
     static_dispatch!{ fn op_add(rhs: &Variant) -> Option<ExecResult<Variant>> }
     static_dispatch!{ fn op_radd(lhs: &Variant) -> Option<ExecResult<Variant>> }
     
@@ -0,0 +1,253 @@
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::fmt::Write;
+use crate::runtime::Variant;
+use crate::runtime::gc::{Gc, GcTrace, gc_barrier};
+use crate::runtime::function::NativeMethod;
+use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
+use crate::runtime::iter::IterState;
+use crate::runtime::types::{Type, MetaObject, UserIterator, BoundMethod, sequence_index, sequence_contains, sequence_cmp};
+use crate::runtime::errors::{ExecResult, RuntimeError};
+
+
+/// A mutable, growable sequence
+#[derive(Debug, Default)]
+pub struct List {
+    items: RefCell<Vec<Variant>>,
+}
+
+unsafe impl GcTrace for List {
+    fn trace(&self) {
+        for item in self.items.borrow().iter() {
+            item.trace();
+        }
+    }
+    
+    fn size_hint(&self) -> usize {
+        core::mem::size_of::<Variant>() * self.items.borrow().capacity()
+    }
+}
+
+impl From<Vec<Variant>> for List {
+    fn from(items: Vec<Variant>) -> Self {
+        Self { items: RefCell::new(items) }
+    }
+}
+
+impl List {
+    pub fn len(&self) -> usize {
+        self.items.borrow().len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.items.borrow().is_empty()
+    }
+    
+    pub fn get(&self, idx: usize) -> Option<Variant> {
+        self.items.borrow().get(idx).copied()
+    }
+    
+    pub fn push(&self, value: Variant) {
+        self.items.borrow_mut().push(value)
+    }
+    
+    pub fn pop(&self) -> Option<Variant> {
+        let item = self.items.borrow_mut().pop();
+        gc_barrier(&item);
+        item
+    }
+    
+    // copy the current contents, so that the list is not borrowed while the items are being used
+    pub fn to_vec(&self) -> Vec<Variant> {
+        self.items.borrow().clone()
+    }
+}
+
+
+// Methods
+
+fn into_list(receiver: &Variant) -> Gc<List> {
+    match receiver {
+        Variant::List(list) => *list,
+        _ => panic!("invalid receiver"),
+    }
+}
+
+static LIST_PUSH: NativeMethod = NativeMethod {
+    name: "push", params: &["value"],
+    func: |receiver, args| {
+        into_list(receiver).push(args[0]);
+        Ok(Variant::Nil)
+    },
+};
+
+static LIST_POP: NativeMethod = NativeMethod {
+    name: "pop", params: &[],
+    func: |receiver, _args| {
+        into_list(receiver).pop()
+            .ok_or_else(|| RuntimeError::invalid_value("pop from empty list"))
+    },
+};
+
+static LIST_METHODS: &[&NativeMethod] = &[&LIST_PUSH, &LIST_POP];
+
+fn get_method(name: &StringSymbol) -> Option<&'static NativeMethod> {
+    if *name == static_symbol!("push") {
+        return Some(&LIST_PUSH);
+    }
+    if *name == static_symbol!("pop") {
+        return Some(&LIST_POP);
+    }
+    None
+}
+
+
+impl MetaObject for Gc<List> {
+    fn type_tag(&self) -> Type { Type::List }
+    
+    fn len(&self) -> Option<ExecResult<usize>> {
+        Some(Ok(List::len(self)))
+    }
+    
+    fn get_index(&self, index: &Variant) -> Option<ExecResult<Variant>> {
+        let items = self.items.borrow();
+        let result = sequence_index(index, items.len())
+            .map(|index| items[index]);
+        Some(result)
+    }
+    
+    fn set_index(&self, index: &Variant, value: Variant) -> Option<ExecResult<()>> {
+        let mut items = self.items.borrow_mut();
+        let result = sequence_index(index, items.len())
+            .map(|index| {
+                gc_barrier(&items[index]);
+                items[index] = value
+            });
+        Some(result)
+    }
+    
+    fn has_item(&self, item: &Variant) -> Option<ExecResult<bool>> {
+        Some(sequence_contains(&self.to_vec(), item))
+    }
+    
+    fn get_attr(&self, name: &StringSymbol) -> Option<ExecResult<Variant>> {
+        let receiver = Variant::List(*self);
+        let result = get_method(name)
+            .map(|method| Variant::BoundMethod(Gc::new(BoundMethod::new(receiver, method.into()))))
+            .ok_or_else(|| RuntimeError::attribute_not_found(&receiver, *name));
+        
+        Some(result)
+    }
+    
+    fn attr_names(&self) -> Vec<StringSymbol> {
+        LIST_METHODS.iter().map(|method| StringSymbol::from(method.name)).collect()
+    }
+    
+    fn iter_init(&self) -> Option<ExecResult<IterState>> {
+        let iter: Box<dyn UserIterator> = Box::new(ListIter(*self));
+        let iter = Gc::from_box(iter);
+        iter.iter_init()
+    }
+    
+    fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        if let Variant::List(other) = other {
+            if Gc::ptr_eq(self, other) {
+                return Some(Ok(true));
+            }
+            return Some(list_eq(&self.to_vec(), &other.to_vec()));
+        }
+        None
+    }
+    
+    fn cmp_lt(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        if let Variant::List(other) = other {
+            let result = sequence_cmp(&self.to_vec(), &other.to_vec())
+                .map(|ordering| ordering == Ordering::Less);
+            return Some(result);
+        }
+        None
+    }
+    
+    fn cmp_le(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        if let Variant::List(other) = other {
+            let result = sequence_cmp(&self.to_vec(), &other.to_vec())
+                .map(|ordering| ordering != Ordering::Greater);
+            return Some(result);
+        }
+        None
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        let items = self.to_vec();
+        
+        let mut buf = String::new();
+        buf.push('[');
+        
+        if let Some((first, rest)) = items.split_first() {
+            write!(&mut buf, "{}", first.fmt_repr()?)
+                .map_err(|err| RuntimeError::other(err.to_string()))?;
+            
+            for item in rest.iter() {
+                write!(&mut buf, ", {}", item.fmt_repr()?)
+                    .map_err(|err| RuntimeError::other(err.to_string()))?;
+            }
+        }
+        buf.push(']');
+        
+        Ok(StringValue::new_uninterned(buf))
+    }
+}
+
+fn list_eq(lhs: &[Variant], rhs: &[Variant]) -> ExecResult<bool> {
+    if lhs.len() != rhs.len() {
+        return Ok(false);
+    }
+    
+    for (a, b) in lhs.iter().zip(rhs.iter()) {
+        if !a.cmp_eq(b)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+
+// List Iterator
+
+// The list can be modified while iterating over it, so the index is always checked
+#[derive(Debug)]
+struct ListIter(Gc<List>);
+
+unsafe impl GcTrace for ListIter {
+    fn trace(&self) {
+        self.0.mark_trace()
+    }
+}
+
+impl UserIterator for ListIter {
+    fn get_item(&self, state: &Variant) -> ExecResult<Variant> {
+        let idx = usize::try_from(state.as_int()?)
+            .map_err(|_| RuntimeError::invalid_value("invalid state"))?;
+        
+        self.0.get(idx)
+            .ok_or_else(|| RuntimeError::invalid_value("list was modified during iteration"))
+    }
+    
+    fn next_state(&self, state: Option<&Variant>) -> ExecResult<Variant> {
+        let next = match state {
+            Some(state) => state.as_int()?
+                .checked_add(1)
+                .ok_or(RuntimeError::overflow_error())?,
+            
+            None => 0,
+        };
+        
+        let next_idx = usize::try_from(next)
+            .map_err(|_| RuntimeError::invalid_value("invalid state"))?;
+        
+        if next_idx >= List::len(&self.0) {
+            return Ok(Variant::Nil)
+        }
+        Ok(Variant::from(next))
+    }
+}
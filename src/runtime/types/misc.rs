@@ -1,9 +1,10 @@
 use core::any::Any;
-use crate::runtime::Variant;
+use crate::language::{IntType, FloatType};
+use crate::runtime::{Variant, VirtualMachine};
 use crate::runtime::gc::{Gc, GcTrace};
-use crate::runtime::function::{Call, Callable};
+use crate::runtime::function::{Call, Callable, NativeCtor};
 use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
-use crate::runtime::types::{Type, MetaObject};
+use crate::runtime::types::{Type, MetaObject, Set, int_from_str, float_from_str};
 use crate::runtime::errors::{ExecResult, RuntimeError};
 
 
@@ -60,6 +61,105 @@ impl MetaObject for Marker {
 }
 
 
+// Type objects, produced by the type() builtin. The primitive types are also builtins, and calling them converts a value.
+
+impl MetaObject for Type {
+    fn type_tag(&self) -> Type { Type::Type }
+    
+    fn invoke(&self, args: &[Variant]) -> Option<ExecResult<Call>> {
+        self.ctor().map(|ctor| ctor.call(args))
+    }
+    
+    fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        match other {
+            Variant::Type(other) => Some(Ok(self == other)),
+            _ => Some(Ok(false)),
+        }
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        let result = format!("<type \"{}\">", self.name());
+        Ok(StringValue::new_uninterned(result))
+    }
+}
+
+impl Type {
+    pub fn ctor(&self) -> Option<&'static NativeCtor> {
+        match self {
+            Self::Boolean => Some(&BOOL_CTOR),
+            Self::Integer => Some(&INT_CTOR),
+            Self::Float => Some(&FLOAT_CTOR),
+            Self::String => Some(&STR_CTOR),
+            Self::Set => Some(&SET_CTOR),
+            _ => None,
+        }
+    }
+}
+
+static BOOL_CTOR: NativeCtor = NativeCtor {
+    name: "bool", params: &["value"], defaults: &[], variadic: None,
+    func: |_vm, args| Ok(Variant::from(args[0].as_bool()?)),
+};
+
+// parse a string, or truncate a float
+static INT_CTOR: NativeCtor = NativeCtor {
+    name: "int", params: &["value"], defaults: &["radix"], variadic: None,
+    func: |_vm, args| {
+        let value = args[0];
+        let radix = match args.get(1) {
+            None | Some(Variant::Nil) => None,
+            Some(radix) => Some(radix.as_int()?),
+        };
+        
+        if let Some(strval) = value.as_strval() {
+            return strval.with_str(|s| int_from_str(s, radix));
+        }
+        
+        if radix.is_some() {
+            return Err(RuntimeError::invalid_value("a radix can only be given when parsing a string"));
+        }
+        
+        match value {
+            Variant::Float(value) if value.is_finite() => {
+                let value = value.trunc();
+                if IntType::MIN as FloatType <= value && value <= IntType::MAX as FloatType {
+                    Ok(Variant::from(value as IntType))
+                } else {
+                    Err(RuntimeError::overflow_error())
+                }
+            }
+            
+            _ => Ok(Variant::from(value.as_int()?))
+        }
+    },
+};
+
+static FLOAT_CTOR: NativeCtor = NativeCtor {
+    name: "float", params: &["value"], defaults: &[], variadic: None,
+    func: |_vm, args| {
+        if let Some(strval) = args[0].as_strval() {
+            return strval.with_str(
+                |s| Ok(Variant::from(float_from_str(s)?))
+            )
+        }
+        
+        Ok(Variant::from(args[0].as_float()?))
+    },
+};
+
+// convert a value into a string
+static STR_CTOR: NativeCtor = NativeCtor {
+    name: "str", params: &["value"], defaults: &[], variadic: None,
+    func: |vm: &mut VirtualMachine<'_>, args| Ok(Variant::from(vm.fmt_str(&args[0])?)),
+};
+
+// create a set containing the arguments
+static SET_CTOR: NativeCtor = NativeCtor {
+    name: "set", params: &[], defaults: &[], variadic: Some("items"),
+    func: |_vm, args| Ok(Variant::Set(Gc::new(Set::from_values(args)?))),
+};
+
+
 impl<F> MetaObject for Gc<F> where F: GcTrace, Gc<F>: Callable {
     fn type_tag(&self) -> Type { Type::Function }
     
@@ -94,6 +194,44 @@ impl MetaObject for Gc<RuntimeError> {
     fn fmt_repr(&self) -> ExecResult<StringValue> {
         Ok(self.kind().name())
     }
+    
+    fn get_attr(&self, name: &StringSymbol) -> Option<ExecResult<Variant>> {
+        if *name == static_symbol!("kind") {
+            return Some(Ok(Variant::from(self.kind().name())));
+        }
+        if *name == static_symbol!("message") {
+            return Some(Ok(Variant::from(*self.message())));
+        }
+        if *name == static_symbol!("traceback") {
+            return Some(Ok(traceback_to_tuple(self)));
+        }
+        None
+    }
+    
+    fn attr_names(&self) -> Vec<StringSymbol> {
+        vec![static_symbol!("kind"), static_symbol!("message"), static_symbol!("traceback")]
+    }
+}
+
+// produces a tuple of (file, line, name) for each frame, most recent call last
+fn traceback_to_tuple(error: &RuntimeError) -> Variant {
+    let frames = error.iter_trace().rev()
+        .map(|site| {
+            let file = site.file_name()
+                .map_or(Variant::Nil, |file| Variant::from(StringValue::new_uninterned(file)));
+            
+            let lineno = site.lineno()
+                .and_then(|lineno| IntType::try_from(lineno).ok())
+                .map_or(Variant::Nil, Variant::from);
+            
+            let name = Variant::from(site.chunk_name());
+            
+            let frame = vec![ file, lineno, name ];
+            Variant::from(frame.into_boxed_slice())
+        })
+        .collect::<Vec<Variant>>();
+    
+    Variant::from(frames.into_boxed_slice())
 }
 
 
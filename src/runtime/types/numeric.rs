@@ -5,6 +5,10 @@ use crate::runtime::Variant;
 use crate::runtime::strings::{StringValue, StrBuffer};
 use crate::runtime::types::{MetaObject, Type};
 use crate::runtime::errors::{ExecResult, RuntimeError};
+#[cfg(feature = "bigint")]
+use core::num::IntErrorKind;
+#[cfg(feature = "bigint")]
+use crate::runtime::types::BigInt;
 
 macro_rules! checked_int_math {
     ( $method:tt, $lhs:expr, $rhs:expr ) => {
@@ -15,16 +19,165 @@ macro_rules! checked_int_math {
     };
 }
 
-pub fn int_from_str(s: &str, radix: IntType) -> ExecResult<IntType> {
-    if !(2..=36).contains(&radix) {
+// like checked_int_math!(), but promotes to BigInt on overflow if that feature is enabled
+macro_rules! promote_int_math {
+    ( $method:tt, $big_method:tt, $lhs:expr, $rhs:expr ) => {
+        {
+            let (lhs, rhs) = ($lhs, $rhs);
+            match lhs.$method(rhs) {
+                Some(value) => Ok(Variant::Integer(value)),
+                
+                #[cfg(feature = "bigint")]
+                None => Ok(BigInt::from(lhs).$big_method(&BigInt::from(rhs)).into()),
+                
+                #[cfg(not(feature = "bigint"))]
+                None => Err(RuntimeError::overflow_error()),
+            }
+        }
+    };
+    
+    ( try $method:tt, $big_method:tt, $lhs:expr, $rhs:expr ) => {
+        {
+            let (lhs, rhs) = ($lhs, $rhs);
+            match lhs.$method(rhs) {
+                Some(value) => Ok(Variant::Integer(value)),
+                
+                #[cfg(feature = "bigint")]
+                None => BigInt::from(lhs).$big_method(&BigInt::from(rhs)).map(Variant::from),
+                
+                #[cfg(not(feature = "bigint"))]
+                None => Err(RuntimeError::overflow_error()),
+            }
+        }
+    };
+}
+
+// if the other operand is a BigInt, evaluate using the reflected BigInt operator
+macro_rules! reflect_bigint {
+    ( $value:expr, $other:expr, $reflected_method:tt ) => {
+        #[cfg(feature = "bigint")]
+        if let Variant::BigInt(other) = $other {
+            return other.$reflected_method(&Variant::Integer(*$value));
+        }
+    };
+}
+
+fn int_floordiv(lhs: IntType, rhs: IntType) -> ExecResult<Variant> {
+    if rhs == 0 {
+        return Err(RuntimeError::divide_by_zero());
+    }
+    
+    let quot = match lhs.checked_div(rhs) {
+        Some(quot) => quot,
+        None => return promote_int_math!(try checked_div, floor_div, lhs, rhs),
+    };
+    
+    // integer division truncates towards zero, adjust to round towards negative infinity
+    if (lhs % rhs != 0) && ((lhs < 0) != (rhs < 0)) {
+        Ok(Variant::Integer(quot - 1))
+    } else {
+        Ok(Variant::Integer(quot))
+    }
+}
+
+// the result takes the sign of the divisor, so that lhs == (lhs // rhs) * rhs + lhs % rhs
+fn int_mod(lhs: IntType, rhs: IntType) -> ExecResult<Variant> {
+    if rhs == 0 {
+        return Err(RuntimeError::divide_by_zero());
+    }
+    
+    // since rhs is nonzero, the only overflow is MIN % -1, which divides exactly
+    let rem = match lhs.checked_rem(rhs) {
+        Some(rem) => rem,
+        None => {
+            debug_assert!(rhs == -1);
+            return Ok(Variant::Integer(0));
+        },
+    };
+    
+    if (rem != 0) && ((rem < 0) != (rhs < 0)) {
+        Ok(Variant::Integer(rem + rhs))
+    } else {
+        Ok(Variant::Integer(rem))
+    }
+}
+
+fn float_mod(lhs: FloatType, rhs: FloatType) -> FloatType {
+    let rem = lhs % rhs;
+    if (rem != 0.0) && ((rem < 0.0) != (rhs < 0.0)) {
+        rem + rhs
+    } else {
+        rem
+    }
+}
+
+fn int_exp(lhs: IntType, rhs: IntType) -> ExecResult<Variant> {
+    // negative exponents produce a fractional result
+    if rhs < 0 {
+        return Ok(Variant::from((lhs as FloatType).powf(rhs as FloatType)));
+    }
+    
+    match u32::try_from(rhs) {
+        Ok(rhs) => match lhs.checked_pow(rhs) {
+            Some(value) => Ok(Variant::Integer(value)),
+            
+            #[cfg(feature = "bigint")]
+            None => Ok(BigInt::from(lhs).pow(rhs).into()),
+            
+            #[cfg(not(feature = "bigint"))]
+            None => Err(RuntimeError::overflow_error()),
+        },
+        Err(..) => Err(RuntimeError::overflow_error()),
+    }
+}
+
+// Surrounding whitespace is ignored, and the digits may follow a sign and a "0b", "0o", or "0x" prefix.
+// If no radix is given then it is taken from the prefix, or is 10 if there is none.
+// Values that are too large for IntType produce a BigInt if that feature is enabled.
+pub fn int_from_str(s: &str, radix: Option<IntType>) -> ExecResult<Variant> {
+    if radix.is_some_and(|radix| !(2..=36).contains(&radix)) {
         return Err(RuntimeError::invalid_value("invalid radix"));
     }
     
-    let value = IntType::from_str_radix(s, radix.try_into().unwrap())
-        .map_err(|_| RuntimeError::invalid_value(format!(
-            "could not parse \"{}\" as int with radix {}", s, radix)
-        ))?;
-    Ok(value)
+    let text = s.trim();
+    let (sign, mut digits) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text.strip_prefix('+').unwrap_or(text)),
+    };
+    
+    // a prefix is only stripped if it agrees with the radix, since "0b1" is also a valid hex number
+    let mut radix = radix;
+    for (prefix, prefix_radix) in [("0b", 2), ("0o", 8), ("0x", 16)] {
+        let has_prefix = digits.get(..2).is_some_and(|head| head.eq_ignore_ascii_case(prefix));
+        if has_prefix && radix.is_none_or(|radix| radix == prefix_radix) {
+            radix = Some(prefix_radix);
+            digits = &digits[2..];
+            break;
+        }
+    }
+    let radix = radix.unwrap_or(10);
+    
+    let error = || RuntimeError::invalid_value(format!(
+        "could not parse \"{}\" as int with radix {}", s, radix
+    ));
+    
+    // from_str_radix() would accept a second sign
+    if digits.starts_with(['+', '-']) {
+        return Err(error());
+    }
+    
+    let text = format!("{}{}", sign, digits);
+    match IntType::from_str_radix(&text, radix.try_into().unwrap()) {
+        Ok(value) => Ok(Variant::from(value)),
+        
+        #[cfg(feature = "bigint")]
+        Err(overflow) if matches!(overflow.kind(), IntErrorKind::PosOverflow | IntErrorKind::NegOverflow) =>
+            BigInt::from_str_radix(&text, radix.try_into().unwrap())
+                .map(Variant::from)
+                .ok_or_else(error),
+        
+        Err(..) => Err(error()),
+    }
 }
 
 impl MetaObject for IntType {
@@ -34,15 +187,28 @@ impl MetaObject for IntType {
     fn as_int(&self) -> Option<ExecResult<IntType>> { Some(Ok(*self)) }
     fn as_float(&self) -> Option<ExecResult<FloatType>> { Some(Ok(*self as FloatType)) }
     
-    fn op_neg(&self) -> Option<ExecResult<Variant>> { Some(Ok(Variant::from(-(*self)))) }
+    fn op_neg(&self) -> Option<ExecResult<Variant>> {
+        match self.checked_neg() {
+            Some(value) => Some(Ok(Variant::from(value))),
+            
+            #[cfg(feature = "bigint")]
+            None => Some(Ok(BigInt::from(*self).neg().into())),
+            
+            #[cfg(not(feature = "bigint"))]
+            None => Some(Err(RuntimeError::overflow_error())),
+        }
+    }
+    
     fn op_pos(&self) -> Option<ExecResult<Variant>> { Some(Ok(Variant::from(*self))) }
     fn op_inv(&self) -> Option<ExecResult<Variant>> { Some(Ok(Variant::from(!(*self)))) }
     
     fn op_mul(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        reflect_bigint!(self, rhs, op_rmul);
+        
         match rhs {
-            Variant::Integer(rhs) => Some(checked_int_math!(checked_mul, *self, *rhs)),
+            Variant::Integer(rhs) => Some(promote_int_math!(checked_mul, mul, *self, *rhs)),
             _ => rhs.as_meta().as_int()
-                .map(|rhs| checked_int_math!(checked_mul, *self, rhs?))
+                .map(|rhs| promote_int_math!(checked_mul, mul, *self, rhs?))
         }
     }
     
@@ -51,12 +217,14 @@ impl MetaObject for IntType {
     }
     
     fn op_div(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        reflect_bigint!(self, rhs, op_rdiv);
+        
         rhs.as_meta().as_int().map(|rhs| {
             let rhs = rhs?;
             if rhs == 0 {
                 Err(RuntimeError::divide_by_zero())
             } else {
-                checked_int_math!(checked_div, *self, rhs)
+                promote_int_math!(try checked_div, div, *self, rhs)
             }
         })
     }
@@ -66,24 +234,48 @@ impl MetaObject for IntType {
             if *self == 0 {
                 Err(RuntimeError::divide_by_zero())
             } else {
-                checked_int_math!(checked_div, lhs?, *self)
+                promote_int_math!(try checked_div, div, lhs?, *self)
             }
         })
     }
     
     fn op_mod(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
-        rhs.as_meta().as_int().map(|rhs| Ok(Variant::from(*self % rhs?)))
+        reflect_bigint!(self, rhs, op_rmod);
+        
+        rhs.as_meta().as_int().map(|rhs| int_mod(*self, rhs?))
     }
     
     fn op_rmod(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
-        lhs.as_meta().as_int().map(|lhs| Ok(Variant::from(lhs? % *self)))
+        lhs.as_meta().as_int().map(|lhs| int_mod(lhs?, *self))
+    }
+    
+    fn op_floordiv(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        reflect_bigint!(self, rhs, op_rfloordiv);
+        
+        rhs.as_meta().as_int().map(|rhs| int_floordiv(*self, rhs?))
+    }
+    
+    fn op_rfloordiv(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        lhs.as_meta().as_int().map(|lhs| int_floordiv(lhs?, *self))
+    }
+    
+    fn op_exp(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        reflect_bigint!(self, rhs, op_rexp);
+        
+        rhs.as_meta().as_int().map(|rhs| int_exp(*self, rhs?))
+    }
+    
+    fn op_rexp(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        lhs.as_meta().as_int().map(|lhs| int_exp(lhs?, *self))
     }
     
     fn op_add(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        reflect_bigint!(self, rhs, op_radd);
+        
         match rhs {
-            Variant::Integer(rhs) => Some(checked_int_math!(checked_add, *self, *rhs)),
+            Variant::Integer(rhs) => Some(promote_int_math!(checked_add, add, *self, *rhs)),
             _ => rhs.as_meta().as_int()
-                .map(|rhs| checked_int_math!(checked_add, *self, rhs?))
+                .map(|rhs| promote_int_math!(checked_add, add, *self, rhs?))
         }
     }
     
@@ -92,18 +284,20 @@ impl MetaObject for IntType {
     }
     
     fn op_sub(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        reflect_bigint!(self, rhs, op_rsub);
+        
         match rhs {
-            Variant::Integer(rhs) => Some(checked_int_math!(checked_sub, *self, *rhs)),
+            Variant::Integer(rhs) => Some(promote_int_math!(checked_sub, sub, *self, *rhs)),
             _ => rhs.as_meta().as_int()
-                .map(|rhs| checked_int_math!(checked_sub, *self, rhs?))
+                .map(|rhs| promote_int_math!(checked_sub, sub, *self, rhs?))
         }
     }
     
     fn op_rsub(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
         match lhs {
-            Variant::Integer(lhs) => Some(checked_int_math!(checked_sub, *lhs, *self)),
+            Variant::Integer(lhs) => Some(promote_int_math!(checked_sub, sub, *lhs, *self)),
             _ => lhs.as_meta().as_int()
-                .map(|lhs| checked_int_math!(checked_sub, lhs?, *self))
+                .map(|lhs| promote_int_math!(checked_sub, sub, lhs?, *self))
         }
     }
     
@@ -168,7 +362,7 @@ impl MetaObject for IntType {
             if rhs < 0 {
                 return Err(RuntimeError::negative_shift_count());
             }
-            return checked_int_math!(checked_shl, *self, rhs.try_into().unwrap());
+            checked_int_math!(checked_shl, *self, rhs.try_into().unwrap())
         })
     }
     
@@ -178,7 +372,7 @@ impl MetaObject for IntType {
             if *self < 0 {
                 return Err(RuntimeError::negative_shift_count());
             }
-            return checked_int_math!(checked_shl, lhs, (*self).try_into().unwrap());
+            checked_int_math!(checked_shl, lhs, (*self).try_into().unwrap())
         })
     }
     
@@ -198,7 +392,7 @@ impl MetaObject for IntType {
             if rhs < 0 {
                 return Err(RuntimeError::negative_shift_count());
             }
-            return checked_int_math!(checked_shr, *self, rhs.try_into().unwrap());
+            checked_int_math!(checked_shr, *self, rhs.try_into().unwrap())
         })
     }
     
@@ -208,11 +402,13 @@ impl MetaObject for IntType {
             if *self < 0 {
                 return Err(RuntimeError::negative_shift_count());
             }
-            return checked_int_math!(checked_shr, lhs, (*self).try_into().unwrap());
+            checked_int_math!(checked_shr, lhs, (*self).try_into().unwrap())
         })
     }
     
     fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        reflect_bigint!(self, other, cmp_eq);
+        
         match other {
             Variant::Integer(other) => Some(Ok(*self == *other)),
             _ => other.as_meta().as_int()
@@ -221,6 +417,11 @@ impl MetaObject for IntType {
     }
     
     fn cmp_lt(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        #[cfg(feature = "bigint")]
+        if let Variant::BigInt(other) = other {
+            return other.cmp_le(&Variant::Integer(*self)).map(|cmp| cmp.map(|cmp| !cmp));
+        }
+        
         match other {
             Variant::Integer(other) => Some(Ok(*self < *other)),
             _ => other.as_meta().as_int()
@@ -229,6 +430,11 @@ impl MetaObject for IntType {
     }
     
     fn cmp_le(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        #[cfg(feature = "bigint")]
+        if let Variant::BigInt(other) = other {
+            return other.cmp_lt(&Variant::Integer(*self)).map(|cmp| cmp.map(|cmp| !cmp));
+        }
+        
         match other {
             Variant::Integer(other) => Some(Ok(*self <= *other)),
             _ => other.as_meta().as_int()
@@ -239,17 +445,18 @@ impl MetaObject for IntType {
     fn fmt_repr(&self) -> ExecResult<StringValue> {
         let mut buf = StrBuffer::<32>::new();
         if write!(buf, "{}", *self).is_ok() {
-            Ok(StringValue::new_maybe_interned(buf))
+            Ok(StringValue::new_uninterned(buf))
         } else {
             // resort to allocated buffer
-            Ok(StringValue::new_maybe_interned(format!("{}", *self)))
+            Ok(StringValue::new_uninterned(format!("{}", *self)))
         }
     }
 }
 
 
+// surrounding whitespace is ignored
 pub fn float_from_str(s: &str) -> ExecResult<FloatType> {
-    let value = FloatType::from_str(s)
+    let value = FloatType::from_str(s.trim())
         .map_err(|_| RuntimeError::invalid_value(format!(
             "could not parse \"{}\" as float", s
         )))?;
@@ -281,11 +488,27 @@ impl MetaObject for FloatType {
     }
     
     fn op_mod(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
-        rhs.as_meta().as_float().map(|rhs| Ok(Variant::from(*self % rhs?)))
+        rhs.as_meta().as_float().map(|rhs| Ok(Variant::from(float_mod(*self, rhs?))))
     }
     
     fn op_rmod(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
-        lhs.as_meta().as_float().map(|lhs| Ok(Variant::from(lhs? % *self)))
+        lhs.as_meta().as_float().map(|lhs| Ok(Variant::from(float_mod(lhs?, *self))))
+    }
+    
+    fn op_floordiv(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        rhs.as_meta().as_float().map(|rhs| Ok(Variant::from((*self / rhs?).floor())))
+    }
+    
+    fn op_rfloordiv(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        lhs.as_meta().as_float().map(|lhs| Ok(Variant::from((lhs? / *self).floor())))
+    }
+    
+    fn op_exp(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        rhs.as_meta().as_float().map(|rhs| Ok(Variant::from(self.powf(rhs?))))
+    }
+    
+    fn op_rexp(&self, lhs: &Variant) -> Option<ExecResult<Variant>> {
+        lhs.as_meta().as_float().map(|lhs| Ok(Variant::from(lhs?.powf(*self))))
     }
     
     fn op_add(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
@@ -317,22 +540,20 @@ impl MetaObject for FloatType {
     }
     
     fn fmt_repr(&self) -> ExecResult<StringValue> {
+        // the debug format is the shortest text that reads back as the same float,
+        // and always includes either a decimal point or an exponent
         fn write_float(value: FloatType, fmt: &mut impl fmt::Write) -> fmt::Result {
-            if !value.is_finite() || value.trunc() != value {
-                write!(fmt, "{}", value)
-            } else {
-                write!(fmt, "{}.0", value)
-            }
+            write!(fmt, "{:?}", value)
         }
         
         let mut buf = StrBuffer::<32>::new();
         if write_float(*self, &mut buf).is_ok() {
-            Ok(StringValue::new_maybe_interned(buf))
+            Ok(StringValue::new_uninterned(buf))
         } else {
             let mut buf = String::new();
             write_float(*self, &mut buf)
                 .map_err(|error| RuntimeError::other(error.to_string()))?;
-            Ok(StringValue::new_maybe_interned(buf))
+            Ok(StringValue::new_uninterned(buf))
         }
     }
 }
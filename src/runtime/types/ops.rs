@@ -1,8 +1,34 @@
 use crate::runtime::Variant;
-use crate::runtime::types::MetaObject;
+use crate::runtime::gc::Gc;
+use crate::runtime::types::{MetaObject, MethodTag, Tuple};
 use crate::runtime::errors::{ExecResult, RuntimeError};
 
 
+// The reflected metamethod is only tried if the operands are of different types.
+// All userdata share the same type tag, so they are distinguished by their concrete type instead.
+fn is_same_type(lhs: &Variant, rhs: &Variant) -> bool {
+    match (lhs, rhs) {
+        (Variant::UserData(lhs), Variant::UserData(rhs)) 
+            => (**lhs).type_id() == (**rhs).type_id(),
+        
+        _ => lhs.type_tag() == rhs.type_tag(),
+    }
+}
+
+// Objects that do not define equality are only equal to themselves
+fn is_same_ref(lhs: &Variant, rhs: &Variant) -> bool {
+    match (lhs, rhs) {
+        (Variant::Function(lhs), Variant::Function(rhs)) => Gc::ptr_eq(lhs, rhs),
+        (Variant::NativeFunction(lhs), Variant::NativeFunction(rhs)) => Gc::ptr_eq(lhs, rhs),
+        (Variant::Class(lhs), Variant::Class(rhs)) => Gc::ptr_eq(lhs, rhs),
+        (Variant::Instance(lhs), Variant::Instance(rhs)) => Gc::ptr_eq(lhs, rhs),
+        (Variant::Iterator(lhs), Variant::Iterator(rhs)) => Gc::ptr_eq(lhs, rhs),
+        (Variant::Error(lhs), Variant::Error(rhs)) => Gc::ptr_eq(lhs, rhs),
+        (Variant::UserData(lhs), Variant::UserData(rhs)) => Gc::ptr_eq(lhs, rhs),
+        _ => false,
+    }
+}
+
 macro_rules! meta_eval_unary {
     ( $operand:expr, $unary_method:tt ) => {
         $operand.as_meta().$unary_method()
@@ -17,7 +43,7 @@ macro_rules! meta_eval_binary {
                 return result;
             }
             
-            if $lhs.type_tag() != $rhs.type_tag() {
+            if !is_same_type($lhs, $rhs) {
                 if let Some(result) = $rhs.as_meta().$reflected_method($lhs) {
                     return result;
                 }
@@ -35,7 +61,7 @@ macro_rules! meta_eval_inequality {
                 return result;
             }
             
-            if $lhs.type_tag() != $rhs.type_tag() {
+            if !is_same_type($lhs, $rhs) {
                 if let Some(result) = $rhs.as_meta().$reflected_method($lhs) {
                     return result.map(|cmp| !cmp);
                 }
@@ -87,6 +113,16 @@ impl Variant {
         meta_eval_binary!(self, rhs, op_mod, op_rmod)
     }
     
+    #[inline(always)]
+    pub fn apply_floordiv(&self, rhs: &Variant) -> ExecResult<Variant> {
+        meta_eval_binary!(self, rhs, op_floordiv, op_rfloordiv)
+    }
+    
+    #[inline(always)]
+    pub fn apply_exp(&self, rhs: &Variant) -> ExecResult<Variant> {
+        meta_eval_binary!(self, rhs, op_exp, op_rexp)
+    }
+    
     #[inline(always)]
     pub fn apply_add(&self, rhs: &Variant) -> ExecResult<Variant> {
         meta_eval_binary!(self, rhs, op_add, op_radd)
@@ -128,13 +164,13 @@ impl Variant {
             return result;
         }
         
-        if self.type_tag() != other.type_tag() {
+        if !is_same_type(self, other) {
             if let Some(result) = other.as_meta().cmp_eq(self) {
                 return result;
             }
         }
         
-        Ok(false)
+        Ok(is_same_ref(self, other))
     }
     
     pub fn cmp_ne(&self, other: &Variant) -> ExecResult<bool> {
@@ -156,4 +192,67 @@ impl Variant {
     pub fn cmp_ge(&self, other: &Variant) -> ExecResult<bool> {
         self.cmp_lt(other).map(|cmp| !cmp)
     }
+    
+    // Containment
+    
+    // the built-in containers check for the item directly, any other iterable is searched for an equal item
+    pub fn cmp_in(&self, container: &Variant) -> ExecResult<bool> {
+        if let Some(result) = container.as_meta().has_item(self) {
+            return result;
+        }
+        
+        if let Some(iter) = container.as_meta().iter_init() {
+            for item in iter? {
+                if self.cmp_eq(&item?)? {
+                    return Ok(true);
+                }
+            }
+            return Ok(false);
+        }
+        
+        Err(RuntimeError::metamethod_not_supported(container, MethodTag::Contains))
+    }
+    
+    pub fn cmp_not_in(&self, container: &Variant) -> ExecResult<bool> {
+        self.cmp_in(container).map(|cmp| !cmp)
+    }
+    
+    // Identity
+    
+    // GC objects are the same only if they are the same reference, other values are compared by value.
+    // Strings are values, since whether a string is allocated on the GC heap is an implementation detail.
+    // Unlike cmp_eq(), this never invokes any metamethods and values of different types are never the same.
+    pub fn cmp_is(&self, other: &Variant) -> bool {
+        match (self, other) {
+            (Self::Nil, Self::Nil) => true,
+            (Self::BoolTrue, Self::BoolTrue) => true,
+            (Self::BoolFalse, Self::BoolFalse) => true,
+            (Self::Marker(a), Self::Marker(b)) => a == b,
+            (Self::Type(a), Self::Type(b)) => a == b,
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(a), Self::BigInt(b)) => Gc::ptr_eq(a, b),
+            
+            (Self::InternStr(..) | Self::InlineStr(..) | Self::GCStr(..), _) =>
+                other.as_strval().is_some_and(|other| self.as_strval().unwrap() == other),
+            
+            (Self::Tuple(Tuple::Empty), Self::Tuple(Tuple::Empty)) => true,
+            (Self::Tuple(Tuple::NonEmpty(a)), Self::Tuple(Tuple::NonEmpty(b))) => Gc::ptr_eq(a, b),
+            (Self::List(a), Self::List(b)) => Gc::ptr_eq(a, b),
+            (Self::Dict(a), Self::Dict(b)) => Gc::ptr_eq(a, b),
+            (Self::Set(a), Self::Set(b)) => Gc::ptr_eq(a, b),
+            (Self::Function(a), Self::Function(b)) => Gc::ptr_eq(a, b),
+            (Self::NativeFunction(a), Self::NativeFunction(b)) => Gc::ptr_eq(a, b),
+            (Self::BoundMethod(a), Self::BoundMethod(b)) => Gc::ptr_eq(a, b),
+            (Self::Class(a), Self::Class(b)) => Gc::ptr_eq(a, b),
+            (Self::Instance(a), Self::Instance(b)) => Gc::ptr_eq(a, b),
+            (Self::Iterator(a), Self::Iterator(b)) => Gc::ptr_eq(a, b),
+            (Self::Error(a), Self::Error(b)) => Gc::ptr_eq(a, b),
+            (Self::UserData(a), Self::UserData(b)) => Gc::ptr_eq(a, b),
+            
+            _ => false,
+        }
+    }
 }
@@ -0,0 +1,246 @@
+use core::cell::RefCell;
+use core::fmt::Write;
+use crate::runtime::{Variant, VariantKey, HashSet, DefaultBuildHasher};
+use crate::runtime::gc::{Gc, GcTrace, gc_barrier};
+use crate::runtime::function::NativeMethod;
+use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
+use crate::runtime::iter::IterState;
+use crate::runtime::types::{Type, MetaObject, Tuple, BoundMethod};
+use crate::runtime::errors::{ExecResult, RuntimeError};
+
+
+/// A mutable collection of unique hashable values
+#[derive(Debug)]
+pub struct Set {
+    items: RefCell<HashSet<VariantKey>>,
+}
+
+unsafe impl GcTrace for Set {
+    fn trace(&self) {
+        for item in self.items.borrow().iter() {
+            item.trace();
+        }
+    }
+    
+    fn size_hint(&self) -> usize {
+        core::mem::size_of::<VariantKey>() * self.items.borrow().capacity()
+    }
+}
+
+impl Default for Set {
+    fn default() -> Self {
+        Self::from(HashSet::with_hasher(DefaultBuildHasher::default()))
+    }
+}
+
+impl From<HashSet<VariantKey>> for Set {
+    fn from(items: HashSet<VariantKey>) -> Self {
+        Self { items: RefCell::new(items) }
+    }
+}
+
+impl Set {
+    pub fn from_values(values: &[Variant]) -> ExecResult<Self> {
+        let set = Self::default();
+        for value in values.iter() {
+            set.insert(VariantKey::try_from(*value)?);
+        }
+        Ok(set)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.items.borrow().len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.items.borrow().is_empty()
+    }
+    
+    pub fn contains(&self, item: &VariantKey) -> bool {
+        self.items.borrow().contains(item)
+    }
+    
+    pub fn insert(&self, item: VariantKey) -> bool {
+        self.items.borrow_mut().insert(item)
+    }
+    
+    pub fn remove(&self, item: &VariantKey) -> bool {
+        let removed = self.items.borrow_mut().take(item);
+        gc_barrier(&removed);
+        removed.is_some()
+    }
+    
+    // copy the current contents, so that the set is not borrowed while the items are being used
+    pub fn to_vec(&self) -> Vec<Variant> {
+        self.items.borrow().iter()
+            .map(|item| *item.value())
+            .collect()
+    }
+    
+    pub fn union(&self, other: &Set) -> Set {
+        self.combine(other, |lhs, rhs| lhs.union(rhs).copied().collect())
+    }
+    
+    pub fn intersection(&self, other: &Set) -> Set {
+        self.combine(other, |lhs, rhs| lhs.intersection(rhs).copied().collect())
+    }
+    
+    pub fn difference(&self, other: &Set) -> Set {
+        self.combine(other, |lhs, rhs| lhs.difference(rhs).copied().collect())
+    }
+    
+    pub fn symmetric_difference(&self, other: &Set) -> Set {
+        self.combine(other, |lhs, rhs| lhs.symmetric_difference(rhs).copied().collect())
+    }
+    
+    fn combine(&self, other: &Set, op: impl Fn(&HashSet<VariantKey>, &HashSet<VariantKey>) -> HashSet<VariantKey>) -> Set {
+        // copy first, in case both operands are the same set
+        let lhs = self.items.borrow().clone();
+        let rhs = other.items.borrow();
+        Set::from(op(&lhs, &rhs))
+    }
+}
+
+
+// Methods
+
+fn into_set(receiver: &Variant) -> Gc<Set> {
+    match receiver {
+        Variant::Set(set) => *set,
+        _ => panic!("invalid receiver"),
+    }
+}
+
+static SET_ADD: NativeMethod = NativeMethod {
+    name: "add", params: &["item"],
+    func: |receiver, args| {
+        let item = VariantKey::try_from(args[0])?;
+        into_set(receiver).insert(item);
+        Ok(Variant::Nil)
+    },
+};
+
+static SET_REMOVE: NativeMethod = NativeMethod {
+    name: "remove", params: &["item"],
+    func: |receiver, args| {
+        let item = VariantKey::try_from(args[0])?;
+        if !into_set(receiver).remove(&item) {
+            return Err(RuntimeError::key_not_found(&args[0]));
+        }
+        Ok(Variant::Nil)
+    },
+};
+
+static SET_CONTAINS: NativeMethod = NativeMethod {
+    name: "contains", params: &["item"],
+    func: |receiver, args| {
+        let item = VariantKey::try_from(args[0])?;
+        Ok(Variant::from(into_set(receiver).contains(&item)))
+    },
+};
+
+static SET_METHODS: &[&NativeMethod] = &[&SET_ADD, &SET_REMOVE, &SET_CONTAINS];
+
+fn get_method(name: &StringSymbol) -> Option<&'static NativeMethod> {
+    if *name == static_symbol!("add") {
+        return Some(&SET_ADD);
+    }
+    if *name == static_symbol!("remove") {
+        return Some(&SET_REMOVE);
+    }
+    if *name == static_symbol!("contains") {
+        return Some(&SET_CONTAINS);
+    }
+    None
+}
+
+
+macro_rules! eval_set_op {
+    ( $lhs:expr, $rhs:expr, $set_op:tt ) => {
+        match $rhs {
+            Variant::Set(rhs) => Some(Ok(Variant::Set(Gc::new($lhs.$set_op(rhs))))),
+            _ => None,
+        }
+    };
+}
+
+impl MetaObject for Gc<Set> {
+    fn type_tag(&self) -> Type { Type::Set }
+    
+    fn len(&self) -> Option<ExecResult<usize>> {
+        Some(Ok(Set::len(self)))
+    }
+    
+    fn has_item(&self, item: &Variant) -> Option<ExecResult<bool>> {
+        let result = VariantKey::try_from(*item)
+            .map(|key| Set::contains(self, &key));
+        Some(result)
+    }
+    
+    fn get_attr(&self, name: &StringSymbol) -> Option<ExecResult<Variant>> {
+        let receiver = Variant::Set(*self);
+        let result = get_method(name)
+            .map(|method| Variant::BoundMethod(Gc::new(BoundMethod::new(receiver, method.into()))))
+            .ok_or_else(|| RuntimeError::attribute_not_found(&receiver, *name));
+        
+        Some(result)
+    }
+    
+    fn attr_names(&self) -> Vec<StringSymbol> {
+        SET_METHODS.iter().map(|method| StringSymbol::from(method.name)).collect()
+    }
+    
+    // iterates over a snapshot of the items
+    fn iter_init(&self) -> Option<ExecResult<IterState>> {
+        Tuple::from(self.to_vec().into_boxed_slice()).iter_init()
+    }
+    
+    fn op_or(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        eval_set_op!(self, rhs, union)
+    }
+    
+    fn op_and(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        eval_set_op!(self, rhs, intersection)
+    }
+    
+    fn op_sub(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        eval_set_op!(self, rhs, difference)
+    }
+    
+    fn op_xor(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
+        eval_set_op!(self, rhs, symmetric_difference)
+    }
+    
+    fn cmp_eq(&self, other: &Variant) -> Option<ExecResult<bool>> {
+        if let Variant::Set(other) = other {
+            if Gc::ptr_eq(self, other) {
+                return Some(Ok(true));
+            }
+            
+            let result = Set::len(self) == Set::len(other)
+                && self.items.borrow().iter().all(|item| other.contains(item));
+            return Some(Ok(result));
+        }
+        None
+    }
+    
+    fn fmt_repr(&self) -> ExecResult<StringValue> {
+        let items = self.to_vec();
+        
+        let mut buf = String::new();
+        buf.push_str("set(");
+        
+        if let Some((first, rest)) = items.split_first() {
+            write!(&mut buf, "{}", first.fmt_repr()?)
+                .map_err(|err| RuntimeError::other(err.to_string()))?;
+            
+            for item in rest.iter() {
+                write!(&mut buf, ", {}", item.fmt_repr()?)
+                    .map_err(|err| RuntimeError::other(err.to_string()))?;
+            }
+        }
+        buf.push(')');
+        
+        Ok(StringValue::new_uninterned(buf))
+    }
+}
@@ -1,8 +1,9 @@
 use core::fmt::Write;
+use crate::utils::escape_str;
 use crate::runtime::Variant;
 use crate::runtime::strings::{StringValue, StrBuffer};
-use crate::runtime::types::{Type, MetaObject};
-use crate::runtime::errors::{ExecResult};
+use crate::runtime::types::{Type, MetaObject, sequence_index};
+use crate::runtime::errors::{ExecResult, RuntimeError};
 
 
 impl MetaObject for StringValue {
@@ -12,6 +13,27 @@ impl MetaObject for StringValue {
         Some(Ok(self.char_count()))
     }
     
+    // strings are indexed by character
+    fn get_index(&self, index: &Variant) -> Option<ExecResult<Variant>> {
+        let result = self.with_str(|s| {
+            let index = sequence_index(index, s.chars().count())?;
+            let ch = s.chars().nth(index).unwrap();
+            
+            let mut buf = [0u8; 4];
+            Ok(Variant::from(StringValue::new_uninterned(ch.encode_utf8(&mut buf))))
+        });
+        Some(result)
+    }
+    
+    // strings contain their substrings
+    fn has_item(&self, item: &Variant) -> Option<ExecResult<bool>> {
+        let result = match item.as_strval() {
+            Some(item) => Ok(self.with_str(|s| item.with_str(|item| s.contains(item)))),
+            None => Err(RuntimeError::invalid_binary_operands(item, &Variant::from(*self))),
+        };
+        Some(result)
+    }
+    
     fn op_add(&self, rhs: &Variant) -> Option<ExecResult<Variant>> {
         if let Some(rhs) = rhs.as_strval() {
             return Some(self.concat(&rhs).map(Variant::from))
@@ -48,12 +70,18 @@ impl MetaObject for StringValue {
     }
     
     fn fmt_repr(&self) -> ExecResult<StringValue> {
-        let mut buf = StrBuffer::<64>::new();
-        if write!(buf, "\"{}\"", self).is_ok() {
-            Ok(StringValue::new_uninterned(buf))
-        } else {
-            // resort to allocated buffer
-            Ok(StringValue::new_uninterned(format!("\"{}\"", self)))
-        }
+        self.with_str(|s| {
+            let mut buf = StrBuffer::<64>::new();
+            if write!(buf, "\"{}\"", escape_str(&s, false)).is_ok() {
+                Ok(StringValue::new_uninterned(buf))
+            } else {
+                // resort to allocated buffer
+                Ok(StringValue::new_uninterned(format!("\"{}\"", escape_str(&s, false))))
+            }
+        })
+    }
+    
+    fn fmt_str(&self) -> ExecResult<StringValue> {
+        Ok(*self)
     }
 }
\ No newline at end of file
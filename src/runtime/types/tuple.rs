@@ -4,18 +4,17 @@ use crate::runtime::Variant;
 use crate::runtime::gc::{Gc, GcTrace};
 use crate::runtime::strings::{StringValue, static_symbol};
 use crate::runtime::iter::IterState;
-use crate::runtime::types::{Type, MetaObject, UserIterator};
+use crate::runtime::types::{Type, MetaObject, UserIterator, sequence_index, sequence_contains, sequence_cmp};
 use crate::runtime::errors::{ExecResult, RuntimeError};
 
 #[derive(Clone, Copy)]
+#[derive(Default)]
 pub enum Tuple {
+    #[default]
     Empty,
     NonEmpty(Gc<[Variant]>),
 }
 
-impl Default for Tuple {
-    fn default() -> Self { Self::Empty }
-}
 
 impl From<Box<[Variant]>> for Tuple {
     fn from(items: Box<[Variant]>) -> Self {
@@ -57,7 +56,7 @@ impl Tuple {
     pub fn items(&self) -> &[Variant] {
         match self {
             Self::Empty => &[] as &[Variant],
-            Self::NonEmpty(items) => &**items,
+            Self::NonEmpty(items) => items,
         }
     }
 }
@@ -83,21 +82,7 @@ impl Tuple {
     
     // compare two tuple lexicographically
     fn cmp(&self, other: &Self) -> ExecResult<Ordering> {
-        let pairs = self.items().iter()
-            .zip(other.items().iter());
-        
-        for (a, b) in pairs {
-            if a.cmp_lt(b)? {
-                return Ok(Ordering::Less)
-            }
-            if !a.cmp_eq(b)? {
-                return Ok(Ordering::Greater)
-            }
-        }
-        
-        // if we get here then all tested elements were equal
-        // in which case the longer tuple is considered greater
-        Ok(self.len().cmp(&other.len()))
+        sequence_cmp(self.items(), other.items())
     }
     
     fn cmp_lt(&self, other: &Self) -> ExecResult<bool> {
@@ -116,6 +101,17 @@ impl MetaObject for Tuple {
         Some(Ok(Tuple::len(self)))
     }
     
+    fn get_index(&self, index: &Variant) -> Option<ExecResult<Variant>> {
+        let items = self.items();
+        let result = sequence_index(index, items.len())
+            .map(|index| items[index]);
+        Some(result)
+    }
+    
+    fn has_item(&self, item: &Variant) -> Option<ExecResult<bool>> {
+        Some(sequence_contains(self.items(), item))
+    }
+    
     fn iter_init(&self) -> Option<ExecResult<IterState>> {
         let iter: Box<dyn UserIterator> = Box::new(TupleIter(*self));
         let iter = Gc::from_box(iter);
@@ -159,10 +155,14 @@ impl MetaObject for Tuple {
                     write!(&mut buf, ", {}", item.fmt_repr()?)
                         .map_err(|err| RuntimeError::other(err.to_string()))?;
                 }
+                
+                // without the trailing comma this would read back as a group
+                if rest.is_empty() {
+                    buf.push(',');
+                }
                 buf.push(')');
-
                 
-                Ok(StringValue::new_maybe_interned(buf))
+                Ok(StringValue::new_uninterned(buf))
             }
         }
     }
@@ -193,8 +193,8 @@ impl UserIterator for TupleIter {
         let idx = usize::try_from(state.as_int()?)
             .map_err(|_| RuntimeError::invalid_value("invalid state"))?;
         
-        let items = self.0.items();
-        Ok(items[idx])
+        self.0.items().get(idx).copied()
+            .ok_or_else(|| RuntimeError::invalid_value("invalid state"))
     }
     
     fn next_state(&self, state: Option<&Variant>) -> ExecResult<Variant> {
@@ -2,7 +2,9 @@ use core::fmt;
 use core::hash::{Hash, Hasher};
 use static_assertions::const_assert_eq;
 use crate::language::{IntType, FloatType};
-use crate::runtime::types::{Tuple, UserData, UserIterator, Marker};
+use crate::runtime::types::{Type, Tuple, List, Dict, Set, UserData, UserIterator, Marker, Class, Instance, BoundMethod};
+#[cfg(feature = "bigint")]
+use crate::runtime::types::BigInt;
 use crate::runtime::function::{Function, NativeFunction};
 use crate::runtime::strings::{StringValue, StringSymbol, InlineStr};
 use crate::runtime::gc::{Gc, GcTrace};
@@ -25,17 +27,32 @@ pub enum Variant {
     
     Marker(Marker),
     
+    // the type of a value that is not an instance of a user-defined class
+    Type(Type),
+    
     Integer(IntType),
     Float(FloatType),
     
+    // only produced when integer arithmetic overflows
+    #[cfg(feature = "bigint")]
+    BigInt(Gc<BigInt>),
+    
     // separate different string types here to keep size down
     InternStr(StringSymbol),
     InlineStr(InlineStr),
     GCStr(Gc<str>),
     
     Tuple(Tuple),
+    List(Gc<List>),
+    Dict(Gc<Dict>),
+    Set(Gc<Set>),
+    
     Function(Gc<Function>),
     NativeFunction(Gc<NativeFunction>),
+    BoundMethod(Gc<BoundMethod>),
+    
+    Class(Gc<Class>),
+    Instance(Gc<Instance>),
     
     Iterator(Gc<dyn UserIterator>),
     
@@ -48,10 +65,20 @@ unsafe impl GcTrace for Variant {
     #[inline]
     fn trace(&self) {
         match self {
+            #[cfg(feature = "bigint")]
+            Self::BigInt(value) => value.mark_trace(),
+            Self::GCStr(gc_str) => gc_str.mark_trace(),
             Self::Tuple(tuple) => tuple.trace(),
+            Self::List(list) => list.mark_trace(),
+            Self::Dict(dict) => dict.mark_trace(),
+            Self::Set(set) => set.mark_trace(),
             Self::Function(fun) => fun.mark_trace(),
             Self::NativeFunction(fun) => fun.mark_trace(),
+            Self::BoundMethod(method) => method.mark_trace(),
+            Self::Class(class) => class.mark_trace(),
+            Self::Instance(instance) => instance.mark_trace(),
             Self::Iterator(iter) => iter.mark_trace(),
+            Self::Error(error) => error.mark_trace(),
             Self::UserData(data) => data.mark_trace(),
             _ => { },
         };
@@ -90,13 +117,6 @@ impl Variant {
         Display(self)
     }
     
-    pub fn fmt_str(&self) -> ExecResult<StringValue> {
-        if let Some(strval) = self.as_strval() {
-            Ok(strval)
-        } else {
-            self.fmt_repr()
-        }
-    }
 }
 
 
@@ -171,9 +191,14 @@ impl Variant {
                 => discr.hash(state),
             
             Self::Integer(value) => (discr, value).hash(state),
+            Self::Type(ty) => (discr, ty).hash(state),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(value) => (discr, &**value).hash(state),
             
             Self::Function(fun) => (discr, fun).hash(state),
             Self::NativeFunction(fun) => (discr, fun).hash(state),
+            Self::Class(class) => (discr, class).hash(state),
+            Self::Instance(instance) => (discr, instance).hash(state),
             Self::Tuple(items) => {
                 discr.hash(state); // also prevent prefix collisions
                 for item in items.as_ref().iter() {
@@ -193,31 +218,42 @@ impl Variant {
 
 
 /// Wrapper for variant that dynamically ensures hashability
-#[derive(Clone)]
-pub struct VariantKey<'a>(&'a Variant);
+#[derive(Debug, Clone, Copy)]
+pub struct VariantKey(Variant);
 
-impl<'a> TryFrom<&'a Variant> for VariantKey<'a> {
+impl VariantKey {
+    pub fn value(&self) -> &Variant { &self.0 }
+}
+
+impl TryFrom<Variant> for VariantKey {
     type Error = Box<RuntimeError>;
-    fn try_from(value: &'a Variant) -> ExecResult<Self> {
+    fn try_from(value: Variant) -> ExecResult<Self> {
         if !value.can_hash() {
-            return Err(RuntimeError::unhashable_value(value));
+            return Err(RuntimeError::unhashable_value(&value));
         }
         Ok(Self(value))
     }
 }
 
-impl Hash for VariantKey<'_> {
+unsafe impl GcTrace for VariantKey {
+    #[inline]
+    fn trace(&self) {
+        self.0.trace()
+    }
+}
+
+impl Hash for VariantKey {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.0.try_hash(state).unwrap()
     }
 }
 
-impl<'s> PartialEq for VariantKey<'_> {
+impl PartialEq for VariantKey {
     fn eq(&self, other: &VariantKey) -> bool {
-        self.0.cmp_eq(other.0).unwrap_or(false)
+        self.0.cmp_eq(&other.0).unwrap_or(false)
     }
 }
-impl Eq for VariantKey<'_> { }
+impl Eq for VariantKey { }
 
 impl fmt::Display for Variant {
     fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -243,18 +279,28 @@ impl fmt::Debug for Variant {
             Self::BoolTrue => fmt.write_str("True"),
             Self::BoolFalse => fmt.write_str("False"),
             Self::Marker(marker) => debug_tuple!(fmt, "Marker", marker),
+            Self::Type(ty) => debug_tuple!(fmt, "Type", ty),
             Self::Integer(value) => debug_tuple!(fmt, "Integer", value),
             Self::Float(value) => debug_tuple!(fmt, "Float", value),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(value) => debug_tuple!(fmt, "BigInt", &value.to_string()),
             Self::InternStr(value) => debug_tuple!(fmt, "InternStr", value),
             Self::InlineStr(value) => debug_tuple!(fmt, "InlineStr", &value.to_string()),
             Self::GCStr(gc_str) => debug_tuple!(fmt, "GCStr", &gc_str.to_string()),
             Self::Tuple(tuple) => debug_tuple!(fmt, "Tuple", tuple),
+            Self::List(list) => debug_tuple!(fmt, "List", &list.to_vec()),
+            Self::Dict(dict) => debug_tuple!(fmt, "Dict", &dict.to_vec()),
+            Self::Set(set) => debug_tuple!(fmt, "Set", &set.to_vec()),
             Self::Function(fun)
                 => debug_tuple!(fmt, "Function", &fun.signature().fmt_signature().to_string()),
             Self::NativeFunction(fun) 
                 => debug_tuple!(fmt, "NativeFunction", &fun.signature().fmt_signature().to_string()),
+            Self::BoundMethod(method)
+                => debug_tuple!(fmt, "BoundMethod", &method.method().fmt_signature().to_string()),
+            Self::Class(class) => debug_tuple!(fmt, "Class", &class.name()),
+            Self::Instance(instance) => debug_tuple!(fmt, "Instance", &instance.class().name()),
             Self::Iterator(iter) => debug_tuple!(fmt, "Iterator", iter),
-            Self::Error(error) => write!(fmt, "{:?}", &**error),
+            Self::Error(error) => write!(fmt, "{:?}", **error),
             Self::UserData(data) => debug_tuple!(fmt, "UserData", data),
         }
     }
@@ -1,17 +1,26 @@
+use core::fmt;
 use core::cell::Cell;
 use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::io;
+use crate::language::IntType;
 use crate::runtime::{Variant, HashMap};
-use crate::runtime::gc::{Gc, GcWeak, GcTrace, gc_collect};
+use crate::runtime::gc::{Gc, GcWeak, GcTrace, GcStats, gc_collect, gc_force, gc_barrier, gc_allocated, gc_stats, gc_pause_factor, gc_set_pause_factor, gc_dump_heap};
 use crate::runtime::function::{Call, Function, Upvalue, UpvalueIndex, Closure};
-use crate::runtime::module::Module;
-use crate::runtime::errors::ExecResult;
+use crate::runtime::module::{Module, ModuleIdent, ModuleLoader, Chunk};
+use crate::runtime::types::take_pending_finalizers;
+use crate::runtime::random::Random;
+use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
+use crate::runtime::errors::{ExecResult, RuntimeError, ErrorKind};
 use crate::debug::traceback::TraceSite;
 use crate::debug::snapshot::{VMSnapshot, VMFrameSnapshot};
 
 mod callframe;
 mod instruction;
+mod cache;
 
-use callframe::VMCallFrame;
+use callframe::{VMCallFrame, ReturnTo};
+pub use cache::InlineCache;
 
 
 // Helpers
@@ -22,13 +31,29 @@ struct CallInfo {
     local_frame: usize,
     call: Call,
     site: TraceSite,
+    on_return: ReturnTo,
+}
+
+// data used to set up an import
+struct ImportInfo {
+    name: StringSymbol,
+    site: TraceSite,
+}
+
+struct ConcatInfo {
+    count: usize,
+    site: TraceSite,
 }
 
 enum Control {
-    Next,            // keep executing
-    Call(CallInfo),  // setup a call
-    Return(Variant), // return from call
-    Exit(Variant),   // stop execution
+    Next,                // keep executing
+    Call(CallInfo),      // setup a call
+    TailCall(CallInfo),  // setup a call, reusing the current call frame if possible
+    Import(ImportInfo),  // execute an imported module
+    Concat(ConcatInfo),  // concatenate values that may have to be formatted by calling a method
+    Inspect,             // write the value on top of the stack to the program output
+    Return(Variant),     // return from call
+    Exit(Variant),       // stop execution
 }
 
 
@@ -99,16 +124,92 @@ impl UpvalueWeakRef {
 // struct UpvalueWeakRef
 
 
+// where print() and the other builtins that produce output write to
+struct Output<'c>(Box<dyn io::Write + 'c>);
+
+impl fmt::Debug for Output<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("Output")
+    }
+}
+
+// where input() reads from
+enum Input<'c> {
+    // stdin is not locked or buffered by the VM, so that whatever else reads from it (e.g. the REPL) still can
+    Stdin,
+    Reader(Box<dyn io::BufRead + 'c>),
+}
+
+impl Input<'_> {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        match self {
+            Self::Stdin => io::stdin().read_line(buf),
+            Self::Reader(reader) => reader.read_line(buf),
+        }
+    }
+}
+
+impl fmt::Debug for Input<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => fmt.write_str("Stdin"),
+            Self::Reader(..) => fmt.write_str("Reader"),
+        }
+    }
+}
+
+
+/// The default limit on the number of nested calls
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+// Methods called from native code (e.g. __str__ called by print()) run in a nested execution loop on the native stack,
+// so the nesting of these calls needs a much lower limit
+const MAX_NESTED_METHOD_CALLS: usize = 64;
+
+// The frames and traceback entries below these indices belong to the native code that is waiting 
+// for a method to return. Errors raised by the method can't unwind past the barrier.
+#[derive(Debug, Default, Clone, Copy)]
+struct CallBarrier {
+    calls: usize,
+    traceback: usize,
+    nested: usize,  // the number of methods called from native code that are in progress
+}
+
+impl CallBarrier {
+    // the number of native calls in progress, each of which has a traceback entry but no call frame
+    fn native_calls(&self) -> usize {
+        self.traceback - self.calls
+    }
+}
+
+// initial capacities, so that most programs never need to grow the stacks
+const INITIAL_STACK_CAPACITY: usize = 256;
+const INITIAL_CALLS_CAPACITY: usize = 32;
+
 // Stack-based Virtual Machine
 #[derive(Debug)]
 pub struct VirtualMachine<'c> {
     traceback: Vec<TraceSite>,
+    barrier: CallBarrier,
+    max_call_depth: usize,
+    budget: Option<u64>,
+    memory_limit: Option<usize>,
+    interrupt: Option<&'static AtomicBool>,
+    output: Output<'c>,
+    input: Input<'c>,
+    args: Vec<String>,
+    exit_code: Option<i32>,
+    random: Random,
     
     frame: VMCallFrame<'c>,  // the active call frame
     calls: Vec<VMCallFrame<'c>>,
     locals: ValueStack,
     stack: ValueStack,
     upvalues: OpenUpvalues,
+    loader: ModuleLoader,
+    
+    // a main chunk given to reload_program(), must not be replaced while any frame refers to it
+    owned_chunk: Vec<u8>,
 }
 
 impl<'c> VirtualMachine<'c> {
@@ -116,20 +217,125 @@ impl<'c> VirtualMachine<'c> {
     pub fn new(main_module: Gc<Module>, main_chunk: &'c [u8]) -> Self {
         Self {
             traceback: Vec::new(),
-            calls: Vec::new(),
-            locals: ValueStack::new(),
-            stack: ValueStack::new(),
+            barrier: CallBarrier::default(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            budget: None,
+            memory_limit: None,
+            interrupt: None,
+            output: Output(Box::new(io::stdout())),
+            input: Input::Stdin,
+            args: Vec::new(),
+            exit_code: None,
+            random: Random::from_entropy(),
+            calls: Vec::with_capacity(INITIAL_CALLS_CAPACITY),
+            locals: ValueStack::with_capacity(INITIAL_STACK_CAPACITY),
+            stack: ValueStack::with_capacity(INITIAL_STACK_CAPACITY),
             frame: VMCallFrame::main_chunk(main_module, main_chunk),
             upvalues: OpenUpvalues::new(),
+            loader: ModuleLoader::for_module(&main_module),
+            owned_chunk: Vec::new(),
         }
     }
     
+    /// Prepare the VM to run a new main chunk, e.g. the next line entered into the REPL.
+    ///
+    /// The memory allocated for the value stacks and call frames is kept, so that it does not have
+    /// to be allocated again. All other state from the previous run is discarded except for the
+    /// VM's settings and the modules that were imported, which stay cached.
+    pub fn reload_program(&mut self, main_module: Gc<Module>, main_chunk: Box<[u8]>) {
+        let main_chunk = main_chunk.into_vec();
+        
+        // SAFETY: The chunk is owned by the VM until the next reload, and moving a Vec does not move its contents.
+        // The old chunk is only dropped after every frame that refers to it has been discarded.
+        let chunk: *const [u8] = main_chunk.as_slice();
+        let chunk = unsafe { chunk.as_ref::<'c>().unwrap() };
+        
+        self.frame = VMCallFrame::main_chunk(main_module, chunk);
+        self.calls.clear();
+        self.owned_chunk = main_chunk;
+        
+        self.exit_code = None;
+        self.traceback.clear();
+        self.barrier = CallBarrier::default();
+        self.locals.clear();
+        self.stack.clear();
+        self.upvalues.clear();
+    }
+    
     pub fn frame(&self) -> &VMCallFrame<'_> { &self.frame }
     
+    pub fn loader(&self) -> &ModuleLoader { &self.loader }
+    
+    pub fn max_call_depth(&self) -> usize { self.max_call_depth }
+    
+    /// Calls nested deeper than this raise a StackOverflowError
+    pub fn set_max_call_depth(&mut self, depth: usize) { self.max_call_depth = depth }
+    
+    /// The number of instructions that can still be executed, if there is a limit
+    pub fn instruction_budget(&self) -> Option<u64> { self.budget }
+    
+    /// Once the budget is used up, execution stops with a BudgetExceededError that can't be caught by the script
+    pub fn set_instruction_budget(&mut self, budget: Option<u64>) { self.budget = budget }
+    
+    pub fn memory_limit(&self) -> Option<usize> { self.memory_limit }
+    
+    /// Exceeding the memory limit raises a MemoryError, unless a garbage collection can bring usage back under it.
+    /// Note that the limit applies to all memory managed by the garbage collector in the current heap, see `GcHeap`.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) { self.memory_limit = limit }
+    
+    /// Statistics for the garbage collector, which is shared by everything in the current heap
+    pub fn gc_stats(&self) -> GcStats { gc_stats() }
+    
+    pub fn gc_pause_factor(&self) -> u16 { gc_pause_factor() }
+    
+    /// The percentage growth in memory use after a collection that will trigger the next one
+    pub fn set_gc_pause_factor(&mut self, percent: u16) { gc_set_pause_factor(percent) }
+    
+    /// Runs a complete garbage collection cycle immediately
+    pub fn collect_garbage(&mut self) { gc_force(self) }
+    
+    /// Writes a report of every allocation reachable from the VM, for diagnosing leaks
+    pub fn dump_heap(&self, out: &mut impl io::Write) -> io::Result<()> { gc_dump_heap(self, out) }
+    
+    /// While running, the VM checks the flag regularly. When it finds the flag set, 
+    /// it clears it and raises an InterruptedError, e.g. after a signal handler sets the flag when Ctrl-C is pressed.
+    pub fn set_interrupt_flag(&mut self, flag: Option<&'static AtomicBool>) { self.interrupt = flag }
+    
+    pub fn loader_mut(&mut self) -> &mut ModuleLoader { &mut self.loader }
+    
+    /// Where program output (e.g. from print()) is written, stdout by default
+    pub fn output(&mut self) -> &mut (dyn io::Write + 'c) { &mut *self.output.0 }
+    
+    /// Redirect program output, e.g. so that an embedder can capture it
+    pub fn set_output(&mut self, output: impl io::Write + 'c) { self.output = Output(Box::new(output)) }
+    
+    /// Read a line of program input (e.g. for input()) into the buffer, including the line ending.
+    /// Like `BufRead::read_line()`, the result is zero at the end of the input.
+    pub fn read_line(&mut self, buf: &mut String) -> io::Result<usize> { self.input.read_line(buf) }
+    
+    /// Read program input from somewhere other than stdin
+    pub fn set_input(&mut self, input: impl io::BufRead + 'c) { self.input = Input::Reader(Box::new(input)) }
+    
+    /// The arguments given to the program, e.g. by the command line (produced by os.args())
+    pub fn args(&self) -> &[String] { &self.args }
+    
+    pub fn set_args(&mut self, args: Vec<String>) { self.args = args }
+    
+    /// Stop running the program as soon as the current instruction is finished, e.g. for os.exit().
+    /// Unlike an error, this can't be caught by the program and doesn't run any pending finalizers.
+    pub fn exit(&mut self, code: i32) { self.exit_code = Some(code) }
+    
+    /// The exit code given to exit(), if the program was stopped that way
+    pub fn exit_code(&self) -> Option<i32> { self.exit_code }
+    
+    /// The random number generator used by the random module, seeded differently for each VM unless
+    /// the program seeds it. It is kept by reload_program(), so the REPL continues the same sequence.
+    pub fn random(&mut self) -> &mut Random { &mut self.random }
+    
     // the return value is mostly of interest to the REPL
-    pub fn run(mut self) -> ExecResult<Variant> {
+    pub fn run(&mut self) -> ExecResult<Variant> {
         loop {
-            if let Control::Exit(value) = self.exec_next()? {
+            if let Control::Exit(value) = self.exec(false)? {
                 return Ok(value)
             }
         }
@@ -139,48 +345,201 @@ impl<'c> VirtualMachine<'c> {
         VMStepper::from(self)
     }
     
+    /// Format a value for str(), print() and string interpolation. Instances of a class that has 
+    /// a __str__ method, or else a __repr__ method, are formatted by calling it.
+    pub fn fmt_str(&mut self, value: &Variant) -> ExecResult<StringValue> {
+        if let Variant::Instance(instance) = value {
+            let class = instance.class();
+            for name in [static_symbol!("__str__"), static_symbol!("__repr__")] {
+                if let Some(method) = class.lookup_method(&name) {
+                    return self.call_format_method(*value, name, method);
+                }
+            }
+        }
+        value.fmt_str()
+    }
+    
+    /// Format a value for repr(). Instances of a class that has a __repr__ method are formatted by calling it.
+    pub fn fmt_repr(&mut self, value: &Variant) -> ExecResult<StringValue> {
+        if let Variant::Instance(instance) = value {
+            let name = static_symbol!("__repr__");
+            if let Some(method) = instance.class().lookup_method(&name) {
+                return self.call_format_method(*value, name, method);
+            }
+        }
+        value.fmt_repr()
+    }
+    
+    fn call_format_method(&mut self, receiver: Variant, name: StringSymbol, method: Gc<Function>) -> ExecResult<StringValue> {
+        let result = self.call_method(receiver, method, &[])?;
+        result.as_strval().ok_or_else(|| RuntimeError::invalid_format_result(name, &result))
+    }
+    
     #[inline]
     fn exec_next(&mut self) -> ExecResult<Control> {
-        let control = self.frame.exec_next(&mut self.stack, &mut self.locals, &mut self.upvalues)
-            .map_err(|error| error.extend_trace(self.traceback.iter().rev().cloned()))?;
+        self.exec(true)
+    }
+    
+    // unless single stepping, instructions are executed in batches that end at a safepoint, 
+    // which is where the rest of the VM's bookkeeping (e.g. collecting garbage) is done
+    #[inline]
+    fn exec(&mut self, single_step: bool) -> ExecResult<Control> {
+        if let Some(budget) = self.budget.as_mut() {
+            if *budget == 0 {
+                let error = RuntimeError::budget_exceeded()
+                    .push_trace(self.frame.get_trace(self.frame.pc))
+                    .extend_trace(self.call_trace());
+                return Err(error);
+            }
+            *budget -= 1;
+        }
+        
+        if let Some(flag) = self.interrupt {
+            if flag.load(Ordering::Relaxed) {
+                flag.store(false, Ordering::Relaxed);
+                let error = RuntimeError::interrupted()
+                    .push_trace(self.frame.get_trace(self.frame.pc))
+                    .extend_trace(self.call_trace());
+                return self.catch_error(error);
+            }
+        }
+        
+        if let Some(limit) = self.memory_limit {
+            if gc_allocated() > limit {
+                gc_force(self);
+                
+                let allocated = gc_allocated();
+                if allocated > limit {
+                    let error = RuntimeError::out_of_memory(allocated)
+                        .push_trace(self.frame.get_trace(self.frame.pc))
+                        .extend_trace(self.call_trace());
+                    return self.catch_error(error);
+                }
+            }
+        }
+        
+        let result =
+            if single_step { self.frame.exec_next(&mut self.stack, &mut self.locals, &mut self.upvalues) }
+            else { self.frame.exec_until_safepoint(&mut self.stack, &mut self.locals, &mut self.upvalues, &mut self.budget) };
+        
+        let result = result.map_err(|error| error.extend_trace(self.call_trace()));
+        
+        let mut control = match result {
+            Ok(control) => control,
+            Err(error) => self.catch_error(error)?,
+        };
         
         match &control {
+            // the main chunk of an imported module has finished, resume the importer
+            Control::Exit(..) if !self.calls.is_empty() => {
+                self.finish_import();
+                control = Control::Next;
+            },
+            
             Control::Exit(..) => return Ok(control),
             
             Control::Return(value) if self.calls.is_empty() =>
                 return Ok(Control::Exit(*value)),
             
-            Control::Return(value) => self.return_call(*value),
-            Control::Call(info) => self.setup_call(info)?,
+            Control::Return(value) => if let Err(error) = self.return_call(*value) {
+                let error = error.extend_trace(self.call_trace());
+                self.catch_error(error)?;
+            },
+            Control::Call(info) => if let Err(error) = self.setup_call(info) {
+                let error = error.push_trace(info.site.clone())
+                    .extend_trace(self.call_trace());
+                self.catch_error(error)?;
+            },
+            
+            Control::TailCall(info) => if let Err(error) = self.setup_tail_call(info) {
+                let error = error.push_trace(info.site.clone())
+                    .extend_trace(self.call_trace());
+                self.catch_error(error)?;
+            },
+            
+            Control::Import(info) => if let Err(error) = self.setup_import(info) {
+                let error = error.push_trace(info.site.clone())
+                    .extend_trace(self.call_trace());
+                self.catch_error(error)?;
+            },
+            
+            Control::Concat(info) => if let Err(error) = self.concat(info) {
+                let error = error.push_trace(info.site.clone())
+                    .extend_trace(self.call_trace());
+                self.catch_error(error)?;
+            },
+            
+            Control::Inspect => if let Err(error) = self.inspect() {
+                let error = error.extend_trace(self.call_trace());
+                self.catch_error(error)?;
+            },
             
             Control::Next => { }
         }
         
+        if self.exit_code.is_some() {
+            return Ok(Control::Exit(Variant::Nil));
+        }
+        
         self.upvalues.prune_invalid();
         gc_collect(self);
+        self.setup_finalizers();
         
         Ok(control)
     }
     
     fn setup_call(&mut self, callinfo: &CallInfo) -> ExecResult<()> {
-        self.traceback.push(callinfo.site.clone());
-        
         match callinfo.call {
             Call::Native { func, nargs } => {
-                let args = self.stack.peek_many(nargs)
-                    .iter().copied().collect::<Vec<Variant>>();
+                let args = self.stack.peek_many(nargs)?.to_vec();
+                
+                self.traceback.push(callinfo.site.clone());
+                let result = func.exec_fun(self, &args);
+                self.traceback.pop();
                 
-                let retval = func.exec_fun(self, &args)?;
+                let retval = result?;
                 self.stack.truncate(callinfo.stack_frame);
                 self.locals.truncate(callinfo.local_frame);
-                self.stack.push(retval);
+                self.push_return_value(callinfo.on_return, retval)?;
+            },
+            
+            Call::NativeMethod { method, receiver, nargs } => {
+                let args = self.stack.peek_many(nargs)?.to_vec();
+                
+                self.traceback.push(callinfo.site.clone());
+                let result = method.exec_method(&receiver, &args);
                 self.traceback.pop();
+                
+                let retval = result?;
+                self.stack.truncate(callinfo.stack_frame);
+                self.locals.truncate(callinfo.local_frame);
+                self.push_return_value(callinfo.on_return, retval)?;
             },
             
-            Call::Chunk { module, chunk_id } => {
+            Call::NativeCtor { ctor, nargs } => {
+                let args = self.stack.peek_many(nargs)?.to_vec();
+                
+                self.traceback.push(callinfo.site.clone());
+                let result = ctor.exec_ctor(self, &args);
+                self.traceback.pop();
+                
+                let retval = result?;
+                self.stack.truncate(callinfo.stack_frame);
+                self.locals.truncate(callinfo.local_frame);
+                self.push_return_value(callinfo.on_return, retval)?;
+            },
+            
+            Call::Chunk { function, .. } => {
+                if self.calls.len() >= self.max_call_depth {
+                    return Err(RuntimeError::stack_overflow(self.max_call_depth));
+                }
+                
+                self.traceback.push(callinfo.site.clone());
+                
                 let mut frame = VMCallFrame::call_frame(
-                    module, chunk_id, callinfo.stack_frame, callinfo.local_frame
+                    function, callinfo.stack_frame, callinfo.local_frame
                 );
+                frame.on_return = callinfo.on_return;
                 core::mem::swap(&mut self.frame, &mut frame);
                 self.calls.push(frame);
                 
@@ -194,7 +553,177 @@ impl<'c> VirtualMachine<'c> {
         Ok(())
     }
     
-    fn return_call(&mut self, retval: Variant) {
+    // interrupt execution to call the __del__ method of every instance that has been found to be unreachable
+    fn setup_finalizers(&mut self) {
+        for instance in take_pending_finalizers().into_iter() {
+            let method = instance.class().lookup_method(&static_symbol!("__del__"))
+                .expect("instance does not have a finalizer");
+            
+            let receiver = Variant::Instance(instance);
+            if let Err(error) = method.method_call(receiver, &[]) {
+                log::warn!("error ignored in finalizer: {}", error);
+                continue;
+            }
+            
+            let stack_frame = self.stack.len();
+            let local_frame = self.locals.len();
+            self.stack.push(Variant::Function(method));
+            self.locals.push(receiver);
+            self.locals.push(Variant::from(0));
+            
+            self.traceback.push(self.frame.get_trace(self.frame.pc));
+            
+            let mut frame = VMCallFrame::call_frame(method, stack_frame, local_frame);
+            frame.finalizer = true;
+            core::mem::swap(&mut self.frame, &mut frame);
+            self.calls.push(frame);
+            
+            log::debug!("Setup finalizer: {}", receiver);
+        }
+    }
+    
+    fn setup_tail_call(&mut self, callinfo: &CallInfo) -> ExecResult<()> {
+        // only a function's call frame can be replaced, and not while an error handler is active
+        if self.frame.function.is_none() || !self.frame.handlers.is_empty() {
+            return self.setup_call(callinfo);
+        }
+        
+        let stack_idx = self.frame.stack_frame();
+        let local_idx = self.frame.local_frame();
+        self.upvalues.close_all_above(&self.locals, local_idx);
+        
+        // native functions are called normally, the RETURN that follows will discard the current frame
+        let function = match callinfo.call {
+            Call::Chunk { function, .. } => function,
+            Call::Native { .. } | Call::NativeMethod { .. } | Call::NativeCtor { .. } => return self.setup_call(callinfo),
+        };
+        
+        // move the new call frame down so that it replaces the current one
+        self.stack.discard_at(stack_idx, callinfo.stack_frame - stack_idx);
+        self.locals.discard_at(local_idx, callinfo.local_frame - local_idx);
+        
+        let (finalizer, on_return) = (self.frame.finalizer, self.frame.on_return);
+        self.frame = VMCallFrame::call_frame(function, stack_idx, local_idx);
+        self.frame.tail_site = Some(callinfo.site.clone());  // keep the traceback informative
+        self.frame.finalizer = finalizer;
+        self.frame.on_return = on_return;
+        
+        log::debug!(
+            "Setup tail call: {{ stack: {}, locals: {} }}", 
+            self.frame.stack_frame(), self.frame.local_frame()
+        );
+        
+        Ok(())
+    }
+    
+    // the sites of all active calls, most recent first
+    // inside a method called from native code, this stops at the method since the native call adds the rest
+    fn call_trace(&self) -> impl Iterator<Item=TraceSite> + '_ {
+        let frames = core::iter::once(&self.frame).chain(self.calls[self.barrier.calls..].iter().rev());
+        let call_sites = self.traceback[self.barrier.traceback..].iter().rev().map(Some).chain(core::iter::repeat(None));
+        
+        frames.zip(call_sites)
+            .flat_map(|(frame, call_site)| frame.tail_site.iter().chain(call_site))
+            .cloned()
+    }
+    
+    // unwind to the nearest error handler, if there is one
+    fn catch_error(&mut self, error: Box<RuntimeError>) -> ExecResult<Control> {
+        // also when it was raised by a method called from native code
+        if matches!(error.kind(), ErrorKind::BudgetExceeded) {
+            return Err(error);
+        }
+        
+        // the native code that called exit() may fail afterwards, but the program is stopping anyway
+        if self.exit_code.is_some() {
+            return Ok(Control::Next);
+        }
+        
+        let catch_frame = core::iter::once(&self.frame).chain(self.calls[self.barrier.calls..].iter().rev())
+            .find(|frame| !frame.handlers.is_empty() || frame.finalizer);
+        
+        match catch_frame {
+            None => return Err(error),
+            
+            // errors don't propagate out of a finalizer, since that would disrupt whatever code it interrupted
+            Some(frame) if frame.handlers.is_empty() => {
+                log::warn!("error ignored in finalizer: {}", error);
+                self.abort_finalizer();
+                return Ok(Control::Next);
+            },
+            
+            Some(..) => { },
+        }
+        
+        while self.frame.handlers.is_empty() {
+            self.frame = self.calls.pop().expect("empty call stack");
+        }
+        
+        let handler = self.frame.handlers.pop().unwrap();
+        
+        self.upvalues.close_all_above(&self.locals, handler.locals_len);
+        self.stack.truncate(handler.stack_len);
+        self.locals.truncate(handler.locals_len);
+        self.traceback.truncate(self.calls.len() + self.barrier.native_calls());
+        
+        self.stack.push(Variant::Error(Gc::new(*error)));
+        self.frame.pc = handler.target;
+        
+        log::debug!(
+            "Catch error: {{ stack: {}, locals: {} }}", 
+            self.stack.len(), self.locals.len()
+        );
+        
+        Ok(Control::Next)
+    }
+    
+    fn setup_import(&mut self, info: &ImportInfo) -> ExecResult<()> {
+        let name = info.name.to_string();
+        
+        let path = self.loader.find_module(&name)
+            .ok_or_else(|| RuntimeError::module_not_found(&name))?;
+        
+        let ident = path.canonicalize()
+            .map(ModuleIdent::SourcePath)
+            .map_err(|error| RuntimeError::import_failed(&name, error))?;
+        
+        if self.is_executing(&ident) {
+            return Err(RuntimeError::circular_import(&name));
+        }
+        
+        if let Some(module) = self.loader.get_cached(&ident) {
+            let globals = self.frame.module().globals();
+            self.loader.bind_exports(&module, &mut globals.borrow_mut());
+            return Ok(());
+        }
+        
+        let (module, chunk) = self.loader.load_module(&name, path)?;
+        
+        // SAFETY: The main chunk is owned by the loader, which lives as long as the VM. Cache entries
+        // are only replaced when their module is not being executed, so the chunk outlives this frame.
+        let chunk: *const [u8] = chunk;
+        let chunk = unsafe { chunk.as_ref::<'c>().unwrap() };
+        
+        let mut frame = VMCallFrame::module_frame(
+            module, chunk, self.stack.len(), self.locals.len()
+        );
+        core::mem::swap(&mut self.frame, &mut frame);
+        self.calls.push(frame);
+        self.traceback.push(info.site.clone());
+        
+        log::debug!("Setup import: {}", *module);
+        
+        Ok(())
+    }
+    
+    // true if the main chunk of the given module is somewhere in the call stack
+    fn is_executing(&self, ident: &ModuleIdent) -> bool {
+        core::iter::once(&self.frame).chain(self.calls.iter())
+            .filter(|frame| matches!(frame.chunk_id, Chunk::Main))
+            .any(|frame| frame.module.ident() == ident)
+    }
+    
+    fn finish_import(&mut self) {
         let stack_idx = self.frame.stack_frame();
         let local_idx = self.frame.local_frame();
         
@@ -203,13 +732,161 @@ impl<'c> VirtualMachine<'c> {
         
         self.stack.truncate(stack_idx);
         self.locals.truncate(local_idx);
-        self.stack.push(retval);
         self.traceback.pop();
         
+        let module = frame.module();
+        self.loader.set_ready(module.ident());
+        
+        let globals = self.frame.module().globals();
+        self.loader.bind_exports(&module, &mut globals.borrow_mut());
+        
+        log::debug!("Finish import: {}", *module);
+    }
+    
+    fn abort_finalizer(&mut self) {
+        while !self.frame.finalizer {
+            self.frame = self.calls.pop().expect("empty call stack");
+        }
+        self.traceback.truncate(self.calls.len() + self.barrier.native_calls());
+        
+        self.upvalues.close_all_above(&self.locals, self.frame.local_frame());
+        self.return_call(Variant::Nil).expect("finalizer result is discarded");
+    }
+    
+    fn return_call(&mut self, retval: Variant) -> ExecResult<()> {
+        let stack_idx = self.frame.stack_frame();
+        let local_idx = self.frame.local_frame();
+        
+        let mut frame = self.calls.pop().expect("empty call stack");
+        core::mem::swap(&mut self.frame, &mut frame);
+        
+        self.stack.truncate(stack_idx);
+        self.locals.truncate(local_idx);
+        let site = self.traceback.pop();
+        
         log::debug!(
             "Return call: {{ stack: {}, locals: {} }}", 
             self.frame.stack_frame(), self.frame.local_frame()
         );
+        
+        if frame.finalizer {
+            return Ok(());
+        }
+        
+        // errors delivering the value belong to the call site
+        self.push_return_value(frame.on_return, retval)
+            .map_err(|error| match site {
+                Some(site) => error.push_trace(site),
+                None => error,
+            })
+    }
+    
+    // Call a method from native code and run it to completion. Unlike calls made by the program, this nests
+    // on the native stack. Errors raised by the method are returned instead of being caught by a handler
+    // outside of it, and the result is not rooted once it is returned.
+    fn call_method(&mut self, receiver: Variant, method: Gc<Function>, args: &[Variant]) -> ExecResult<Variant> {
+        if self.barrier.nested >= MAX_NESTED_METHOD_CALLS {
+            return Err(RuntimeError::stack_overflow(MAX_NESTED_METHOD_CALLS));
+        }
+        
+        let call = method.method_call(receiver, args)?;
+        
+        // the method is called from the site of the native call that is in progress
+        let site = self.traceback.last().cloned()
+            .unwrap_or_else(|| self.frame.get_trace(self.frame.pc));
+        
+        let depth = self.calls.len();
+        let traceback_len = self.traceback.len();
+        let callinfo = CallInfo {
+            stack_frame: self.stack.len(),
+            local_frame: self.locals.len(),
+            call,
+            site,
+            on_return: ReturnTo::Stack,
+        };
+        
+        self.stack.push(receiver);
+        self.stack.extend(args);
+        self.locals.push(receiver);
+        self.locals.push(Variant::from(IntType::try_from(args.len()).unwrap()));
+        
+        let saved_barrier = self.barrier;
+        self.barrier = CallBarrier {
+            calls: depth + 1,
+            traceback: traceback_len + 1,
+            nested: saved_barrier.nested + 1,
+        };
+        
+        let mut result = self.setup_call(&callinfo);
+        while result.is_ok() && self.calls.len() > depth && self.exit_code.is_none() {
+            result = self.exec(false).map(|_| ());
+        }
+        
+        self.barrier = saved_barrier;
+        
+        // the method raised an error, or the program was stopped by exit()
+        if result.is_err() || self.calls.len() > depth {
+            while self.calls.len() > depth {
+                self.frame = self.calls.pop().expect("empty call stack");
+            }
+            self.upvalues.close_all_above(&self.locals, callinfo.local_frame);
+            self.stack.truncate(callinfo.stack_frame);
+            self.locals.truncate(callinfo.local_frame);
+            self.traceback.truncate(traceback_len);
+            
+            result?;
+            return Ok(Variant::Nil);
+        }
+        
+        self.stack.pop()
+    }
+    
+    // string interpolation where some of the values are instances, which can only be formatted by the VM
+    fn concat(&mut self, info: &ConcatInfo) -> ExecResult<()> {
+        // any methods are called from the site of the interpolation, like from a native call
+        self.traceback.push(info.site.clone());
+        let result = self.format_parts(info.count);
+        self.traceback.pop();
+        
+        let buf = result?;
+        self.stack.discard(info.count)?;
+        self.stack.push(Variant::from(StringValue::new_uninterned(buf)));
+        Ok(())
+    }
+    
+    fn inspect(&mut self) -> ExecResult<()> {
+        let line = format!("{}\n", self.stack.peek()?.display_echo());
+        let output = self.output();
+        output.write_all(line.as_bytes())
+            .and_then(|_| output.flush())
+            .map_err(|error| RuntimeError::other(format!("could not write output: {}", error)))
+    }
+    
+    fn format_parts(&mut self, count: usize) -> ExecResult<String> {
+        let mut buf = String::new();
+        for idx in 0..count {
+            // the values stay on the stack so they remain rooted while formatting
+            let value = self.stack.peek_many(count)?[idx];
+            self.fmt_str(&value)?.with_str(|s| buf.push_str(s));
+        }
+        Ok(buf)
+    }
+    
+    fn push_return_value(&mut self, on_return: ReturnTo, retval: Variant) -> ExecResult<()> {
+        match on_return {
+            ReturnTo::Stack => self.stack.push(retval),
+            ReturnTo::Local(index) => self.locals.set_local(index, retval),
+            
+            ReturnTo::Compare { negate } => {
+                let result = retval.as_bool()? != negate;
+                self.stack.push(Variant::from(result));
+            },
+            
+            ReturnTo::Jump { offset, jump_if } => if retval.as_bool()? == jump_if {
+                self.frame.pc = self.frame.offset_pc(offset).expect("pc overflow/underflow");
+            },
+        }
+        Ok(())
     }
 }
 
@@ -235,11 +912,23 @@ unsafe impl GcTrace for VirtualMachine<'_> {
         for upval_ref in self.upvalues.iter_refs() {
             upval_ref.mark_trace();
         }
+        
+        // imported modules
+        self.loader.trace();
     }
 }
 
 
 // Stack Manipulation
+
+// The verifier can't follow the depth of the stack through every instruction (e.g. `ITER_UNPACK`), 
+// so running off the bottom of the stack is an error rather than a panic.
+#[cold]
+#[inline(never)]
+fn stack_underflow() -> Box<RuntimeError> {
+    RuntimeError::invalid_bytecode("stack underflow")
+}
+
 #[derive(Debug)]
 struct ValueStack {
     stack: Vec<Variant>,
@@ -250,6 +939,10 @@ impl ValueStack {
         Self { stack: Vec::new() }
     }
     
+    fn with_capacity(capacity: usize) -> Self {
+        Self { stack: Vec::with_capacity(capacity) }
+    }
+    
     fn take(self) -> Vec<Variant> {
         self.stack
     }
@@ -275,13 +968,20 @@ impl ValueStack {
     }
     
     #[inline(always)]
-    fn pop(&mut self) -> Variant {
-        self.stack.pop().expect("empty stack")
+    fn pop(&mut self) -> ExecResult<Variant> {
+        self.stack.pop().ok_or_else(stack_underflow)
     }
     
+    // the index where the last `count` values begin
     #[inline(always)]
-    fn pop_many(&mut self, count: usize) -> Vec<Variant> {
-        self.stack.split_off(self.stack.len() - count)
+    fn index_from_top(&self, count: usize) -> ExecResult<usize> {
+        self.stack.len().checked_sub(count).ok_or_else(stack_underflow)
+    }
+    
+    #[inline(always)]
+    fn pop_many(&mut self, count: usize) -> ExecResult<Vec<Variant>> {
+        let index = self.index_from_top(count)?;
+        Ok(self.stack.split_off(index))
     }
 
     #[inline(always)]
@@ -290,8 +990,10 @@ impl ValueStack {
     }
     
     #[inline(always)]
-    fn discard(&mut self, count: usize) {
-        self.stack.truncate(self.stack.len() - count)
+    fn discard(&mut self, count: usize) -> ExecResult<()> {
+        let index = self.index_from_top(count)?;
+        self.stack.truncate(index);
+        Ok(())
     }
     
     #[inline(always)]
@@ -316,14 +1018,16 @@ impl ValueStack {
     }
     
     #[inline(always)]
-    fn replace(&mut self, value: Variant) {
-        *self.stack.last_mut().expect("empty stack") = value;
+    fn replace(&mut self, value: Variant) -> ExecResult<()> {
+        *self.stack.last_mut().ok_or_else(stack_underflow)? = value;
+        Ok(())
     }
     
     #[inline(always)]
-    fn replace_many(&mut self, count: usize, value: Variant) {
-        let replace_range = (self.stack.len()-count).. ;
+    fn replace_many(&mut self, count: usize, value: Variant) -> ExecResult<()> {
+        let replace_range = self.index_from_top(count)?.. ;
         self.stack.splice(replace_range, core::iter::once(value));
+        Ok(())
     }
     
     #[inline(always)]
@@ -340,36 +1044,71 @@ impl ValueStack {
     }
     
     #[inline(always)]
-    fn peek(&self) -> &Variant {
-        self.stack.last().expect("empty stack")
+    fn peek(&self) -> ExecResult<&Variant> {
+        self.stack.last().ok_or_else(stack_underflow)
+    }
+    
+    #[inline(always)]
+    fn peek_at(&self, index: usize) -> ExecResult<&Variant> {
+        self.stack.get(index).ok_or_else(stack_underflow)
     }
     
+    /// Access a local variable using an index decoded from an instruction operand.
+    /// The verifier ensures that these are always in range, so with the `unchecked` feature they are not bounds checked.
     #[inline(always)]
-    fn peek_at(&self, index: usize) -> &Variant {
-        self.stack.get(index)
-            .expect("index out of bounds")
+    fn local(&self, index: usize) -> &Variant {
+        #[cfg(feature = "unchecked")]
+        {
+            debug_assert!(index < self.stack.len(), "local index out of bounds");
+            // SAFETY: chunks that pass the verifier never access a local above the number defined in the frame
+            unsafe { self.stack.get_unchecked(index) }
+        }
+        
+        #[cfg(not(feature = "unchecked"))]
+        self.stack.get(index).expect("local index out of bounds")
     }
     
     #[inline(always)]
-    fn peek_many(&self, count: usize) -> &[Variant] {
-        let (_, peek) = self.stack.as_slice().split_at(self.stack.len() - count);
-        peek
+    fn set_local(&mut self, index: usize, value: Variant) {
+        #[cfg(feature = "unchecked")]
+        {
+            debug_assert!(index < self.stack.len(), "local index out of bounds");
+            // SAFETY: see local()
+            unsafe { *self.stack.get_unchecked_mut(index) = value }
+        }
+        
+        #[cfg(not(feature = "unchecked"))]
+        self.replace_at(index, value)
     }
     
+    #[inline(always)]
+    fn peek_many(&self, count: usize) -> ExecResult<&[Variant]> {
+        let index = self.index_from_top(count)?;
+        Ok(&self.stack[index..])
+    }
+    
+    // an open upvalue can outlive its local if a chunk discards it without closing the upvalue first
     #[inline]
-    fn get_closure(&self, closure: &Closure) -> Variant {
+    fn get_closure(&self, closure: &Closure) -> ExecResult<Variant> {
         match closure {
-            Closure::Open(index) => *self.peek_at(*index),
-            Closure::Closed(cell) => cell.get(),
+            Closure::Open(index) => self.peek_at(*index).copied(),
+            Closure::Closed(cell) => Ok(cell.get()),
         }
     }
     
     #[inline]
-    fn set_closure(&mut self, closure: &Closure, value: Variant) {
+    fn set_closure(&mut self, closure: &Closure, value: Variant) -> ExecResult<()> {
         match closure {
-            Closure::Open(index) => self.replace_at(*index, value),
-            Closure::Closed(cell) => cell.set(value),
+            Closure::Open(index) => {
+                let item = self.stack.get_mut(*index).ok_or_else(stack_underflow)?;
+                *item = value;
+            },
+            Closure::Closed(cell) => {
+                gc_barrier(&cell.get());
+                cell.set(value)
+            },
         }
+        Ok(())
     }
 }
 
@@ -387,6 +1126,10 @@ impl OpenUpvalues {
         }
     }
     
+    fn clear(&mut self) {
+        self.upvalues.clear()
+    }
+    
     fn iter_refs(&self) -> impl Iterator<Item=&UpvalueWeakRef> {
         self.upvalues.values().flat_map(|refs| refs.iter())
     }
@@ -420,6 +1163,20 @@ impl OpenUpvalues {
             .or_insert_with(|| vec![ weak_ref ]);
     }
     
+    /// Close all open upvalues that refer to locals at or above the given index
+    fn close_all_above(&mut self, locals: &ValueStack, index: usize) {
+        let open_indices = self.upvalues.keys()
+            .copied()
+            .filter(|open_index| *open_index >= index)
+            .collect::<Vec<usize>>();
+        
+        for open_index in open_indices.into_iter() {
+            // the local can only be missing if the chunk discarded it without closing its upvalues
+            let value = locals.peek_at(open_index).copied().unwrap_or(Variant::Nil);
+            self.close_upvalues(open_index, value);
+        }
+    }
+    
     fn close_upvalues(&mut self, index: usize, value: Variant) {
         if let Some(upvalues) = self.upvalues.remove(&index) {
             let gc_cell = Gc::new(Cell::new(value));
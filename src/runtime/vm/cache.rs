@@ -0,0 +1,102 @@
+use core::cell::Cell;
+use crate::runtime::Variant;
+use crate::runtime::gc::Gc;
+use crate::runtime::function::Function;
+use crate::runtime::module::Namespace;
+use crate::runtime::types::{Class, Instance, BoundMethod};
+use crate::runtime::strings::StringSymbol;
+use crate::runtime::errors::ExecResult;
+
+
+/// A monomorphic inline cache belonging to a single `LD_GLOBAL` or `GET_ATTR` instruction.
+///
+/// The cache remembers where the most recent lookup made by its instruction was found.
+/// Globals and instance fields are found again using their slot in a `SlotMap`, which is checked
+/// against the name every time it is used, so the cache does not need to be invalidated when
+/// variables or fields are added or removed. Methods are cached for a single class ID,
+/// so looking up a method on any other class replaces the cached method.
+#[derive(Debug, Default)]
+pub struct InlineCache {
+    entry: Cell<CacheEntry>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+enum CacheEntry {
+    #[default]
+    Empty,
+    Slot(usize),
+    // the method is only used while the class is alive, since class IDs are never reused
+    Method {
+        class: u64,
+        name: StringSymbol,
+        method: Gc<Function>,
+    },
+}
+
+impl InlineCache {
+    #[inline]
+    pub(crate) fn load_global(&self, globals: &Namespace, name: &StringSymbol) -> ExecResult<Variant> {
+        if let CacheEntry::Slot(slot) = self.entry.get() {
+            if let Some(value) = globals.lookup_slot(slot, name) {
+                return Ok(*value);
+            }
+        }
+        
+        let (slot, value) = globals.lookup_full(name)?;
+        self.entry.set(CacheEntry::Slot(slot));
+        Ok(*value)
+    }
+    
+    // this must produce the same result as Variant::get_attr()
+    #[inline]
+    pub(crate) fn get_attr(&self, receiver: &Variant, name: &StringSymbol) -> ExecResult<Variant> {
+        match receiver {
+            // fields shadow methods
+            Variant::Instance(instance) => {
+                if let Some(value) = self.get_field(instance, name) {
+                    return Ok(value);
+                }
+                if let Some(method) = self.get_method(&instance.class(), name) {
+                    let method = BoundMethod::new(*receiver, method.into());
+                    return Ok(Variant::BoundMethod(Gc::new(method)));
+                }
+            },
+            
+            Variant::Class(class) => {
+                if let Some(method) = self.get_method(class, name) {
+                    return Ok(Variant::Function(method));
+                }
+            },
+            
+            _ => { },
+        }
+        
+        // produces the error if the attribute was not found
+        receiver.get_attr(name)
+    }
+    
+    fn get_field(&self, instance: &Instance, name: &StringSymbol) -> Option<Variant> {
+        let fields = instance.fields();
+        if let CacheEntry::Slot(slot) = self.entry.get() {
+            if let Some(value) = fields.get_slot(slot, name) {
+                return Some(*value);
+            }
+        }
+        
+        let (slot, value) = fields.get_full(name)?;
+        self.entry.set(CacheEntry::Slot(slot));
+        Some(*value)
+    }
+    
+    fn get_method(&self, class: &Class, name: &StringSymbol) -> Option<Gc<Function>> {
+        if let CacheEntry::Method { class: class_id, name: method_name, method } = self.entry.get() {
+            if class_id == class.id() && method_name == *name {
+                return Some(method);
+            }
+        }
+        
+        let method = class.lookup_method(name)?;
+        self.entry.set(CacheEntry::Method { class: class.id(), name: *name, method });
+        Some(method)
+    }
+}
@@ -1,27 +1,61 @@
 use crate::codegen::OpCode;
 use crate::debug::snapshot::VMFrameSnapshot;
+use crate::debug::traceback::TraceSite;
 use crate::runtime::gc::{Gc, GcTrace};
-use crate::runtime::module::{Module, Chunk, FunctionID};
+use crate::runtime::module::{Module, Chunk};
+use crate::runtime::function::Function;
 
 
+// marks the start of a try-block in the current call frame
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ErrorHandler {
+    pub(super) target: usize,      // pc to jump to when an error is caught
+    pub(super) stack_len: usize,   // length of the value stack when the handler was registered
+    pub(super) locals_len: usize,  // length of the locals stack when the handler was registered
+}
+
+// where the value returned by a call frame goes
+#[derive(Debug, Clone, Copy)]
+pub(super) enum ReturnTo {
+    Stack,         // pushed onto the value stack, like the result of any other expression
+    Local(usize),  // stored in a local variable, for a register instruction whose operator was overloaded
+    
+    // for overloaded comparisons, the value is converted to a bool (and negated if needed)
+    Compare { negate: bool },               // the bool is pushed onto the value stack
+    Jump { offset: isize, jump_if: bool },  // the caller jumps by the offset if the bool matches
+}
+
 #[derive(Debug)]
 pub struct VMCallFrame<'c> {
     pub(super) module: Gc<Module>,
+    pub(super) function: Option<Gc<Function>>,  // the function being executed, if this is not the main chunk
     pub(super) chunk: &'c [u8],
     pub(super) chunk_id: Chunk,
     pub(super) stack_idx: usize,   // start index for this frame in the value stack
     pub(super) local_idx: usize,   // start index for this frame in the locals stack
     pub(super) pc: usize,
+    pub(super) handlers: Vec<ErrorHandler>,
+    pub(super) tail_site: Option<TraceSite>,  // the most recent tail call that replaced this frame
+    pub(super) finalizer: bool,  // this frame is running a __del__ method, so its result is discarded
+    pub(super) on_return: ReturnTo,
 }
 
 unsafe impl GcTrace for VMCallFrame<'_> {
     fn trace(&self) {
         self.module.mark_trace();
+        if let Some(function) = self.function {
+            function.mark_trace();
+        }
+        if let Some(site) = &self.tail_site {
+            site.trace();
+        }
     }
 }
 
 impl<'c> VMCallFrame<'c> {
-    pub fn call_frame(module: Gc<Module>, fun_id: FunctionID, stack_idx: usize, local_idx: usize) -> Self {
+    pub fn call_frame(function: Gc<Function>, stack_idx: usize, local_idx: usize) -> Self {
+        let module = function.module();
+        let fun_id = function.fun_id();
         
         // This hack allows us to get around the self-referentiality of storing both "module" and "chunk"
         // in the same struct. The alternative would be to store "chunk" as an `Option<Box<[u8]>>` and call 
@@ -33,22 +67,49 @@ impl<'c> VMCallFrame<'c> {
         
         Self {
             module,
+            function: Some(function),
             chunk,
             chunk_id: Chunk::Function(fun_id),
             stack_idx,
             local_idx,
             pc: 0,
+            handlers: Vec::new(),
+            tail_site: None,
+            finalizer: false,
+            on_return: ReturnTo::Stack,
         }
     }
     
     pub fn main_chunk(module: Gc<Module>, chunk: &'c [u8]) -> Self {
         Self {
             module,
+            function: None,
             chunk,
             chunk_id: Chunk::Main,
             stack_idx: 0,
             local_idx: 0,
             pc: 0,
+            handlers: Vec::new(),
+            tail_site: None,
+            finalizer: false,
+            on_return: ReturnTo::Stack,
+        }
+    }
+    
+    // the main chunk of an imported module, executed on top of the importer's stack
+    pub fn module_frame(module: Gc<Module>, chunk: &'c [u8], stack_idx: usize, local_idx: usize) -> Self {
+        Self {
+            module,
+            function: None,
+            chunk,
+            chunk_id: Chunk::Main,
+            stack_idx,
+            local_idx,
+            pc: 0,
+            handlers: Vec::new(),
+            tail_site: None,
+            finalizer: false,
+            on_return: ReturnTo::Stack,
         }
     }
     
@@ -1,43 +1,176 @@
+use core::ops::Range;
 use crate::language::{IntType, Access};
 use crate::codegen::{OpCode, LocalIndex, UpvalueTarget};
+use crate::codegen::opcodes::{CacheIndex, REG_CONST_SRC1, REG_CONST_SRC2};
 use crate::debug::traceback::TraceSite;
-use crate::runtime::Variant;
+use crate::runtime::{Variant, VariantKey};
 use crate::runtime::gc::Gc;
-use crate::runtime::function::{Function, Upvalue, UpvalueIndex};
-use crate::runtime::strings::StringSymbol;
+use crate::runtime::function::{Call, Function, Upvalue, UpvalueIndex};
+use crate::runtime::types::{Class, BoundMethod, List, Dict};
+use crate::runtime::strings::{StringValue, StringSymbol, static_symbol};
 use crate::runtime::module::{ConstID, FunctionID, FunctionProto};
 use crate::runtime::iter::IterState;
 use crate::runtime::errors::{ExecResult, RuntimeError};
-use crate::runtime::vm::{ValueStack, OpenUpvalues, CallInfo, Control, VMCallFrame};
+use crate::runtime::vm::{ValueStack, OpenUpvalues, CallInfo, ImportInfo, ConcatInfo, Control, VMCallFrame};
+use crate::runtime::vm::callframe::{ErrorHandler, ReturnTo};
 
 
+#[cold]
+#[inline(never)]
+fn invalid_instruction(op_byte: u8) -> ! {
+    panic!("invalid instruction: {:x}", op_byte)
+}
+
 // Operand casts
 
+// the verifier only knows the kind of an operand if it was loaded directly from a constant,
+// so these are errors rather than panics
+
+#[cold]
+#[inline(never)]
+fn invalid_operand() -> Box<RuntimeError> {
+    RuntimeError::invalid_bytecode("invalid operand")
+}
+
 #[inline]
-fn into_name(value: Variant) -> StringSymbol {
+fn into_name(value: Variant) -> ExecResult<StringSymbol> {
     value.as_strval()
-        .expect("invalid operand")
-        .as_intern()
+        .map(|name| name.as_intern())
+        .ok_or_else(invalid_operand)
 }
 
 #[inline]
-fn into_usize(value: Variant) -> usize {
+fn into_usize(value: Variant) -> ExecResult<usize> {
     if let Variant::Integer(value) = value {
         if let Ok(value) = usize::try_from(value) {
-            return value;
+            return Ok(value);
         }
     }
-    panic!("invalid operand")
+    Err(invalid_operand())
+}
+
+#[inline]
+fn into_class(value: Variant) -> ExecResult<Gc<Class>> {
+    match value {
+        Variant::Class(class) => Ok(class),
+        _ => Err(invalid_operand()),
+    }
 }
 
 #[inline]
-fn into_function(value: Variant) -> Gc<Function> {
+fn into_function(value: Variant) -> ExecResult<Gc<Function>> {
     match value {
-        Variant::Function(fun) => fun,
-        _ => panic!("invalid operand")
+        Variant::Function(fun) => Ok(fun),
+        _ => Err(invalid_operand()),
     }
 }
 
+// build a dict from a sequence of alternating keys and values
+fn build_dict(entries: &[Variant]) -> ExecResult<Dict> {
+    let dict = Dict::with_capacity(entries.len() / 2);
+    for entry in entries.chunks_exact(2) {
+        let key = VariantKey::try_from(entry[0])?;
+        dict.insert(key, entry[1]);
+    }
+    Ok(dict)
+}
+
+
+// Operator overloading
+
+// User-defined classes overload operators with methods such as __neg__ and __add__. These are called instead of 
+// raising an error when the builtin operator doesn't support the operands. If the left operand doesn't have a method, 
+// the reflected method (e.g. __radd__) of the right operand is called, unless both are instances of the same class.
+// Comparisons are the exception, they are reflected even for the same class (see below).
+
+#[cold]
+#[inline(never)]
+fn find_unary_method(operand: &Variant, name: StringSymbol) -> Option<Gc<Function>> {
+    match operand {
+        Variant::Instance(instance) => instance.class().lookup_method(&name),
+        _ => None,
+    }
+}
+
+struct OperatorMethod {
+    receiver: Variant,
+    method: Gc<Function>,
+    arg: Variant,
+    reflected: bool,
+}
+
+#[cold]
+#[inline(never)]
+fn find_binary_method(lhs: &Variant, rhs: &Variant, (name, reflected): (StringSymbol, StringSymbol), reflect_same_class: bool) -> Option<OperatorMethod> {
+    if let Variant::Instance(instance) = lhs {
+        if let Some(method) = instance.class().lookup_method(&name) {
+            return Some(OperatorMethod { receiver: *lhs, method, arg: *rhs, reflected: false });
+        }
+    }
+    
+    if let Variant::Instance(instance) = rhs {
+        let same_class = matches!(lhs, Variant::Instance(lhs) if Gc::ptr_eq(&lhs.class(), &instance.class()));
+        if reflect_same_class || !same_class {
+            if let Some(method) = instance.class().lookup_method(&reflected) {
+                return Some(OperatorMethod { receiver: *rhs, method, arg: *lhs, reflected: true });
+            }
+        }
+    }
+    
+    None
+}
+
+// Comparisons are overloaded with __eq__, __lt__ and __le__, the rest are derived from these the same way
+// as for the builtin types. The reflected method of an inequality is called with the operands swapped and its
+// result negated, so `a < b` becomes `not (b <= a)`. This is done for operands of the same class too, so that
+// defining only one of __lt__ or __le__ is enough for all four inequalities.
+#[derive(Clone, Copy)]
+enum Comparison {
+    EQ, NE, LT, LE, GT, GE,
+}
+
+impl Comparison {
+    fn method_names(self) -> (StringSymbol, StringSymbol) {
+        match self {
+            Self::EQ | Self::NE => (static_symbol!("__eq__"), static_symbol!("__eq__")),
+            Self::LT | Self::GE => (static_symbol!("__lt__"), static_symbol!("__le__")),
+            Self::LE | Self::GT => (static_symbol!("__le__"), static_symbol!("__lt__")),
+        }
+    }
+    
+    fn negate(self, reflected: bool) -> bool {
+        let negated = matches!(self, Self::NE | Self::GT | Self::GE);
+        let inequality = !matches!(self, Self::EQ | Self::NE);
+        negated != (reflected && inequality)
+    }
+    
+    // equality falls back to comparing references instead of failing, so instances are always checked for a method
+    #[inline(always)]
+    fn may_overload(self, lhs: &Variant, rhs: &Variant, result: &ExecResult<bool>) -> bool {
+        result.is_err() || matches!(self, Self::EQ | Self::NE)
+            && (matches!(lhs, Variant::Instance(..)) || matches!(rhs, Variant::Instance(..)))
+    }
+}
+
+macro_rules! operator_method_names {
+    ( apply_neg ) => { "__neg__" };
+    ( apply_pos ) => { "__pos__" };
+    ( apply_inv ) => { "__inv__" };
+    
+    ( apply_add ) => { (static_symbol!("__add__"), static_symbol!("__radd__")) };
+    ( apply_sub ) => { (static_symbol!("__sub__"), static_symbol!("__rsub__")) };
+    ( apply_mul ) => { (static_symbol!("__mul__"), static_symbol!("__rmul__")) };
+    ( apply_div ) => { (static_symbol!("__div__"), static_symbol!("__rdiv__")) };
+    ( apply_mod ) => { (static_symbol!("__mod__"), static_symbol!("__rmod__")) };
+    ( apply_floordiv ) => { (static_symbol!("__floordiv__"), static_symbol!("__rfloordiv__")) };
+    ( apply_exp ) => { (static_symbol!("__exp__"), static_symbol!("__rexp__")) };
+    ( apply_and ) => { (static_symbol!("__and__"), static_symbol!("__rand__")) };
+    ( apply_xor ) => { (static_symbol!("__xor__"), static_symbol!("__rxor__")) };
+    ( apply_or  ) => { (static_symbol!("__or__"), static_symbol!("__ror__")) };
+    ( apply_shl ) => { (static_symbol!("__shl__"), static_symbol!("__rshl__")) };
+    ( apply_shr ) => { (static_symbol!("__shr__"), static_symbol!("__rshr__")) };
+}
+
 
 // Helper macros
 macro_rules! read_le_bytes {
@@ -49,19 +182,40 @@ macro_rules! read_le_bytes {
 macro_rules! eval_unary_op {
     ( $stack:expr, $apply_method:tt ) => {
         {
-            let result = $stack.peek().$apply_method()?;
-            $stack.replace(result);
+            let result = $stack.peek()?.$apply_method()?;
+            $stack.replace(result)?;
+        }
+    };
+    
+    ( $self:expr, $offset:expr, $stack:expr, $locals:expr, $apply_method:tt ) => {
+        {
+            let operand = *$stack.peek()?;
+            match operand.$apply_method() {
+                Ok(result) => $stack.replace(result)?,
+                Err(error) => {
+                    let name = static_symbol!(operator_method_names!($apply_method));
+                    let method = find_unary_method(&operand, name).ok_or(error)?;
+                    $stack.pop()?;
+                    let call = $self.call_operator_method($offset, $stack, $locals, operand, method, None, ReturnTo::Stack)?;
+                    return Ok(Control::Call(call));
+                },
+            }
         }
     }
 }
 
 macro_rules! eval_binary_op {
-    ( $stack:expr, $apply_method:tt ) => {
+    ( $self:expr, $offset:expr, $stack:expr, $locals:expr, $apply_method:tt ) => {
         {
-            let rhs = $stack.pop();
-            let lhs = $stack.peek();
-            let result = lhs.$apply_method(&rhs)?;
-            $stack.replace(result);
+            let rhs = $stack.pop()?;
+            let lhs = *$stack.peek()?;
+            match lhs.$apply_method(&rhs) {
+                Ok(result) => $stack.replace(result)?,
+                Err(error) => {
+                    $stack.pop()?;
+                    return $self.overloaded_binary_op($offset, $stack, $locals, (lhs, rhs), operator_method_names!($apply_method), ReturnTo::Stack, error);
+                },
+            }
         }
     };
 }
@@ -69,10 +223,84 @@ macro_rules! eval_binary_op {
 macro_rules! eval_cmp {
     ( $stack:expr, $cmp_method:tt ) => {
         {
-            let rhs = $stack.pop();
-            let lhs = $stack.peek();
+            let rhs = $stack.pop()?;
+            let lhs = $stack.peek()?;
             let result = lhs.$cmp_method(&rhs)?;
-            $stack.replace(Variant::from(result));
+            $stack.replace(Variant::from(result))?;
+        }
+    };
+    
+    ( $self:expr, $offset:expr, $stack:expr, $locals:expr, $cmp_method:tt, $comparison:expr ) => {
+        {
+            let rhs = $stack.pop()?;
+            let lhs = *$stack.peek()?;
+            let result = lhs.$cmp_method(&rhs);
+            if $comparison.may_overload(&lhs, &rhs, &result) {
+                if let Some(method) = find_binary_method(&lhs, &rhs, $comparison.method_names(), true) {
+                    $stack.pop()?;
+                    let on_return = ReturnTo::Compare { negate: $comparison.negate(method.reflected) };
+                    let call = $self.call_operator_method($offset, $stack, $locals, method.receiver, method.method, Some(method.arg), on_return)?;
+                    return Ok(Control::Call(call));
+                }
+            }
+            $stack.replace(Variant::from(result?))?;
+        }
+    };
+}
+
+macro_rules! eval_local_const_op {
+    ( $self:expr, $offset:expr, $stack:expr, $locals:expr, $data:expr, $apply_method:tt ) => {
+        {
+            let lhs = *$locals.local($self.frame_offset(LocalIndex::from($data[0])));
+            let rhs = $self.module.get_const(ConstID::from($data[1]));
+            match lhs.$apply_method(&rhs) {
+                Ok(result) => $stack.push(result),
+                Err(error) => return $self.overloaded_binary_op($offset, $stack, $locals, (lhs, rhs), operator_method_names!($apply_method), ReturnTo::Stack, error),
+            }
+        }
+    };
+}
+
+// reads a source operand of a register instruction
+macro_rules! read_register {
+    ( $self:expr, $locals:expr, $mode:expr, $const_bit:expr, $operand:expr ) => {
+        if $mode & $const_bit != 0 {
+            $self.module.get_const(ConstID::from($operand))
+        } else {
+            *$locals.local($self.frame_offset(LocalIndex::from($operand)))
+        }
+    };
+}
+
+macro_rules! eval_register_op {
+    ( $self:expr, $offset:expr, $stack:expr, $locals:expr, $data:expr, $apply_method:tt ) => {
+        {
+            let lhs = read_register!($self, $locals, $data[0], REG_CONST_SRC1, $data[2]);
+            let rhs = read_register!($self, $locals, $data[0], REG_CONST_SRC2, $data[3]);
+            let dst = $self.frame_offset(LocalIndex::from($data[1]));
+            match lhs.$apply_method(&rhs) {
+                Ok(result) => $locals.set_local(dst, result),
+                Err(error) => return $self.overloaded_binary_op($offset, $stack, $locals, (lhs, rhs), operator_method_names!($apply_method), ReturnTo::Local(dst), error),
+            }
+        }
+    };
+}
+
+macro_rules! cmp_jump {
+    ( $self:expr, $offset:expr, $stack:expr, $locals:expr, $data:expr, $cmp_method:tt, $comparison:expr, $jump_if:expr ) => {
+        {
+            let rhs = $stack.pop()?;
+            let lhs = $stack.pop()?;
+            let offset = isize::from(read_le_bytes!(i16, $data));
+            let result = lhs.$cmp_method(&rhs);
+            if $comparison.may_overload(&lhs, &rhs, &result) {
+                if let Some(method) = find_binary_method(&lhs, &rhs, $comparison.method_names(), true) {
+                    let on_return = ReturnTo::Jump { offset, jump_if: $jump_if != $comparison.negate(method.reflected) };
+                    let call = $self.call_operator_method($offset, $stack, $locals, method.receiver, method.method, Some(method.arg), on_return)?;
+                    return Ok(Control::Call(call));
+                }
+            }
+            cond_jump!($self, result? == $jump_if, offset)
         }
     };
 }
@@ -90,7 +318,7 @@ macro_rules! cond_jump {
 
 impl<'c> VMCallFrame<'c> {
     #[inline(always)]
-    fn offset_pc(&self, offset: isize) -> Option<usize> {
+    pub(super) fn offset_pc(&self, offset: isize) -> Option<usize> {
         if offset >= 0 {
             usize::checked_add(self.pc, offset as usize)
         } else {
@@ -98,6 +326,16 @@ impl<'c> VMCallFrame<'c> {
         }
     }
 
+    #[inline]
+    fn push_handler(&mut self, offset: isize, stack: &ValueStack, locals: &ValueStack) {
+        let handler = ErrorHandler {
+            target: self.offset_pc(offset).expect("pc overflow/underflow"),
+            stack_len: stack.len(),
+            locals_len: locals.len(),
+        };
+        self.handlers.push(handler);
+    }
+    
     // convert a local variable index to an index into the value stack
     #[inline]
     fn frame_offset(&self, local: LocalIndex) -> usize {
@@ -105,41 +343,168 @@ impl<'c> VMCallFrame<'c> {
     }
     
     #[inline]
-    fn get_trace(&self, offset: usize) -> TraceSite {
+    pub(super) fn get_trace(&self, offset: usize) -> TraceSite {
         TraceSite::Chunk {
             offset,
             module: self.module,
             chunk_id: self.chunk_id,
         }
     }
+    
+    // the compiler records local variables with names similar to a global that is loaded, in case it was misspelled
+    #[cold]
+    fn suggest_local_name(&self, offset: usize, name: StringSymbol, error: Box<RuntimeError>) -> Box<RuntimeError> {
+        let hint = self.module.data().debug_symbols(&self.chunk_id)
+            .and_then(|symbols| symbols.lookup_name_hint(offset));
+        
+        match hint {
+            Some(hint) => RuntimeError::name_not_defined(name, Some(hint)),
+            None => error,
+        }
+    }
 
+    // the verifier ensures that execution never leaves the chunk and that every instruction is complete,
+    // so with the `unchecked` feature instructions are decoded without bounds checks
+    #[inline(always)]
+    fn read_byte(&self, offset: usize) -> u8 {
+        #[cfg(feature = "unchecked")]
+        {
+            debug_assert!(offset < self.chunk.len(), "pc out of bounds");
+            // SAFETY: chunks that pass the verifier only jump to the start of an instruction inside the chunk,
+            // and can't run past the end of the chunk
+            unsafe { *self.chunk.get_unchecked(offset) }
+        }
+        
+        #[cfg(not(feature = "unchecked"))]
+        *self.chunk.get(offset).expect("pc out of bounds")
+    }
+    
+    #[inline(always)]
+    fn read_operand(&self, range: Range<usize>) -> &'c [u8] {
+        #[cfg(feature = "unchecked")]
+        {
+            debug_assert!(range.end <= self.chunk.len(), "truncated instruction");
+            // SAFETY: chunks that pass the verifier only contain complete instructions
+            unsafe { self.chunk.get_unchecked(range) }
+        }
+        
+        #[cfg(not(feature = "unchecked"))]
+        self.chunk.get(range).expect("truncated instruction")
+    }
+    
     #[inline]
     pub(super) fn exec_next(&mut self, stack: &mut ValueStack, locals: &mut ValueStack, upvalues: &mut OpenUpvalues) -> ExecResult<Control> {
-        let op_byte = self.chunk.get(self.pc).expect("pc out of bounds");
-        let opcode = OpCode::from_byte(*op_byte)
-            .unwrap_or_else(|| panic!("invalid instruction: {:x}", op_byte));
+        let op_byte = self.read_byte(self.pc);
+        let opcode = match OpCode::from_byte(op_byte) {
+            Some(opcode) => opcode,
+            None => invalid_instruction(op_byte),
+        };
         
         let data_slice = (self.pc + 1) .. (self.pc + opcode.instr_len());
         let current_offset = self.pc;
         self.pc += opcode.instr_len(); // pc points to next instruction
         
-        let data = self.chunk.get(data_slice).expect("truncated instruction");
+        let data = self.read_operand(data_slice);
         
         self.exec_instruction(current_offset, opcode, data, stack, locals, upvalues)
             .map_err(|error| error.push_trace(self.get_trace(current_offset)))
     }
     
+    /// Executes instructions until reaching a safepoint, where the VM needs to do its bookkeeping.
+    /// Safepoints are instructions that leave the current call frame and jumps that go backwards, so the number 
+    /// of instructions between safepoints is limited by the length of the chunk. Also stops when the budget runs out.
     #[inline]
-    fn get_callee(&self, locals: &ValueStack) -> Gc<Function> {
-        into_function(*locals.peek_at(self.frame_offset(0)))
+    pub(super) fn exec_until_safepoint(&mut self, stack: &mut ValueStack, locals: &mut ValueStack, upvalues: &mut OpenUpvalues, budget: &mut Option<u64>) -> ExecResult<Control> {
+        loop {
+            let offset = self.pc;
+            let control = self.exec_next(stack, locals, upvalues)?;
+            if !matches!(control, Control::Next) || self.pc <= offset {
+                return Ok(control);
+            }
+            
+            if let Some(budget) = budget.as_mut() {
+                if *budget == 0 {
+                    return Ok(control);
+                }
+                *budget -= 1;
+            }
+        }
+    }
+    
+    #[inline]
+    fn get_callee(&self) -> Gc<Function> {
+        self.function.expect("no function for call frame")
+    }
+    
+    // read nargs and identify the start of the call frame
+    fn prepare_call(&self, current_offset: usize, stack: &mut ValueStack, locals: &mut ValueStack) -> ExecResult<CallInfo> {
+        let nargs_value = stack.pop()?;
+        let nargs = into_usize(nargs_value)?;
+        
+        let stack_frame = stack.index_from_top(1 + nargs)?;
+        let local_frame = locals.len();
+        
+        let callee = *stack.peek_at(stack_frame)?;
+        let args = stack.peek_many(nargs)?;
+        let call = callee.invoke(args)?;
+        
+        // the receiver slot usually holds the callee, unless it is a method call
+        let receiver = match &call {
+            Call::Chunk { receiver, .. } => *receiver,
+            Call::Native { .. } | Call::NativeMethod { .. } | Call::NativeCtor { .. } => callee,
+        };
+        locals.push(receiver);
+        locals.push(nargs_value);
+        
+        let call = CallInfo {
+            stack_frame,
+            local_frame,
+            call,
+            site: self.get_trace(current_offset),
+            on_return: ReturnTo::Stack,
+        };
+        Ok(call)
+    }
+    
+    // the operands are placed on the stack like the receiver and argument of any other method call
+    #[allow(clippy::too_many_arguments)]
+    fn call_operator_method(&self, current_offset: usize, stack: &mut ValueStack, locals: &mut ValueStack, receiver: Variant, method: Gc<Function>, arg: Option<Variant>, on_return: ReturnTo) -> ExecResult<CallInfo> {
+        let args = arg.as_slice();
+        let call = method.method_call(receiver, args)?;
+        
+        let stack_frame = stack.len();
+        let local_frame = locals.len();
+        stack.push(receiver);
+        stack.extend(args);
+        locals.push(receiver);
+        locals.push(Variant::from(IntType::from(arg.is_some())));
+        
+        let call = CallInfo {
+            stack_frame,
+            local_frame,
+            call,
+            site: self.get_trace(current_offset),
+            on_return,
+        };
+        Ok(call)
+    }
+    
+    // called when a binary operator doesn't support its operands, which have already been removed from the stack
+    #[cold]
+    #[inline(never)]
+    #[allow(clippy::too_many_arguments)]
+    fn overloaded_binary_op(&self, current_offset: usize, stack: &mut ValueStack, locals: &mut ValueStack, (lhs, rhs): (Variant, Variant), names: (StringSymbol, StringSymbol), on_return: ReturnTo, error: Box<RuntimeError>) -> ExecResult<Control> {
+        let method = find_binary_method(&lhs, &rhs, names, false).ok_or(error)?;
+        let call = self.call_operator_method(current_offset, stack, locals, method.receiver, method.method, Some(method.arg), on_return)?;
+        Ok(Control::Call(call))
     }
     
     // setup a new function, potentially capturing local variables
-    fn make_function(&self, locals: &ValueStack, proto: &FunctionProto) -> Function {
+    fn make_function(&self, proto: &FunctionProto) -> Function {
         let upvalues = proto.upvalues().iter().map(|upval| match upval {
                 UpvalueTarget::Local(index) => Upvalue::new(self.frame_offset(*index)),
                 UpvalueTarget::Upvalue(index) => {
-                    let upval = &*self.get_callee(locals).upvalue(*index);
+                    let upval = &*self.get_callee().upvalue(*index);
                     upval.clone()
                 },
             })
@@ -159,86 +524,95 @@ impl<'c> VMCallFrame<'c> {
                 if stack.is_empty() {
                     return Ok(Control::Exit(Variant::Nil))
                 }
-                let value = stack.pop();
+                let value = stack.pop()?;
                 return Ok(Control::Exit(value))
             }
             
             OpCode::Return => {
-                let value = stack.pop();
+                let value = stack.pop()?;
                 return Ok(Control::Return(value))
             },
             
             OpCode::Error => {
-                let value = stack.pop();
+                let value = stack.pop()?;
                 if let Variant::Error(error) = value {
                     return Err(Box::new((*error).clone()));
                 }
-                panic!("invalid operand")
+                return Err(RuntimeError::user_error(value.fmt_str()?));
             },
             
-            OpCode::Call => {
-                // read nargs and identify the start of the call frame
-                let nargs_value = stack.pop();
-                let nargs = into_usize(nargs_value);
-
-                let call_len = 1 + nargs;
-                let stack_frame = stack.len() - call_len;
-                let local_frame = locals.len();
-                
-                let callee = stack.peek_at(stack_frame);
-                locals.push(*callee);
-                locals.push(nargs_value);
-                
-                let args = stack.peek_many(nargs);
-                
-                let call = CallInfo {
-                    stack_frame,
-                    local_frame,
-                    call: callee.invoke(args)?,
+            OpCode::PushHandler => {
+                let offset = isize::from(read_le_bytes!(i16, data));
+                self.push_handler(offset, stack, locals);
+            },
+            OpCode::LongPushHandler => {
+                let offset = isize::try_from(read_le_bytes!(i32, data)).unwrap();
+                self.push_handler(offset, stack, locals);
+            },
+            OpCode::PopHandler => {
+                self.handlers.pop().expect("empty handler stack");
+            },
+            
+            OpCode::Import => {
+                let import = ImportInfo {
+                    name: into_name(stack.pop()?)?,
                     site: self.get_trace(current_offset),
                 };
+                return Ok(Control::Import(import))
+            },
+            OpCode::Export => {
+                let name = into_name(stack.pop()?)?;
+                self.module.globals().borrow_mut().export(&name)?;
+            },
+            
+            OpCode::Call => {
+                let call = self.prepare_call(current_offset, stack, locals)?;
                 return Ok(Control::Call(call))
             },
+            OpCode::TailCall => {
+                let call = self.prepare_call(current_offset, stack, locals)?;
+                return Ok(Control::TailCall(call))
+            },
             
             OpCode::InsertArgs => {
-                let callee = self.get_callee(locals);
+                let callee = self.get_callee();
                 let nargs = callee.signature().param_count();
-                locals.extend(stack.peek_many(nargs));
-                stack.discard(nargs);
+                locals.extend(stack.peek_many(nargs)?);
+                stack.discard(nargs)?;
             }
             
             OpCode::Pop => { 
-                stack.pop(); 
+                stack.pop()?; 
             },
             OpCode::Drop => { 
                 let count = usize::from(data[0]);
-                stack.discard(count);
+                stack.discard(count)?;
             }
             OpCode::DropN => {
-                let count = into_usize(stack.pop());
-                stack.discard(count);
+                let count = into_usize(stack.pop()?)?;
+                stack.discard(count)?;
             }
             OpCode::Clone => {
-                stack.push(*stack.peek());
+                stack.push(*stack.peek()?);
             }
             
             OpCode::IterInit => {
-                let iter = stack.peek().iter_init()?;
-                stack.replace(*iter.get_iter());
+                let iter = stack.peek()?.iter_init()?;
+                stack.replace(*iter.get_iter())?;
                 stack.push(*iter.get_state());
             }
             
             OpCode::IterNext => {
-                let state = stack.pop();
-                let value = stack.peek().iter_get(&state)?;
-                let next_state = stack.peek().iter_next(&state)?;
+                let state = stack.pop()?;
+                let value = stack.peek()?.iter_get(&state)?;
+                let next_state = stack.peek()?.iter_next(&state)?;
                 stack.push(next_state);
                 stack.push(value);
             }
             
             OpCode::IterUnpack => {
-                let state = stack.pop();
-                let iter = stack.pop();
+                let state = stack.pop()?;
+                let iter = stack.pop()?;
                 
                 let mut count = IntType::from(0);
                 for value in IterState::new(iter, state) {
@@ -249,21 +623,85 @@ impl<'c> VMCallFrame<'c> {
                 stack.push(count.into());
             }
             
+            OpCode::TupleLen => {
+                let len = match stack.peek()? {
+                    Variant::Tuple(tuple) => IntType::try_from(tuple.len())
+                        .map_err(|_| RuntimeError::overflow_error())?,
+                    _ => -1,
+                };
+                stack.replace(Variant::Integer(len))?;
+            }
+            
+            OpCode::GetAttr => {
+                let cache = self.module.data().get_cache(read_le_bytes!(CacheIndex, data));
+                let name = into_name(stack.pop()?)?;
+                let value = cache.get_attr(stack.peek()?, &name)?;
+                stack.replace(value)?;
+            }
+            OpCode::SetAttr => {
+                let name = into_name(stack.pop()?)?;
+                let receiver = stack.pop()?;
+                receiver.set_attr(&name, *stack.peek()?)?;
+            }
+            OpCode::GetSuper => {
+                let name = into_name(stack.pop()?)?;
+                let class = into_class(stack.pop()?)?;
+                let receiver = stack.pop()?;
+                
+                let method = class.lookup_method(&name)
+                    .ok_or_else(|| RuntimeError::attribute_not_found(&Variant::Class(class), name))?;
+                stack.push(Variant::BoundMethod(Gc::new(BoundMethod::new(receiver, method.into()))));
+            }
+            
+            OpCode::GetIndex => {
+                let index = stack.pop()?;
+                let value = stack.peek()?.get_index(&index)?;
+                stack.replace(value)?;
+            }
+            OpCode::SetIndex => {
+                let index = stack.pop()?;
+                let receiver = stack.pop()?;
+                receiver.set_index(&index, *stack.peek()?)?;
+            }
+            
             OpCode::LoadFunction => {
                 let fun_id = FunctionID::from(data[0]);
                 let proto = self.module.get_function(fun_id);
-                let function = Gc::new(self.make_function(locals, proto));
+                let function = Gc::new(self.make_function(proto));
                 upvalues.register(function);
                 stack.push(Variant::Function(function));
             }
             OpCode::LoadFunction16 => {
                 let fun_id = FunctionID::from(read_le_bytes!(u16, data));
                 let proto = self.module.get_function(fun_id);
-                let function = Gc::new(self.make_function(locals, proto));
+                let function = Gc::new(self.make_function(proto));
                 upvalues.register(function);
                 stack.push(Variant::Function(function));
             }
             
+            OpCode::Class => {
+                let method_count = usize::from(data[0]);
+                
+                let mut methods = stack.pop_many(2 * method_count)?.into_iter();
+                let parent = match stack.pop()? {
+                    Variant::Nil => None,
+                    Variant::Class(parent) => Some(parent),
+                    parent => return Err(RuntimeError::invalid_parent_class(&parent)),
+                };
+                let name = match stack.pop()? {
+                    Variant::Nil => None,
+                    name => Some(into_name(name)?),
+                };
+                
+                let mut method_table = Vec::with_capacity(method_count);
+                while let (Some(method_name), Some(method)) = (methods.next(), methods.next()) {
+                    method_table.push((into_name(method_name)?, into_function(method)?));
+                }
+                
+                let class = Class::new(name, parent, method_table.into_iter());
+                stack.push(Variant::Class(Gc::new(class)));
+            }
+            
             OpCode::LoadConst => {
                 let cid = ConstID::from(data[0]);
                 let value = self.module.get_const(cid);
@@ -276,18 +714,18 @@ impl<'c> VMCallFrame<'c> {
             },
             
             OpCode::InsertGlobal => {
-                let name = into_name(stack.pop());
-                let value = *stack.peek();
+                let name = into_name(stack.pop()?)?;
+                let value = *stack.peek()?;
                 self.module.globals().borrow_mut().create(name, Access::ReadOnly, value);
             },
             OpCode::InsertGlobalMut => {
-                let name = into_name(stack.pop());
-                let value = *stack.peek();
+                let name = into_name(stack.pop()?)?;
+                let value = *stack.peek()?;
                 self.module.globals().borrow_mut().create(name, Access::ReadWrite, value);
             },
             OpCode::StoreGlobal => {
-                let name = into_name(stack.pop());
-                let value = *stack.peek();
+                let name = into_name(stack.pop()?)?;
+                let value = *stack.peek()?;
                 
                 let globals = self.module.globals();
                 let mut namespace = globals.borrow_mut();
@@ -295,67 +733,69 @@ impl<'c> VMCallFrame<'c> {
                 *store = value;
             },
             OpCode::LoadGlobal => {
+                let cache = self.module.data().get_cache(read_le_bytes!(CacheIndex, data));
                 let value = {
-                    let name = into_name(*stack.peek());
-                    *self.module.globals().borrow().lookup(&name)?
+                    let name = into_name(*stack.peek()?)?;
+                    cache.load_global(&self.module.globals().borrow(), &name)
+                        .map_err(|error| self.suggest_local_name(current_offset, name, error))?
                 };
-                stack.replace(value);
+                stack.replace(value)?;
             },
             
             OpCode::InsertLocal => {
-                locals.push(*stack.peek());
+                locals.push(*stack.peek()?);
             },
             OpCode::StoreLocal => {
                 let index = LocalIndex::from(data[0]);
-                locals.replace_at(self.frame_offset(index), *stack.peek());
+                locals.set_local(self.frame_offset(index), *stack.peek()?);
             },
             OpCode::StoreLocal16 => {
                 let index = LocalIndex::from(read_le_bytes!(u16, data));
-                locals.replace_at(self.frame_offset(index), *stack.peek());
+                locals.set_local(self.frame_offset(index), *stack.peek()?);
             },
             OpCode::LoadLocal => {
                 let index = LocalIndex::from(data[0]);
-                stack.push(*locals.peek_at(self.frame_offset(index)));
+                stack.push(*locals.local(self.frame_offset(index)));
             },
             OpCode::LoadLocal16 => {
                 let index = LocalIndex::from(read_le_bytes!(u16, data));
-                stack.push(*locals.peek_at(self.frame_offset(index)));
+                stack.push(*locals.local(self.frame_offset(index)));
             },
             OpCode::DropLocals => {
                 let count = LocalIndex::from(data[0]);
-                locals.discard(usize::from(count));
+                locals.discard(usize::from(count))?;
             },
             
             OpCode::StoreUpvalue => {
                 let index = UpvalueIndex::from(data[0]);
-                let closure = self.get_callee(locals).upvalue(index).closure();
-                locals.set_closure(&closure, *stack.peek());
+                let closure = self.get_callee().upvalue(index).closure();
+                locals.set_closure(&closure, *stack.peek()?)?;
             }
             OpCode::StoreUpvalue16 => {
                 let index = UpvalueIndex::from(read_le_bytes!(u16, data));
-                let closure = self.get_callee(locals).upvalue(index).closure();
-                locals.set_closure(&closure, *stack.peek());
+                let closure = self.get_callee().upvalue(index).closure();
+                locals.set_closure(&closure, *stack.peek()?)?;
             }
             OpCode::LoadUpvalue => {
                 let index = UpvalueIndex::from(data[0]);
-                let closure = self.get_callee(locals).upvalue(index).closure();
-                stack.push(locals.get_closure(&closure));
+                let closure = self.get_callee().upvalue(index).closure();
+                stack.push(locals.get_closure(&closure)?);
             }
             OpCode::LoadUpvalue16 => {
                 let index = UpvalueIndex::from(read_le_bytes!(u16, data));
-                let closure = self.get_callee(locals).upvalue(index).closure();
-                stack.push(locals.get_closure(&closure));
+                let closure = self.get_callee().upvalue(index).closure();
+                stack.push(locals.get_closure(&closure)?);
             }
             
             OpCode::CloseUpvalue => {
                 let local_index = LocalIndex::from(data[0]);
                 let local_index = self.frame_offset(local_index);
-                upvalues.close_upvalues(local_index, *locals.peek_at(local_index));
+                upvalues.close_upvalues(local_index, *locals.local(local_index));
             }
             OpCode::CloseUpvalue16 => {
                 let local_index = LocalIndex::from(read_le_bytes!(u16, data));
                 let local_index = self.frame_offset(local_index);
-                upvalues.close_upvalues(local_index, *locals.peek_at(local_index));
+                upvalues.close_upvalues(local_index, *locals.local(local_index));
             }
             
             OpCode::Nil => stack.push(Variant::Nil),
@@ -366,20 +806,62 @@ impl<'c> VMCallFrame<'c> {
             OpCode::Tuple => {
                 let tuple_len = usize::from(data[0]);
                 
-                let items = stack.pop_many(tuple_len).into_boxed_slice();
+                let items = stack.pop_many(tuple_len)?.into_boxed_slice();
                 stack.push(Variant::from(items));
             },
             OpCode::TupleN => {
-                let tuple_len = into_usize(stack.pop());
+                let tuple_len = into_usize(stack.pop()?)?;
                 
                 if tuple_len > 0 {
-                    let items = stack.pop_many(tuple_len).into_boxed_slice();
+                    let items = stack.pop_many(tuple_len)?.into_boxed_slice();
                     stack.push(Variant::from(items));
                 } else {
                     stack.push(Variant::Tuple(Default::default()));
                 }
             },
             
+            OpCode::BuildList => {
+                let list_len = usize::from(data[0]);
+                
+                let items = stack.pop_many(list_len)?;
+                stack.push(Variant::List(Gc::new(List::from(items))));
+            },
+            OpCode::BuildListN => {
+                let list_len = into_usize(stack.pop()?)?;
+                
+                let items = stack.pop_many(list_len)?;
+                stack.push(Variant::List(Gc::new(List::from(items))));
+            },
+            
+            OpCode::Concat => {
+                let count = usize::from(data[0]);
+                
+                // instances may have a __str__ method, which only the VM can call
+                if stack.peek_many(count)?.iter().any(|value| matches!(value, Variant::Instance(..))) {
+                    let site = self.get_trace(current_offset);
+                    return Ok(Control::Concat(ConcatInfo { count, site }));
+                }
+                
+                let mut buf = String::new();
+                for value in stack.pop_many(count)?.iter() {
+                    value.fmt_str()?.with_str(|s| buf.push_str(s));
+                }
+                stack.push(Variant::from(StringValue::new_uninterned(buf)));
+            },
+            
+            OpCode::BuildDict => {
+                let dict_len = usize::from(data[0]);
+                
+                let entries = stack.pop_many(2 * dict_len)?;
+                stack.push(Variant::Dict(Gc::new(build_dict(&entries)?)));
+            },
+            OpCode::BuildDictN => {
+                let dict_len = into_usize(stack.pop()?)?;
+                
+                let entries = stack.pop_many(2 * dict_len)?;
+                stack.push(Variant::Dict(Gc::new(build_dict(&entries)?)));
+            },
+            
             OpCode::UInt8 => {
                 let value = IntType::from(data[0]);
                 stack.push(Variant::Integer(value))
@@ -393,28 +875,38 @@ impl<'c> VMCallFrame<'c> {
                 stack.push(Variant::Integer(IntType::from(value)))
             }
             
-            OpCode::Neg => eval_unary_op!(stack, apply_neg),
-            OpCode::Pos => eval_unary_op!(stack, apply_pos),
-            OpCode::Inv => eval_unary_op!(stack, apply_inv),
+            OpCode::Neg => eval_unary_op!(self, current_offset, stack, locals, apply_neg),
+            OpCode::Pos => eval_unary_op!(self, current_offset, stack, locals, apply_pos),
+            OpCode::Inv => eval_unary_op!(self, current_offset, stack, locals, apply_inv),
             OpCode::Not => eval_unary_op!(stack, apply_not),
             
-            OpCode::And => eval_binary_op!(stack, apply_and),
-            OpCode::Xor => eval_binary_op!(stack, apply_xor),
-            OpCode::Or  => eval_binary_op!(stack, apply_or),
-            OpCode::Shl => eval_binary_op!(stack, apply_shl),
-            OpCode::Shr => eval_binary_op!(stack, apply_shr),
-            OpCode::Add => eval_binary_op!(stack, apply_add),
-            OpCode::Sub => eval_binary_op!(stack, apply_sub),
-            OpCode::Mul => eval_binary_op!(stack, apply_mul),
-            OpCode::Div => eval_binary_op!(stack, apply_div),
-            OpCode::Mod => eval_binary_op!(stack, apply_mod),
-            
-            OpCode::EQ => eval_cmp!(stack, cmp_eq),
-            OpCode::NE => eval_cmp!(stack, cmp_ne),
-            OpCode::LT => eval_cmp!(stack, cmp_lt),
-            OpCode::LE => eval_cmp!(stack, cmp_le),
-            OpCode::GE => eval_cmp!(stack, cmp_ge),
-            OpCode::GT => eval_cmp!(stack, cmp_gt),
+            OpCode::And => eval_binary_op!(self, current_offset, stack, locals, apply_and),
+            OpCode::Xor => eval_binary_op!(self, current_offset, stack, locals, apply_xor),
+            OpCode::Or  => eval_binary_op!(self, current_offset, stack, locals, apply_or),
+            OpCode::Shl => eval_binary_op!(self, current_offset, stack, locals, apply_shl),
+            OpCode::Shr => eval_binary_op!(self, current_offset, stack, locals, apply_shr),
+            OpCode::Add => eval_binary_op!(self, current_offset, stack, locals, apply_add),
+            OpCode::Sub => eval_binary_op!(self, current_offset, stack, locals, apply_sub),
+            OpCode::Mul => eval_binary_op!(self, current_offset, stack, locals, apply_mul),
+            OpCode::Div => eval_binary_op!(self, current_offset, stack, locals, apply_div),
+            OpCode::Mod => eval_binary_op!(self, current_offset, stack, locals, apply_mod),
+            OpCode::FloorDiv => eval_binary_op!(self, current_offset, stack, locals, apply_floordiv),
+            OpCode::Exp => eval_binary_op!(self, current_offset, stack, locals, apply_exp),
+            
+            OpCode::EQ => eval_cmp!(self, current_offset, stack, locals, cmp_eq, Comparison::EQ),
+            OpCode::NE => eval_cmp!(self, current_offset, stack, locals, cmp_ne, Comparison::NE),
+            OpCode::LT => eval_cmp!(self, current_offset, stack, locals, cmp_lt, Comparison::LT),
+            OpCode::LE => eval_cmp!(self, current_offset, stack, locals, cmp_le, Comparison::LE),
+            OpCode::GE => eval_cmp!(self, current_offset, stack, locals, cmp_ge, Comparison::GE),
+            OpCode::GT => eval_cmp!(self, current_offset, stack, locals, cmp_gt, Comparison::GT),
+            OpCode::In => eval_cmp!(stack, cmp_in),
+            OpCode::NotIn => eval_cmp!(stack, cmp_not_in),
+            OpCode::Is => {
+                let rhs = stack.pop()?;
+                let lhs = stack.peek()?;
+                let result = lhs.cmp_is(&rhs);
+                stack.replace(Variant::from(result))?;
+            },
             
             OpCode::Jump => {
                 let offset = isize::from(read_le_bytes!(i16, data));
@@ -425,19 +917,43 @@ impl<'c> VMCallFrame<'c> {
                 self.pc = self.offset_pc(offset).expect("pc overflow/underflow");
             }
             
-            OpCode::JumpIfFalse    => cond_jump!(self, !stack.peek().as_bool()?, isize::from(read_le_bytes!(i16, data))),
-            OpCode::JumpIfTrue     => cond_jump!(self, stack.peek().as_bool()?,  isize::from(read_le_bytes!(i16, data))),
-            OpCode::PopJumpIfFalse => cond_jump!(self, !stack.pop().as_bool()?,  isize::from(read_le_bytes!(i16, data))),
-            OpCode::PopJumpIfTrue  => cond_jump!(self, stack.pop().as_bool()?,   isize::from(read_le_bytes!(i16, data))),
+            OpCode::JumpIfFalse    => cond_jump!(self, !stack.peek()?.as_bool()?, isize::from(read_le_bytes!(i16, data))),
+            OpCode::JumpIfTrue     => cond_jump!(self, stack.peek()?.as_bool()?,  isize::from(read_le_bytes!(i16, data))),
+            OpCode::PopJumpIfFalse => cond_jump!(self, !stack.pop()?.as_bool()?,  isize::from(read_le_bytes!(i16, data))),
+            OpCode::PopJumpIfTrue  => cond_jump!(self, stack.pop()?.as_bool()?,   isize::from(read_le_bytes!(i16, data))),
+            OpCode::JumpIfNil      => cond_jump!(self, stack.peek()?.is_nil(),    isize::from(read_le_bytes!(i16, data))),
+            OpCode::JumpIfNotNil   => cond_jump!(self, !stack.peek()?.is_nil(),   isize::from(read_le_bytes!(i16, data))),
+            
+            OpCode::LongJumpIfFalse    => cond_jump!(self, !stack.peek()?.as_bool()?, isize::try_from(read_le_bytes!(i32, data)).unwrap()),
+            OpCode::LongJumpIfTrue     => cond_jump!(self, stack.peek()?.as_bool()?,  isize::try_from(read_le_bytes!(i32, data)).unwrap()),
+            OpCode::PopLongJumpIfFalse => cond_jump!(self, !stack.pop()?.as_bool()?,  isize::try_from(read_le_bytes!(i32, data)).unwrap()),
+            OpCode::PopLongJumpIfTrue  => cond_jump!(self, stack.pop()?.as_bool()?,   isize::try_from(read_le_bytes!(i32, data)).unwrap()),
+            OpCode::LongJumpIfNil      => cond_jump!(self, stack.peek()?.is_nil(),    isize::try_from(read_le_bytes!(i32, data)).unwrap()),
+            OpCode::LongJumpIfNotNil   => cond_jump!(self, !stack.peek()?.is_nil(),   isize::try_from(read_le_bytes!(i32, data)).unwrap()),
+            
+            OpCode::AddLocalConst => eval_local_const_op!(self, current_offset, stack, locals, data, apply_add),
+            OpCode::SubLocalConst => eval_local_const_op!(self, current_offset, stack, locals, data, apply_sub),
             
-            OpCode::LongJumpIfFalse    => cond_jump!(self, !stack.peek().as_bool()?, isize::try_from(read_le_bytes!(i32, data)).unwrap()),
-            OpCode::LongJumpIfTrue     => cond_jump!(self, stack.peek().as_bool()?,  isize::try_from(read_le_bytes!(i32, data)).unwrap()),
-            OpCode::PopLongJumpIfFalse => cond_jump!(self, !stack.pop().as_bool()?,  isize::try_from(read_le_bytes!(i32, data)).unwrap()),
-            OpCode::PopLongJumpIfTrue  => cond_jump!(self, stack.pop().as_bool()?,   isize::try_from(read_le_bytes!(i32, data)).unwrap()),
+            OpCode::JumpIfEQ    => cmp_jump!(self, current_offset, stack, locals, data, cmp_eq, Comparison::EQ, true),
+            OpCode::JumpIfNotEQ => cmp_jump!(self, current_offset, stack, locals, data, cmp_eq, Comparison::EQ, false),
+            OpCode::JumpIfLT    => cmp_jump!(self, current_offset, stack, locals, data, cmp_lt, Comparison::LT, true),
+            OpCode::JumpIfNotLT => cmp_jump!(self, current_offset, stack, locals, data, cmp_lt, Comparison::LT, false),
+            OpCode::JumpIfLE    => cmp_jump!(self, current_offset, stack, locals, data, cmp_le, Comparison::LE, true),
+            OpCode::JumpIfNotLE => cmp_jump!(self, current_offset, stack, locals, data, cmp_le, Comparison::LE, false),
+            
+            OpCode::MoveReg => {
+                let value = read_register!(self, locals, data[0], REG_CONST_SRC1, data[2]);
+                locals.set_local(self.frame_offset(LocalIndex::from(data[1])), value);
+            },
+            OpCode::AddReg => eval_register_op!(self, current_offset, stack, locals, data, apply_add),
+            OpCode::SubReg => eval_register_op!(self, current_offset, stack, locals, data, apply_sub),
+            OpCode::MulReg => eval_register_op!(self, current_offset, stack, locals, data, apply_mul),
+            OpCode::DivReg => eval_register_op!(self, current_offset, stack, locals, data, apply_div),
+            OpCode::ModReg => eval_register_op!(self, current_offset, stack, locals, data, apply_mod),
             
-            OpCode::Inspect => println!("{}", stack.peek().display_echo()),
+            OpCode::Inspect => return Ok(Control::Inspect),
             OpCode::Assert => {
-                if !stack.peek().as_bool()? {
+                if !stack.peek()?.as_bool()? {
                     return Err(RuntimeError::assert_failed(None));
                 }
             }
@@ -1,16 +1,14 @@
 use core::fmt;
 use std::fs;
-use std::path::{PathBuf, Path};
+use std::path::PathBuf;
 use std::io;
-use crate::utils::{self, ReadChars};
+use crate::utils;
 
-use crate::lexer::LexerBuilder;
+use crate::lexer::{LexerBuilder, Lexer, LexerInput};
 use crate::parser::{Parser, ParserError};
 use crate::parser::stmt::StmtMeta;
 use crate::runtime::strings::StringInterner;
 
-type ReadFileChars = ReadChars<io::BufReader<fs::File>>;
-
 #[derive(Debug, Hash)]
 pub enum ModuleSource {
     String(String),
@@ -22,14 +20,16 @@ impl ModuleSource {
     pub fn read_text(&self) -> io::Result<SourceText> {
         match self {
             Self::String(string) => Ok(SourceText::String(string.clone())),
-            Self::File(ref path) => Ok(SourceText::File(Self::read_source_file(path)?)),
+            Self::File(ref path) => Ok(SourceText::File(fs::File::open(path)?)),
         }
     }
     
-    fn read_source_file(path: &Path) -> io::Result<ReadFileChars> {
-        let file = fs::File::open(path)?;
-        let reader = io::BufReader::new(file);
-        Ok(ReadChars::new(reader))
+    /// A hash of the source text that is stable across runs
+    pub fn source_hash(&self) -> io::Result<u64> {
+        match self {
+            Self::String(string) => Ok(utils::stable_hash(string.as_bytes())),
+            Self::File(ref path) => Ok(utils::stable_hash(&fs::read(path)?)),
+        }
     }
 }
 
@@ -43,10 +43,22 @@ impl fmt::Display for ModuleSource {
 }
 
 
-#[derive(Debug)]
 pub enum SourceText {
     String(String),
-    File(ReadFileChars),
+    File(fs::File),
+    
+    /// Text that is lexed as it is read, e.g. piped from stdin
+    Reader(Box<dyn io::Read>),
+}
+
+impl fmt::Debug for SourceText {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(string) => fmt.debug_tuple("String").field(string).finish(),
+            Self::File(file) => fmt.debug_tuple("File").field(file).finish(),
+            Self::Reader(..) => fmt.write_str("Reader(..)"),
+        }
+    }
 }
 
 impl<S> From<S> for SourceText where S: ToString {
@@ -84,21 +96,17 @@ impl<'f, 's> ParseContext<'f, 's> {
     }
 
     // Helper to deal with the separate branches for parsing SourceText
+    // files and readers are lexed as they are read, so they are never held in memory all at once
     fn collect_parser_output(&mut self, source: SourceText) -> Vec<Result<StmtMeta, ParserError>> {
         match source {
-            SourceText::String(text) => {
-                let mut chars = Vec::with_capacity(text.len());
-                chars.extend(text.chars().map(Ok));
-                
-                let lexer = self.lexer_factory.build(chars.into_iter());
-                let parser = Parser::new(self.interner, lexer);
-                parser.collect()
-            }
-            SourceText::File(text) => {
-                let lexer = self.lexer_factory.build(text);
-                let parser = Parser::new(self.interner, lexer);
-                parser.collect()
-            },
+            SourceText::String(text) => self.parse_input(self.lexer_factory.build(&text)),
+            SourceText::File(file) => self.parse_input(self.lexer_factory.build_reader(file)),
+            SourceText::Reader(reader) => self.parse_input(self.lexer_factory.build_reader(reader)),
         }
     }
+    
+    fn parse_input<S>(&mut self, lexer: Lexer<S>) -> Vec<Result<StmtMeta, ParserError>> where S: LexerInput {
+        let parser = Parser::new(self.interner, lexer);
+        parser.collect()
+    }
 }
\ No newline at end of file
@@ -40,7 +40,7 @@ impl Display for TitleCase<'_> {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         let mut prev = None;
         for next in self.target.chars() {
-            if next.is_alphabetic() && prev.map_or(true, char::is_whitespace) {
+            if next.is_alphabetic() && prev.is_none_or(char::is_whitespace) {
                 for c in next.to_uppercase() {
                     fmt.write_char(c)?;
                 }
@@ -54,6 +54,39 @@ impl Display for TitleCase<'_> {
     }
 }
 
+// escapes the contents of a string literal, so that reading it back produces the same string
+pub fn escape_str(target: &impl AsRef<str>, interpolated: bool) -> impl Display + '_ {
+    EscapeStr { target: target.as_ref(), interpolated }
+}
+
+struct EscapeStr<'s> {
+    target: &'s str,
+    interpolated: bool,
+}
+
+// braces are doubled inside of interpolated strings
+impl Display for EscapeStr<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        for ch in self.target.chars() {
+            match ch {
+                '\\' => fmt.write_str("\\\\")?,
+                '\"' => fmt.write_str("\\\"")?,
+                '\t' => fmt.write_str("\\t")?,
+                '\n' => fmt.write_str("\\n")?,
+                '\r' => fmt.write_str("\\r")?,
+                '\0' => fmt.write_str("\\0")?,
+                '{' | '}' if self.interpolated => {
+                    fmt.write_char(ch)?;
+                    fmt.write_char(ch)?;
+                },
+                ch if ch.is_control() => write!(fmt, "\\u{{{:x}}}", u32::from(ch))?,
+                ch => fmt.write_char(ch)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 pub fn fmt_join<'a>(sep: impl Display + 'a, items: &'a [impl Display]) -> impl Display + 'a {
     DisplayJoin { sep, items }
 }
@@ -159,4 +192,51 @@ pub fn format_error(fmt: &mut fmt::Formatter<'_>, title: &str, message: Option<&
         (Some(message), None) => write!(fmt, "{}: {}", title, message),
         (Some(message), Some(error)) => write!(fmt, "{}: {}: {}", title, message, error),
     }
-}
\ No newline at end of file
+}
+
+// A hash that is stable across program runs and platforms (FNV-1a), 
+// unlike the randomly seeded hashers used for hash tables
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    
+    bytes.iter().fold(FNV_OFFSET, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME))
+}
+
+// The number of single character insertions, deletions, substitutions or swaps of adjacent characters
+// needed to change one string into the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+    
+    // each row holds the distances from a prefix of a to every prefix of b
+    let mut prev_row = Vec::new();
+    let mut row = (0..=b.len()).collect::<Vec<usize>>();
+    for i in 1..=a.len() {
+        let mut next_row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (row[j - 1] + cost).min(row[j] + 1).min(next_row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(prev_row[j - 2] + 1);
+            }
+            next_row[j] = distance;
+        }
+        prev_row = core::mem::replace(&mut row, next_row);
+    }
+    row[b.len()]
+}
+
+// Finds the candidate that is most likely to be what was meant by a misspelled name, if any are close enough.
+// Ties are broken by picking the smallest candidate, so that the result doesn't depend on the order of the candidates.
+pub fn closest_match<S>(name: &str, candidates: impl IntoIterator<Item=S>) -> Option<S> where S: AsRef<str> {
+    // very short names are too close to everything to get a useful suggestion
+    let name_len = name.chars().count();
+    let max_distance = (name_len / 3).max(1).min(name_len.saturating_sub(1));
+    
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(name, candidate.as_ref()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by(|(a_dist, a), (b_dist, b)| a_dist.cmp(b_dist).then_with(|| a.as_ref().cmp(b.as_ref())))
+        .map(|(_, candidate)| candidate)
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use std::process::{Command, Output};
+
+
+fn run_sphinx(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sphinx"))
+        .args(args)
+        .output()
+        .expect("failed to run sphinx")
+}
+
+fn stdout_of(args: &[&str]) -> String {
+    let output = run_sphinx(args);
+    assert!(output.status.success(), "sphinx {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn cmd_passes_trailing_args() {
+    assert_eq!(stdout_of(&["-c", "print(os.args())", "a", "b"]), "(\"a\", \"b\")\n");
+    assert_eq!(stdout_of(&["-c", "print(os.args())", "a"]), "(\"a\",)\n");
+    assert_eq!(stdout_of(&["-c", "print(os.args())"]), "()\n");
+}
+
+#[test]
+fn file_passes_trailing_args() {
+    let path = std::env::temp_dir().join("sphinx_test_cli_args.sph");
+    fs::write(&path, "print(os.args())\n").unwrap();
+    
+    let output = stdout_of(&[path.to_str().unwrap(), "a", "b"]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output, "(\"a\", \"b\")\n");
+}
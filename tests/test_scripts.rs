@@ -1,15 +1,19 @@
 use std::path::Path;
 
-use sphinx;
 use sphinx::builtins;
+use sphinx::BuildErrors;
+use sphinx::debug::SourceError;
 use sphinx::source::ModuleSource;
-use sphinx::codegen::{Program, CompiledProgram};
+use sphinx::codegen::{Program, CompiledProgram, CompileOptions, Backend};
 use sphinx::runtime::{Module, VirtualMachine};
 use sphinx::runtime::errors::{ExecResult, ErrorKind};
 
 
-fn build_program(source: &ModuleSource) -> Option<CompiledProgram> {
-    match sphinx::build_module(source) {
+// scripts that are expected to run are also run using the experimental register backend
+const BACKENDS: [Backend; 2] = [ Backend::Stack, Backend::Register ];
+
+fn build_program(source: &ModuleSource, backend: Backend) -> Option<CompiledProgram> {
+    match sphinx::build_module_with_options(source, CompileOptions { backend }) {
         Err(errors) => {
             sphinx::print_build_errors(&errors, source);
             None
@@ -19,35 +23,64 @@ fn build_program(source: &ModuleSource) -> Option<CompiledProgram> {
     }
 }
 
-fn run_test_script(path: &Path) -> ExecResult<()> {
+fn run_test_script(path: &Path, backend: Backend) -> ExecResult<()> {
     let source = ModuleSource::File(path.into());
-    let build = build_program(&source).expect("build failed");
+    let build = build_program(&source, backend).expect("build failed");
     
-    let program = Program::load(build.program);
+    let program = Program::load(build.program).with_symbols(build.symbols);
     
     let main_env = builtins::create_prelude();
     let main_module = Module::with_env(Some(source), program.data, main_env);
     
-    let vm = VirtualMachine::new(main_module, &program.main);
+    let mut vm = VirtualMachine::new(main_module, &program.main);
     vm.run()?;
     
     Ok(())
 }
 
+fn compile_test_script(path: &Path) -> Result<CompiledProgram, BuildErrors> {
+    let source = ModuleSource::File(path.into());
+    sphinx::build_module(&source)
+}
+
 macro_rules! test_script {
     ( $name:tt, $path:expr ) => {
         #[test]
         fn $name() {
-            if let Err(error) = run_test_script(Path::new($path)) {
-                panic!("{}{}", error.traceback(), error);
+            for backend in BACKENDS {
+                if let Err(error) = run_test_script(Path::new($path), backend) {
+                    panic!("{:?} backend: {}{}", backend, error.traceback(), error);
+                }
             }
         }
     };
     ( $name:tt, $path:expr, error: $error:pat ) => {
         #[test]
         fn $name() {
-            let error = run_test_script(Path::new($path)).unwrap_err();
-            assert!(matches!(error.kind(), $error));
+            for backend in BACKENDS {
+                let error = run_test_script(Path::new($path), backend).unwrap_err();
+                assert!(matches!(error.kind(), $error), "{:?} backend", backend);
+            }
+        }
+    };
+    ( $name:tt, $path:expr, compile_error ) => {
+        #[test]
+        fn $name() {
+            match compile_test_script(Path::new($path)) {
+                Err(BuildErrors::Compile(errors)) => assert!(errors.iter().all(|error| error.debug_symbol().is_some())),
+                Err(..) => panic!("expected compile error"),
+                Ok(..) => panic!("compiled successfully"),
+            }
+        }
+    };
+    ( $name:tt, $path:expr, syntax_error ) => {
+        #[test]
+        fn $name() {
+            match compile_test_script(Path::new($path)) {
+                Err(BuildErrors::Syntax(errors)) => assert!(errors.iter().all(|error| error.debug_symbol().is_some())),
+                Err(..) => panic!("expected syntax error"),
+                Ok(..) => panic!("compiled successfully"),
+            }
         }
     };
 }
@@ -56,6 +89,14 @@ macro_rules! test_script {
 
 test_script!(empty_file, "tests/empty_file.sph");
 test_script!(precedence, "tests/precedence.sph");
+test_script!(shebang, "tests/shebang.sph");
+
+mod comment_tests {
+    use super::*;
+    
+    test_script!(block, "tests/comments/block.sph");
+    test_script!(unclosed, "tests/comments/unclosed.sph", syntax_error);
+}
 
 mod if_tests {
     use super::*;
@@ -64,6 +105,28 @@ mod if_tests {
     test_script!(else_, "tests/if/else.sph");
     test_script!(if_, "tests/if/if.sph");
     test_script!(truth, "tests/if/truth.sph");
+    test_script!(value, "tests/if/value.sph");
+}
+
+mod match_tests {
+    use super::*;
+    
+    test_script!(literal, "tests/match/literal.sph");
+    test_script!(tuple, "tests/match/tuple.sph");
+    test_script!(rest, "tests/match/rest.sph");
+    test_script!(guard, "tests/match/guard.sph");
+    test_script!(no_match, "tests/match/no_match.sph");
+    test_script!(control_flow, "tests/match/control_flow.sph");
+    test_script!(duplicate_binding, "tests/match/duplicate_binding.sph", syntax_error);
+    test_script!(missing_then, "tests/match/missing_then.sph", syntax_error);
+}
+
+mod block_tests {
+    use super::*;
+    
+    test_script!(value, "tests/block/value.sph");
+    test_script!(break_, "tests/block/break.sph");
+    test_script!(scope, "tests/block/scope.sph");
 }
 
 mod loop_tests {
@@ -78,6 +141,11 @@ mod tuple_tests {
     
     test_script!(assignment, "tests/tuple/assignment.sph");
     test_script!(comparison, "tests/tuple/comparison.sph");
+    test_script!(iterate, "tests/tuple/iterate.sph");
+    test_script!(large, "tests/tuple/large.sph");
+    test_script!(trailing_comma, "tests/tuple/trailing_comma.sph");
+    test_script!(unpack_too_many, "tests/tuple/unpack_too_many.sph", error: ErrorKind::UnpackError);
+    test_script!(unpack_too_few, "tests/tuple/unpack_too_few.sph", error: ErrorKind::UnpackError);
 }
 
 mod while_tests {
@@ -85,6 +153,7 @@ mod while_tests {
     
     test_script!(while_, "tests/while/while.sph");
     test_script!(continue_, "tests/while/continue.sph");
+    test_script!(compare, "tests/while/compare.sph");
 }
 
 mod for_tests {
@@ -95,12 +164,185 @@ mod for_tests {
 }
 
 
+mod builtin_tests {
+    use super::*;
+    
+    test_script!(len, "tests/builtins/len.sph");
+    test_script!(len_unsized, "tests/builtins/len_unsized.sph", error: ErrorKind::MethodNotSupported);
+    test_script!(conversions, "tests/builtins/conversions.sph");
+    test_script!(int_invalid, "tests/builtins/int_invalid.sph", error: ErrorKind::InvalidValue);
+    test_script!(repr, "tests/builtins/repr.sph");
+    test_script!(write_invalid_sep, "tests/builtins/write_invalid_sep.sph", error: ErrorKind::InvalidValue);
+}
+
+mod type_tests {
+    use super::*;
+    
+    test_script!(type_of, "tests/types/type_of.sph");
+    test_script!(isinstance, "tests/types/isinstance.sph");
+    test_script!(isinstance_not_a_type, "tests/types/isinstance_not_a_type.sph", error: ErrorKind::InvalidValue);
+}
+
+mod os_tests {
+    use super::*;
+    
+    test_script!(env, "tests/os/env.sph");
+    test_script!(env_invalid_name, "tests/os/env_invalid_name.sph", error: ErrorKind::InvalidValue);
+    test_script!(path, "tests/os/path.sph");
+    test_script!(exit, "tests/os/exit.sph");
+}
+
+mod random_tests {
+    use super::*;
+    
+    test_script!(seed, "tests/random/seed.sph");
+    test_script!(values, "tests/random/values.sph");
+    test_script!(empty_choice, "tests/random/empty_choice.sph", error: ErrorKind::InvalidValue);
+    test_script!(empty_range, "tests/random/empty_range.sph", error: ErrorKind::InvalidValue);
+}
+
 mod iterator_tests {
     use super::*;
     
     test_script!(zip_unzip, "tests/iterators/zip_unzip.sph");
 }
 
+mod arithmetic_tests {
+    use super::*;
+    
+    test_script!(modulo, "tests/arithmetic/modulo.sph");
+    test_script!(divide_by_zero, "tests/arithmetic/divide_by_zero.sph", error: ErrorKind::DivideByZero);
+    test_script!(modulo_by_zero, "tests/arithmetic/modulo_by_zero.sph", error: ErrorKind::DivideByZero);
+    test_script!(update_modulo_by_zero, "tests/arithmetic/update_modulo_by_zero.sph", error: ErrorKind::DivideByZero);
+    test_script!(floor_div, "tests/arithmetic/floor_div.sph");
+    test_script!(floor_div_by_zero, "tests/arithmetic/floor_div_by_zero.sph", error: ErrorKind::DivideByZero);
+    test_script!(exponent, "tests/arithmetic/exponent.sph");
+    #[cfg(not(feature = "bigint"))]
+    test_script!(exponent_overflow, "tests/arithmetic/exponent_overflow.sph", error: ErrorKind::OverflowError);
+    #[cfg(feature = "bigint")]
+    test_script!(bigint, "tests/arithmetic/bigint.sph");
+    #[cfg(not(feature = "bigint"))]
+    test_script!(literal_overflow, "tests/arithmetic/literal_overflow.sph", syntax_error);
+    #[cfg(feature = "bigint")]
+    test_script!(bigint_literals, "tests/arithmetic/bigint_literals.sph");
+    test_script!(float_literals, "tests/arithmetic/float_literals.sph");
+}
+
+mod try_tests {
+    use super::*;
+    
+    test_script!(catch_runtime_error, "tests/try/catch_runtime_error.sph");
+    test_script!(finally, "tests/try/finally.sph");
+    test_script!(finally_reraise, "tests/try/finally_reraise.sph");
+    test_script!(raise_uncaught, "tests/try/raise_uncaught.sph", error: ErrorKind::UserError);
+    test_script!(raise_in_function, "tests/try/raise_in_function.sph");
+    test_script!(control_flow, "tests/try/control_flow.sph");
+    test_script!(capture_in_try, "tests/try/capture_in_try.sph");
+    test_script!(traceback, "tests/try/traceback.sph");
+    test_script!(catch_native_error, "tests/try/catch_native_error.sph");
+}
+
+mod class_tests {
+    use super::*;
+    
+    test_script!(methods, "tests/class/methods.sph");
+    test_script!(constructor, "tests/class/constructor.sph");
+    test_script!(default_constructor, "tests/class/default_constructor.sph");
+    test_script!(bound_method, "tests/class/bound_method.sph");
+    test_script!(closure_self, "tests/class/closure_self.sph");
+    test_script!(missing_attribute, "tests/class/missing_attribute.sph", error: ErrorKind::AttributeNotFound);
+    test_script!(self_outside_method, "tests/class/self_outside_method.sph", compile_error);
+    test_script!(constructor_return_value, "tests/class/constructor_return_value.sph", compile_error);
+    test_script!(inheritance, "tests/class/inheritance.sph");
+    test_script!(super_, "tests/class/super.sph");
+    test_script!(super_without_parent, "tests/class/super_without_parent.sph", compile_error);
+    test_script!(invalid_parent, "tests/class/invalid_parent.sph", error: ErrorKind::InvalidValue);
+    test_script!(finalizer, "tests/class/finalizer.sph");
+    test_script!(cached_method, "tests/class/cached_method.sph");
+    test_script!(operators, "tests/class/operators.sph");
+    test_script!(operator_not_supported, "tests/class/operator_not_supported.sph", error: ErrorKind::InvalidBinaryOperand);
+    test_script!(comparison, "tests/class/comparison.sph");
+    test_script!(comparison_derived, "tests/class/comparison_derived.sph");
+    test_script!(format, "tests/class/format.sph");
+    test_script!(format_recursion, "tests/class/format_recursion.sph", error: ErrorKind::StackOverflow);
+}
+
+mod list_tests {
+    use super::*;
+    
+    test_script!(literal, "tests/list/literal.sph");
+    test_script!(index, "tests/list/index.sph");
+    test_script!(index_out_of_bounds, "tests/list/index_out_of_bounds.sph", error: ErrorKind::IndexOutOfBounds);
+    test_script!(methods, "tests/list/methods.sph");
+    test_script!(pop_empty, "tests/list/pop_empty.sph", error: ErrorKind::InvalidValue);
+    test_script!(iteration, "tests/list/iteration.sph");
+    test_script!(comparison, "tests/list/comparison.sph");
+}
+
+mod string_tests {
+    use super::*;
+    
+    test_script!(interpolation, "tests/string/interpolation.sph");
+    test_script!(interpolation_empty, "tests/string/interpolation_empty.sph", syntax_error);
+    test_script!(interpolation_unmatched, "tests/string/interpolation_unmatched.sph", syntax_error);
+    test_script!(escapes, "tests/string/escapes.sph");
+    test_script!(raw, "tests/string/raw.sph");
+    test_script!(multiline, "tests/string/multiline.sph");
+    test_script!(invalid_escape, "tests/string/invalid_escape.sph", syntax_error);
+    test_script!(invalid_codepoint, "tests/string/invalid_codepoint.sph", syntax_error);
+    test_script!(comparison, "tests/string/comparison.sph");
+    test_script!(compare_with_int, "tests/string/compare_with_int.sph", error: ErrorKind::InvalidBinaryOperand);
+}
+
+mod dict_tests {
+    use super::*;
+    
+    test_script!(literal, "tests/dict/literal.sph");
+    test_script!(index, "tests/dict/index.sph");
+    test_script!(missing_key, "tests/dict/missing_key.sph", error: ErrorKind::KeyNotFound);
+    test_script!(methods, "tests/dict/methods.sph");
+    test_script!(delete_missing, "tests/dict/delete_missing.sph", error: ErrorKind::KeyNotFound);
+    test_script!(iteration, "tests/dict/iteration.sph");
+    test_script!(unhashable_key, "tests/dict/unhashable_key.sph", error: ErrorKind::UnhashableValue);
+}
+
+mod set_tests {
+    use super::*;
+    
+    test_script!(constructor, "tests/set/constructor.sph");
+    test_script!(methods, "tests/set/methods.sph");
+    test_script!(remove_missing, "tests/set/remove_missing.sph", error: ErrorKind::KeyNotFound);
+    test_script!(unhashable, "tests/set/unhashable.sph", error: ErrorKind::UnhashableValue);
+    test_script!(operators, "tests/set/operators.sph");
+    test_script!(iteration, "tests/set/iteration.sph");
+}
+
+mod containment_tests {
+    use super::*;
+    
+    test_script!(builtin, "tests/containment/builtin.sph");
+    test_script!(iterator, "tests/containment/iterator.sph");
+    test_script!(precedence, "tests/containment/precedence.sph");
+    test_script!(unsupported, "tests/containment/unsupported.sph", error: ErrorKind::MethodNotSupported);
+    test_script!(string_item, "tests/containment/string_item.sph", error: ErrorKind::InvalidBinaryOperand);
+}
+
+mod identity_tests {
+    use super::*;
+    
+    test_script!(values, "tests/identity/values.sph");
+    test_script!(references, "tests/identity/references.sph");
+}
+
+mod optional_tests {
+    use super::*;
+    
+    test_script!(chain, "tests/optional/chain.sph");
+    test_script!(coalesce, "tests/optional/coalesce.sph");
+    test_script!(false_receiver, "tests/optional/false_receiver.sph", error: ErrorKind::MethodNotSupported);
+    test_script!(assign, "tests/optional/assign.sph", syntax_error);
+}
+
 mod variable_tests {
     use super::*;
     
@@ -109,6 +351,11 @@ mod variable_tests {
     test_script!(in_nested_block, "tests/variable/in_nested_block.sph");
     test_script!(redeclare_global, "tests/variable/redeclare_global.sph");
     test_script!(assign_to_outer_block, "tests/variable/assign_to_outer_block.sph");
+    test_script!(assign_immutable_local, "tests/variable/assign_immutable_local.sph", compile_error);
+    test_script!(assign_immutable_upvalue, "tests/variable/assign_immutable_upvalue.sph", compile_error);
+    test_script!(update_immutable_local, "tests/variable/update_immutable_local.sph", compile_error);
+    test_script!(unicode_names, "tests/variable/unicode_names.sph");
+    test_script!(cached_global, "tests/variable/cached_global.sph");
 }
 
 mod function_tests {
@@ -118,14 +365,70 @@ mod function_tests {
     test_script!(local_recursion, "tests/function/local_recursion.sph");
     test_script!(nested_call_with_arguments, "tests/function/nested_call_with_arguments.sph");
     test_script!(inner_block, "tests/function/inner_block.sph");
-    test_script!(missing_arguments, "tests/function/missing_arguments.sph", error: ErrorKind::MissingArguments {..});
+    test_script!(missing_arguments, "tests/function/missing_arguments.sph", error: ErrorKind::MissingArguments);
     test_script!(argument_unpack, "tests/function/argument_unpack.sph");
+    test_script!(call_syntax, "tests/function/call_syntax.sph");
+    test_script!(default_arguments, "tests/function/default_arguments.sph");
+    test_script!(lambda, "tests/function/lambda.sph");
+    test_script!(pipeline, "tests/function/pipeline.sph");
+    test_script!(return_, "tests/function/return.sph");
+    test_script!(unreachable, "tests/function/unreachable.sph");
+    test_script!(tail_call, "tests/function/tail_call.sph");
+    test_script!(stack_overflow, "tests/function/stack_overflow.sph", error: ErrorKind::StackOverflow);
+    test_script!(catch_stack_overflow, "tests/function/catch_stack_overflow.sph");
 }
 
 mod closure_tests {
     use super::*;
     
     test_script!(open_closure_in_function, "tests/closure/open_closure_in_function.sph");
+    test_script!(close_on_return, "tests/closure/close_on_return.sph");
     test_script!(assign_to_upvalue, "tests/closure/assign_to_upvalue.sph");
     test_script!(nested_closure, "tests/closure/nested_closure.sph");
-}
\ No newline at end of file
+}
+
+mod attribute_tests {
+    use super::*;
+    
+    test_script!(not_supported, "tests/attribute/not_supported.sph", error: ErrorKind::MethodNotSupported);
+    test_script!(assign_not_supported, "tests/attribute/assign_not_supported.sph", error: ErrorKind::MethodNotSupported);
+    test_script!(update_not_supported, "tests/attribute/update_not_supported.sph", error: ErrorKind::MethodNotSupported);
+    test_script!(exponent_like_names, "tests/attribute/exponent_like_names.sph");
+    test_script!(cached_field, "tests/attribute/cached_field.sph");
+}
+
+mod index_tests {
+    use super::*;
+    
+    test_script!(tuple, "tests/index/tuple.sph");
+    test_script!(string, "tests/index/string.sph");
+    test_script!(out_of_bounds, "tests/index/out_of_bounds.sph", error: ErrorKind::IndexOutOfBounds);
+    test_script!(not_supported, "tests/index/not_supported.sph", error: ErrorKind::MethodNotSupported);
+    test_script!(assign_not_supported, "tests/index/assign_not_supported.sph", error: ErrorKind::MethodNotSupported);
+    test_script!(update_not_supported, "tests/index/update_not_supported.sph", error: ErrorKind::MethodNotSupported);
+}
+
+mod gc_tests {
+    use super::*;
+    
+    test_script!(stats, "tests/gc/stats.sph");
+    test_script!(collect, "tests/gc/collect.sph");
+    test_script!(pause_factor, "tests/gc/pause_factor.sph");
+    test_script!(invalid_pause_factor, "tests/gc/invalid_pause_factor.sph", error: ErrorKind::InvalidValue);
+}
+
+mod import_tests {
+    use super::*;
+    
+    test_script!(basic, "tests/import/basic.sph");
+    test_script!(nested, "tests/import/nested.sph");
+    test_script!(cached, "tests/import/cached.sph");
+    test_script!(catch_error, "tests/import/catch_error.sph");
+    test_script!(visibility, "tests/import/visibility.sph");
+    test_script!(after_import, "tests/import/after_import.sph", error: ErrorKind::AssertFailed);
+    test_script!(export_not_toplevel, "tests/import/export_not_toplevel.sph", compile_error);
+    test_script!(export_not_declaration, "tests/import/export_not_declaration.sph", compile_error);
+    test_script!(not_found, "tests/import/not_found.sph", error: ErrorKind::ImportError);
+    test_script!(circular, "tests/import/circular.sph", error: ErrorKind::ImportError);
+    test_script!(build_error, "tests/import/build_error.sph", error: ErrorKind::ImportError);
+}